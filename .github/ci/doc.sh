@@ -44,6 +44,9 @@ docserver-builder -i ./embassy-net-driver -o webroot/crates/embassy-net-driver/g
 docserver-builder -i ./embassy-net-driver-channel -o webroot/crates/embassy-net-driver-channel/git.zup
 docserver-builder -i ./embassy-net-wiznet -o webroot/crates/embassy-net-wiznet/git.zup
 docserver-builder -i ./embassy-net-ppp -o webroot/crates/embassy-net-ppp/git.zup
+docserver-builder -i ./embassy-at -o webroot/crates/embassy-at/git.zup
+docserver-builder -i ./embassy-modbus -o webroot/crates/embassy-modbus/git.zup
+docserver-builder -i ./embassy-gnss -o webroot/crates/embassy-gnss/git.zup
 docserver-builder -i ./embassy-net-tuntap -o webroot/crates/embassy-net-tuntap/git.zup
 docserver-builder -i ./embassy-net-enc28j60 -o webroot/crates/embassy-net-enc28j60/git.zup
 docserver-builder -i ./embassy-net-esp-hosted -o webroot/crates/embassy-net-esp-hosted/git.zup
@@ -8,8 +8,14 @@ export RUSTUP_HOME=/ci/cache/rustup
 export CARGO_HOME=/ci/cache/cargo
 export CARGO_TARGET_DIR=/ci/cache/target
 
+cargo test --manifest-path ./embassy-futures/Cargo.toml --lib
 cargo test --manifest-path ./embassy-sync/Cargo.toml 
 cargo test --manifest-path ./embassy-embedded-hal/Cargo.toml 
+cargo test --manifest-path ./embassy-embedded-hal/Cargo.toml --features std,littlefs2
+cargo test --manifest-path ./embassy-usb/Cargo.toml
+cargo test --manifest-path ./embassy-at/Cargo.toml
+cargo test --manifest-path ./embassy-modbus/Cargo.toml
+cargo test --manifest-path ./embassy-gnss/Cargo.toml
 cargo test --manifest-path ./embassy-hal-internal/Cargo.toml 
 cargo test --manifest-path ./embassy-time/Cargo.toml --features generic-queue,mock-driver
 cargo test --manifest-path ./embassy-time-driver/Cargo.toml
@@ -0,0 +1,654 @@
+//! CMUX multiplexer, basic option of 3GPP TS 27.010.
+//!
+//! The multiplexer runs several virtual channels over one serial port. Each [`Channel`]
+//! implements the `embedded-io-async` traits, so it can be used by the [AT client](crate::new)
+//! or by `embassy-net-ppp`. The modem must be switched to CMUX mode first, usually with
+//! `AT+CMUX=0`, and the frame size must match the `N1` parameter of this command.
+//!
+//! Only UIH frames are used for the data. Commands of the modem on the control channel, such as
+//! modem status or flow control, are acknowledged but not acted upon.
+
+use core::cell::Cell;
+use core::convert::Infallible;
+
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_sync::channel;
+use embassy_sync::pipe::Pipe;
+use embassy_sync::signal::Signal;
+use embedded_io_async::{BufRead, ErrorType, Read, Write};
+use heapless::Vec;
+
+/// Maximum length of the information field of a frame.
+pub const MAX_FRAME_SIZE: usize = 127;
+
+const TX_QUEUE_LEN: usize = 4;
+const READ_BUF_LEN: usize = 64;
+
+const FLAG: u8 = 0xF9;
+const EA: u8 = 0x01;
+const CR: u8 = 0x02;
+const PF: u8 = 0x10;
+
+const SABM: u8 = 0x2F;
+const UA: u8 = 0x63;
+const DM: u8 = 0x0F;
+const DISC: u8 = 0x43;
+const UIH: u8 = 0xEF;
+
+/// Multiplexer close down message type, on the control channel.
+const CLD: u8 = 0xC0;
+
+/// CMUX configuration.
+#[non_exhaustive]
+#[derive(Clone, Copy)]
+pub struct Config {
+    /// Maximum length of the information field of the transmitted frames.
+    ///
+    /// Defaults to 31, the default of `AT+CMUX`. At most [`MAX_FRAME_SIZE`].
+    pub frame_size: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self { frame_size: 31 }
+    }
+}
+
+/// Error returned by [`Runner::run`].
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RunError<E> {
+    /// Reading from the serial port failed.
+    Read(E),
+    /// Writing to the serial port failed.
+    Write(E),
+    /// Reading from the serial port got EOF.
+    Eof,
+    /// The modem refused to open the channel with the given DLCI.
+    Refused(u8),
+    /// The modem closed the multiplexer.
+    Closed,
+}
+
+/// Whether a frame is a command or a response, which sets its C/R bit.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum FrameKind {
+    Command,
+    Response,
+}
+
+/// Frame sent by the runner in reply to the modem.
+struct Reply {
+    dlci: u8,
+    control: u8,
+    kind: FrameKind,
+    data: Vec<u8, MAX_FRAME_SIZE>,
+    /// The multiplexer is closed once the frame is sent.
+    close: bool,
+}
+
+struct TxFrame {
+    dlci: u8,
+    data: Vec<u8, MAX_FRAME_SIZE>,
+}
+
+/// Internal state for the multiplexer, with `N` channels receiving into buffers of `BUF` bytes.
+pub struct State<const N: usize, const BUF: usize> {
+    rx: [Pipe<NoopRawMutex, BUF>; N],
+    tx: channel::Channel<NoopRawMutex, TxFrame, TX_QUEUE_LEN>,
+}
+
+impl<const N: usize, const BUF: usize> State<N, BUF> {
+    /// Create a new `State`.
+    pub fn new() -> Self {
+        Self {
+            rx: core::array::from_fn(|_| Pipe::new()),
+            tx: channel::Channel::new(),
+        }
+    }
+}
+
+impl<const N: usize, const BUF: usize> Default for State<N, BUF> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Background runner for the multiplexer.
+///
+/// You must call `.run()` in a background task for the channels to operate.
+pub struct Runner<'d, const N: usize, const BUF: usize> {
+    state: &'d State<N, BUF>,
+}
+
+impl<'d, const N: usize, const BUF: usize> Runner<'d, N, BUF> {
+    /// You must call this in a background task for the channels to operate.
+    ///
+    /// It opens the control channel and the `N` channels, then forwards the data between the
+    /// channels and the serial port. The data written to the channels is held back until the
+    /// modem acknowledged all of them. Reception is flow-controlled: a channel which is not read
+    /// stalls the others once its buffer is full.
+    ///
+    /// The serial port is read and written concurrently, so `reader` doesn't need to be
+    /// cancel-safe: a read is never dropped before it completes, until this function returns.
+    pub async fn run<R: Read, W: Write<Error = R::Error>>(
+        &mut self,
+        mut reader: R,
+        mut writer: W,
+    ) -> Result<Infallible, RunError<R::Error>> {
+        let state = self.state;
+        let mut tx_buf = [0; MAX_FRAME_SIZE + 6];
+
+        let all_open = (1u64 << (N + 1)) - 1;
+        let open = Cell::new(0u64);
+        // Signaled when a channel is opened or closed.
+        let open_changed = Signal::<NoopRawMutex, ()>::new();
+        // Frames sent by the runner itself, in reply to the modem.
+        let replies = channel::Channel::<NoopRawMutex, Reply, TX_QUEUE_LEN>::new();
+
+        for dlci in 0..=N as u8 {
+            let n = encode_frame(dlci, SABM | PF, FrameKind::Command, &[], &mut tx_buf);
+            writer.write_all(&tx_buf[..n]).await.map_err(RunError::Write)?;
+        }
+
+        let rx_fut = async {
+            let mut rx_buf = [0; READ_BUF_LEN];
+            let mut decoder = Decoder::new();
+
+            loop {
+                let n = reader.read(&mut rx_buf).await.map_err(RunError::Read)?;
+                if n == 0 {
+                    return Err(RunError::Eof);
+                }
+
+                for &byte in &rx_buf[..n] {
+                    if !decoder.feed(byte) {
+                        continue;
+                    }
+
+                    let frame = decoder.frame();
+                    let dlci = frame.dlci as usize;
+                    match frame.control & !PF {
+                        UA if dlci <= N => {
+                            debug!("cmux: DLCI {} open", dlci);
+                            open.set(open.get() | 1 << dlci);
+                            open_changed.signal(());
+                        }
+                        DM => return Err(RunError::Refused(frame.dlci)),
+                        DISC => {
+                            open.set(open.get() & !(1 << dlci));
+                            open_changed.signal(());
+                            replies
+                                .send(Reply {
+                                    dlci: frame.dlci,
+                                    control: UA | PF,
+                                    kind: FrameKind::Response,
+                                    data: Vec::new(),
+                                    close: dlci == 0,
+                                })
+                                .await;
+                        }
+                        UIH if dlci == 0 => {
+                            let Some(&kind) = frame.data.first() else {
+                                continue;
+                            };
+                            if kind & CR == 0 {
+                                // Response to one of our commands.
+                                continue;
+                            }
+
+                            // Acknowledge the command by sending it back as a response.
+                            let mut data: Vec<u8, MAX_FRAME_SIZE> = unwrap!(Vec::from_slice(frame.data));
+                            data[0] &= !CR;
+                            replies
+                                .send(Reply {
+                                    dlci: 0,
+                                    control: UIH,
+                                    kind: FrameKind::Command,
+                                    data,
+                                    close: kind & !(CR | EA) == CLD,
+                                })
+                                .await;
+                        }
+                        UIH if dlci <= N => state.rx[dlci - 1].write_all(frame.data).await,
+                        _ => trace!("cmux: ignoring frame {:02x} on DLCI {}", frame.control, dlci),
+                    }
+                }
+            }
+        };
+
+        let tx_fut = async {
+            loop {
+                let data_fut = async {
+                    while open.get() != all_open {
+                        open_changed.wait().await;
+                    }
+                    state.tx.receive().await
+                };
+
+                // Both futures are cancel-safe.
+                match select(replies.receive(), data_fut).await {
+                    Either::First(reply) => {
+                        let n = encode_frame(reply.dlci, reply.control, reply.kind, &reply.data, &mut tx_buf);
+                        writer.write_all(&tx_buf[..n]).await.map_err(RunError::Write)?;
+                        if reply.close {
+                            writer.flush().await.map_err(RunError::Write)?;
+                            return Err(RunError::Closed);
+                        }
+                    }
+                    Either::Second(frame) => {
+                        let n = encode_frame(frame.dlci, UIH, FrameKind::Command, &frame.data, &mut tx_buf);
+                        writer.write_all(&tx_buf[..n]).await.map_err(RunError::Write)?;
+                    }
+                }
+            }
+        };
+
+        // Both only return on error, and the multiplexer is then stopped.
+        match select(rx_fut, tx_fut).await {
+            Either::First(r) | Either::Second(r) => r,
+        }
+    }
+
+    /// Close the multiplexer, returning the modem to AT command mode.
+    ///
+    /// Call this after [`run`](Self::run) is canceled. Data still queued in the channels is
+    /// dropped.
+    pub async fn close<W: Write>(&mut self, mut writer: W) -> Result<(), W::Error> {
+        while self.state.tx.try_receive().is_ok() {}
+
+        let mut tx_buf = [0; 8];
+        let n = encode_frame(0, UIH, FrameKind::Command, &[CLD | CR | EA, EA], &mut tx_buf);
+        writer.write_all(&tx_buf[..n]).await?;
+        writer.flush().await
+    }
+}
+
+/// Virtual channel of the multiplexer.
+pub struct Channel<'d, const BUF: usize> {
+    reader: ChannelReader<'d, BUF>,
+    writer: ChannelWriter<'d>,
+}
+
+impl<'d, const BUF: usize> Channel<'d, BUF> {
+    /// DLCI of the channel, starting at 1.
+    pub fn dlci(&self) -> u8 {
+        self.writer.dlci
+    }
+
+    /// Split the channel into a reader and a writer, for example for the [AT client](crate::new).
+    pub fn split(self) -> (ChannelReader<'d, BUF>, ChannelWriter<'d>) {
+        (self.reader, self.writer)
+    }
+}
+
+impl<'d, const BUF: usize> ErrorType for Channel<'d, BUF> {
+    type Error = Infallible;
+}
+
+impl<'d, const BUF: usize> Read for Channel<'d, BUF> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.reader.read(buf).await
+    }
+}
+
+impl<'d, const BUF: usize> BufRead for Channel<'d, BUF> {
+    async fn fill_buf(&mut self) -> Result<&[u8], Self::Error> {
+        self.reader.fill_buf().await
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.reader.consume(amt)
+    }
+}
+
+impl<'d, const BUF: usize> Write for Channel<'d, BUF> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.writer.write(buf).await
+    }
+}
+
+/// Receiving half of a [`Channel`].
+pub struct ChannelReader<'d, const BUF: usize> {
+    rx: &'d Pipe<NoopRawMutex, BUF>,
+    buf: [u8; READ_BUF_LEN],
+    start: usize,
+    end: usize,
+}
+
+impl<'d, const BUF: usize> ErrorType for ChannelReader<'d, BUF> {
+    type Error = Infallible;
+}
+
+impl<'d, const BUF: usize> Read for ChannelReader<'d, BUF> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if self.start == self.end {
+            return Ok(self.rx.read(buf).await);
+        }
+
+        let n = buf.len().min(self.end - self.start);
+        buf[..n].copy_from_slice(&self.buf[self.start..self.start + n]);
+        self.start += n;
+        Ok(n)
+    }
+}
+
+impl<'d, const BUF: usize> BufRead for ChannelReader<'d, BUF> {
+    async fn fill_buf(&mut self) -> Result<&[u8], Self::Error> {
+        if self.start == self.end {
+            self.end = self.rx.read(&mut self.buf).await;
+            self.start = 0;
+        }
+        Ok(&self.buf[self.start..self.end])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.start = (self.start + amt).min(self.end);
+    }
+}
+
+/// Transmitting half of a [`Channel`].
+pub struct ChannelWriter<'d> {
+    dlci: u8,
+    tx: &'d channel::Channel<NoopRawMutex, TxFrame, TX_QUEUE_LEN>,
+    frame_size: usize,
+}
+
+impl<'d> ErrorType for ChannelWriter<'d> {
+    type Error = Infallible;
+}
+
+impl<'d> Write for ChannelWriter<'d> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let n = buf.len().min(self.frame_size);
+        let data = unwrap!(Vec::from_slice(&buf[..n]));
+        self.tx.send(TxFrame { dlci: self.dlci, data }).await;
+        Ok(n)
+    }
+}
+
+/// Create a CMUX multiplexer.
+///
+/// This returns the `N` channels, with DLCIs 1 to `N`, and a `Runner`. You must call `.run()`
+/// on it in a background task with the reader and the writer of the serial port.
+pub fn new<'d, const N: usize, const BUF: usize>(
+    state: &'d mut State<N, BUF>,
+    config: Config,
+) -> (Runner<'d, N, BUF>, [Channel<'d, BUF>; N]) {
+    assert!(N > 0 && N < 63);
+    assert!(config.frame_size > 0 && config.frame_size <= MAX_FRAME_SIZE);
+
+    let state = &*state;
+    let channels = core::array::from_fn(|i| Channel {
+        reader: ChannelReader {
+            rx: &state.rx[i],
+            buf: [0; READ_BUF_LEN],
+            start: 0,
+            end: 0,
+        },
+        writer: ChannelWriter {
+            dlci: i as u8 + 1,
+            tx: &state.tx,
+            frame_size: config.frame_size,
+        },
+    });
+
+    (Runner { state }, channels)
+}
+
+const fn crc_table() -> [u8; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xE0 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// CRC-8 of the frame check sequence, reflected polynomial `x^8 + x^2 + x + 1`.
+static CRC_TABLE: [u8; 256] = crc_table();
+
+fn crc(crc: u8, byte: u8) -> u8 {
+    CRC_TABLE[(crc ^ byte) as usize]
+}
+
+/// C/R bit of the address field of a frame, per table 1 of TS 27.010.
+///
+/// The commands of the initiator and the responses of the responder have it set.
+fn address_cr(kind: FrameKind, initiator: bool) -> u8 {
+    if (kind == FrameKind::Command) == initiator {
+        CR
+    } else {
+        0
+    }
+}
+
+/// Encode a frame sent by the initiator, returning its length in `out`.
+///
+/// The UIH frames, including the ones carrying responses of the control channel in their
+/// information field, are commands. `data` must be at most [`MAX_FRAME_SIZE`] long.
+fn encode_frame(dlci: u8, control: u8, kind: FrameKind, data: &[u8], out: &mut [u8]) -> usize {
+    let header = [
+        (dlci << 2) | address_cr(kind, true) | EA,
+        control,
+        ((data.len() as u8) << 1) | EA,
+    ];
+    let fcs = 0xFF - header.iter().fold(0xFF, |acc, b| crc(acc, *b));
+
+    let n = data.len();
+    out[0] = FLAG;
+    out[1..4].copy_from_slice(&header);
+    out[4..4 + n].copy_from_slice(data);
+    out[4 + n] = fcs;
+    out[5 + n] = FLAG;
+    n + 6
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DecodeState {
+    Flag,
+    Address,
+    Control,
+    Length,
+    Length2,
+    Data,
+    Fcs,
+    End,
+}
+
+struct Frame<'a> {
+    dlci: u8,
+    control: u8,
+    data: &'a [u8],
+}
+
+/// Frame decoder, checking the frame check sequence over the header as done for UIH frames.
+struct Decoder {
+    state: DecodeState,
+    address: u8,
+    control: u8,
+    len: usize,
+    crc: u8,
+    data: Vec<u8, MAX_FRAME_SIZE>,
+    valid: bool,
+}
+
+impl Decoder {
+    const fn new() -> Self {
+        Self {
+            state: DecodeState::Flag,
+            address: 0,
+            control: 0,
+            len: 0,
+            crc: 0xFF,
+            data: Vec::new(),
+            valid: false,
+        }
+    }
+
+    /// Feed a received byte, returning whether a valid frame is complete.
+    fn feed(&mut self, byte: u8) -> bool {
+        match self.state {
+            DecodeState::Flag => {
+                if byte == FLAG {
+                    self.state = DecodeState::Address;
+                }
+            }
+            DecodeState::Address => {
+                // Consecutive flags are allowed between frames.
+                if byte != FLAG {
+                    self.address = byte;
+                    self.crc = crc(0xFF, byte);
+                    self.state = DecodeState::Control;
+                }
+            }
+            DecodeState::Control => {
+                self.control = byte;
+                self.crc = crc(self.crc, byte);
+                self.state = DecodeState::Length;
+            }
+            DecodeState::Length => {
+                self.crc = crc(self.crc, byte);
+                self.len = (byte >> 1) as usize;
+                if byte & EA != 0 {
+                    self.start_data();
+                } else {
+                    self.state = DecodeState::Length2;
+                }
+            }
+            DecodeState::Length2 => {
+                self.crc = crc(self.crc, byte);
+                self.len |= (byte as usize) << 7;
+                self.start_data();
+            }
+            DecodeState::Data => {
+                if self.data.push(byte).is_err() {
+                    self.valid = false;
+                }
+                self.len -= 1;
+                if self.len == 0 {
+                    self.state = DecodeState::Fcs;
+                }
+            }
+            DecodeState::Fcs => {
+                if crc(self.crc, byte) != 0xCF {
+                    self.valid = false;
+                }
+                self.state = DecodeState::End;
+            }
+            DecodeState::End => {
+                if byte != FLAG {
+                    self.state = DecodeState::Flag;
+                    return false;
+                }
+                // The closing flag can also open the next frame.
+                self.state = DecodeState::Address;
+                if !self.valid {
+                    warn!("cmux: dropping invalid frame");
+                }
+                return self.valid;
+            }
+        }
+        false
+    }
+
+    fn start_data(&mut self) {
+        self.data.clear();
+        self.valid = true;
+        self.state = if self.len == 0 {
+            DecodeState::Fcs
+        } else {
+            DecodeState::Data
+        };
+    }
+
+    fn frame(&self) -> Frame<'_> {
+        Frame {
+            dlci: self.address >> 2,
+            control: self.control,
+            data: &self.data,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_sabm() {
+        let mut buf = [0; 8];
+        let n = encode_frame(0, SABM | PF, FrameKind::Command, &[], &mut buf);
+        assert_eq!(&buf[..n], &[0xF9, 0x03, 0x3F, 0x01, 0x1C, 0xF9]);
+    }
+
+    #[test]
+    fn encodes_ua_response() {
+        let mut buf = [0; 8];
+        let n = encode_frame(1, UA | PF, FrameKind::Response, &[], &mut buf);
+        // C/R cleared in a response of the initiator.
+        assert_eq!(&buf[1..3], &[0x05, 0x73]);
+
+        let mut decoder = Decoder::new();
+        assert_eq!(buf[..n].iter().filter(|b| decoder.feed(**b)).count(), 1);
+        assert_eq!(decoder.frame().control, UA | PF);
+    }
+
+    #[test]
+    fn address_cr_follows_role() {
+        assert_eq!(address_cr(FrameKind::Command, true), CR);
+        assert_eq!(address_cr(FrameKind::Response, true), 0);
+        assert_eq!(address_cr(FrameKind::Command, false), 0);
+        assert_eq!(address_cr(FrameKind::Response, false), CR);
+    }
+
+    #[test]
+    fn decodes_ua() {
+        let mut decoder = Decoder::new();
+        let complete: usize = [0xF9, 0x03, 0x73, 0x01, 0xD7, 0xF9]
+            .iter()
+            .filter(|b| decoder.feed(**b))
+            .count();
+        assert_eq!(complete, 1);
+
+        let frame = decoder.frame();
+        assert_eq!(frame.dlci, 0);
+        assert_eq!(frame.control, UA | PF);
+        assert!(frame.data.is_empty());
+    }
+
+    #[test]
+    fn round_trips_uih() {
+        let mut buf = [0; MAX_FRAME_SIZE + 6];
+        let n = encode_frame(2, UIH, FrameKind::Command, b"AT\r", &mut buf);
+
+        let mut decoder = Decoder::new();
+        assert_eq!(buf[..n].iter().filter(|b| decoder.feed(**b)).count(), 1);
+        let frame = decoder.frame();
+        assert_eq!(frame.dlci, 2);
+        assert_eq!(frame.control, UIH);
+        assert_eq!(frame.data, b"AT\r");
+    }
+
+    #[test]
+    fn drops_corrupted_frame() {
+        let mut buf = [0; MAX_FRAME_SIZE + 6];
+        let n = encode_frame(1, UIH, FrameKind::Command, b"OK", &mut buf);
+        buf[n - 2] ^= 0x55;
+
+        let mut decoder = Decoder::new();
+        assert_eq!(buf[..n].iter().filter(|b| decoder.feed(**b)).count(), 0);
+    }
+}
@@ -0,0 +1,485 @@
+#![no_std]
+#![allow(async_fn_in_trait)]
+#![warn(missing_docs)]
+#![doc = include_str!("../README.md")]
+
+// must be first
+mod fmt;
+
+pub mod cmux;
+pub mod modem;
+
+use core::cell::RefCell;
+use core::convert::Infallible;
+
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::pubsub::{self, ImmediatePublisher, PubSubChannel};
+use embassy_sync::signal::Signal;
+use embassy_time::{with_timeout, Duration};
+use embedded_io_async::{BufRead, Write};
+use heapless::Vec;
+
+/// Maximum length of a command or of a received line, without the terminator.
+pub const MAX_LINE_LEN: usize = 128;
+/// Maximum length of the information text of a response.
+pub const MAX_RESPONSE_LEN: usize = 256;
+/// Number of URCs queued for each subscriber.
+pub const URC_QUEUE_LEN: usize = 8;
+/// Maximum number of URC subscribers.
+pub const MAX_URC_SUBSCRIBERS: usize = 4;
+
+/// Unsolicited result code, without the line terminator.
+pub type Urc = Vec<u8, MAX_LINE_LEN>;
+
+/// Subscriber to the URCs, see [`Client::subscribe`].
+pub type UrcSubscriber<'d> = pubsub::Subscriber<'d, NoopRawMutex, Urc, URC_QUEUE_LEN, MAX_URC_SUBSCRIBERS, 1>;
+
+type UrcChannel = PubSubChannel<NoopRawMutex, Urc, URC_QUEUE_LEN, MAX_URC_SUBSCRIBERS, 1>;
+type UrcPublisher<'d> = ImmediatePublisher<'d, NoopRawMutex, Urc, URC_QUEUE_LEN, MAX_URC_SUBSCRIBERS, 1>;
+
+/// AT client configuration.
+#[non_exhaustive]
+#[derive(Clone, Copy)]
+pub struct Config<'a> {
+    /// Timeout of [`Client::send`].
+    pub timeout: Duration,
+    /// Prefixes of the URCs the modem can send while a command is running, e.g. `"+CREG:"`.
+    ///
+    /// Lines received while no command is running are always URCs. While a command is running,
+    /// lines starting with the name of the command (`+CREG:` for `AT+CREG?`) belong to its
+    /// response, and the other lines belong to it as well unless they start with one of these
+    /// prefixes.
+    pub urc_prefixes: &'a [&'a str],
+}
+
+impl<'a> Default for Config<'a> {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(1),
+            urc_prefixes: &[],
+        }
+    }
+}
+
+/// Error returned by [`Client::send`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// Writing to the serial port failed.
+    Write(E),
+    /// No final result code was received before the timeout.
+    Timeout,
+    /// The command is longer than [`MAX_LINE_LEN`].
+    CommandTooLong,
+    /// The response is longer than [`MAX_RESPONSE_LEN`].
+    ResponseTooLong,
+    /// The modem answered `ERROR`, or a `+CME ERROR` / `+CMS ERROR` with a verbose message.
+    Error,
+    /// The modem answered `+CME ERROR: <code>`.
+    CmeError(u16),
+    /// The modem answered `+CMS ERROR: <code>`.
+    CmsError(u16),
+    /// The modem answered `NO CARRIER`.
+    NoCarrier,
+    /// The modem answered `BUSY`.
+    Busy,
+    /// The modem answered `NO ANSWER`.
+    NoAnswer,
+    /// The modem answered `NO DIALTONE`.
+    NoDialtone,
+}
+
+/// Error returned by [`Runner::run`].
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RunError<E> {
+    /// Reading from the serial port failed.
+    Read(E),
+    /// Reading from the serial port got EOF.
+    Eof,
+}
+
+/// Final result code ending a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+enum Final {
+    Ok,
+    Error,
+    CmeError(u16),
+    CmsError(u16),
+    NoCarrier,
+    Busy,
+    NoAnswer,
+    NoDialtone,
+}
+
+/// Information text of a successful command.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Response {
+    data: Vec<u8, MAX_RESPONSE_LEN>,
+}
+
+impl Response {
+    /// Lines of the information text, without the terminators.
+    pub fn lines(&self) -> impl Iterator<Item = &[u8]> {
+        self.data.split(|b| *b == b'\n').filter(|line| !line.is_empty())
+    }
+
+    /// Parameters of the first line starting with `name` followed by `:`.
+    ///
+    /// For example `info("+CSQ")` returns `20,99` for the line `+CSQ: 20,99`.
+    pub fn info(&self, name: &str) -> Option<&[u8]> {
+        self.lines().find_map(|line| {
+            let params = line.strip_prefix(name.as_bytes())?.strip_prefix(b":")?;
+            Some(params.strip_prefix(b" ").unwrap_or(params))
+        })
+    }
+
+    /// Information text, with the lines separated by `\n`.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+struct Pending {
+    active: bool,
+    command: Vec<u8, MAX_LINE_LEN>,
+    response: Vec<u8, MAX_RESPONSE_LEN>,
+    overflow: bool,
+}
+
+struct Shared {
+    pending: Mutex<NoopRawMutex, RefCell<Pending>>,
+    done: Signal<NoopRawMutex, Final>,
+    urc: UrcChannel,
+}
+
+/// Internal state for the AT client.
+pub struct State {
+    shared: Shared,
+}
+
+impl State {
+    /// Create a new `State`.
+    pub const fn new() -> Self {
+        Self {
+            shared: Shared {
+                pending: Mutex::new(RefCell::new(Pending {
+                    active: false,
+                    command: Vec::new(),
+                    response: Vec::new(),
+                    overflow: false,
+                })),
+                done: Signal::new(),
+                urc: PubSubChannel::new(),
+            },
+        }
+    }
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Background runner for the AT client.
+///
+/// You must call `.run()` in a background task for the client to operate.
+pub struct Runner<'d> {
+    shared: &'d Shared,
+    urc_prefixes: &'d [&'d str],
+}
+
+impl<'d> Runner<'d> {
+    /// You must call this in a background task for the client to operate.
+    ///
+    /// It reads the lines received from the modem, completes the commands sent by the
+    /// [`Client`] and publishes the URCs to the subscribers. URCs are dropped for subscribers
+    /// whose queue is full.
+    ///
+    /// It is allowed to cancel this function's future, for example to hand the serial port
+    /// over to the [CMUX multiplexer](crate::cmux) after `AT+CMUX`. A partially received line
+    /// is lost.
+    pub async fn run<R: BufRead>(&mut self, mut reader: R) -> Result<Infallible, RunError<R::Error>> {
+        let publisher = self.shared.urc.immediate_publisher();
+        let mut line: Vec<u8, MAX_LINE_LEN> = Vec::new();
+        let mut discard = false;
+
+        loop {
+            let buf = reader.fill_buf().await.map_err(RunError::Read)?;
+            if buf.is_empty() {
+                return Err(RunError::Eof);
+            }
+
+            for &byte in buf {
+                match byte {
+                    b'\r' | b'\n' => {
+                        if !discard && !line.is_empty() {
+                            self.handle_line(&publisher, &line);
+                        }
+                        discard = false;
+                        line.clear();
+                    }
+                    _ if discard => {}
+                    _ => {
+                        if line.push(byte).is_err() {
+                            warn!("AT line too long, discarding");
+                            discard = true;
+                        }
+                    }
+                }
+            }
+
+            let n = buf.len();
+            reader.consume(n);
+        }
+    }
+
+    fn handle_line(&self, publisher: &UrcPublisher<'_>, line: &[u8]) {
+        let is_urc = self.shared.pending.lock(|pending| {
+            let mut pending = pending.borrow_mut();
+
+            if !pending.active {
+                // Final result codes of a command which timed out are dropped.
+                return parse_final(line).is_none();
+            }
+
+            if line == pending.command.as_slice() {
+                // Echo of the command.
+                return false;
+            }
+
+            if let Some(result) = parse_final(line) {
+                pending.active = false;
+                self.shared.done.signal(result);
+                return false;
+            }
+
+            if !is_response_of(&pending.command, line)
+                && self
+                    .urc_prefixes
+                    .iter()
+                    .any(|prefix| line.starts_with(prefix.as_bytes()))
+            {
+                return true;
+            }
+
+            let separator: &[u8] = if pending.response.is_empty() { b"" } else { b"\n" };
+            if pending.response.extend_from_slice(separator).is_err()
+                || pending.response.extend_from_slice(line).is_err()
+            {
+                pending.overflow = true;
+            }
+            false
+        });
+
+        if is_urc {
+            // `line` is at most `MAX_LINE_LEN` long.
+            publisher.publish_immediate(unwrap!(Vec::from_slice(line)));
+        }
+    }
+}
+
+/// AT command client.
+pub struct Client<'d, W> {
+    shared: &'d Shared,
+    writer: W,
+    timeout: Duration,
+}
+
+impl<'d, W: Write> Client<'d, W> {
+    /// Send a command and wait for its final result code, with the configured timeout.
+    ///
+    /// `command` is sent as is, followed by `\r`, e.g. `client.send("AT+CSQ")`.
+    pub async fn send(&mut self, command: &str) -> Result<Response, Error<W::Error>> {
+        self.send_with_timeout(command, self.timeout).await
+    }
+
+    /// Send a command and wait for its final result code, for at most `timeout`.
+    ///
+    /// The timeout includes writing the command.
+    pub async fn send_with_timeout(&mut self, command: &str, timeout: Duration) -> Result<Response, Error<W::Error>> {
+        let command = command.as_bytes();
+        if command.len() > MAX_LINE_LEN {
+            return Err(Error::CommandTooLong);
+        }
+
+        self.shared.pending.lock(|pending| {
+            let mut pending = pending.borrow_mut();
+            pending.active = true;
+            pending.command = unwrap!(Vec::from_slice(command));
+            pending.response.clear();
+            pending.overflow = false;
+        });
+        self.shared.done.reset();
+
+        let shared = self.shared;
+        let writer = &mut self.writer;
+        let result = with_timeout(timeout, async {
+            writer.write_all(command).await?;
+            writer.write_all(b"\r").await?;
+            writer.flush().await?;
+            Ok::<_, W::Error>(shared.done.wait().await)
+        })
+        .await;
+
+        let (response, overflow) = self.shared.pending.lock(|pending| {
+            let mut pending = pending.borrow_mut();
+            pending.active = false;
+            (core::mem::take(&mut pending.response), pending.overflow)
+        });
+
+        match result {
+            Err(_) => Err(Error::Timeout),
+            Ok(Err(e)) => Err(Error::Write(e)),
+            Ok(Ok(Final::Ok)) if overflow => Err(Error::ResponseTooLong),
+            Ok(Ok(Final::Ok)) => Ok(Response { data: response }),
+            Ok(Ok(Final::Error)) => Err(Error::Error),
+            Ok(Ok(Final::CmeError(code))) => Err(Error::CmeError(code)),
+            Ok(Ok(Final::CmsError(code))) => Err(Error::CmsError(code)),
+            Ok(Ok(Final::NoCarrier)) => Err(Error::NoCarrier),
+            Ok(Ok(Final::Busy)) => Err(Error::Busy),
+            Ok(Ok(Final::NoAnswer)) => Err(Error::NoAnswer),
+            Ok(Ok(Final::NoDialtone)) => Err(Error::NoDialtone),
+        }
+    }
+
+    /// Subscribe to the URCs.
+    ///
+    /// At most [`MAX_URC_SUBSCRIBERS`] subscribers can exist at the same time.
+    pub fn subscribe(&self) -> Result<UrcSubscriber<'d>, pubsub::Error> {
+        self.shared.urc.subscriber()
+    }
+
+    /// Return the serial port, for example to start the [CMUX multiplexer](crate::cmux).
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Create an AT client.
+///
+/// This returns two structs:
+/// - a `Client` sending the commands through `writer`.
+/// - a `Runner`. You must call `.run()` on it in a background task, with the reader of the
+///   serial port.
+pub fn new<'d, W: Write>(state: &'d mut State, writer: W, config: Config<'d>) -> (Runner<'d>, Client<'d, W>) {
+    let shared = &state.shared;
+    (
+        Runner {
+            shared,
+            urc_prefixes: config.urc_prefixes,
+        },
+        Client {
+            shared,
+            writer,
+            timeout: config.timeout,
+        },
+    )
+}
+
+fn parse_final(line: &[u8]) -> Option<Final> {
+    let result = match line {
+        b"OK" | b"CONNECT" => Final::Ok,
+        b"ERROR" => Final::Error,
+        b"NO CARRIER" => Final::NoCarrier,
+        b"BUSY" => Final::Busy,
+        b"NO ANSWER" => Final::NoAnswer,
+        b"NO DIALTONE" => Final::NoDialtone,
+        _ if line.starts_with(b"CONNECT ") => Final::Ok,
+        _ => {
+            if let Some(code) = line.strip_prefix(b"+CME ERROR:") {
+                parse_code(code).map_or(Final::Error, Final::CmeError)
+            } else if let Some(code) = line.strip_prefix(b"+CMS ERROR:") {
+                parse_code(code).map_or(Final::Error, Final::CmsError)
+            } else {
+                return None;
+            }
+        }
+    };
+    Some(result)
+}
+
+fn parse_code(code: &[u8]) -> Option<u16> {
+    core::str::from_utf8(code).ok()?.trim().parse().ok()
+}
+
+/// Whether `line` starts with the name of `command` followed by `:`, like `+CSQ: 20,99` for
+/// `AT+CSQ`.
+fn is_response_of(command: &[u8], line: &[u8]) -> bool {
+    let Some(command) = command.strip_prefix(b"AT").or_else(|| command.strip_prefix(b"at")) else {
+        return false;
+    };
+    let len = command
+        .iter()
+        .position(|b| matches!(b, b'=' | b'?' | b';'))
+        .unwrap_or(command.len());
+    let name = &command[..len];
+
+    !name.is_empty() && line.strip_prefix(name).is_some_and(|rest| rest.starts_with(b":"))
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_futures::join::join;
+
+    use super::*;
+
+    #[test]
+    fn parses_final_result_codes() {
+        assert_eq!(parse_final(b"OK"), Some(Final::Ok));
+        assert_eq!(parse_final(b"CONNECT 115200"), Some(Final::Ok));
+        assert_eq!(parse_final(b"+CME ERROR: 10"), Some(Final::CmeError(10)));
+        assert_eq!(parse_final(b"+CMS ERROR: 500"), Some(Final::CmsError(500)));
+        assert_eq!(parse_final(b"+CME ERROR: SIM not inserted"), Some(Final::Error));
+        assert_eq!(parse_final(b"+CSQ: 20,99"), None);
+    }
+
+    #[test]
+    fn matches_command_name() {
+        assert!(is_response_of(b"AT+CSQ", b"+CSQ: 20,99"));
+        assert!(is_response_of(b"AT+CREG?", b"+CREG: 0,1"));
+        assert!(!is_response_of(b"AT+CREG?", b"+CREGX: 0"));
+        assert!(!is_response_of(b"ATI", b"Quectel"));
+    }
+
+    #[futures_test::test]
+    async fn routes_response_and_urcs() {
+        let mut state = State::new();
+        let config = Config {
+            urc_prefixes: &["+CREG:", "+CMTI:"],
+            ..Default::default()
+        };
+        let mut out = [0; 16];
+        let (mut runner, mut client) = new(&mut state, &mut out[..], config);
+        let mut urcs = client.subscribe().unwrap();
+
+        let reader: &[u8] = b"\r\n+CMTI: \"SM\",1\r\nAT+CSQ\r\r\n+CSQ: 20,99\r\n+CREG: 1\r\n\r\nOK\r\n";
+        let (response, run) = join(client.send("AT+CSQ"), runner.run(reader)).await;
+
+        let response = response.unwrap();
+        assert_eq!(response.info("+CSQ"), Some(&b"20,99"[..]));
+        assert_eq!(response.lines().count(), 1);
+        assert!(matches!(run, Err(RunError::Eof)));
+
+        assert_eq!(urcs.try_next_message_pure().unwrap(), &b"+CMTI: \"SM\",1"[..]);
+        assert_eq!(urcs.try_next_message_pure().unwrap(), &b"+CREG: 1"[..]);
+        assert!(urcs.try_next_message_pure().is_none());
+        drop(urcs);
+        assert_eq!(&out[..7], b"AT+CSQ\r");
+    }
+
+    #[futures_test::test]
+    async fn reports_cme_error() {
+        let mut state = State::new();
+        let mut out = [0; 16];
+        let (mut runner, mut client) = new(&mut state, &mut out[..], Config::default());
+
+        let reader: &[u8] = b"\r\n+CME ERROR: 10\r\n";
+        let (response, _) = join(client.send("AT+CPIN?"), runner.run(reader)).await;
+        assert!(matches!(response, Err(Error::CmeError(10))));
+    }
+}
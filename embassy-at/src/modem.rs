@@ -0,0 +1,100 @@
+//! Cellular modem abstraction.
+//!
+//! [`CellularModem`] is implemented by the drivers of the modems, usually on top of the
+//! [AT client](crate::Client), and consumed by the network stacks. For example
+//! `embassy-net-ppp` runs PPP over the data mode of any `CellularModem`.
+
+use core::fmt::Write as _;
+
+use embedded_io_async::{BufRead, Write};
+use heapless::String;
+
+use crate::{Client, Error, MAX_LINE_LEN};
+
+/// Type of a PDP context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PdpType {
+    /// IPv4.
+    Ip,
+    /// IPv6.
+    Ipv6,
+    /// Dual-stack IPv4 and IPv6.
+    Ipv4v6,
+}
+
+impl PdpType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            PdpType::Ip => "IP",
+            PdpType::Ipv6 => "IPV6",
+            PdpType::Ipv4v6 => "IPV4V6",
+        }
+    }
+}
+
+/// PDP context, the packet data connection to an access point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PdpContext<'a> {
+    /// Context identifier, usually 1.
+    pub cid: u8,
+    /// Type of the context.
+    pub pdp_type: PdpType,
+    /// Access point name of the operator.
+    pub apn: &'a str,
+}
+
+/// Cellular modem with a data mode.
+pub trait CellularModem {
+    /// Error of the modem operations.
+    type Error;
+
+    /// Serial link of the data mode, carrying PPP frames.
+    type Data<'a>: BufRead + Write
+    where
+        Self: 'a;
+
+    /// Power the modem on and wait until it answers AT commands.
+    async fn power_on(&mut self) -> Result<(), Self::Error>;
+
+    /// Power the modem off.
+    async fn power_off(&mut self) -> Result<(), Self::Error>;
+
+    /// Define the PDP context used by [`data_mode`](Self::data_mode).
+    async fn set_pdp_context(&mut self, context: &PdpContext<'_>) -> Result<(), Self::Error>;
+
+    /// Activate the PDP context `cid` and switch to data mode.
+    ///
+    /// The modem stays in data mode until the returned link is dropped, or the peer ends the
+    /// connection.
+    async fn data_mode(&mut self, cid: u8) -> Result<Self::Data<'_>, Self::Error>;
+}
+
+impl<'d, W: Write> Client<'d, W> {
+    /// Define a PDP context with `AT+CGDCONT`.
+    pub async fn set_pdp_context(&mut self, context: &PdpContext<'_>) -> Result<(), Error<W::Error>> {
+        let mut command: String<MAX_LINE_LEN> = String::new();
+        write!(
+            command,
+            "AT+CGDCONT={},\"{}\",\"{}\"",
+            context.cid,
+            context.pdp_type.as_str(),
+            context.apn
+        )
+        .map_err(|_| Error::CommandTooLong)?;
+        self.send(&command).await?;
+        Ok(())
+    }
+
+    /// Activate the PDP context `cid` with `ATD*99***<cid>#`, and wait for `CONNECT`.
+    ///
+    /// The serial link is in data mode afterwards, and must be handed over to the network stack,
+    /// for example by canceling [`Runner::run`](crate::Runner::run).
+    pub async fn dial_data(&mut self, cid: u8) -> Result<(), Error<W::Error>> {
+        let mut command: String<MAX_LINE_LEN> = String::new();
+        write!(command, "ATD*99***{}#", cid).map_err(|_| Error::CommandTooLong)?;
+        self.send(&command).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,281 @@
+use display_interface::{AsyncWriteOnlyDataCommand, DataFormat, DisplayError};
+use embedded_hal_1::digital::OutputPin;
+use embedded_hal_async::digital::Wait;
+use embedded_hal_async::spi::SpiDevice;
+
+/// Tearing effect pin placeholder, used when the display TE output is not connected.
+pub struct NoTearingEffect;
+
+impl embedded_hal_1::digital::ErrorType for NoTearingEffect {
+    type Error = core::convert::Infallible;
+}
+
+impl Wait for NoTearingEffect {
+    async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Async `display-interface` adapter for SPI displays with a data/command pin.
+///
+/// Big writes are split in chunks of at most `max_chunk` bytes, so a full framebuffer can be sent
+/// with a single [`send_data`](AsyncWriteOnlyDataCommand::send_data) call even if the SPI driver's
+/// DMA has a maximum transfer length. Data that is not a plain byte slice (iterators,
+/// little-endian `u16`...) is converted through an internal buffer of `N` bytes.
+///
+/// When a tearing effect pin is set with [`with_tearing_effect`](Self::with_tearing_effect), the
+/// first data write after the frame sync command waits for the TE rising edge, so the framebuffer
+/// is not updated while the display is scanning it out.
+pub struct SpiDisplayInterface<SPI, DC, TE = NoTearingEffect, const N: usize = 64> {
+    spi: SPI,
+    dc: DC,
+    te: TE,
+    sync_command: Option<u8>,
+    sync_pending: bool,
+    max_chunk: usize,
+    buf: [u8; N],
+}
+
+impl<SPI, DC, const N: usize> SpiDisplayInterface<SPI, DC, NoTearingEffect, N>
+where
+    SPI: SpiDevice,
+    DC: OutputPin,
+{
+    /// Create a new display interface from a SPI device and a data/command pin.
+    ///
+    /// The DC pin is low for commands and high for data.
+    pub fn new(spi: SPI, dc: DC) -> Self {
+        Self {
+            spi,
+            dc,
+            te: NoTearingEffect,
+            sync_command: None,
+            sync_pending: false,
+            max_chunk: u16::MAX as usize,
+            buf: [0; N],
+        }
+    }
+
+    /// Synchronize frame writes with the display tearing effect output.
+    ///
+    /// `sync_command` is the command starting a frame write, usually the memory write command
+    /// (`0x2C` on most MIPI DCS controllers).
+    pub fn with_tearing_effect<TE: Wait>(self, te: TE, sync_command: u8) -> SpiDisplayInterface<SPI, DC, TE, N> {
+        SpiDisplayInterface {
+            spi: self.spi,
+            dc: self.dc,
+            te,
+            sync_command: Some(sync_command),
+            sync_pending: false,
+            max_chunk: self.max_chunk,
+            buf: self.buf,
+        }
+    }
+}
+
+impl<SPI, DC, TE, const N: usize> SpiDisplayInterface<SPI, DC, TE, N>
+where
+    SPI: SpiDevice,
+    DC: OutputPin,
+    TE: Wait,
+{
+    /// Set the maximum number of bytes sent in a single SPI transfer.
+    ///
+    /// Defaults to 65535, the maximum DMA transfer length on most STM32 chips. Some nRF chips
+    /// need a smaller value, e.g. 255 on the nRF52832.
+    pub fn set_max_chunk(&mut self, max_chunk: usize) {
+        assert!(max_chunk > 0);
+        self.max_chunk = max_chunk;
+    }
+
+    /// Release the SPI device and the pins.
+    pub fn release(self) -> (SPI, DC, TE) {
+        (self.spi, self.dc, self.te)
+    }
+
+    async fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), DisplayError> {
+        for chunk in bytes.chunks(self.max_chunk) {
+            self.spi.write(chunk).await.map_err(|_| DisplayError::BusWriteError)?;
+        }
+        Ok(())
+    }
+
+    async fn write_iter(&mut self, iter: impl Iterator<Item = u8>) -> Result<(), DisplayError> {
+        let mut len = 0;
+        for byte in iter {
+            self.buf[len] = byte;
+            len += 1;
+            if len == N {
+                self.spi
+                    .write(&self.buf)
+                    .await
+                    .map_err(|_| DisplayError::BusWriteError)?;
+                len = 0;
+            }
+        }
+        if len > 0 {
+            self.spi
+                .write(&self.buf[..len])
+                .await
+                .map_err(|_| DisplayError::BusWriteError)?;
+        }
+        Ok(())
+    }
+
+    async fn write(&mut self, data: DataFormat<'_>) -> Result<(), DisplayError> {
+        match data {
+            DataFormat::U8(bytes) => self.write_bytes(bytes).await,
+            DataFormat::U16(words) => self.write_bytes(as_bytes(words)).await,
+            DataFormat::U16BE(words) => {
+                for w in words.iter_mut() {
+                    *w = w.to_be();
+                }
+                self.write_bytes(as_bytes(words)).await
+            }
+            DataFormat::U16LE(words) => {
+                for w in words.iter_mut() {
+                    *w = w.to_le();
+                }
+                self.write_bytes(as_bytes(words)).await
+            }
+            DataFormat::U8Iter(iter) => self.write_iter(iter).await,
+            DataFormat::U16BEIter(iter) => self.write_iter(iter.flat_map(u16::to_be_bytes)).await,
+            DataFormat::U16LEIter(iter) => self.write_iter(iter.flat_map(u16::to_le_bytes)).await,
+            _ => Err(DisplayError::DataFormatNotImplemented),
+        }
+    }
+}
+
+impl<SPI, DC, TE, const N: usize> AsyncWriteOnlyDataCommand for SpiDisplayInterface<SPI, DC, TE, N>
+where
+    SPI: SpiDevice,
+    DC: OutputPin,
+    TE: Wait,
+{
+    async fn send_commands(&mut self, cmd: DataFormat<'_>) -> Result<(), DisplayError> {
+        let last = match &cmd {
+            DataFormat::U8(bytes) => bytes.last().copied(),
+            _ => None,
+        };
+
+        self.dc.set_low().map_err(|_| DisplayError::DCError)?;
+        self.write(cmd).await?;
+
+        self.sync_pending = self.sync_command.is_some() && last == self.sync_command;
+        Ok(())
+    }
+
+    async fn send_data(&mut self, buf: DataFormat<'_>) -> Result<(), DisplayError> {
+        if self.sync_pending {
+            self.sync_pending = false;
+            self.te
+                .wait_for_rising_edge()
+                .await
+                .map_err(|_| DisplayError::BusWriteError)?;
+        }
+
+        self.dc.set_high().map_err(|_| DisplayError::DCError)?;
+        self.write(buf).await
+    }
+}
+
+fn as_bytes(words: &[u16]) -> &[u8] {
+    // Safety: u8 has no alignment requirement and the length covers the same memory.
+    unsafe { core::slice::from_raw_parts(words.as_ptr() as *const u8, words.len() * 2) }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use alloc::vec::Vec;
+    use core::convert::Infallible;
+
+    use embedded_hal_1::spi::Operation;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Bus {
+        // (dc, bytes) of each SPI transfer
+        writes: Vec<(bool, Vec<u8>)>,
+        dc: bool,
+    }
+
+    struct Spi<'a>(&'a core::cell::RefCell<Bus>);
+    struct Dc<'a>(&'a core::cell::RefCell<Bus>);
+
+    impl embedded_hal_1::spi::ErrorType for Spi<'_> {
+        type Error = Infallible;
+    }
+
+    impl SpiDevice for Spi<'_> {
+        async fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Infallible> {
+            let mut bus = self.0.borrow_mut();
+            for op in operations {
+                if let Operation::Write(buf) = op {
+                    let dc = bus.dc;
+                    bus.writes.push((dc, buf.to_vec()));
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl embedded_hal_1::digital::ErrorType for Dc<'_> {
+        type Error = Infallible;
+    }
+
+    impl OutputPin for Dc<'_> {
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            self.0.borrow_mut().dc = false;
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            self.0.borrow_mut().dc = true;
+            Ok(())
+        }
+    }
+
+    #[futures_test::test]
+    async fn chunks_data() {
+        let bus = core::cell::RefCell::new(Bus::default());
+        let mut di: SpiDisplayInterface<_, _, _, 4> = SpiDisplayInterface::new(Spi(&bus), Dc(&bus));
+        di.set_max_chunk(3);
+
+        di.send_commands(DataFormat::U8(&[0x2C])).await.unwrap();
+        di.send_data(DataFormat::U8(&[1, 2, 3, 4, 5])).await.unwrap();
+        di.send_data(DataFormat::U16BEIter(&mut [0x0102u16, 0x0304, 0x0506].into_iter()))
+            .await
+            .unwrap();
+
+        let bus = bus.into_inner();
+        assert_eq!(
+            bus.writes,
+            [
+                (false, alloc::vec![0x2C]),
+                (true, alloc::vec![1, 2, 3]),
+                (true, alloc::vec![4, 5]),
+                (true, alloc::vec![1, 2, 3, 4]),
+                (true, alloc::vec![5, 6]),
+            ]
+        );
+    }
+}
@@ -1,7 +1,11 @@
 //! Adapters between embedded-hal traits.
 
 mod blocking_async;
+#[cfg(feature = "display-interface")]
+mod display;
 mod yielding_async;
 
 pub use blocking_async::BlockingAsync;
+#[cfg(feature = "display-interface")]
+pub use display::{NoTearingEffect, SpiDisplayInterface};
 pub use yielding_async::YieldingAsync;
@@ -0,0 +1,68 @@
+//! Analog to digital converter traits.
+//!
+//! [`AdcChannel`] is implemented by the ADC drivers of the HALs, so the sensor drivers and the
+//! application code can read analog inputs without depending on a particular chip. The readings
+//! are in millivolts, converted by the HAL from the configured reference and resolution.
+
+/// Analog input, bound to its converter.
+pub trait AdcChannel {
+    /// Error type.
+    type Error: core::fmt::Debug;
+
+    /// Convert the input once, and return its voltage in millivolts.
+    ///
+    /// Differential inputs can be negative.
+    async fn read_mv(&mut self) -> Result<i32, Self::Error>;
+
+    /// Convert the input `count` times, and return the average voltage in millivolts.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `count` is zero.
+    async fn read_average_mv(&mut self, count: u32) -> Result<i32, Self::Error> {
+        assert!(count > 0);
+
+        let mut sum: i64 = 0;
+        for _ in 0..count {
+            sum += self.read_mv().await? as i64;
+        }
+        Ok((sum / count as i64) as i32)
+    }
+}
+
+impl<T: AdcChannel + ?Sized> AdcChannel for &mut T {
+    type Error = T::Error;
+
+    async fn read_mv(&mut self) -> Result<i32, Self::Error> {
+        T::read_mv(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use super::*;
+
+    struct Ramp(i32);
+
+    impl AdcChannel for Ramp {
+        type Error = Infallible;
+
+        async fn read_mv(&mut self) -> Result<i32, Infallible> {
+            self.0 += 10;
+            Ok(self.0)
+        }
+    }
+
+    async fn read_sensor(mut channel: impl AdcChannel) -> i32 {
+        channel.read_average_mv(4).await.unwrap()
+    }
+
+    #[futures_test::test]
+    async fn averages_readings() {
+        let mut ramp = Ramp(1000);
+        assert_eq!(read_sensor(&mut ramp).await, 1025);
+        assert_eq!(ramp.read_mv().await, Ok(1050));
+    }
+}
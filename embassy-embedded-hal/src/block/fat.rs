@@ -0,0 +1,101 @@
+//! FAT filesystem on a block device, using `embedded-fatfs`.
+//!
+//! Long file names are supported. All accesses to a volume go through an async mutex, so several
+//! tasks can share a volume, and sector transfers await the block device instead of blocking the
+//! executor.
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::mutex::{Mutex, MutexGuard};
+use embedded_fatfs::{DefaultTimeProvider, FsOptions, LossyOemCpConverter};
+
+use super::{Block, BlockDevice, BlockStream, BlockStreamError};
+
+/// FAT filesystem on a block device.
+pub type FileSystem<D> = embedded_fatfs::FileSystem<BlockStream<D>, DefaultTimeProvider, LossyOemCpConverter>;
+
+/// FAT filesystem error.
+pub type Error<E> = embedded_fatfs::Error<BlockStreamError<E>>;
+
+/// Mounted FAT volume, shared through an async mutex.
+pub struct Volume<M: RawMutex, D: BlockDevice> {
+    fs: Mutex<M, FileSystem<D>>,
+}
+
+impl<M: RawMutex, D: BlockDevice> Volume<M, D> {
+    /// Mount the FAT volume of a block device.
+    ///
+    /// If the first block is a master boot record instead of a FAT boot sector, the first
+    /// partition is mounted.
+    pub async fn mount(mut dev: D) -> Result<Self, Error<D::Error>> {
+        let num_blocks = dev.num_blocks().await.map_err(device_error)?;
+
+        let mut block = Block::new();
+        dev.read(0, core::slice::from_mut(&mut block))
+            .await
+            .map_err(device_error)?;
+        let (first_block, num_blocks) = first_partition(&block).unwrap_or((0, num_blocks));
+
+        let stream = BlockStream::new_range(dev, first_block, num_blocks);
+        let fs = FileSystem::new(stream, FsOptions::new()).await?;
+        Ok(Self { fs: Mutex::new(fs) })
+    }
+
+    /// Lock the volume for exclusive access.
+    ///
+    /// Keep the lock while a file or directory of the volume is open.
+    pub async fn lock(&self) -> MutexGuard<'_, M, FileSystem<D>> {
+        self.fs.lock().await
+    }
+
+    /// Unmount the volume, flushing the filesystem metadata.
+    pub async fn unmount(self) -> Result<(), Error<D::Error>> {
+        self.fs.into_inner().unmount().await
+    }
+}
+
+fn device_error<E>(e: E) -> Error<E> {
+    embedded_fatfs::Error::Io(BlockStreamError::Device(e))
+}
+
+/// Find the first partition of a master boot record.
+///
+/// Returns `None` if `block` looks like a FAT boot sector, or has no partition.
+fn first_partition(block: &Block) -> Option<(u32, u32)> {
+    if block[510..512] != [0x55, 0xAA] {
+        return None;
+    }
+    // A FAT boot sector starts with a jump instruction, a MBR with boot code.
+    if &block[0x36..0x39] == b"FAT" || &block[0x52..0x55] == b"FAT" {
+        return None;
+    }
+
+    let entry = &block[0x1BE..0x1CE];
+    let first_block = u32::from_le_bytes(entry[8..12].try_into().unwrap());
+    let num_blocks = u32::from_le_bytes(entry[12..16].try_into().unwrap());
+    match (entry[4], num_blocks) {
+        (0, _) | (_, 0) => None,
+        _ => Some((first_block, num_blocks)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_partition() {
+        let mut block = Block::new();
+        assert_eq!(first_partition(&block), None);
+
+        block[510] = 0x55;
+        block[511] = 0xAA;
+        // FAT32 partition at block 8192
+        block[0x1BE + 4] = 0x0C;
+        block[0x1BE + 8..0x1BE + 12].copy_from_slice(&8192u32.to_le_bytes());
+        block[0x1BE + 12..0x1BE + 16].copy_from_slice(&1_000_000u32.to_le_bytes());
+        assert_eq!(first_partition(&block), Some((8192, 1_000_000)));
+
+        // FAT32 boot sector
+        block[0x52..0x55].copy_from_slice(b"FAT");
+        assert_eq!(first_partition(&block), None);
+    }
+}
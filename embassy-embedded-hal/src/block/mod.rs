@@ -0,0 +1,84 @@
+//! Block devices, such as SD cards.
+//!
+//! [`BlockDevice`] is implemented by the SD card drivers of the HALs and by the SPI-mode
+//! [`SdSpi`] driver, so filesystem code can be written once for all of them. [`BlockStream`]
+//! turns a block device into a byte stream, and the `fat` module (with the `fatfs` feature)
+//! mounts a FAT filesystem on it.
+use core::ops::{Deref, DerefMut};
+
+#[cfg(feature = "fatfs")]
+pub mod fat;
+mod sd_spi;
+mod stream;
+
+pub use sd_spi::{CardType, SdSpi, SdSpiError};
+pub use stream::{BlockStream, BlockStreamError};
+
+/// Size of a block, in bytes.
+pub const BLOCK_SIZE: usize = 512;
+
+/// Aligned data block.
+///
+/// This is a 512-byte array, aligned to 4 bytes to satisfy DMA requirements.
+#[repr(align(4))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Block(pub [u8; BLOCK_SIZE]);
+
+impl Block {
+    /// Create a block filled with zeros.
+    pub const fn new() -> Self {
+        Self([0; BLOCK_SIZE])
+    }
+}
+
+impl Default for Block {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for Block {
+    type Target = [u8; BLOCK_SIZE];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Block {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// Async block device.
+pub trait BlockDevice {
+    /// Error type.
+    type Error: core::fmt::Debug;
+
+    /// Number of blocks of the device.
+    async fn num_blocks(&mut self) -> Result<u32, Self::Error>;
+
+    /// Read consecutive blocks, starting at block `block_idx`.
+    async fn read(&mut self, block_idx: u32, blocks: &mut [Block]) -> Result<(), Self::Error>;
+
+    /// Write consecutive blocks, starting at block `block_idx`.
+    async fn write(&mut self, block_idx: u32, blocks: &[Block]) -> Result<(), Self::Error>;
+}
+
+impl<T: BlockDevice> BlockDevice for &mut T {
+    type Error = T::Error;
+
+    async fn num_blocks(&mut self) -> Result<u32, Self::Error> {
+        T::num_blocks(self).await
+    }
+
+    async fn read(&mut self, block_idx: u32, blocks: &mut [Block]) -> Result<(), Self::Error> {
+        T::read(self, block_idx, blocks).await
+    }
+
+    async fn write(&mut self, block_idx: u32, blocks: &[Block]) -> Result<(), Self::Error> {
+        T::write(self, block_idx, blocks).await
+    }
+}
@@ -0,0 +1,567 @@
+//! SD/MMC card driver in SPI mode.
+use embedded_hal_1::digital::OutputPin;
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::spi::SpiBus;
+
+use super::{Block, BlockDevice, BLOCK_SIZE};
+use crate::SetConfig;
+
+/// GO_IDLE_STATE
+const CMD0: u8 = 0;
+/// SEND_OP_COND, for MMC cards
+const CMD1: u8 = 1;
+/// SEND_IF_COND
+const CMD8: u8 = 8;
+/// SEND_CSD
+const CMD9: u8 = 9;
+/// STOP_TRANSMISSION
+const CMD12: u8 = 12;
+/// SET_BLOCKLEN
+const CMD16: u8 = 16;
+/// READ_SINGLE_BLOCK
+const CMD17: u8 = 17;
+/// READ_MULTIPLE_BLOCK
+const CMD18: u8 = 18;
+/// WRITE_BLOCK
+const CMD24: u8 = 24;
+/// WRITE_MULTIPLE_BLOCK
+const CMD25: u8 = 25;
+/// APP_CMD
+const CMD55: u8 = 55;
+/// READ_OCR
+const CMD58: u8 = 58;
+/// CRC_ON_OFF
+const CMD59: u8 = 59;
+/// SD_SEND_OP_COND
+const ACMD41: u8 = 41;
+
+const R1_IDLE: u8 = 0x01;
+const R1_ILLEGAL_COMMAND: u8 = 0x04;
+const R1_COM_CRC_ERROR: u8 = 0x08;
+
+const TOKEN_START_BLOCK: u8 = 0xFE;
+const TOKEN_START_MULTI_WRITE: u8 = 0xFC;
+const TOKEN_STOP_TRAN: u8 = 0xFD;
+
+const DATA_RES_MASK: u8 = 0x1F;
+const DATA_RES_ACCEPTED: u8 = 0x05;
+const DATA_RES_CRC_ERROR: u8 = 0x0B;
+
+/// Timeout while waiting for the card to finish an operation, in milliseconds.
+const BUSY_TIMEOUT_MS: u32 = 500;
+/// Timeout while waiting for a data block, in milliseconds.
+const READ_TIMEOUT_MS: u32 = 100;
+/// Timeout for the card initialization, in milliseconds.
+const INIT_TIMEOUT_MS: u32 = 1000;
+
+/// Card type.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CardType {
+    /// MMC card.
+    Mmc,
+    /// SD version 1 card.
+    Sd1,
+    /// SD version 2 standard capacity card.
+    Sd2,
+    /// SDHC or SDXC card.
+    Sdhc,
+}
+
+/// SD over SPI error.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum SdSpiError<SPI, CS> {
+    /// An operation on the SPI bus failed.
+    Spi(SPI),
+    /// Setting the value of the Chip Select (CS) pin failed.
+    Cs(CS),
+    /// The SPI bus could not be configured.
+    Config,
+    /// The card did not respond in time.
+    Timeout,
+    /// CRC mismatch on a command or a data block.
+    Crc,
+    /// The card responded to a command with the given R1 error bits.
+    Card(u8),
+    /// The card is not supported, or there is no card.
+    UnsupportedCard,
+    /// The card sent the given error token instead of a data block.
+    Read(u8),
+    /// The card rejected a written block with the given data response.
+    Write(u8),
+    /// The card has not been initialized with [`SdSpi::init`].
+    NotInitialized,
+}
+
+type Error<SPI, CS> =
+    SdSpiError<<SPI as embedded_hal_async::spi::ErrorType>::Error, <CS as embedded_hal_1::digital::ErrorType>::Error>;
+
+#[derive(Copy, Clone)]
+struct Card {
+    card_type: CardType,
+    num_blocks: u32,
+}
+
+/// SD/MMC card driver in SPI mode.
+///
+/// This is for chips without an SD card peripheral. It takes the SPI bus and the chip select pin
+/// separately, since the card initialization requires clocks with CS deasserted.
+///
+/// The card is initialized with the bus running with `init_config`, which must set a frequency of
+/// at most 400 kHz, then `config` is applied for the data transfers.
+pub struct SdSpi<SPI: SetConfig, CS, D> {
+    spi: SPI,
+    cs: CS,
+    delay: D,
+    init_config: SPI::Config,
+    config: SPI::Config,
+    card: Option<Card>,
+}
+
+impl<SPI, CS, D> SdSpi<SPI, CS, D>
+where
+    SPI: SpiBus + SetConfig,
+    CS: OutputPin,
+    D: DelayNs,
+{
+    /// Create a new SD card driver.
+    ///
+    /// [`init`](Self::init) must be called before accessing the card.
+    pub fn new(spi: SPI, cs: CS, delay: D, init_config: SPI::Config, config: SPI::Config) -> Self {
+        Self {
+            spi,
+            cs,
+            delay,
+            init_config,
+            config,
+            card: None,
+        }
+    }
+
+    /// Initialize the card.
+    ///
+    /// This must be called again after the card has been removed and inserted.
+    pub async fn init(&mut self) -> Result<CardType, Error<SPI, CS>> {
+        self.card = None;
+        self.spi.set_config(&self.init_config).map_err(|_| SdSpiError::Config)?;
+
+        // At least 74 clocks with CS high to enter the native mode.
+        self.cs.set_high().map_err(SdSpiError::Cs)?;
+        self.send(&[0xFF; 10]).await?;
+
+        self.select()?;
+        let res = self.init_inner().await;
+        let deselect = self.deselect().await;
+        let card = res?;
+        deselect?;
+
+        self.spi.set_config(&self.config).map_err(|_| SdSpiError::Config)?;
+        self.card = Some(card);
+        Ok(card.card_type)
+    }
+
+    /// Get the type of the initialized card.
+    pub fn card_type(&self) -> Option<CardType> {
+        self.card.map(|c| c.card_type)
+    }
+
+    /// Release the SPI bus, the CS pin and the delay.
+    pub fn release(self) -> (SPI, CS, D) {
+        (self.spi, self.cs, self.delay)
+    }
+
+    async fn init_inner(&mut self) -> Result<Card, Error<SPI, CS>> {
+        // Enter SPI mode. The card may still be powering up, so retry a few times.
+        let mut attempts = 0;
+        loop {
+            match self.cmd(CMD0, 0).await {
+                Ok(R1_IDLE) => break,
+                Ok(_) | Err(SdSpiError::Timeout) => {}
+                Err(e) => return Err(e),
+            }
+            attempts += 1;
+            if attempts == 10 {
+                return Err(SdSpiError::UnsupportedCard);
+            }
+            self.delay.delay_ms(10).await;
+        }
+
+        // Enable CRC checking.
+        let r1 = self.cmd(CMD59, 1).await?;
+        check_r1(r1 & !R1_IDLE)?;
+
+        // Check for a version 2 card, with the 2.7-3.6V range and a 0xAA check pattern.
+        let v2 = match self.cmd(CMD8, 0x1AA).await? {
+            r1 if r1 & R1_ILLEGAL_COMMAND != 0 => false,
+            R1_IDLE => {
+                let mut r7 = [0xFF; 4];
+                self.transfer(&mut r7).await?;
+                if r7[3] != 0xAA {
+                    return Err(SdSpiError::UnsupportedCard);
+                }
+                true
+            }
+            r1 => return Err(SdSpiError::Card(r1)),
+        };
+
+        // Wait for the end of the card initialization. Cards that don't know ACMD41 are MMC.
+        let hcs = if v2 { 0x4000_0000 } else { 0 };
+        let mut mmc = false;
+        let mut elapsed = 0;
+        loop {
+            let r1 = match mmc {
+                true => self.cmd(CMD1, 0).await?,
+                false => self.acmd(ACMD41, hcs).await?,
+            };
+            if r1 == 0 {
+                break;
+            }
+            if r1 & R1_ILLEGAL_COMMAND != 0 && !v2 && !mmc {
+                mmc = true;
+                continue;
+            }
+            check_r1(r1 & !R1_IDLE)?;
+
+            if elapsed >= INIT_TIMEOUT_MS {
+                return Err(SdSpiError::Timeout);
+            }
+            self.delay.delay_ms(10).await;
+            elapsed += 10;
+        }
+
+        let card_type = if mmc {
+            CardType::Mmc
+        } else if v2 {
+            // The CCS bit of the OCR tells the addressing mode.
+            check_r1(self.cmd(CMD58, 0).await?)?;
+            let mut ocr = [0xFF; 4];
+            self.transfer(&mut ocr).await?;
+            match ocr[0] & 0x40 != 0 {
+                true => CardType::Sdhc,
+                false => CardType::Sd2,
+            }
+        } else {
+            CardType::Sd1
+        };
+
+        if card_type != CardType::Sdhc {
+            check_r1(self.cmd(CMD16, BLOCK_SIZE as u32).await?)?;
+        }
+
+        check_r1(self.cmd(CMD9, 0).await?)?;
+        let mut csd = [0; 16];
+        self.read_data(&mut csd).await?;
+
+        Ok(Card {
+            card_type,
+            num_blocks: csd_num_blocks(&csd),
+        })
+    }
+
+    fn card(&self) -> Result<Card, Error<SPI, CS>> {
+        self.card.ok_or(SdSpiError::NotInitialized)
+    }
+
+    fn select(&mut self) -> Result<(), Error<SPI, CS>> {
+        self.cs.set_low().map_err(SdSpiError::Cs)
+    }
+
+    async fn deselect(&mut self) -> Result<(), Error<SPI, CS>> {
+        self.cs.set_high().map_err(SdSpiError::Cs)?;
+        // One more byte so the card releases its data output.
+        self.send(&[0xFF]).await
+    }
+
+    async fn send(&mut self, data: &[u8]) -> Result<(), Error<SPI, CS>> {
+        self.spi.write(data).await.map_err(SdSpiError::Spi)
+    }
+
+    /// Read into `buf`, keeping the card data input high.
+    async fn transfer(&mut self, buf: &mut [u8]) -> Result<(), Error<SPI, CS>> {
+        buf.fill(0xFF);
+        self.spi.transfer_in_place(buf).await.map_err(SdSpiError::Spi)
+    }
+
+    async fn read_byte(&mut self) -> Result<u8, Error<SPI, CS>> {
+        let mut buf = [0xFF];
+        self.transfer(&mut buf).await?;
+        Ok(buf[0])
+    }
+
+    /// Read bytes until `done` returns true, and return the last byte.
+    async fn poll_byte(&mut self, timeout_ms: u32, done: fn(u8) -> bool) -> Result<u8, Error<SPI, CS>> {
+        let mut elapsed = 0;
+        loop {
+            for _ in 0..16 {
+                let byte = self.read_byte().await?;
+                if done(byte) {
+                    return Ok(byte);
+                }
+            }
+            if elapsed >= timeout_ms {
+                return Err(SdSpiError::Timeout);
+            }
+            self.delay.delay_ms(1).await;
+            elapsed += 1;
+        }
+    }
+
+    /// Wait until the card does not hold its data output low anymore.
+    async fn wait_ready(&mut self) -> Result<(), Error<SPI, CS>> {
+        self.poll_byte(BUSY_TIMEOUT_MS, |b| b == 0xFF).await.map(|_| ())
+    }
+
+    /// Send a command to the selected card, and return the R1 response.
+    async fn cmd(&mut self, cmd: u8, arg: u32) -> Result<u8, Error<SPI, CS>> {
+        // The card is not ready to accept commands right after power up, and sends data during a
+        // multiple block read.
+        if cmd != CMD0 && cmd != CMD12 {
+            self.wait_ready().await?;
+        }
+
+        let mut buf = [0x40 | cmd, 0, 0, 0, 0, 0];
+        buf[1..5].copy_from_slice(&arg.to_be_bytes());
+        buf[5] = crc7(&buf[..5]) << 1 | 1;
+        self.send(&buf).await?;
+
+        if cmd == CMD12 {
+            // Skip the stuff byte.
+            self.read_byte().await?;
+        }
+
+        for _ in 0..10 {
+            let r1 = self.read_byte().await?;
+            if r1 & 0x80 == 0 {
+                return Ok(r1);
+            }
+        }
+        Err(SdSpiError::Timeout)
+    }
+
+    async fn acmd(&mut self, cmd: u8, arg: u32) -> Result<u8, Error<SPI, CS>> {
+        let r1 = self.cmd(CMD55, 0).await?;
+        if r1 & !R1_IDLE != 0 {
+            return Ok(r1);
+        }
+        self.cmd(cmd, arg).await
+    }
+
+    /// Receive a data block, after a read command.
+    async fn read_data(&mut self, buf: &mut [u8]) -> Result<(), Error<SPI, CS>> {
+        let token = self.poll_byte(READ_TIMEOUT_MS, |b| b != 0xFF).await?;
+        if token != TOKEN_START_BLOCK {
+            return Err(SdSpiError::Read(token));
+        }
+
+        self.transfer(buf).await?;
+        let mut crc = [0xFF; 2];
+        self.transfer(&mut crc).await?;
+        if u16::from_be_bytes(crc) != crc16(buf) {
+            return Err(SdSpiError::Crc);
+        }
+        Ok(())
+    }
+
+    /// Send a data block, after a write command.
+    async fn write_data(&mut self, token: u8, data: &[u8]) -> Result<(), Error<SPI, CS>> {
+        self.wait_ready().await?;
+
+        self.send(&[token]).await?;
+        self.send(data).await?;
+        self.send(&crc16(data).to_be_bytes()).await?;
+
+        match self.read_byte().await? & DATA_RES_MASK {
+            DATA_RES_ACCEPTED => Ok(()),
+            DATA_RES_CRC_ERROR => Err(SdSpiError::Crc),
+            res => Err(SdSpiError::Write(res)),
+        }
+    }
+
+    async fn read_inner(&mut self, addr: u32, blocks: &mut [Block]) -> Result<(), Error<SPI, CS>> {
+        if let [block] = blocks {
+            check_r1(self.cmd(CMD17, addr).await?)?;
+            return self.read_data(&mut block.0).await;
+        }
+
+        check_r1(self.cmd(CMD18, addr).await?)?;
+        let mut res = Ok(());
+        for block in blocks.iter_mut() {
+            res = self.read_data(&mut block.0).await;
+            if res.is_err() {
+                break;
+            }
+        }
+
+        // Stop the transmission, even if a block failed.
+        let r1 = self.cmd(CMD12, 0).await;
+        res?;
+        check_r1(r1?)?;
+        self.wait_ready().await
+    }
+
+    async fn write_inner(&mut self, addr: u32, blocks: &[Block]) -> Result<(), Error<SPI, CS>> {
+        if let [block] = blocks {
+            check_r1(self.cmd(CMD24, addr).await?)?;
+            self.write_data(TOKEN_START_BLOCK, &block.0).await?;
+        } else {
+            check_r1(self.cmd(CMD25, addr).await?)?;
+            let mut res = Ok(());
+            for block in blocks {
+                res = self.write_data(TOKEN_START_MULTI_WRITE, &block.0).await;
+                if res.is_err() {
+                    break;
+                }
+            }
+
+            // Stop the transmission, even if a block failed.
+            self.wait_ready().await?;
+            self.send(&[TOKEN_STOP_TRAN]).await?;
+            res?;
+        }
+
+        // Wait for the end of the programming.
+        self.wait_ready().await
+    }
+}
+
+impl<SPI, CS, D> BlockDevice for SdSpi<SPI, CS, D>
+where
+    SPI: SpiBus + SetConfig,
+    CS: OutputPin,
+    D: DelayNs,
+{
+    type Error = Error<SPI, CS>;
+
+    async fn num_blocks(&mut self) -> Result<u32, Self::Error> {
+        Ok(self.card()?.num_blocks)
+    }
+
+    async fn read(&mut self, block_idx: u32, blocks: &mut [Block]) -> Result<(), Self::Error> {
+        let card = self.card()?;
+        if blocks.is_empty() {
+            return Ok(());
+        }
+
+        self.select()?;
+        let res = self.read_inner(card.address(block_idx), blocks).await;
+        let deselect = self.deselect().await;
+        res?;
+        deselect
+    }
+
+    async fn write(&mut self, block_idx: u32, blocks: &[Block]) -> Result<(), Self::Error> {
+        let card = self.card()?;
+        if blocks.is_empty() {
+            return Ok(());
+        }
+
+        self.select()?;
+        let res = self.write_inner(card.address(block_idx), blocks).await;
+        let deselect = self.deselect().await;
+        res?;
+        deselect
+    }
+}
+
+impl Card {
+    /// Address argument of the read and write commands: standard capacity cards use byte
+    /// addresses, SDHC/SDXC cards use block addresses.
+    fn address(&self, block_idx: u32) -> u32 {
+        match self.card_type {
+            CardType::Sdhc => block_idx,
+            _ => block_idx * BLOCK_SIZE as u32,
+        }
+    }
+}
+
+fn check_r1<SPI, CS>(r1: u8) -> Result<(), SdSpiError<SPI, CS>> {
+    match r1 {
+        0 => Ok(()),
+        r1 if r1 & R1_COM_CRC_ERROR != 0 => Err(SdSpiError::Crc),
+        r1 => Err(SdSpiError::Card(r1)),
+    }
+}
+
+/// Number of 512-byte blocks of a card, from its CSD register.
+fn csd_num_blocks(csd: &[u8; 16]) -> u32 {
+    match csd[0] >> 6 {
+        // CSD version 2.0: C_SIZE[69:48], in units of 512 KiB.
+        1 => {
+            let c_size = ((csd[7] as u32 & 0x3F) << 16) | ((csd[8] as u32) << 8) | csd[9] as u32;
+            (c_size + 1) * 1024
+        }
+        // CSD version 1.0 and MMC: C_SIZE[73:62], C_SIZE_MULT[49:47], READ_BL_LEN[83:80].
+        _ => {
+            let read_bl_len = csd[5] as u32 & 0x0F;
+            let c_size = ((csd[6] as u32 & 0x03) << 10) | ((csd[7] as u32) << 2) | (csd[8] as u32 >> 6);
+            let c_size_mult = ((csd[9] as u32 & 0x03) << 1) | (csd[10] as u32 >> 7);
+            (c_size + 1) << (c_size_mult + 2 + read_bl_len - 9)
+        }
+    }
+}
+
+/// CRC7 of a command.
+fn crc7(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for &byte in data {
+        let mut byte = byte;
+        for _ in 0..8 {
+            crc <<= 1;
+            if (byte ^ crc) & 0x80 != 0 {
+                crc ^= 0x09;
+            }
+            byte <<= 1;
+        }
+    }
+    crc & 0x7F
+}
+
+/// CRC16-CCITT of a data block.
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = match crc & 0x8000 != 0 {
+                true => (crc << 1) ^ 0x1021,
+                false => crc << 1,
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc7() {
+        // CMD0, CMD8 and CMD17 with their well known CRC bytes.
+        assert_eq!(crc7(&[0x40, 0x00, 0x00, 0x00, 0x00]) << 1 | 1, 0x95);
+        assert_eq!(crc7(&[0x48, 0x00, 0x00, 0x01, 0xAA]) << 1 | 1, 0x87);
+        assert_eq!(crc7(&[0x51, 0x00, 0x00, 0x00, 0x00]) << 1 | 1, 0x55);
+    }
+
+    #[test]
+    fn test_crc16() {
+        assert_eq!(crc16(&[0xFF; BLOCK_SIZE]), 0x7FA1);
+    }
+
+    #[test]
+    fn test_csd_num_blocks() {
+        // SDHC card, 7.8 GB
+        let csd = [
+            0x40, 0x0E, 0x00, 0x32, 0x5B, 0x59, 0x00, 0x00, 0x3A, 0x0D, 0x7F, 0x80, 0x0A, 0x40, 0x00, 0x8D,
+        ];
+        assert_eq!(csd_num_blocks(&csd), 15_218_688);
+
+        // Standard capacity card, 1 GB
+        let csd = [
+            0x00, 0x26, 0x00, 0x32, 0x5F, 0x59, 0x83, 0xC8, 0xBE, 0xFB, 0xCF, 0xFF, 0x92, 0x40, 0x40, 0xC3,
+        ];
+        assert_eq!(csd_num_blocks(&csd), 1_984_000);
+    }
+}
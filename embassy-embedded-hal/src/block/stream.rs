@@ -0,0 +1,226 @@
+use embedded_io_async::{ErrorKind, ErrorType, Read, Seek, SeekFrom, Write};
+
+use super::{Block, BlockDevice, BLOCK_SIZE};
+
+/// Error returned by [`BlockStream`].
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BlockStreamError<E> {
+    /// An operation on the block device failed.
+    Device(E),
+    /// Seek before the start of the stream.
+    InvalidSeek,
+}
+
+impl<E: core::fmt::Debug> embedded_io_async::Error for BlockStreamError<E> {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Device(_) => ErrorKind::Other,
+            Self::InvalidSeek => ErrorKind::InvalidInput,
+        }
+    }
+}
+
+/// Byte stream over a range of blocks of a block device.
+///
+/// This implements the `embedded-io-async` traits, for filesystems and other code working on
+/// bytes. One block is cached: writes are buffered until another block is accessed or the stream
+/// is flushed.
+pub struct BlockStream<D: BlockDevice> {
+    dev: D,
+    first_block: u32,
+    num_blocks: u32,
+    pos: u64,
+    cache: Block,
+    cached: Option<u32>,
+    dirty: bool,
+}
+
+impl<D: BlockDevice> BlockStream<D> {
+    /// Create a stream over the whole device.
+    pub async fn new(mut dev: D) -> Result<Self, D::Error> {
+        let num_blocks = dev.num_blocks().await?;
+        Ok(Self::new_range(dev, 0, num_blocks))
+    }
+
+    /// Create a stream over `num_blocks` blocks, starting at block `first_block`.
+    pub fn new_range(dev: D, first_block: u32, num_blocks: u32) -> Self {
+        Self {
+            dev,
+            first_block,
+            num_blocks,
+            pos: 0,
+            cache: Block::new(),
+            cached: None,
+            dirty: false,
+        }
+    }
+
+    /// Length of the stream, in bytes.
+    pub fn len(&self) -> u64 {
+        self.num_blocks as u64 * BLOCK_SIZE as u64
+    }
+
+    /// Whether the stream is empty.
+    pub fn is_empty(&self) -> bool {
+        self.num_blocks == 0
+    }
+
+    /// Flush the cached block and release the block device.
+    pub async fn release(mut self) -> Result<D, D::Error> {
+        self.write_back().await?;
+        Ok(self.dev)
+    }
+
+    async fn write_back(&mut self) -> Result<(), D::Error> {
+        if let (Some(idx), true) = (self.cached, self.dirty) {
+            self.dev
+                .write(self.first_block + idx, core::slice::from_ref(&self.cache))
+                .await?;
+            self.dirty = false;
+        }
+        Ok(())
+    }
+
+    /// Make block `idx` the cached block. If `load` is false the block is about to be
+    /// overwritten entirely, so it is not read from the device.
+    async fn cache_block(&mut self, idx: u32, load: bool) -> Result<(), D::Error> {
+        if self.cached == Some(idx) {
+            return Ok(());
+        }
+        self.write_back().await?;
+        self.cached = None;
+        if load {
+            self.dev
+                .read(self.first_block + idx, core::slice::from_mut(&mut self.cache))
+                .await?;
+        }
+        self.cached = Some(idx);
+        Ok(())
+    }
+}
+
+impl<D: BlockDevice> ErrorType for BlockStream<D> {
+    type Error = BlockStreamError<D::Error>;
+}
+
+impl<D: BlockDevice> Read for BlockStream<D> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() || self.pos >= self.len() {
+            return Ok(0);
+        }
+
+        let idx = (self.pos / BLOCK_SIZE as u64) as u32;
+        let start = (self.pos % BLOCK_SIZE as u64) as usize;
+        let n = buf.len().min(BLOCK_SIZE - start);
+
+        self.cache_block(idx, true).await.map_err(BlockStreamError::Device)?;
+        buf[..n].copy_from_slice(&self.cache[start..start + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<D: BlockDevice> Write for BlockStream<D> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() || self.pos >= self.len() {
+            return Ok(0);
+        }
+
+        let idx = (self.pos / BLOCK_SIZE as u64) as u32;
+        let start = (self.pos % BLOCK_SIZE as u64) as usize;
+        let n = buf.len().min(BLOCK_SIZE - start);
+
+        self.cache_block(idx, n != BLOCK_SIZE)
+            .await
+            .map_err(BlockStreamError::Device)?;
+        self.cache[start..start + n].copy_from_slice(&buf[..n]);
+        self.dirty = true;
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.write_back().await.map_err(BlockStreamError::Device)
+    }
+}
+
+impl<D: BlockDevice> Seek for BlockStream<D> {
+    async fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        let new = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len().checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+        self.pos = new.ok_or(BlockStreamError::InvalidSeek)?;
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use core::convert::Infallible;
+
+    use super::*;
+
+    struct MemBlockDevice {
+        blocks: Vec<Block>,
+        writes: usize,
+    }
+
+    impl BlockDevice for MemBlockDevice {
+        type Error = Infallible;
+
+        async fn num_blocks(&mut self) -> Result<u32, Infallible> {
+            Ok(self.blocks.len() as u32)
+        }
+
+        async fn read(&mut self, block_idx: u32, blocks: &mut [Block]) -> Result<(), Infallible> {
+            let start = block_idx as usize;
+            blocks.clone_from_slice(&self.blocks[start..start + blocks.len()]);
+            Ok(())
+        }
+
+        async fn write(&mut self, block_idx: u32, blocks: &[Block]) -> Result<(), Infallible> {
+            let start = block_idx as usize;
+            self.blocks[start..start + blocks.len()].clone_from_slice(blocks);
+            self.writes += 1;
+            Ok(())
+        }
+    }
+
+    #[futures_test::test]
+    async fn write_and_read_across_blocks() {
+        let dev = MemBlockDevice {
+            blocks: vec![Block::new(); 4],
+            writes: 0,
+        };
+        let mut stream = BlockStream::new_range(dev, 1, 2);
+
+        stream.seek(SeekFrom::Start(500)).await.unwrap();
+        stream.write_all(&[0xAA; 20]).await.unwrap();
+        stream.flush().await.unwrap();
+
+        stream.seek(SeekFrom::Current(-20)).await.unwrap();
+        let mut buf = [0; 20];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(buf, [0xAA; 20]);
+
+        // Writes past the end are truncated.
+        stream.seek(SeekFrom::End(-2)).await.unwrap();
+        assert_eq!(stream.write(&[0x55; 4]).await.unwrap(), 2);
+        assert_eq!(stream.write(&[0x55; 4]).await.unwrap(), 0);
+
+        let dev = stream.release().await.unwrap();
+        assert_eq!(dev.writes, 3);
+        assert_eq!(dev.blocks[0].0, [0; BLOCK_SIZE]);
+        assert_eq!(dev.blocks[1][500..], [0xAA; 12]);
+        assert_eq!(dev.blocks[2][..8], [0xAA; 8]);
+        assert_eq!(dev.blocks[2][510..], [0x55; 2]);
+        assert_eq!(dev.blocks[3].0, [0; BLOCK_SIZE]);
+    }
+}
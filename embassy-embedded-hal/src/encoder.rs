@@ -0,0 +1,229 @@
+//! Rotary encoder decoding in software.
+//!
+//! [`RotaryEncoder`] decodes the quadrature signals of an incremental rotary encoder from two
+//! input pins, waiting for their edges with the [`Wait`] trait. It is meant for the cases where
+//! the hardware decoders (timer encoder mode on STM32, QDEC on nRF) are unavailable, or the pins
+//! don't route to them. It is suited to manually operated knobs, not to high speed motor encoders.
+
+use embassy_futures::select::{select, Either};
+use embedded_hal_1::digital::InputPin;
+use embedded_hal_async::digital::Wait;
+
+/// Number of quadrature states between two detents of the encoder.
+///
+/// See the datasheet of the encoder: it is usually given as the number of pulses per revolution
+/// against the number of detents per revolution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Detent {
+    /// A full quadrature cycle (4 edges) per detent, both signals are at the same level in every detent.
+    /// This is the most common type of encoders.
+    Full,
+    /// Half a quadrature cycle (2 edges) per detent, the signals alternate between both low and both
+    /// high in the detents.
+    Half,
+    /// A quarter quadrature cycle (1 edge) per detent, or no detents: every edge is a step.
+    Quarter,
+}
+
+/// Direction of a step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Direction {
+    /// Signal A leads signal B.
+    Clockwise,
+    /// Signal B leads signal A.
+    CounterClockwise,
+}
+
+/// Quadrature decoder state machine.
+///
+/// Invalid transitions, where both signals changed at once, are ignored, and the bounces of the
+/// contacts cancel out, since a step is only reported when the encoder settles in a detent.
+#[derive(Debug, Clone, Copy)]
+struct Decoder {
+    detent: Detent,
+    /// Index of the state in the quadrature sequence.
+    state: u8,
+    /// State of the detent the encoder started from.
+    rest: u8,
+    /// Quarter steps since the last detent.
+    quarters: i8,
+}
+
+impl Decoder {
+    fn new(detent: Detent, a: bool, b: bool) -> Self {
+        let state = Self::index(a, b);
+        Self {
+            detent,
+            state,
+            rest: state,
+            quarters: 0,
+        }
+    }
+
+    /// Index in the sequence 00, 10, 11, 01 of the signals (A, B) when turning clockwise.
+    fn index(a: bool, b: bool) -> u8 {
+        match (a, b) {
+            (false, false) => 0,
+            (true, false) => 1,
+            (true, true) => 2,
+            (false, true) => 3,
+        }
+    }
+
+    fn is_detent(&self, state: u8) -> bool {
+        match self.detent {
+            Detent::Full => state == self.rest,
+            Detent::Half => state % 2 == self.rest % 2,
+            Detent::Quarter => true,
+        }
+    }
+
+    /// Return whether the levels differ from the decoded state.
+    fn changed(&self, a: bool, b: bool) -> bool {
+        Self::index(a, b) != self.state
+    }
+
+    /// Update the state with the levels of the signals, returning the step, if any.
+    fn update(&mut self, a: bool, b: bool) -> Option<Direction> {
+        let state = Self::index(a, b);
+        match state.wrapping_sub(self.state) % 4 {
+            1 => self.quarters += 1,
+            3 => self.quarters -= 1,
+            _ => {}
+        }
+        self.state = state;
+
+        if !self.is_detent(state) || self.quarters == 0 {
+            return None;
+        }
+
+        let direction = if self.quarters > 0 {
+            Direction::Clockwise
+        } else {
+            Direction::CounterClockwise
+        };
+        self.quarters = 0;
+        Some(direction)
+    }
+}
+
+/// Rotary encoder, decoded in software from the A and B signals.
+///
+/// The direction is reversed by swapping the pins.
+pub struct RotaryEncoder<A, B> {
+    a: A,
+    b: B,
+    decoder: Decoder,
+    position: i32,
+}
+
+impl<A, B> RotaryEncoder<A, B>
+where
+    A: InputPin + Wait,
+    B: InputPin + Wait<Error = A::Error>,
+{
+    /// Create a new rotary encoder at position 0.
+    ///
+    /// The encoder must be resting in a detent, as the current levels of the pins are taken as
+    /// the first detent.
+    pub fn new(mut a: A, mut b: B, detent: Detent) -> Result<Self, A::Error> {
+        let decoder = Decoder::new(detent, a.is_high()?, b.is_high()?);
+        Ok(Self {
+            a,
+            b,
+            decoder,
+            position: 0,
+        })
+    }
+
+    /// Wait for the next step, and update the position.
+    pub async fn wait_step(&mut self) -> Result<Direction, A::Error> {
+        loop {
+            let (a, b) = (self.a.is_high()?, self.b.is_high()?);
+
+            // An edge can happen between the read of the levels and the start of the wait, so only
+            // wait if the levels are still the decoded ones.
+            if !self.decoder.changed(a, b) {
+                match select(self.a.wait_for_any_edge(), self.b.wait_for_any_edge()).await {
+                    Either::First(res) => res?,
+                    Either::Second(res) => res?,
+                }
+                continue;
+            }
+
+            if let Some(direction) = self.decoder.update(a, b) {
+                self.position = match direction {
+                    Direction::Clockwise => self.position.wrapping_add(1),
+                    Direction::CounterClockwise => self.position.wrapping_sub(1),
+                };
+                return Ok(direction);
+            }
+        }
+    }
+
+    /// Get the position, in steps from position 0, positive clockwise.
+    ///
+    /// The position is only updated by [`wait_step`](Self::wait_step).
+    pub fn position(&self) -> i32 {
+        self.position
+    }
+
+    /// Set the position, in steps.
+    pub fn set_position(&mut self, position: i32) {
+        self.position = position;
+    }
+
+    /// Release the pins.
+    pub fn release(self) -> (A, B) {
+        (self.a, self.b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CW: [(bool, bool); 4] = [(true, false), (true, true), (false, true), (false, false)];
+
+    #[test]
+    fn full_detent() {
+        let mut decoder = Decoder::new(Detent::Full, false, false);
+        for (a, b) in &CW[..3] {
+            assert_eq!(decoder.update(*a, *b), None);
+        }
+        assert_eq!(decoder.update(false, false), Some(Direction::Clockwise));
+
+        for (a, b) in CW.iter().rev().skip(1) {
+            assert_eq!(decoder.update(*a, *b), None);
+        }
+        assert_eq!(decoder.update(false, false), Some(Direction::CounterClockwise));
+    }
+
+    #[test]
+    fn half_detent() {
+        let mut decoder = Decoder::new(Detent::Half, false, false);
+        assert_eq!(decoder.update(true, false), None);
+        assert_eq!(decoder.update(true, true), Some(Direction::Clockwise));
+        assert_eq!(decoder.update(false, true), None);
+        assert_eq!(decoder.update(false, false), Some(Direction::Clockwise));
+        assert_eq!(decoder.update(false, true), None);
+        assert_eq!(decoder.update(true, true), Some(Direction::CounterClockwise));
+    }
+
+    #[test]
+    fn bounces_cancel_out() {
+        let mut decoder = Decoder::new(Detent::Full, false, false);
+        assert_eq!(decoder.update(true, false), None);
+        assert_eq!(decoder.update(false, false), None);
+        assert_eq!(decoder.update(true, false), None);
+        assert_eq!(decoder.update(false, false), None);
+
+        let mut decoder = Decoder::new(Detent::Quarter, false, false);
+        assert_eq!(decoder.update(true, false), Some(Direction::Clockwise));
+        assert_eq!(decoder.update(false, false), Some(Direction::CounterClockwise));
+        // Both signals changed, the direction is unknown.
+        assert_eq!(decoder.update(true, true), None);
+    }
+}
@@ -0,0 +1,133 @@
+//! littlefs filesystem on NOR flash, using `littlefs2`.
+//!
+//! littlefs is power-loss safe and spreads the erase cycles over the flash, which makes it a good
+//! fit for configuration and log storage on bare flash.
+//!
+//! littlefs calls the flash driver synchronously, so it needs a blocking [`NorFlash`]. To share
+//! the filesystem between tasks, [`Littlefs`] guards it with an async mutex, and each operation
+//! done with [`Littlefs::with_fs`] holds the lock only while it runs.
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::mutex::Mutex;
+use embedded_storage::nor_flash::NorFlash;
+use generic_array::ArrayLength;
+use littlefs2::driver::Storage;
+use littlefs2::fs::{Allocation, Filesystem};
+use littlefs2::io;
+use typenum::{Const, ToUInt, U};
+
+/// littlefs storage on a NOR flash.
+///
+/// The littlefs block is the flash erase size. The filesystem size and the buffers are set with the
+/// const generics:
+/// - `BLOCK_COUNT`: number of erase blocks used, starting at the beginning of the flash.
+/// - `CACHE`: size of the read, program and per-file caches, in bytes. It must be a multiple of
+///   the read and write sizes of the flash, and a divisor of the erase size.
+/// - `LOOKAHEAD`: size of the lookahead buffer, in multiples of 8 bytes. Each byte tracks the
+///   allocation of 8 blocks.
+pub struct LfsStorage<F, const BLOCK_COUNT: usize, const CACHE: usize = 256, const LOOKAHEAD: usize = 1> {
+    flash: F,
+}
+
+impl<F: NorFlash, const BLOCK_COUNT: usize, const CACHE: usize, const LOOKAHEAD: usize>
+    LfsStorage<F, BLOCK_COUNT, CACHE, LOOKAHEAD>
+{
+    /// Create a littlefs storage on `flash`.
+    ///
+    /// Panics if the flash is smaller than `BLOCK_COUNT` erase blocks.
+    pub fn new(flash: F) -> Self {
+        assert!(flash.capacity() >= BLOCK_COUNT * F::ERASE_SIZE);
+        Self { flash }
+    }
+
+    /// Release the flash.
+    pub fn release(self) -> F {
+        self.flash
+    }
+}
+
+impl<F, const BLOCK_COUNT: usize, const CACHE: usize, const LOOKAHEAD: usize> Storage
+    for LfsStorage<F, BLOCK_COUNT, CACHE, LOOKAHEAD>
+where
+    F: NorFlash,
+    Const<CACHE>: ToUInt,
+    Const<LOOKAHEAD>: ToUInt,
+    U<CACHE>: ArrayLength<u8>,
+    U<LOOKAHEAD>: ArrayLength<u64>,
+{
+    const READ_SIZE: usize = F::READ_SIZE;
+    const WRITE_SIZE: usize = F::WRITE_SIZE;
+    const BLOCK_SIZE: usize = F::ERASE_SIZE;
+    const BLOCK_COUNT: usize = BLOCK_COUNT;
+    const BLOCK_CYCLES: isize = 500;
+
+    type CACHE_SIZE = U<CACHE>;
+    type LOOKAHEAD_SIZE = U<LOOKAHEAD>;
+
+    fn read(&mut self, off: usize, buf: &mut [u8]) -> io::Result<usize> {
+        self.flash.read(off as u32, buf).map_err(|_| io::Error::Io)?;
+        Ok(buf.len())
+    }
+
+    fn write(&mut self, off: usize, data: &[u8]) -> io::Result<usize> {
+        self.flash.write(off as u32, data).map_err(|_| io::Error::Io)?;
+        Ok(data.len())
+    }
+
+    fn erase(&mut self, off: usize, len: usize) -> io::Result<usize> {
+        self.flash
+            .erase(off as u32, (off + len) as u32)
+            .map_err(|_| io::Error::Io)?;
+        Ok(len)
+    }
+}
+
+struct Inner<S: Storage> {
+    storage: S,
+    alloc: Allocation<S>,
+}
+
+/// littlefs filesystem shared through an async mutex.
+pub struct Littlefs<M: RawMutex, S: Storage> {
+    inner: Mutex<M, Inner<S>>,
+}
+
+impl<M: RawMutex, S: Storage> Littlefs<M, S> {
+    /// Create a filesystem on `storage`.
+    ///
+    /// Use [`is_mountable`](Self::is_mountable) and [`format`](Self::format) the first time.
+    pub fn new(storage: S) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                storage,
+                alloc: Filesystem::allocate(),
+            }),
+        }
+    }
+
+    /// Check whether the storage holds a valid filesystem.
+    pub async fn is_mountable(&self) -> bool {
+        let mut inner = self.inner.lock().await;
+        Filesystem::is_mountable(&mut inner.storage)
+    }
+
+    /// Format the storage, erasing all the files.
+    pub async fn format(&self) -> io::Result<()> {
+        let mut inner = self.inner.lock().await;
+        Filesystem::format(&mut inner.storage)
+    }
+
+    /// Mount the filesystem and run `f` on it, with exclusive access.
+    ///
+    /// Files opened by `f` are closed when it returns, so everything is persisted.
+    pub async fn with_fs<R>(&self, f: impl FnOnce(&Filesystem<'_, S>) -> io::Result<R>) -> io::Result<R> {
+        let mut inner = self.inner.lock().await;
+        let Inner { storage, alloc } = &mut *inner;
+        let fs = Filesystem::mount(alloc, storage)?;
+        f(&fs)
+    }
+
+    /// Release the storage.
+    pub fn release(self) -> S {
+        self.inner.into_inner().storage
+    }
+}
@@ -1,6 +1,8 @@
 //! Utilities related to flash.
 
 mod concat_flash;
+#[cfg(feature = "littlefs2")]
+pub mod littlefs;
 #[cfg(test)]
 pub(crate) mod mem_flash;
 pub mod partition;
@@ -0,0 +1,164 @@
+//! Line framing on top of `embedded-io-async` buffered readers.
+//!
+//! [`BufReadExt`] is implemented for every [`BufRead`], so all the buffered UARTs of the HALs
+//! get `read_until` and `read_line`, with an optional inter-character timeout when the `time`
+//! feature is enabled. This is the usual framing of AT-command modems.
+#[cfg(feature = "time")]
+use embassy_time::{with_timeout, Duration};
+use embedded_io_async::BufRead;
+
+/// Error returned by [`BufReadExt::read_until`] and [`BufReadExt::read_line`].
+///
+/// The bytes read before the error are left in the buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ReadUntilError<E> {
+    /// Error of the underlying reader.
+    Read(E),
+    /// The buffer is full and the delimiter was not found.
+    BufferFull,
+    /// The reader reached end of file after the given number of bytes, without the delimiter.
+    Eof(usize),
+    /// No byte was received during the timeout, after the given number of bytes.
+    Timeout(usize),
+}
+
+/// Delimiter-based reads for buffered readers.
+pub trait BufReadExt: BufRead {
+    /// Read bytes into `buf` until `delim` is found, returning the number of bytes read including
+    /// the delimiter.
+    ///
+    /// Bytes after the delimiter are kept in the reader for the next call. Dropping the future
+    /// does not lose bytes from the reader, but the ones already copied to `buf`.
+    async fn read_until(&mut self, delim: u8, buf: &mut [u8]) -> Result<usize, ReadUntilError<Self::Error>> {
+        read_until(self, delim, buf, None).await
+    }
+
+    /// Read a line terminated by `\n` into `buf`, returning its length including the terminator.
+    ///
+    /// A `\r` before the `\n` is kept as well.
+    async fn read_line(&mut self, buf: &mut [u8]) -> Result<usize, ReadUntilError<Self::Error>> {
+        read_until(self, b'\n', buf, None).await
+    }
+
+    /// Like [`read_until`](Self::read_until), but fails with [`ReadUntilError::Timeout`] when no
+    /// byte is received for `timeout`.
+    ///
+    /// The timeout restarts with each received chunk, so it bounds the gap between characters
+    /// rather than the length of the whole read.
+    #[cfg(feature = "time")]
+    async fn read_until_with_timeout(
+        &mut self,
+        delim: u8,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<usize, ReadUntilError<Self::Error>> {
+        read_until(self, delim, buf, Some(timeout)).await
+    }
+
+    /// Like [`read_line`](Self::read_line), with an inter-character timeout.
+    #[cfg(feature = "time")]
+    async fn read_line_with_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<usize, ReadUntilError<Self::Error>> {
+        read_until(self, b'\n', buf, Some(timeout)).await
+    }
+}
+
+impl<R: BufRead + ?Sized> BufReadExt for R {}
+
+/// Inter-character timeout, only available with the `time` feature.
+#[cfg(feature = "time")]
+type Timeout = Option<Duration>;
+#[cfg(not(feature = "time"))]
+type Timeout = Option<core::convert::Infallible>;
+
+async fn read_until<R: BufRead + ?Sized>(
+    reader: &mut R,
+    delim: u8,
+    buf: &mut [u8],
+    timeout: Timeout,
+) -> Result<usize, ReadUntilError<R::Error>> {
+    let mut n = 0;
+    loop {
+        if n == buf.len() {
+            return Err(ReadUntilError::BufferFull);
+        }
+
+        let available = match timeout {
+            #[cfg(feature = "time")]
+            Some(timeout) => with_timeout(timeout, reader.fill_buf())
+                .await
+                .map_err(|_| ReadUntilError::Timeout(n))?,
+            #[cfg(not(feature = "time"))]
+            Some(never) => match never {},
+            None => reader.fill_buf().await,
+        }
+        .map_err(ReadUntilError::Read)?;
+
+        if available.is_empty() {
+            return Err(ReadUntilError::Eof(n));
+        }
+
+        let (found, len) = match available.iter().position(|b| *b == delim) {
+            Some(i) => (true, i + 1),
+            None => (false, available.len()),
+        };
+        let copied = len.min(buf.len() - n);
+        buf[n..n + copied].copy_from_slice(&available[..copied]);
+        reader.consume(copied);
+        n += copied;
+
+        if found && copied == len {
+            return Ok(n);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[futures_test::test]
+    async fn reads_lines() {
+        let mut reader: &[u8] = b"AT\r\nOK\r\n";
+        let mut buf = [0; 16];
+
+        let n = reader.read_line(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"AT\r\n");
+        let n = reader.read_line(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"OK\r\n");
+        assert_eq!(reader.read_line(&mut buf).await, Err(ReadUntilError::Eof(0)));
+    }
+
+    #[futures_test::test]
+    async fn reads_until_delimiter() {
+        let mut reader: &[u8] = b"+CSQ: 20,99>rest";
+        let mut buf = [0; 16];
+
+        let n = reader.read_until(b'>', &mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"+CSQ: 20,99>");
+        assert_eq!(reader, b"rest");
+    }
+
+    #[futures_test::test]
+    async fn reports_full_buffer() {
+        let mut reader: &[u8] = b"0123456789\n";
+        let mut buf = [0; 4];
+
+        assert_eq!(reader.read_line(&mut buf).await, Err(ReadUntilError::BufferFull));
+        assert_eq!(&buf, b"0123");
+        assert_eq!(reader, b"456789\n");
+    }
+
+    #[futures_test::test]
+    async fn reports_eof_without_delimiter() {
+        let mut reader: &[u8] = b"partial";
+        let mut buf = [0; 16];
+
+        assert_eq!(reader.read_line(&mut buf).await, Err(ReadUntilError::Eof(7)));
+        assert_eq!(&buf[..7], b"partial");
+    }
+}
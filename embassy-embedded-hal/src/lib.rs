@@ -4,8 +4,15 @@
 #![doc = include_str!("../README.md")]
 
 pub mod adapter;
+pub mod adc;
+pub mod block;
+pub mod encoder;
 pub mod flash;
+pub mod io;
 pub mod shared_bus;
+#[cfg(feature = "time")]
+pub mod supervisor;
+pub mod wifi;
 
 /// Set the configuration of a peripheral driver.
 ///
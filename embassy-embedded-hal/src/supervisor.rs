@@ -0,0 +1,268 @@
+//! Die temperature and supply voltage supervisor.
+//!
+//! [`Supervisor::run`] periodically samples the [`InternalSensors`] of the chip, and publishes
+//! the readings and the threshold [`Alarms`] through [`Watch`]es, so any number of tasks can
+//! follow them, for example to derate a motor or a radio when the chip gets too hot.
+
+use core::convert::Infallible;
+use core::fmt::Debug;
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::watch::{Receiver, Watch};
+use embassy_time::{Duration, Ticker};
+
+/// Internal temperature and supply voltage sensors of a chip.
+///
+/// Implemented on top of the HAL drivers, for example the ADC temperature and VREFINT channels
+/// on STM32, or the TEMP peripheral and the VDD input of the SAADC on nRF.
+pub trait InternalSensors {
+    /// Error type.
+    type Error: Debug;
+
+    /// Measure the die temperature, in millidegrees Celsius.
+    async fn read_temperature_mc(&mut self) -> Result<i32, Self::Error>;
+
+    /// Measure the supply voltage, in millivolts.
+    async fn read_vdd_mv(&mut self) -> Result<u32, Self::Error>;
+}
+
+impl<T: InternalSensors + ?Sized> InternalSensors for &mut T {
+    type Error = T::Error;
+
+    async fn read_temperature_mc(&mut self) -> Result<i32, Self::Error> {
+        T::read_temperature_mc(self).await
+    }
+
+    async fn read_vdd_mv(&mut self) -> Result<u32, Self::Error> {
+        T::read_vdd_mv(self).await
+    }
+}
+
+/// Reading of the internal sensors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Reading {
+    /// Die temperature, in millidegrees Celsius.
+    pub temperature_mc: i32,
+    /// Supply voltage, in millivolts.
+    pub vdd_mv: u32,
+}
+
+/// Active threshold alarms.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Alarms {
+    /// The temperature is above [`Config::over_temperature_mc`].
+    pub over_temperature: bool,
+    /// The temperature is below [`Config::under_temperature_mc`].
+    pub under_temperature: bool,
+    /// The supply voltage is above [`Config::overvoltage_mv`].
+    pub overvoltage: bool,
+    /// The supply voltage is below [`Config::undervoltage_mv`].
+    pub undervoltage: bool,
+}
+
+impl Alarms {
+    /// Return whether any alarm is active.
+    pub fn any(&self) -> bool {
+        self.over_temperature || self.under_temperature || self.overvoltage || self.undervoltage
+    }
+}
+
+/// Supervisor configuration.
+///
+/// An alarm is raised when its threshold is crossed, and cleared once the value is back inside
+/// the threshold by the hysteresis, so noisy readings around a threshold don't toggle it.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// Sampling period.
+    pub period: Duration,
+    /// Upper temperature threshold, in millidegrees Celsius.
+    pub over_temperature_mc: Option<i32>,
+    /// Lower temperature threshold, in millidegrees Celsius.
+    pub under_temperature_mc: Option<i32>,
+    /// Hysteresis of the temperature alarms, in millidegrees Celsius.
+    pub temperature_hysteresis_mc: i32,
+    /// Upper supply voltage threshold, in millivolts.
+    pub overvoltage_mv: Option<u32>,
+    /// Lower supply voltage threshold, in millivolts.
+    pub undervoltage_mv: Option<u32>,
+    /// Hysteresis of the supply voltage alarms, in millivolts.
+    pub voltage_hysteresis_mv: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            period: Duration::from_secs(1),
+            over_temperature_mc: Some(85_000),
+            under_temperature_mc: None,
+            temperature_hysteresis_mc: 2_000,
+            overvoltage_mv: None,
+            undervoltage_mv: None,
+            voltage_hysteresis_mv: 50,
+        }
+    }
+}
+
+impl Config {
+    fn alarms(&self, reading: &Reading, active: Alarms) -> Alarms {
+        let t = reading.temperature_mc as i64;
+        let t_hyst = self.temperature_hysteresis_mc as i64;
+        let v = reading.vdd_mv as i64;
+        let v_hyst = self.voltage_hysteresis_mv as i64;
+
+        // The lower thresholds are checked as upper thresholds of the negated values.
+        let check = |limit: Option<i64>, value: i64, hysteresis: i64, active: bool| {
+            limit.map_or(false, |limit| exceeds(value, limit, hysteresis, active))
+        };
+
+        Alarms {
+            over_temperature: check(
+                self.over_temperature_mc.map(|l| l as i64),
+                t,
+                t_hyst,
+                active.over_temperature,
+            ),
+            under_temperature: check(
+                self.under_temperature_mc.map(|l| -(l as i64)),
+                -t,
+                t_hyst,
+                active.under_temperature,
+            ),
+            overvoltage: check(self.overvoltage_mv.map(|l| l as i64), v, v_hyst, active.overvoltage),
+            undervoltage: check(
+                self.undervoltage_mv.map(|l| -(l as i64)),
+                -v,
+                v_hyst,
+                active.undervoltage,
+            ),
+        }
+    }
+}
+
+/// Return whether `value` is above `limit`, or above `limit - hysteresis` if it already was.
+fn exceeds(value: i64, limit: i64, hysteresis: i64, active: bool) -> bool {
+    if active {
+        value > limit - hysteresis
+    } else {
+        value > limit
+    }
+}
+
+/// Die temperature and supply voltage supervisor, followed by up to `N` receivers of each kind.
+///
+/// Supervisors are generally declared as `static`s, so the task running the supervisor and the
+/// tasks following it can all borrow it.
+pub struct Supervisor<M: RawMutex, const N: usize> {
+    readings: Watch<M, Reading, N>,
+    alarms: Watch<M, Alarms, N>,
+}
+
+impl<M: RawMutex, const N: usize> Supervisor<M, N> {
+    /// Create a new supervisor, without readings yet.
+    pub const fn new() -> Self {
+        Self {
+            readings: Watch::new(),
+            alarms: Watch::new(),
+        }
+    }
+
+    /// Return the latest reading, if any.
+    pub fn reading(&self) -> Option<Reading> {
+        self.readings.try_get()
+    }
+
+    /// Return the active alarms.
+    pub fn alarms(&self) -> Alarms {
+        self.alarms.try_get().unwrap_or_default()
+    }
+
+    /// Follow the readings, sent after every sample.
+    ///
+    /// Returns `None` if there are already `N` receivers.
+    pub fn readings(&self) -> Option<Receiver<'_, M, Reading, N>> {
+        self.readings.receiver()
+    }
+
+    /// Follow the alarms, sent after the first sample, then whenever an alarm is raised or cleared.
+    ///
+    /// Returns `None` if there are already `N` receivers.
+    pub fn alarm_changes(&self) -> Option<Receiver<'_, M, Alarms, N>> {
+        self.alarms.receiver()
+    }
+
+    /// Sample the sensors every [`Config::period`], forever.
+    ///
+    /// Returns the error of the sensors if a measurement fails.
+    pub async fn run<S: InternalSensors>(&self, mut sensors: S, config: Config) -> Result<Infallible, S::Error> {
+        let mut ticker = Ticker::every(config.period);
+        let mut active = None;
+
+        loop {
+            let reading = Reading {
+                temperature_mc: sensors.read_temperature_mc().await?,
+                vdd_mv: sensors.read_vdd_mv().await?,
+            };
+            self.readings.send(reading);
+
+            let alarms = config.alarms(&reading, active.unwrap_or_default());
+            if active != Some(alarms) {
+                self.alarms.send(alarms);
+                active = Some(alarms);
+            }
+
+            ticker.next().await;
+        }
+    }
+}
+
+impl<M: RawMutex, const N: usize> Default for Supervisor<M, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(temperature_mc: i32, vdd_mv: u32) -> Reading {
+        Reading { temperature_mc, vdd_mv }
+    }
+
+    #[test]
+    fn temperature_alarm_hysteresis() {
+        let config = Config::default();
+
+        let alarms = config.alarms(&reading(86_000, 3300), Alarms::default());
+        assert!(alarms.over_temperature);
+        assert!(!alarms.undervoltage);
+
+        // Still raised inside the hysteresis.
+        let alarms = config.alarms(&reading(84_000, 3300), alarms);
+        assert!(alarms.over_temperature);
+
+        let alarms = config.alarms(&reading(82_500, 3300), alarms);
+        assert!(!alarms.any());
+
+        let alarms = config.alarms(&reading(84_000, 3300), alarms);
+        assert!(!alarms.any());
+    }
+
+    #[test]
+    fn undervoltage_alarm() {
+        let mut config = Config::default();
+        config.undervoltage_mv = Some(2000);
+
+        let alarms = config.alarms(&reading(25_000, 1950), Alarms::default());
+        assert!(alarms.undervoltage);
+
+        let alarms = config.alarms(&reading(25_000, 2030), alarms);
+        assert!(alarms.undervoltage);
+
+        let alarms = config.alarms(&reading(25_000, 2060), alarms);
+        assert!(!alarms.undervoltage);
+    }
+}
@@ -0,0 +1,274 @@
+//! Bus plumbing for external WiFi modules.
+//!
+//! WiFi modules attached over SPI or SDIO all talk through the same kind of bus transactions: a
+//! command header, followed by a payload written to or read from the module, with an IRQ pin
+//! telling when the module has something to say. The [`ModuleBus`] trait captures these
+//! transactions, so a module driver can live out of tree and run on any HAL, and
+//! [`SpiModuleBus`] implements it over any async [`SpiDevice`].
+//!
+//! The driver then exposes the module to `embassy-net` through `embassy-net-driver-channel`: its
+//! runner moves the packets between the channel and the bus, like the in-tree `cyw43` and
+//! `embassy-net-esp-hosted` drivers do.
+//!
+//! The payloads are often larger than what the bus transfers at once, firmware blobs in
+//! particular: [`write_chunked`], [`read_chunked`] and [`upload_firmware`] split them in
+//! transactions of at most [`ModuleBus::max_transfer`] bytes.
+
+use core::fmt::Debug;
+
+use embassy_futures::yield_now;
+use embedded_hal_1::spi::Operation;
+use embedded_hal_async::digital::Wait;
+use embedded_hal_async::spi::SpiDevice;
+
+/// Bus to an external WiFi module.
+pub trait ModuleBus {
+    /// Bus error.
+    type Error: Debug;
+
+    /// Largest payload of a single transaction, for example limited by the DMA transfer length or
+    /// the buffer of the module.
+    fn max_transfer(&self) -> usize;
+
+    /// Write `header`, then `data`, in a single transaction.
+    async fn write(&mut self, header: &[u8], data: &[u8]) -> Result<(), Self::Error>;
+
+    /// Write `header`, then read `data`, in a single transaction.
+    async fn read(&mut self, header: &[u8], data: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Wait for the module to signal an event, typically on its IRQ pin.
+    ///
+    /// The default implementation always reports an event, resulting in active polling of the
+    /// module.
+    async fn wait_for_irq(&mut self) -> Result<(), Self::Error> {
+        yield_now().await;
+        Ok(())
+    }
+}
+
+/// Active level of the IRQ pin of a module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum IrqLevel {
+    /// The module drives the pin high to signal an event.
+    High,
+    /// The module drives the pin low to signal an event.
+    Low,
+}
+
+/// Error of a [`SpiModuleBus`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SpiModuleBusError<S, I> {
+    /// Error of the SPI device.
+    Spi(S),
+    /// Error of the IRQ pin.
+    Irq(I),
+}
+
+/// [`ModuleBus`] over an SPI device, with an IRQ pin.
+///
+/// The chip select is held by the [`SpiDevice`] for the whole transaction, header and payload.
+pub struct SpiModuleBus<SPI, IRQ> {
+    spi: SPI,
+    irq: IRQ,
+    irq_level: IrqLevel,
+    max_transfer: usize,
+}
+
+impl<SPI, IRQ> SpiModuleBus<SPI, IRQ>
+where
+    SPI: SpiDevice,
+    IRQ: Wait,
+{
+    /// Create a new SPI module bus, transferring at most `max_transfer` payload bytes per transaction.
+    pub fn new(spi: SPI, irq: IRQ, irq_level: IrqLevel, max_transfer: usize) -> Self {
+        assert!(max_transfer > 0);
+        Self {
+            spi,
+            irq,
+            irq_level,
+            max_transfer,
+        }
+    }
+
+    /// Release the SPI device and the IRQ pin.
+    pub fn release(self) -> (SPI, IRQ) {
+        (self.spi, self.irq)
+    }
+}
+
+impl<SPI, IRQ> ModuleBus for SpiModuleBus<SPI, IRQ>
+where
+    SPI: SpiDevice,
+    IRQ: Wait,
+{
+    type Error = SpiModuleBusError<SPI::Error, IRQ::Error>;
+
+    fn max_transfer(&self) -> usize {
+        self.max_transfer
+    }
+
+    async fn write(&mut self, header: &[u8], data: &[u8]) -> Result<(), Self::Error> {
+        self.spi
+            .transaction(&mut [Operation::Write(header), Operation::Write(data)])
+            .await
+            .map_err(SpiModuleBusError::Spi)
+    }
+
+    async fn read(&mut self, header: &[u8], data: &mut [u8]) -> Result<(), Self::Error> {
+        self.spi
+            .transaction(&mut [Operation::Write(header), Operation::Read(data)])
+            .await
+            .map_err(SpiModuleBusError::Spi)
+    }
+
+    async fn wait_for_irq(&mut self) -> Result<(), Self::Error> {
+        let res = match self.irq_level {
+            IrqLevel::High => self.irq.wait_for_high().await,
+            IrqLevel::Low => self.irq.wait_for_low().await,
+        };
+        res.map_err(SpiModuleBusError::Irq)
+    }
+}
+
+/// Write `data` in transactions of at most [`ModuleBus::max_transfer`] bytes.
+///
+/// Each transaction starts with the header built by `header` from the offset of the chunk in
+/// `data` and its length.
+pub async fn write_chunked<B: ModuleBus, const H: usize>(
+    bus: &mut B,
+    data: &[u8],
+    mut header: impl FnMut(usize, usize) -> [u8; H],
+) -> Result<(), B::Error> {
+    let max = bus.max_transfer();
+    for (i, chunk) in data.chunks(max).enumerate() {
+        bus.write(&header(i * max, chunk.len()), chunk).await?;
+    }
+    Ok(())
+}
+
+/// Read `data` in transactions of at most [`ModuleBus::max_transfer`] bytes.
+///
+/// Each transaction starts with the header built by `header` from the offset of the chunk in
+/// `data` and its length.
+pub async fn read_chunked<B: ModuleBus, const H: usize>(
+    bus: &mut B,
+    data: &mut [u8],
+    mut header: impl FnMut(usize, usize) -> [u8; H],
+) -> Result<(), B::Error> {
+    let max = bus.max_transfer();
+    for (i, chunk) in data.chunks_mut(max).enumerate() {
+        bus.read(&header(i * max, chunk.len()), chunk).await?;
+    }
+    Ok(())
+}
+
+/// Upload a firmware blob to the memory of the module, from `base_address`.
+///
+/// Each chunk is written with the header built by `header` from its address and length. With
+/// `handshake`, the upload waits for the module to signal on its IRQ pin that it is ready for the
+/// next chunk, as the modules writing the chunks to their flash do.
+pub async fn upload_firmware<B: ModuleBus, const H: usize>(
+    bus: &mut B,
+    firmware: &[u8],
+    base_address: u32,
+    handshake: bool,
+    mut header: impl FnMut(u32, usize) -> [u8; H],
+) -> Result<(), B::Error> {
+    let max = bus.max_transfer();
+    for (i, chunk) in firmware.chunks(max).enumerate() {
+        let address = base_address + (i * max) as u32;
+        bus.write(&header(address, chunk.len()), chunk).await?;
+        if handshake {
+            bus.wait_for_irq().await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use alloc::vec::Vec;
+    use core::convert::Infallible;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Bus {
+        // (header, data) of each write transaction
+        writes: Vec<(Vec<u8>, Vec<u8>)>,
+        irqs: usize,
+    }
+
+    impl ModuleBus for Bus {
+        type Error = Infallible;
+
+        fn max_transfer(&self) -> usize {
+            4
+        }
+
+        async fn write(&mut self, header: &[u8], data: &[u8]) -> Result<(), Infallible> {
+            self.writes.push((header.to_vec(), data.to_vec()));
+            Ok(())
+        }
+
+        async fn read(&mut self, header: &[u8], data: &mut [u8]) -> Result<(), Infallible> {
+            data.fill(header[0]);
+            Ok(())
+        }
+
+        async fn wait_for_irq(&mut self) -> Result<(), Infallible> {
+            self.irqs += 1;
+            Ok(())
+        }
+    }
+
+    #[futures_test::test]
+    async fn writes_chunks() {
+        let mut bus = Bus::default();
+        write_chunked(&mut bus, &[1, 2, 3, 4, 5, 6], |offset, len| [offset as u8, len as u8])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            bus.writes,
+            [
+                (alloc::vec![0, 4], alloc::vec![1, 2, 3, 4]),
+                (alloc::vec![4, 2], alloc::vec![5, 6]),
+            ]
+        );
+    }
+
+    #[futures_test::test]
+    async fn reads_chunks() {
+        let mut bus = Bus::default();
+        let mut data = [0; 6];
+        read_chunked(&mut bus, &mut data, |offset, _| [offset as u8])
+            .await
+            .unwrap();
+
+        assert_eq!(data, [0, 0, 0, 0, 4, 4]);
+    }
+
+    #[futures_test::test]
+    async fn uploads_firmware_with_handshake() {
+        let mut bus = Bus::default();
+        upload_firmware(&mut bus, &[0; 10], 0x1000, true, |address, _| address.to_be_bytes())
+            .await
+            .unwrap();
+
+        let addresses: Vec<_> = bus.writes.iter().map(|(header, _)| header.clone()).collect();
+        assert_eq!(
+            addresses,
+            [
+                alloc::vec![0, 0, 0x10, 0],
+                alloc::vec![0, 0, 0x10, 4],
+                alloc::vec![0, 0, 0x10, 8],
+            ]
+        );
+        assert_eq!(bus.irqs, 3);
+    }
+}
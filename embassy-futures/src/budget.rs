@@ -0,0 +1,249 @@
+//! Cooperative scheduling budget, for compute-heavy tasks.
+//!
+//! A task only returns to the executor when a future it awaits is pending. A compute-heavy task
+//! awaiting futures that are always ready, such as a channel that is never empty, then keeps the
+//! executor busy, and the other tasks wait for it to block.
+//!
+//! [`with_budget`] gives such a task a number of ready polls. The instrumented futures, which are
+//! the channels, pipes and signals of `embassy-sync` and [`consume_budget`], consume it at each
+//! await point, and once it is exhausted the next one yields instead of completing: the task is put
+//! at the back of the run queue, and its budget is refilled. The budget is refilled as well whenever
+//! the task is pending, since it then returns to the executor anyway. The budgeted tasks are
+//! scheduled round-robin with the other tasks, without yields sprinkled in their code.
+//!
+//! ```rust,ignore
+//! #[embassy_executor::task]
+//! async fn dsp(samples: Receiver<'static, CriticalSectionRawMutex, [i16; 64], 4>) {
+//!     with_budget(8, async {
+//!         loop {
+//!             let block = samples.receive().await;
+//!             process(block);
+//!         }
+//!     })
+//!     .await
+//! }
+//! ```
+//!
+//! The budget belongs to the task: an instrumented future only consumes it when it is polled with
+//! the waker of the budgeted task, so the tasks of other executors, interrupt executors and cores
+//! are not affected. Outside of [`with_budget`], the instrumented futures only pay for an atomic
+//! load. When budgets are nested, the innermost one is consumed.
+use core::cell::Cell;
+use core::future::{poll_fn, Future};
+use core::pin::Pin;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+use core::task::{Context, Poll, Waker};
+
+/// Budget of the budgeted future being polled, null if none.
+///
+/// It is only dereferenced in a critical section, in which the future that set it can't complete
+/// its poll, and then clear it.
+static CURRENT: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
+/// Budget of a future being polled, on the stack of [`Budgeted::poll`].
+struct State<'a> {
+    waker: &'a Waker,
+    remaining: &'a Cell<u32>,
+}
+
+/// Clears [`CURRENT`] when [`Budgeted::poll`] returns, or unwinds.
+struct ClearOnDrop(*mut ());
+
+impl Drop for ClearOnDrop {
+    fn drop(&mut self) {
+        critical_section::with(|_| {
+            if CURRENT.load(Ordering::Relaxed) == self.0 {
+                CURRENT.store(ptr::null_mut(), Ordering::Relaxed);
+            }
+        });
+    }
+}
+
+/// Run `fut` with a budget of `polls` ready polls of the instrumented futures between two yields.
+///
+/// A budget of 0 is clamped to 1: the task still makes progress, yielding at every other
+/// instrumented future.
+pub fn with_budget<F: Future>(polls: u32, fut: F) -> Budgeted<F> {
+    let polls = polls.max(1);
+    Budgeted {
+        fut,
+        polls,
+        remaining: Cell::new(polls),
+    }
+}
+
+/// Future for the [`with_budget`] function.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Budgeted<F> {
+    fut: F,
+    polls: u32,
+    remaining: Cell<u32>,
+}
+
+impl<F: Future> Future for Budgeted<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        // safety: `fut` is never moved out of `self`.
+        let this = unsafe { self.get_unchecked_mut() };
+        let state = State {
+            waker: cx.waker(),
+            remaining: &this.remaining,
+        };
+        let current = &state as *const State as *mut ();
+        CURRENT.store(current, Ordering::Relaxed);
+        let guard = ClearOnDrop(current);
+        let res = unsafe { Pin::new_unchecked(&mut this.fut) }.poll(cx);
+        drop(guard);
+
+        if res.is_pending() {
+            this.remaining.set(this.polls);
+        }
+        res
+    }
+}
+
+/// Consume one unit of the budget of the current task, if any.
+///
+/// Returns `Poll::Pending`, after waking the task, once the budget is exhausted. This is meant to
+/// be called first by the futures completing without waiting, so they yield.
+pub fn poll_budget(cx: &mut Context<'_>) -> Poll<()> {
+    if CURRENT.load(Ordering::Relaxed).is_null() {
+        return Poll::Ready(());
+    }
+
+    critical_section::with(|_| {
+        let current = CURRENT.load(Ordering::Relaxed) as *const State;
+        // safety: the budget is cleared before its future returns, which it can't do in the
+        // critical section.
+        let Some(state) = (unsafe { current.as_ref() }) else {
+            return Poll::Ready(());
+        };
+        if !state.waker.will_wake(cx.waker()) {
+            // Another task, e.g. of an interrupt executor.
+            return Poll::Ready(());
+        }
+        match state.remaining.get() {
+            0 => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+            n => {
+                state.remaining.set(n - 1);
+                Poll::Ready(())
+            }
+        }
+    })
+}
+
+/// Consume one unit of the budget of the current task, yielding once it is exhausted.
+///
+/// This can be called in loops that don't await any instrumented future. Outside of
+/// [`with_budget`], this completes immediately.
+pub async fn consume_budget() {
+    poll_fn(poll_budget).await
+}
+
+#[cfg(test)]
+mod tests {
+    use core::pin::pin;
+    use core::task::{RawWaker, RawWakerVTable};
+
+    use super::*;
+
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(|p| RawWaker::new(p, &VTABLE), |_| {}, |_| {}, |_| {});
+
+    /// Waker of the task `id`. The tests run in parallel, so each one uses its own tasks.
+    fn waker(id: usize) -> Waker {
+        unsafe { Waker::from_raw(RawWaker::new(id as *const (), &VTABLE)) }
+    }
+
+    fn poll<F: Future>(fut: Pin<&mut F>, id: usize) -> Poll<F::Output> {
+        fut.poll(&mut Context::from_waker(&waker(id)))
+    }
+
+    /// Poll `fut` to completion, counting the yields.
+    ///
+    /// The tests running in parallel would overwrite each other's budget, so they are serialized
+    /// by the critical section, which is reentrant.
+    fn count_yields(fut: impl Future<Output = ()>, id: usize) -> usize {
+        critical_section::with(|_| {
+            let mut fut = pin!(fut);
+            let mut yields = 0;
+            while poll(fut.as_mut(), id).is_pending() {
+                yields += 1;
+            }
+            yields
+        })
+    }
+
+    #[test]
+    fn yields_once_exhausted() {
+        let fut = with_budget(3, async {
+            for _ in 0..7 {
+                consume_budget().await;
+            }
+        });
+        assert_eq!(count_yields(fut, 1), 2);
+    }
+
+    #[test]
+    fn zero_is_clamped_to_one() {
+        let fut = with_budget(0, async {
+            for _ in 0..3 {
+                consume_budget().await;
+            }
+        });
+        assert_eq!(count_yields(fut, 2), 2);
+    }
+
+    #[test]
+    fn unbudgeted_never_yields() {
+        for _ in 0..100 {
+            assert_eq!(poll(pin!(consume_budget()), 3), Poll::Ready(()));
+        }
+    }
+
+    #[test]
+    fn other_tasks_are_not_budgeted() {
+        let fut = with_budget(1, async {
+            consume_budget().await;
+            // Polled by another task, e.g. joined from an interrupt executor.
+            for _ in 0..3 {
+                assert_eq!(poll(pin!(consume_budget()), 5), Poll::Ready(()));
+            }
+            consume_budget().await;
+        });
+        assert_eq!(count_yields(fut, 4), 1);
+    }
+
+    #[test]
+    fn pending_refills() {
+        let mut fut = pin!(with_budget(2, async {
+            consume_budget().await;
+            crate::yield_now().await;
+            consume_budget().await;
+            consume_budget().await;
+            consume_budget().await;
+        }));
+        critical_section::with(|_| {
+            // Pending in `yield_now`, which refills the budget.
+            assert!(poll(fut.as_mut(), 6).is_pending());
+            // Two ready polls, then a yield.
+            assert!(poll(fut.as_mut(), 6).is_pending());
+            assert!(poll(fut.as_mut(), 6).is_ready());
+        });
+    }
+
+    #[test]
+    fn not_consumed_after_completion() {
+        let fut = with_budget(1, async {
+            consume_budget().await;
+        });
+        assert_eq!(count_yields(fut, 7), 0);
+        for _ in 0..3 {
+            assert_eq!(poll(pin!(consume_budget()), 7), Poll::Ready(()));
+        }
+    }
+}
@@ -8,6 +8,7 @@ pub(crate) mod fmt;
 mod block_on;
 mod yield_now;
 
+pub mod budget;
 pub mod join;
 pub mod select;
 
@@ -0,0 +1,378 @@
+#![no_std]
+#![warn(missing_docs)]
+#![doc = include_str!("../README.md")]
+
+// must be first
+mod fmt;
+
+pub mod nmea;
+pub mod ubx;
+
+use embedded_io_async::{BufRead, Write};
+pub use nmea::{Date, FixQuality, Time};
+use nmea::{Gga, Rmc, Sentence};
+
+/// Maximum length of a received message, NMEA sentence or UBX frame.
+pub const MAX_MESSAGE_LEN: usize = 256;
+
+/// Error returned by the [`Gnss`] driver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// Reading from the serial port failed.
+    Read(E),
+    /// Writing to the serial port failed.
+    Write(E),
+    /// Reading from the serial port got EOF.
+    Eof,
+    /// The receiver rejected a UBX configuration message.
+    Nak,
+    /// The UBX message is longer than [`MAX_MESSAGE_LEN`].
+    PayloadTooLong,
+}
+
+/// Message received from the receiver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Message<'a> {
+    /// NMEA sentence, from `$` to the line terminator. The checksum is not checked yet, see
+    /// [`nmea::parse`].
+    Nmea(&'a [u8]),
+    /// UBX frame, with a valid checksum.
+    Ubx {
+        /// Class of the message.
+        class: u8,
+        /// Id of the message.
+        id: u8,
+        /// Payload of the message.
+        payload: &'a [u8],
+    },
+}
+
+/// Position fix.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Fix {
+    /// UTC time of the fix.
+    pub time: Time,
+    /// UTC date of the fix, from the RMC sentence of the same epoch.
+    pub date: Option<Date>,
+    /// Latitude in degrees, positive north.
+    pub latitude: f64,
+    /// Longitude in degrees, positive east.
+    pub longitude: f64,
+    /// Altitude above mean sea level, in meters.
+    pub altitude: Option<f32>,
+    /// Quality of the fix.
+    pub quality: FixQuality,
+    /// Number of satellites used.
+    pub satellites: u8,
+    /// Horizontal dilution of precision.
+    pub hdop: Option<f32>,
+    /// Speed over ground in knots, from the RMC sentence of the same epoch.
+    pub speed_knots: Option<f32>,
+    /// Course over ground in degrees, from the RMC sentence of the same epoch.
+    pub course: Option<f32>,
+}
+
+impl Fix {
+    fn new(gga: &Gga, rmc: Option<&Rmc>) -> Option<Self> {
+        if gga.quality == FixQuality::Invalid {
+            return None;
+        }
+        let rmc = rmc.filter(|rmc| rmc.time == gga.time);
+
+        Some(Self {
+            time: gga.time,
+            date: rmc.and_then(|rmc| rmc.date),
+            latitude: gga.latitude?,
+            longitude: gga.longitude?,
+            altitude: gga.altitude,
+            quality: gga.quality,
+            satellites: gga.satellites,
+            hdop: gga.hdop,
+            speed_knots: rmc.and_then(|rmc| rmc.speed_knots),
+            course: rmc.and_then(|rmc| rmc.course),
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum FramerState {
+    Idle,
+    Nmea,
+    UbxSync,
+    UbxHeader,
+    UbxBody(usize),
+}
+
+/// Splits the received bytes into NMEA sentences and UBX frames.
+struct Framer {
+    buf: [u8; MAX_MESSAGE_LEN],
+    len: usize,
+    state: FramerState,
+}
+
+impl Framer {
+    const fn new() -> Self {
+        Self {
+            buf: [0; MAX_MESSAGE_LEN],
+            len: 0,
+            state: FramerState::Idle,
+        }
+    }
+
+    /// Feed a received byte, returning whether a message is complete.
+    fn push(&mut self, byte: u8) -> bool {
+        match self.state {
+            FramerState::Idle => self.start(byte),
+            FramerState::Nmea => {
+                if byte == b'$' || byte == ubx::SYNC[0] {
+                    // Truncated sentence.
+                    self.start(byte);
+                } else if self.append(byte) && byte == b'\n' {
+                    self.state = FramerState::Idle;
+                    return true;
+                }
+            }
+            FramerState::UbxSync => {
+                if byte == ubx::SYNC[1] {
+                    self.len = 0;
+                    self.state = FramerState::UbxHeader;
+                } else {
+                    self.start(byte);
+                }
+            }
+            FramerState::UbxHeader => {
+                self.append(byte);
+                if self.len == 4 {
+                    let len = u16::from_le_bytes([self.buf[2], self.buf[3]]) as usize;
+                    // Payload and checksum.
+                    self.state = FramerState::UbxBody(len + 2);
+                }
+            }
+            FramerState::UbxBody(remaining) => {
+                if !self.append(byte) {
+                    return false;
+                }
+                if remaining > 1 {
+                    self.state = FramerState::UbxBody(remaining - 1);
+                    return false;
+                }
+
+                self.state = FramerState::Idle;
+                let (frame, checksum) = self.buf[..self.len].split_at(self.len - 2);
+                if ubx::checksum(frame) == checksum {
+                    return true;
+                }
+                warn!("gnss: dropping UBX frame with invalid checksum");
+            }
+        }
+        false
+    }
+
+    fn start(&mut self, byte: u8) {
+        self.len = 0;
+        self.state = match byte {
+            b'$' => {
+                self.append(byte);
+                FramerState::Nmea
+            }
+            b if b == ubx::SYNC[0] => FramerState::UbxSync,
+            _ => FramerState::Idle,
+        };
+    }
+
+    fn append(&mut self, byte: u8) -> bool {
+        if self.len == self.buf.len() {
+            warn!("gnss: message too long, dropping");
+            self.state = FramerState::Idle;
+            return false;
+        }
+        self.buf[self.len] = byte;
+        self.len += 1;
+        true
+    }
+
+    fn message(&self) -> Message<'_> {
+        match self.buf[0] {
+            b'$' => Message::Nmea(&self.buf[..self.len]),
+            _ => Message::Ubx {
+                class: self.buf[0],
+                id: self.buf[1],
+                payload: &self.buf[4..self.len - 2],
+            },
+        }
+    }
+}
+
+/// GNSS receiver driver.
+///
+/// The receiver must send the GGA sentences for [`next_fix`](Self::next_fix), and the RMC
+/// sentences for the date, speed and course of the fixes.
+pub struct Gnss<R, W> {
+    reader: R,
+    writer: W,
+    framer: Framer,
+    last_rmc: Option<Rmc>,
+}
+
+impl<R: BufRead, W: Write<Error = R::Error>> Gnss<R, W> {
+    /// Create a new driver from the receiving and transmitting halves of the serial port,
+    /// usually a buffered UART.
+    pub fn new(reader: R, writer: W) -> Self {
+        Self {
+            reader,
+            writer,
+            framer: Framer::new(),
+            last_rmc: None,
+        }
+    }
+
+    /// Wait for the next message from the receiver.
+    pub async fn next_message(&mut self) -> Result<Message<'_>, Error<R::Error>> {
+        loop {
+            let buf = self.reader.fill_buf().await.map_err(Error::Read)?;
+            if buf.is_empty() {
+                return Err(Error::Eof);
+            }
+
+            let mut n = 0;
+            let mut complete = false;
+            for &byte in buf {
+                n += 1;
+                if self.framer.push(byte) {
+                    complete = true;
+                    break;
+                }
+            }
+            self.reader.consume(n);
+
+            if complete {
+                return Ok(self.framer.message());
+            }
+        }
+    }
+
+    /// Wait for the next valid position fix.
+    ///
+    /// The fix is returned on its GGA sentence, which the receivers send after the RMC sentence
+    /// of the same epoch. Sentences without a fix and invalid sentences are skipped.
+    pub async fn next_fix(&mut self) -> Result<Fix, Error<R::Error>> {
+        loop {
+            let Message::Nmea(sentence) = self.next_message().await? else {
+                continue;
+            };
+
+            match nmea::parse(sentence) {
+                Ok(Sentence::Rmc(rmc)) => self.last_rmc = Some(rmc),
+                Ok(Sentence::Gga(gga)) => {
+                    if let Some(fix) = Fix::new(&gga, self.last_rmc.as_ref()) {
+                        return Ok(fix);
+                    }
+                }
+                Ok(Sentence::Other) => {}
+                Err(e) => warn!("gnss: invalid NMEA sentence: {:?}", e),
+            }
+        }
+    }
+
+    /// Send a UBX message, without waiting for its acknowledgement.
+    ///
+    /// The payload must be at most [`MAX_MESSAGE_LEN`] - [`ubx::FRAME_OVERHEAD`] bytes long.
+    pub async fn send_ubx(&mut self, class: u8, id: u8, payload: &[u8]) -> Result<(), Error<R::Error>> {
+        let mut buf = [0; MAX_MESSAGE_LEN];
+        let n = ubx::encode(class, id, payload, &mut buf).map_err(|_| Error::PayloadTooLong)?;
+        self.writer.write_all(&buf[..n]).await.map_err(Error::Write)?;
+        self.writer.flush().await.map_err(Error::Write)
+    }
+
+    /// Send a UBX configuration message, and wait for its acknowledgement.
+    ///
+    /// The messages received in the meantime are dropped. Use a timeout, since receivers
+    /// without UBX support never answer.
+    pub async fn configure(&mut self, class: u8, id: u8, payload: &[u8]) -> Result<(), Error<R::Error>> {
+        self.send_ubx(class, id, payload).await?;
+
+        loop {
+            if let Message::Ubx {
+                class: ubx::CLASS_ACK,
+                id: ack,
+                payload: &[acked_class, acked_id],
+            } = self.next_message().await?
+            {
+                if acked_class == class && acked_id == id {
+                    return match ack {
+                        ubx::ID_ACK_ACK => Ok(()),
+                        _ => Err(Error::Nak),
+                    };
+                }
+            }
+        }
+    }
+
+    /// Return the halves of the serial port.
+    pub fn into_inner(self) -> (R, W) {
+        (self.reader, self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use embedded_io_async::ErrorType;
+
+    use super::*;
+
+    struct NoWrite;
+
+    impl ErrorType for NoWrite {
+        type Error = Infallible;
+    }
+
+    impl Write for NoWrite {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+            Ok(buf.len())
+        }
+    }
+
+    #[futures_test::test]
+    async fn returns_fix_with_date() {
+        let mut input = [0; 256];
+        let mut len = 0;
+        for chunk in [
+            &b"\xB5\x62\x05\x01\x02\x00\x06\x08\x16\x3F"[..],
+            b"$GPRMC,123519.50,A,4807.038,N,01131.000,W,0.5,84.4,230324,,,A*",
+            b"7F\r\n",
+            b"$GPGGA,123519.50,4807.038,N,01131.000,W,1,08,0.9,545.4,M,46.9,M,,*7E\r\n",
+        ] {
+            input[len..len + chunk.len()].copy_from_slice(chunk);
+            len += chunk.len();
+        }
+
+        let mut gnss = Gnss::new(&input[..len], NoWrite);
+
+        assert_eq!(
+            gnss.next_message().await,
+            Ok(Message::Ubx {
+                class: ubx::CLASS_ACK,
+                id: ubx::ID_ACK_ACK,
+                payload: &[ubx::CLASS_CFG, ubx::ID_CFG_RATE],
+            })
+        );
+
+        let fix = gnss.next_fix().await.unwrap();
+        assert_eq!(fix.quality, FixQuality::Gps);
+        assert_eq!(fix.satellites, 8);
+        assert_eq!(
+            fix.date,
+            Some(Date {
+                year: 2024,
+                month: 3,
+                day: 23
+            })
+        );
+        assert_eq!(fix.speed_knots, Some(0.5));
+    }
+}
@@ -0,0 +1,345 @@
+//! NMEA 0183 sentence parsing.
+//!
+//! Only the sentences needed for a position fix are parsed: GGA and RMC, from any talker.
+
+use core::str::Split;
+
+/// UTC time of day.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Time {
+    /// Hour, 0 to 23.
+    pub hour: u8,
+    /// Minute, 0 to 59.
+    pub minute: u8,
+    /// Second, 0 to 60.
+    pub second: u8,
+    /// Millisecond, 0 to 999.
+    pub millisecond: u16,
+}
+
+/// UTC date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Date {
+    /// Year, from 2000.
+    pub year: u16,
+    /// Month, 1 to 12.
+    pub month: u8,
+    /// Day of the month, 1 to 31.
+    pub day: u8,
+}
+
+/// Quality of a position fix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FixQuality {
+    /// No fix.
+    Invalid,
+    /// Autonomous GNSS fix.
+    Gps,
+    /// Differential GNSS fix.
+    Dgps,
+    /// PPS fix.
+    Pps,
+    /// Real-time kinematic, fixed integers.
+    Rtk,
+    /// Real-time kinematic, float integers.
+    FloatRtk,
+    /// Estimated, dead reckoning.
+    Estimated,
+    /// Manual input.
+    Manual,
+    /// Simulation.
+    Simulation,
+}
+
+/// GGA sentence: time, position and fix data.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Gga {
+    /// Time of the fix.
+    pub time: Time,
+    /// Latitude in degrees, positive north.
+    pub latitude: Option<f64>,
+    /// Longitude in degrees, positive east.
+    pub longitude: Option<f64>,
+    /// Quality of the fix.
+    pub quality: FixQuality,
+    /// Number of satellites used.
+    pub satellites: u8,
+    /// Horizontal dilution of precision.
+    pub hdop: Option<f32>,
+    /// Altitude above mean sea level, in meters.
+    pub altitude: Option<f32>,
+}
+
+/// RMC sentence: recommended minimum data.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Rmc {
+    /// Time of the fix.
+    pub time: Time,
+    /// Date of the fix.
+    pub date: Option<Date>,
+    /// Whether the data is valid.
+    pub valid: bool,
+    /// Latitude in degrees, positive north.
+    pub latitude: Option<f64>,
+    /// Longitude in degrees, positive east.
+    pub longitude: Option<f64>,
+    /// Speed over ground, in knots.
+    pub speed_knots: Option<f32>,
+    /// Course over ground, in degrees from true north.
+    pub course: Option<f32>,
+}
+
+/// Parsed NMEA sentence.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Sentence {
+    /// GGA sentence.
+    Gga(Gga),
+    /// RMC sentence.
+    Rmc(Rmc),
+    /// Valid sentence of another type.
+    Other,
+}
+
+/// NMEA parsing error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ParseError {
+    /// The sentence does not start with `$` or has no checksum.
+    Framing,
+    /// The checksum does not match.
+    Checksum,
+    /// A field is missing or malformed.
+    Field,
+}
+
+/// Parse a sentence, from `$` to the checksum, with or without the line terminator.
+pub fn parse(sentence: &[u8]) -> Result<Sentence, ParseError> {
+    let body = checked_body(sentence)?;
+    let body = core::str::from_utf8(body).map_err(|_| ParseError::Framing)?;
+
+    let mut fields = body.split(',');
+    let address = fields.next().ok_or(ParseError::Framing)?;
+    // Proprietary sentences, like `$PUBX`, have a longer address.
+    if address.len() != 5 {
+        return Ok(Sentence::Other);
+    }
+
+    match &address[2..] {
+        "GGA" => parse_gga(fields).map(Sentence::Gga),
+        "RMC" => parse_rmc(fields).map(Sentence::Rmc),
+        _ => Ok(Sentence::Other),
+    }
+}
+
+/// Return the sentence between `$` and `*`, after checking its checksum.
+fn checked_body(sentence: &[u8]) -> Result<&[u8], ParseError> {
+    let sentence = sentence.strip_suffix(b"\n").unwrap_or(sentence);
+    let sentence = sentence.strip_suffix(b"\r").unwrap_or(sentence);
+    let sentence = sentence.strip_prefix(b"$").ok_or(ParseError::Framing)?;
+
+    let star = sentence.iter().rposition(|b| *b == b'*').ok_or(ParseError::Framing)?;
+    let (body, checksum) = (&sentence[..star], &sentence[star + 1..]);
+
+    let checksum = core::str::from_utf8(checksum).map_err(|_| ParseError::Framing)?;
+    let expected = u8::from_str_radix(checksum, 16).map_err(|_| ParseError::Framing)?;
+    if body.iter().fold(0, |acc, b| acc ^ b) != expected {
+        return Err(ParseError::Checksum);
+    }
+    Ok(body)
+}
+
+fn parse_gga(mut fields: Split<'_, char>) -> Result<Gga, ParseError> {
+    let time = parse_time(next(&mut fields)?)?;
+    let latitude = parse_coordinate(next(&mut fields)?, next(&mut fields)?)?;
+    let longitude = parse_coordinate(next(&mut fields)?, next(&mut fields)?)?;
+    let quality = match next(&mut fields)? {
+        "" | "0" => FixQuality::Invalid,
+        "1" => FixQuality::Gps,
+        "2" => FixQuality::Dgps,
+        "3" => FixQuality::Pps,
+        "4" => FixQuality::Rtk,
+        "5" => FixQuality::FloatRtk,
+        "6" => FixQuality::Estimated,
+        "7" => FixQuality::Manual,
+        "8" => FixQuality::Simulation,
+        _ => return Err(ParseError::Field),
+    };
+    let satellites = parse_optional(next(&mut fields)?)?.unwrap_or(0);
+    let hdop = parse_optional(next(&mut fields)?)?;
+    let altitude = parse_optional(next(&mut fields)?)?;
+
+    Ok(Gga {
+        time,
+        latitude,
+        longitude,
+        quality,
+        satellites,
+        hdop,
+        altitude,
+    })
+}
+
+fn parse_rmc(mut fields: Split<'_, char>) -> Result<Rmc, ParseError> {
+    let time = parse_time(next(&mut fields)?)?;
+    let valid = next(&mut fields)? == "A";
+    let latitude = parse_coordinate(next(&mut fields)?, next(&mut fields)?)?;
+    let longitude = parse_coordinate(next(&mut fields)?, next(&mut fields)?)?;
+    let speed_knots = parse_optional(next(&mut fields)?)?;
+    let course = parse_optional(next(&mut fields)?)?;
+    let date = parse_date(next(&mut fields)?)?;
+
+    Ok(Rmc {
+        time,
+        date,
+        valid,
+        latitude,
+        longitude,
+        speed_knots,
+        course,
+    })
+}
+
+fn next<'a>(fields: &mut Split<'a, char>) -> Result<&'a str, ParseError> {
+    fields.next().ok_or(ParseError::Field)
+}
+
+fn parse_optional<T: core::str::FromStr>(field: &str) -> Result<Option<T>, ParseError> {
+    match field {
+        "" => Ok(None),
+        _ => field.parse().map(Some).map_err(|_| ParseError::Field),
+    }
+}
+
+fn parse_digits(field: &str) -> Result<u8, ParseError> {
+    field.parse().map_err(|_| ParseError::Field)
+}
+
+/// Parse `hhmmss.sss`.
+fn parse_time(field: &str) -> Result<Time, ParseError> {
+    if field.len() < 6 || !field.is_ascii() {
+        return Err(ParseError::Field);
+    }
+
+    let millisecond = match &field[6..] {
+        "" => 0,
+        fraction => {
+            let digits = fraction.strip_prefix('.').ok_or(ParseError::Field)?;
+            let digits = &digits[..digits.len().min(3)];
+            let value: u16 = digits.parse().map_err(|_| ParseError::Field)?;
+            value * 10u16.pow(3 - digits.len() as u32)
+        }
+    };
+
+    Ok(Time {
+        hour: parse_digits(&field[0..2])?,
+        minute: parse_digits(&field[2..4])?,
+        second: parse_digits(&field[4..6])?,
+        millisecond,
+    })
+}
+
+/// Parse `ddmmyy`, or an empty field.
+fn parse_date(field: &str) -> Result<Option<Date>, ParseError> {
+    if field.is_empty() {
+        return Ok(None);
+    }
+    if field.len() != 6 || !field.is_ascii() {
+        return Err(ParseError::Field);
+    }
+
+    Ok(Some(Date {
+        day: parse_digits(&field[0..2])?,
+        month: parse_digits(&field[2..4])?,
+        year: 2000 + parse_digits(&field[4..6])? as u16,
+    }))
+}
+
+/// Parse `dddmm.mmmm` with its hemisphere, or empty fields.
+fn parse_coordinate(value: &str, hemisphere: &str) -> Result<Option<f64>, ParseError> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    if !value.is_ascii() {
+        return Err(ParseError::Field);
+    }
+
+    // The minutes are the two digits before the decimal point, and the decimals.
+    let dot = value.find('.').unwrap_or(value.len());
+    let split = dot.checked_sub(2).ok_or(ParseError::Field)?;
+    let degrees: f64 = match &value[..split] {
+        "" => 0.0,
+        degrees => degrees.parse().map_err(|_| ParseError::Field)?,
+    };
+    let minutes: f64 = value[split..].parse().map_err(|_| ParseError::Field)?;
+    let coordinate = degrees + minutes / 60.0;
+
+    match hemisphere {
+        "N" | "E" => Ok(Some(coordinate)),
+        "S" | "W" => Ok(Some(-coordinate)),
+        _ => Err(ParseError::Field),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_gga() {
+        let sentence = b"$GPGGA,123519.50,4807.038,N,01131.000,W,1,08,0.9,545.4,M,46.9,M,,*7E\r\n";
+        let Ok(Sentence::Gga(gga)) = parse(sentence) else {
+            panic!("not a GGA sentence");
+        };
+
+        assert_eq!(
+            gga.time,
+            Time {
+                hour: 12,
+                minute: 35,
+                second: 19,
+                millisecond: 500
+            }
+        );
+        assert!((gga.latitude.unwrap() - 48.1173).abs() < 1e-6);
+        assert!((gga.longitude.unwrap() + 11.516_666).abs() < 1e-6);
+        assert_eq!(gga.quality, FixQuality::Gps);
+        assert_eq!(gga.satellites, 8);
+        assert_eq!(gga.hdop, Some(0.9));
+        assert_eq!(gga.altitude, Some(545.4));
+    }
+
+    #[test]
+    fn parses_rmc_without_fix() {
+        let sentence = b"$GNRMC,000001.00,V,,,,,,,010124,,,N*64";
+        let Ok(Sentence::Rmc(rmc)) = parse(sentence) else {
+            panic!("not a RMC sentence");
+        };
+
+        assert!(!rmc.valid);
+        assert_eq!(rmc.latitude, None);
+        assert_eq!(
+            rmc.date,
+            Some(Date {
+                year: 2024,
+                month: 1,
+                day: 1
+            })
+        );
+    }
+
+    #[test]
+    fn checks_checksum() {
+        assert_eq!(
+            parse(b"$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*48"),
+            Err(ParseError::Checksum)
+        );
+        assert_eq!(parse(b"GPGGA,123519*47"), Err(ParseError::Framing));
+        assert_eq!(parse(b"$GPGSV,1,1,00*79"), Ok(Sentence::Other));
+    }
+}
@@ -0,0 +1,106 @@
+//! UBX protocol of u-blox receivers.
+//!
+//! Only the framing and a few configuration messages are provided. Other messages can be built
+//! from the interface description of the receiver and sent with
+//! [`Gnss::configure`](crate::Gnss::configure).
+
+/// Synchronization characters starting each frame.
+pub const SYNC: [u8; 2] = [0xB5, 0x62];
+
+/// Length of a frame without its payload.
+pub const FRAME_OVERHEAD: usize = 8;
+
+/// Navigation results class.
+pub const CLASS_NAV: u8 = 0x01;
+/// Acknowledgement class.
+pub const CLASS_ACK: u8 = 0x05;
+/// Configuration class.
+pub const CLASS_CFG: u8 = 0x06;
+
+/// Message acknowledged, in [`CLASS_ACK`].
+pub const ID_ACK_ACK: u8 = 0x01;
+/// Message not acknowledged, in [`CLASS_ACK`].
+pub const ID_ACK_NAK: u8 = 0x00;
+/// Message rate configuration, in [`CLASS_CFG`].
+pub const ID_CFG_MSG: u8 = 0x01;
+/// Navigation rate configuration, in [`CLASS_CFG`].
+pub const ID_CFG_RATE: u8 = 0x08;
+
+/// Standard NMEA messages class, for [`cfg_msg`].
+pub const CLASS_NMEA: u8 = 0xF0;
+
+/// Error returned by [`encode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EncodeError {
+    /// The frame doesn't fit in the output buffer.
+    PayloadTooLong,
+}
+
+/// Fletcher checksum of the class, id, length and payload of a frame.
+pub fn checksum(data: &[u8]) -> [u8; 2] {
+    let (mut a, mut b) = (0u8, 0u8);
+    for byte in data {
+        a = a.wrapping_add(*byte);
+        b = b.wrapping_add(a);
+    }
+    [a, b]
+}
+
+/// Encode a frame into `out`, returning its length.
+///
+/// `out` must be at least [`FRAME_OVERHEAD`] bytes longer than the payload.
+pub fn encode(class: u8, id: u8, payload: &[u8], out: &mut [u8]) -> Result<usize, EncodeError> {
+    let len = payload.len();
+    if len > u16::MAX as usize || out.len() < len + FRAME_OVERHEAD {
+        return Err(EncodeError::PayloadTooLong);
+    }
+
+    out[..2].copy_from_slice(&SYNC);
+    out[2] = class;
+    out[3] = id;
+    out[4..6].copy_from_slice(&(len as u16).to_le_bytes());
+    out[6..6 + len].copy_from_slice(payload);
+    let checksum = checksum(&out[2..6 + len]);
+    out[6 + len..8 + len].copy_from_slice(&checksum);
+    Ok(len + FRAME_OVERHEAD)
+}
+
+/// Payload of `CFG-MSG`, sending the message `class`/`id` every `rate` navigation solutions on
+/// the current port, or disabling it when `rate` is 0.
+///
+/// For example `cfg_msg(CLASS_NMEA, 0x03, 0)` disables the NMEA GSV sentences.
+pub fn cfg_msg(class: u8, id: u8, rate: u8) -> [u8; 3] {
+    [class, id, rate]
+}
+
+/// Payload of `CFG-RATE`, computing a navigation solution every `period_ms` milliseconds,
+/// aligned to GPS time.
+pub fn cfg_rate(period_ms: u16) -> [u8; 6] {
+    let period = period_ms.to_le_bytes();
+    [period[0], period[1], 1, 0, 1, 0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_cfg_rate() {
+        let mut buf = [0; 14];
+        let n = encode(CLASS_CFG, ID_CFG_RATE, &cfg_rate(200), &mut buf).unwrap();
+        assert_eq!(
+            &buf[..n],
+            &[0xB5, 0x62, 0x06, 0x08, 0x06, 0x00, 0xC8, 0x00, 0x01, 0x00, 0x01, 0x00, 0xDE, 0x6A]
+        );
+    }
+
+    #[test]
+    fn rejects_long_payload() {
+        let mut buf = [0; 14];
+        assert_eq!(
+            encode(CLASS_CFG, ID_CFG_RATE, &[0; 7], &mut buf),
+            Err(EncodeError::PayloadTooLong)
+        );
+    }
+}
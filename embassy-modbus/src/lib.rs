@@ -0,0 +1,588 @@
+#![no_std]
+#![allow(async_fn_in_trait)]
+#![warn(missing_docs)]
+#![doc = include_str!("../README.md")]
+
+// must be first
+mod fmt;
+
+pub mod rtu;
+pub mod tcp;
+
+/// Maximum length of a protocol data unit (function code and data).
+pub const MAX_PDU_LEN: usize = 253;
+
+/// Unit address of the broadcast requests on a serial line, which the slaves don't answer.
+pub const BROADCAST: u8 = 0;
+
+const READ_COILS: u8 = 0x01;
+const READ_DISCRETE_INPUTS: u8 = 0x02;
+const READ_HOLDING_REGISTERS: u8 = 0x03;
+const READ_INPUT_REGISTERS: u8 = 0x04;
+const WRITE_SINGLE_COIL: u8 = 0x05;
+const WRITE_SINGLE_REGISTER: u8 = 0x06;
+const WRITE_MULTIPLE_COILS: u8 = 0x0F;
+const WRITE_MULTIPLE_REGISTERS: u8 = 0x10;
+
+/// Maximum number of bits read by a request.
+const MAX_READ_BITS: usize = 2000;
+/// Maximum number of registers read by a request.
+const MAX_READ_REGISTERS: usize = 125;
+/// Maximum number of coils written by a request.
+const MAX_WRITE_COILS: usize = 1968;
+/// Maximum number of registers written by a request.
+const MAX_WRITE_REGISTERS: usize = 123;
+
+/// Exception code, answered by a slave instead of the normal response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Exception {
+    /// The function code isn't supported.
+    IllegalFunction,
+    /// The data address isn't supported.
+    IllegalDataAddress,
+    /// A value in the request isn't supported.
+    IllegalDataValue,
+    /// An unrecoverable error occurred while performing the action.
+    ServerDeviceFailure,
+    /// The request was accepted, but will take a long time to process.
+    Acknowledge,
+    /// The slave is busy processing a long-duration command.
+    ServerDeviceBusy,
+    /// The gateway couldn't allocate a path to the target device.
+    GatewayPathUnavailable,
+    /// The target device behind the gateway didn't respond.
+    GatewayTargetFailedToRespond,
+    /// Another exception code.
+    Other(u8),
+}
+
+impl Exception {
+    /// Exception code on the wire.
+    pub fn code(self) -> u8 {
+        match self {
+            Self::IllegalFunction => 0x01,
+            Self::IllegalDataAddress => 0x02,
+            Self::IllegalDataValue => 0x03,
+            Self::ServerDeviceFailure => 0x04,
+            Self::Acknowledge => 0x05,
+            Self::ServerDeviceBusy => 0x06,
+            Self::GatewayPathUnavailable => 0x0A,
+            Self::GatewayTargetFailedToRespond => 0x0B,
+            Self::Other(code) => code,
+        }
+    }
+
+    /// Exception of the code on the wire.
+    pub fn from_code(code: u8) -> Self {
+        match code {
+            0x01 => Self::IllegalFunction,
+            0x02 => Self::IllegalDataAddress,
+            0x03 => Self::IllegalDataValue,
+            0x04 => Self::ServerDeviceFailure,
+            0x05 => Self::Acknowledge,
+            0x06 => Self::ServerDeviceBusy,
+            0x0A => Self::GatewayPathUnavailable,
+            0x0B => Self::GatewayTargetFailedToRespond,
+            code => Self::Other(code),
+        }
+    }
+}
+
+/// Error of a Modbus transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// The serial port or the stream failed.
+    Io(E),
+    /// No response was received before the timeout.
+    Timeout,
+    /// The stream was closed.
+    Eof,
+    /// A frame was received with a wrong CRC, or a malformed header.
+    InvalidFrame,
+    /// The slave answered with an exception.
+    Exception(Exception),
+    /// The response doesn't match the request.
+    UnexpectedResponse,
+    /// The number of values to read or write is out of the range allowed by the protocol.
+    InvalidCount,
+}
+
+/// Register map of a slave, implemented by the application.
+///
+/// Addresses are the 0-based addresses of the protocol data unit. Each method returns the
+/// exception to answer instead of the response, the default implementations answering
+/// [`Exception::IllegalFunction`] for the tables the application doesn't have.
+pub trait RegisterMap {
+    /// Read the coil at `address`.
+    fn read_coil(&mut self, address: u16) -> Result<bool, Exception> {
+        let _ = address;
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Read the discrete input at `address`.
+    fn read_discrete_input(&mut self, address: u16) -> Result<bool, Exception> {
+        let _ = address;
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Read the holding registers starting at `address` to `values`.
+    fn read_holding_registers(&mut self, address: u16, values: &mut [u16]) -> Result<(), Exception> {
+        let _ = (address, values);
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Read the input registers starting at `address` to `values`.
+    fn read_input_registers(&mut self, address: u16, values: &mut [u16]) -> Result<(), Exception> {
+        let _ = (address, values);
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Write the coil at `address`.
+    fn write_coil(&mut self, address: u16, value: bool) -> Result<(), Exception> {
+        let _ = (address, value);
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Write `values` to the holding registers starting at `address`.
+    fn write_registers(&mut self, address: u16, values: &[u16]) -> Result<(), Exception> {
+        let _ = (address, values);
+        Err(Exception::IllegalFunction)
+    }
+}
+
+/// Transport of the requests of a [`Master`], over a serial line or TCP.
+pub trait Transport {
+    /// Error of the serial port or stream.
+    type Error;
+
+    /// Send the request PDU `request` to `unit`, and receive the response PDU in `response`.
+    ///
+    /// Returns the length of the response, 0 for the broadcast requests, which are not answered.
+    async fn transact(
+        &mut self,
+        unit: u8,
+        request: &[u8],
+        response: &mut [u8; MAX_PDU_LEN],
+    ) -> Result<usize, Error<Self::Error>>;
+}
+
+/// Modbus master (client), sending requests to the slaves over a [`Transport`].
+pub struct Master<T> {
+    transport: T,
+}
+
+impl<T: Transport> Master<T> {
+    /// Create a new master over `transport`.
+    pub fn new(transport: T) -> Self {
+        Self { transport }
+    }
+
+    /// Release the transport.
+    pub fn release(self) -> T {
+        self.transport
+    }
+
+    /// Read the coils of `unit` starting at `address`, filling `coils`.
+    pub async fn read_coils(&mut self, unit: u8, address: u16, coils: &mut [bool]) -> Result<(), Error<T::Error>> {
+        self.read_bits(unit, READ_COILS, address, coils).await
+    }
+
+    /// Read the discrete inputs of `unit` starting at `address`, filling `inputs`.
+    pub async fn read_discrete_inputs(
+        &mut self,
+        unit: u8,
+        address: u16,
+        inputs: &mut [bool],
+    ) -> Result<(), Error<T::Error>> {
+        self.read_bits(unit, READ_DISCRETE_INPUTS, address, inputs).await
+    }
+
+    /// Read the holding registers of `unit` starting at `address`, filling `values`.
+    pub async fn read_holding_registers(
+        &mut self,
+        unit: u8,
+        address: u16,
+        values: &mut [u16],
+    ) -> Result<(), Error<T::Error>> {
+        self.read_registers(unit, READ_HOLDING_REGISTERS, address, values).await
+    }
+
+    /// Read the input registers of `unit` starting at `address`, filling `values`.
+    pub async fn read_input_registers(
+        &mut self,
+        unit: u8,
+        address: u16,
+        values: &mut [u16],
+    ) -> Result<(), Error<T::Error>> {
+        self.read_registers(unit, READ_INPUT_REGISTERS, address, values).await
+    }
+
+    /// Write the coil of `unit` at `address`.
+    pub async fn write_coil(&mut self, unit: u8, address: u16, value: bool) -> Result<(), Error<T::Error>> {
+        let value: u16 = if value { 0xFF00 } else { 0x0000 };
+        let request = encode(WRITE_SINGLE_COIL, address, value);
+        self.write(unit, &request, &request).await
+    }
+
+    /// Write the holding register of `unit` at `address`.
+    pub async fn write_register(&mut self, unit: u8, address: u16, value: u16) -> Result<(), Error<T::Error>> {
+        let request = encode(WRITE_SINGLE_REGISTER, address, value);
+        self.write(unit, &request, &request).await
+    }
+
+    /// Write `coils` to the coils of `unit` starting at `address`.
+    pub async fn write_coils(&mut self, unit: u8, address: u16, coils: &[bool]) -> Result<(), Error<T::Error>> {
+        if coils.is_empty() || coils.len() > MAX_WRITE_COILS {
+            return Err(Error::InvalidCount);
+        }
+
+        let head = encode(WRITE_MULTIPLE_COILS, address, coils.len() as u16);
+        let bytes = coils.len().div_ceil(8);
+        let mut request = [0; MAX_PDU_LEN];
+        request[..5].copy_from_slice(&head);
+        request[5] = bytes as u8;
+        for (i, _) in coils.iter().enumerate().filter(|(_, coil)| **coil) {
+            request[6 + i / 8] |= 1 << (i % 8);
+        }
+        self.write(unit, &request[..6 + bytes], &head).await
+    }
+
+    /// Write `values` to the holding registers of `unit` starting at `address`.
+    pub async fn write_registers(&mut self, unit: u8, address: u16, values: &[u16]) -> Result<(), Error<T::Error>> {
+        if values.is_empty() || values.len() > MAX_WRITE_REGISTERS {
+            return Err(Error::InvalidCount);
+        }
+
+        let head = encode(WRITE_MULTIPLE_REGISTERS, address, values.len() as u16);
+        let mut request = [0; MAX_PDU_LEN];
+        request[..5].copy_from_slice(&head);
+        request[5] = (values.len() * 2) as u8;
+        for (i, value) in values.iter().enumerate() {
+            request[6 + 2 * i..8 + 2 * i].copy_from_slice(&value.to_be_bytes());
+        }
+        self.write(unit, &request[..6 + values.len() * 2], &head).await
+    }
+
+    async fn read_bits(
+        &mut self,
+        unit: u8,
+        function: u8,
+        address: u16,
+        bits: &mut [bool],
+    ) -> Result<(), Error<T::Error>> {
+        if bits.is_empty() || bits.len() > MAX_READ_BITS {
+            return Err(Error::InvalidCount);
+        }
+
+        let mut response = [0; MAX_PDU_LEN];
+        let request = encode(function, address, bits.len() as u16);
+        let response = self.transact(unit, &request, &mut response).await?;
+
+        let bytes = bits.len().div_ceil(8);
+        if response.len() != 2 + bytes || response[1] as usize != bytes {
+            return Err(Error::UnexpectedResponse);
+        }
+        for (i, bit) in bits.iter_mut().enumerate() {
+            *bit = response[2 + i / 8] & (1 << (i % 8)) != 0;
+        }
+        Ok(())
+    }
+
+    async fn read_registers(
+        &mut self,
+        unit: u8,
+        function: u8,
+        address: u16,
+        values: &mut [u16],
+    ) -> Result<(), Error<T::Error>> {
+        if values.is_empty() || values.len() > MAX_READ_REGISTERS {
+            return Err(Error::InvalidCount);
+        }
+
+        let mut response = [0; MAX_PDU_LEN];
+        let request = encode(function, address, values.len() as u16);
+        let response = self.transact(unit, &request, &mut response).await?;
+
+        let bytes = values.len() * 2;
+        if response.len() != 2 + bytes || response[1] as usize != bytes {
+            return Err(Error::UnexpectedResponse);
+        }
+        for (value, data) in values.iter_mut().zip(response[2..].chunks_exact(2)) {
+            *value = u16::from_be_bytes([data[0], data[1]]);
+        }
+        Ok(())
+    }
+
+    /// Send a write request, whose response echoes `echo`.
+    async fn write(&mut self, unit: u8, request: &[u8], echo: &[u8]) -> Result<(), Error<T::Error>> {
+        let mut response = [0; MAX_PDU_LEN];
+        let response = self.transact(unit, request, &mut response).await?;
+        if !response.is_empty() && response != echo {
+            return Err(Error::UnexpectedResponse);
+        }
+        Ok(())
+    }
+
+    /// Send `request`, and return the response, or the exception answered by the slave.
+    async fn transact<'r>(
+        &mut self,
+        unit: u8,
+        request: &[u8],
+        response: &'r mut [u8; MAX_PDU_LEN],
+    ) -> Result<&'r [u8], Error<T::Error>> {
+        let len = self.transport.transact(unit, request, response).await?;
+        let response = &response[..len];
+        match response {
+            [] => Ok(response),
+            [function, code] if *function == request[0] | 0x80 => Err(Error::Exception(Exception::from_code(*code))),
+            [function, ..] if *function == request[0] => Ok(response),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+}
+
+/// Encode a request made of a function code and two 16-bit fields.
+fn encode(function: u8, address: u16, value: u16) -> [u8; 5] {
+    let [a0, a1] = address.to_be_bytes();
+    let [v0, v1] = value.to_be_bytes();
+    [function, a0, a1, v0, v1]
+}
+
+/// Handle the request PDU `request` with `map`, writing the response PDU to `response`.
+///
+/// Returns the length of the response.
+pub(crate) fn handle_request(map: &mut impl RegisterMap, request: &[u8], response: &mut [u8; MAX_PDU_LEN]) -> usize {
+    let function = request.first().copied().unwrap_or(0);
+    match process_request(map, request, response) {
+        Ok(len) => len,
+        Err(exception) => {
+            trace!("modbus: exception {:?} to function {}", exception, function);
+            response[0] = function | 0x80;
+            response[1] = exception.code();
+            2
+        }
+    }
+}
+
+fn process_request(
+    map: &mut impl RegisterMap,
+    request: &[u8],
+    response: &mut [u8; MAX_PDU_LEN],
+) -> Result<usize, Exception> {
+    let (&function, data) = request.split_first().ok_or(Exception::IllegalFunction)?;
+    response[0] = function;
+
+    match function {
+        READ_COILS | READ_DISCRETE_INPUTS => {
+            let (address, count) = parse_fields(data, 4)?;
+            check_range(address, count, MAX_READ_BITS)?;
+
+            let bytes = count.div_ceil(8);
+            response[1] = bytes as u8;
+            response[2..2 + bytes].fill(0);
+            for i in 0..count {
+                let address = address + i as u16;
+                let bit = match function {
+                    READ_COILS => map.read_coil(address)?,
+                    _ => map.read_discrete_input(address)?,
+                };
+                if bit {
+                    response[2 + i / 8] |= 1 << (i % 8);
+                }
+            }
+            Ok(2 + bytes)
+        }
+        READ_HOLDING_REGISTERS | READ_INPUT_REGISTERS => {
+            let (address, count) = parse_fields(data, 4)?;
+            check_range(address, count, MAX_READ_REGISTERS)?;
+
+            let mut values = [0; MAX_READ_REGISTERS];
+            let values = &mut values[..count];
+            match function {
+                READ_HOLDING_REGISTERS => map.read_holding_registers(address, values)?,
+                _ => map.read_input_registers(address, values)?,
+            }
+
+            response[1] = (count * 2) as u8;
+            for (i, value) in values.iter().enumerate() {
+                response[2 + 2 * i..4 + 2 * i].copy_from_slice(&value.to_be_bytes());
+            }
+            Ok(2 + count * 2)
+        }
+        WRITE_SINGLE_COIL => {
+            let (address, value) = parse_fields(data, 4)?;
+            let value = match value {
+                0xFF00 => true,
+                0x0000 => false,
+                _ => return Err(Exception::IllegalDataValue),
+            };
+            map.write_coil(address, value)?;
+            response[1..5].copy_from_slice(data);
+            Ok(5)
+        }
+        WRITE_SINGLE_REGISTER => {
+            let (address, value) = parse_fields(data, 4)?;
+            map.write_registers(address, &[value as u16])?;
+            response[1..5].copy_from_slice(data);
+            Ok(5)
+        }
+        WRITE_MULTIPLE_COILS => {
+            let (address, count) = parse_fields(data, 5 + data.get(4).copied().unwrap_or(0) as usize)?;
+            check_range(address, count, MAX_WRITE_COILS)?;
+            if data[4] as usize != count.div_ceil(8) {
+                return Err(Exception::IllegalDataValue);
+            }
+
+            for i in 0..count {
+                let value = data[5 + i / 8] & (1 << (i % 8)) != 0;
+                map.write_coil(address + i as u16, value)?;
+            }
+            response[1..5].copy_from_slice(&data[..4]);
+            Ok(5)
+        }
+        WRITE_MULTIPLE_REGISTERS => {
+            let (address, count) = parse_fields(data, 5 + data.get(4).copied().unwrap_or(0) as usize)?;
+            check_range(address, count, MAX_WRITE_REGISTERS)?;
+            if data[4] as usize != count * 2 {
+                return Err(Exception::IllegalDataValue);
+            }
+
+            let mut values = [0; MAX_WRITE_REGISTERS];
+            let values = &mut values[..count];
+            for (value, data) in values.iter_mut().zip(data[5..].chunks_exact(2)) {
+                *value = u16::from_be_bytes([data[0], data[1]]);
+            }
+            map.write_registers(address, values)?;
+            response[1..5].copy_from_slice(&data[..4]);
+            Ok(5)
+        }
+        _ => Err(Exception::IllegalFunction),
+    }
+}
+
+/// Parse the address and the count or value of a request whose data is `len` bytes long.
+fn parse_fields(data: &[u8], len: usize) -> Result<(u16, usize), Exception> {
+    if data.len() != len || len < 4 {
+        return Err(Exception::IllegalDataValue);
+    }
+    let address = u16::from_be_bytes([data[0], data[1]]);
+    let value = u16::from_be_bytes([data[2], data[3]]);
+    Ok((address, value as usize))
+}
+
+/// Check the count of a request, and that the addresses it covers exist.
+fn check_range(address: u16, count: usize, max: usize) -> Result<(), Exception> {
+    if count == 0 || count > max {
+        return Err(Exception::IllegalDataValue);
+    }
+    if address as usize + count > 0x1_0000 {
+        return Err(Exception::IllegalDataAddress);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Map {
+        coils: [bool; 16],
+        registers: [u16; 8],
+    }
+
+    impl RegisterMap for Map {
+        fn read_coil(&mut self, address: u16) -> Result<bool, Exception> {
+            self.coils
+                .get(address as usize)
+                .copied()
+                .ok_or(Exception::IllegalDataAddress)
+        }
+
+        fn write_coil(&mut self, address: u16, value: bool) -> Result<(), Exception> {
+            let coil = self
+                .coils
+                .get_mut(address as usize)
+                .ok_or(Exception::IllegalDataAddress)?;
+            *coil = value;
+            Ok(())
+        }
+
+        fn read_holding_registers(&mut self, address: u16, values: &mut [u16]) -> Result<(), Exception> {
+            let registers = self.registers.get(address as usize..address as usize + values.len());
+            values.copy_from_slice(registers.ok_or(Exception::IllegalDataAddress)?);
+            Ok(())
+        }
+
+        fn write_registers(&mut self, address: u16, values: &[u16]) -> Result<(), Exception> {
+            let registers = self
+                .registers
+                .get_mut(address as usize..address as usize + values.len());
+            registers.ok_or(Exception::IllegalDataAddress)?.copy_from_slice(values);
+            Ok(())
+        }
+    }
+
+    fn handle(map: &mut Map, request: &[u8]) -> ([u8; MAX_PDU_LEN], usize) {
+        let mut response = [0; MAX_PDU_LEN];
+        let len = handle_request(map, request, &mut response);
+        (response, len)
+    }
+
+    #[test]
+    fn reads_and_writes_registers() {
+        let mut map = Map::default();
+
+        let (response, len) = handle(&mut map, &[0x10, 0x00, 0x01, 0x00, 0x02, 0x04, 0x12, 0x34, 0x56, 0x78]);
+        assert_eq!(&response[..len], &[0x10, 0x00, 0x01, 0x00, 0x02]);
+        assert_eq!(map.registers[1..3], [0x1234, 0x5678]);
+
+        let (response, len) = handle(&mut map, &[0x06, 0x00, 0x00, 0xAB, 0xCD]);
+        assert_eq!(&response[..len], &[0x06, 0x00, 0x00, 0xAB, 0xCD]);
+
+        let (response, len) = handle(&mut map, &[0x03, 0x00, 0x00, 0x00, 0x03]);
+        assert_eq!(&response[..len], &[0x03, 0x06, 0xAB, 0xCD, 0x12, 0x34, 0x56, 0x78]);
+    }
+
+    #[test]
+    fn reads_and_writes_coils() {
+        let mut map = Map::default();
+
+        let (response, len) = handle(
+            &mut map,
+            &[0x0F, 0x00, 0x02, 0x00, 0x0A, 0x02, 0b1100_1101, 0b0000_0001],
+        );
+        assert_eq!(&response[..len], &[0x0F, 0x00, 0x02, 0x00, 0x0A]);
+
+        let (response, len) = handle(&mut map, &[0x05, 0x00, 0x00, 0xFF, 0x00]);
+        assert_eq!(&response[..len], &[0x05, 0x00, 0x00, 0xFF, 0x00]);
+
+        let (response, len) = handle(&mut map, &[0x01, 0x00, 0x00, 0x00, 0x0C]);
+        assert_eq!(&response[..len], &[0x01, 0x02, 0b0011_0101, 0b0000_0111]);
+    }
+
+    #[test]
+    fn answers_exceptions() {
+        let mut map = Map::default();
+
+        // Unsupported table.
+        let (response, len) = handle(&mut map, &[0x04, 0x00, 0x00, 0x00, 0x01]);
+        assert_eq!(&response[..len], &[0x84, 0x01]);
+
+        // Out of the map.
+        let (response, len) = handle(&mut map, &[0x03, 0x00, 0x07, 0x00, 0x02]);
+        assert_eq!(&response[..len], &[0x83, 0x02]);
+
+        // Invalid count and coil value.
+        let (response, len) = handle(&mut map, &[0x03, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(&response[..len], &[0x83, 0x03]);
+        let (response, len) = handle(&mut map, &[0x05, 0x00, 0x00, 0x12, 0x34]);
+        assert_eq!(&response[..len], &[0x85, 0x03]);
+
+        // Unknown function.
+        let (response, len) = handle(&mut map, &[0x2B, 0x0E]);
+        assert_eq!(&response[..len], &[0xAB, 0x01]);
+    }
+}
@@ -0,0 +1,311 @@
+//! Modbus RTU, over a serial line.
+//!
+//! An RTU frame is the unit address, the PDU and a CRC, and frames are delimited by silences of
+//! at least 3.5 characters on the line. The [`RtuPort`] trait sends and receives whole frames:
+//! [`TimedPort`] implements it over any serial port, timing the silences itself, and HALs able to
+//! detect them in hardware can implement it directly. For example, with the DE pin of an STM32
+//! UART driving the RS-485 transceiver, and its idle line detection ending the frames:
+//!
+//! ```rust,ignore
+//! struct Port<'d>(Uart<'d, USART2, DMA1_CH7, DMA1_CH6>);
+//!
+//! impl RtuPort for Port<'_> {
+//!     type Error = usart::Error;
+//!
+//!     async fn write_frame(&mut self, frame: &[u8]) -> Result<(), Self::Error> {
+//!         self.0.write(frame).await
+//!     }
+//!
+//!     async fn read_frame(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+//!         self.0.read_until_idle(buf).await
+//!     }
+//! }
+//!
+//! let uart = Uart::new_with_de(p.USART2, p.PA3, p.PA2, Irqs, p.PA1, p.DMA1_CH7, p.DMA1_CH6, config)?;
+//! let mut slave = RtuSlave::new(Port(uart), 17);
+//! let error = slave.run(&mut registers).await;
+//! ```
+
+use embassy_time::{with_deadline, with_timeout, Duration, Instant, Timer};
+use embedded_io_async::{Read, Write};
+
+use crate::{handle_request, Error, RegisterMap, Transport, BROADCAST, MAX_PDU_LEN};
+
+/// Maximum length of an RTU frame.
+pub const MAX_FRAME_LEN: usize = MAX_PDU_LEN + 3;
+
+/// Serial port sending and receiving whole RTU frames.
+pub trait RtuPort {
+    /// Error of the serial port.
+    type Error;
+
+    /// Send `frame`, after the silence ending the previous frame.
+    async fn write_frame(&mut self, frame: &[u8]) -> Result<(), Self::Error>;
+
+    /// Wait for a frame, reading it to `buf`, and return its length.
+    ///
+    /// The bytes of a frame longer than `buf` are dropped.
+    async fn read_frame(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error>;
+}
+
+/// [`RtuPort`] over a serial port, timing the 3.5 character silences delimiting the frames.
+///
+/// The reads of the serial port are cancelled at the end of each frame, so they must not lose
+/// data when cancelled, as with a buffered UART.
+pub struct TimedPort<P> {
+    port: P,
+    silence: Duration,
+    last_activity: Instant,
+}
+
+impl<P: Read + Write> TimedPort<P> {
+    /// Create a new timed port over `port`, configured at `baudrate`.
+    pub fn new(port: P, baudrate: u32) -> Self {
+        // 3.5 characters of 11 bits, or a fixed 1.75 ms above 19200 baud, as per the specification.
+        let silence = if baudrate > 19200 {
+            Duration::from_micros(1750)
+        } else {
+            Duration::from_micros(38_500_000 / baudrate as u64)
+        };
+        Self {
+            port,
+            silence,
+            last_activity: Instant::now(),
+        }
+    }
+
+    /// Release the serial port.
+    pub fn release(self) -> P {
+        self.port
+    }
+}
+
+impl<P: Read + Write> RtuPort for TimedPort<P> {
+    type Error = P::Error;
+
+    async fn write_frame(&mut self, frame: &[u8]) -> Result<(), Self::Error> {
+        Timer::at(self.last_activity + self.silence).await;
+        self.port.write_all(frame).await?;
+        self.port.flush().await?;
+        self.last_activity = Instant::now();
+        Ok(())
+    }
+
+    async fn read_frame(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let mut len = self.port.read(buf).await?;
+        let mut overflow = [0; 16];
+        loop {
+            self.last_activity = Instant::now();
+            let full = len == buf.len();
+            let dest = if full { &mut overflow[..] } else { &mut buf[len..] };
+            match with_timeout(self.silence, self.port.read(dest)).await {
+                Ok(Ok(0)) | Err(_) => return Ok(len),
+                Ok(Ok(n)) => {
+                    if !full {
+                        len += n;
+                    }
+                }
+                Ok(Err(e)) => return Err(e),
+            }
+        }
+    }
+}
+
+/// [`Transport`] of a [`Master`](crate::Master) over a serial line.
+pub struct RtuTransport<P> {
+    port: P,
+    timeout: Duration,
+}
+
+impl<P: RtuPort> RtuTransport<P> {
+    /// Create a new RTU transport over `port`, waiting at most `timeout` for the responses.
+    pub fn new(port: P, timeout: Duration) -> Self {
+        Self { port, timeout }
+    }
+
+    /// Release the port.
+    pub fn release(self) -> P {
+        self.port
+    }
+}
+
+impl<P: RtuPort> Transport for RtuTransport<P> {
+    type Error = P::Error;
+
+    async fn transact(
+        &mut self,
+        unit: u8,
+        request: &[u8],
+        response: &mut [u8; MAX_PDU_LEN],
+    ) -> Result<usize, Error<Self::Error>> {
+        let mut frame = [0; MAX_FRAME_LEN];
+        let len = encode_frame(unit, request, &mut frame);
+        self.port.write_frame(&frame[..len]).await.map_err(Error::Io)?;
+        if unit == BROADCAST {
+            return Ok(0);
+        }
+
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let len = match with_deadline(deadline, self.port.read_frame(&mut frame)).await {
+                Ok(res) => res.map_err(Error::Io)?,
+                Err(_) => return Err(Error::Timeout),
+            };
+            let (from, pdu) = decode_frame(&frame[..len]).ok_or(Error::InvalidFrame)?;
+            // Late response to a previous request, which timed out.
+            if from != unit {
+                debug!("modbus: dropping frame from unit {}", from);
+                continue;
+            }
+            response[..pdu.len()].copy_from_slice(pdu);
+            return Ok(pdu.len());
+        }
+    }
+}
+
+/// Modbus slave (server) on a serial line.
+pub struct RtuSlave<P> {
+    port: P,
+    unit: u8,
+}
+
+impl<P: RtuPort> RtuSlave<P> {
+    /// Create a new slave answering the requests to `unit`, from 1 to 247.
+    pub fn new(port: P, unit: u8) -> Self {
+        assert!(unit != BROADCAST && unit <= 247);
+        Self { port, unit }
+    }
+
+    /// Release the port.
+    pub fn release(self) -> P {
+        self.port
+    }
+
+    /// Answer the requests from `map`, until the port fails.
+    ///
+    /// The broadcast requests are processed, but not answered, and the frames with a wrong CRC are
+    /// ignored.
+    pub async fn run(&mut self, map: &mut impl RegisterMap) -> P::Error {
+        let mut frame = [0; MAX_FRAME_LEN];
+        let mut response = [0; MAX_PDU_LEN];
+        loop {
+            let len = match self.port.read_frame(&mut frame).await {
+                Ok(len) => len,
+                Err(e) => return e,
+            };
+            let Some((unit, pdu)) = decode_frame(&frame[..len]) else {
+                debug!("modbus: dropping invalid frame");
+                continue;
+            };
+            if unit != self.unit && unit != BROADCAST {
+                continue;
+            }
+
+            let len = handle_request(map, pdu, &mut response);
+            if unit == BROADCAST {
+                continue;
+            }
+            let len = encode_frame(self.unit, &response[..len], &mut frame);
+            if let Err(e) = self.port.write_frame(&frame[..len]).await {
+                return e;
+            }
+        }
+    }
+}
+
+/// Encode the frame of `pdu` to `unit`, returning its length.
+fn encode_frame(unit: u8, pdu: &[u8], frame: &mut [u8; MAX_FRAME_LEN]) -> usize {
+    let len = pdu.len() + 1;
+    frame[0] = unit;
+    frame[1..len].copy_from_slice(pdu);
+    let crc = crc16(&frame[..len]);
+    frame[len..len + 2].copy_from_slice(&crc.to_le_bytes());
+    len + 2
+}
+
+/// Decode the unit address and the PDU of `frame`, `None` if it is too short or its CRC is wrong.
+fn decode_frame(frame: &[u8]) -> Option<(u8, &[u8])> {
+    if frame.len() < 4 {
+        return None;
+    }
+    let (data, crc) = frame.split_at(frame.len() - 2);
+    if crc16(data).to_le_bytes() != crc {
+        return None;
+    }
+    Some((data[0], &data[1..]))
+}
+
+/// CRC-16/MODBUS, sent low byte first.
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for byte in data {
+        crc ^= *byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xA001 } else { crc >> 1 };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use super::*;
+    use crate::{Exception, Master};
+
+    /// Port answering the requests itself, as a slave at unit 1 would.
+    struct Loopback {
+        registers: [u16; 4],
+        response: [u8; MAX_FRAME_LEN],
+        len: usize,
+    }
+
+    impl RegisterMap for [u16; 4] {
+        fn read_holding_registers(&mut self, address: u16, values: &mut [u16]) -> Result<(), Exception> {
+            let registers = self.get(address as usize..address as usize + values.len());
+            values.copy_from_slice(registers.ok_or(Exception::IllegalDataAddress)?);
+            Ok(())
+        }
+    }
+
+    impl RtuPort for Loopback {
+        type Error = Infallible;
+
+        async fn write_frame(&mut self, frame: &[u8]) -> Result<(), Infallible> {
+            let (unit, pdu) = decode_frame(frame).unwrap();
+            let mut response = [0; MAX_PDU_LEN];
+            let len = handle_request(&mut self.registers, pdu, &mut response);
+            self.len = encode_frame(unit, &response[..len], &mut self.response);
+            Ok(())
+        }
+
+        async fn read_frame(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+            buf[..self.len].copy_from_slice(&self.response[..self.len]);
+            Ok(self.len)
+        }
+    }
+
+    #[test]
+    fn computes_crc() {
+        let crc = crc16(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x0A]);
+        assert_eq!(crc.to_le_bytes(), [0xC5, 0xCD]);
+    }
+
+    #[futures_test::test]
+    async fn master_reads_registers() {
+        let port = Loopback {
+            registers: [1, 2, 3, 4],
+            response: [0; MAX_FRAME_LEN],
+            len: 0,
+        };
+        let mut master = Master::new(RtuTransport::new(port, Duration::from_millis(100)));
+
+        let mut values = [0; 2];
+        master.read_holding_registers(1, 2, &mut values).await.unwrap();
+        assert_eq!(values, [3, 4]);
+
+        let res = master.read_holding_registers(1, 3, &mut values).await;
+        assert_eq!(res, Err(Error::Exception(Exception::IllegalDataAddress)));
+    }
+}
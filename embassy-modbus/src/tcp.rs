@@ -0,0 +1,227 @@
+//! Modbus TCP, over a stream.
+//!
+//! Each PDU is prefixed by the 7 byte MBAP header: a transaction identifier matching the
+//! responses to the requests, the protocol identifier, the length of the rest of the frame and
+//! the unit address. The streams are usually `embassy-net` `TcpSocket`s, connected to port 502:
+//!
+//! ```rust,ignore
+//! let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
+//! loop {
+//!     socket.accept(502).await?;
+//!     if let Err(e) = tcp::serve(&mut socket, &mut registers).await {
+//!         warn!("modbus: {:?}", e);
+//!     }
+//!     socket.close();
+//!     socket.flush().await?;
+//! }
+//! ```
+
+use embassy_time::{with_deadline, Duration, Instant};
+use embedded_io_async::{Read, ReadExactError, Write};
+
+use crate::{handle_request, Error, RegisterMap, Transport, MAX_PDU_LEN};
+
+/// Length of the MBAP header.
+const HEADER_LEN: usize = 7;
+
+/// MBAP header.
+struct Header {
+    transaction_id: u16,
+    unit: u8,
+    /// Length of the PDU.
+    len: usize,
+}
+
+/// [`Transport`] of a [`Master`](crate::Master) over a stream.
+///
+/// After a timeout, the stream may hold a partial frame, so the connection should be reopened.
+pub struct TcpTransport<S> {
+    stream: S,
+    transaction_id: u16,
+    timeout: Duration,
+}
+
+impl<S: Read + Write> TcpTransport<S> {
+    /// Create a new TCP transport over `stream`, waiting at most `timeout` for the responses.
+    pub fn new(stream: S, timeout: Duration) -> Self {
+        Self {
+            stream,
+            transaction_id: 0,
+            timeout,
+        }
+    }
+
+    /// Release the stream.
+    pub fn release(self) -> S {
+        self.stream
+    }
+}
+
+impl<S: Read + Write> Transport for TcpTransport<S> {
+    type Error = S::Error;
+
+    async fn transact(
+        &mut self,
+        unit: u8,
+        request: &[u8],
+        response: &mut [u8; MAX_PDU_LEN],
+    ) -> Result<usize, Error<Self::Error>> {
+        self.transaction_id = self.transaction_id.wrapping_add(1);
+        let header = Header {
+            transaction_id: self.transaction_id,
+            unit,
+            len: request.len(),
+        };
+        write_frame(&mut self.stream, &header, request).await?;
+
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let header = match with_deadline(deadline, read_frame(&mut self.stream, response)).await {
+                Ok(res) => res?,
+                Err(_) => return Err(Error::Timeout),
+            };
+            // Late response to a previous request, which timed out.
+            if header.transaction_id != self.transaction_id {
+                debug!("modbus: dropping response to transaction {}", header.transaction_id);
+                continue;
+            }
+            if header.unit != unit {
+                return Err(Error::UnexpectedResponse);
+            }
+            return Ok(header.len);
+        }
+    }
+}
+
+/// Answer the requests received on `stream` from `map`, until it is closed by the master.
+///
+/// The requests are answered whatever their unit address.
+pub async fn serve<S: Read + Write>(mut stream: S, map: &mut impl RegisterMap) -> Result<(), Error<S::Error>> {
+    let mut request = [0; MAX_PDU_LEN];
+    let mut response = [0; MAX_PDU_LEN];
+    loop {
+        let header = match read_frame(&mut stream, &mut request).await {
+            Ok(header) => header,
+            Err(Error::Eof) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let len = handle_request(map, &request[..header.len], &mut response);
+        let header = Header { len, ..header };
+        write_frame(&mut stream, &header, &response[..len]).await?;
+    }
+}
+
+async fn write_frame<S: Write>(stream: &mut S, header: &Header, pdu: &[u8]) -> Result<(), Error<S::Error>> {
+    let mut frame = [0; HEADER_LEN + MAX_PDU_LEN];
+    frame[0..2].copy_from_slice(&header.transaction_id.to_be_bytes());
+    frame[2..4].copy_from_slice(&0u16.to_be_bytes());
+    frame[4..6].copy_from_slice(&(pdu.len() as u16 + 1).to_be_bytes());
+    frame[6] = header.unit;
+    frame[HEADER_LEN..HEADER_LEN + pdu.len()].copy_from_slice(pdu);
+
+    let frame = &frame[..HEADER_LEN + pdu.len()];
+    stream.write_all(frame).await.map_err(Error::Io)?;
+    stream.flush().await.map_err(Error::Io)
+}
+
+/// Read a frame, its PDU to `pdu`, and return its header.
+async fn read_frame<S: Read>(stream: &mut S, pdu: &mut [u8; MAX_PDU_LEN]) -> Result<Header, Error<S::Error>> {
+    let mut header = [0; HEADER_LEN];
+    read_exact(stream, &mut header).await?;
+
+    let protocol = u16::from_be_bytes([header[2], header[3]]);
+    let len = u16::from_be_bytes([header[4], header[5]]) as usize;
+    if protocol != 0 || !(2..=MAX_PDU_LEN + 1).contains(&len) {
+        return Err(Error::InvalidFrame);
+    }
+
+    let len = len - 1;
+    read_exact(stream, &mut pdu[..len]).await?;
+    Ok(Header {
+        transaction_id: u16::from_be_bytes([header[0], header[1]]),
+        unit: header[6],
+        len,
+    })
+}
+
+async fn read_exact<S: Read>(stream: &mut S, buf: &mut [u8]) -> Result<(), Error<S::Error>> {
+    stream.read_exact(buf).await.map_err(|e| match e {
+        ReadExactError::UnexpectedEof => Error::Eof,
+        ReadExactError::Other(e) => Error::Io(e),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use alloc::vec::Vec;
+    use core::convert::Infallible;
+
+    use super::*;
+    use crate::Exception;
+
+    /// Stream reading from `input`, and writing to `output`.
+    struct Stream<'a> {
+        input: &'a [u8],
+        output: Vec<u8>,
+    }
+
+    impl embedded_io_async::ErrorType for Stream<'_> {
+        type Error = Infallible;
+    }
+
+    impl Read for Stream<'_> {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+            self.input.read(buf).await
+        }
+    }
+
+    impl Write for Stream<'_> {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+            self.output.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    struct Inputs;
+
+    impl RegisterMap for Inputs {
+        fn read_input_registers(&mut self, address: u16, values: &mut [u16]) -> Result<(), Exception> {
+            for (i, value) in values.iter_mut().enumerate() {
+                *value = address + i as u16;
+            }
+            Ok(())
+        }
+    }
+
+    #[futures_test::test]
+    async fn serves_until_eof() {
+        let input = [
+            0x12, 0x34, 0x00, 0x00, 0x00, 0x06, 0x01, 0x04, 0x00, 0x10, 0x00, 0x02, // read input registers
+            0x12, 0x35, 0x00, 0x00, 0x00, 0x06, 0x01, 0x03, 0x00, 0x10, 0x00, 0x02, // read holding registers
+        ];
+        let mut stream = Stream {
+            input: &input,
+            output: Vec::new(),
+        };
+        serve(&mut stream, &mut Inputs).await.unwrap();
+
+        let output = [
+            0x12, 0x34, 0x00, 0x00, 0x00, 0x07, 0x01, 0x04, 0x04, 0x00, 0x10, 0x00, 0x11, // registers
+            0x12, 0x35, 0x00, 0x00, 0x00, 0x03, 0x01, 0x83, 0x01, // illegal function
+        ];
+        assert_eq!(stream.output, output);
+    }
+
+    #[futures_test::test]
+    async fn rejects_invalid_header() {
+        let mut stream = Stream {
+            input: &[0x12, 0x34, 0x00, 0x01, 0x00, 0x06, 0x01],
+            output: Vec::new(),
+        };
+        let mut pdu = [0; MAX_PDU_LEN];
+        let res = read_frame(&mut stream, &mut pdu).await;
+        assert!(matches!(res, Err(Error::InvalidFrame)));
+    }
+}
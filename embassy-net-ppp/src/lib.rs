@@ -147,6 +147,36 @@ impl<'d> Runner<'d> {
     }
 }
 
+/// Error returned by [`Runner::run_modem`].
+#[cfg(feature = "modem")]
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ModemRunError<M, E> {
+    /// The modem failed to switch to data mode.
+    Modem(M),
+    /// The PPP connection failed.
+    Run(RunError<E>),
+}
+
+#[cfg(feature = "modem")]
+impl<'d> Runner<'d> {
+    /// Switch `modem` to data mode with the PDP context `cid`, and run PPP over it.
+    ///
+    /// The PDP context must be defined first, with
+    /// [`CellularModem::set_pdp_context`](embassy_at::modem::CellularModem::set_pdp_context).
+    /// Behaves as [`run`](Self::run) otherwise. Call it again to reconnect after an error.
+    pub async fn run_modem<'m, M: embassy_at::modem::CellularModem>(
+        &mut self,
+        modem: &'m mut M,
+        cid: u8,
+        config: ppproto::Config<'_>,
+        on_ipv4_up: impl FnMut(Ipv4Status),
+    ) -> Result<Infallible, ModemRunError<M::Error, <M::Data<'m> as embedded_io_async::ErrorType>::Error>> {
+        let data = modem.data_mode(cid).await.map_err(ModemRunError::Modem)?;
+        self.run(data, config, on_ipv4_up).await.map_err(ModemRunError::Run)
+    }
+}
+
 /// Create a PPP embassy-net driver instance.
 ///
 /// This returns two structs:
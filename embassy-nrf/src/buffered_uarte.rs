@@ -7,6 +7,9 @@
 //! just serial communications.
 //!
 //! Please also see [crate::uarte] to understand when [BufferedUarte] should be used.
+//!
+//! Line-oriented reads with inter-character timeouts, for example for AT-command modems, are
+//! provided for all buffered readers by `embassy_embedded_hal::io::BufReadExt`.
 
 use core::cmp::min;
 use core::future::poll_fn;
@@ -0,0 +1,248 @@
+//! Clock control (CLOCK) driver.
+//!
+//! The HFXO is started on demand with [`Clock::request_hfxo`], for the peripherals needing an
+//! accurate high frequency clock (radio, SAADC, USB...), and stopped again when the last request
+//! is released. While it is stopped, the HFCLK falls back to the low power HFINT oscillator.
+//!
+//! The LFCLK source is selected at init with [`Config::lfclk_source`](crate::config::Config). When it
+//! is the RC oscillator, it must be calibrated against the HFXO periodically, see [`Clock::calibrate`].
+//!
+//! The accuracy the application needs (HFXO for USB, LFCLK accuracy for a radio stack) is checked
+//! at init, see [`ClockRequirements`](crate::config::ClockRequirements). [`Clock::clocks`] reports
+//! the sources actually running.
+
+use core::cell::RefCell;
+use core::future::poll_fn;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::task::Poll;
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::waitqueue::{AtomicWaker, MultiWakerRegistration};
+
+use crate::interrupt::typelevel::Interrupt;
+use crate::{interrupt, pac};
+
+/// Number of HFXO requests keeping it running.
+static HFXO_USERS: AtomicUsize = AtomicUsize::new(0);
+static HFXO_WAKERS: Mutex<CriticalSectionRawMutex, RefCell<MultiWakerRegistration<4>>> = Mutex::const_new(
+    CriticalSectionRawMutex::new(),
+    RefCell::new(MultiWakerRegistration::new()),
+);
+
+static CAL_DONE: AtomicBool = AtomicBool::new(false);
+static CAL_WAKER: AtomicWaker = AtomicWaker::new();
+
+fn regs() -> &'static pac::clock::RegisterBlock {
+    unsafe { &*pac::CLOCK::ptr() }
+}
+
+/// Interrupt handler.
+pub struct InterruptHandler {
+    _private: (),
+}
+
+impl interrupt::typelevel::Handler<interrupt::typelevel::POWER_CLOCK> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        let r = regs();
+
+        if r.events_hfclkstarted.read().bits() != 0 {
+            r.events_hfclkstarted.reset();
+            critical_section::with(|cs| HFXO_WAKERS.borrow(cs).borrow_mut().wake());
+        }
+
+        if r.events_done.read().bits() != 0 {
+            r.events_done.reset();
+            CAL_DONE.store(true, Ordering::Release);
+            CAL_WAKER.wake();
+        }
+    }
+}
+
+/// High frequency clock source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Hfclk {
+    /// Internal RC oscillator (HFINT).
+    Internal,
+    /// External crystal oscillator (HFXO).
+    ExternalXtal,
+}
+
+/// Low frequency clock source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Lfclk {
+    /// Internal RC oscillator.
+    InternalRC,
+    /// Synthesized from the high frequency clock source.
+    Synthesized,
+    /// External crystal oscillator, or external source.
+    ExternalXtal,
+}
+
+/// Clocks currently running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Clocks {
+    /// Source of the high frequency clock.
+    pub hfclk: Hfclk,
+    /// Source of the low frequency clock, `None` if it is stopped.
+    pub lfclk: Option<Lfclk>,
+    /// Number of [`HfxoRequest`]s keeping the HFXO running.
+    pub hfxo_requests: usize,
+}
+
+/// Clock control driver.
+#[derive(Clone, Copy)]
+pub struct Clock {
+    _private: (),
+}
+
+impl Clock {
+    /// Create a new clock control driver.
+    ///
+    /// The `POWER_CLOCK` interrupt can be shared with the `HardwareVbusDetect` of the USB driver, by
+    /// binding both interrupt handlers.
+    pub fn new(
+        _irq: impl interrupt::typelevel::Binding<interrupt::typelevel::POWER_CLOCK, InterruptHandler> + 'static,
+    ) -> Self {
+        let r = regs();
+        r.events_done.reset();
+        r.intenset.write(|w| w.hfclkstarted().set().done().set());
+
+        interrupt::typelevel::POWER_CLOCK::unpend();
+        unsafe { interrupt::typelevel::POWER_CLOCK::enable() };
+
+        Self { _private: () }
+    }
+
+    /// Request the HFXO, starting it if needed, and wait until it is running.
+    ///
+    /// The HFXO keeps running until all the returned requests are dropped.
+    pub async fn request_hfxo(&self) -> HfxoRequest {
+        // Created first, so the HFXO is released if the future is dropped while starting it.
+        let request = HfxoRequest::new();
+
+        poll_fn(|cx| {
+            critical_section::with(|cs| HFXO_WAKERS.borrow(cs).borrow_mut().register(cx.waker()));
+            if hfxo_running() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+
+        request
+    }
+
+    /// Calibrate the LFCLK RC oscillator, starting the HFXO for the duration of the calibration.
+    ///
+    /// The calibration takes about 16 ms, and should be repeated every few seconds, or when the
+    /// temperature changes, for the RC oscillator to stay within 500 ppm. Only one calibration
+    /// may run at a time.
+    pub async fn calibrate(&self) {
+        let _hfxo = self.request_hfxo().await;
+
+        let r = regs();
+        CAL_DONE.store(false, Ordering::Relaxed);
+        r.tasks_cal.write(|w| unsafe { w.bits(1) });
+
+        poll_fn(|cx| {
+            CAL_WAKER.register(cx.waker());
+            if CAL_DONE.load(Ordering::Acquire) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+    }
+
+    /// Calibrate the LFCLK RC oscillator every `interval`, forever.
+    ///
+    /// Nothing is done while the LFCLK runs from another source. Meant to run in its own task,
+    /// 4 seconds being a good interval in most environments.
+    #[cfg(feature = "time")]
+    pub async fn run_calibration(&self, interval: embassy_time::Duration) -> ! {
+        loop {
+            if self.clocks().lfclk == Some(Lfclk::InternalRC) {
+                self.calibrate().await;
+            }
+            embassy_time::Timer::after(interval).await;
+        }
+    }
+
+    /// Get the clocks currently running.
+    pub fn clocks(&self) -> Clocks {
+        let r = regs();
+
+        let hfclk = if r.hfclkstat.read().src().is_xtal() {
+            Hfclk::ExternalXtal
+        } else {
+            Hfclk::Internal
+        };
+
+        let lfclkstat = r.lfclkstat.read();
+        let lfclk = if !lfclkstat.state().is_running() {
+            None
+        } else if lfclkstat.src().is_rc() {
+            Some(Lfclk::InternalRC)
+        } else if lfclkstat.src().is_synth() {
+            Some(Lfclk::Synthesized)
+        } else {
+            Some(Lfclk::ExternalXtal)
+        };
+
+        Clocks {
+            hfclk,
+            lfclk,
+            hfxo_requests: HFXO_USERS.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Request keeping the HFXO running, returned by [`Clock::request_hfxo`].
+///
+/// The HFXO is stopped when the last request is dropped.
+pub struct HfxoRequest {
+    _private: (),
+}
+
+impl HfxoRequest {
+    fn new() -> Self {
+        critical_section::with(|_| {
+            let users = HFXO_USERS.load(Ordering::Relaxed);
+            HFXO_USERS.store(users + 1, Ordering::Relaxed);
+            if users == 0 {
+                // Also started when already running, in case it is still being stopped.
+                // Datasheet says this is likely to take 0.36ms
+                regs().tasks_hfclkstart.write(|w| unsafe { w.bits(1) });
+            }
+        });
+        Self { _private: () }
+    }
+}
+
+impl Drop for HfxoRequest {
+    fn drop(&mut self) {
+        critical_section::with(|_| {
+            let users = HFXO_USERS.load(Ordering::Relaxed) - 1;
+            HFXO_USERS.store(users, Ordering::Relaxed);
+            if users == 0 {
+                regs().tasks_hfclkstop.write(|w| unsafe { w.bits(1) });
+            }
+        });
+    }
+}
+
+fn hfxo_running() -> bool {
+    let stat = regs().hfclkstat.read();
+    stat.src().is_xtal() && stat.state().is_running()
+}
+
+/// Keep the HFXO started at init running, as if it was requested forever.
+pub(crate) fn keep_hfxo() {
+    core::mem::forget(HfxoRequest::new());
+}
@@ -42,6 +42,8 @@ mod time_driver;
 
 #[cfg(not(feature = "nrf51"))]
 pub mod buffered_uarte;
+#[cfg(feature = "_nrf52")]
+pub mod clock;
 pub mod gpio;
 #[cfg(feature = "gpiote")]
 pub mod gpiote;
@@ -72,6 +74,7 @@ pub mod qdec;
 pub mod qspi;
 #[cfg(not(any(feature = "_nrf5340-app", feature = "_nrf9160")))]
 pub mod rng;
+pub mod rtc;
 #[cfg(not(any(feature = "nrf51", feature = "nrf52820", feature = "_nrf5340-net")))]
 pub mod saadc;
 #[cfg(not(feature = "nrf51"))]
@@ -201,6 +204,52 @@ pub mod config {
         ExternalFullSwing,
     }
 
+    impl LfclkSource {
+        /// Nominal error of the source, in ppm, when the HFCLK runs from `hfclk`.
+        ///
+        /// The RC oscillator is within 500 ppm when calibrated regularly, see
+        /// [`Clock::calibrate`](crate::clock::Clock::calibrate). The synthesized clock has the
+        /// accuracy of the HFCLK: 40 ppm from the HFXO, 15000 ppm from the internal oscillator.
+        /// External crystals are assumed to be within 50 ppm.
+        #[cfg_attr(any(feature = "_nrf5340", feature = "_nrf9160"), allow(unused_variables))]
+        pub const fn accuracy_ppm(&self, hfclk: &HfclkSource) -> u32 {
+            match self {
+                Self::InternalRC => 500,
+                #[cfg(not(any(feature = "_nrf5340", feature = "_nrf9160")))]
+                Self::Synthesized => match hfclk {
+                    HfclkSource::Internal => 15_000,
+                    HfclkSource::ExternalXtal => 40,
+                },
+                _ => 50,
+            }
+        }
+    }
+
+    /// Clock accuracy needed by the application, checked by [`init`](crate::init).
+    #[derive(Default)]
+    pub struct ClockRequirements {
+        /// The HFCLK must run from the HFXO, e.g. for USB, which needs an accurate clock.
+        pub hfxo: bool,
+        /// Maximum error of the LFCLK, in ppm, e.g. the sleep clock accuracy a BLE stack assumes
+        /// for its connection interval.
+        pub lfclk_accuracy_ppm: Option<u32>,
+    }
+
+    /// Clock configuration not meeting the [`ClockRequirements`].
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub enum ConfigError {
+        /// The HFXO is required, but the HFCLK runs from the internal oscillator.
+        HfxoRequired,
+        /// The LFCLK source is less accurate than required.
+        LfclkInaccurate {
+            /// Nominal error of the LFCLK source, in ppm.
+            source_ppm: u32,
+            /// Maximum error required, in ppm.
+            required_ppm: u32,
+        },
+    }
+
     /// SWD access port protection setting.
     #[non_exhaustive]
     pub enum Debug {
@@ -260,6 +309,29 @@ pub mod config {
         pub time_interrupt_priority: crate::interrupt::Priority,
         /// Enable or disable the debug port.
         pub debug: Debug,
+        /// Clock accuracy needed by the application. [`init`](crate::init) panics if the clock
+        /// sources do not meet it.
+        pub clock_requirements: ClockRequirements,
+    }
+
+    impl Config {
+        /// Check the clock sources against the [`clock_requirements`](Self::clock_requirements).
+        pub fn validate(&self) -> Result<(), ConfigError> {
+            let req = &self.clock_requirements;
+            if req.hfxo && matches!(self.hfclk_source, HfclkSource::Internal) {
+                return Err(ConfigError::HfxoRequired);
+            }
+            if let Some(required_ppm) = req.lfclk_accuracy_ppm {
+                let source_ppm = self.lfclk_source.accuracy_ppm(&self.hfclk_source);
+                if source_ppm > required_ppm {
+                    return Err(ConfigError::LfclkInaccurate {
+                        source_ppm,
+                        required_ppm,
+                    });
+                }
+            }
+            Ok(())
+        }
     }
 
     impl Default for Config {
@@ -294,6 +366,7 @@ pub mod config {
                 debug: Debug::NotConfigured,
                 #[cfg(not(feature = "_ns"))]
                 debug: Debug::Allowed,
+                clock_requirements: ClockRequirements::default(),
             }
         }
     }
@@ -386,6 +459,10 @@ pub fn init(config: config::Config) -> Peripherals {
     // before doing anything important.
     let peripherals = Peripherals::take();
 
+    if let Err(e) = config.validate() {
+        panic!("Invalid clock configuration: {:?}", e);
+    }
+
     #[allow(unused_mut)]
     let mut needs_reset = false;
 
@@ -502,6 +579,10 @@ pub fn init(config: config::Config) -> Peripherals {
             r.events_hfclkstarted.write(|w| unsafe { w.bits(0) });
             r.tasks_hfclkstart.write(|w| unsafe { w.bits(1) });
             while r.events_hfclkstarted.read().bits() == 0 {}
+
+            // Never stopped by the on-demand HFXO requests.
+            #[cfg(feature = "_nrf52")]
+            clock::keep_hfxo();
         }
     }
 
@@ -0,0 +1,32 @@
+//! Real Time Counter (RTC) instances.
+//!
+//! The RTCs run from the low frequency clock, which makes them the low power choice to trigger
+//! other peripherals through PPI, for example in [`Saadc::run_rtc_sampler`](crate::saadc::Saadc::run_rtc_sampler).
+//!
+//! Note that the time driver uses RTC1 when the `time-driver-rtc1` feature is enabled.
+
+#![macro_use]
+
+use crate::{pac, Peripheral};
+
+pub(crate) mod sealed {
+    use super::*;
+
+    pub trait Instance {
+        fn regs() -> &'static pac::rtc0::RegisterBlock;
+    }
+}
+
+/// RTC instance.
+pub trait Instance: Peripheral<P = Self> + sealed::Instance + 'static + Send {}
+
+macro_rules! impl_rtc {
+    ($type:ident, $pac_type:ident) => {
+        impl crate::rtc::sealed::Instance for peripherals::$type {
+            fn regs() -> &'static pac::rtc0::RegisterBlock {
+                unsafe { &*(pac::$pac_type::ptr() as *const pac::rtc0::RegisterBlock) }
+            }
+        }
+        impl crate::rtc::Instance for peripherals::$type {}
+    };
+}
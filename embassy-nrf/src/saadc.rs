@@ -6,6 +6,7 @@ use core::future::poll_fn;
 use core::sync::atomic::{compiler_fence, Ordering};
 use core::task::Poll;
 
+use embassy_embedded_hal::adc::AdcChannel;
 use embassy_hal_internal::drop::OnDrop;
 use embassy_hal_internal::{impl_peripheral, into_ref, PeripheralRef};
 use embassy_sync::waitqueue::AtomicWaker;
@@ -19,14 +20,19 @@ use saadc::resolution::VAL_A;
 use self::sealed::Input as _;
 use crate::interrupt::InterruptExt;
 use crate::ppi::{ConfigurableChannel, Event, Ppi, Task};
+use crate::rtc::Instance as RtcInstance;
 use crate::timer::{Frequency, Instance as TimerInstance, Timer};
+use crate::util::AutoIdle;
 use crate::{interrupt, pac, peripherals, Peripheral};
 
 /// SAADC error
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[non_exhaustive]
-pub enum Error {}
+pub enum Error {
+    /// The voltage is unknown, because the channel uses VDD as reference.
+    VddReference,
+}
 
 /// Interrupt handler.
 pub struct InterruptHandler {
@@ -55,6 +61,18 @@ impl interrupt::typelevel::Handler<interrupt::typelevel::SAADC> for InterruptHan
 }
 
 static WAKER: AtomicWaker = AtomicWaker::new();
+static AUTO_IDLE: AutoIdle = AutoIdle::new();
+
+fn set_enabled(enabled: bool) {
+    let r = unsafe { &*SAADC::ptr() };
+    r.enable.write(|w| {
+        if enabled {
+            w.enable().enabled()
+        } else {
+            w.enable().disabled()
+        }
+    });
+}
 
 /// Used to configure the SAADC peripheral.
 ///
@@ -135,9 +153,19 @@ pub enum CallbackResult {
     Stop,
 }
 
+/// Why a sampler returned.
+enum SamplerExit {
+    /// The callback asked to stop.
+    Stop,
+    /// The calibration is due.
+    Calibrate,
+}
+
 /// One-shot and continuous SAADC.
 pub struct Saadc<'d, const N: usize> {
     _p: PeripheralRef<'d, peripherals::SAADC>,
+    /// Input range in millivolts and result bits of each channel, `None` with the VDD reference.
+    ranges: [Option<(u32, u32)>; N],
 }
 
 impl<'d, const N: usize> Saadc<'d, N> {
@@ -156,6 +184,7 @@ impl<'d, const N: usize> Saadc<'d, N> {
 
         // Configure channels
         r.enable.write(|w| w.enable().enabled());
+        AUTO_IDLE.set(false, set_enabled);
         r.resolution.write(|w| w.val().variant(resolution.into()));
         r.oversample.write(|w| w.oversample().variant(oversample.into()));
 
@@ -192,15 +221,36 @@ impl<'d, const N: usize> Saadc<'d, N> {
         interrupt::SAADC.unpend();
         unsafe { interrupt::SAADC.enable() };
 
-        Self { _p: saadc }
+        let ranges = core::array::from_fn(|i| {
+            let cc = &channel_configs[i];
+            let (num, den) = cc.gain.ratio();
+            // A differential result has one bit for the sign.
+            let bits = resolution.bits() - cc.n_channel.is_some() as u32;
+            match cc.reference {
+                Reference::INTERNAL => Some((INTERNAL_REFERENCE_MV * den / num, bits)),
+                Reference::VDD1_4 => None,
+            }
+        });
+
+        Self { _p: saadc, ranges }
     }
 
     fn regs() -> &'static saadc::RegisterBlock {
         unsafe { &*SAADC::ptr() }
     }
 
+    /// Enable or disable the auto idle mode.
+    ///
+    /// In the auto idle mode, the peripheral is disabled whenever no calibration or sampling is in
+    /// flight, and enabled again by the next one, cutting the idle current. It is disabled by
+    /// default.
+    pub fn set_auto_idle(&mut self, enabled: bool) {
+        AUTO_IDLE.set(enabled, set_enabled);
+    }
+
     /// Perform SAADC calibration. Completes when done.
     pub async fn calibrate(&self) {
+        let _idle = AUTO_IDLE.wake(set_enabled);
         let r = Self::regs();
 
         // Reset and enable the end event
@@ -233,6 +283,7 @@ impl<'d, const N: usize> Saadc<'d, N> {
     /// consumption remains higher if sampling is not stopped explicitly). Cancellation will
     /// also cause the sampling to be stopped.
     pub async fn sample(&mut self, buf: &mut [i16; N]) {
+        let _idle = AUTO_IDLE.wake(set_enabled);
         // In case the future is dropped, stop the task and wait for it to end.
         let on_drop = OnDrop::new(Self::stop_sampling_immediately);
 
@@ -329,24 +380,127 @@ impl<'d, const N: usize> Saadc<'d, N> {
         self.run_sampler(
             bufs,
             None,
-            || {
-                sample_ppi.enable();
+            None,
+            |enable| {
+                if enable {
+                    sample_ppi.enable();
+                }
             },
             callback,
         )
         .await;
     }
 
+    /// Continuous sampling with double buffers, triggered by an RTC.
+    ///
+    /// This is the low power variant of [`run_task_sampler`](Self::run_task_sampler): the RTC
+    /// runs from the low frequency clock, and triggers the samples and the buffer swaps through
+    /// PPI, so the CPU only wakes up when a buffer is full. The RTC counts at
+    /// 32768Hz / (`prescaler` + 1), and a sample is taken every `sample_counter` ticks. For
+    /// example, 100Hz can be achieved using a prescaler of 0 and a counter threshold of 328
+    /// (99.9Hz), or 1Hz using a prescaler of 327 and a counter threshold of 100.
+    ///
+    /// If `calibration_interval` is set, the SAADC is calibrated again every
+    /// `calibration_interval` buffers, to compensate for the temperature drift of the offset.
+    /// No samples are taken while calibrating, and the next buffer starts afterwards.
+    ///
+    /// The callback and cancellation work as in [`run_task_sampler`](Self::run_task_sampler).
+    /// The RTC is stopped prior to returning.
+    pub async fn run_rtc_sampler<F, R: RtcInstance, const N0: usize>(
+        &mut self,
+        _rtc: &mut R,
+        ppi_ch1: &mut impl ConfigurableChannel,
+        ppi_ch2: &mut impl ConfigurableChannel,
+        prescaler: u16,
+        sample_counter: u32,
+        calibration_interval: Option<u32>,
+        bufs: &mut [[[i16; N]; N0]; 2],
+        mut callback: F,
+    ) where
+        F: FnMut(&[[i16; N]]) -> CallbackResult,
+    {
+        assert!(prescaler < (1 << 12), "RTC prescaler is 12 bits");
+        assert!(
+            sample_counter > 0 && sample_counter < (1 << 24),
+            "RTC counter is 24 bits"
+        );
+
+        let r = Self::regs();
+        let rtc = R::regs();
+
+        // Stop the RTC even if the future is dropped.
+        let _stop_rtc = OnDrop::new(|| {
+            let rtc = R::regs();
+            rtc.tasks_stop.write(|w| unsafe { w.bits(1) });
+            rtc.evtenclr.write(|w| w.compare0().clear());
+        });
+
+        rtc.tasks_stop.write(|w| unsafe { w.bits(1) });
+        rtc.tasks_clear.write(|w| unsafe { w.bits(1) });
+        rtc.prescaler.write(|w| unsafe { w.prescaler().bits(prescaler) });
+        rtc.cc[0].write(|w| unsafe { w.compare().bits(sample_counter) });
+        rtc.events_compare[0].reset();
+        // RTC events are only routed to PPI when enabled in EVTEN.
+        rtc.evtenset.write(|w| w.compare0().set());
+
+        let mut start_ppi =
+            Ppi::new_one_to_one(ppi_ch1, Event::from_reg(&r.events_end), Task::from_reg(&r.tasks_start));
+
+        // The RTC has no shortcut to clear on compare, so the PPI channel forks to the clear task.
+        let mut sample_ppi = Ppi::new_one_to_two(
+            ppi_ch2,
+            Event::from_reg(&rtc.events_compare[0]),
+            Task::from_reg(&r.tasks_sample),
+            Task::from_reg(&rtc.tasks_clear),
+        );
+
+        rtc.tasks_start.write(|w| unsafe { w.bits(1) });
+
+        loop {
+            start_ppi.enable();
+            let exit = self
+                .run_sampler(
+                    bufs,
+                    None,
+                    calibration_interval,
+                    |enable| {
+                        if enable {
+                            sample_ppi.enable();
+                        } else {
+                            // Don't restart the conversions when stopping.
+                            sample_ppi.disable();
+                            start_ppi.disable();
+                        }
+                    },
+                    &mut callback,
+                )
+                .await;
+
+            match exit {
+                SamplerExit::Stop => return,
+                SamplerExit::Calibrate => self.calibrate().await,
+            }
+        }
+    }
+
+    /// Run the double buffered sampling until the callback stops it, or `calibration_interval`
+    /// buffers were sampled.
+    ///
+    /// `trigger` is called with `true` once the sampling started, to enable the sample triggers,
+    /// and with `false` before stopping for a calibration, to disable them.
     async fn run_sampler<I, F, const N0: usize>(
         &mut self,
         bufs: &mut [[[i16; N]; N0]; 2],
         sample_rate_divisor: Option<u16>,
-        mut init: I,
+        calibration_interval: Option<u32>,
+        mut trigger: I,
         mut callback: F,
-    ) where
-        I: FnMut(),
+    ) -> SamplerExit
+    where
+        I: FnMut(bool),
         F: FnMut(&[[i16; N]]) -> CallbackResult,
     {
+        let _idle = AUTO_IDLE.wake(set_enabled);
         // In case the future is dropped, stop the task and wait for it to end.
         let on_drop = OnDrop::new(Self::stop_sampling_immediately);
 
@@ -393,6 +547,7 @@ impl<'d, const N: usize> Saadc<'d, N> {
         let mut inited = false;
 
         let mut current_buffer = 0;
+        let mut buffers_sampled = 0;
 
         // Wait for events and complete when the sampler indicates it has had enough.
         let r = poll_fn(|cx| {
@@ -412,9 +567,15 @@ impl<'d, const N: usize> Saadc<'d, N> {
                         current_buffer = next_buffer;
                     }
                     CallbackResult::Stop => {
-                        return Poll::Ready(());
+                        return Poll::Ready(SamplerExit::Stop);
                     }
                 }
+
+                buffers_sampled += 1;
+                if calibration_interval == Some(buffers_sampled) {
+                    trigger(false);
+                    return Poll::Ready(SamplerExit::Calibrate);
+                }
             }
 
             if r.events_started.read().bits() != 0 {
@@ -422,7 +583,7 @@ impl<'d, const N: usize> Saadc<'d, N> {
                 r.intenset.write(|w| w.started().set());
 
                 if !inited {
-                    init();
+                    trigger(true);
                     inited = true;
                 }
 
@@ -474,7 +635,23 @@ impl<'d> Saadc<'d, 1> {
     ) where
         S: FnMut(&[[i16; 1]]) -> CallbackResult,
     {
-        self.run_sampler(bufs, Some(sample_rate_divisor), || {}, sampler).await;
+        self.run_sampler(bufs, Some(sample_rate_divisor), None, |_| {}, sampler)
+            .await;
+    }
+}
+
+impl<'d> AdcChannel for Saadc<'d, 1> {
+    type Error = Error;
+
+    /// Sample the channel, and convert the result with its gain and the internal reference.
+    ///
+    /// Fails with the VDD reference, whose voltage is unknown.
+    async fn read_mv(&mut self) -> Result<i32, Error> {
+        let (range_mv, bits) = self.ranges[0].ok_or(Error::VddReference)?;
+
+        let mut buf = [0; 1];
+        self.sample(&mut buf).await;
+        Ok((buf[0] as i64 * range_mv as i64 >> bits) as i32)
     }
 }
 
@@ -500,6 +677,22 @@ impl From<Gain> for GAIN_A {
     }
 }
 
+impl Gain {
+    /// Gain as a `(numerator, denominator)` ratio.
+    const fn ratio(&self) -> (u32, u32) {
+        match self {
+            Gain::GAIN1_6 => (1, 6),
+            Gain::GAIN1_5 => (1, 5),
+            Gain::GAIN1_4 => (1, 4),
+            Gain::GAIN1_3 => (1, 3),
+            Gain::GAIN1_2 => (1, 2),
+            Gain::GAIN1 => (1, 1),
+            Gain::GAIN2 => (2, 1),
+            Gain::GAIN4 => (4, 1),
+        }
+    }
+}
+
 /// Gain control
 #[non_exhaustive]
 #[derive(Clone, Copy)]
@@ -531,6 +724,9 @@ impl From<Reference> for REFSEL_A {
     }
 }
 
+/// Voltage of the internal reference.
+const INTERNAL_REFERENCE_MV: u32 = 600;
+
 /// Reference control
 #[non_exhaustive]
 #[derive(Clone, Copy)]
@@ -648,6 +844,17 @@ impl From<Resolution> for VAL_A {
     }
 }
 
+impl Resolution {
+    const fn bits(&self) -> u32 {
+        match self {
+            Resolution::_8BIT => 8,
+            Resolution::_10BIT => 10,
+            Resolution::_12BIT => 12,
+            Resolution::_14BIT => 14,
+        }
+    }
+}
+
 /// Set the resolution
 #[non_exhaustive]
 #[derive(Clone, Copy)]
@@ -17,7 +17,7 @@ use crate::chip::FORCE_COPY_BUFFER_SIZE;
 use crate::gpio::sealed::Pin as _;
 use crate::gpio::{self, AnyPin, Pin as GpioPin, PselBits};
 use crate::interrupt::typelevel::Interrupt;
-use crate::util::{slice_in_ram_or, slice_ptr_parts, slice_ptr_parts_mut};
+use crate::util::{slice_in_ram_or, slice_ptr_parts, slice_ptr_parts_mut, AutoIdle};
 use crate::{interrupt, pac, Peripheral};
 
 /// SPIM error
@@ -194,6 +194,7 @@ impl<'d, T: Instance> Spim<'d, T> {
 
         // Enable SPIM instance.
         r.enable.write(|w| w.enable().enabled());
+        T::state().auto_idle.set(false, set_enabled::<T>);
 
         let mut spim = Self { _p: spim };
 
@@ -209,6 +210,14 @@ impl<'d, T: Instance> Spim<'d, T> {
         spim
     }
 
+    /// Enable or disable the auto idle mode.
+    ///
+    /// In the auto idle mode, the peripheral is disabled whenever no transaction is in flight, and
+    /// enabled again by the next one, cutting the idle current. It is disabled by default.
+    pub fn set_auto_idle(&mut self, enabled: bool) {
+        T::state().auto_idle.set(enabled, set_enabled::<T>);
+    }
+
     fn prepare(&mut self, rx: *mut [u8], tx: *const [u8]) -> Result<(), Error> {
         slice_in_ram_or(tx, Error::BufferNotInRAM)?;
         // NOTE: RAM slice check for rx is not necessary, as a mutable
@@ -257,6 +266,7 @@ impl<'d, T: Instance> Spim<'d, T> {
     }
 
     fn blocking_inner_from_ram(&mut self, rx: *mut [u8], tx: *const [u8]) -> Result<(), Error> {
+        let _idle = T::state().auto_idle.wake(set_enabled::<T>);
         self.prepare(rx, tx)?;
 
         #[cfg(feature = "_nrf52832_anomaly_109")]
@@ -284,6 +294,7 @@ impl<'d, T: Instance> Spim<'d, T> {
     }
 
     async fn async_inner_from_ram(&mut self, rx: *mut [u8], tx: *const [u8]) -> Result<(), Error> {
+        let _idle = T::state().auto_idle.wake(set_enabled::<T>);
         self.prepare(rx, tx)?;
 
         #[cfg(feature = "_nrf52832_anomaly_109")]
@@ -438,6 +449,16 @@ impl<'d, T: Instance> Drop for Spim<'d, T> {
     }
 }
 
+fn set_enabled<T: Instance>(enabled: bool) {
+    T::regs().enable.write(|w| {
+        if enabled {
+            w.enable().enabled()
+        } else {
+            w.enable().disabled()
+        }
+    });
+}
+
 pub(crate) mod sealed {
     #[cfg(feature = "_nrf52832_anomaly_109")]
     use core::sync::atomic::AtomicU8;
@@ -448,6 +469,7 @@ pub(crate) mod sealed {
 
     pub struct State {
         pub waker: AtomicWaker,
+        pub auto_idle: AutoIdle,
         #[cfg(feature = "_nrf52832_anomaly_109")]
         pub rx: AtomicU8,
         #[cfg(feature = "_nrf52832_anomaly_109")]
@@ -458,6 +480,7 @@ pub(crate) mod sealed {
         pub const fn new() -> Self {
             Self {
                 waker: AtomicWaker::new(),
+                auto_idle: AutoIdle::new(),
                 #[cfg(feature = "_nrf52832_anomaly_109")]
                 rx: AtomicU8::new(0),
                 #[cfg(feature = "_nrf52832_anomaly_109")]
@@ -3,6 +3,7 @@
 #![macro_use]
 use core::future::poll_fn;
 use core::marker::PhantomData;
+use core::ptr;
 use core::sync::atomic::{compiler_fence, Ordering};
 use core::task::Poll;
 
@@ -29,6 +30,8 @@ pub enum Error {
     RxBufferTooLong,
     /// EasyDMA can only read from data memory, read only buffers in flash will fail.
     BufferNotInRAM,
+    /// The frame queue is full.
+    QueueFull,
 }
 
 /// SPIS configuration.
@@ -78,6 +81,12 @@ impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandl
         let r = T::regs();
         let s = T::state();
 
+        if !s.queue.slots.load(Ordering::Relaxed).is_null() {
+            s.queue.on_interrupt(r);
+            s.waker.wake();
+            return;
+        }
+
         if r.events_end.read().bits() != 0 {
             s.waker.wake();
             r.intenclr.write(|w| w.end().clear());
@@ -450,21 +459,286 @@ impl<'d, T: Instance> Drop for Spis<'d, T> {
     }
 }
 
+/// Slot of the frame queue of [`SpisQueue`].
+#[derive(Clone, Copy)]
+pub struct QueueSlot {
+    rx: *mut u8,
+    rx_len: usize,
+    tx: *mut u8,
+    tx_len: usize,
+    n_rx: usize,
+    n_tx: usize,
+}
+
+impl QueueSlot {
+    /// Create an empty slot.
+    pub const fn new() -> Self {
+        Self {
+            rx: ptr::null_mut(),
+            rx_len: 0,
+            tx: ptr::null_mut(),
+            tx_len: 0,
+            n_rx: 0,
+            n_tx: 0,
+        }
+    }
+}
+
+impl Default for QueueSlot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Frame transferred by [`SpisQueue`], with the buffers it was pushed with.
+pub struct Frame<'d> {
+    /// Receive buffer.
+    pub rx: &'d mut [u8],
+    /// Transmit buffer.
+    pub tx: &'d mut [u8],
+    /// Number of bytes received, at most the length of `rx`.
+    pub n_rx: usize,
+    /// Number of bytes transmitted, at most the length of `tx`.
+    pub n_tx: usize,
+}
+
+/// SPIS frame queue.
+///
+/// Each assertion of CS by the master transfers one frame, from a queue of buffers: as soon as a
+/// frame ends, the interrupt handler hands the next queued buffers to the SPIS, without waiting for
+/// the task. Back-to-back transactions of the master, such as the polling of a sensor hub, are then
+/// not lost while the task processes the previous frame.
+///
+/// When the queue is empty, the transactions of the master are ignored by the SPIS, which sends the
+/// [`Config::def`] byte.
+pub struct SpisQueue<'d, T: Instance> {
+    _spis: Spis<'d, T>,
+    slots: *mut QueueSlot,
+    capacity: usize,
+    _buffers: PhantomData<&'d mut [u8]>,
+}
+
+impl<'d, T: Instance> SpisQueue<'d, T> {
+    /// Create a new frame queue, with one slot per queued frame.
+    ///
+    /// The end of the transfers acquires the semaphore, regardless of [`Config::auto_acquire`].
+    pub fn new(spis: Spis<'d, T>, slots: &'d mut [QueueSlot]) -> Self {
+        assert!(!slots.is_empty());
+
+        let r = T::regs();
+        let q = &T::state().queue;
+
+        r.intenclr.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+        r.shorts.write(|w| w.end_acquire().enabled());
+        r.events_end.reset();
+        r.events_acquired.reset();
+
+        q.len.store(slots.len(), Ordering::Relaxed);
+        q.pushed.store(0, Ordering::Relaxed);
+        q.completed.store(0, Ordering::Relaxed);
+        q.popped.store(0, Ordering::Relaxed);
+        q.loaded.store(false, Ordering::Relaxed);
+        q.slots.store(slots.as_mut_ptr(), Ordering::Release);
+
+        r.intenset.write(|w| w.end().set().acquired().set());
+
+        Self {
+            _spis: spis,
+            slots: slots.as_mut_ptr(),
+            capacity: slots.len(),
+            _buffers: PhantomData,
+        }
+    }
+
+    /// Queue the buffers of a frame.
+    pub fn push(&mut self, rx: &'d mut [u8], tx: &'d mut [u8]) -> Result<(), Error> {
+        let q = &T::state().queue;
+        let pushed = q.pushed.load(Ordering::Relaxed);
+        if q.count(pushed, q.popped.load(Ordering::Acquire)) == self.capacity {
+            return Err(Error::QueueFull);
+        }
+
+        let slot = QueueSlot {
+            rx: rx.as_mut_ptr(),
+            rx_len: rx.len(),
+            tx: tx.as_mut_ptr(),
+            tx_len: tx.len(),
+            n_rx: 0,
+            n_tx: 0,
+        };
+        unsafe { self.slots.add(pushed % self.capacity).write_volatile(slot) };
+        q.pushed.store(q.next(pushed), Ordering::Release);
+
+        // The interrupt handler hands the buffers to the SPIS.
+        T::Interrupt::pend();
+        Ok(())
+    }
+
+    /// Number of frames queued or completed, and not yet returned by [`wait_frame`](Self::wait_frame).
+    pub fn len(&self) -> usize {
+        let q = &T::state().queue;
+        q.count(q.pushed.load(Ordering::Relaxed), q.popped.load(Ordering::Relaxed))
+    }
+
+    /// Return whether no frames are queued or completed.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Return whether the queue is full.
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity
+    }
+
+    /// Wait for the next frame to be transferred, and return its buffers.
+    ///
+    /// The frames are returned in the order they were pushed. This waits forever if the queue is empty.
+    pub async fn wait_frame(&mut self) -> Frame<'d> {
+        let s = T::state();
+        let q = &s.queue;
+        let popped = q.popped.load(Ordering::Relaxed);
+
+        poll_fn(|cx| {
+            s.waker.register(cx.waker());
+            if q.completed.load(Ordering::Acquire) != popped {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+
+        compiler_fence(Ordering::SeqCst);
+
+        let slot = unsafe { self.slots.add(popped % self.capacity).read_volatile() };
+        q.popped.store(q.next(popped), Ordering::Release);
+
+        Frame {
+            rx: unsafe { core::slice::from_raw_parts_mut(slot.rx, slot.rx_len) },
+            tx: unsafe { core::slice::from_raw_parts_mut(slot.tx, slot.tx_len) },
+            n_rx: slot.n_rx,
+            n_tx: slot.n_tx,
+        }
+    }
+}
+
+impl<'d, T: Instance> Drop for SpisQueue<'d, T> {
+    fn drop(&mut self) {
+        let r = T::regs();
+        r.intenclr.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+
+        // Take the buffers back from the SPIS, at the end of the current transaction if any.
+        if r.semstat.read().bits() != 1 {
+            r.tasks_acquire.write(|w| unsafe { w.bits(1) });
+            while r.semstat.read().bits() != 1 {}
+        }
+        compiler_fence(Ordering::SeqCst);
+
+        T::state().queue.slots.store(ptr::null_mut(), Ordering::Relaxed);
+    }
+}
+
 pub(crate) mod sealed {
+    use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize};
+
     use embassy_sync::waitqueue::AtomicWaker;
 
     use super::*;
 
     pub struct State {
         pub waker: AtomicWaker,
+        pub queue: QueueState,
     }
 
     impl State {
         pub const fn new() -> Self {
             Self {
                 waker: AtomicWaker::new(),
+                queue: QueueState::new(),
+            }
+        }
+    }
+
+    /// State of the frame queue, shared with the interrupt handler.
+    ///
+    /// The frames are counted modulo twice the number of slots, to tell a full queue from an
+    /// empty one. The slots from `popped` to `completed` hold the completed frames, and the slots
+    /// from `completed` to `pushed` the queued ones, the first of which is loaded in the SPIS.
+    pub struct QueueState {
+        pub slots: AtomicPtr<QueueSlot>,
+        pub len: AtomicUsize,
+        pub pushed: AtomicUsize,
+        pub completed: AtomicUsize,
+        pub popped: AtomicUsize,
+        pub loaded: AtomicBool,
+    }
+
+    impl QueueState {
+        pub const fn new() -> Self {
+            Self {
+                slots: AtomicPtr::new(ptr::null_mut()),
+                len: AtomicUsize::new(0),
+                pushed: AtomicUsize::new(0),
+                completed: AtomicUsize::new(0),
+                popped: AtomicUsize::new(0),
+                loaded: AtomicBool::new(false),
             }
         }
+
+        pub fn next(&self, index: usize) -> usize {
+            (index + 1) % (2 * self.len.load(Ordering::Relaxed))
+        }
+
+        pub fn count(&self, end: usize, start: usize) -> usize {
+            let modulo = 2 * self.len.load(Ordering::Relaxed);
+            (end + modulo - start) % modulo
+        }
+
+        pub fn on_interrupt(&self, r: &pac::spis0::RegisterBlock) {
+            let slots = self.slots.load(Ordering::Acquire);
+            let len = self.len.load(Ordering::Relaxed);
+
+            if r.events_end.read().bits() != 0 {
+                r.events_end.reset();
+                if self.loaded.load(Ordering::Relaxed) {
+                    let completed = self.completed.load(Ordering::Relaxed);
+                    unsafe {
+                        let slot = slots.add(completed % len);
+                        (*slot).n_rx = r.rxd.amount.read().bits() as usize;
+                        (*slot).n_tx = r.txd.amount.read().bits() as usize;
+                    }
+                    self.loaded.store(false, Ordering::Relaxed);
+                    compiler_fence(Ordering::SeqCst);
+                    self.completed.store(self.next(completed), Ordering::Release);
+                }
+            }
+
+            if r.events_acquired.read().bits() != 0 {
+                r.events_acquired.reset();
+            }
+
+            let completed = self.completed.load(Ordering::Relaxed);
+            if self.loaded.load(Ordering::Relaxed) || completed == self.pushed.load(Ordering::Acquire) {
+                return;
+            }
+
+            if r.semstat.read().bits() != 1 {
+                // Load the frame once the semaphore is acquired.
+                r.tasks_acquire.write(|w| unsafe { w.bits(1) });
+                return;
+            }
+
+            let slot = unsafe { slots.add(completed % len).read_volatile() };
+            r.txd.ptr.write(|w| unsafe { w.ptr().bits(slot.tx as _) });
+            r.txd.maxcnt.write(|w| unsafe { w.maxcnt().bits(slot.tx_len as _) });
+            r.rxd.ptr.write(|w| unsafe { w.ptr().bits(slot.rx as _) });
+            r.rxd.maxcnt.write(|w| unsafe { w.maxcnt().bits(slot.rx_len as _) });
+            r.status.write(|w| w.overflow().clear().overread().clear());
+            self.loaded.store(true, Ordering::Relaxed);
+
+            compiler_fence(Ordering::SeqCst);
+            r.tasks_release.write(|w| unsafe { w.bits(1) });
+        }
     }
 
     pub trait Instance {
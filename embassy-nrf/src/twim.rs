@@ -17,7 +17,7 @@ use embassy_time::{Duration, Instant};
 use crate::chip::{EASY_DMA_SIZE, FORCE_COPY_BUFFER_SIZE};
 use crate::gpio::Pin as GpioPin;
 use crate::interrupt::typelevel::Interrupt;
-use crate::util::{slice_in_ram, slice_in_ram_or};
+use crate::util::{slice_in_ram, slice_in_ram_or, AutoIdle};
 use crate::{gpio, interrupt, pac, Peripheral};
 
 /// TWI frequency
@@ -166,6 +166,7 @@ impl<'d, T: Instance> Twim<'d, T> {
 
         // Enable TWIM instance.
         r.enable.write(|w| w.enable().enabled());
+        T::state().auto_idle.set(false, set_enabled::<T>);
 
         let mut twim = Self { _p: twim };
 
@@ -181,6 +182,14 @@ impl<'d, T: Instance> Twim<'d, T> {
         twim
     }
 
+    /// Enable or disable the auto idle mode.
+    ///
+    /// In the auto idle mode, the peripheral is disabled whenever no transaction is in flight, and
+    /// enabled again by the next one, cutting the idle current. It is disabled by default.
+    pub fn set_auto_idle(&mut self, enabled: bool) {
+        T::state().auto_idle.set(enabled, set_enabled::<T>);
+    }
+
     /// Set TX buffer, checking that it is in RAM and has suitable length.
     unsafe fn set_tx_buffer(&mut self, buffer: &[u8]) -> Result<(), Error> {
         slice_in_ram_or(buffer, Error::BufferNotInRAM)?;
@@ -493,6 +502,7 @@ impl<'d, T: Instance> Twim<'d, T> {
     /// The buffer must have a length of at most 255 bytes on the nRF52832
     /// and at most 65535 bytes on the nRF52840.
     pub fn blocking_write(&mut self, address: u8, buffer: &[u8]) -> Result<(), Error> {
+        let _idle = T::state().auto_idle.wake(set_enabled::<T>);
         self.setup_write(address, buffer, false)?;
         self.blocking_wait();
         compiler_fence(SeqCst);
@@ -503,6 +513,7 @@ impl<'d, T: Instance> Twim<'d, T> {
 
     /// Same as [`blocking_write`](Twim::blocking_write) but will fail instead of copying data into RAM. Consult the module level documentation to learn more.
     pub fn blocking_write_from_ram(&mut self, address: u8, buffer: &[u8]) -> Result<(), Error> {
+        let _idle = T::state().auto_idle.wake(set_enabled::<T>);
         self.setup_write_from_ram(address, buffer, false)?;
         self.blocking_wait();
         compiler_fence(SeqCst);
@@ -516,6 +527,7 @@ impl<'d, T: Instance> Twim<'d, T> {
     /// The buffer must have a length of at most 255 bytes on the nRF52832
     /// and at most 65535 bytes on the nRF52840.
     pub fn blocking_read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Error> {
+        let _idle = T::state().auto_idle.wake(set_enabled::<T>);
         self.setup_read(address, buffer, false)?;
         self.blocking_wait();
         compiler_fence(SeqCst);
@@ -530,6 +542,7 @@ impl<'d, T: Instance> Twim<'d, T> {
     /// The buffers must have a length of at most 255 bytes on the nRF52832
     /// and at most 65535 bytes on the nRF52840.
     pub fn blocking_write_read(&mut self, address: u8, wr_buffer: &[u8], rd_buffer: &mut [u8]) -> Result<(), Error> {
+        let _idle = T::state().auto_idle.wake(set_enabled::<T>);
         self.setup_write_read(address, wr_buffer, rd_buffer, false)?;
         self.blocking_wait();
         compiler_fence(SeqCst);
@@ -546,6 +559,7 @@ impl<'d, T: Instance> Twim<'d, T> {
         wr_buffer: &[u8],
         rd_buffer: &mut [u8],
     ) -> Result<(), Error> {
+        let _idle = T::state().auto_idle.wake(set_enabled::<T>);
         self.setup_write_read_from_ram(address, wr_buffer, rd_buffer, false)?;
         self.blocking_wait();
         compiler_fence(SeqCst);
@@ -562,6 +576,7 @@ impl<'d, T: Instance> Twim<'d, T> {
     /// See [`blocking_write`].
     #[cfg(feature = "time")]
     pub fn blocking_write_timeout(&mut self, address: u8, buffer: &[u8], timeout: Duration) -> Result<(), Error> {
+        let _idle = T::state().auto_idle.wake(set_enabled::<T>);
         self.setup_write(address, buffer, false)?;
         self.blocking_wait_timeout(timeout)?;
         compiler_fence(SeqCst);
@@ -578,6 +593,7 @@ impl<'d, T: Instance> Twim<'d, T> {
         buffer: &[u8],
         timeout: Duration,
     ) -> Result<(), Error> {
+        let _idle = T::state().auto_idle.wake(set_enabled::<T>);
         self.setup_write_from_ram(address, buffer, false)?;
         self.blocking_wait_timeout(timeout)?;
         compiler_fence(SeqCst);
@@ -592,6 +608,7 @@ impl<'d, T: Instance> Twim<'d, T> {
     /// and at most 65535 bytes on the nRF52840.
     #[cfg(feature = "time")]
     pub fn blocking_read_timeout(&mut self, address: u8, buffer: &mut [u8], timeout: Duration) -> Result<(), Error> {
+        let _idle = T::state().auto_idle.wake(set_enabled::<T>);
         self.setup_read(address, buffer, false)?;
         self.blocking_wait_timeout(timeout)?;
         compiler_fence(SeqCst);
@@ -613,6 +630,7 @@ impl<'d, T: Instance> Twim<'d, T> {
         rd_buffer: &mut [u8],
         timeout: Duration,
     ) -> Result<(), Error> {
+        let _idle = T::state().auto_idle.wake(set_enabled::<T>);
         self.setup_write_read(address, wr_buffer, rd_buffer, false)?;
         self.blocking_wait_timeout(timeout)?;
         compiler_fence(SeqCst);
@@ -631,6 +649,7 @@ impl<'d, T: Instance> Twim<'d, T> {
         rd_buffer: &mut [u8],
         timeout: Duration,
     ) -> Result<(), Error> {
+        let _idle = T::state().auto_idle.wake(set_enabled::<T>);
         self.setup_write_read_from_ram(address, wr_buffer, rd_buffer, false)?;
         self.blocking_wait_timeout(timeout)?;
         compiler_fence(SeqCst);
@@ -647,6 +666,7 @@ impl<'d, T: Instance> Twim<'d, T> {
     /// The buffer must have a length of at most 255 bytes on the nRF52832
     /// and at most 65535 bytes on the nRF52840.
     pub async fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Error> {
+        let _idle = T::state().auto_idle.wake(set_enabled::<T>);
         self.setup_read(address, buffer, true)?;
         self.async_wait().await;
         compiler_fence(SeqCst);
@@ -660,6 +680,7 @@ impl<'d, T: Instance> Twim<'d, T> {
     /// The buffer must have a length of at most 255 bytes on the nRF52832
     /// and at most 65535 bytes on the nRF52840.
     pub async fn write(&mut self, address: u8, buffer: &[u8]) -> Result<(), Error> {
+        let _idle = T::state().auto_idle.wake(set_enabled::<T>);
         self.setup_write(address, buffer, true)?;
         self.async_wait().await;
         compiler_fence(SeqCst);
@@ -670,6 +691,7 @@ impl<'d, T: Instance> Twim<'d, T> {
 
     /// Same as [`write`](Twim::write) but will fail instead of copying data into RAM. Consult the module level documentation to learn more.
     pub async fn write_from_ram(&mut self, address: u8, buffer: &[u8]) -> Result<(), Error> {
+        let _idle = T::state().auto_idle.wake(set_enabled::<T>);
         self.setup_write_from_ram(address, buffer, true)?;
         self.async_wait().await;
         compiler_fence(SeqCst);
@@ -684,6 +706,7 @@ impl<'d, T: Instance> Twim<'d, T> {
     /// The buffers must have a length of at most 255 bytes on the nRF52832
     /// and at most 65535 bytes on the nRF52840.
     pub async fn write_read(&mut self, address: u8, wr_buffer: &[u8], rd_buffer: &mut [u8]) -> Result<(), Error> {
+        let _idle = T::state().auto_idle.wake(set_enabled::<T>);
         self.setup_write_read(address, wr_buffer, rd_buffer, true)?;
         self.async_wait().await;
         compiler_fence(SeqCst);
@@ -700,6 +723,7 @@ impl<'d, T: Instance> Twim<'d, T> {
         wr_buffer: &[u8],
         rd_buffer: &mut [u8],
     ) -> Result<(), Error> {
+        let _idle = T::state().auto_idle.wake(set_enabled::<T>);
         self.setup_write_read_from_ram(address, wr_buffer, rd_buffer, true)?;
         self.async_wait().await;
         compiler_fence(SeqCst);
@@ -727,17 +751,29 @@ impl<'a, T: Instance> Drop for Twim<'a, T> {
     }
 }
 
+fn set_enabled<T: Instance>(enabled: bool) {
+    T::regs().enable.write(|w| {
+        if enabled {
+            w.enable().enabled()
+        } else {
+            w.enable().disabled()
+        }
+    });
+}
+
 pub(crate) mod sealed {
     use super::*;
 
     pub struct State {
         pub end_waker: AtomicWaker,
+        pub auto_idle: AutoIdle,
     }
 
     impl State {
         pub const fn new() -> Self {
             Self {
                 end_waker: AtomicWaker::new(),
+                auto_idle: AutoIdle::new(),
             }
         }
     }
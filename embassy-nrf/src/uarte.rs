@@ -30,7 +30,7 @@ use crate::gpio::{self, AnyPin, Pin as GpioPin, PselBits};
 use crate::interrupt::typelevel::Interrupt;
 use crate::ppi::{AnyConfigurableChannel, ConfigurableChannel, Event, Ppi, Task};
 use crate::timer::{Frequency, Instance as TimerInstance, Timer};
-use crate::util::slice_in_ram_or;
+use crate::util::{slice_in_ram_or, AutoIdle};
 use crate::{interrupt, pac, Peripheral};
 
 /// UARTE config.
@@ -177,6 +177,7 @@ impl<'d, T: Instance> Uarte<'d, T> {
             _ => panic!("RTS and CTS pins must be either both set or none set."),
         };
         configure(r, config, hardware_flow_control);
+        T::state().auto_idle.set(false, set_enabled::<T>);
 
         let s = T::state();
         s.tx_rx_refcount.store(2, Ordering::Relaxed);
@@ -208,6 +209,15 @@ impl<'d, T: Instance> Uarte<'d, T> {
         (self.tx, self.rx.with_idle(timer, ppi_ch1, ppi_ch2))
     }
 
+    /// Enable or disable the auto idle mode.
+    ///
+    /// In the auto idle mode, the peripheral is disabled whenever no transfer is in flight, and
+    /// enabled again by the next one, cutting the idle current. The bytes received while no read
+    /// is in flight are lost. It is disabled by default.
+    pub fn set_auto_idle(&mut self, enabled: bool) {
+        T::state().auto_idle.set(enabled, set_enabled::<T>);
+    }
+
     /// Return the endtx event for use with PPI
     pub fn event_endtx(&self) -> Event {
         let r = T::regs();
@@ -314,6 +324,7 @@ impl<'d, T: Instance> UarteTx<'d, T> {
 
         let hardware_flow_control = cts.is_some();
         configure(r, config, hardware_flow_control);
+        T::state().auto_idle.set(false, set_enabled::<T>);
 
         T::Interrupt::unpend();
         unsafe { T::Interrupt::enable() };
@@ -324,6 +335,17 @@ impl<'d, T: Instance> UarteTx<'d, T> {
         Self { _p: uarte }
     }
 
+    /// Enable or disable the auto idle mode.
+    ///
+    /// In the auto idle mode, the peripheral is disabled whenever no transfer is in flight, and
+    /// enabled again by the next one, cutting the idle current. The bytes received while no read
+    /// is in flight are lost. It is disabled by default.
+    ///
+    /// The transmitter and the receiver share the peripheral, so this sets the mode of both.
+    pub fn set_auto_idle(&mut self, enabled: bool) {
+        T::state().auto_idle.set(enabled, set_enabled::<T>);
+    }
+
     /// Write all bytes in the buffer.
     pub async fn write(&mut self, buffer: &[u8]) -> Result<(), Error> {
         match self.write_from_ram(buffer).await {
@@ -349,6 +371,7 @@ impl<'d, T: Instance> UarteTx<'d, T> {
             return Err(Error::BufferTooLong);
         }
 
+        let _idle = T::state().auto_idle.wake(set_enabled::<T>);
         let ptr = buffer.as_ptr();
         let len = buffer.len();
 
@@ -419,6 +442,7 @@ impl<'d, T: Instance> UarteTx<'d, T> {
             return Err(Error::BufferTooLong);
         }
 
+        let _idle = T::state().auto_idle.wake(set_enabled::<T>);
         let ptr = buffer.as_ptr();
         let len = buffer.len();
 
@@ -513,6 +537,7 @@ impl<'d, T: Instance> UarteRx<'d, T> {
 
         let hardware_flow_control = rts.is_some();
         configure(r, config, hardware_flow_control);
+        T::state().auto_idle.set(false, set_enabled::<T>);
 
         let s = T::state();
         s.tx_rx_refcount.store(1, Ordering::Relaxed);
@@ -520,6 +545,17 @@ impl<'d, T: Instance> UarteRx<'d, T> {
         Self { _p: uarte }
     }
 
+    /// Enable or disable the auto idle mode.
+    ///
+    /// In the auto idle mode, the peripheral is disabled whenever no transfer is in flight, and
+    /// enabled again by the next one, cutting the idle current. The bytes received while no read
+    /// is in flight are lost. It is disabled by default.
+    ///
+    /// The transmitter and the receiver share the peripheral, so this sets the mode of both.
+    pub fn set_auto_idle(&mut self, enabled: bool) {
+        T::state().auto_idle.set(enabled, set_enabled::<T>);
+    }
+
     /// Upgrade to an instance that supports idle line detection.
     pub fn with_idle<U: TimerInstance>(
         self,
@@ -579,6 +615,7 @@ impl<'d, T: Instance> UarteRx<'d, T> {
             return Err(Error::BufferTooLong);
         }
 
+        let _idle = T::state().auto_idle.wake(set_enabled::<T>);
         let ptr = buffer.as_ptr();
         let len = buffer.len();
 
@@ -633,6 +670,7 @@ impl<'d, T: Instance> UarteRx<'d, T> {
             return Err(Error::BufferTooLong);
         }
 
+        let _idle = T::state().auto_idle.wake(set_enabled::<T>);
         let ptr = buffer.as_ptr();
         let len = buffer.len();
 
@@ -687,6 +725,11 @@ pub struct UarteRxWithIdle<'d, T: Instance, U: TimerInstance> {
 }
 
 impl<'d, T: Instance, U: TimerInstance> UarteRxWithIdle<'d, T, U> {
+    /// Enable or disable the auto idle mode, see [`UarteRx::set_auto_idle`].
+    pub fn set_auto_idle(&mut self, enabled: bool) {
+        self.rx.set_auto_idle(enabled);
+    }
+
     /// Read bytes until the buffer is filled.
     pub async fn read(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
         self.ppi_ch1.disable();
@@ -710,6 +753,7 @@ impl<'d, T: Instance, U: TimerInstance> UarteRxWithIdle<'d, T, U> {
             return Err(Error::BufferTooLong);
         }
 
+        let _idle = T::state().auto_idle.wake(set_enabled::<T>);
         let ptr = buffer.as_ptr();
         let len = buffer.len();
 
@@ -769,6 +813,7 @@ impl<'d, T: Instance, U: TimerInstance> UarteRxWithIdle<'d, T, U> {
             return Err(Error::BufferTooLong);
         }
 
+        let _idle = T::state().auto_idle.wake(set_enabled::<T>);
         let ptr = buffer.as_ptr();
         let len = buffer.len();
 
@@ -849,6 +894,16 @@ pub(crate) fn apply_workaround_for_enable_anomaly(r: &crate::pac::uarte0::Regist
     }
 }
 
+fn set_enabled<T: Instance>(enabled: bool) {
+    T::regs().enable.write(|w| {
+        if enabled {
+            w.enable().enabled()
+        } else {
+            w.enable().disabled()
+        }
+    });
+}
+
 pub(crate) fn drop_tx_rx(r: &pac::uarte0::RegisterBlock, s: &sealed::State) {
     if s.tx_rx_refcount.fetch_sub(1, Ordering::Relaxed) == 1 {
         // Finally we can disable, and we do so for the peripheral
@@ -875,6 +930,7 @@ pub(crate) mod sealed {
         pub endrx_waker: AtomicWaker,
         pub endtx_waker: AtomicWaker,
         pub tx_rx_refcount: AtomicU8,
+        pub auto_idle: AutoIdle,
     }
     impl State {
         pub const fn new() -> Self {
@@ -882,6 +938,7 @@ pub(crate) mod sealed {
                 endrx_waker: AtomicWaker::new(),
                 endtx_waker: AtomicWaker::new(),
                 tx_rx_refcount: AtomicU8::new(0),
+                auto_idle: AutoIdle::new(),
             }
         }
     }
@@ -179,6 +179,10 @@ impl<'d, T: Instance, V: VbusDetect> driver::Bus for Bus<'d, T, V> {
     async fn enable(&mut self) {
         let regs = T::regs();
 
+        // The pull-up may have been left enabled, by a bootloader or before the last USB removal: the
+        // host must not see the device until the power is ready.
+        regs.usbpullup.write(|w| w.connect().disabled());
+
         errata::pre_enable();
 
         regs.enable.write(|w| w.enable().enabled());
@@ -218,6 +222,7 @@ impl<'d, T: Instance, V: VbusDetect> driver::Bus for Bus<'d, T, V> {
 
     async fn disable(&mut self) {
         let regs = T::regs();
+        regs.usbpullup.write(|w| w.connect().disabled());
         regs.enable.write(|x| x.enable().disabled());
     }
 
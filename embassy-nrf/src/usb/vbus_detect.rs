@@ -80,12 +80,20 @@ impl HardwareVbusDetect {
     pub fn new(_irq: impl interrupt::typelevel::Binding<UsbRegIrq, InterruptHandler> + 'static) -> Self {
         let regs = unsafe { &*UsbRegPeri::ptr() };
 
+        // Discard the events from before, the current state is read from `USBREGSTATUS`.
+        regs.events_usbdetected.reset();
+        regs.events_usbremoved.reset();
+        regs.events_usbpwrrdy.reset();
+
         UsbRegIrq::unpend();
         unsafe { UsbRegIrq::enable() };
 
         regs.intenset
             .write(|w| w.usbdetected().set().usbremoved().set().usbpwrrdy().set());
 
+        // The cable may have been plugged before the interrupt was enabled.
+        BUS_WAKER.wake();
+
         Self { _private: () }
     }
 }
@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 use core::mem;
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 
 const SRAM_LOWER: usize = 0x2000_0000;
 const SRAM_UPPER: usize = 0x3000_0000;
@@ -30,3 +31,71 @@ pub(crate) fn slice_in_ram_or<T, E>(slice: *const [T], err: E) -> Result<(), E>
         Err(err)
     }
 }
+
+/// Disabling of a peripheral between the transactions of its driver.
+///
+/// In the auto idle mode, the peripheral is only enabled while a transaction is in flight. Its
+/// configuration registers keep their values while it is disabled, so it doesn't need to be
+/// configured again. The transactions are counted, for the drivers split in halves sharing the
+/// peripheral.
+pub(crate) struct AutoIdle {
+    enabled: AtomicBool,
+    busy: AtomicU8,
+}
+
+impl AutoIdle {
+    pub(crate) const fn new() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            busy: AtomicU8::new(0),
+        }
+    }
+
+    /// Enable or disable the auto idle mode, disabling or enabling the peripheral now with
+    /// `set_enabled` if no transaction is in flight.
+    pub(crate) fn set(&self, enabled: bool, set_enabled: fn(bool)) {
+        critical_section::with(|_| {
+            if self.enabled.load(Ordering::Relaxed) != enabled && self.busy.load(Ordering::Relaxed) == 0 {
+                set_enabled(!enabled);
+            }
+            self.enabled.store(enabled, Ordering::Relaxed);
+        })
+    }
+
+    /// Enable the peripheral with `set_enabled` for a transaction, until the returned guard is
+    /// dropped.
+    ///
+    /// The guard must be dropped after any access to the registers, so it is declared first.
+    #[must_use]
+    pub(crate) fn wake(&self, set_enabled: fn(bool)) -> IdleGuard<'_> {
+        critical_section::with(|_| {
+            let busy = self.busy.load(Ordering::Relaxed);
+            if busy == 0 && self.enabled.load(Ordering::Relaxed) {
+                set_enabled(true);
+            }
+            self.busy.store(busy + 1, Ordering::Relaxed);
+        });
+        IdleGuard {
+            idle: self,
+            set_enabled,
+        }
+    }
+}
+
+/// Peripheral enabled for a transaction in the auto idle mode, disabled again when dropped.
+pub(crate) struct IdleGuard<'a> {
+    idle: &'a AutoIdle,
+    set_enabled: fn(bool),
+}
+
+impl<'a> Drop for IdleGuard<'a> {
+    fn drop(&mut self) {
+        critical_section::with(|_| {
+            let busy = self.idle.busy.load(Ordering::Relaxed) - 1;
+            if busy == 0 && self.idle.enabled.load(Ordering::Relaxed) {
+                (self.set_enabled)(false);
+            }
+            self.idle.busy.store(busy, Ordering::Relaxed);
+        })
+    }
+}
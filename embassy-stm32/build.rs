@@ -193,6 +193,12 @@ fn main() {
         Some("tim15") => "TIM15",
         Some("tim21") => "TIM21",
         Some("tim22") => "TIM22",
+        Some("lptim1") => {
+            if !chip_name.starts_with("stm32l4") && !chip_name.starts_with("stm32l5") {
+                panic!("time-driver-lptim1 is only supported on STM32L4 and STM32L5.")
+            }
+            "LPTIM1"
+        }
         Some("any") => {
             if singletons.contains(&"TIM2".to_string()) {
                 "TIM2"
@@ -458,7 +464,7 @@ fn main() {
         }
     }
 
-    let force_refcount = HashSet::from(["usart"]);
+    let force_refcount = HashSet::from(["usart", "spi", "i2c", "adc"]);
     let mut refcount_statics = BTreeSet::new();
 
     let mut clock_names = BTreeSet::new();
@@ -606,6 +612,12 @@ fn main() {
                         #after_enable
                         #rst
                     }
+                    fn enable_with_cs(_cs: critical_section::CriticalSection) {
+                        #before_enable
+                        #incr_stop_refcount
+                        crate::pac::RCC.#en_reg().modify(|w| w.#set_en_field(true));
+                        #after_enable
+                    }
                     fn disable_with_cs(_cs: critical_section::CriticalSection) {
                         #before_disable
                         crate::pac::RCC.#en_reg().modify(|w| w.#set_en_field(false));
@@ -1048,11 +1060,19 @@ fn main() {
         (("spi", "TX"), quote!(crate::spi::TxDma)),
         (("i2c", "RX"), quote!(crate::i2c::RxDma)),
         (("i2c", "TX"), quote!(crate::i2c::TxDma)),
+        (("cryp", "IN"), quote!(crate::cryp::DmaIn)),
+        (("cryp", "OUT"), quote!(crate::cryp::DmaOut)),
+        (("hash", "IN"), quote!(crate::hash::Dma)),
         (("dcmi", "DCMI"), quote!(crate::dcmi::FrameDma)),
         (("dcmi", "PSSI"), quote!(crate::dcmi::FrameDma)),
         // SDMMCv1 uses the same channel for both directions, so just implement for RX
         (("sdmmc", "RX"), quote!(crate::sdmmc::SdmmcDma)),
         (("quadspi", "QUADSPI"), quote!(crate::qspi::QuadDma)),
+        (("adc", "ADC"), quote!(crate::adc::RxDma)),
+        (("adc", "ADC1"), quote!(crate::adc::RxDma)),
+        (("adc", "ADC2"), quote!(crate::adc::RxDma)),
+        (("adc", "ADC3"), quote!(crate::adc::RxDma)),
+        (("adc", "ADC4"), quote!(crate::adc::RxDma)),
         (("dac", "CH1"), quote!(crate::dac::DacDma1)),
         (("dac", "CH2"), quote!(crate::dac::DacDma2)),
         (("timer", "UP"), quote!(crate::timer::UpDma)),
@@ -1252,6 +1272,7 @@ fn main() {
 
     let gpio_base = METADATA.peripherals.iter().find(|p| p.name == "GPIOA").unwrap().address as u32;
     let gpio_stride = 0x400;
+    let mut pin_af_masks: Vec<TokenStream> = Vec::new();
 
     for p in METADATA.peripherals {
         if let Some(regs) = &p.registers {
@@ -1263,6 +1284,19 @@ fn main() {
                 for pin_num in 0u32..16 {
                     let pin_name = format!("P{}{}", port_letter, pin_num);
 
+                    // Alternate functions of the pin, over all the peripherals it can connect to.
+                    let af_mask = METADATA
+                        .peripherals
+                        .iter()
+                        .flat_map(|peri| peri.pins.iter())
+                        .filter(|pin| pin.pin == pin_name)
+                        .filter_map(|pin| pin.af)
+                        .fold(0u16, |mask, af| mask | 1 << af);
+                    if af_mask != 0 {
+                        let pin_port = (port_num * 16 + pin_num) as u8;
+                        pin_af_masks.push(quote!(#pin_port => #af_mask,));
+                    }
+
                     pins_table.push(vec![
                         pin_name.clone(),
                         p.name.to_string(),
@@ -1317,6 +1351,16 @@ fn main() {
         }
     }
 
+    g.extend(quote! {
+        /// Mask of the alternate function numbers available on a pin, from its `pin_port`.
+        pub(crate) fn pin_af_mask(pin_port: u8) -> u16 {
+            match pin_port {
+                #(#pin_af_masks)*
+                _ => 0,
+            }
+        }
+    });
+
     let mut dma_channel_count: usize = 0;
     let mut bdma_channel_count: usize = 0;
     let mut gpdma_channel_count: usize = 0;
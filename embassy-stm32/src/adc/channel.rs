@@ -0,0 +1,65 @@
+use core::convert::Infallible;
+
+use embassy_embedded_hal::adc::AdcChannel;
+
+use super::{Adc, AdcPin, Instance, Resolution};
+
+/// ADC pin bound to its ADC, implementing the portable [`AdcChannel`] trait.
+///
+/// Created by [`Adc::channel`].
+pub struct AnalogChannel<'a, 'd, T: Instance, P> {
+    adc: &'a mut Adc<'d, T>,
+    pin: &'a mut P,
+    vref_mv: u32,
+    max_count: u32,
+}
+
+impl<'d, T: Instance> Adc<'d, T> {
+    /// Bind `pin` to this ADC, to read it through the [`AdcChannel`] trait.
+    ///
+    /// `vref_mv` is the voltage of the reference, usually VDDA, and `resolution` must be the
+    /// resolution set on the ADC. The readings are not calibrated.
+    pub fn channel<'a, P: AdcPin<T>>(
+        &'a mut self,
+        pin: &'a mut P,
+        vref_mv: u32,
+        resolution: Resolution,
+    ) -> AnalogChannel<'a, 'd, T, P> {
+        AnalogChannel {
+            adc: self,
+            pin,
+            vref_mv,
+            max_count: resolution.to_max_count(),
+        }
+    }
+}
+
+impl<'a, 'd, T: Instance, P> AnalogChannel<'a, 'd, T, P> {
+    fn to_mv(&self, sample: u16) -> i32 {
+        (sample as u32 * self.vref_mv / self.max_count) as i32
+    }
+}
+
+#[cfg(not(adc_v4))]
+impl<'a, 'd, T: Instance, P: AdcPin<T>> AdcChannel for AnalogChannel<'a, 'd, T, P> {
+    type Error = Infallible;
+
+    async fn read_mv(&mut self) -> Result<i32, Infallible> {
+        #[cfg(adc_v1)]
+        let sample = self.adc.read(self.pin).await;
+        #[cfg(not(adc_v1))]
+        let sample = self.adc.read(self.pin);
+
+        Ok(self.to_mv(sample))
+    }
+}
+
+#[cfg(adc_v4)]
+impl<'a, 'd, T: Instance, P: AdcPin<T> + crate::gpio::sealed::Pin> AdcChannel for AnalogChannel<'a, 'd, T, P> {
+    type Error = Infallible;
+
+    async fn read_mv(&mut self) -> Result<i32, Infallible> {
+        let sample = self.adc.read(self.pin);
+        Ok(self.to_mv(sample))
+    }
+}
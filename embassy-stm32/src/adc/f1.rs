@@ -6,6 +6,7 @@ use embassy_hal_internal::into_ref;
 use embedded_hal_02::blocking::delay::DelayUs;
 
 use crate::adc::{Adc, AdcPin, Instance, SampleTime};
+use crate::rcc::AutoIdle;
 use crate::time::Hertz;
 use crate::{interrupt, Peripheral};
 
@@ -75,6 +76,7 @@ impl<'d, T: Instance> Adc<'d, T> {
         Self {
             adc,
             sample_time: Default::default(),
+            auto_idle: AutoIdle::new(),
         }
     }
 
@@ -96,6 +98,7 @@ impl<'d, T: Instance> Adc<'d, T> {
     }
 
     pub fn enable_vref(&self, _delay: &mut impl DelayUs<u32>) -> Vref {
+        let _clock = self.auto_idle.wake();
         T::regs().cr2().modify(|reg| {
             reg.set_tsvrefe(true);
         });
@@ -103,6 +106,7 @@ impl<'d, T: Instance> Adc<'d, T> {
     }
 
     pub fn enable_temperature(&self) -> Temperature {
+        let _clock = self.auto_idle.wake();
         T::regs().cr2().modify(|reg| {
             reg.set_tsvrefe(true);
         });
@@ -136,6 +140,7 @@ impl<'d, T: Instance> Adc<'d, T> {
     }
 
     pub async fn read(&mut self, pin: &mut impl AdcPin<T>) -> u16 {
+        let _clock = self.auto_idle.wake();
         Self::set_channel_sample_time(pin.channel(), self.sample_time);
         T::regs().cr1().modify(|reg| {
             reg.set_scan(false);
@@ -167,6 +172,7 @@ impl<'d, T: Instance> Adc<'d, T> {
 
 impl<'d, T: Instance> Drop for Adc<'d, T> {
     fn drop(&mut self) {
+        self.auto_idle.set(false);
         T::regs().cr2().modify(|reg| reg.set_adon(false));
 
         T::disable();
@@ -7,6 +7,7 @@ use embedded_hal_02::blocking::delay::DelayUs;
 
 use crate::adc::{Adc, AdcPin, Instance, SampleTime};
 use crate::interrupt::typelevel::Interrupt;
+use crate::rcc::AutoIdle;
 use crate::time::Hertz;
 use crate::{interrupt, Peripheral};
 
@@ -98,6 +99,7 @@ impl<'d, T: Instance> Adc<'d, T> {
         Self {
             adc,
             sample_time: Default::default(),
+            auto_idle: AutoIdle::new(),
         }
     }
 
@@ -118,12 +120,14 @@ impl<'d, T: Instance> Adc<'d, T> {
     }
 
     pub fn enable_vref(&self, _delay: &mut impl DelayUs<u32>) -> Vref {
+        let _clock = self.auto_idle.wake();
         T::common_regs().ccr().modify(|w| w.set_vrefen(true));
 
         Vref {}
     }
 
     pub fn enable_temperature(&self) -> Temperature {
+        let _clock = self.auto_idle.wake();
         T::common_regs().ccr().modify(|w| w.set_tsen(true));
 
         Temperature {}
@@ -156,6 +160,7 @@ impl<'d, T: Instance> Adc<'d, T> {
     }
 
     pub async fn read(&mut self, pin: &mut impl AdcPin<T>) -> u16 {
+        let _clock = self.auto_idle.wake();
         Self::set_channel_sample_time(pin.channel(), self.sample_time);
 
         // Configure the channel to sample
@@ -175,6 +180,7 @@ impl<'d, T: Instance> Adc<'d, T> {
 
 impl<'d, T: Instance> Drop for Adc<'d, T> {
     fn drop(&mut self) {
+        self.auto_idle.set(false);
         use crate::pac::adc::vals;
 
         T::regs().cr().modify(|w| w.set_adstp(true));
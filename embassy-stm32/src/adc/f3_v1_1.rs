@@ -9,6 +9,7 @@ use embassy_time::Instant;
 use super::Resolution;
 use crate::adc::{Adc, AdcPin, Instance, SampleTime};
 use crate::interrupt::typelevel::Interrupt;
+use crate::rcc::AutoIdle;
 use crate::time::Hertz;
 use crate::{interrupt, Peripheral};
 
@@ -118,7 +119,10 @@ impl Calibration {
 
 impl<T: Instance> Drop for Vref<T> {
     fn drop(&mut self) {
-        update_vref::<T>(-1)
+        // The clock may be gated by the auto idle mode of the ADC.
+        T::enable();
+        update_vref::<T>(-1);
+        T::disable();
     }
 }
 
@@ -132,7 +136,10 @@ impl<T: Instance> super::sealed::AdcPin<T> for Temperature<T> {
 
 impl<T: Instance> Drop for Temperature<T> {
     fn drop(&mut self) {
-        update_vref::<T>(-1)
+        // The clock may be gated by the auto idle mode of the ADC.
+        T::enable();
+        update_vref::<T>(-1);
+        T::disable();
     }
 }
 
@@ -153,7 +160,10 @@ impl<'d, T: Instance> Adc<'d, T> {
             T::Interrupt::enable();
         }
 
-        Self { adc }
+        Self {
+            adc,
+            auto_idle: AutoIdle::new(),
+        }
     }
 
     fn freq() -> Hertz {
@@ -162,6 +172,7 @@ impl<'d, T: Instance> Adc<'d, T> {
     }
 
     pub async fn set_resolution(&mut self, res: Resolution) {
+        let _clock = self.auto_idle.wake();
         let was_on = Self::is_on();
         if was_on {
             self.stop_adc().await;
@@ -175,6 +186,7 @@ impl<'d, T: Instance> Adc<'d, T> {
     }
 
     pub fn resolution(&self) -> Resolution {
+        let _clock = self.auto_idle.wake();
         match T::regs().cr1().read().res() {
             crate::pac::adc::vals::Res::TWELVEBIT => Resolution::TwelveBit,
             crate::pac::adc::vals::Res::TENBIT => Resolution::TenBit,
@@ -184,12 +196,14 @@ impl<'d, T: Instance> Adc<'d, T> {
     }
 
     pub fn enable_vref(&self) -> Vref<T> {
+        let _clock = self.auto_idle.wake();
         update_vref::<T>(1);
 
         Vref(core::marker::PhantomData)
     }
 
     pub fn enable_temperature(&self) -> Temperature<T> {
+        let _clock = self.auto_idle.wake();
         T::regs().ccr().modify(|w| w.set_tsvrefe(true));
 
         Temperature::<T>(core::marker::PhantomData)
@@ -240,6 +254,7 @@ impl<'d, T: Instance> Adc<'d, T> {
     }
 
     pub async fn start_adc(&self) {
+        let _clock = self.auto_idle.wake();
         //defmt::trace!("Turn ADC on");
         T::regs().cr2().modify(|w| w.set_adon(true));
         //defmt::trace!("Waiting for ADC to turn on");
@@ -258,6 +273,7 @@ impl<'d, T: Instance> Adc<'d, T> {
     }
 
     pub async fn stop_adc(&self) {
+        let _clock = self.auto_idle.wake();
         if T::regs().cr2().read().adon() {
             //defmt::trace!("ADC should be on, wait for it to start");
             while !T::regs().csr().read().adons1() {
@@ -277,6 +293,7 @@ impl<'d, T: Instance> Adc<'d, T> {
     }
 
     pub async fn read(&mut self, pin: &mut impl AdcPin<T>) -> u16 {
+        let _clock = self.auto_idle.wake();
         self.set_sample_sequence(&[pin.channel()]).await;
         self.convert().await
     }
@@ -289,6 +306,7 @@ impl<'d, T: Instance> Adc<'d, T> {
     }
 
     pub async fn set_sample_time(&mut self, pin: &mut impl AdcPin<T>, sample_time: SampleTime) {
+        let _clock = self.auto_idle.wake();
         if Self::get_channel_sample_time(pin.channel()) != sample_time {
             self.stop_adc().await;
             unsafe {
@@ -299,6 +317,7 @@ impl<'d, T: Instance> Adc<'d, T> {
     }
 
     pub fn get_sample_time(&self, pin: &impl AdcPin<T>) -> SampleTime {
+        let _clock = self.auto_idle.wake();
         Self::get_channel_sample_time(pin.channel())
     }
 
@@ -396,6 +415,7 @@ impl<'d, T: Instance> Adc<'d, T> {
     }
 
     pub fn us_for_cfg(&self, res: Resolution, sample_time: SampleTime) -> u32 {
+        let _clock = self.auto_idle.wake();
         let res_clks = Self::get_res_clks(res);
         let sample_clks = Self::get_sample_time_clks(sample_time);
         (res_clks + sample_clks) * 1_000_000 / Self::freq().0
@@ -404,6 +424,7 @@ impl<'d, T: Instance> Adc<'d, T> {
 
 impl<'d, T: Instance> Drop for Adc<'d, T> {
     fn drop(&mut self) {
+        self.auto_idle.set(false);
         while !T::regs().sr().read().adons() {}
 
         T::regs().cr2().modify(|w| w.set_adon(false));
@@ -13,13 +13,19 @@
 #[cfg_attr(adc_v4, path = "v4.rs")]
 mod _version;
 
+#[cfg(any(adc_v1, adc_v2, adc_v3, adc_g0, adc_v4))]
+mod channel;
 #[cfg(not(any(adc_f1, adc_f3_v2)))]
 mod resolution;
+#[cfg(adc_v3)]
+pub mod ringbuffered;
 mod sample_time;
 
 #[allow(unused)]
 #[cfg(not(adc_f3_v2))]
 pub use _version::*;
+#[cfg(any(adc_v1, adc_v2, adc_v3, adc_g0, adc_v4))]
+pub use channel::AnalogChannel;
 #[cfg(not(any(adc_f1, adc_f3, adc_f3_v2)))]
 pub use resolution::Resolution;
 #[cfg(not(adc_f3_v2))]
@@ -33,18 +39,32 @@ pub struct Adc<'d, T: Instance> {
     adc: crate::PeripheralRef<'d, T>,
     #[cfg(not(any(adc_f3_v2, adc_f3_v1_1)))]
     sample_time: SampleTime,
+    #[cfg(not(adc_f3_v2))]
+    auto_idle: crate::rcc::AutoIdle<T>,
+}
+
+#[cfg(not(adc_f3_v2))]
+impl<'d, T: Instance> Adc<'d, T> {
+    /// Enable or disable the auto idle mode.
+    ///
+    /// In the auto idle mode, the clock of the peripheral is gated whenever no conversion is in
+    /// flight, and enabled again by the next one, cutting the idle current. It is disabled by
+    /// default.
+    pub fn set_auto_idle(&mut self, enabled: bool) {
+        self.auto_idle.set(enabled);
+    }
 }
 
 pub(crate) mod sealed {
-    #[cfg(any(adc_f1, adc_f3, adc_v1, adc_f3_v1_1))]
+    #[cfg(any(adc_f1, adc_f3, adc_v1, adc_f3_v1_1, adc_v3))]
     use embassy_sync::waitqueue::AtomicWaker;
 
-    #[cfg(any(adc_f1, adc_f3, adc_v1, adc_f3_v1_1))]
+    #[cfg(any(adc_f1, adc_f3, adc_v1, adc_f3_v1_1, adc_v3))]
     pub struct State {
         pub waker: AtomicWaker,
     }
 
-    #[cfg(any(adc_f1, adc_f3, adc_v1, adc_f3_v1_1))]
+    #[cfg(any(adc_f1, adc_f3, adc_v1, adc_f3_v1_1, adc_v3))]
     impl State {
         pub const fn new() -> Self {
             Self {
@@ -61,7 +81,7 @@ pub(crate) mod sealed {
         fn regs() -> crate::pac::adc::Adc;
         #[cfg(not(any(adc_f1, adc_v1, adc_f3_v2, adc_f3_v1_1, adc_g0)))]
         fn common_regs() -> crate::pac::adccommon::AdcCommon;
-        #[cfg(any(adc_f1, adc_f3, adc_v1, adc_f3_v1_1))]
+        #[cfg(any(adc_f1, adc_f3, adc_v1, adc_f3_v1_1, adc_v3))]
         fn state() -> &'static State;
     }
 
@@ -84,6 +104,8 @@ pub trait Instance: sealed::Instance + crate::Peripheral<P = Self> {}
 #[cfg(any(adc_f1, adc_v1, adc_v2, adc_v3, adc_v4, adc_f3, adc_f3_v1_1, adc_g0))]
 pub trait Instance: sealed::Instance + crate::Peripheral<P = Self> + crate::rcc::RccPeripheral {}
 
+dma_trait!(RxDma, Instance);
+
 /// ADC pin.
 pub trait AdcPin<T: Instance>: sealed::AdcPin<T> {}
 /// ADC internal channel.
@@ -101,7 +123,7 @@ foreach_adc!(
                 return crate::pac::$common_inst
             }
 
-            #[cfg(any(adc_f1, adc_f3, adc_v1, adc_f3_v1_1))]
+            #[cfg(any(adc_f1, adc_f3, adc_v1, adc_f3_v1_1, adc_v3))]
             fn state() -> &'static sealed::State {
                 static STATE: sealed::State = sealed::State::new();
                 &STATE
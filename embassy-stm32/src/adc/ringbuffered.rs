@@ -0,0 +1,163 @@
+//! Continuous conversions of a regular sequence into a DMA ring buffer.
+
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use embassy_hal_internal::into_ref;
+
+use crate::adc::{Adc, AdcPin, Instance, RxDma, SampleTime};
+use crate::dma::ReadableRingBuffer;
+use crate::Peripheral;
+
+/// Maximum number of channels in a regular sequence.
+pub const MAX_SEQUENCE_LEN: usize = 16;
+
+/// Ring-buffered ADC error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// Samples were overwritten by the DMA before being read. The conversions were restarted.
+    Overrun,
+}
+
+/// ADC converting a regular sequence continuously into a DMA ring buffer.
+///
+/// Samples are stored in the order of the sequence, starting with its first channel.
+pub struct RingBufferedAdc<'d, T: Instance, D: RxDma<T>> {
+    adc: Adc<'d, T>,
+    ring_buf: ReadableRingBuffer<'d, D, u16>,
+    sequence_len: usize,
+}
+
+impl<'d, T: Instance> Adc<'d, T> {
+    /// Configure a regular sequence of channels converted continuously into `dma_buf`.
+    ///
+    /// Each channel of `sequence` is converted with its own sample time, in order, and the
+    /// sequence starts over when it ends. The resolution and oversampling of the ADC apply to
+    /// all the channels. `dma_buf` must be large enough to hold the samples converted while the
+    /// application is not reading, and should be a multiple of twice the sequence length.
+    ///
+    /// Call [`RingBufferedAdc::start`] to start the conversions.
+    pub fn into_ring_buffered<D: RxDma<T>>(
+        mut self,
+        dma: impl Peripheral<P = D> + 'd,
+        dma_buf: &'d mut [u16],
+        sequence: &mut [(&mut dyn AdcPin<T>, SampleTime)],
+    ) -> RingBufferedAdc<'d, T, D> {
+        into_ref!(dma);
+        assert!(!sequence.is_empty() && sequence.len() <= MAX_SEQUENCE_LEN);
+        assert!(!dma_buf.is_empty() && dma_buf.len() <= 0xFFFF);
+
+        // The sequence is converted continuously.
+        self.auto_idle.set(false);
+
+        let r = T::regs();
+        Self::enable();
+
+        r.sqr1().modify(|w| w.set_l(sequence.len() as u8 - 1));
+        for (i, (pin, sample_time)) in sequence.iter().enumerate() {
+            let ch = pin.channel();
+            Self::set_channel_sample_time(ch, *sample_time);
+            match i {
+                0..=3 => r.sqr1().modify(|w| w.set_sq(i, ch)),
+                4..=8 => r.sqr2().modify(|w| w.set_sq(i - 4, ch)),
+                9..=13 => r.sqr3().modify(|w| w.set_sq(i - 9, ch)),
+                _ => r.sqr4().modify(|w| w.set_sq(i - 14, ch)),
+            }
+        }
+
+        r.cfgr().modify(|w| {
+            w.set_cont(true);
+            // DMA circular mode.
+            w.set_dmaen(true);
+            w.set_dmacfg(true);
+            // Keep converting on overrun, the ring buffer detects lost samples.
+            w.set_ovrmod(true);
+        });
+
+        let request = dma.request();
+        let dr = r.dr().as_ptr() as *mut u16;
+        let ring_buf = unsafe { ReadableRingBuffer::new(dma, request, dr, dma_buf, Default::default()) };
+
+        RingBufferedAdc {
+            adc: self,
+            ring_buf,
+            sequence_len: sequence.len(),
+        }
+    }
+}
+
+impl<'d, T: Instance, D: RxDma<T>> RingBufferedAdc<'d, T, D> {
+    /// Clear the ring buffer and start the conversions.
+    ///
+    /// The conversions are stopped first if they are running.
+    pub fn start(&mut self) {
+        self.stop();
+
+        self.ring_buf.clear();
+        self.ring_buf.start();
+
+        compiler_fence(Ordering::SeqCst);
+        // OVR is cleared by writing 1, the other flags are left alone.
+        T::regs().isr().write(|w| w.set_ovr(true));
+        T::regs().cr().modify(|w| w.set_adstart(true));
+    }
+
+    /// Stop the conversions and the DMA transfer.
+    pub fn stop(&mut self) {
+        let r = T::regs();
+        if r.cr().read().adstart() {
+            r.cr().modify(|w| w.set_adstp(true));
+            while r.cr().read().adstart() {}
+        }
+
+        self.ring_buf.request_stop();
+        while self.ring_buf.is_running() {}
+        compiler_fence(Ordering::SeqCst);
+    }
+
+    /// Wait until a conversion falls out of the range of the analog watchdog.
+    ///
+    /// The watchdog must be enabled with [`Adc::enable_watchdog`] before creating the
+    /// ring-buffered ADC.
+    pub async fn wait_for_out_of_range(&mut self) {
+        self.adc.wait_for_out_of_range().await
+    }
+
+    /// Number of channels in the sequence.
+    pub fn sequence_len(&self) -> usize {
+        self.sequence_len
+    }
+
+    /// Wait until `buf` is filled with samples.
+    ///
+    /// The length of `buf` must be a multiple of the sequence length, so each call returns whole
+    /// sequences. Returns the number of samples left in the ring buffer.
+    ///
+    /// On overrun the conversions are restarted, so the next call returns fresh samples starting
+    /// again with the first channel of the sequence.
+    pub async fn read(&mut self, buf: &mut [u16]) -> Result<usize, Error> {
+        assert!(buf.len() % self.sequence_len == 0);
+
+        match self.ring_buf.read_exact(buf).await {
+            Ok(remaining) => Ok(remaining),
+            Err(_) => {
+                self.start();
+                Err(Error::Overrun)
+            }
+        }
+    }
+}
+
+impl<'d, T: Instance, D: RxDma<T>> Drop for RingBufferedAdc<'d, T, D> {
+    fn drop(&mut self) {
+        self.stop();
+
+        let r = T::regs();
+        r.cfgr().modify(|w| {
+            w.set_cont(false);
+            w.set_dmaen(false);
+            w.set_dmacfg(false);
+        });
+        r.cr().modify(|w| w.set_addis(true));
+    }
+}
@@ -8,6 +8,7 @@ use embedded_hal_02::blocking::delay::DelayUs;
 use crate::adc::{Adc, AdcPin, Instance, Resolution, SampleTime};
 use crate::interrupt::typelevel::Interrupt;
 use crate::peripherals::ADC;
+use crate::rcc::AutoIdle;
 use crate::{interrupt, Peripheral};
 
 pub const VDDA_CALIB_MV: u32 = 3300;
@@ -94,10 +95,12 @@ impl<'d, T: Instance> Adc<'d, T> {
         Self {
             adc,
             sample_time: Default::default(),
+            auto_idle: AutoIdle::new(),
         }
     }
 
     pub fn enable_vbat(&self, _delay: &mut impl DelayUs<u32>) -> Vbat {
+        let _clock = self.auto_idle.wake();
         // SMP must be ≥ 56 ADC clock cycles when using HSI14.
         //
         // 6.3.20 Vbat monitoring characteristics
@@ -107,6 +110,7 @@ impl<'d, T: Instance> Adc<'d, T> {
     }
 
     pub fn enable_vref(&self, delay: &mut impl DelayUs<u32>) -> Vref {
+        let _clock = self.auto_idle.wake();
         // Table 28. Embedded internal reference voltage
         // tstart = 10μs
         T::regs().ccr().modify(|reg| reg.set_vrefen(true));
@@ -115,6 +119,7 @@ impl<'d, T: Instance> Adc<'d, T> {
     }
 
     pub fn enable_temperature(&self, delay: &mut impl DelayUs<u32>) -> Temperature {
+        let _clock = self.auto_idle.wake();
         // SMP must be ≥ 56 ADC clock cycles when using HSI14.
         //
         // 6.3.19 Temperature sensor characteristics
@@ -130,10 +135,12 @@ impl<'d, T: Instance> Adc<'d, T> {
     }
 
     pub fn set_resolution(&mut self, resolution: Resolution) {
+        let _clock = self.auto_idle.wake();
         T::regs().cfgr1().modify(|reg| reg.set_res(resolution.into()));
     }
 
     pub async fn read(&mut self, pin: &mut impl AdcPin<T>) -> u16 {
+        let _clock = self.auto_idle.wake();
         let channel = pin.channel();
         pin.set_as_analog();
 
@@ -170,6 +177,7 @@ impl<'d, T: Instance> Adc<'d, T> {
 
 impl<'d, T: Instance> Drop for Adc<'d, T> {
     fn drop(&mut self) {
+        self.auto_idle.set(false);
         // A.7.3 ADC disable code example
         T::regs().cr().modify(|reg| reg.set_adstp(true));
         while T::regs().cr().read().adstp() {}
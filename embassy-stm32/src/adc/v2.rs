@@ -3,6 +3,7 @@ use embedded_hal_02::blocking::delay::DelayUs;
 
 use crate::adc::{Adc, AdcPin, Instance, Resolution, SampleTime};
 use crate::peripherals::ADC1;
+use crate::rcc::AutoIdle;
 use crate::time::Hertz;
 use crate::Peripheral;
 
@@ -112,6 +113,7 @@ where
         Self {
             adc,
             sample_time: Default::default(),
+            auto_idle: AutoIdle::new(),
         }
     }
 
@@ -120,12 +122,14 @@ where
     }
 
     pub fn set_resolution(&mut self, resolution: Resolution) {
+        let _clock = self.auto_idle.wake();
         T::regs().cr1().modify(|reg| reg.set_res(resolution.into()));
     }
 
     /// Enables internal voltage reference and returns [VrefInt], which can be used in
     /// [Adc::read_internal()] to perform conversion.
     pub fn enable_vrefint(&self) -> VrefInt {
+        let _clock = self.auto_idle.wake();
         T::common_regs().ccr().modify(|reg| {
             reg.set_tsvrefe(true);
         });
@@ -139,6 +143,7 @@ where
     /// On STM32F42 and STM32F43 this can not be used together with [Vbat]. If both are enabled,
     /// temperature sensor will return vbat value.
     pub fn enable_temperature(&self) -> Temperature {
+        let _clock = self.auto_idle.wake();
         T::common_regs().ccr().modify(|reg| {
             reg.set_tsvrefe(true);
         });
@@ -149,6 +154,7 @@ where
     /// Enables vbat input and returns [Vbat], which can be used in
     /// [Adc::read_internal()] to perform conversion.
     pub fn enable_vbat(&self) -> Vbat {
+        let _clock = self.auto_idle.wake();
         T::common_regs().ccr().modify(|reg| {
             reg.set_vbate(true);
         });
@@ -179,6 +185,7 @@ where
     }
 
     pub fn read(&mut self, pin: &mut impl AdcPin<T>) -> u16 {
+        let _clock = self.auto_idle.wake();
         pin.set_as_analog();
 
         // Configure ADC
@@ -205,6 +212,7 @@ where
 
 impl<'d, T: Instance> Drop for Adc<'d, T> {
     fn drop(&mut self) {
+        self.auto_idle.set(false);
         T::regs().cr2().modify(|reg| {
             reg.set_adon(false);
         });
@@ -1,14 +1,133 @@
+#[cfg(adc_v3)]
+use core::future::poll_fn;
+#[cfg(adc_v3)]
+use core::marker::PhantomData;
+#[cfg(adc_v3)]
+use core::task::Poll;
+
+#[cfg(adc_v3)]
+use embassy_hal_internal::drop::OnDrop;
 use embassy_hal_internal::into_ref;
 use embedded_hal_02::blocking::delay::DelayUs;
 
 use crate::adc::{Adc, AdcPin, Instance, Resolution, SampleTime};
+#[cfg(adc_v3)]
+use crate::interrupt;
+#[cfg(adc_v3)]
+use crate::interrupt::typelevel::Interrupt;
+#[cfg(adc_v3)]
+use crate::pac::adc::vals::Exten;
+use crate::rcc::AutoIdle;
 use crate::Peripheral;
 
 /// Default VREF voltage used for sample conversion to millivolts.
 pub const VREF_DEFAULT_MV: u32 = 3300;
-/// VREF voltage used for factory calibration of VREFINTCAL register.
-pub const VREF_CALIB_MV: u32 = 3000;
+cfg_if::cfg_if! {
+    if #[cfg(stm32wl)] {
+        /// VDDA voltage used for factory calibration of VREFINTCAL register.
+        pub const VREF_CALIB_MV: u32 = 3300;
+        /// VDDA voltage used for factory calibration of the temperature sensor.
+        pub const TS_CALIB_MV: u32 = 3300;
+    } else if #[cfg(stm32wb)] {
+        /// VDDA voltage used for factory calibration of VREFINTCAL register.
+        pub const VREF_CALIB_MV: u32 = 3600;
+        /// VDDA voltage used for factory calibration of the temperature sensor.
+        pub const TS_CALIB_MV: u32 = 3000;
+    } else {
+        /// VDDA voltage used for factory calibration of VREFINTCAL register.
+        pub const VREF_CALIB_MV: u32 = 3000;
+        /// VDDA voltage used for factory calibration of the temperature sensor.
+        pub const TS_CALIB_MV: u32 = 3000;
+    }
+}
+
+// Factory calibration values in system memory, 12-bit readings done with VDDA = VREF_CALIB_MV
+// for VREFINT, and VDDA = TS_CALIB_MV for the temperature sensor.
+cfg_if::cfg_if! {
+    if #[cfg(stm32l5)] {
+        const VREFINT_CAL: *const u16 = 0x0BFA_05AA as _;
+        const TS_CAL1: *const u16 = 0x0BFA_05A8 as _;
+        const TS_CAL2: *const u16 = 0x0BFA_05CA as _;
+    } else if #[cfg(stm32wl)] {
+        const VREFINT_CAL: *const u16 = 0x1FFF_75AA as _;
+        const TS_CAL1: *const u16 = 0x1FFF_75A8 as _;
+        const TS_CAL2: *const u16 = 0x1FFF_75C8 as _;
+    } else if #[cfg(stm32g0)] {
+        const VREFINT_CAL: *const u16 = 0x1FFF_75AA as _;
+        const TS_CAL1: *const u16 = 0x1FFF_75A8 as _;
+    } else {
+        const VREFINT_CAL: *const u16 = 0x1FFF_75AA as _;
+        const TS_CAL1: *const u16 = 0x1FFF_75A8 as _;
+        const TS_CAL2: *const u16 = 0x1FFF_75CA as _;
+    }
+}
+
+/// Ratio of the internal VBAT bridge divider.
+const VBAT_DIVIDER: u32 = 3;
 
+/// Maximum number of channels in the injected sequence.
+#[cfg(adc_v3)]
+pub const MAX_INJECTED_LEN: usize = 4;
+
+/// Interrupt handler, needed by the injected conversions and the analog watchdog.
+#[cfg(adc_v3)]
+pub struct InterruptHandler<T: Instance> {
+    _phantom: PhantomData<T>,
+}
+
+#[cfg(adc_v3)]
+impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        let isr = T::regs().isr().read();
+        let ier = T::regs().ier().read();
+        let jeos = isr.jeos() && ier.jeosie();
+        let awd = isr.awd(0) && ier.awdie(0);
+        if !jeos && !awd {
+            return;
+        }
+
+        T::regs().ier().modify(|w| {
+            if jeos {
+                w.set_jeosie(false);
+            }
+            if awd {
+                w.set_awdie(0, false);
+            }
+        });
+        T::state().waker.wake();
+    }
+}
+
+/// Trigger edge of the injected conversions.
+#[cfg(adc_v3)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TriggerEdge {
+    /// Rising edge.
+    Rising,
+    /// Falling edge.
+    Falling,
+    /// Both edges.
+    Both,
+}
+
+/// Start of the injected conversions.
+#[cfg(adc_v3)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InjectedTrigger {
+    /// Started by [`Adc::read_injected`].
+    Software,
+    /// Started by a timer or EXTI event.
+    External {
+        /// Event number, `JEXTSEL` in the reference manual. For example TIM1_TRGO is 0 on STM32L4.
+        source: u8,
+        /// Active edge of the event.
+        edge: TriggerEdge,
+    },
+}
+
+/// Internal voltage reference channel, see [`Adc::enable_vrefint`].
 pub struct VrefInt;
 impl<T: Instance> AdcPin<T> for VrefInt {}
 impl<T: Instance> super::sealed::AdcPin<T> for VrefInt {
@@ -21,6 +140,7 @@ impl<T: Instance> super::sealed::AdcPin<T> for VrefInt {
     }
 }
 
+/// Internal temperature sensor channel, see [`Adc::enable_temperature`].
 pub struct Temperature;
 impl<T: Instance> AdcPin<T> for Temperature {}
 impl<T: Instance> super::sealed::AdcPin<T> for Temperature {
@@ -33,6 +153,7 @@ impl<T: Instance> super::sealed::AdcPin<T> for Temperature {
     }
 }
 
+/// Backup domain supply channel, see [`Adc::enable_vbat`].
 pub struct Vbat;
 impl<T: Instance> AdcPin<T> for Vbat {}
 impl<T: Instance> super::sealed::AdcPin<T> for Vbat {
@@ -45,6 +166,75 @@ impl<T: Instance> super::sealed::AdcPin<T> for Vbat {
     }
 }
 
+impl VrefInt {
+    /// Factory calibration value: 12-bit reading of VREFINT with VDDA at [`VREF_CALIB_MV`].
+    pub fn calibrated_value(&self) -> u16 {
+        unsafe { VREFINT_CAL.read_volatile() }
+    }
+}
+
+impl Temperature {
+    /// Temperature of the first factory calibration point, in degrees Celsius.
+    pub const CAL1_CELSIUS: i32 = 30;
+    /// Temperature of the second factory calibration point, in degrees Celsius.
+    #[cfg(not(stm32g0))]
+    pub const CAL2_CELSIUS: i32 = if cfg!(any(stm32l47x, stm32l48x, stm32l49x, stm32l4ax)) {
+        110
+    } else {
+        130
+    };
+    /// Typical slope of the sensor, in microvolts per degree Celsius.
+    #[cfg(stm32g0)]
+    pub const AVG_SLOPE_UV: i32 = 2500;
+
+    /// Factory calibration value: 12-bit reading at [`CAL1_CELSIUS`](Self::CAL1_CELSIUS) with
+    /// VDDA at [`TS_CALIB_MV`].
+    pub fn calibrated_value_1(&self) -> u16 {
+        unsafe { TS_CAL1.read_volatile() }
+    }
+
+    /// Factory calibration value: 12-bit reading at [`CAL2_CELSIUS`](Self::CAL2_CELSIUS) with
+    /// VDDA at [`TS_CALIB_MV`].
+    #[cfg(not(stm32g0))]
+    pub fn calibrated_value_2(&self) -> u16 {
+        unsafe { TS_CAL2.read_volatile() }
+    }
+}
+
+/// Oversampling ratio.
+#[cfg(not(adc_g0))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OversamplingRatio {
+    /// 2 conversions.
+    X2 = 0,
+    /// 4 conversions.
+    X4 = 1,
+    /// 8 conversions.
+    X8 = 2,
+    /// 16 conversions.
+    X16 = 3,
+    /// 32 conversions.
+    X32 = 4,
+    /// 64 conversions.
+    X64 = 5,
+    /// 128 conversions.
+    X128 = 6,
+    /// 256 conversions.
+    X256 = 7,
+}
+
+/// Oversampling configuration.
+#[cfg(not(adc_g0))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Oversampling {
+    /// Number of accumulated conversions.
+    pub ratio: OversamplingRatio,
+    /// Right shift applied to the accumulated result, between 0 and 8.
+    pub shift: u8,
+}
+
 impl<'d, T: Instance> Adc<'d, T> {
     pub fn new(adc: impl Peripheral<P = T> + 'd, delay: &mut impl DelayUs<u32>) -> Self {
         into_ref!(adc);
@@ -75,10 +265,12 @@ impl<'d, T: Instance> Adc<'d, T> {
         Self {
             adc,
             sample_time: Default::default(),
+            auto_idle: AutoIdle::new(),
         }
     }
 
     pub fn enable_vrefint(&self, delay: &mut impl DelayUs<u32>) -> VrefInt {
+        let _clock = self.auto_idle.wake();
         #[cfg(not(adc_g0))]
         T::common_regs().ccr().modify(|reg| {
             reg.set_vrefen(true);
@@ -98,6 +290,7 @@ impl<'d, T: Instance> Adc<'d, T> {
     }
 
     pub fn enable_temperature(&self) -> Temperature {
+        let _clock = self.auto_idle.wake();
         #[cfg(not(adc_g0))]
         T::common_regs().ccr().modify(|reg| {
             reg.set_ch17sel(true);
@@ -111,6 +304,7 @@ impl<'d, T: Instance> Adc<'d, T> {
     }
 
     pub fn enable_vbat(&self) -> Vbat {
+        let _clock = self.auto_idle.wake();
         #[cfg(not(adc_g0))]
         T::common_regs().ccr().modify(|reg| {
             reg.set_ch18sel(true);
@@ -123,26 +317,87 @@ impl<'d, T: Instance> Adc<'d, T> {
         Vbat {}
     }
 
+    /// Measure VDDA in millivolts, from VREFINT and its factory calibration value.
+    ///
+    /// The result is the reference of the other conversions to millivolts. Like all the internal
+    /// channels, VREFINT needs a long sample time, see the datasheet.
+    pub fn read_vdda_mv(&mut self, vrefint: &mut VrefInt) -> u32 {
+        let _clock = self.auto_idle.wake();
+        let sample = Self::to_12_bit(self.read(vrefint));
+        VREF_CALIB_MV * vrefint.calibrated_value() as u32 / sample.max(1)
+    }
+
+    /// Measure the temperature of the die in degrees Celsius, from the factory calibration of the
+    /// sensor.
+    ///
+    /// `vdda_mv` is the supply voltage of the ADC, usually measured with
+    /// [`read_vdda_mv`](Self::read_vdda_mv).
+    pub fn read_temperature_celsius(&mut self, temperature: &mut Temperature, vdda_mv: u32) -> f32 {
+        let _clock = self.auto_idle.wake();
+        // Sample as if it was converted with VDDA at the calibration voltage.
+        let sample = (Self::to_12_bit(self.read(temperature)) * vdda_mv) as f32 / TS_CALIB_MV as f32;
+        let cal1 = temperature.calibrated_value_1() as f32;
+
+        #[cfg(not(stm32g0))]
+        {
+            let cal2 = temperature.calibrated_value_2() as f32;
+            (Temperature::CAL2_CELSIUS - Temperature::CAL1_CELSIUS) as f32 * (sample - cal1) / (cal2 - cal1)
+                + Temperature::CAL1_CELSIUS as f32
+        }
+        #[cfg(stm32g0)]
+        {
+            let uv_per_count = (TS_CALIB_MV * 1000) as f32 / 4095.0;
+            (sample - cal1) * uv_per_count / Temperature::AVG_SLOPE_UV as f32 + Temperature::CAL1_CELSIUS as f32
+        }
+    }
+
+    /// Measure VBAT in millivolts, through the internal bridge divider.
+    ///
+    /// `vdda_mv` is the supply voltage of the ADC, usually measured with
+    /// [`read_vdda_mv`](Self::read_vdda_mv). Disable the VBAT channel when not measuring, since
+    /// the bridge draws current from the battery.
+    pub fn read_vbat_mv(&mut self, vbat: &mut Vbat, vdda_mv: u32) -> u32 {
+        let _clock = self.auto_idle.wake();
+        let sample = Self::to_12_bit(self.read(vbat));
+        sample * vdda_mv * VBAT_DIVIDER / 4095
+    }
+
+    /// Disable the VBAT channel, see [`enable_vbat`](Self::enable_vbat).
+    pub fn disable_vbat(&self, _vbat: Vbat) {
+        let _clock = self.auto_idle.wake();
+        #[cfg(not(adc_g0))]
+        T::common_regs().ccr().modify(|reg| {
+            reg.set_ch18sel(false);
+        });
+        #[cfg(adc_g0)]
+        T::regs().ccr().modify(|reg| {
+            reg.set_vbaten(false);
+        });
+    }
+
+    /// Scale a sample converted with the current resolution to 12 bits.
+    fn to_12_bit(sample: u16) -> u32 {
+        #[cfg(not(adc_g0))]
+        let res = T::regs().cfgr().read().res();
+        #[cfg(adc_g0)]
+        let res = T::regs().cfgr1().read().res();
+
+        // 12, 10, 8 or 6 bits.
+        (sample as u32) << (2 * res.to_bits() as u32)
+    }
+
     pub fn set_sample_time(&mut self, sample_time: SampleTime) {
         self.sample_time = sample_time;
     }
 
     pub fn set_resolution(&mut self, resolution: Resolution) {
+        let _clock = self.auto_idle.wake();
         #[cfg(not(adc_g0))]
         T::regs().cfgr().modify(|reg| reg.set_res(resolution.into()));
         #[cfg(adc_g0)]
         T::regs().cfgr1().modify(|reg| reg.set_res(resolution.into()));
     }
 
-    /*
-    /// Convert a raw sample from the `Temperature` to deg C
-    pub fn to_degrees_centigrade(sample: u16) -> f32 {
-        (130.0 - 30.0) / (VtempCal130::get().read() as f32 - VtempCal30::get().read() as f32)
-            * (sample as f32 - VtempCal30::get().read() as f32)
-            + 30.0
-    }
-     */
-
     /// Perform a single conversion.
     fn convert(&mut self) -> u16 {
         T::regs().isr().modify(|reg| {
@@ -162,7 +417,189 @@ impl<'d, T: Instance> Adc<'d, T> {
         T::regs().dr().read().0 as u16
     }
 
-    pub fn read(&mut self, pin: &mut impl AdcPin<T>) -> u16 {
+    /// Configure the hardware oversampling of the regular conversions, `None` to disable it.
+    ///
+    /// `ratio` conversions are accumulated and the sum is shifted right by `shift` bits, between
+    /// 0 and 8. The shift must keep the result within 16 bits.
+    #[cfg(not(adc_g0))]
+    pub fn set_oversampling(&mut self, oversampling: Option<Oversampling>) {
+        let _clock = self.auto_idle.wake();
+        T::regs().cfgr2().modify(|reg| match oversampling {
+            Some(oversampling) => {
+                assert!(oversampling.shift <= 8);
+                reg.set_ovsr(oversampling.ratio as u8);
+                reg.set_ovss(oversampling.shift);
+                reg.set_rovse(true);
+            }
+            None => reg.set_rovse(false),
+        });
+    }
+
+    /// Create a new ADC driver with its interrupt enabled.
+    ///
+    /// The interrupt is needed by [`read_injected`](Self::read_injected) and
+    /// [`wait_for_out_of_range`](Self::wait_for_out_of_range).
+    #[cfg(adc_v3)]
+    pub fn new_with_interrupt(
+        adc: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        delay: &mut impl DelayUs<u32>,
+    ) -> Self {
+        let this = Self::new(adc, delay);
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        this
+    }
+
+    /// Configure the injected sequence, of up to [`MAX_INJECTED_LEN`] channels.
+    ///
+    /// Injected conversions interrupt the regular ones, and are usually started by a timer to
+    /// sample at a precise point of a PWM period, for example to measure motor phase currents.
+    #[cfg(adc_v3)]
+    pub fn configure_injected(&mut self, sequence: &mut [(&mut dyn AdcPin<T>, SampleTime)], trigger: InjectedTrigger) {
+        let _clock = self.auto_idle.wake();
+        assert!(!sequence.is_empty() && sequence.len() <= MAX_INJECTED_LEN);
+
+        Self::enable();
+        Self::stop_injected();
+
+        for (pin, sample_time) in sequence.iter() {
+            Self::set_channel_sample_time(pin.channel(), *sample_time);
+        }
+
+        T::regs().jsqr().write(|w| {
+            w.set_jl(sequence.len() as u8 - 1);
+            for (i, (pin, _)) in sequence.iter().enumerate() {
+                w.set_jsq(i, pin.channel());
+            }
+            match trigger {
+                InjectedTrigger::Software => w.set_jexten(Exten::DISABLED),
+                InjectedTrigger::External { source, edge } => {
+                    w.set_jextsel(source);
+                    w.set_jexten(match edge {
+                        TriggerEdge::Rising => Exten::RISINGEDGE,
+                        TriggerEdge::Falling => Exten::FALLINGEDGE,
+                        TriggerEdge::Both => Exten::BOTHEDGES,
+                    });
+                }
+            }
+        });
+    }
+
+    /// Convert the injected sequence, writing one sample per channel to `buf`.
+    ///
+    /// With an external trigger, the conversions start at the next trigger event.
+    #[cfg(adc_v3)]
+    pub async fn read_injected(&mut self, buf: &mut [u16]) {
+        let _clock = self.auto_idle.wake();
+        let len = T::regs().jsqr().read().jl() as usize + 1;
+        assert!(buf.len() >= len);
+
+        Self::enable();
+        T::regs().isr().write(|w| {
+            w.set_jeoc(true);
+            w.set_jeos(true);
+        });
+        T::regs().cr().modify(|w| w.set_jadstart(true));
+
+        let on_drop = OnDrop::new(|| Self::stop_injected());
+        poll_fn(|cx| {
+            T::state().waker.register(cx.waker());
+            T::regs().ier().modify(|w| w.set_jeosie(true));
+
+            if T::regs().isr().read().jeos() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+        on_drop.defuse();
+
+        for (i, sample) in buf[..len].iter_mut().enumerate() {
+            *sample = T::regs().jdr(i).read().jdata();
+        }
+    }
+
+    #[cfg(adc_v3)]
+    fn stop_injected() {
+        let r = T::regs();
+        if r.cr().read().jadstart() {
+            r.cr().modify(|w| w.set_jadstp(true));
+            while r.cr().read().jadstart() {}
+        }
+    }
+
+    /// Enable the analog watchdog on `pin`, or on all the channels when `None`.
+    ///
+    /// The watchdog flags conversions outside of `low..=high`, both regular and injected ones.
+    /// The thresholds are 12-bit values whatever the resolution.
+    #[cfg(adc_v3)]
+    pub fn enable_watchdog(&mut self, pin: Option<&mut dyn AdcPin<T>>, low: u16, high: u16) {
+        let _clock = self.auto_idle.wake();
+        assert!(low <= high && high < 1 << 12);
+
+        T::regs().tr1().write(|w| {
+            w.set_lt1(low);
+            w.set_ht1(high);
+        });
+        T::regs().cfgr().modify(|w| {
+            match pin {
+                Some(pin) => {
+                    w.set_awd1ch(pin.channel());
+                    w.set_awd1sgl(true);
+                }
+                None => w.set_awd1sgl(false),
+            }
+            w.set_awd1en(true);
+            w.set_jawd1en(true);
+        });
+    }
+
+    /// Disable the analog watchdog.
+    #[cfg(adc_v3)]
+    pub fn disable_watchdog(&mut self) {
+        let _clock = self.auto_idle.wake();
+        T::regs().cfgr().modify(|w| {
+            w.set_awd1en(false);
+            w.set_jawd1en(false);
+        });
+        T::regs().ier().modify(|w| w.set_awdie(0, false));
+    }
+
+    /// Wait until a conversion falls out of the range of the analog watchdog.
+    ///
+    /// Only conversions done while waiting are checked, for example injected ones. For continuous
+    /// conversions, use [`RingBufferedAdc::wait_for_out_of_range`](super::ringbuffered::RingBufferedAdc::wait_for_out_of_range).
+    #[cfg(adc_v3)]
+    pub async fn wait_for_out_of_range(&mut self) {
+        let _clock = self.auto_idle.wake();
+        T::regs().isr().write(|w| w.set_awd(0, true));
+
+        let on_drop = OnDrop::new(|| T::regs().ier().modify(|w| w.set_awdie(0, false)));
+        poll_fn(|cx| {
+            T::state().waker.register(cx.waker());
+            T::regs().ier().modify(|w| w.set_awdie(0, true));
+
+            if T::regs().isr().read().awd(0) {
+                T::regs().isr().write(|w| w.set_awd(0, true));
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+        on_drop.defuse();
+        T::regs().ier().modify(|w| w.set_awdie(0, false));
+    }
+
+    pub(super) fn enable() {
+        if T::regs().cr().read().aden() && !T::regs().cr().read().addis() {
+            return;
+        }
+
         // Make sure bits are off
         while T::regs().cr().read().addis() {
             // spin
@@ -179,6 +616,11 @@ impl<'d, T: Instance> Adc<'d, T> {
         while !T::regs().isr().read().adrdy() {
             // spin
         }
+    }
+
+    pub fn read(&mut self, pin: &mut impl AdcPin<T>) -> u16 {
+        let _clock = self.auto_idle.wake();
+        Self::enable();
 
         // Configure channel
         Self::set_channel_sample_time(pin.channel(), self.sample_time);
@@ -206,12 +648,12 @@ impl<'d, T: Instance> Adc<'d, T> {
     }
 
     #[cfg(adc_g0)]
-    fn set_channel_sample_time(_ch: u8, sample_time: SampleTime) {
+    pub(super) fn set_channel_sample_time(_ch: u8, sample_time: SampleTime) {
         T::regs().smpr().modify(|reg| reg.set_smp1(sample_time.into()));
     }
 
     #[cfg(not(adc_g0))]
-    fn set_channel_sample_time(ch: u8, sample_time: SampleTime) {
+    pub(super) fn set_channel_sample_time(ch: u8, sample_time: SampleTime) {
         let sample_time = sample_time.into();
         T::regs()
             .smpr(ch as usize / 10)
@@ -4,6 +4,7 @@ use pac::adc::vals::{Adcaldif, Boost, Difsel, Exten, Pcsel};
 use pac::adccommon::vals::Presc;
 
 use super::{Adc, AdcPin, Instance, InternalChannel, Resolution, SampleTime};
+use crate::rcc::AutoIdle;
 use crate::time::Hertz;
 use crate::{pac, Peripheral};
 
@@ -160,6 +161,7 @@ impl<'d, T: Instance> Adc<'d, T> {
         let mut s = Self {
             adc,
             sample_time: Default::default(),
+            auto_idle: AutoIdle::new(),
         };
         s.power_up(delay);
         s.configure_differential_inputs();
@@ -218,6 +220,7 @@ impl<'d, T: Instance> Adc<'d, T> {
 
     /// Enable reading the voltage reference internal channel.
     pub fn enable_vrefint(&self) -> VrefInt {
+        let _clock = self.auto_idle.wake();
         T::common_regs().ccr().modify(|reg| {
             reg.set_vrefen(true);
         });
@@ -227,6 +230,7 @@ impl<'d, T: Instance> Adc<'d, T> {
 
     /// Enable reading the temperature internal channel.
     pub fn enable_temperature(&self) -> Temperature {
+        let _clock = self.auto_idle.wake();
         T::common_regs().ccr().modify(|reg| {
             reg.set_vsenseen(true);
         });
@@ -236,6 +240,7 @@ impl<'d, T: Instance> Adc<'d, T> {
 
     /// Enable reading the vbat internal channel.
     pub fn enable_vbat(&self) -> Vbat {
+        let _clock = self.auto_idle.wake();
         T::common_regs().ccr().modify(|reg| {
             reg.set_vbaten(true);
         });
@@ -250,6 +255,7 @@ impl<'d, T: Instance> Adc<'d, T> {
 
     /// Set the ADC resolution.
     pub fn set_resolution(&mut self, resolution: Resolution) {
+        let _clock = self.auto_idle.wake();
         T::regs().cfgr().modify(|reg| reg.set_res(resolution.into()));
     }
 
@@ -278,6 +284,7 @@ impl<'d, T: Instance> Adc<'d, T> {
         P: AdcPin<T>,
         P: crate::gpio::sealed::Pin,
     {
+        let _clock = self.auto_idle.wake();
         pin.set_as_analog();
 
         self.read_channel(pin.channel())
@@ -285,6 +292,7 @@ impl<'d, T: Instance> Adc<'d, T> {
 
     /// Read an ADC internal channel.
     pub fn read_internal(&mut self, channel: &mut impl InternalChannel<T>) -> u16 {
+        let _clock = self.auto_idle.wake();
         self.read_channel(channel.channel())
     }
 
@@ -0,0 +1,222 @@
+//! Bit-banged I2C master.
+
+use embassy_time::{Duration, Instant};
+use embedded_hal_1::i2c::{NoAcknowledgeSource, Operation};
+
+use super::Pacer;
+use crate::gpio::{Level, OutputOpenDrain, Pin, Pull, Speed};
+use crate::time::Hertz;
+use crate::Peripheral;
+
+/// Bit-banged I2C error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// ACK not received.
+    Nack(NoAcknowledgeSource),
+    /// A slave stretched the clock for longer than the timeout.
+    Timeout,
+}
+
+impl embedded_hal_1::i2c::Error for Error {
+    fn kind(&self) -> embedded_hal_1::i2c::ErrorKind {
+        match *self {
+            Self::Nack(source) => embedded_hal_1::i2c::ErrorKind::NoAcknowledge(source),
+            Self::Timeout => embedded_hal_1::i2c::ErrorKind::Other,
+        }
+    }
+}
+
+/// Bit-banged I2C configuration.
+#[non_exhaustive]
+#[derive(Copy, Clone)]
+pub struct Config {
+    /// Clock frequency.
+    pub frequency: Hertz,
+    /// Enable the internal pull-ups on SDA and SCL.
+    ///
+    /// Using external pull-up resistors is recommended for I2C.
+    pub pullup: bool,
+    /// Longest clock stretching by a slave.
+    pub timeout: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            frequency: Hertz(100_000),
+            pullup: false,
+            timeout: Duration::from_millis(10),
+        }
+    }
+}
+
+/// Bit-banged I2C master.
+///
+/// Clock stretching is supported, but not multiple masters.
+pub struct BitbangI2c<'d> {
+    scl: OutputOpenDrain<'d>,
+    sda: OutputOpenDrain<'d>,
+    config: Config,
+    pacer: Pacer,
+}
+
+impl<'d> BitbangI2c<'d> {
+    /// Create a new bit-banged I2C master.
+    pub fn new(
+        scl: impl Peripheral<P = impl Pin> + 'd,
+        sda: impl Peripheral<P = impl Pin> + 'd,
+        config: Config,
+    ) -> Self {
+        let pull = match config.pullup {
+            true => Pull::Up,
+            false => Pull::None,
+        };
+        Self {
+            scl: OutputOpenDrain::new(scl, Level::High, Speed::Medium, pull),
+            sda: OutputOpenDrain::new(sda, Level::High, Speed::Medium, pull),
+            pacer: Pacer::new(config.frequency),
+            config,
+        }
+    }
+
+    /// Execute the operations of a transaction with the slave at `address`.
+    ///
+    /// Consecutive operations of the same kind are merged, and a repeated start separates a
+    /// read from a write. The transaction always ends with a stop condition.
+    pub async fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Error> {
+        self.pacer.start();
+        let result = self.transaction_inner(address, operations).await;
+        let stop = self.stop().await;
+        result.and(stop)
+    }
+
+    async fn transaction_inner(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Error> {
+        // Kind of the previous operation, true for a read.
+        let mut previous = None;
+        for i in 0..operations.len() {
+            let next_is_read = matches!(operations.get(i + 1), Some(Operation::Read(_)));
+            match &mut operations[i] {
+                Operation::Read(buffer) => {
+                    if previous != Some(true) {
+                        self.start(previous.is_some()).await?;
+                        self.write_address(address, true).await?;
+                    }
+                    let len = buffer.len();
+                    for (n, byte) in buffer.iter_mut().enumerate() {
+                        // The master NACKs the last byte it reads.
+                        let ack = n + 1 < len || next_is_read;
+                        *byte = self.read_byte(ack).await?;
+                    }
+                    previous = Some(true);
+                }
+                Operation::Write(buffer) => {
+                    if previous != Some(false) {
+                        self.start(previous.is_some()).await?;
+                        self.write_address(address, false).await?;
+                    }
+                    for &byte in buffer.iter() {
+                        if !self.write_byte(byte).await? {
+                            return Err(Error::Nack(NoAcknowledgeSource::Data));
+                        }
+                    }
+                    previous = Some(false);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn write_address(&mut self, address: u8, read: bool) -> Result<(), Error> {
+        match self.write_byte(address << 1 | read as u8).await? {
+            true => Ok(()),
+            false => Err(Error::Nack(NoAcknowledgeSource::Address)),
+        }
+    }
+
+    /// Release SCL, and wait until a slave stretching the clock releases it too.
+    async fn release_scl(&mut self) -> Result<(), Error> {
+        self.scl.set_high();
+        let deadline = Instant::now() + self.config.timeout;
+        while self.scl.is_low() {
+            if Instant::now() > deadline {
+                return Err(Error::Timeout);
+            }
+            embassy_futures::yield_now().await;
+        }
+        // Count the high half period from the end of the stretching.
+        self.pacer.start();
+        Ok(())
+    }
+
+    async fn start(&mut self, repeated: bool) -> Result<(), Error> {
+        if repeated {
+            self.sda.set_high();
+            self.pacer.wait().await;
+            self.release_scl().await?;
+            self.pacer.wait().await;
+        }
+        // START condition: SDA falling while SCL is high.
+        self.sda.set_low();
+        self.pacer.wait().await;
+        self.scl.set_low();
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> Result<(), Error> {
+        self.sda.set_low();
+        self.pacer.wait().await;
+        let result = self.release_scl().await;
+        self.pacer.wait().await;
+        // STOP condition: SDA rising while SCL is high.
+        self.sda.set_high();
+        self.pacer.wait().await;
+        result
+    }
+
+    async fn write_bit(&mut self, bit: bool) -> Result<(), Error> {
+        self.sda.set_level(bit.into());
+        self.pacer.wait().await;
+        self.release_scl().await?;
+        self.pacer.wait().await;
+        self.scl.set_low();
+        Ok(())
+    }
+
+    async fn read_bit(&mut self) -> Result<bool, Error> {
+        self.sda.set_high();
+        self.pacer.wait().await;
+        self.release_scl().await?;
+        self.pacer.wait().await;
+        let bit = self.sda.is_high();
+        self.scl.set_low();
+        Ok(bit)
+    }
+
+    /// Write a byte, returning whether it was ACKed.
+    async fn write_byte(&mut self, byte: u8) -> Result<bool, Error> {
+        for i in (0..8).rev() {
+            self.write_bit(byte & (1 << i) != 0).await?;
+        }
+        Ok(!self.read_bit().await?)
+    }
+
+    async fn read_byte(&mut self, ack: bool) -> Result<u8, Error> {
+        let mut byte = 0;
+        for _ in 0..8 {
+            byte = (byte << 1) | self.read_bit().await? as u8;
+        }
+        self.write_bit(!ack).await?;
+        Ok(byte)
+    }
+}
+
+impl<'d> embedded_hal_1::i2c::ErrorType for BitbangI2c<'d> {
+    type Error = Error;
+}
+
+impl<'d> embedded_hal_async::i2c::I2c for BitbangI2c<'d> {
+    async fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+        self.transaction(address, operations).await
+    }
+}
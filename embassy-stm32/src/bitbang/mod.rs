@@ -0,0 +1,42 @@
+//! Bit-banged SPI and I2C, on any GPIO pins.
+//!
+//! These drivers implement the same `embedded-hal-async` traits as the hardware
+//! [`Spi`](crate::spi::Spi) and [`I2c`](crate::i2c::I2c) drivers, so the code using a bus doesn't
+//! change when a board revision routes it to pins without a peripheral. The clock edges are
+//! scheduled on deadlines of the time driver, so the frequency doesn't depend on the time spent
+//! between the edges, but the clock period is rounded to the tick period, and is at least 2 ticks.
+//! They are much slower than the peripherals, and meant for low-speed sensors.
+
+use embassy_time::{Duration, Instant, Timer};
+
+use crate::time::Hertz;
+
+pub mod i2c;
+pub mod spi;
+
+/// Edge scheduler, waiting for deadlines every half period of the clock.
+struct Pacer {
+    half_period: Duration,
+    next: Instant,
+}
+
+impl Pacer {
+    fn new(frequency: Hertz) -> Self {
+        let half_period = Duration::from_hz(2 * frequency.0 as u64).max(Duration::from_ticks(1));
+        Self {
+            half_period,
+            next: Instant::now(),
+        }
+    }
+
+    /// Start a transfer, counting the half periods from now.
+    fn start(&mut self) {
+        self.next = Instant::now();
+    }
+
+    /// Wait for the next half period.
+    async fn wait(&mut self) {
+        self.next += self.half_period;
+        Timer::at(self.next).await;
+    }
+}
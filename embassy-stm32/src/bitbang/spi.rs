@@ -0,0 +1,213 @@
+//! Bit-banged SPI master.
+
+use core::convert::Infallible;
+
+pub use embedded_hal_1::spi::{Mode, Phase, Polarity, MODE_0, MODE_1, MODE_2, MODE_3};
+
+use super::Pacer;
+use crate::gpio::{Input, Level, Output, Pin, Pull, Speed};
+use crate::time::Hertz;
+use crate::Peripheral;
+
+/// Bit order.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum BitOrder {
+    /// Least significant bit first.
+    LsbFirst,
+    /// Most significant bit first.
+    MsbFirst,
+}
+
+/// Bit-banged SPI configuration.
+#[non_exhaustive]
+#[derive(Copy, Clone)]
+pub struct Config {
+    /// SPI mode.
+    pub mode: Mode,
+    /// Bit order.
+    pub bit_order: BitOrder,
+    /// Clock frequency.
+    pub frequency: Hertz,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            mode: MODE_0,
+            bit_order: BitOrder::MsbFirst,
+            frequency: Hertz(100_000),
+        }
+    }
+}
+
+/// Bit-banged SPI master.
+pub struct BitbangSpi<'d> {
+    sck: Output<'d>,
+    mosi: Option<Output<'d>>,
+    miso: Option<Input<'d>>,
+    config: Config,
+    pacer: Pacer,
+}
+
+impl<'d> BitbangSpi<'d> {
+    /// Create a new bit-banged SPI master.
+    pub fn new(
+        sck: impl Peripheral<P = impl Pin> + 'd,
+        mosi: impl Peripheral<P = impl Pin> + 'd,
+        miso: impl Peripheral<P = impl Pin> + 'd,
+        config: Config,
+    ) -> Self {
+        let mosi = Output::new(mosi, Level::Low, Speed::Medium);
+        let miso = Input::new(miso, Pull::None);
+        Self::new_inner(sck, Some(mosi), Some(miso), config)
+    }
+
+    /// Create a new bit-banged SPI master, only writing.
+    pub fn new_txonly(
+        sck: impl Peripheral<P = impl Pin> + 'd,
+        mosi: impl Peripheral<P = impl Pin> + 'd,
+        config: Config,
+    ) -> Self {
+        let mosi = Output::new(mosi, Level::Low, Speed::Medium);
+        Self::new_inner(sck, Some(mosi), None, config)
+    }
+
+    /// Create a new bit-banged SPI master, only reading.
+    pub fn new_rxonly(
+        sck: impl Peripheral<P = impl Pin> + 'd,
+        miso: impl Peripheral<P = impl Pin> + 'd,
+        config: Config,
+    ) -> Self {
+        let miso = Input::new(miso, Pull::None);
+        Self::new_inner(sck, None, Some(miso), config)
+    }
+
+    fn new_inner(
+        sck: impl Peripheral<P = impl Pin> + 'd,
+        mosi: Option<Output<'d>>,
+        miso: Option<Input<'d>>,
+        config: Config,
+    ) -> Self {
+        let idle = match config.mode.polarity {
+            Polarity::IdleLow => Level::Low,
+            Polarity::IdleHigh => Level::High,
+        };
+        Self {
+            sck: Output::new(sck, idle, Speed::Medium),
+            mosi,
+            miso,
+            pacer: Pacer::new(config.frequency),
+            config,
+        }
+    }
+
+    /// Reconfigure the driver.
+    pub fn set_config(&mut self, config: &Config) {
+        self.config = *config;
+        self.pacer = Pacer::new(config.frequency);
+        match config.mode.polarity {
+            Polarity::IdleLow => self.sck.set_low(),
+            Polarity::IdleHigh => self.sck.set_high(),
+        }
+    }
+
+    /// Simultaneously write `words` on MOSI and read MISO into them.
+    pub async fn transfer_in_place(&mut self, words: &mut [u8]) {
+        self.pacer.start();
+        for word in words {
+            *word = self.transfer_word(*word).await;
+        }
+    }
+
+    /// Write `words`, ignoring MISO.
+    pub async fn write(&mut self, words: &[u8]) {
+        self.pacer.start();
+        for &word in words {
+            self.transfer_word(word).await;
+        }
+    }
+
+    /// Read into `words`, writing zeros.
+    pub async fn read(&mut self, words: &mut [u8]) {
+        self.pacer.start();
+        for word in words {
+            *word = self.transfer_word(0).await;
+        }
+    }
+
+    /// Simultaneously write `write` and read into `read`, for `max(read.len(), write.len())` words.
+    ///
+    /// The extra words of `read` are ignored, and `write` is padded with zeros.
+    pub async fn transfer(&mut self, read: &mut [u8], write: &[u8]) {
+        self.pacer.start();
+        for i in 0..read.len().max(write.len()) {
+            let word = self.transfer_word(write.get(i).copied().unwrap_or(0)).await;
+            if let Some(r) = read.get_mut(i) {
+                *r = word;
+            }
+        }
+    }
+
+    async fn transfer_word(&mut self, word: u8) -> u8 {
+        let mut read = 0;
+        for i in 0..8 {
+            let bit = match self.config.bit_order {
+                BitOrder::MsbFirst => 7 - i,
+                BitOrder::LsbFirst => i,
+            };
+            if self.transfer_bit(word & (1 << bit) != 0).await {
+                read |= 1 << bit;
+            }
+        }
+        read
+    }
+
+    async fn transfer_bit(&mut self, bit: bool) -> bool {
+        // With the first phase, the data is valid before the first edge, and sampled on it. With
+        // the second phase, it is valid after the first edge, and sampled on the second one.
+        if self.config.mode.phase == Phase::CaptureOnSecondTransition {
+            self.sck.toggle();
+        }
+        if let Some(mosi) = &mut self.mosi {
+            mosi.set_level(bit.into());
+        }
+        self.pacer.wait().await;
+        self.sck.toggle();
+        let read = self.miso.as_ref().map_or(false, |miso| miso.is_high());
+        self.pacer.wait().await;
+        if self.config.mode.phase == Phase::CaptureOnFirstTransition {
+            self.sck.toggle();
+        }
+        read
+    }
+}
+
+impl<'d> embedded_hal_1::spi::ErrorType for BitbangSpi<'d> {
+    type Error = Infallible;
+}
+
+impl<'d> embedded_hal_async::spi::SpiBus<u8> for BitbangSpi<'d> {
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        self.write(words).await;
+        Ok(())
+    }
+
+    async fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        self.read(words).await;
+        Ok(())
+    }
+
+    async fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        self.transfer(read, write).await;
+        Ok(())
+    }
+
+    async fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        self.transfer_in_place(words).await;
+        Ok(())
+    }
+}
@@ -92,6 +92,9 @@ impl<T: Instance> interrupt::typelevel::Handler<T::SCEInterrupt> for SceInterrup
 }
 
 /// CAN driver
+///
+/// The hardware filter banks are configured with `modify_filters()` of the `bxcan` driver, which is
+/// reachable through `Deref`. The `bxcan` driver also implements the `embedded-can` traits.
 pub struct Can<'d, T: Instance> {
     can: bxcan::Can<BxcanInstance<'d, T>>,
 }
@@ -137,6 +140,8 @@ impl<'d, T: Instance> Can<'d, T> {
         {
             T::regs().ier().write(|w| {
                 w.set_errie(true);
+                w.set_bofie(true);
+                w.set_epvie(true);
                 w.set_fmpie(0, true);
                 w.set_fmpie(1, true);
                 w.set_tmeie(true);
@@ -183,6 +188,50 @@ impl<'d, T: Instance> Can<'d, T> {
             .leave_disabled();
     }
 
+    /// Enable or disable automatic bus-off recovery.
+    ///
+    /// When enabled, the peripheral leaves the Bus_Off state by itself after monitoring 128
+    /// occurrences of 11 consecutive recessive bits. Otherwise, use
+    /// [`recover_from_bus_off`](Self::recover_from_bus_off). This must be set before enabling the
+    /// peripheral.
+    pub fn set_automatic_bus_off_recovery(&mut self, enabled: bool) {
+        T::regs().mcr().modify(|w| w.set_abom(enabled));
+    }
+
+    /// Read the error counters.
+    pub fn error_counters(&self) -> ErrorCounters {
+        let esr = T::regs().esr().read();
+        ErrorCounters {
+            transmit: esr.tec(),
+            receive: esr.rec(),
+            bus_off: esr.boff(),
+        }
+    }
+
+    /// Recover from the Bus_Off state.
+    ///
+    /// This requests the recovery sequence, and waits until the peripheral has monitored 128
+    /// occurrences of 11 consecutive recessive bits and is back on the bus. Returns immediately if
+    /// the peripheral is not in the Bus_Off state.
+    pub async fn recover_from_bus_off(&mut self) {
+        let regs = T::regs();
+        if !regs.esr().read().boff() {
+            return;
+        }
+
+        // Entering and leaving initialization mode starts the recovery sequence.
+        regs.mcr().modify(|w| w.set_inrq(true));
+        while !regs.msr().read().inak() {
+            embassy_futures::yield_now().await;
+        }
+        regs.mcr().modify(|w| w.set_inrq(false));
+
+        // There is no interrupt for leaving the Bus_Off state.
+        while regs.esr().read().boff() || regs.msr().read().inak() {
+            embassy_futures::yield_now().await;
+        }
+    }
+
     /// Enables the peripheral and synchronizes with the bus.
     ///
     /// This will wait for 11 consecutive recessive bits (bus idle state).
@@ -1,4 +1,4 @@
-//! Enums shared between CAN controller types.
+//! Enums and types shared between CAN controller types.
 
 /// Bus error
 #[derive(Debug)]
@@ -28,3 +28,15 @@ pub enum BusError {
     ///  At least one of error counter has reached the Error_Warning limit of 96.
     BusWarning,
 }
+
+/// CAN error counters and state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ErrorCounters {
+    /// Transmit error counter.
+    pub transmit: u8,
+    /// Receive error counter.
+    pub receive: u8,
+    /// The controller is in the Bus_Off state.
+    pub bus_off: bool,
+}
@@ -1,5 +1,6 @@
 use core::future::poll_fn;
 use core::marker::PhantomData;
+use core::num::NonZeroU8;
 use core::ops::{Deref, DerefMut};
 use core::task::Poll;
 
@@ -91,6 +92,26 @@ impl RxFrame {
     }
 }
 
+/// Entry of the TX event FIFO.
+///
+/// An event is stored when a frame whose header has a `marker` is transmitted, which allows
+/// matching transmissions to the frames queued with `write`.
+#[derive(Debug, Clone, Copy)]
+pub struct TxEvent {
+    /// Identifier of the transmitted frame.
+    pub id: Id,
+    /// Marker of the transmitted frame.
+    pub marker: u8,
+    /// Data length of the transmitted frame, in bytes.
+    pub len: u8,
+    /// The frame was transmitted in CAN FD format.
+    pub fd_format: bool,
+    /// The frame was transmitted with bit rate switching.
+    pub bit_rate_switching: bool,
+    /// Value of the FDCAN timestamp counter when the frame was transmitted.
+    pub timestamp: u16,
+}
+
 /// Payload of a (FD)CAN data frame.
 ///
 /// Contains 0 to 64 Bytes of data.
@@ -162,6 +183,7 @@ impl<T: Instance> interrupt::typelevel::Handler<T::IT0Interrupt> for IT0Interrup
         if ir.tefn() {
             regs.ir().write(|w| w.set_tefn(true));
             T::state().tx_waker.wake();
+            T::state().tx_event_waker.wake();
         }
 
         if ir.ped() || ir.pea() {
@@ -276,6 +298,82 @@ fn curr_error<T: Instance>() -> Option<BusError> {
     None
 }
 
+fn error_counters<T: Instance>() -> ErrorCounters {
+    let ecr = T::regs().ecr().read();
+    ErrorCounters {
+        transmit: ecr.tec() as u8,
+        receive: ecr.rec() as u8,
+        bus_off: T::regs().psr().read().bo(),
+    }
+}
+
+/// Address of an element of the TX event FIFO in the message RAM.
+#[cfg(not(stm32h7))]
+fn tx_event_element<T: Instance>(index: usize) -> *const u32 {
+    // The message RAM layout is fixed, the TX event FIFO starts at 0x260.
+    unsafe { (T::MSG_RAM as *const u8).add(0x260).cast::<u32>().add(2 * index) }
+}
+
+/// Address of an element of the TX event FIFO in the message RAM.
+#[cfg(stm32h7)]
+fn tx_event_element<T: Instance>(index: usize) -> *const u32 {
+    // The start address is set by `configure_msg_ram`, in words.
+    let start = T::regs().txefc().read().efsa() as usize;
+    unsafe { (T::MSG_RAM as *const u32).add(start + 2 * index) }
+}
+
+fn try_read_tx_event<T: Instance>() -> Option<TxEvent> {
+    let regs = T::regs();
+    let status = regs.txefs().read();
+    if status.effl() == 0 {
+        return None;
+    }
+
+    let index = status.efgi();
+    let element = tx_event_element::<T>(index as usize);
+    // SAFETY: the element is in the message RAM, and owned by the CPU until acknowledged.
+    let (e0, e1) = unsafe { (element.read_volatile(), element.add(1).read_volatile()) };
+    regs.txefa().write(|w| w.set_efai(index));
+
+    let id = if e0 & (1 << 30) != 0 {
+        Id::Extended(ExtendedId::new(e0 & 0x1FFF_FFFF).unwrap())
+    } else {
+        Id::Standard(StandardId::new(((e0 >> 18) & 0x7FF) as u16).unwrap())
+    };
+    let fd_format = e1 & (1 << 21) != 0;
+    let len = match ((e1 >> 16) & 0xF) as u8 {
+        dlc @ 0..=8 => dlc,
+        _ if !fd_format => 8,
+        9 => 12,
+        10 => 16,
+        11 => 20,
+        12 => 24,
+        13 => 32,
+        14 => 48,
+        _ => 64,
+    };
+
+    Some(TxEvent {
+        id,
+        marker: (e1 >> 24) as u8,
+        len,
+        fd_format,
+        bit_rate_switching: e1 & (1 << 20) != 0,
+        timestamp: e1 as u16,
+    })
+}
+
+async fn read_tx_event<T: Instance>() -> TxEvent {
+    poll_fn(|cx| {
+        T::state().tx_event_waker.register(cx.waker());
+        match try_read_tx_event::<T>() {
+            Some(event) => Poll::Ready(event),
+            None => Poll::Pending,
+        }
+    })
+    .await
+}
+
 impl<'d, T: Instance> Fdcan<'d, T, fdcan::ConfigMode> {
     /// Creates a new Fdcan instance, keeping the peripheral in sleep mode.
     /// You must call [Fdcan::enable_non_blocking] to use the peripheral.
@@ -323,6 +421,7 @@ impl<'d, T: Instance> Fdcan<'d, T, fdcan::ConfigMode> {
         can.enable_interrupt(fdcan::interrupt::Interrupt::RxFifo0NewMsg);
         can.enable_interrupt(fdcan::interrupt::Interrupt::RxFifo1NewMsg);
         can.enable_interrupt(fdcan::interrupt::Interrupt::TxComplete);
+        can.enable_interrupt(fdcan::interrupt::Interrupt::TxEventFifoNewEntry);
         can.enable_interrupt_line(fdcan::interrupt::InterruptLine::_0, true);
         can.enable_interrupt_line(fdcan::interrupt::InterruptLine::_1, true);
 
@@ -340,6 +439,37 @@ impl<'d, T: Instance> Fdcan<'d, T, fdcan::ConfigMode> {
             seg2: bit_timing.seg2,
         });
     }
+
+    /// Configures the data phase bit timings calculated from the supplied bitrate, and allows
+    /// CAN FD frames with bit rate switching.
+    ///
+    /// Frames are sent with the data bitrate when their header has `bit_rate_switching` set.
+    /// Enable `transceiver_delay_compensation` for data bitrates above 1 Mbit/s.
+    pub fn set_fd_data_bitrate(&mut self, bitrate: u32, transceiver_delay_compensation: bool) {
+        let bit_timing = util::calc_can_timings(T::frequency(), bitrate).unwrap();
+        // The data phase prescaler is limited to 32.
+        let prescaler = u8::try_from(bit_timing.prescaler.get())
+            .ok()
+            .filter(|p| *p <= 32)
+            .and_then(NonZeroU8::new)
+            .expect("data bitrate too low for the FDCAN kernel clock");
+        self.can.set_data_bit_timing(config::DataBitTiming {
+            transceiver_delay_compensation,
+            sync_jump_width: bit_timing.sync_jump_width,
+            prescaler,
+            seg1: bit_timing.seg1,
+            seg2: bit_timing.seg2,
+        });
+        self.can
+            .set_frame_transmit(config::FrameTransmissionConfig::AllowFdCanAndBRS);
+    }
+}
+
+impl<'d, T: Instance, M: FdcanOperatingMode> Fdcan<'d, T, M> {
+    /// Read the error counters.
+    pub fn error_counters(&self) -> ErrorCounters {
+        error_counters::<T>()
+    }
 }
 
 macro_rules! impl_transition {
@@ -402,6 +532,16 @@ where
         .await
     }
 
+    /// Waits for the next entry of the TX event FIFO.
+    pub async fn read_tx_event(&mut self) -> TxEvent {
+        read_tx_event::<T>().await
+    }
+
+    /// Reads the next entry of the TX event FIFO, if any.
+    pub fn try_read_tx_event(&mut self) -> Option<TxEvent> {
+        try_read_tx_event::<T>()
+    }
+
     /// Flush one of the TX mailboxes.
     pub async fn flush(&self, mb: fdcan::Mailbox) {
         poll_fn(|cx| {
@@ -501,6 +641,16 @@ impl<'c, 'd, T: Instance, M: fdcan::Transmit> FdcanTx<'c, 'd, T, M> {
         })
         .await
     }
+
+    /// Waits for the next entry of the TX event FIFO.
+    pub async fn read_tx_event(&mut self) -> TxEvent {
+        read_tx_event::<T>().await
+    }
+
+    /// Reads the next entry of the TX event FIFO, if any.
+    pub fn try_read_tx_event(&mut self) -> Option<TxEvent> {
+        try_read_tx_event::<T>()
+    }
 }
 
 /// FDCAN Rx only Instance
@@ -570,6 +720,7 @@ pub(crate) mod sealed {
 
     pub struct State {
         pub tx_waker: AtomicWaker,
+        pub tx_event_waker: AtomicWaker,
         pub err_waker: AtomicWaker,
         pub rx_waker: AtomicWaker,
     }
@@ -578,6 +729,7 @@ pub(crate) mod sealed {
         pub const fn new() -> Self {
             Self {
                 tx_waker: AtomicWaker::new(),
+                tx_event_waker: AtomicWaker::new(),
                 err_waker: AtomicWaker::new(),
                 rx_waker: AtomicWaker::new(),
             }
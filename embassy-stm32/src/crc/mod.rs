@@ -5,3 +5,27 @@
 mod _version;
 
 pub use _version::*;
+#[cfg(any(dma, bdma, gpdma))]
+use embassy_hal_internal::into_ref;
+
+#[cfg(any(dma, bdma, gpdma))]
+use crate::dma::word::Word;
+#[cfg(any(dma, bdma, gpdma))]
+use crate::dma::{Channel, Transfer};
+#[cfg(any(dma, bdma, gpdma))]
+use crate::Peripheral;
+
+/// Number of values written to the CRC unit per DMA transfer, within the transfer length limit of
+/// all the DMA controllers.
+#[cfg(any(dma, bdma, gpdma))]
+const DMA_CHUNK_LEN: usize = 0x3FFF;
+
+/// Write `data` to the CRC data register `dr` with a memory-to-memory DMA transfer.
+#[cfg(any(dma, bdma, gpdma))]
+async fn feed_dma<W: Word>(dma: impl Peripheral<P = impl Channel>, data: &[W], dr: *mut W) {
+    into_ref!(dma);
+    for chunk in data.chunks(DMA_CHUNK_LEN) {
+        // safety: the data register stays valid, and the transfer is awaited before `chunk` is released.
+        unsafe { Transfer::new_write_memory_to_memory(dma.reborrow(), chunk, dr, Default::default()) }.await;
+    }
+}
@@ -1,5 +1,7 @@
 use embassy_hal_internal::{into_ref, PeripheralRef};
 
+#[cfg(any(dma, bdma, gpdma))]
+use crate::dma::Channel;
 use crate::pac::CRC as PAC_CRC;
 use crate::peripherals::CRC;
 use crate::rcc::sealed::RccPeripheral;
@@ -45,6 +47,16 @@ impl<'d> Crc<'d> {
         self.read()
     }
 
+    /// Feed a slice of words to the peripheral with DMA, and return the result.
+    ///
+    /// The DMA channel copies the words to the CRC unit without the CPU, for large buffers like
+    /// firmware images. The channel must support memory-to-memory transfers.
+    #[cfg(any(dma, bdma, gpdma))]
+    pub async fn feed_words_dma(&mut self, dma: impl Peripheral<P = impl Channel>, words: &[u32]) -> u32 {
+        super::feed_dma(dma, words, PAC_CRC.dr().as_ptr() as *mut u32).await;
+        self.read()
+    }
+
     /// Read the CRC result value.
     pub fn read(&self) -> u32 {
         PAC_CRC.dr().read()
@@ -1,5 +1,7 @@
 use embassy_hal_internal::{into_ref, PeripheralRef};
 
+#[cfg(any(dma, bdma, gpdma))]
+use crate::dma::Channel;
 use crate::pac::crc::vals;
 use crate::pac::CRC as PAC_CRC;
 use crate::peripherals::CRC;
@@ -158,6 +160,15 @@ impl<'d> Crc<'d> {
         }
         PAC_CRC.dr().read()
     }
+    /// Feeds a slice of bytes into the CRC peripheral with DMA. Returns the computed checksum.
+    ///
+    /// The DMA channel copies the bytes to the CRC unit without the CPU, for large buffers like
+    /// firmware images. The channel must support memory-to-memory transfers.
+    #[cfg(any(dma, bdma, gpdma))]
+    pub async fn feed_bytes_dma(&mut self, dma: impl Peripheral<P = impl Channel>, bytes: &[u8]) -> u32 {
+        super::feed_dma(dma, bytes, PAC_CRC.dr8().as_ptr() as *mut u8).await;
+        PAC_CRC.dr().read()
+    }
     /// Feeds a words into the CRC peripheral. Returns the computed checksum.
     pub fn feed_word(&mut self, word: u32) -> u32 {
         PAC_CRC.dr().write_value(word as u32);
@@ -170,4 +181,13 @@ impl<'d> Crc<'d> {
         }
         PAC_CRC.dr().read()
     }
+    /// Feeds a slice of words into the CRC peripheral with DMA. Returns the computed checksum.
+    ///
+    /// The DMA channel copies the words to the CRC unit without the CPU, for large buffers like
+    /// firmware images. The channel must support memory-to-memory transfers.
+    #[cfg(any(dma, bdma, gpdma))]
+    pub async fn feed_words_dma(&mut self, dma: impl Peripheral<P = impl Channel>, words: &[u32]) -> u32 {
+        super::feed_dma(dma, words, PAC_CRC.dr().as_ptr() as *mut u32).await;
+        PAC_CRC.dr().read()
+    }
 }
@@ -0,0 +1,593 @@
+//! Cryptographic processor (CRYP), hardware AES.
+//!
+//! The processor encrypts and decrypts with AES in ECB, CBC and CTR modes, and on the parts with
+//! a GCM-capable processor, authenticates with AES-GCM. The blocking methods feed the processor
+//! from the CPU, and the async methods with two DMA channels.
+//!
+//! With the `cipher` and `aead` features, [`AesCipher`] and [`AesGcm`] implement the RustCrypto
+//! traits, for the crates generic over the block cipher or the AEAD they use.
+#![macro_use]
+
+#[cfg(any(feature = "cipher", all(feature = "aead", not(cryp_v1))))]
+use core::marker::PhantomData;
+
+use embassy_futures::join::join;
+use embassy_hal_internal::{into_ref, PeripheralRef};
+
+use crate::dma::{NoDma, Transfer};
+use crate::{pac, peripherals, Peripheral};
+
+/// Size of an AES block, in bytes.
+pub const BLOCK_SIZE: usize = 16;
+
+/// Number of words moved per DMA transfer, a whole number of blocks within the transfer length
+/// limit of all the DMA controllers.
+const DMA_CHUNK_LEN: usize = 0x3FFC;
+
+const ALGOMODE_AES_ECB: u8 = 4;
+const ALGOMODE_AES_CBC: u8 = 5;
+const ALGOMODE_AES_CTR: u8 = 6;
+const ALGOMODE_AES_KEY_PREPARATION: u8 = 7;
+#[cfg(not(cryp_v1))]
+const ALGOMODE_AES_GCM: u8 = 8;
+
+/// Data swapping of 8-bit data, so the blocks are fed as byte strings.
+const DATATYPE_BYTES: u8 = 2;
+
+#[cfg(not(cryp_v1))]
+const GCM_PHASE_INIT: u8 = 0;
+#[cfg(not(cryp_v1))]
+const GCM_PHASE_HEADER: u8 = 1;
+#[cfg(not(cryp_v1))]
+const GCM_PHASE_PAYLOAD: u8 = 2;
+#[cfg(not(cryp_v1))]
+const GCM_PHASE_FINAL: u8 = 3;
+
+/// Direction of the operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Direction {
+    /// Encrypt the plaintext.
+    Encrypt,
+    /// Decrypt the ciphertext.
+    Decrypt,
+}
+
+/// Chaining mode of the AES blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Mode {
+    /// Electronic codebook, each block processed independently.
+    Ecb,
+    /// Cipher block chaining, with the initialization vector.
+    Cbc([u8; 16]),
+    /// Counter, with the initial counter block, whose last 32 bits are incremented for each block.
+    Ctr([u8; 16]),
+}
+
+impl Mode {
+    fn algomode(&self) -> u8 {
+        match self {
+            Mode::Ecb => ALGOMODE_AES_ECB,
+            Mode::Cbc(_) => ALGOMODE_AES_CBC,
+            Mode::Ctr(_) => ALGOMODE_AES_CTR,
+        }
+    }
+
+    fn iv(&self) -> Option<&[u8; 16]> {
+        match self {
+            Mode::Ecb => None,
+            Mode::Cbc(iv) | Mode::Ctr(iv) => Some(iv),
+        }
+    }
+}
+
+/// CRYP driver.
+pub struct Cryp<'d, T: Instance, DmaIn = NoDma, DmaOut = NoDma> {
+    _peri: PeripheralRef<'d, T>,
+    indma: PeripheralRef<'d, DmaIn>,
+    outdma: PeripheralRef<'d, DmaOut>,
+}
+
+impl<'d, T: Instance, DmaIn, DmaOut> Cryp<'d, T, DmaIn, DmaOut> {
+    /// Create a new CRYP driver.
+    ///
+    /// The DMA channels are only used by the async methods, pass [`NoDma`] to only use the
+    /// blocking ones.
+    pub fn new(
+        peri: impl Peripheral<P = T> + 'd,
+        indma: impl Peripheral<P = DmaIn> + 'd,
+        outdma: impl Peripheral<P = DmaOut> + 'd,
+    ) -> Self {
+        into_ref!(peri, indma, outdma);
+        T::enable_and_reset();
+        Self {
+            _peri: peri,
+            indma,
+            outdma,
+        }
+    }
+
+    /// Encrypt or decrypt `input` to `output` with `key`, of 16, 24 or 32 bytes.
+    ///
+    /// In ECB and CBC modes, the length of `input` must be a multiple of [`BLOCK_SIZE`]. In CTR
+    /// mode, the last block may be partial.
+    pub fn blocking_process(&mut self, key: &[u8], mode: Mode, dir: Direction, input: &[u8], output: &mut [u8]) {
+        check_lengths(&mode, input, output);
+        start::<T>(key, mode.algomode(), dir, mode.iv());
+        for (input, output) in input.chunks(BLOCK_SIZE).zip(output.chunks_mut(BLOCK_SIZE)) {
+            process_block::<T>(input, output);
+        }
+        stop::<T>();
+    }
+
+    /// Encrypt or decrypt `input` to `output` with AES-GCM, and return the authentication tag.
+    ///
+    /// `aad` is authenticated but not encrypted. When decrypting, the returned tag must be compared
+    /// with the received one before using `output`. On CRYP v2, the length of the plaintext must be
+    /// a multiple of [`BLOCK_SIZE`] when encrypting.
+    #[cfg(not(cryp_v1))]
+    pub fn blocking_gcm(
+        &mut self,
+        key: &[u8],
+        iv: &[u8; 12],
+        dir: Direction,
+        aad: &[u8],
+        input: &[u8],
+        output: &mut [u8],
+    ) -> [u8; 16] {
+        check_gcm_lengths(dir, input, output);
+        gcm_start::<T>(key, iv, dir, aad);
+        for (input, output) in input.chunks(BLOCK_SIZE).zip(output.chunks_mut(BLOCK_SIZE)) {
+            process_block::<T>(input, output);
+        }
+        gcm_finish::<T>(aad.len(), input.len())
+    }
+
+    /// Get a RustCrypto block cipher, encrypting and decrypting single blocks with `key`.
+    #[cfg(feature = "cipher")]
+    pub fn block_cipher(&mut self, key: &[u8]) -> AesCipher<'_, T> {
+        AesCipher {
+            key: Key::new(key),
+            _cryp: PhantomData,
+        }
+    }
+
+    /// Get a RustCrypto AEAD, authenticating with AES-GCM with `key`.
+    #[cfg(all(feature = "aead", not(cryp_v1)))]
+    pub fn aead(&mut self, key: &[u8]) -> AesGcm<'_, T> {
+        AesGcm {
+            key: Key::new(key),
+            _cryp: PhantomData,
+        }
+    }
+}
+
+impl<'d, T: Instance, DmaIn, DmaOut> Cryp<'d, T, DmaIn, DmaOut>
+where
+    DmaIn: crate::cryp::DmaIn<T>,
+    DmaOut: crate::cryp::DmaOut<T>,
+{
+    /// Encrypt or decrypt `input` to `output` with `key`, of 16, 24 or 32 bytes.
+    ///
+    /// The buffers must be 4-byte aligned. In ECB and CBC modes, the length of `input` must be a
+    /// multiple of [`BLOCK_SIZE`]. In CTR mode, the last block may be partial.
+    pub async fn process(&mut self, key: &[u8], mode: Mode, dir: Direction, input: &[u8], output: &mut [u8]) {
+        check_lengths(&mode, input, output);
+        start::<T>(key, mode.algomode(), dir, mode.iv());
+        self.dma_payload(input, output).await;
+        stop::<T>();
+    }
+
+    /// Encrypt or decrypt `input` to `output` with AES-GCM, and return the authentication tag.
+    ///
+    /// The buffers must be 4-byte aligned. See [`blocking_gcm`](Self::blocking_gcm).
+    #[cfg(not(cryp_v1))]
+    pub async fn gcm(
+        &mut self,
+        key: &[u8],
+        iv: &[u8; 12],
+        dir: Direction,
+        aad: &[u8],
+        input: &[u8],
+        output: &mut [u8],
+    ) -> [u8; 16] {
+        check_gcm_lengths(dir, input, output);
+        gcm_start::<T>(key, iv, dir, aad);
+        self.dma_payload(input, output).await;
+        gcm_finish::<T>(aad.len(), input.len())
+    }
+
+    /// Process the whole blocks of `input` with DMA, and the last partial block from the CPU.
+    async fn dma_payload(&mut self, input: &[u8], output: &mut [u8]) {
+        assert!(input.as_ptr() as usize % 4 == 0 && output.as_ptr() as usize % 4 == 0);
+
+        let r = T::regs();
+        let blocks = input.len() / BLOCK_SIZE * BLOCK_SIZE;
+        // safety: the buffers are aligned, and the words are only accessed as bytes otherwise.
+        let src = unsafe { core::slice::from_raw_parts(input.as_ptr() as *const u32, blocks / 4) };
+        let dst = unsafe { core::slice::from_raw_parts_mut(output.as_mut_ptr() as *mut u32, blocks / 4) };
+
+        for (src, dst) in src.chunks(DMA_CHUNK_LEN).zip(dst.chunks_mut(DMA_CHUNK_LEN)) {
+            let out_request = self.outdma.request();
+            let out_f = unsafe {
+                Transfer::new_read(
+                    &mut self.outdma,
+                    out_request,
+                    r.dout().as_ptr(),
+                    dst,
+                    Default::default(),
+                )
+            };
+            let in_request = self.indma.request();
+            let in_f =
+                unsafe { Transfer::new_write(&mut self.indma, in_request, src, r.din().as_ptr(), Default::default()) };
+
+            r.dmacr().modify(|w| {
+                w.set_dien(true);
+                w.set_doen(true);
+            });
+            join(in_f, out_f).await;
+            r.dmacr().modify(|w| {
+                w.set_dien(false);
+                w.set_doen(false);
+            });
+        }
+
+        if blocks < input.len() {
+            process_block::<T>(&input[blocks..], &mut output[blocks..]);
+        }
+    }
+}
+
+fn check_lengths(mode: &Mode, input: &[u8], output: &mut [u8]) {
+    assert!(input.len() == output.len());
+    if !matches!(mode, Mode::Ctr(_)) {
+        assert!(input.len() % BLOCK_SIZE == 0);
+    }
+}
+
+#[cfg(not(cryp_v1))]
+fn check_gcm_lengths(dir: Direction, input: &[u8], output: &mut [u8]) {
+    assert!(input.len() == output.len());
+    // The padding of a partial last block would be authenticated along the ciphertext.
+    #[cfg(cryp_v2)]
+    assert!(dir == Direction::Decrypt || input.len() % BLOCK_SIZE == 0);
+    #[cfg(not(cryp_v2))]
+    let _ = dir;
+}
+
+/// Configure the processor with `key`, and enable it.
+fn start<T: Instance>(key: &[u8], algomode: u8, dir: Direction, iv: Option<&[u8; 16]>) {
+    assert!(matches!(key.len(), 16 | 24 | 32));
+
+    let r = T::regs();
+    r.cr().modify(|w| w.set_crypen(false));
+    r.cr().modify(|w| {
+        w.set_keysize((key.len() / 8 - 2) as u8);
+        w.set_datatype(DATATYPE_BYTES);
+    });
+
+    // The key is right-aligned in the key registers, most significant word first.
+    let first = 8 - key.len() / 4;
+    for (i, word) in key.chunks_exact(4).enumerate() {
+        let n = first + i;
+        let word = u32::from_be_bytes(word.try_into().unwrap());
+        if n % 2 == 0 {
+            r.key(n / 2).klr().write_value(word);
+        } else {
+            r.key(n / 2).krr().write_value(word);
+        }
+    }
+
+    // The decryption key schedule of ECB and CBC is prepared from the encryption key.
+    if dir == Direction::Decrypt && matches!(algomode, ALGOMODE_AES_ECB | ALGOMODE_AES_CBC) {
+        set_algomode(r, ALGOMODE_AES_KEY_PREPARATION);
+        r.cr().modify(|w| w.set_crypen(true));
+        while r.sr().read().busy() {}
+    }
+
+    set_algomode(r, algomode);
+    r.cr().modify(|w| w.set_algodir(dir == Direction::Decrypt));
+    if let Some(iv) = iv {
+        write_iv(r, iv);
+    }
+
+    r.cr().modify(|w| w.set_fflush(true));
+    r.cr().modify(|w| w.set_crypen(true));
+}
+
+/// Wait for the end of the operation, and disable the processor.
+fn stop<T: Instance>() {
+    let r = T::regs();
+    while r.sr().read().busy() {}
+    r.cr().modify(|w| w.set_crypen(false));
+}
+
+/// Process a block, padding `input` with zeros when shorter than [`BLOCK_SIZE`].
+fn process_block<T: Instance>(input: &[u8], output: &mut [u8]) {
+    let r = T::regs();
+    let mut block = [0; BLOCK_SIZE];
+    block[..input.len()].copy_from_slice(input);
+
+    // The padding of the last block of a GCM payload is left out of the authentication.
+    #[cfg(not(any(cryp_v1, cryp_v2)))]
+    if input.len() < BLOCK_SIZE && r.cr().read().gcm_ccmph() == GCM_PHASE_PAYLOAD {
+        r.cr().modify(|w| w.set_npblb((BLOCK_SIZE - input.len()) as u8));
+    }
+
+    write_block(r, &block);
+    for word in block.chunks_exact_mut(4) {
+        while !r.sr().read().ofne() {}
+        word.copy_from_slice(&r.dout().read().to_le_bytes());
+    }
+    output.copy_from_slice(&block[..output.len()]);
+}
+
+fn write_block(r: pac::cryp::Cryp, block: &[u8; BLOCK_SIZE]) {
+    for word in block.chunks_exact(4) {
+        while !r.sr().read().ifnf() {}
+        r.din().write_value(u32::from_le_bytes(word.try_into().unwrap()));
+    }
+}
+
+fn write_iv(r: pac::cryp::Cryp, iv: &[u8; 16]) {
+    let word = |i: usize| u32::from_be_bytes(iv[4 * i..4 * i + 4].try_into().unwrap());
+    r.init(0).ivlr().write_value(word(0));
+    r.init(0).ivrr().write_value(word(1));
+    r.init(1).ivlr().write_value(word(2));
+    r.init(1).ivrr().write_value(word(3));
+}
+
+fn set_algomode(r: pac::cryp::Cryp, algomode: u8) {
+    #[cfg(cryp_v1)]
+    r.cr().modify(|w| w.set_algomode(algomode));
+    #[cfg(not(cryp_v1))]
+    r.cr().modify(|w| {
+        w.set_algomode0(algomode & 0b111);
+        w.set_algomode3(algomode & 0b1000 != 0);
+    });
+}
+
+/// Run the GCM init and header phases, and start the payload phase.
+#[cfg(not(cryp_v1))]
+fn gcm_start<T: Instance>(key: &[u8], iv: &[u8; 12], dir: Direction, aad: &[u8]) {
+    let r = T::regs();
+
+    // Init phase, computing the hash subkey.
+    let mut counter = [0; 16];
+    counter[..12].copy_from_slice(iv);
+    counter[15] = 2;
+    r.cr().modify(|w| w.set_gcm_ccmph(GCM_PHASE_INIT));
+    start::<T>(key, ALGOMODE_AES_GCM, Direction::Encrypt, Some(&counter));
+    while r.cr().read().crypen() {}
+
+    if !aad.is_empty() {
+        r.cr().modify(|w| w.set_gcm_ccmph(GCM_PHASE_HEADER));
+        r.cr().modify(|w| w.set_crypen(true));
+        for chunk in aad.chunks(BLOCK_SIZE) {
+            let mut block = [0; BLOCK_SIZE];
+            block[..chunk.len()].copy_from_slice(chunk);
+            write_block(r, &block);
+        }
+        while r.sr().read().busy() {}
+        r.cr().modify(|w| w.set_crypen(false));
+    }
+
+    r.cr().modify(|w| {
+        w.set_gcm_ccmph(GCM_PHASE_PAYLOAD);
+        w.set_algodir(dir == Direction::Decrypt);
+    });
+    r.cr().modify(|w| w.set_crypen(true));
+}
+
+/// Run the GCM final phase, returning the authentication tag.
+#[cfg(not(cryp_v1))]
+fn gcm_finish<T: Instance>(aad_len: usize, payload_len: usize) -> [u8; 16] {
+    let r = T::regs();
+    while r.sr().read().busy() {}
+
+    r.cr().modify(|w| {
+        w.set_gcm_ccmph(GCM_PHASE_FINAL);
+        w.set_algodir(false);
+    });
+
+    // The lengths in bits, as 64-bit big-endian values. CRYP v2 applies the data swapping to them.
+    let aad_bits = aad_len as u64 * 8;
+    let payload_bits = payload_len as u64 * 8;
+    for word in [aad_bits >> 32, aad_bits, payload_bits >> 32, payload_bits] {
+        let word = word as u32;
+        #[cfg(cryp_v2)]
+        let word = word.swap_bytes();
+        while !r.sr().read().ifnf() {}
+        r.din().write_value(word);
+    }
+
+    let mut tag = [0; 16];
+    for word in tag.chunks_exact_mut(4) {
+        while !r.sr().read().ofne() {}
+        word.copy_from_slice(&r.dout().read().to_le_bytes());
+    }
+    r.cr().modify(|w| w.set_crypen(false));
+    tag
+}
+
+/// AES key, copied for the RustCrypto wrappers.
+#[cfg(any(feature = "cipher", all(feature = "aead", not(cryp_v1))))]
+struct Key {
+    bytes: [u8; 32],
+    len: usize,
+}
+
+#[cfg(any(feature = "cipher", all(feature = "aead", not(cryp_v1))))]
+impl Key {
+    fn new(key: &[u8]) -> Self {
+        assert!(matches!(key.len(), 16 | 24 | 32));
+        let mut bytes = [0; 32];
+        bytes[..key.len()].copy_from_slice(key);
+        Self { bytes, len: key.len() }
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+/// AES block cipher implementing the RustCrypto traits, returned by [`Cryp::block_cipher`].
+///
+/// The processor is configured again for each block, so this is slower than processing whole
+/// buffers with [`Cryp::blocking_process`].
+#[cfg(feature = "cipher")]
+pub struct AesCipher<'a, T: Instance> {
+    key: Key,
+    _cryp: PhantomData<&'a mut T>,
+}
+
+#[cfg(feature = "cipher")]
+mod block_cipher {
+    use cipher::consts::{U1, U16};
+    use cipher::inout::InOut;
+    use cipher::{
+        Block, BlockBackend, BlockCipher, BlockClosure, BlockDecrypt, BlockEncrypt, BlockSizeUser, ParBlocksSizeUser,
+    };
+
+    use super::{process_block, start, stop, AesCipher, Direction, Instance, ALGOMODE_AES_ECB};
+
+    impl<T: Instance> BlockSizeUser for AesCipher<'_, T> {
+        type BlockSize = U16;
+    }
+
+    impl<T: Instance> BlockCipher for AesCipher<'_, T> {}
+
+    impl<T: Instance> BlockEncrypt for AesCipher<'_, T> {
+        fn encrypt_with_backend(&self, f: impl BlockClosure<BlockSize = U16>) {
+            f.call(&mut Backend {
+                cipher: self,
+                dir: Direction::Encrypt,
+            })
+        }
+    }
+
+    impl<T: Instance> BlockDecrypt for AesCipher<'_, T> {
+        fn decrypt_with_backend(&self, f: impl BlockClosure<BlockSize = U16>) {
+            f.call(&mut Backend {
+                cipher: self,
+                dir: Direction::Decrypt,
+            })
+        }
+    }
+
+    struct Backend<'b, 'a, T: Instance> {
+        cipher: &'b AesCipher<'a, T>,
+        dir: Direction,
+    }
+
+    impl<T: Instance> BlockSizeUser for Backend<'_, '_, T> {
+        type BlockSize = U16;
+    }
+
+    impl<T: Instance> ParBlocksSizeUser for Backend<'_, '_, T> {
+        type ParBlocksSize = U1;
+    }
+
+    impl<T: Instance> BlockBackend for Backend<'_, '_, T> {
+        fn proc_block(&mut self, mut block: InOut<'_, '_, Block<Self>>) {
+            let mut output = [0; 16];
+            start::<T>(self.cipher.key.as_slice(), ALGOMODE_AES_ECB, self.dir, None);
+            process_block::<T>(block.get_in(), &mut output);
+            stop::<T>();
+            block.get_out().copy_from_slice(&output);
+        }
+    }
+}
+
+/// AES-GCM AEAD implementing the RustCrypto traits, returned by [`Cryp::aead`].
+#[cfg(all(feature = "aead", not(cryp_v1)))]
+pub struct AesGcm<'a, T: Instance> {
+    key: Key,
+    _cryp: PhantomData<&'a mut T>,
+}
+
+#[cfg(all(feature = "aead", not(cryp_v1)))]
+mod aead_impl {
+    use aead::consts::{U0, U12, U16};
+    use aead::{AeadCore, AeadInPlace, Nonce, Tag};
+
+    use super::{gcm_finish, gcm_start, process_block, AesGcm, Direction, Instance, BLOCK_SIZE};
+
+    impl<T: Instance> AeadCore for AesGcm<'_, T> {
+        type NonceSize = U12;
+        type TagSize = U16;
+        type CiphertextOverhead = U0;
+    }
+
+    impl<T: Instance> AeadInPlace for AesGcm<'_, T> {
+        fn encrypt_in_place_detached(
+            &self,
+            nonce: &Nonce<Self>,
+            associated_data: &[u8],
+            buffer: &mut [u8],
+        ) -> aead::Result<Tag<Self>> {
+            let tag = self.process(nonce, Direction::Encrypt, associated_data, buffer);
+            Ok(Tag::<Self>::clone_from_slice(&tag))
+        }
+
+        fn decrypt_in_place_detached(
+            &self,
+            nonce: &Nonce<Self>,
+            associated_data: &[u8],
+            buffer: &mut [u8],
+            tag: &Tag<Self>,
+        ) -> aead::Result<()> {
+            let computed = self.process(nonce, Direction::Decrypt, associated_data, buffer);
+            // Compared in constant time, and the unauthenticated plaintext is never released.
+            let diff = computed.iter().zip(tag.iter()).fold(0, |diff, (a, b)| diff | (a ^ b));
+            if diff != 0 {
+                buffer.fill(0);
+                return Err(aead::Error);
+            }
+            Ok(())
+        }
+    }
+
+    impl<T: Instance> AesGcm<'_, T> {
+        fn process(&self, nonce: &Nonce<Self>, dir: Direction, aad: &[u8], buffer: &mut [u8]) -> [u8; 16] {
+            #[cfg(cryp_v2)]
+            assert!(dir == Direction::Decrypt || buffer.len() % BLOCK_SIZE == 0);
+
+            gcm_start::<T>(self.key.as_slice(), nonce.as_slice().try_into().unwrap(), dir, aad);
+            for chunk in buffer.chunks_mut(BLOCK_SIZE) {
+                let mut input = [0; BLOCK_SIZE];
+                input[..chunk.len()].copy_from_slice(chunk);
+                process_block::<T>(&input[..chunk.len()], chunk);
+            }
+            gcm_finish::<T>(aad.len(), buffer.len())
+        }
+    }
+}
+
+pub(crate) mod sealed {
+    use super::*;
+
+    pub trait Instance {
+        fn regs() -> pac::cryp::Cryp;
+    }
+}
+
+/// CRYP instance trait.
+pub trait Instance: sealed::Instance + Peripheral<P = Self> + crate::rcc::RccPeripheral + 'static + Send {}
+
+foreach_peripheral!(
+    (cryp, $inst:ident) => {
+        impl Instance for peripherals::$inst {}
+
+        impl sealed::Instance for peripherals::$inst {
+            fn regs() -> crate::pac::cryp::Cryp {
+                crate::pac::$inst
+            }
+        }
+    };
+);
+
+dma_trait!(DmaIn, Instance);
+dma_trait!(DmaOut, Instance);
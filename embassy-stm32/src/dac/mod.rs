@@ -6,12 +6,13 @@ use core::marker::PhantomData;
 use embassy_hal_internal::{into_ref, PeripheralRef};
 
 use crate::dma::NoDma;
-#[cfg(any(dac_v3, dac_v4, dac_v5, dac_v6, dac_v7))]
 use crate::pac::dac;
 use crate::rcc::RccPeripheral;
 use crate::{peripherals, Peripheral};
 
+mod siggen;
 mod tsel;
+pub use siggen::{fill_table, Shape, SignalGenerator};
 pub use tsel::TriggerSel;
 
 /// Operating mode for DAC channel
@@ -96,6 +97,143 @@ pub enum ValueArray<'a> {
     Bit12Right(&'a [u16]),
 }
 
+impl<'a> ValueArray<'a> {
+    #[cfg(not(gpdma))]
+    fn len(&self) -> usize {
+        match self {
+            ValueArray::Bit8(buf) => buf.len(),
+            ValueArray::Bit12Left(buf) => buf.len(),
+            ValueArray::Bit12Right(buf) => buf.len(),
+        }
+    }
+}
+
+impl<'a> DualValueArray<'a> {
+    #[cfg(not(gpdma))]
+    fn len(&self) -> usize {
+        match self {
+            DualValueArray::Bit8(buf) => buf.len(),
+            DualValueArray::Bit12Left(buf) => buf.len(),
+            DualValueArray::Bit12Right(buf) => buf.len(),
+        }
+    }
+}
+
+impl DualValue {
+    /// Pack the values as written to the dual-channel data holding register, for
+    /// [`DualValueArray`] buffers.
+    ///
+    /// Channel 1 is in the low half of the word and channel 2 in the high half. The 8-bit
+    /// values use the low 16 bits only.
+    pub const fn to_bits(self) -> u32 {
+        match self {
+            DualValue::Bit8(v1, v2) => v1 as u32 | ((v2 as u32) << 8),
+            DualValue::Bit12Left(v1, v2) => (v1 & 0xFFF0) as u32 | ((v2 & 0xFFF0) as u32) << 16,
+            DualValue::Bit12Right(v1, v2) => (v1 & 0xFFF) as u32 | ((v2 & 0xFFF) as u32) << 16,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// Array variant of [`DualValue`], with the values packed by [`DualValue::to_bits`].
+pub enum DualValueArray<'a> {
+    /// 8 bit values
+    Bit8(&'a [u16]),
+    /// 12 bit values, left-aligned
+    Bit12Left(&'a [u32]),
+    /// 12 bit values, right-aligned
+    Bit12Right(&'a [u32]),
+}
+
+/// Waveform generated by a DAC channel.
+///
+/// The generator updates the output on each trigger, so triggering must be enabled. The
+/// generated wave is added to the value of the data holding register.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Waveform {
+    /// No waveform, the channel outputs the data holding register.
+    None,
+    /// Pseudo-random noise, keeping the given number of low bits of the LFSR, from 1 to 12.
+    Noise(u8),
+    /// Triangle wave with an amplitude of `2^n - 1`, `n` from 1 to 12.
+    Triangle(u8),
+}
+
+/// DAC error.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The DMA caught up with the samples written to the ring buffer, and output stale samples.
+    Underrun,
+}
+
+/// Maximum number of samples of a single DMA transfer.
+#[cfg(not(gpdma))]
+const MAX_TRANSFER_LEN: usize = 0xFFFF;
+
+/// Number of samples copied to the ring buffer at once by [`write_ring`].
+#[cfg(not(gpdma))]
+const RING_CHUNK_LEN: usize = 32;
+
+/// Output `data` with a circular DMA transfer from `ring`, refilled from `data` as the DMA reads
+/// it, so the samples are output back to back whatever the length of `data`.
+///
+/// `ring` is first filled with the start of `data`. Without `circular`, `data` is followed by a
+/// whole ring of its last sample, so the DMA is stopped after `data` was output, and the output
+/// holds the last sample, as after a single transfer.
+#[cfg(not(gpdma))]
+async fn write_ring<C: crate::dma::Channel, W: crate::dma::word::Word, S: Copy + Into<W>>(
+    channel: &mut PeripheralRef<'_, C>,
+    request: crate::dma::Request,
+    peri_addr: *mut W,
+    data: &[S],
+    ring: &mut [W],
+    circular: bool,
+) -> Result<(), Error> {
+    assert!(!data.is_empty() && ring.len() >= 2);
+
+    let last: W = data[data.len() - 1].into();
+    let repeats = if circular { usize::MAX } else { 1 };
+    let mut samples = core::iter::repeat(data)
+        .take(repeats)
+        .flatten()
+        .map(|&s| s.into())
+        .chain(core::iter::repeat(last).take(if circular { 0 } else { ring.len() }));
+
+    for slot in ring.iter_mut() {
+        *slot = unwrap!(samples.next());
+    }
+
+    let options = crate::dma::TransferOptions {
+        half_transfer_ir: true,
+        complete_transfer_ir: true,
+        ..Default::default()
+    };
+    let mut ring = unsafe { crate::dma::WritableRingBuffer::new(channel, request, peri_addr, ring, options) };
+    ring.start();
+
+    let mut chunk = [last; RING_CHUNK_LEN];
+    let res = loop {
+        let mut len = 0;
+        for (slot, sample) in chunk.iter_mut().zip(&mut samples) {
+            *slot = sample;
+            len += 1;
+        }
+        if len == 0 {
+            break Ok(());
+        }
+        if ring.write_exact(&chunk[..len]).await.is_err() {
+            break Err(Error::Underrun);
+        }
+    };
+
+    ring.request_stop();
+    while ring.is_running() {}
+    res
+}
+
 /// Driver for a single DAC channel.
 ///
 /// If you want to use both channels, either together or independently,
@@ -231,6 +369,26 @@ impl<'d, T: Instance, const N: u8, DMA> DacChannel<'d, T, N, DMA> {
         });
     }
 
+    /// Set the waveform generated by this channel.
+    ///
+    /// This method disables the channel, so you may need to re-enable afterwards.
+    pub fn set_waveform(&mut self, waveform: Waveform) {
+        let (wave, bits) = match waveform {
+            Waveform::None => (dac::vals::Wave::DISABLED, 1),
+            Waveform::Noise(bits) => (dac::vals::Wave::NOISE, bits),
+            Waveform::Triangle(bits) => (dac::vals::Wave::TRIANGLE, bits),
+        };
+        assert!((1..=12).contains(&bits));
+
+        critical_section::with(|_| {
+            T::regs().cr().modify(|reg| {
+                reg.set_en(Self::IDX, false);
+                reg.set_wave(Self::IDX, wave);
+                reg.set_mamp(Self::IDX, bits - 1);
+            });
+        });
+    }
+
     /// Write a new value to this channel.
     ///
     /// If triggering is not enabled, the new value is immediately output; otherwise,
@@ -291,14 +449,99 @@ macro_rules! impl_dma_methods {
             /// flag can be set. This configures a circular DMA transfer that continually outputs
             /// `data`. Note that for performance reasons in circular mode the transfer-complete
             /// interrupt is disabled.
+            ///
+            /// The samples are output at the rate of the trigger, usually a timer update event
+            /// selected with [`set_trigger`](Self::set_trigger). A single DMA transfer holds at
+            /// most 65535 samples, use [`write_ring_buffered`](Self::write_ring_buffered) for
+            /// longer buffers.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `data` holds more than 65535 samples.
             #[cfg(not(gpdma))]
             pub async fn write(&mut self, data: ValueArray<'_>, circular: bool) {
+                assert!(data.len() <= MAX_TRANSFER_LEN);
+
                 // Enable DAC and DMA
                 T::regs().cr().modify(|w| {
                     w.set_en(Self::IDX, true);
                     w.set_dmaen(Self::IDX, true);
                 });
 
+                self.write_transfer(data, circular).await;
+
+                T::regs().cr().modify(|w| {
+                    w.set_en(Self::IDX, false);
+                    w.set_dmaen(Self::IDX, false);
+                });
+            }
+
+            /// Write `data`, of any length, to this channel via a circular DMA transfer from `ring`.
+            ///
+            /// The DMA outputs `ring` continuously, and its half and complete transfer interrupts
+            /// wake this future to refill the part it has just output with the next samples of
+            /// `data`. Unlike a sequence of DMA transfers, there is no gap between the samples,
+            /// whose rate is set by the trigger as in [`write`](Self::write). `ring` must be long
+            /// enough for the samples to be copied before the DMA reaches them, otherwise
+            /// [`Error::Underrun`] is returned. In circular mode, `data` repeats until the future
+            /// is dropped.
+            ///
+            /// The 8-bit samples are written to the data holding register as 16-bit words.
+            #[cfg(not(gpdma))]
+            pub async fn write_ring_buffered(
+                &mut self,
+                data: ValueArray<'_>,
+                ring: &mut [u16],
+                circular: bool,
+            ) -> Result<(), Error> {
+                T::regs().cr().modify(|w| {
+                    w.set_en(Self::IDX, true);
+                    w.set_dmaen(Self::IDX, true);
+                });
+                let _disable = embassy_hal_internal::drop::OnDrop::new(|| {
+                    T::regs().cr().modify(|w| {
+                        w.set_en(Self::IDX, false);
+                        w.set_dmaen(Self::IDX, false);
+                    });
+                });
+
+                let request = self.dma.request();
+                let regs = T::regs();
+                match data {
+                    ValueArray::Bit8(buf) => {
+                        let dhr = regs.dhr8r(Self::IDX).as_ptr() as *mut u16;
+                        write_ring(&mut self.dma, request, dhr, buf, ring, circular).await
+                    }
+                    ValueArray::Bit12Left(buf) => {
+                        let dhr = regs.dhr12l(Self::IDX).as_ptr() as *mut u16;
+                        write_ring(&mut self.dma, request, dhr, buf, ring, circular).await
+                    }
+                    ValueArray::Bit12Right(buf) => {
+                        let dhr = regs.dhr12r(Self::IDX).as_ptr() as *mut u16;
+                        write_ring(&mut self.dma, request, dhr, buf, ring, circular).await
+                    }
+                }
+            }
+
+            /// Output the signal of `generator`, until the future is dropped.
+            ///
+            /// This sets the trigger of the channel to the timer of the generator, and starts it.
+            #[cfg(not(gpdma))]
+            pub async fn generate<TIM: crate::timer::Basic16bitInstance>(
+                &mut self,
+                generator: &SignalGenerator<'_, TIM>,
+            ) {
+                self.set_trigger(generator.trigger());
+                self.set_triggering(true);
+
+                generator.start();
+                let _stop = embassy_hal_internal::drop::OnDrop::new(|| generator.stop());
+
+                self.write(ValueArray::Bit12Right(generator.table()), true).await;
+            }
+
+            #[cfg(not(gpdma))]
+            async fn write_transfer(&mut self, data: ValueArray<'_>, circular: bool) {
                 let tx_request = self.dma.request();
                 let dma_channel = &mut self.dma;
 
@@ -341,11 +584,6 @@ macro_rules! impl_dma_methods {
                 };
 
                 tx_f.await;
-
-                T::regs().cr().modify(|w| {
-                    w.set_en(Self::IDX, false);
-                    w.set_dmaen(Self::IDX, false);
-                });
             }
         }
     };
@@ -488,6 +726,118 @@ impl<'d, T: Instance, DMACh1, DMACh2> Dac<'d, T, DMACh1, DMACh2> {
     }
 }
 
+impl<'d, T: Instance, DMACh1: DacDma1<T>, DMACh2> Dac<'d, T, DMACh1, DMACh2> {
+    /// Write `data` to both channels simultaneously via the DMA of channel 1.
+    ///
+    /// Both channels should use the same trigger, so they are updated together. The `circular`
+    /// flag and the length limit behave as in [`DacChannel::write`].
+    #[cfg(not(gpdma))]
+    pub async fn write(&mut self, data: DualValueArray<'_>, circular: bool) {
+        assert!(data.len() <= MAX_TRANSFER_LEN);
+
+        T::regs().cr().modify(|w| {
+            w.set_en(0, true);
+            w.set_en(1, true);
+            w.set_dmaen(0, true);
+        });
+
+        self.write_transfer(data, circular).await;
+
+        T::regs().cr().modify(|w| {
+            w.set_en(0, false);
+            w.set_en(1, false);
+            w.set_dmaen(0, false);
+        });
+    }
+
+    /// Write `data`, of any length, to both channels via a circular DMA transfer from `ring`.
+    ///
+    /// This works as [`DacChannel::write_ring_buffered`], with the DMA of channel 1. The 8-bit
+    /// samples are written to the data holding register as 32-bit words.
+    #[cfg(not(gpdma))]
+    pub async fn write_ring_buffered(
+        &mut self,
+        data: DualValueArray<'_>,
+        ring: &mut [u32],
+        circular: bool,
+    ) -> Result<(), Error> {
+        T::regs().cr().modify(|w| {
+            w.set_en(0, true);
+            w.set_en(1, true);
+            w.set_dmaen(0, true);
+        });
+        let _disable = embassy_hal_internal::drop::OnDrop::new(|| {
+            T::regs().cr().modify(|w| {
+                w.set_en(0, false);
+                w.set_en(1, false);
+                w.set_dmaen(0, false);
+            });
+        });
+
+        let request = self.ch1.dma.request();
+        let regs = T::regs();
+        match data {
+            DualValueArray::Bit8(buf) => {
+                let dhr = regs.dhr8rd().as_ptr() as *mut u32;
+                write_ring(&mut self.ch1.dma, request, dhr, buf, ring, circular).await
+            }
+            DualValueArray::Bit12Left(buf) => {
+                let dhr = regs.dhr12ld().as_ptr() as *mut u32;
+                write_ring(&mut self.ch1.dma, request, dhr, buf, ring, circular).await
+            }
+            DualValueArray::Bit12Right(buf) => {
+                let dhr = regs.dhr12rd().as_ptr() as *mut u32;
+                write_ring(&mut self.ch1.dma, request, dhr, buf, ring, circular).await
+            }
+        }
+    }
+
+    #[cfg(not(gpdma))]
+    async fn write_transfer(&mut self, data: DualValueArray<'_>, circular: bool) {
+        let tx_request = self.ch1.dma.request();
+        let dma_channel = &mut self.ch1.dma;
+
+        let tx_options = crate::dma::TransferOptions {
+            circular,
+            half_transfer_ir: false,
+            complete_transfer_ir: !circular,
+            ..Default::default()
+        };
+
+        let tx_f = match data {
+            DualValueArray::Bit8(buf) => unsafe {
+                crate::dma::Transfer::new_write(
+                    dma_channel,
+                    tx_request,
+                    buf,
+                    T::regs().dhr8rd().as_ptr() as *mut u16,
+                    tx_options,
+                )
+            },
+            DualValueArray::Bit12Left(buf) => unsafe {
+                crate::dma::Transfer::new_write(
+                    dma_channel,
+                    tx_request,
+                    buf,
+                    T::regs().dhr12ld().as_ptr() as *mut u32,
+                    tx_options,
+                )
+            },
+            DualValueArray::Bit12Right(buf) => unsafe {
+                crate::dma::Transfer::new_write(
+                    dma_channel,
+                    tx_request,
+                    buf,
+                    T::regs().dhr12rd().as_ptr() as *mut u32,
+                    tx_options,
+                )
+            },
+        };
+
+        tx_f.await;
+    }
+}
+
 pub(crate) mod sealed {
     pub trait Instance {
         fn regs() -> &'static crate::pac::dac::Dac;
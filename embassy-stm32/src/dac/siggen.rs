@@ -0,0 +1,164 @@
+//! Signal generator, outputting a waveform table with a DAC channel, a timer and a DMA channel.
+
+use embassy_hal_internal::{into_ref, PeripheralRef};
+
+use super::TriggerSel;
+use crate::time::Hertz;
+use crate::timer::sealed::Basic16bitInstance as _;
+use crate::timer::{Basic16bitInstance, MasterMode};
+use crate::Peripheral;
+
+/// Maximum value of a 12-bit sample.
+const MAX_SAMPLE: u16 = 4095;
+
+/// Standard waveform shapes.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Shape {
+    /// Sine wave.
+    Sine,
+    /// Triangle wave, rising during the first half of the period.
+    Triangle,
+    /// Sawtooth wave, rising during the whole period.
+    Sawtooth,
+    /// Square wave, high during the first half of the period.
+    Square,
+}
+
+/// Fill `table` with one period of `shape`, as 12-bit right-aligned samples.
+///
+/// The samples swing from `offset - amplitude` to `offset + amplitude`, clamped to the 12-bit range.
+pub fn fill_table(table: &mut [u16], shape: Shape, amplitude: u16, offset: u16) {
+    let len = table.len() as f32;
+    for (i, sample) in table.iter_mut().enumerate() {
+        let phase = i as f32 / len;
+        // Normalized sample, from -1 to 1.
+        let x = match shape {
+            Shape::Sine => sin_2pi(phase),
+            Shape::Triangle if phase < 0.5 => 4.0 * phase - 1.0,
+            Shape::Triangle => 3.0 - 4.0 * phase,
+            Shape::Sawtooth => 2.0 * phase - 1.0,
+            Shape::Square if phase < 0.5 => 1.0,
+            Shape::Square => -1.0,
+        };
+        let value = offset as f32 + amplitude as f32 * x;
+        *sample = (value + 0.5).clamp(0.0, MAX_SAMPLE as f32) as u16;
+    }
+}
+
+/// `sin(2 * pi * phase)`, for `phase` from 0 to 1, precise enough for 12-bit samples.
+fn sin_2pi(phase: f32) -> f32 {
+    use core::f32::consts::PI;
+
+    // Reduce to -pi/2..=pi/2, where the series converges quickly.
+    let x = 2.0 * PI * phase;
+    let x = if x > 1.5 * PI {
+        x - 2.0 * PI
+    } else if x > 0.5 * PI {
+        PI - x
+    } else {
+        x
+    };
+
+    let x2 = x * x;
+    x * (1.0 - x2 / 6.0 * (1.0 - x2 / 20.0 * (1.0 - x2 / 42.0)))
+}
+
+/// Signal generator.
+///
+/// The generator owns the timer triggering the DAC, which outputs one sample of the table per
+/// update event, so the signal frequency is the update rate divided by the length of the table.
+/// The table is output by [`DacChannel::generate`](super::DacChannel::generate), with a circular DMA
+/// transfer.
+///
+/// The frequency can be changed while the signal is generated: the new prescaler and auto-reload
+/// values are preloaded, and only take effect at the end of the current sample, without glitches.
+pub struct SignalGenerator<'d, TIM: Basic16bitInstance> {
+    _tim: PeripheralRef<'d, TIM>,
+    trigger: TriggerSel,
+    table: &'d [u16],
+}
+
+impl<'d, TIM: Basic16bitInstance> SignalGenerator<'d, TIM> {
+    /// Create a new signal generator, outputting `table` at `frequency`.
+    ///
+    /// `trigger` must be the DAC trigger of `tim`, for example `TriggerSel::Tim6` for TIM6. The
+    /// table holds one period of the signal, as 12-bit right-aligned samples, see [`fill_table`].
+    pub fn new(tim: impl Peripheral<P = TIM> + 'd, trigger: TriggerSel, table: &'d [u16], frequency: Hertz) -> Self {
+        into_ref!(tim);
+        assert!(!table.is_empty());
+
+        TIM::enable_and_reset();
+        tim.set_master_mode(MasterMode::Update);
+        tim.set_autoreload_preload(true);
+
+        let this = Self {
+            _tim: tim,
+            trigger,
+            table,
+        };
+        this.set_frequency(frequency);
+
+        // Load the preloaded values now.
+        let regs = TIM::regs();
+        regs.cr1()
+            .modify(|w| w.set_urs(crate::pac::timer::vals::Urs::COUNTERONLY));
+        regs.egr().write(|w| w.set_ug(true));
+        regs.cr1().modify(|w| w.set_urs(crate::pac::timer::vals::Urs::ANYEVENT));
+
+        this
+    }
+
+    /// Set the frequency of the signal.
+    ///
+    /// The frequency is rounded down to a multiple of the timer clock divided by the length of the
+    /// table.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sample rate is higher than half of the timer clock.
+    pub fn set_frequency(&self, frequency: Hertz) {
+        let sample_rate = frequency.0 as u64 * self.table.len() as u64;
+        assert!(sample_rate > 0);
+
+        let ticks = TIM::frequency().0 as u64 / sample_rate;
+        let psc = (ticks.saturating_sub(1) / (1 << 16)) as u16;
+        let arr = ticks / (psc as u64 + 1);
+        assert!(arr >= 2, "signal frequency too high");
+
+        let regs = TIM::regs();
+        regs.psc().write(|w| w.set_psc(psc));
+        regs.arr().write(|w| w.set_arr((arr - 1) as u16));
+    }
+
+    /// Get the frequency of the signal.
+    pub fn frequency(&self) -> Hertz {
+        let regs = TIM::regs();
+        let ticks = (regs.psc().read().psc() as u32 + 1) * (regs.arr().read().arr() as u32 + 1);
+        Hertz(TIM::frequency().0 / ticks / self.table.len() as u32)
+    }
+
+    /// The DAC trigger of the timer.
+    pub fn trigger(&self) -> TriggerSel {
+        self.trigger
+    }
+
+    /// The waveform table.
+    pub fn table(&self) -> &'d [u16] {
+        self.table
+    }
+
+    pub(super) fn start(&self) {
+        TIM::regs().cr1().modify(|w| w.set_cen(true));
+    }
+
+    pub(super) fn stop(&self) {
+        TIM::regs().cr1().modify(|w| w.set_cen(false));
+    }
+}
+
+impl<'d, TIM: Basic16bitInstance> Drop for SignalGenerator<'d, TIM> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,33 @@
+//! Register dumps, to attach the state of a driver to a bug report.
+//!
+//! The drivers supporting it have a `debug_dump()` method, which snapshots their registers into a
+//! caller-provided buffer. Print the dump when an async operation hangs, for example after a
+//! timeout:
+//!
+//! ```rust,ignore
+//! if with_timeout(Duration::from_secs(1), uart.read(&mut buf)).await.is_err() {
+//!     let mut regs = [Register::default(); 8];
+//!     for reg in uart.debug_dump(&mut regs) {
+//!         info!("{}: {:08x}", reg.name, reg.value);
+//!     }
+//! }
+//! ```
+
+/// Register value in a dump.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Register {
+    /// Register name, as in the reference manual.
+    pub name: &'static str,
+    /// Raw register value.
+    pub value: u32,
+}
+
+/// Write `registers` to `buf`, truncating the dump if `buf` is too short.
+pub(crate) fn dump<'a>(buf: &'a mut [Register], registers: &[(&'static str, u32)]) -> &'a [Register] {
+    let len = registers.len().min(buf.len());
+    for (reg, &(name, value)) in buf.iter_mut().zip(registers) {
+        *reg = Register { name, value };
+    }
+    &buf[..len]
+}
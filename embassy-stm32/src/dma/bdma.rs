@@ -12,6 +12,8 @@ use super::ringbuffer::{DmaCtrl, OverrunError, ReadableDmaRingBuffer, WritableDm
 use super::word::{Word, WordSize};
 use super::Dir;
 use crate::_generated::BDMA_CHANNEL_COUNT;
+#[cfg(feature = "debug-dump")]
+use crate::debug_dump::Register;
 use crate::interrupt::typelevel::Interrupt;
 use crate::interrupt::Priority;
 use crate::pac;
@@ -59,6 +61,8 @@ impl From<Dir> for vals::Dir {
         match raw {
             Dir::MemoryToPeripheral => Self::FROMMEMORY,
             Dir::PeripheralToMemory => Self::FROMPERIPHERAL,
+            // Reads from the memory address, and writes to the "peripheral" address.
+            Dir::MemoryToMemory => Self::FROMMEMORY,
         }
     }
 }
@@ -275,6 +279,34 @@ impl<'a, C: Channel> Transfer<'a, C> {
         )
     }
 
+    /// Create a new memory-to-memory DMA transfer, writing `buf` to the fixed address `dest_addr`.
+    ///
+    /// The transfer is not paced by a peripheral request, and runs as fast as the bus allows.
+    /// This is meant for peripherals without DMA request, like the CRC unit.
+    pub unsafe fn new_write_memory_to_memory<W: Word>(
+        channel: impl Peripheral<P = C> + 'a,
+        buf: &'a [W],
+        dest_addr: *mut W,
+        options: TransferOptions,
+    ) -> Self {
+        into_ref!(channel);
+
+        let (ptr, len) = super::slice_ptr_parts(buf);
+        assert!(len > 0 && len <= 0xFFFF);
+
+        Self::new_inner(
+            channel,
+            Request::default(),
+            Dir::MemoryToMemory,
+            dest_addr as *const u32,
+            ptr as *mut u32,
+            len,
+            true,
+            W::size(),
+            options,
+        )
+    }
+
     unsafe fn new_inner(
         channel: PeripheralRef<'a, C>,
         _request: Request,
@@ -309,6 +341,7 @@ impl<'a, C: Channel> Transfer<'a, C> {
             w.set_msize(data_size.into());
             w.set_minc(incr_mem);
             w.set_dir(dir.into());
+            w.set_mem2mem(dir == Dir::MemoryToMemory);
             w.set_teie(true);
             w.set_tcie(options.complete_transfer_ir);
             w.set_htie(options.half_transfer_ir);
@@ -363,6 +396,31 @@ impl<'a, C: Channel> Transfer<'a, C> {
         ch.ndtr().read().ndt()
     }
 
+    /// Snapshot the registers of the channel into `buf`, for bug reports.
+    #[cfg(feature = "debug-dump")]
+    pub fn debug_dump<'b>(&self, buf: &'b mut [Register]) -> &'b [Register] {
+        let ch = self.channel.regs().ch(self.channel.num());
+        crate::debug_dump::dump(
+            buf,
+            &[
+                ("ISR", self.channel.regs().isr().read().0),
+                ("CR", ch.cr().read().0),
+                ("NDTR", ch.ndtr().read().0),
+                ("PAR", ch.par().read()),
+                ("MAR", ch.mar().read()),
+            ],
+        )
+    }
+
+    /// Wait until the transfer finishes, or stop it after `timeout`.
+    ///
+    /// On timeout, the channel is stopped before returning, and
+    /// [`get_remaining_transfers`](Self::get_remaining_transfers) gives the number of transfers not done.
+    #[cfg(feature = "time")]
+    pub async fn wait_timeout(&mut self, timeout: embassy_time::Duration) -> Result<(), super::Error> {
+        super::wait_timeout(self, Some(timeout)).await
+    }
+
     /// Blocking wait until the transfer finishes.
     pub fn blocking_wait(mut self) {
         while self.is_running() {}
@@ -375,6 +433,20 @@ impl<'a, C: Channel> Transfer<'a, C> {
     }
 }
 
+impl<'a, C: Channel> super::TransferControl for Transfer<'a, C> {
+    fn request_stop(&mut self) {
+        Transfer::request_stop(self)
+    }
+
+    fn is_running(&mut self) -> bool {
+        Transfer::is_running(self)
+    }
+
+    fn get_remaining_transfers(&self) -> u16 {
+        Transfer::get_remaining_transfers(self)
+    }
+}
+
 impl<'a, C: Channel> Drop for Transfer<'a, C> {
     fn drop(&mut self) {
         self.request_stop();
@@ -495,9 +567,12 @@ impl<'a, C: Channel, W: Word> ReadableRingBuffer<'a, C, W> {
 
     /// Start the ring buffer operation.
     ///
-    /// You must call this after creating it for it to work.
+    /// You must call this after creating it for it to work. After [`request_stop`](Self::request_stop),
+    /// the DMA restarts at the beginning of the buffer, so call [`clear`](Self::clear) first.
     pub fn start(&mut self) {
         let ch = self.channel.regs().ch(self.channel.num());
+        // NDTR keeps its value when the channel is stopped, and can't be written while it runs.
+        ch.ndtr().write(|w| w.set_ndt(self.ringbuf.cap() as u16));
         ch.cr().write_value(self.cr)
     }
 
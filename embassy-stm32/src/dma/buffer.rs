@@ -0,0 +1,148 @@
+//! Buffers owned by a DMA transfer.
+//!
+//! The drivers stop the DMA when the future of a transfer is dropped, so the borrowed buffers of
+//! their `read` and `write` methods can't be reused while the DMA still accesses them. A future
+//! leaked with [`core::mem::forget`] is never dropped though, and the DMA then keeps accessing
+//! a buffer which may be reused, for example a stack buffer after the function returned. These
+//! methods are deprecated for this reason.
+//!
+//! The `*_buffer` methods of the drivers instead take the ownership of a [`DmaReadBuffer`] or
+//! [`DmaWriteBuffer`] for the whole transfer, and give it back at the end. A leaked future leaks
+//! the buffer with it, so its memory is never reused. The buffers are implemented for `'static`
+//! references, to static buffers or buffers from a `StaticCell`, and [`Prefix`] transfers only the
+//! start of a buffer:
+//!
+//! ```rust,ignore
+//! static BUF: StaticCell<[u8; 64]> = StaticCell::new();
+//!
+//! let mut buf = BUF.init([0; 64]);
+//! loop {
+//!     let (result, b) = uart.read_buffer(buf).await;
+//!     buf = b;
+//!     result?;
+//!
+//!     // Echo the first 8 bytes.
+//!     let (result, b) = uart.write_buffer(Prefix::new(buf, 8)).await;
+//!     buf = b.into_inner();
+//!     result?;
+//! }
+//! ```
+//!
+//! The trait implementations of the drivers, such as `embedded_io_async::Write`, take borrowed
+//! buffers and can't give this guarantee.
+
+use super::word::Word;
+
+/// Buffer written by the DMA, in a read transfer (peripheral to memory).
+///
+/// # Safety
+///
+/// The pointer and length returned by [`dma_read_buffer`](Self::dma_read_buffer) must stay valid
+/// for writes as long as the buffer is not dropped, even when the buffer is moved, and the memory
+/// must not be accessed other than through the buffer.
+pub unsafe trait DmaReadBuffer {
+    /// Word of the buffer.
+    type Word: Word;
+
+    /// Get the pointer to the first word of the buffer, and its length in words.
+    fn dma_read_buffer(&mut self) -> (*mut Self::Word, usize);
+}
+
+/// Buffer read by the DMA, in a write transfer (memory to peripheral).
+///
+/// # Safety
+///
+/// The pointer and length returned by [`dma_write_buffer`](Self::dma_write_buffer) must stay
+/// valid for reads as long as the buffer is not dropped, even when the buffer is moved, and the
+/// memory must not be written meanwhile.
+pub unsafe trait DmaWriteBuffer {
+    /// Word of the buffer.
+    type Word: Word;
+
+    /// Get the pointer to the first word of the buffer, and its length in words.
+    fn dma_write_buffer(&self) -> (*const Self::Word, usize);
+}
+
+unsafe impl<W: Word> DmaReadBuffer for &'static mut [W] {
+    type Word = W;
+
+    fn dma_read_buffer(&mut self) -> (*mut W, usize) {
+        (self.as_mut_ptr(), self.len())
+    }
+}
+
+unsafe impl<W: Word, const N: usize> DmaReadBuffer for &'static mut [W; N] {
+    type Word = W;
+
+    fn dma_read_buffer(&mut self) -> (*mut W, usize) {
+        (self.as_mut_ptr(), N)
+    }
+}
+
+unsafe impl<W: Word> DmaWriteBuffer for &'static [W] {
+    type Word = W;
+
+    fn dma_write_buffer(&self) -> (*const W, usize) {
+        (self.as_ptr(), self.len())
+    }
+}
+
+unsafe impl<W: Word, const N: usize> DmaWriteBuffer for &'static [W; N] {
+    type Word = W;
+
+    fn dma_write_buffer(&self) -> (*const W, usize) {
+        (self.as_ptr(), N)
+    }
+}
+
+unsafe impl<W: Word> DmaWriteBuffer for &'static mut [W] {
+    type Word = W;
+
+    fn dma_write_buffer(&self) -> (*const W, usize) {
+        (self.as_ptr(), self.len())
+    }
+}
+
+unsafe impl<W: Word, const N: usize> DmaWriteBuffer for &'static mut [W; N] {
+    type Word = W;
+
+    fn dma_write_buffer(&self) -> (*const W, usize) {
+        (self.as_ptr(), N)
+    }
+}
+
+/// The first words of a buffer, for transfers shorter than the buffer.
+pub struct Prefix<B> {
+    buffer: B,
+    len: usize,
+}
+
+impl<B> Prefix<B> {
+    /// Select the first `len` words of `buffer`, or the whole buffer if it is shorter.
+    pub fn new(buffer: B, len: usize) -> Self {
+        Self { buffer, len }
+    }
+
+    /// Give the whole buffer back.
+    pub fn into_inner(self) -> B {
+        self.buffer
+    }
+}
+
+unsafe impl<B: DmaReadBuffer> DmaReadBuffer for Prefix<B> {
+    type Word = B::Word;
+
+    fn dma_read_buffer(&mut self) -> (*mut B::Word, usize) {
+        let (ptr, len) = self.buffer.dma_read_buffer();
+        (ptr, len.min(self.len))
+    }
+}
+
+unsafe impl<B: DmaWriteBuffer> DmaWriteBuffer for Prefix<B> {
+    type Word = B::Word;
+
+    fn dma_write_buffer(&self) -> (*const B::Word, usize) {
+        let (ptr, len) = self.buffer.dma_write_buffer();
+        (ptr, len.min(self.len))
+    }
+}
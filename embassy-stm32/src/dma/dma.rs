@@ -11,6 +11,8 @@ use super::ringbuffer::{DmaCtrl, OverrunError, ReadableDmaRingBuffer, WritableDm
 use super::word::{Word, WordSize};
 use super::Dir;
 use crate::_generated::DMA_CHANNEL_COUNT;
+#[cfg(feature = "debug-dump")]
+use crate::debug_dump::Register;
 use crate::interrupt::typelevel::Interrupt;
 use crate::interrupt::Priority;
 use crate::pac::dma::{regs, vals};
@@ -70,6 +72,7 @@ impl From<Dir> for vals::Dir {
         match raw {
             Dir::MemoryToPeripheral => Self::MEMORYTOPERIPHERAL,
             Dir::PeripheralToMemory => Self::PERIPHERALTOMEMORY,
+            Dir::MemoryToMemory => Self::MEMORYTOMEMORY,
         }
     }
 }
@@ -346,6 +349,42 @@ impl<'a, C: Channel> Transfer<'a, C> {
         )
     }
 
+    /// Create a new memory-to-memory DMA transfer, writing `buf` to the fixed address `dest_addr`.
+    ///
+    /// The transfer is not paced by a peripheral request, and runs as fast as the bus allows.
+    /// This is meant for peripherals without DMA request, like the CRC unit.
+    ///
+    /// Only the DMA2 controller supports memory-to-memory transfers on the STM32F2/F4/F7.
+    pub unsafe fn new_write_memory_to_memory<W: Word>(
+        channel: impl Peripheral<P = C> + 'a,
+        buf: &'a [W],
+        dest_addr: *mut W,
+        options: TransferOptions,
+    ) -> Self {
+        into_ref!(channel);
+
+        let (ptr, len) = super::slice_ptr_parts(buf);
+        assert!(len > 0 && len <= 0xFFFF);
+
+        // The direct mode isn't allowed in memory-to-memory mode.
+        let options = TransferOptions {
+            fifo_threshold: Some(options.fifo_threshold.unwrap_or(FifoThreshold::Full)),
+            ..options
+        };
+
+        Self::new_inner(
+            channel,
+            Request::default(),
+            Dir::MemoryToMemory,
+            dest_addr as *const u32,
+            ptr as *mut u32,
+            len,
+            true,
+            W::size(),
+            options,
+        )
+    }
+
     unsafe fn new_inner(
         channel: PeripheralRef<'a, C>,
         _request: Request,
@@ -368,8 +407,14 @@ impl<'a, C: Channel> Transfer<'a, C> {
         #[cfg(dmamux)]
         super::dmamux::configure_dmamux(&mut *this.channel, _request);
 
-        ch.par().write_value(peri_addr as u32);
-        ch.m0ar().write_value(mem_addr as u32);
+        // In memory-to-memory mode, the stream reads from PAR and writes to M0AR.
+        let (par, m0ar, incr_par, incr_m0ar) = match dir {
+            Dir::MemoryToMemory => (mem_addr as u32, peri_addr as u32, incr_mem, false),
+            _ => (peri_addr as u32, mem_addr as u32, false, incr_mem),
+        };
+
+        ch.par().write_value(par);
+        ch.m0ar().write_value(m0ar);
         ch.ndtr().write_value(regs::Ndtr(mem_len as _));
         ch.fcr().write(|w| {
             if let Some(fth) = options.fifo_threshold {
@@ -386,8 +431,8 @@ impl<'a, C: Channel> Transfer<'a, C> {
             w.set_msize(data_size.into());
             w.set_psize(data_size.into());
             w.set_pl(vals::Pl::VERYHIGH);
-            w.set_minc(incr_mem);
-            w.set_pinc(false);
+            w.set_minc(incr_m0ar);
+            w.set_pinc(incr_par);
             w.set_teie(true);
             w.set_tcie(options.complete_transfer_ir);
             w.set_circ(options.circular);
@@ -449,6 +494,31 @@ impl<'a, C: Channel> Transfer<'a, C> {
         ch.ndtr().read().ndt()
     }
 
+    /// Snapshot the registers of the channel into `buf`, for bug reports.
+    #[cfg(feature = "debug-dump")]
+    pub fn debug_dump<'b>(&self, buf: &'b mut [Register]) -> &'b [Register] {
+        let ch = self.channel.regs().st(self.channel.num());
+        crate::debug_dump::dump(
+            buf,
+            &[
+                ("CR", ch.cr().read().0),
+                ("NDTR", ch.ndtr().read().0),
+                ("PAR", ch.par().read()),
+                ("M0AR", ch.m0ar().read()),
+                ("FCR", ch.fcr().read().0),
+            ],
+        )
+    }
+
+    /// Wait until the transfer finishes, or stop it after `timeout`.
+    ///
+    /// On timeout, the channel is stopped before returning, and
+    /// [`get_remaining_transfers`](Self::get_remaining_transfers) gives the number of transfers not done.
+    #[cfg(feature = "time")]
+    pub async fn wait_timeout(&mut self, timeout: embassy_time::Duration) -> Result<(), super::Error> {
+        super::wait_timeout(self, Some(timeout)).await
+    }
+
     /// Blocking wait until the transfer finishes.
     pub fn blocking_wait(mut self) {
         while self.is_running() {}
@@ -460,6 +530,20 @@ impl<'a, C: Channel> Transfer<'a, C> {
     }
 }
 
+impl<'a, C: Channel> super::TransferControl for Transfer<'a, C> {
+    fn request_stop(&mut self) {
+        Transfer::request_stop(self)
+    }
+
+    fn is_running(&mut self) -> bool {
+        Transfer::is_running(self)
+    }
+
+    fn get_remaining_transfers(&self) -> u16 {
+        Transfer::get_remaining_transfers(self)
+    }
+}
+
 impl<'a, C: Channel> Drop for Transfer<'a, C> {
     fn drop(&mut self) {
         self.request_stop();
@@ -502,11 +586,54 @@ impl<'a, C: Channel, W: Word> DoubleBuffered<'a, C, W> {
         buf1: *mut W,
         len: usize,
         options: TransferOptions,
+    ) -> Self {
+        Self::new_inner(
+            channel,
+            _request,
+            Dir::PeripheralToMemory,
+            peri_addr,
+            buf0,
+            buf1,
+            len,
+            options,
+        )
+    }
+
+    /// Create a new write DMA transfer (memory to peripheral).
+    pub unsafe fn new_write(
+        channel: impl Peripheral<P = C> + 'a,
+        _request: Request,
+        buf0: *const W,
+        buf1: *const W,
+        peri_addr: *mut W,
+        len: usize,
+        options: TransferOptions,
+    ) -> Self {
+        Self::new_inner(
+            channel,
+            _request,
+            Dir::MemoryToPeripheral,
+            peri_addr,
+            buf0 as *mut W,
+            buf1 as *mut W,
+            len,
+            options,
+        )
+    }
+
+    unsafe fn new_inner(
+        channel: impl Peripheral<P = C> + 'a,
+        _request: Request,
+        dir: Dir,
+        peri_addr: *mut W,
+        buf0: *mut W,
+        buf1: *mut W,
+        len: usize,
+        options: TransferOptions,
     ) -> Self {
         into_ref!(channel);
         assert!(len > 0 && len <= 0xFFFF);
 
-        let dir = Dir::PeripheralToMemory;
         let data_size = W::size();
 
         let channel_number = channel.num();
@@ -548,6 +675,7 @@ impl<'a, C: Channel, W: Word> DoubleBuffered<'a, C, W> {
             w.set_pinc(false);
             w.set_teie(true);
             w.set_tcie(true);
+            w.set_dbm(vals::Dbm::ENABLED);
             #[cfg(dma_v1)]
             w.set_trbuff(true);
 
@@ -579,7 +707,8 @@ impl<'a, C: Channel, W: Word> DoubleBuffered<'a, C, W> {
 
     /// Set the first buffer address.
     ///
-    /// You may call this while DMA is transferring the other buffer.
+    /// You may call this while DMA is transferring the other buffer. For a write transfer, the
+    /// buffer is only read.
     pub unsafe fn set_buffer0(&mut self, buffer: *mut W) {
         let ch = self.channel.regs().st(self.channel.num());
         ch.m0ar().write_value(buffer as _);
@@ -587,7 +716,8 @@ impl<'a, C: Channel, W: Word> DoubleBuffered<'a, C, W> {
 
     /// Set the second buffer address.
     ///
-    /// You may call this while DMA is transferring the other buffer.
+    /// You may call this while DMA is transferring the other buffer. For a write transfer, the
+    /// buffer is only read.
     pub unsafe fn set_buffer1(&mut self, buffer: *mut W) {
         let ch = self.channel.regs().st(self.channel.num());
         ch.m1ar().write_value(buffer as _);
@@ -748,9 +878,12 @@ impl<'a, C: Channel, W: Word> ReadableRingBuffer<'a, C, W> {
 
     /// Start the ring buffer operation.
     ///
-    /// You must call this after creating it for it to work.
+    /// You must call this after creating it for it to work. After [`request_stop`](Self::request_stop),
+    /// the DMA restarts at the beginning of the buffer, so call [`clear`](Self::clear) first.
     pub fn start(&mut self) {
         let ch = self.channel.regs().st(self.channel.num());
+        // NDTR keeps its value when the stream is stopped, and can't be written while it runs.
+        ch.ndtr().write_value(regs::Ndtr(self.ringbuf.cap() as _));
         ch.cr().write_value(self.cr);
     }
 
@@ -11,6 +11,8 @@ use embassy_sync::waitqueue::AtomicWaker;
 use super::word::{Word, WordSize};
 use super::Dir;
 use crate::_generated::GPDMA_CHANNEL_COUNT;
+#[cfg(feature = "debug-dump")]
+use crate::debug_dump::Register;
 use crate::interrupt::typelevel::Interrupt;
 use crate::interrupt::Priority;
 use crate::pac;
@@ -240,6 +242,34 @@ impl<'a, C: Channel> Transfer<'a, C> {
         )
     }
 
+    /// Create a new memory-to-memory DMA transfer, writing `buf` to the fixed address `dest_addr`.
+    ///
+    /// The transfer is not paced by a peripheral request, and runs as fast as the bus allows.
+    /// This is meant for peripherals without DMA request, like the CRC unit.
+    pub unsafe fn new_write_memory_to_memory<W: Word>(
+        channel: impl Peripheral<P = C> + 'a,
+        buf: &'a [W],
+        dest_addr: *mut W,
+        options: TransferOptions,
+    ) -> Self {
+        into_ref!(channel);
+
+        let (ptr, len) = super::slice_ptr_parts(buf);
+        assert!(len > 0 && len * W::size().bytes() <= 0xFFFF);
+
+        Self::new_inner(
+            channel,
+            Request::default(),
+            Dir::MemoryToMemory,
+            dest_addr as *const u32,
+            ptr as *mut u32,
+            len,
+            true,
+            W::size(),
+            options,
+        )
+    }
+
     unsafe fn new_inner(
         channel: PeripheralRef<'a, C>,
         request: Request,
@@ -267,14 +297,15 @@ impl<'a, C: Channel> Transfer<'a, C> {
         ch.tr1().write(|w| {
             w.set_sdw(data_size.into());
             w.set_ddw(data_size.into());
-            w.set_sinc(dir == Dir::MemoryToPeripheral && incr_mem);
+            w.set_sinc(dir != Dir::PeripheralToMemory && incr_mem);
             w.set_dinc(dir == Dir::PeripheralToMemory && incr_mem);
         });
         ch.tr2().write(|w| {
             w.set_dreq(match dir {
-                Dir::MemoryToPeripheral => vals::ChTr2Dreq::DESTINATIONPERIPHERAL,
+                Dir::MemoryToPeripheral | Dir::MemoryToMemory => vals::ChTr2Dreq::DESTINATIONPERIPHERAL,
                 Dir::PeripheralToMemory => vals::ChTr2Dreq::SOURCEPERIPHERAL,
             });
+            w.set_swreq(dir == Dir::MemoryToMemory);
             w.set_reqsel(request);
         });
         ch.br1().write(|w| {
@@ -283,7 +314,7 @@ impl<'a, C: Channel> Transfer<'a, C> {
         });
 
         match dir {
-            Dir::MemoryToPeripheral => {
+            Dir::MemoryToPeripheral | Dir::MemoryToMemory => {
                 ch.sar().write_value(mem_addr as _);
                 ch.dar().write_value(peri_addr as _);
             }
@@ -334,6 +365,33 @@ impl<'a, C: Channel> Transfer<'a, C> {
         ch.br1().read().bndt()
     }
 
+    /// Snapshot the registers of the channel into `buf`, for bug reports.
+    #[cfg(feature = "debug-dump")]
+    pub fn debug_dump<'b>(&self, buf: &'b mut [Register]) -> &'b [Register] {
+        let ch = self.channel.regs().ch(self.channel.num());
+        crate::debug_dump::dump(
+            buf,
+            &[
+                ("CR", ch.cr().read().0),
+                ("SR", ch.sr().read().0),
+                ("TR1", ch.tr1().read().0),
+                ("TR2", ch.tr2().read().0),
+                ("BR1", ch.br1().read().0),
+                ("SAR", ch.sar().read()),
+                ("DAR", ch.dar().read()),
+            ],
+        )
+    }
+
+    /// Wait until the transfer finishes, or stop it after `timeout`.
+    ///
+    /// On timeout, the channel is stopped before returning, and
+    /// [`get_remaining_transfers`](Self::get_remaining_transfers) gives the number of transfers not done.
+    #[cfg(feature = "time")]
+    pub async fn wait_timeout(&mut self, timeout: embassy_time::Duration) -> Result<(), super::Error> {
+        super::wait_timeout(self, Some(timeout)).await
+    }
+
     /// Blocking wait until the transfer finishes.
     pub fn blocking_wait(mut self) {
         while self.is_running() {}
@@ -345,6 +403,20 @@ impl<'a, C: Channel> Transfer<'a, C> {
     }
 }
 
+impl<'a, C: Channel> super::TransferControl for Transfer<'a, C> {
+    fn request_stop(&mut self) {
+        Transfer::request_stop(self)
+    }
+
+    fn is_running(&mut self) -> bool {
+        Transfer::is_running(self)
+    }
+
+    fn get_remaining_transfers(&self) -> u16 {
+        Transfer::get_remaining_transfers(self)
+    }
+}
+
 impl<'a, C: Channel> Drop for Transfer<'a, C> {
     fn drop(&mut self) {
         self.request_stop();
@@ -23,9 +23,11 @@ pub use gpdma::*;
 #[cfg(dmamux)]
 mod dmamux;
 
+pub mod buffer;
 pub(crate) mod ringbuffer;
 pub mod word;
 
+use core::future::Future;
 use core::mem;
 
 use embassy_hal_internal::impl_peripheral;
@@ -39,6 +41,59 @@ use crate::interrupt::Priority;
 enum Dir {
     MemoryToPeripheral,
     PeripheralToMemory,
+    /// Memory to a fixed address, paced by the DMA itself instead of a peripheral request.
+    MemoryToMemory,
+}
+
+/// DMA transfer error.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// The transfer did not finish before its timeout.
+    Timeout,
+}
+
+/// Optional timeout of the transfers of a driver.
+///
+/// There is no timeout without the `time` feature.
+#[cfg(feature = "time")]
+pub(crate) type TransferTimeout = Option<embassy_time::Duration>;
+#[cfg(not(feature = "time"))]
+pub(crate) type TransferTimeout = Option<core::convert::Infallible>;
+
+/// Control of a running transfer, implemented by the transfers of all the DMA controllers.
+pub(crate) trait TransferControl: Future<Output = ()> + Unpin {
+    fn request_stop(&mut self);
+    fn is_running(&mut self) -> bool;
+    fn get_remaining_transfers(&self) -> u16;
+}
+
+/// Wait until `transfer` finishes, or stop it after `timeout` if there is one.
+pub(crate) async fn wait_timeout(transfer: &mut impl TransferControl, timeout: TransferTimeout) -> Result<(), Error> {
+    #[cfg(feature = "time")]
+    if let Some(timeout) = timeout {
+        if embassy_time::with_timeout(timeout, &mut *transfer).await.is_ok() {
+            return Ok(());
+        }
+
+        transfer.request_stop();
+        while transfer.is_running() {}
+
+        // "Subsequent reads and writes cannot be moved ahead of preceding reads."
+        core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst);
+
+        // The transfer may have finished meanwhile.
+        return match transfer.get_remaining_transfers() {
+            0 => Ok(()),
+            _ => Err(Error::Timeout),
+        };
+    }
+    #[cfg(not(feature = "time"))]
+    let _ = timeout;
+
+    transfer.await;
+    Ok(())
 }
 
 /// "No DMA" placeholder.
@@ -47,7 +102,8 @@ enum Dir {
 /// to indicate it should not use DMA.
 ///
 /// This often causes async functionality to not be available on the instance,
-/// leaving only blocking functionality.
+/// leaving only blocking functionality. The USART and I2C drivers instead fall back to an
+/// interrupt-driven implementation of their async API. See also [`crate::mode`].
 pub struct NoDma;
 
 impl_peripheral!(NoDma);
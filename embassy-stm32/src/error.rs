@@ -0,0 +1,213 @@
+//! Errors of all the drivers.
+//!
+//! Each driver has its own error type. [`Error`] wraps them in a single type, and classifies them
+//! with [`ErrorKind`], so recovery code can handle the errors of several drivers together:
+//!
+//! ```rust,ignore
+//! async fn poll_sensors(i2c: &mut I2c<'_, I2C1, NoDma, NoDma>, uart: &mut Uart<'_, USART2, NoDma, NoDma>)
+//!     -> Result<(), embassy_stm32::Error>
+//! {
+//!     // ...
+//! }
+//!
+//! match poll_sensors(&mut i2c, &mut uart).await {
+//!     Err(e) if e.kind() == ErrorKind::Timeout => { /* retry */ }
+//!     Err(e) => defmt::panic!("{:?}", e),
+//!     Ok(()) => {}
+//! }
+//! ```
+
+/// Class of an error, common to all the drivers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// An operation did not complete in time.
+    Timeout,
+    /// A device did not acknowledge.
+    Nack,
+    /// Received data was lost because it was not read in time.
+    Overrun,
+    /// Bus error or arbitration loss.
+    Bus,
+    /// Corrupted data: framing, noise, parity or CRC error.
+    Data,
+    /// Invalid use of the driver, like an unsupported buffer length.
+    Usage,
+    /// Any other error.
+    Other,
+}
+
+/// Error of any driver.
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// Flash error.
+    Flash(crate::flash::Error),
+    /// DCMI error.
+    #[cfg(dcmi)]
+    Dcmi(crate::dcmi::Error),
+    /// DMA transfer error.
+    Dma(crate::dma::Error),
+    /// I2C error.
+    #[cfg(i2c)]
+    I2c(crate::i2c::Error),
+    /// RNG error.
+    #[cfg(rng)]
+    Rng(crate::rng::Error),
+    /// SAI error.
+    #[cfg(sai)]
+    Sai(crate::sai::Error),
+    /// SDMMC error.
+    #[cfg(sdmmc)]
+    Sdmmc(crate::sdmmc::Error),
+    /// SPI error.
+    #[cfg(spi)]
+    Spi(crate::spi::Error),
+    /// USART error.
+    #[cfg(usart)]
+    Usart(crate::usart::Error),
+}
+
+impl Error {
+    /// Get the class of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Flash(e) => {
+                use crate::flash::Error;
+                match e {
+                    Error::Size | Error::Unaligned => ErrorKind::Usage,
+                    _ => ErrorKind::Other,
+                }
+            }
+            #[cfg(dcmi)]
+            Self::Dcmi(e) => match e {
+                crate::dcmi::Error::Overrun => ErrorKind::Overrun,
+                crate::dcmi::Error::PeripheralError => ErrorKind::Other,
+            },
+            Self::Dma(e) => match e {
+                crate::dma::Error::Timeout => ErrorKind::Timeout,
+            },
+            #[cfg(i2c)]
+            Self::I2c(e) => {
+                use crate::i2c::Error;
+                match e {
+                    Error::Bus | Error::Arbitration => ErrorKind::Bus,
+                    Error::Nack => ErrorKind::Nack,
+                    Error::Timeout => ErrorKind::Timeout,
+                    Error::Crc => ErrorKind::Data,
+                    Error::Overrun => ErrorKind::Overrun,
+                    Error::ZeroLengthTransfer => ErrorKind::Usage,
+                }
+            }
+            #[cfg(rng)]
+            Self::Rng(_) => ErrorKind::Other,
+            #[cfg(sai)]
+            Self::Sai(e) => {
+                use crate::sai::Error;
+                match e {
+                    Error::NotATransmitter | Error::NotAReceiver => ErrorKind::Usage,
+                    Error::Overrun => ErrorKind::Overrun,
+                }
+            }
+            #[cfg(sdmmc)]
+            Self::Sdmmc(e) => {
+                use crate::sdmmc::Error;
+                match e {
+                    Error::Timeout | Error::SoftwareTimeout => ErrorKind::Timeout,
+                    Error::CmdCrc | Error::DataCrc => ErrorKind::Data,
+                    _ => ErrorKind::Other,
+                }
+            }
+            #[cfg(spi)]
+            Self::Spi(e) => {
+                use crate::spi::Error;
+                match e {
+                    Error::Framing | Error::Crc => ErrorKind::Data,
+                    Error::ModeFault => ErrorKind::Bus,
+                    Error::Overrun => ErrorKind::Overrun,
+                    Error::Timeout => ErrorKind::Timeout,
+                }
+            }
+            #[cfg(usart)]
+            Self::Usart(e) => {
+                use crate::usart::Error;
+                match e {
+                    Error::Framing | Error::Noise | Error::Parity => ErrorKind::Data,
+                    Error::Overrun => ErrorKind::Overrun,
+                    Error::BufferTooLong => ErrorKind::Usage,
+                    Error::Timeout => ErrorKind::Timeout,
+                }
+            }
+        }
+    }
+}
+
+macro_rules! impl_from {
+    ($variant:ident, $error:ty) => {
+        impl From<$error> for Error {
+            fn from(e: $error) -> Self {
+                Self::$variant(e)
+            }
+        }
+    };
+}
+
+impl_from!(Flash, crate::flash::Error);
+#[cfg(dcmi)]
+impl_from!(Dcmi, crate::dcmi::Error);
+impl_from!(Dma, crate::dma::Error);
+#[cfg(i2c)]
+impl_from!(I2c, crate::i2c::Error);
+#[cfg(rng)]
+impl_from!(Rng, crate::rng::Error);
+#[cfg(sai)]
+impl_from!(Sai, crate::sai::Error);
+#[cfg(sdmmc)]
+impl_from!(Sdmmc, crate::sdmmc::Error);
+#[cfg(spi)]
+impl_from!(Spi, crate::spi::Error);
+#[cfg(usart)]
+impl_from!(Usart, crate::usart::Error);
+
+impl embedded_io::Error for Error {
+    fn kind(&self) -> embedded_io::ErrorKind {
+        match self.kind() {
+            ErrorKind::Timeout => embedded_io::ErrorKind::TimedOut,
+            ErrorKind::Data => embedded_io::ErrorKind::InvalidData,
+            ErrorKind::Usage => embedded_io::ErrorKind::InvalidInput,
+            _ => embedded_io::ErrorKind::Other,
+        }
+    }
+}
+
+impl embedded_hal_1::i2c::Error for Error {
+    fn kind(&self) -> embedded_hal_1::i2c::ErrorKind {
+        match self {
+            #[cfg(i2c)]
+            Self::I2c(e) => embedded_hal_1::i2c::Error::kind(e),
+            _ => match self.kind() {
+                ErrorKind::Nack => {
+                    embedded_hal_1::i2c::ErrorKind::NoAcknowledge(embedded_hal_1::i2c::NoAcknowledgeSource::Unknown)
+                }
+                ErrorKind::Bus => embedded_hal_1::i2c::ErrorKind::Bus,
+                ErrorKind::Overrun => embedded_hal_1::i2c::ErrorKind::Overrun,
+                _ => embedded_hal_1::i2c::ErrorKind::Other,
+            },
+        }
+    }
+}
+
+impl embedded_hal_1::spi::Error for Error {
+    fn kind(&self) -> embedded_hal_1::spi::ErrorKind {
+        match self {
+            #[cfg(spi)]
+            Self::Spi(e) => embedded_hal_1::spi::Error::kind(e),
+            _ => match self.kind() {
+                ErrorKind::Overrun => embedded_hal_1::spi::ErrorKind::Overrun,
+                _ => embedded_hal_1::spi::ErrorKind::Other,
+            },
+        }
+    }
+}
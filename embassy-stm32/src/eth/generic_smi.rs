@@ -6,7 +6,7 @@ use futures::task::Context;
 #[cfg(feature = "time")]
 use futures::FutureExt;
 
-use super::{StationManagement, PHY};
+use super::{PhyTestMode, StationManagement, PHY};
 
 #[allow(dead_code)]
 mod phy_consts {
@@ -98,6 +98,17 @@ unsafe impl PHY for GenericSMI {
         // Got link
         true
     }
+
+    fn set_test_mode<S: StationManagement>(&mut self, sm: &mut S, mode: PhyTestMode) -> bool {
+        let bcr = match mode {
+            PhyTestMode::Normal => PHY_REG_BCR_AN | PHY_REG_BCR_ANRST | PHY_REG_BCR_100M,
+            // The loopback path ignores the auto-negotiation, so the speed and duplex are forced.
+            PhyTestMode::Loopback => PHY_REG_BCR_LOOPBACK | PHY_REG_BCR_100M | PHY_REG_BCR_FD,
+            PhyTestMode::CollisionTest => PHY_REG_BCR_COLTEST | PHY_REG_BCR_100M | PHY_REG_BCR_FD,
+        };
+        sm.smi_write(self.phy_addr, PHY_REG_BCR, bcr);
+        true
+    }
 }
 
 /// Public functions for the PHY
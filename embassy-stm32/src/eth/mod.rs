@@ -5,25 +5,63 @@
 #[cfg_attr(eth_v2, path = "v2/mod.rs")]
 mod _version;
 pub mod generic_smi;
+#[cfg(any(eth_v1b, eth_v1c, eth_v2))]
+pub mod ptp;
+#[cfg(feature = "time")]
+mod self_test;
 
 use core::mem::MaybeUninit;
+use core::ops::Range;
+use core::sync::atomic::{AtomicU32, Ordering};
 use core::task::Context;
 
 use embassy_net_driver::{Capabilities, HardwareAddress, LinkState};
 use embassy_sync::waitqueue::AtomicWaker;
 
 pub use self::_version::{InterruptHandler, *};
+#[cfg(feature = "time")]
+pub use self::self_test::SelfTestError;
 use crate::rcc::RccPeripheral;
 
-#[allow(unused)]
-const MTU: usize = 1514;
-const TX_BUFFER_SIZE: usize = 1514;
-const RX_BUFFER_SIZE: usize = 1536;
+/// Default size of the transmit buffers, enough for a full Ethernet frame without its CRC.
+pub const TX_BUFFER_SIZE: usize = 1514;
+/// Default size of the receive buffers, enough for a full Ethernet frame.
+pub const RX_BUFFER_SIZE: usize = 1536;
 
 #[repr(C, align(8))]
 #[derive(Copy, Clone)]
 pub(crate) struct Packet<const N: usize>([u8; N]);
 
+/// Packet buffers of a descriptor ring, with their size erased.
+pub(crate) struct Packets<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+    stride: usize,
+}
+
+impl<'a> Packets<'a> {
+    pub(crate) fn new<const N: usize>(packets: &'a mut [Packet<N>]) -> Self {
+        let stride = core::mem::size_of::<Packet<N>>();
+        let buf = unsafe { core::slice::from_raw_parts_mut(packets.as_mut_ptr() as *mut u8, packets.len() * stride) };
+        Self { buf, len: N, stride }
+    }
+
+    /// Number of packets.
+    pub(crate) fn count(&self) -> usize {
+        self.buf.len() / self.stride
+    }
+
+    /// Size of each packet buffer.
+    pub(crate) fn buffer_len(&self) -> usize {
+        self.len
+    }
+
+    /// Buffer of the packet `i`.
+    pub(crate) fn get(&mut self, i: usize) -> &mut [u8] {
+        &mut self.buf[i * self.stride..][..self.len]
+    }
+}
+
 /// Ethernet packet queue.
 ///
 /// This struct owns the memory used for reading and writing packets.
@@ -32,14 +70,27 @@ pub(crate) struct Packet<const N: usize>([u8; N]);
 /// queue. A bigger queue allows the hardware to receive more packets while the
 /// CPU is busy doing other things, which may increase performance (especially for RX)
 /// at the cost of more RAM usage.
-pub struct PacketQueue<const TX: usize, const RX: usize> {
+///
+/// `TX_SIZE` and `RX_SIZE` are the sizes of each transmit and receive buffer, in bytes. They
+/// default to full Ethernet frames, and can be reduced on memory-constrained parts to run with
+/// a smaller MTU. The MTU reported to the network stack is the smaller of `TX_SIZE` and
+/// `RX_SIZE - 4`, and received frames longer than `RX_SIZE` are dropped. `RX_SIZE` must be a multiple of 4. The
+/// total size is given by [`memory_size`](Self::memory_size).
+pub struct PacketQueue<
+    const TX: usize,
+    const RX: usize,
+    const TX_SIZE: usize = TX_BUFFER_SIZE,
+    const RX_SIZE: usize = RX_BUFFER_SIZE,
+> {
     tx_desc: [TDes; TX],
     rx_desc: [RDes; RX],
-    tx_buf: [Packet<TX_BUFFER_SIZE>; TX],
-    rx_buf: [Packet<RX_BUFFER_SIZE>; RX],
+    tx_buf: [Packet<TX_SIZE>; TX],
+    rx_buf: [Packet<RX_SIZE>; RX],
 }
 
-impl<const TX: usize, const RX: usize> PacketQueue<TX, RX> {
+impl<const TX: usize, const RX: usize, const TX_SIZE: usize, const RX_SIZE: usize>
+    PacketQueue<TX, RX, TX_SIZE, RX_SIZE>
+{
     /// Create a new packet queue.
     pub const fn new() -> Self {
         const NEW_TDES: TDes = TDes::new();
@@ -47,11 +98,22 @@ impl<const TX: usize, const RX: usize> PacketQueue<TX, RX> {
         Self {
             tx_desc: [NEW_TDES; TX],
             rx_desc: [NEW_RDES; RX],
-            tx_buf: [Packet([0; TX_BUFFER_SIZE]); TX],
-            rx_buf: [Packet([0; RX_BUFFER_SIZE]); RX],
+            tx_buf: [Packet([0; TX_SIZE]); TX],
+            rx_buf: [Packet([0; RX_SIZE]); RX],
         }
     }
 
+    /// Static memory used by the queue, in bytes: the descriptors and the packet buffers.
+    ///
+    /// This can be checked at compile time against the memory budget of the application:
+    ///
+    /// ```rust,ignore
+    /// const _: () = assert!(PacketQueue::<4, 4, 600, 600>::memory_size() <= 8 * 1024);
+    /// ```
+    pub const fn memory_size() -> usize {
+        core::mem::size_of::<Self>()
+    }
+
     /// Initialize a packet queue in-place.
     ///
     /// This can be helpful to avoid accidentally stack-allocating the packet queue in the stack. The
@@ -71,7 +133,238 @@ impl<const TX: usize, const RX: usize> PacketQueue<TX, RX> {
     }
 }
 
+/// Memory ranges the Ethernet DMA cannot access.
+#[cfg(stm32h7)]
+const DMA_INACCESSIBLE: &[Range<usize>] = &[
+    // ITCM
+    0x0000_0000..0x0001_0000,
+    // DTCM
+    0x2000_0000..0x2002_0000,
+];
+#[cfg(stm32f7)]
+const DMA_INACCESSIBLE: &[Range<usize>] = &[
+    // ITCM
+    0x0000_0000..0x0000_4000,
+];
+#[cfg(stm32f4)]
+const DMA_INACCESSIBLE: &[Range<usize>] = &[
+    // CCM
+    0x1000_0000..0x1001_0000,
+];
+#[cfg(not(any(stm32h7, stm32f7, stm32f4)))]
+const DMA_INACCESSIBLE: &[Range<usize>] = &[];
+
+/// Packet queue placed in memory the Ethernet DMA can access.
+///
+/// The descriptor rings and packet buffers are read and written by the Ethernet DMA, so they must not
+/// live in tightly-coupled memories (ITCM/DTCM on H7 and F7, CCM on F4). Stack variables and statics
+/// end up in DTCM with the default linker scripts of some chips, so place the [`PacketQueue`] in a
+/// suitable section with `#[link_section]` when needed.
+///
+/// A `&mut PacketQueue` converts into an `EthMemory` with [`EthMemory::new`], so it can be passed
+/// directly to the [`Ethernet`] constructors.
+pub struct EthMemory<
+    'd,
+    const TX: usize,
+    const RX: usize,
+    const TX_SIZE: usize = TX_BUFFER_SIZE,
+    const RX_SIZE: usize = RX_BUFFER_SIZE,
+> {
+    pub(crate) queue: &'d mut PacketQueue<TX, RX, TX_SIZE, RX_SIZE>,
+}
+
+impl<'d, const TX: usize, const RX: usize, const TX_SIZE: usize, const RX_SIZE: usize>
+    EthMemory<'d, TX, RX, TX_SIZE, RX_SIZE>
+{
+    const LAYOUT_OK: () = {
+        // The DMA requires word-aligned descriptors and buffers.
+        assert!(core::mem::align_of::<TDes>() >= 4 && core::mem::size_of::<TDes>() % 4 == 0);
+        assert!(core::mem::align_of::<RDes>() >= 4 && core::mem::size_of::<RDes>() % 4 == 0);
+        assert!(core::mem::align_of::<Packet<TX_SIZE>>() >= 4);
+        assert!(TX_SIZE > 0 && RX_SIZE > 0);
+        assert!(RX_SIZE % 4 == 0);
+        assert!(TX > 0 && RX > 0);
+    };
+
+    /// Use `queue` as Ethernet memory.
+    ///
+    /// Panics if the queue lies in a memory region the Ethernet DMA cannot access.
+    pub fn new(queue: &'d mut PacketQueue<TX, RX, TX_SIZE, RX_SIZE>) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let _ = Self::LAYOUT_OK;
+
+        let start = queue as *const _ as usize;
+        let end = start + PacketQueue::<TX, RX, TX_SIZE, RX_SIZE>::memory_size();
+        for region in DMA_INACCESSIBLE {
+            if start < region.end && region.start < end {
+                panic!(
+                    "Ethernet packet queue at {:#x} is not accessible by the Ethernet DMA",
+                    start
+                );
+            }
+        }
+
+        Self { queue }
+    }
+
+    /// Initialize a packet queue in-place and use it as Ethernet memory.
+    ///
+    /// This is the same as [`PacketQueue::init`] followed by [`EthMemory::new`], and is meant to be
+    /// used with an uninitialized `static` placed with `#[link_section]`.
+    pub fn init(queue: &'d mut MaybeUninit<PacketQueue<TX, RX, TX_SIZE, RX_SIZE>>) -> Self {
+        PacketQueue::init(queue);
+        Self::new(unsafe { queue.assume_init_mut() })
+    }
+
+    /// Use `queue` as Ethernet memory without checking its location.
+    ///
+    /// # Safety
+    ///
+    /// The queue must be accessible by the Ethernet DMA.
+    pub unsafe fn new_unchecked(queue: &'d mut PacketQueue<TX, RX, TX_SIZE, RX_SIZE>) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let _ = Self::LAYOUT_OK;
+
+        Self { queue }
+    }
+}
+
+impl<'d, const TX: usize, const RX: usize, const TX_SIZE: usize, const RX_SIZE: usize>
+    From<&'d mut PacketQueue<TX, RX, TX_SIZE, RX_SIZE>> for EthMemory<'d, TX, RX, TX_SIZE, RX_SIZE>
+{
+    fn from(queue: &'d mut PacketQueue<TX, RX, TX_SIZE, RX_SIZE>) -> Self {
+        Self::new(queue)
+    }
+}
+
+/// Ethernet statistics counters.
+///
+/// The MAC counters come from the MMC (MAC management counters) block and wrap around on overflow.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Statistics {
+    /// Frames received with a CRC error.
+    pub rx_crc_errors: u32,
+    /// Frames received with an alignment error.
+    pub rx_alignment_errors: u32,
+    /// Frames transmitted successfully after exactly one collision.
+    pub tx_single_collisions: u32,
+    /// Frames transmitted successfully after more than one collision.
+    pub tx_multiple_collisions: u32,
+    /// Frames dropped by the DMA engine because no receive descriptor was available.
+    pub rx_dropped_frames: u32,
+    /// Number of times the receive descriptor ring was found completely full by the driver.
+    pub rx_ring_overruns: u32,
+}
+
+/// Wake-on-LAN configuration, see [`Ethernet::enable_wakeup`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct WakeupConfig {
+    /// Wake up on a magic packet (six 0xFF bytes followed by the MAC address repeated 16 times).
+    pub magic_packet: bool,
+    /// Wake up on a frame matching one of the wakeup frame filters.
+    pub wakeup_frame: bool,
+    /// Wake up on any unicast frame addressed to this MAC address.
+    pub global_unicast: bool,
+}
+
+/// Event that woke the MAC up from power-down mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WakeupSource {
+    /// A magic packet was received.
+    MagicPacket,
+    /// A wakeup frame (or a unicast frame, with [`WakeupConfig::global_unicast`]) was received.
+    WakeupFrame,
+}
+
+/// Driver performance counters, see [`perf_counters`].
+///
+/// All the counters are cumulative since reset and wrap around on overflow. Take the
+/// [`delta`](Self::delta) of two snapshots to get rates, e.g. descriptors reused per second.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PerfCounters {
+    /// Ethernet interrupts handled.
+    pub interrupts: u32,
+    /// Receive descriptors handed back to the DMA after their frame was consumed.
+    pub rx_descriptors: u32,
+    /// Transmit descriptors handed to the DMA.
+    pub tx_descriptors: u32,
+    /// Bytes of received frames passed to the network stack.
+    pub rx_bytes: u32,
+    /// Bytes of transmitted frames written by the network stack.
+    pub tx_bytes: u32,
+}
+
+impl PerfCounters {
+    /// Difference between this snapshot and an `earlier` one.
+    pub fn delta(&self, earlier: &Self) -> Self {
+        Self {
+            interrupts: self.interrupts.wrapping_sub(earlier.interrupts),
+            rx_descriptors: self.rx_descriptors.wrapping_sub(earlier.rx_descriptors),
+            tx_descriptors: self.tx_descriptors.wrapping_sub(earlier.tx_descriptors),
+            rx_bytes: self.rx_bytes.wrapping_sub(earlier.rx_bytes),
+            tx_bytes: self.tx_bytes.wrapping_sub(earlier.tx_bytes),
+        }
+    }
+}
+
+struct AtomicPerfCounters {
+    interrupts: AtomicU32,
+    rx_descriptors: AtomicU32,
+    tx_descriptors: AtomicU32,
+    rx_bytes: AtomicU32,
+    tx_bytes: AtomicU32,
+}
+
+static PERF: AtomicPerfCounters = AtomicPerfCounters {
+    interrupts: AtomicU32::new(0),
+    rx_descriptors: AtomicU32::new(0),
+    tx_descriptors: AtomicU32::new(0),
+    rx_bytes: AtomicU32::new(0),
+    tx_bytes: AtomicU32::new(0),
+};
+
+/// Read the driver performance counters.
+///
+/// This is a free function rather than a method so the counters can still be read after the
+/// [`Ethernet`] driver has been moved into the network stack.
+pub fn perf_counters() -> PerfCounters {
+    PerfCounters {
+        interrupts: PERF.interrupts.load(Ordering::Relaxed),
+        rx_descriptors: PERF.rx_descriptors.load(Ordering::Relaxed),
+        tx_descriptors: PERF.tx_descriptors.load(Ordering::Relaxed),
+        rx_bytes: PERF.rx_bytes.load(Ordering::Relaxed),
+        tx_bytes: PERF.tx_bytes.load(Ordering::Relaxed),
+    }
+}
+
+fn count_interrupt() {
+    PERF.interrupts.fetch_add(1, Ordering::Relaxed);
+}
+
 static WAKER: AtomicWaker = AtomicWaker::new();
+static WAKEUP_WAKER: AtomicWaker = AtomicWaker::new();
+
+impl<'d, T: Instance, P: PHY> Ethernet<'d, T, P> {
+    /// PTP timestamp of the frame `id`, once it has been transmitted.
+    ///
+    /// Returns `None` if the frame wasn't timestamped, is still being sent, or was sent so long ago
+    /// that its descriptor has been reused.
+    #[cfg(any(eth_v1b, eth_v1c, eth_v2))]
+    pub fn tx_timestamp(&self, id: ptp::TxFrameId) -> Option<ptp::Timestamp> {
+        self.tx.timestamp(id)
+    }
+
+    /// Put the PHY in a test mode, or back in normal operation with [`PhyTestMode::Normal`].
+    ///
+    /// Returns `false` if the PHY doesn't support `mode`.
+    pub fn set_phy_test_mode(&mut self, mode: PhyTestMode) -> bool {
+        self.phy.set_test_mode(&mut self.station_management, mode)
+    }
+}
 
 impl<'d, T: Instance, P: PHY> embassy_net_driver::Driver for Ethernet<'d, T, P> {
     type RxToken<'a> = RxToken<'a, 'd> where Self: 'a;
@@ -97,7 +390,8 @@ impl<'d, T: Instance, P: PHY> embassy_net_driver::Driver for Ethernet<'d, T, P>
 
     fn capabilities(&self) -> Capabilities {
         let mut caps = Capabilities::default();
-        caps.max_transmission_unit = MTU;
+        // Leave room for the CRC of the received frames.
+        caps.max_transmission_unit = self.tx.buffer_len().min(self.rx.buffer_len() - 4);
         caps.max_burst_size = Some(self.tx.len());
         caps
     }
@@ -120,6 +414,14 @@ pub struct RxToken<'a, 'd> {
     rx: &'a mut RDesRing<'d>,
 }
 
+#[cfg(any(eth_v1b, eth_v1c, eth_v2))]
+impl<'a, 'd> RxToken<'a, 'd> {
+    /// PTP timestamp of the received frame, if [`ptp::Ptp`] is enabled.
+    pub fn timestamp(&self) -> Option<ptp::Timestamp> {
+        self.rx.timestamp()
+    }
+}
+
 impl<'a, 'd> embassy_net_driver::RxToken for RxToken<'a, 'd> {
     fn consume<R, F>(self, f: F) -> R
     where
@@ -127,8 +429,11 @@ impl<'a, 'd> embassy_net_driver::RxToken for RxToken<'a, 'd> {
     {
         // NOTE(unwrap): we checked the queue wasn't full when creating the token.
         let pkt = unwrap!(self.rx.available());
+        let len = pkt.len() as u32;
         let r = f(pkt);
         self.rx.pop_packet();
+        PERF.rx_descriptors.fetch_add(1, Ordering::Relaxed);
+        PERF.rx_bytes.fetch_add(len, Ordering::Relaxed);
         r
     }
 }
@@ -138,6 +443,19 @@ pub struct TxToken<'a, 'd> {
     tx: &'a mut TDesRing<'d>,
 }
 
+#[cfg(any(eth_v1b, eth_v1c, eth_v2))]
+impl<'a, 'd> TxToken<'a, 'd> {
+    /// Send a frame like [`consume`](embassy_net_driver::TxToken::consume), and return its
+    /// identifier, to read its PTP timestamp with [`Ethernet::tx_timestamp`].
+    pub fn consume_with_id<R, F>(self, len: usize, f: F) -> (R, ptp::TxFrameId)
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let id = self.tx.next_frame_id();
+        (embassy_net_driver::TxToken::consume(self, len, f), id)
+    }
+}
+
 impl<'a, 'd> embassy_net_driver::TxToken for TxToken<'a, 'd> {
     fn consume<R, F>(self, len: usize, f: F) -> R
     where
@@ -147,6 +465,8 @@ impl<'a, 'd> embassy_net_driver::TxToken for TxToken<'a, 'd> {
         let pkt = unwrap!(self.tx.available());
         let r = f(&mut pkt[..len]);
         self.tx.transmit(len);
+        PERF.tx_descriptors.fetch_add(1, Ordering::Relaxed);
+        PERF.tx_bytes.fetch_add(len as u32, Ordering::Relaxed);
         r
     }
 }
@@ -175,6 +495,26 @@ pub unsafe trait PHY {
     fn phy_init<S: StationManagement>(&mut self, sm: &mut S);
     /// Poll link to see if it is up and FD with 100Mbps
     fn poll_link<S: StationManagement>(&mut self, sm: &mut S, cx: &mut Context) -> bool;
+    /// Put the PHY in a test mode, or back in normal operation.
+    ///
+    /// Returns `false` if the PHY doesn't support `mode`, which is the default.
+    fn set_test_mode<S: StationManagement>(&mut self, sm: &mut S, mode: PhyTestMode) -> bool {
+        let _ = (sm, mode);
+        false
+    }
+}
+
+/// PHY test mode, see [`PHY::set_test_mode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PhyTestMode {
+    /// Normal operation, with auto-negotiation.
+    Normal,
+    /// Loopback of the transmitted frames to the MAC, at 100 Mbit/s full-duplex, without sending
+    /// them on the line.
+    Loopback,
+    /// Collision signal asserted while transmitting, to test the collision handling of the MAC.
+    CollisionTest,
 }
 
 pub(crate) mod sealed {
@@ -0,0 +1,264 @@
+//! Precision Time Protocol (IEEE 1588) hardware clock and timestamping.
+//!
+//! The Ethernet MAC contains a PTP clock which is used to timestamp frames as they
+//! pass the MII interface. Once enabled with [`Ptp::new`], every transmitted and received
+//! frame is timestamped. The timestamp of a received frame is read from its token with
+//! [`RxToken::timestamp`](super::RxToken::timestamp). A frame sent with
+//! [`TxToken::consume_with_id`](super::TxToken::consume_with_id) is identified by a [`TxFrameId`],
+//! and its timestamp is read with [`Ethernet::tx_timestamp`] once it has been transmitted.
+
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use super::{Ethernet, Instance, PHY};
+use crate::pac::ETH;
+use crate::rcc::sealed::RccPeripheral;
+
+/// Timestamp control register fields, identical on all MAC versions.
+mod tscr {
+    /// Timestamp enable
+    pub const TSE: u32 = 1 << 0;
+    /// Timestamp fine or coarse update
+    pub const TSFCU: u32 = 1 << 1;
+    /// Timestamp initialize
+    pub const TSSTI: u32 = 1 << 2;
+    /// Timestamp update
+    pub const TSSTU: u32 = 1 << 3;
+    /// Timestamp addend register update
+    pub const TSARU: u32 = 1 << 5;
+    /// Timestamp snapshot for all received frames enable
+    pub const TSSARFE: u32 = 1 << 8;
+    /// Timestamp subsecond rollover: digital (1 ns resolution) instead of binary
+    pub const TSSSR: u32 = 1 << 9;
+    /// PTP packet snooping for version 2 format enable
+    pub const TSPTPPSV2E: u32 = 1 << 10;
+}
+
+/// Sign bit of the nanoseconds update register, subtract the update from the current time.
+const NANOSECONDS_SUB: u32 = 1 << 31;
+const NANOSECONDS_MASK: u32 = 0x7FFF_FFFF;
+const NANOS_PER_SECOND: u32 = 1_000_000_000;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// PTP clock time or frame timestamp.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Timestamp {
+    /// Seconds.
+    pub seconds: u32,
+    /// Nanoseconds, always less than 1_000_000_000.
+    pub nanoseconds: u32,
+}
+
+impl Timestamp {
+    /// Create a timestamp from the raw seconds and nanoseconds words written back by the DMA.
+    pub(crate) fn from_raw(seconds: u32, nanoseconds: u32) -> Self {
+        Self {
+            seconds,
+            nanoseconds: nanoseconds & NANOSECONDS_MASK,
+        }
+    }
+}
+
+/// Identifier of a transmitted frame, to read its timestamp with [`Ethernet::tx_timestamp`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TxFrameId {
+    /// Index of the descriptor of the frame.
+    pub(crate) index: usize,
+    /// Number of frames sent before this one, wrapping around.
+    pub(crate) seq: u32,
+}
+
+/// Whether frames should be timestamped by the MAC.
+pub(crate) fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// PTP hardware clock.
+///
+/// This handle only touches the PTP registers of the MAC, so it can be kept around after the
+/// [`Ethernet`] driver has been handed over to the network stack.
+pub struct Ptp<T: Instance> {
+    addend: u32,
+    phantom: PhantomData<T>,
+}
+
+impl<T: Instance> Ptp<T> {
+    /// Enable the PTP block, start the PTP clock at zero and timestamp all frames.
+    pub fn new<P: PHY>(_eth: &mut Ethernet<'_, T, P>) -> Self {
+        let hclk = <T as RccPeripheral>::frequency().0;
+
+        // The accumulator overflows at most once every two HCLK cycles, and every overflow
+        // adds `ssinc` nanoseconds to the clock.
+        let ssinc = (2 * NANOS_PER_SECOND as u64).div_ceil(hclk as u64) as u32;
+        assert!(ssinc <= 0xFF);
+        let overflow_freq = NANOS_PER_SECOND as u64 / ssinc as u64;
+        let addend = unwrap!(u32::try_from((overflow_freq << 32) / hclk as u64));
+
+        // Mask the timestamp trigger interrupt, we don't use the target time.
+        regs::mask_interrupt();
+
+        regs::set_control(tscr::TSE | tscr::TSSSR | tscr::TSSARFE | tscr::TSPTPPSV2E);
+        regs::set_subsecond_increment(ssinc);
+
+        let this = Self {
+            addend,
+            phantom: PhantomData,
+        };
+        this.write_addend(addend);
+
+        // Switch to fine correction, the addend is now valid.
+        regs::set_control(regs::control() | tscr::TSFCU);
+
+        this.set_time(Timestamp::default());
+
+        ENABLED.store(true, Ordering::Relaxed);
+
+        this
+    }
+
+    /// Get the current PTP clock time.
+    pub fn get_time(&self) -> Timestamp {
+        // Read the seconds twice, in case the nanoseconds rolled over in between.
+        loop {
+            let seconds = regs::seconds();
+            let nanoseconds = regs::nanoseconds();
+            if seconds == regs::seconds() {
+                return Timestamp::from_raw(seconds, nanoseconds);
+            }
+        }
+    }
+
+    /// Set the PTP clock time.
+    pub fn set_time(&self, time: Timestamp) {
+        assert!(time.nanoseconds < NANOS_PER_SECOND);
+
+        regs::set_update(time.seconds, time.nanoseconds);
+        regs::set_control(regs::control() | tscr::TSSTI);
+        while regs::control() & tscr::TSSTI != 0 {}
+    }
+
+    /// Add (or subtract, if `negative` is true) an offset to the PTP clock time.
+    pub fn adjust_time(&self, offset: Timestamp, negative: bool) {
+        assert!(offset.nanoseconds < NANOS_PER_SECOND);
+
+        if negative {
+            // In digital rollover mode, the nanoseconds must be written as 10^9 - value when subtracting.
+            let nanoseconds = if offset.nanoseconds == 0 {
+                0
+            } else {
+                NANOS_PER_SECOND - offset.nanoseconds
+            };
+            regs::set_update(offset.seconds, nanoseconds | NANOSECONDS_SUB);
+        } else {
+            regs::set_update(offset.seconds, offset.nanoseconds);
+        }
+        regs::set_control(regs::control() | tscr::TSSTU);
+        while regs::control() & tscr::TSSTU != 0 {}
+    }
+
+    /// Adjust the PTP clock rate by `ppb` parts per billion.
+    ///
+    /// The adjustment is relative to the nominal rate computed from HCLK, it does not accumulate.
+    pub fn adjust_frequency(&self, ppb: i32) {
+        let delta = (self.addend as i64 * ppb as i64) / NANOS_PER_SECOND as i64;
+        let addend = unwrap!(u32::try_from(self.addend as i64 + delta));
+        self.write_addend(addend);
+    }
+
+    fn write_addend(&self, addend: u32) {
+        regs::set_addend(addend);
+        regs::set_control(regs::control() | tscr::TSARU);
+        while regs::control() & tscr::TSARU != 0 {}
+    }
+}
+
+impl<T: Instance> Drop for Ptp<T> {
+    fn drop(&mut self) {
+        ENABLED.store(false, Ordering::Relaxed);
+        regs::set_control(0);
+    }
+}
+
+#[cfg(any(eth_v1b, eth_v1c))]
+mod regs {
+    use super::ETH;
+
+    pub fn mask_interrupt() {
+        ETH.ethernet_mac().macimr().modify(|w| w.set_tstim(true));
+    }
+
+    pub fn control() -> u32 {
+        ETH.ethernet_ptp().ptptscr().read().0
+    }
+
+    pub fn set_control(val: u32) {
+        ETH.ethernet_ptp().ptptscr().write(|w| w.0 = val);
+    }
+
+    pub fn set_subsecond_increment(ssinc: u32) {
+        ETH.ethernet_ptp().ptpssir().write(|w| w.0 = ssinc);
+    }
+
+    pub fn set_addend(addend: u32) {
+        ETH.ethernet_ptp().ptptsar().write(|w| w.0 = addend);
+    }
+
+    pub fn seconds() -> u32 {
+        ETH.ethernet_ptp().ptptshr().read().0
+    }
+
+    pub fn nanoseconds() -> u32 {
+        ETH.ethernet_ptp().ptptslr().read().0
+    }
+
+    pub fn set_update(seconds: u32, nanoseconds: u32) {
+        ETH.ethernet_ptp().ptptshur().write(|w| w.0 = seconds);
+        ETH.ethernet_ptp().ptptslur().write(|w| w.0 = nanoseconds);
+    }
+}
+
+#[cfg(eth_v2)]
+mod regs {
+    use super::ETH;
+
+    /// Sub-second increment field position in MACSSIR
+    const MACSSIR_SSINC_SHIFT: u32 = 16;
+
+    pub fn mask_interrupt() {
+        ETH.ethernet_mac().macier().modify(|w| w.set_tsie(false));
+    }
+
+    pub fn control() -> u32 {
+        ETH.ethernet_mac().mactscr().read().0
+    }
+
+    pub fn set_control(val: u32) {
+        ETH.ethernet_mac().mactscr().write(|w| w.0 = val);
+    }
+
+    pub fn set_subsecond_increment(ssinc: u32) {
+        ETH.ethernet_mac()
+            .macssir()
+            .write(|w| w.0 = ssinc << MACSSIR_SSINC_SHIFT);
+    }
+
+    pub fn set_addend(addend: u32) {
+        ETH.ethernet_mac().mactsar().write(|w| w.0 = addend);
+    }
+
+    pub fn seconds() -> u32 {
+        ETH.ethernet_mac().macstsr().read().0
+    }
+
+    pub fn nanoseconds() -> u32 {
+        ETH.ethernet_mac().macstnr().read().0
+    }
+
+    pub fn set_update(seconds: u32, nanoseconds: u32) {
+        ETH.ethernet_mac().macstsur().write(|w| w.0 = seconds);
+        ETH.ethernet_mac().macstnur().write(|w| w.0 = nanoseconds);
+    }
+}
@@ -0,0 +1,104 @@
+//! Self-test of the Ethernet path, for factory test firmware.
+use core::future::poll_fn;
+use core::task::Poll;
+
+use embassy_time::{with_timeout, Duration};
+
+use super::{Ethernet, Instance, PHY, WAKER};
+
+/// Length of the test frames, without the CRC.
+const FRAME_LEN: usize = 128;
+/// Local experimental EtherType, marking the test frames.
+const ETHERTYPE: [u8; 2] = [0x88, 0xB5];
+/// Time for a test frame to come back.
+const TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Self-test error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SelfTestError {
+    /// The test frame wasn't transmitted or received back in time.
+    Timeout {
+        /// Index of the test frame.
+        frame: u32,
+    },
+    /// The test frame was received back with different contents.
+    Mismatch {
+        /// Index of the test frame.
+        frame: u32,
+    },
+}
+
+impl<'d, T: Instance, P: PHY> Ethernet<'d, T, P> {
+    /// Transmit `frames` test frames, and check that each is received back unchanged.
+    ///
+    /// The frames must be looped back, by the MAC with [`set_loopback`](Self::set_loopback), by
+    /// the PHY with [`PhyTestMode::Loopback`](super::PhyTestMode::Loopback), or by an external
+    /// loopback plug. The frames are addressed to the MAC address of the driver, and the other
+    /// received frames are dropped during the test, so it must not run while the driver is used
+    /// by a network stack.
+    pub async fn self_test(&mut self, frames: u32) -> Result<(), SelfTestError> {
+        for frame in 0..frames {
+            with_timeout(TIMEOUT, self.transmit_test_frame(frame))
+                .await
+                .map_err(|_| SelfTestError::Timeout { frame })?;
+            let matches = with_timeout(TIMEOUT, self.receive_test_frame(frame))
+                .await
+                .map_err(|_| SelfTestError::Timeout { frame })?;
+            if !matches {
+                return Err(SelfTestError::Mismatch { frame });
+            }
+        }
+        Ok(())
+    }
+
+    async fn transmit_test_frame(&mut self, frame: u32) {
+        poll_fn(|cx| {
+            WAKER.register(cx.waker());
+            if self.tx.available().is_some() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+
+        // NOTE(unwrap): we waited for a descriptor to be available above.
+        let buf = unwrap!(self.tx.available());
+        fill_test_frame(&mut buf[..FRAME_LEN], &self.mac_addr, frame);
+        self.tx.transmit(FRAME_LEN);
+    }
+
+    /// Wait for the test frame, returning whether it came back unchanged.
+    async fn receive_test_frame(&mut self, frame: u32) -> bool {
+        let mut expected = [0; FRAME_LEN];
+        fill_test_frame(&mut expected, &self.mac_addr, frame);
+
+        poll_fn(|cx| {
+            WAKER.register(cx.waker());
+            while let Some(pkt) = self.rx.available() {
+                // Skip the frames from the line, possibly received along with the test frames.
+                if pkt.len() < FRAME_LEN || pkt[12..14] != ETHERTYPE {
+                    self.rx.pop_packet();
+                    continue;
+                }
+                let matches = pkt[..FRAME_LEN] == expected;
+                self.rx.pop_packet();
+                return Poll::Ready(matches);
+            }
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+/// Fill `buf` with test frame number `frame`, addressed from and to `mac_addr`.
+fn fill_test_frame(buf: &mut [u8], mac_addr: &[u8; 6], frame: u32) {
+    buf[0..6].copy_from_slice(mac_addr);
+    buf[6..12].copy_from_slice(mac_addr);
+    buf[12..14].copy_from_slice(&ETHERTYPE);
+    buf[14..18].copy_from_slice(&frame.to_be_bytes());
+    for (i, b) in buf[18..].iter_mut().enumerate() {
+        *b = (i as u32 ^ frame) as u8;
+    }
+}
@@ -3,8 +3,10 @@
 mod rx_desc;
 mod tx_desc;
 
+use core::future::poll_fn;
 use core::marker::PhantomData;
 use core::sync::atomic::{fence, Ordering};
+use core::task::Poll;
 
 use embassy_hal_internal::{into_ref, PeripheralRef};
 use stm32_metapac::eth::vals::{Apcs, Cr, Dm, DmaomrSr, Fes, Ftf, Ifg, MbProgress, Mw, Pbl, Rsf, St, Tsf};
@@ -19,17 +21,28 @@ use crate::interrupt::InterruptExt;
 use crate::pac::AFIO;
 #[cfg(any(eth_v1b, eth_v1c))]
 use crate::pac::SYSCFG;
-use crate::pac::{ETH, RCC};
+use crate::pac::{ETH, EXTI, RCC};
 use crate::rcc::sealed::RccPeripheral;
 use crate::{interrupt, Peripheral};
 
+/// EXTI line connected to the Ethernet wakeup event.
+const EXTI_WAKEUP_LINE: usize = 19;
+
 /// Interrupt handler.
 pub struct InterruptHandler {}
 
 impl interrupt::typelevel::Handler<interrupt::typelevel::ETH> for InterruptHandler {
     unsafe fn on_interrupt() {
+        count_interrupt();
         WAKER.wake();
 
+        let mac = ETH.ethernet_mac();
+        if mac.macsr().read().pmts() {
+            // Mask the PMT interrupt, `wait_for_wakeup` reads and clears the status.
+            mac.macimr().modify(|w| w.set_pmtim(true));
+            WAKEUP_WAKER.wake();
+        }
+
         // TODO: Check and clear more flags
         let dma = ETH.ethernet_dma();
 
@@ -94,8 +107,8 @@ macro_rules! config_pins {
 
 impl<'d, T: Instance, P: PHY> Ethernet<'d, T, P> {
     /// safety: the returned instance is not leak-safe
-    pub fn new<const TX: usize, const RX: usize>(
-        queue: &'d mut PacketQueue<TX, RX>,
+    pub fn new<const TX: usize, const RX: usize, const TX_SIZE: usize, const RX_SIZE: usize>(
+        queue: impl Into<EthMemory<'d, TX, RX, TX_SIZE, RX_SIZE>>,
         peri: impl Peripheral<P = T> + 'd,
         _irq: impl interrupt::typelevel::Binding<interrupt::typelevel::ETH, InterruptHandler> + 'd,
         ref_clk: impl Peripheral<P = impl RefClkPin<T>> + 'd,
@@ -111,6 +124,7 @@ impl<'d, T: Instance, P: PHY> Ethernet<'d, T, P> {
         mac_addr: [u8; 6],
     ) -> Self {
         into_ref!(peri, ref_clk, mdio, mdc, crs, rx_d0, rx_d1, tx_d0, tx_d1, tx_en);
+        let queue = queue.into().queue;
 
         // Enable the necessary Clocks
         #[cfg(eth_v1a)]
@@ -187,7 +201,9 @@ impl<'d, T: Instance, P: PHY> Ethernet<'d, T, P> {
         });
 
         dma.dmabmr().modify(|w| {
-            w.set_pbl(Pbl::PBL32) // programmable burst length - 32 ?
+            w.set_pbl(Pbl::PBL32); // programmable burst length - 32 ?
+            #[cfg(any(eth_v1b, eth_v1c))]
+            w.set_edfe(true); // enhanced descriptors, needed for PTP timestamps
         });
 
         // TODO MTU size setting not found for v1 ethernet, check if correct
@@ -229,8 +245,8 @@ impl<'d, T: Instance, P: PHY> Ethernet<'d, T, P> {
                 clock_range: clock_range,
             },
             mac_addr,
-            tx: TDesRing::new(&mut queue.tx_desc, &mut queue.tx_buf),
-            rx: RDesRing::new(&mut queue.rx_desc, &mut queue.rx_buf),
+            tx: TDesRing::new(&mut queue.tx_desc, Packets::new(&mut queue.tx_buf)),
+            rx: RDesRing::new(&mut queue.rx_desc, Packets::new(&mut queue.rx_buf)),
         };
 
         fence(Ordering::SeqCst);
@@ -265,6 +281,124 @@ impl<'d, T: Instance, P: PHY> Ethernet<'d, T, P> {
 
         this
     }
+
+    /// Read the Ethernet statistics counters.
+    pub fn stats(&mut self) -> Statistics {
+        let mmc = ETH.ethernet_mmc();
+
+        Statistics {
+            rx_crc_errors: mmc.mmcrfcecr().read().0,
+            rx_alignment_errors: mmc.mmcrfaecr().read().0,
+            tx_single_collisions: mmc.mmctgfsccr().read().0,
+            tx_multiple_collisions: mmc.mmctgfmsccr().read().0,
+            rx_dropped_frames: self.rx.dropped_frames(),
+            rx_ring_overruns: self.rx.overruns(),
+        }
+    }
+
+    /// Enable or disable the internal loopback of the MAC.
+    ///
+    /// In loopback, the transmitted frames are received back by the MAC without going through the
+    /// PHY, and nothing is sent on the line. Frames received from the PHY are ignored.
+    pub fn set_loopback(&mut self, enabled: bool) {
+        ETH.ethernet_mac().maccr().modify(|w| w.set_lm(enabled));
+    }
+
+    /// Snapshot the main MAC and DMA registers into `buf`, for bug reports.
+    #[cfg(feature = "debug-dump")]
+    pub fn debug_dump<'b>(&self, buf: &'b mut [crate::debug_dump::Register]) -> &'b [crate::debug_dump::Register] {
+        let mac = ETH.ethernet_mac();
+        let dma = ETH.ethernet_dma();
+        crate::debug_dump::dump(
+            buf,
+            &[
+                ("MACCR", mac.maccr().read().0),
+                ("MACFFR", mac.macffr().read().0),
+                ("DMABMR", dma.dmabmr().read().0),
+                ("DMAOMR", dma.dmaomr().read().0),
+                ("DMASR", dma.dmasr().read().0),
+                ("DMAIER", dma.dmaier().read().0),
+            ],
+        )
+    }
+
+    /// Put the MAC in power-down mode and arm Wake-on-LAN detection.
+    ///
+    /// Transmission stops and received frames are discarded, except for the wakeup frames selected
+    /// in `config`. The Ethernet wakeup EXTI line is configured as an event, so the core can enter
+    /// Stop mode until [`wait_for_wakeup`](Self::wait_for_wakeup) returns.
+    pub fn enable_wakeup(&mut self, config: WakeupConfig) {
+        let dma = ETH.ethernet_dma();
+        let mac = ETH.ethernet_mac();
+
+        // Disable the TX DMA and the MAC before entering power-down
+        dma.dmaomr().modify(|w| w.set_st(St::STOPPED));
+        mac.maccr().modify(|w| {
+            w.set_re(false);
+            w.set_te(false);
+        });
+
+        // Reading the register clears the stale wakeup flags
+        mac.macpmtcsr().read();
+        mac.macpmtcsr().modify(|w| {
+            w.set_mpe(config.magic_packet);
+            w.set_wfe(config.wakeup_frame);
+            w.set_gu(config.global_unicast);
+            w.set_pd(true);
+        });
+
+        // The receiver must run to detect wakeup frames
+        mac.maccr().modify(|w| w.set_re(true));
+        mac.macimr().modify(|w| w.set_pmtim(false));
+
+        critical_section::with(|_| {
+            EXTI.rtsr(0).modify(|w| w.set_line(EXTI_WAKEUP_LINE, true));
+            EXTI.emr(0).modify(|w| w.set_line(EXTI_WAKEUP_LINE, true));
+        });
+    }
+
+    /// Wait for a wakeup frame armed with [`enable_wakeup`](Self::enable_wakeup).
+    ///
+    /// The MAC leaves power-down mode by itself when the frame is received, transmission is
+    /// restarted before returning.
+    pub async fn wait_for_wakeup(&mut self) -> WakeupSource {
+        let dma = ETH.ethernet_dma();
+        let mac = ETH.ethernet_mac();
+
+        let source = poll_fn(|cx| {
+            WAKEUP_WAKER.register(cx.waker());
+
+            // Reading the register clears the received flags
+            let pmtcsr = mac.macpmtcsr().read();
+            if pmtcsr.mpr() {
+                return Poll::Ready(WakeupSource::MagicPacket);
+            }
+            if pmtcsr.wfr() {
+                return Poll::Ready(WakeupSource::WakeupFrame);
+            }
+
+            // The interrupt handler masks the interrupt when it fires.
+            mac.macimr().modify(|w| w.set_pmtim(false));
+            Poll::Pending
+        })
+        .await;
+
+        critical_section::with(|_| {
+            EXTI.emr(0).modify(|w| w.set_line(EXTI_WAKEUP_LINE, false));
+            EXTI.rtsr(0).modify(|w| w.set_line(EXTI_WAKEUP_LINE, false));
+        });
+
+        mac.macimr().modify(|w| w.set_pmtim(true));
+        mac.macpmtcsr().modify(|w| {
+            w.set_mpe(false);
+            w.set_wfe(false);
+            w.set_gu(false);
+        });
+        mac.maccr().modify(|w| w.set_te(true));
+        dma.dmaomr().modify(|w| w.set_st(St::STARTED));
+
+        source
+    }
 }
 
 /// Ethernet station management interface.
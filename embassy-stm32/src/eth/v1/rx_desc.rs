@@ -3,7 +3,6 @@ use core::sync::atomic::{compiler_fence, fence, Ordering};
 use stm32_metapac::eth::vals::{Rpd, Rps};
 use vcell::VolatileCell;
 
-use crate::eth::RX_BUFFER_SIZE;
 use crate::pac::ETH;
 
 mod rx_consts {
@@ -15,6 +14,8 @@ mod rx_consts {
     pub const RXDESC_0_LS: u32 = 1 << 8;
     /// Error summary
     pub const RXDESC_0_ES: u32 = 1 << 15;
+    /// Timestamp valid (enhanced format only)
+    pub const RXDESC_0_TSV: u32 = 1 << 7;
     /// Frame length
     pub const RXDESC_0_FL_MASK: u32 = 0x3FFF;
     pub const RXDESC_0_FL_SHIFT: usize = 16;
@@ -24,11 +25,18 @@ mod rx_consts {
     pub const RXDESC_1_RCH: u32 = 1 << 14;
     /// End Of Ring
     pub const RXDESC_1_RER: u32 = 1 << 15;
+
+    /// Missed frames counter in DMAMFBOCR
+    pub const DMAMFBOCR_MFC_MASK: u32 = 0xffff;
+    /// Missed frames counter overflow in DMAMFBOCR
+    pub const DMAMFBOCR_OMFC: u32 = 1 << 16;
 }
 
 use rx_consts::*;
 
-use super::Packet;
+use super::Packets;
+#[cfg(any(eth_v1b, eth_v1c))]
+use crate::eth::ptp::{self, Timestamp};
 
 /// Receive Descriptor representation
 ///
@@ -36,12 +44,23 @@ use super::Packet;
 /// * rdes1: allocated buffer length
 /// * rdes2: data buffer address
 /// * rdes3: next descriptor address
+/// * rdes4: extended status (enhanced format only)
+/// * rdes5: reserved (enhanced format only)
+/// * rdes6, rdes7: receive timestamp (enhanced format only)
 #[repr(C)]
 pub(crate) struct RDes {
     rdes0: VolatileCell<u32>,
     rdes1: VolatileCell<u32>,
     rdes2: VolatileCell<u32>,
     rdes3: VolatileCell<u32>,
+    #[cfg(any(eth_v1b, eth_v1c))]
+    rdes4: VolatileCell<u32>,
+    #[cfg(any(eth_v1b, eth_v1c))]
+    rdes5: VolatileCell<u32>,
+    #[cfg(any(eth_v1b, eth_v1c))]
+    rdes6: VolatileCell<u32>,
+    #[cfg(any(eth_v1b, eth_v1c))]
+    rdes7: VolatileCell<u32>,
 }
 
 impl RDes {
@@ -51,6 +70,14 @@ impl RDes {
             rdes1: VolatileCell::new(0),
             rdes2: VolatileCell::new(0),
             rdes3: VolatileCell::new(0),
+            #[cfg(any(eth_v1b, eth_v1c))]
+            rdes4: VolatileCell::new(0),
+            #[cfg(any(eth_v1b, eth_v1c))]
+            rdes5: VolatileCell::new(0),
+            #[cfg(any(eth_v1b, eth_v1c))]
+            rdes6: VolatileCell::new(0),
+            #[cfg(any(eth_v1b, eth_v1c))]
+            rdes7: VolatileCell::new(0),
         }
     }
 
@@ -72,9 +99,8 @@ impl RDes {
 
     /// Configures the reception buffer address and length and passed descriptor ownership to the DMA
     #[inline(always)]
-    fn set_ready(&self, buf: *mut u8) {
-        self.rdes1
-            .set(self.rdes1.get() | (RX_BUFFER_SIZE as u32) & RXDESC_1_RBS_MASK);
+    fn set_ready(&self, buf: *mut u8, len: usize) {
+        self.rdes1.set(self.rdes1.get() | (len as u32) & RXDESC_1_RBS_MASK);
         self.rdes2.set(buf as u32);
 
         // "Preceding reads and writes cannot be moved past subsequent writes."
@@ -105,7 +131,19 @@ impl RDes {
         ((self.rdes0.get() >> RXDESC_0_FL_SHIFT) & RXDESC_0_FL_MASK) as usize
     }
 
-    fn setup(&self, next: Option<&Self>, buf: *mut u8) {
+    /// Receive timestamp written back by the DMA, if any
+    #[cfg(any(eth_v1b, eth_v1c))]
+    #[inline(always)]
+    fn timestamp(&self) -> Option<Timestamp> {
+        // Without timestamping, this bit reports IP header checksum errors instead.
+        if ptp::enabled() && self.rdes0.get() & RXDESC_0_TSV != 0 {
+            Some(Timestamp::from_raw(self.rdes7.get(), self.rdes6.get()))
+        } else {
+            None
+        }
+    }
+
+    fn setup(&self, next: Option<&Self>, buf: *mut u8, len: usize) {
         // Defer this initialization to this function, so we can have `RingEntry` on bss.
         self.rdes1.set(self.rdes1.get() | RXDESC_1_RCH);
 
@@ -117,7 +155,7 @@ impl RDes {
             }
         }
 
-        self.set_ready(buf);
+        self.set_ready(buf, len);
     }
 }
 
@@ -132,17 +170,20 @@ pub enum RunningState {
 /// Rx ring of descriptors and packets
 pub(crate) struct RDesRing<'a> {
     descriptors: &'a mut [RDes],
-    buffers: &'a mut [Packet<RX_BUFFER_SIZE>],
+    buffers: Packets<'a>,
     index: usize,
+    overruns: u32,
+    dropped: u32,
 }
 
 impl<'a> RDesRing<'a> {
-    pub(crate) fn new(descriptors: &'a mut [RDes], buffers: &'a mut [Packet<RX_BUFFER_SIZE>]) -> Self {
+    pub(crate) fn new(descriptors: &'a mut [RDes], mut buffers: Packets<'a>) -> Self {
         assert!(descriptors.len() > 1);
-        assert!(descriptors.len() == buffers.len());
+        assert!(descriptors.len() == buffers.count());
 
+        let len = buffers.buffer_len();
         for (i, entry) in descriptors.iter().enumerate() {
-            entry.setup(descriptors.get(i + 1), buffers[i].0.as_mut_ptr());
+            entry.setup(descriptors.get(i + 1), buffers.get(i).as_mut_ptr(), len);
         }
 
         // Register rx descriptor start
@@ -155,6 +196,8 @@ impl<'a> RDesRing<'a> {
             descriptors,
             buffers,
             index: 0,
+            overruns: 0,
+            dropped: 0,
         }
     }
 
@@ -211,7 +254,13 @@ impl<'a> RDesRing<'a> {
 
         let descriptor = &mut self.descriptors[self.index];
         let len = descriptor.packet_len();
-        return Some(&mut self.buffers[self.index].0[..len]);
+        return Some(&mut self.buffers.get(self.index)[..len]);
+    }
+
+    /// Timestamp of the packet returned by `available`, if any.
+    #[cfg(any(eth_v1b, eth_v1c))]
+    pub(crate) fn timestamp(&self) -> Option<Timestamp> {
+        self.descriptors[self.index].timestamp()
     }
 
     /// Pop the packet previously returned by `available`.
@@ -219,7 +268,15 @@ impl<'a> RDesRing<'a> {
         let descriptor = &mut self.descriptors[self.index];
         assert!(descriptor.available());
 
-        self.descriptors[self.index].set_ready(self.buffers[self.index].0.as_mut_ptr());
+        // The DMA engine fills descriptors in order, so if the one behind us is also owned by
+        // the CPU, the whole ring was full and the DMA engine had nowhere to put new frames.
+        let prev = (self.index + self.descriptors.len() - 1) % self.descriptors.len();
+        if self.descriptors[prev].available() {
+            self.overruns = self.overruns.wrapping_add(1);
+        }
+
+        let len = self.buffers.buffer_len();
+        self.descriptors[self.index].set_ready(self.buffers.get(self.index).as_mut_ptr(), len);
 
         self.demand_poll();
 
@@ -229,4 +286,27 @@ impl<'a> RDesRing<'a> {
             self.index = 0
         }
     }
+
+    /// Size of the receive buffers.
+    pub(crate) fn buffer_len(&self) -> usize {
+        self.buffers.buffer_len()
+    }
+
+    /// Number of times `pop_packet` found the ring completely full.
+    pub(crate) fn overruns(&self) -> u32 {
+        self.overruns
+    }
+
+    /// Number of frames dropped by the DMA engine because no descriptor was available.
+    pub(crate) fn dropped_frames(&mut self) -> u32 {
+        // The hardware counter is cleared on read, so accumulate it in software.
+        let r = ETH.ethernet_dma().dmamfbocr().read().0;
+        let missed = if r & DMAMFBOCR_OMFC != 0 {
+            DMAMFBOCR_MFC_MASK
+        } else {
+            r & DMAMFBOCR_MFC_MASK
+        };
+        self.dropped = self.dropped.wrapping_add(missed);
+        self.dropped
+    }
 }
@@ -2,7 +2,6 @@ use core::sync::atomic::{compiler_fence, fence, Ordering};
 
 use vcell::VolatileCell;
 
-use crate::eth::TX_BUFFER_SIZE;
 use crate::pac::ETH;
 
 /// Transmit and Receive Descriptor fields
@@ -20,6 +19,10 @@ mod tx_consts {
     pub const TXDESC_0_TCH: u32 = 1 << 20;
     // Error status
     pub const TXDESC_0_ES: u32 = 1 << 15;
+    // Transmit timestamp enable
+    pub const TXDESC_0_TTSE: u32 = 1 << 25;
+    // Transmit timestamp status
+    pub const TXDESC_0_TTSS: u32 = 1 << 17;
 
     // Transmit buffer size
     pub const TXDESC_1_TBS_SHIFT: usize = 0;
@@ -27,7 +30,9 @@ mod tx_consts {
 }
 use tx_consts::*;
 
-use super::Packet;
+use super::Packets;
+#[cfg(any(eth_v1b, eth_v1c))]
+use crate::eth::ptp::{self, Timestamp, TxFrameId};
 
 /// Transmit Descriptor representation
 ///
@@ -35,12 +40,22 @@ use super::Packet;
 /// * tdes1: buffer lengths
 /// * tdes2: data buffer address
 /// * tdes3: next descriptor address
+/// * tdes4, tdes5: reserved (enhanced format only)
+/// * tdes6, tdes7: transmit timestamp (enhanced format only)
 #[repr(C)]
 pub(crate) struct TDes {
     tdes0: VolatileCell<u32>,
     tdes1: VolatileCell<u32>,
     tdes2: VolatileCell<u32>,
     tdes3: VolatileCell<u32>,
+    #[cfg(any(eth_v1b, eth_v1c))]
+    tdes4: VolatileCell<u32>,
+    #[cfg(any(eth_v1b, eth_v1c))]
+    tdes5: VolatileCell<u32>,
+    #[cfg(any(eth_v1b, eth_v1c))]
+    tdes6: VolatileCell<u32>,
+    #[cfg(any(eth_v1b, eth_v1c))]
+    tdes7: VolatileCell<u32>,
 }
 
 impl TDes {
@@ -50,6 +65,14 @@ impl TDes {
             tdes1: VolatileCell::new(0),
             tdes2: VolatileCell::new(0),
             tdes3: VolatileCell::new(0),
+            #[cfg(any(eth_v1b, eth_v1c))]
+            tdes4: VolatileCell::new(0),
+            #[cfg(any(eth_v1b, eth_v1c))]
+            tdes5: VolatileCell::new(0),
+            #[cfg(any(eth_v1b, eth_v1c))]
+            tdes6: VolatileCell::new(0),
+            #[cfg(any(eth_v1b, eth_v1c))]
+            tdes7: VolatileCell::new(0),
         }
     }
 
@@ -89,6 +112,23 @@ impl TDes {
         self.tdes0.set(self.tdes0.get() | TXDESC_0_TER);
     }
 
+    /// Request a transmit timestamp for the next frame sent with this descriptor
+    #[cfg(any(eth_v1b, eth_v1c))]
+    fn set_timestamp_enable(&self, enable: bool) {
+        let tdes0 = self.tdes0.get() & !(TXDESC_0_TTSE | TXDESC_0_TTSS);
+        self.tdes0.set(if enable { tdes0 | TXDESC_0_TTSE } else { tdes0 });
+    }
+
+    /// Transmit timestamp written back by the DMA, if any
+    #[cfg(any(eth_v1b, eth_v1c))]
+    fn timestamp(&self) -> Option<Timestamp> {
+        let tdes0 = self.tdes0.get();
+        if tdes0 & (TXDESC_0_OWN | TXDESC_0_TTSS) != TXDESC_0_TTSS {
+            return None;
+        }
+        Some(Timestamp::from_raw(self.tdes7.get(), self.tdes6.get()))
+    }
+
     // set up as a part fo the ring buffer - configures the tdes
     fn setup(&self, next: Option<&Self>) {
         // Defer this initialization to this function, so we can have `RingEntry` on bss.
@@ -105,15 +145,18 @@ impl TDes {
 
 pub(crate) struct TDesRing<'a> {
     descriptors: &'a mut [TDes],
-    buffers: &'a mut [Packet<TX_BUFFER_SIZE>],
+    buffers: Packets<'a>,
     index: usize,
+    /// Number of frames sent, wrapping around.
+    #[cfg(any(eth_v1b, eth_v1c))]
+    seq: u32,
 }
 
 impl<'a> TDesRing<'a> {
     /// Initialise this TDesRing. Assume TDesRing is corrupt
-    pub(crate) fn new(descriptors: &'a mut [TDes], buffers: &'a mut [Packet<TX_BUFFER_SIZE>]) -> Self {
+    pub(crate) fn new(descriptors: &'a mut [TDes], buffers: Packets<'a>) -> Self {
         assert!(descriptors.len() > 0);
-        assert!(descriptors.len() == buffers.len());
+        assert!(descriptors.len() == buffers.count());
 
         for (i, entry) in descriptors.iter().enumerate() {
             entry.setup(descriptors.get(i + 1));
@@ -128,6 +171,8 @@ impl<'a> TDesRing<'a> {
             descriptors,
             buffers,
             index: 0,
+            #[cfg(any(eth_v1b, eth_v1c))]
+            seq: 0,
         }
     }
 
@@ -135,11 +180,36 @@ impl<'a> TDesRing<'a> {
         self.descriptors.len()
     }
 
+    /// Size of the transmit buffers.
+    pub(crate) fn buffer_len(&self) -> usize {
+        self.buffers.buffer_len()
+    }
+
+    /// Identifier of the next frame to be sent.
+    #[cfg(any(eth_v1b, eth_v1c))]
+    pub(crate) fn next_frame_id(&self) -> TxFrameId {
+        TxFrameId {
+            index: self.index,
+            seq: self.seq,
+        }
+    }
+
+    /// Timestamp of the frame `id`, if it was sent and its descriptor wasn't reused since.
+    #[cfg(any(eth_v1b, eth_v1c))]
+    pub(crate) fn timestamp(&self, id: TxFrameId) -> Option<Timestamp> {
+        // 0 for the last frame sent, wraps around if `id` wasn't sent yet.
+        let age = self.seq.wrapping_sub(id.seq).wrapping_sub(1);
+        if age >= self.descriptors.len() as u32 {
+            return None;
+        }
+        self.descriptors[id.index].timestamp()
+    }
+
     /// Return the next available packet buffer for transmitting, or None
     pub(crate) fn available(&mut self) -> Option<&mut [u8]> {
         let descriptor = &mut self.descriptors[self.index];
         if descriptor.available() {
-            Some(&mut self.buffers[self.index].0)
+            Some(self.buffers.get(self.index))
         } else {
             None
         }
@@ -150,8 +220,10 @@ impl<'a> TDesRing<'a> {
         let descriptor = &mut self.descriptors[self.index];
         assert!(descriptor.available());
 
-        descriptor.set_buffer1(self.buffers[self.index].0.as_ptr());
+        descriptor.set_buffer1(self.buffers.get(self.index).as_ptr());
         descriptor.set_buffer1_len(len);
+        #[cfg(any(eth_v1b, eth_v1c))]
+        descriptor.set_timestamp_enable(ptp::enabled());
 
         descriptor.set_owned();
 
@@ -160,6 +232,11 @@ impl<'a> TDesRing<'a> {
         // "Preceding reads and writes cannot be moved past subsequent writes."
         fence(Ordering::Release);
 
+        #[cfg(any(eth_v1b, eth_v1c))]
+        {
+            self.seq = self.seq.wrapping_add(1);
+        }
+
         // Move the index to the next descriptor
         self.index += 1;
         if self.index == self.descriptors.len() {
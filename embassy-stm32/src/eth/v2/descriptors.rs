@@ -2,7 +2,8 @@ use core::sync::atomic::{fence, Ordering};
 
 use vcell::VolatileCell;
 
-use crate::eth::{Packet, RX_BUFFER_SIZE, TX_BUFFER_SIZE};
+use crate::eth::ptp::{self, Timestamp, TxFrameId};
+use crate::eth::Packets;
 use crate::pac::ETH;
 
 /// Transmit and Receive Descriptor fields
@@ -16,12 +17,20 @@ mod emac_consts {
     pub const EMAC_DES0_BUF1AP: u32 = 0xFFFF_FFFF;
 
     pub const EMAC_TDES2_IOC: u32 = 0x8000_0000;
+    pub const EMAC_TDES2_TTSE: u32 = 0x4000_0000;
     pub const EMAC_TDES2_B1L: u32 = 0x0000_3FFF;
 
+    pub const EMAC_TDES3_TTSS: u32 = 0x0002_0000;
+
+    pub const EMAC_RDES1_TSA: u32 = 0x0000_4000;
+
     pub const EMAC_RDES3_IOC: u32 = 0x4000_0000;
     pub const EMAC_RDES3_PL: u32 = 0x0000_7FFF;
     pub const EMAC_RDES3_BUF1V: u32 = 0x0100_0000;
     pub const EMAC_RDES3_PKTLEN: u32 = 0x0000_7FFF;
+
+    pub const EMAC_DMACMFCR_MFC: u32 = 0x0000_07FF;
+    pub const EMAC_DMACMFCR_MFCO: u32 = 0x0000_8000;
 }
 use emac_consts::*;
 
@@ -53,19 +62,30 @@ impl TDes {
     fn available(&self) -> bool {
         self.tdes3.get() & EMAC_DES3_OWN == 0
     }
+
+    /// Transmit timestamp written back by the DMA, if any
+    fn timestamp(&self) -> Option<Timestamp> {
+        let tdes3 = self.tdes3.get();
+        if tdes3 & (EMAC_DES3_OWN | EMAC_DES3_CTXT | EMAC_TDES3_TTSS) != EMAC_TDES3_TTSS {
+            return None;
+        }
+        Some(Timestamp::from_raw(self.tdes1.get(), self.tdes0.get()))
+    }
 }
 
 pub(crate) struct TDesRing<'a> {
     descriptors: &'a mut [TDes],
-    buffers: &'a mut [Packet<TX_BUFFER_SIZE>],
+    buffers: Packets<'a>,
     index: usize,
+    /// Number of frames sent, wrapping around.
+    seq: u32,
 }
 
 impl<'a> TDesRing<'a> {
     /// Initialise this TDesRing. Assume TDesRing is corrupt.
-    pub fn new(descriptors: &'a mut [TDes], buffers: &'a mut [Packet<TX_BUFFER_SIZE>]) -> Self {
+    pub fn new(descriptors: &'a mut [TDes], buffers: Packets<'a>) -> Self {
         assert!(descriptors.len() > 0);
-        assert!(descriptors.len() == buffers.len());
+        assert!(descriptors.len() == buffers.count());
 
         for td in descriptors.iter_mut() {
             *td = TDes::new();
@@ -82,6 +102,7 @@ impl<'a> TDesRing<'a> {
             descriptors,
             buffers,
             index: 0,
+            seq: 0,
         }
     }
 
@@ -89,11 +110,34 @@ impl<'a> TDesRing<'a> {
         self.descriptors.len()
     }
 
+    /// Size of the transmit buffers.
+    pub(crate) fn buffer_len(&self) -> usize {
+        self.buffers.buffer_len()
+    }
+
+    /// Identifier of the next frame to be sent.
+    pub(crate) fn next_frame_id(&self) -> TxFrameId {
+        TxFrameId {
+            index: self.index,
+            seq: self.seq,
+        }
+    }
+
+    /// Timestamp of the frame `id`, if it was sent and its descriptor wasn't reused since.
+    pub(crate) fn timestamp(&self, id: TxFrameId) -> Option<Timestamp> {
+        // 0 for the last frame sent, wraps around if `id` wasn't sent yet.
+        let age = self.seq.wrapping_sub(id.seq).wrapping_sub(1);
+        if age >= self.descriptors.len() as u32 {
+            return None;
+        }
+        self.descriptors[id.index].timestamp()
+    }
+
     /// Return the next available packet buffer for transmitting, or None
     pub(crate) fn available(&mut self) -> Option<&mut [u8]> {
         let d = &mut self.descriptors[self.index];
         if d.available() {
-            Some(&mut self.buffers[self.index].0)
+            Some(self.buffers.get(self.index))
         } else {
             None
         }
@@ -106,8 +150,9 @@ impl<'a> TDesRing<'a> {
         assert!(len as u32 <= EMAC_TDES2_B1L);
 
         // Read format
-        td.tdes0.set(self.buffers[self.index].0.as_ptr() as u32);
-        td.tdes2.set(len as u32 & EMAC_TDES2_B1L | EMAC_TDES2_IOC);
+        td.tdes0.set(self.buffers.get(self.index).as_ptr() as u32);
+        let ttse = if ptp::enabled() { EMAC_TDES2_TTSE } else { 0 };
+        td.tdes2.set(len as u32 & EMAC_TDES2_B1L | EMAC_TDES2_IOC | ttse);
 
         // FD: Contains first buffer of packet
         // LD: Contains last buffer of packet
@@ -123,6 +168,7 @@ impl<'a> TDesRing<'a> {
         // See issue #2129
         ETH.ethernet_dma().dmactx_dtpr().write(|w| w.0 = &td as *const _ as u32);
 
+        self.seq = self.seq.wrapping_add(1);
         self.index = (self.index + 1) % self.descriptors.len();
     }
 }
@@ -178,18 +224,20 @@ impl RDes {
 /// Rx ring of descriptors and packets
 pub(crate) struct RDesRing<'a> {
     descriptors: &'a mut [RDes],
-    buffers: &'a mut [Packet<RX_BUFFER_SIZE>],
+    buffers: Packets<'a>,
     index: usize,
+    overruns: u32,
+    dropped: u32,
 }
 
 impl<'a> RDesRing<'a> {
-    pub(crate) fn new(descriptors: &'a mut [RDes], buffers: &'a mut [Packet<RX_BUFFER_SIZE>]) -> Self {
+    pub(crate) fn new(descriptors: &'a mut [RDes], mut buffers: Packets<'a>) -> Self {
         assert!(descriptors.len() > 1);
-        assert!(descriptors.len() == buffers.len());
+        assert!(descriptors.len() == buffers.count());
 
         for (i, desc) in descriptors.iter_mut().enumerate() {
             *desc = RDes::new();
-            desc.set_ready(buffers[i].0.as_mut_ptr());
+            desc.set_ready(buffers.get(i).as_mut_ptr());
         }
 
         let dma = ETH.ethernet_dma();
@@ -201,6 +249,8 @@ impl<'a> RDesRing<'a> {
             descriptors,
             buffers,
             index: 0,
+            overruns: 0,
+            dropped: 0,
         }
     }
 
@@ -218,6 +268,13 @@ impl<'a> RDesRing<'a> {
                 return None;
             }
 
+            // Context descriptors carry the timestamp of the previous packet, which was
+            // already collected when that packet was returned. Skip them.
+            if descriptor.rdes3.get() & EMAC_DES3_CTXT != 0 {
+                self.pop_packet();
+                continue;
+            }
+
             // If packet is invalid, pop it and try again.
             if !descriptor.valid() {
                 warn!("invalid packet: {:08x}", descriptor.rdes0.get());
@@ -228,17 +285,47 @@ impl<'a> RDesRing<'a> {
             break;
         }
 
+        // If the packet was timestamped, the timestamp is in the following context descriptor.
+        // Wait for the DMA to write it back before handing out the packet.
+        if self.descriptors[self.index].rdes1.get() & EMAC_RDES1_TSA != 0 {
+            let next = &self.descriptors[(self.index + 1) % self.descriptors.len()];
+            if !next.available() {
+                return None;
+            }
+        }
+
         let descriptor = &mut self.descriptors[self.index];
         let len = (descriptor.rdes3.get() & EMAC_RDES3_PKTLEN) as usize;
-        return Some(&mut self.buffers[self.index].0[..len]);
+        return Some(&mut self.buffers.get(self.index)[..len]);
+    }
+
+    /// Timestamp of the packet returned by `available`, if any.
+    pub(crate) fn timestamp(&self) -> Option<Timestamp> {
+        if !ptp::enabled() || self.descriptors[self.index].rdes1.get() & EMAC_RDES1_TSA == 0 {
+            return None;
+        }
+        let next = &self.descriptors[(self.index + 1) % self.descriptors.len()];
+        if next.available() && next.rdes3.get() & EMAC_DES3_CTXT != 0 {
+            Some(Timestamp::from_raw(next.rdes1.get(), next.rdes0.get()))
+        } else {
+            None
+        }
     }
 
     /// Pop the packet previously returned by `available`.
     pub(crate) fn pop_packet(&mut self) {
+        assert!(self.descriptors[self.index].available());
+
+        // The DMA engine fills descriptors in order, so if the one behind us is also owned by
+        // the CPU, the whole ring was full and the DMA engine had nowhere to put new frames.
+        let prev = (self.index + self.descriptors.len() - 1) % self.descriptors.len();
+        if self.descriptors[prev].available() {
+            self.overruns = self.overruns.wrapping_add(1);
+        }
+
         let rd = &mut self.descriptors[self.index];
-        assert!(rd.available());
 
-        rd.set_ready(self.buffers[self.index].0.as_mut_ptr());
+        rd.set_ready(self.buffers.get(self.index).as_mut_ptr());
 
         // "Preceding reads and writes cannot be moved past subsequent writes."
         fence(Ordering::Release);
@@ -250,4 +337,27 @@ impl<'a> RDesRing<'a> {
         // Increment index.
         self.index = (self.index + 1) % self.descriptors.len();
     }
+
+    /// Size of the receive buffers.
+    pub(crate) fn buffer_len(&self) -> usize {
+        self.buffers.buffer_len()
+    }
+
+    /// Number of times `pop_packet` found the ring completely full.
+    pub(crate) fn overruns(&self) -> u32 {
+        self.overruns
+    }
+
+    /// Number of frames dropped by the DMA engine because no descriptor was available.
+    pub(crate) fn dropped_frames(&mut self) -> u32 {
+        // The hardware counter is cleared on read, so accumulate it in software.
+        let r = ETH.ethernet_dma().dmacmfcr().read().0;
+        let missed = if r & EMAC_DMACMFCR_MFCO != 0 {
+            EMAC_DMACMFCR_MFC
+        } else {
+            r & EMAC_DMACMFCR_MFC
+        };
+        self.dropped = self.dropped.wrapping_add(missed);
+        self.dropped
+    }
 }
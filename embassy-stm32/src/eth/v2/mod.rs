@@ -1,7 +1,9 @@
 mod descriptors;
 
+use core::future::poll_fn;
 use core::marker::PhantomData;
 use core::sync::atomic::{fence, Ordering};
+use core::task::Poll;
 
 use embassy_hal_internal::{into_ref, PeripheralRef};
 
@@ -19,8 +21,16 @@ pub struct InterruptHandler {}
 
 impl interrupt::typelevel::Handler<interrupt::typelevel::ETH> for InterruptHandler {
     unsafe fn on_interrupt() {
+        count_interrupt();
         WAKER.wake();
 
+        let mac = ETH.ethernet_mac();
+        if mac.macisr().read().pmtis() {
+            // Disable the PMT interrupt, `wait_for_wakeup` reads and clears the status.
+            mac.macier().modify(|w| w.set_pmtie(false));
+            WAKEUP_WAKER.wake();
+        }
+
         // TODO: Check and clear more flags
         let dma = ETH.ethernet_dma();
 
@@ -65,8 +75,8 @@ macro_rules! config_pins {
 
 impl<'d, T: Instance, P: PHY> Ethernet<'d, T, P> {
     /// Create a new RMII ethernet driver using 9 pins.
-    pub fn new<const TX: usize, const RX: usize>(
-        queue: &'d mut PacketQueue<TX, RX>,
+    pub fn new<const TX: usize, const RX: usize, const TX_SIZE: usize, const RX_SIZE: usize>(
+        queue: impl Into<EthMemory<'d, TX, RX, TX_SIZE, RX_SIZE>>,
         peri: impl Peripheral<P = T> + 'd,
         irq: impl interrupt::typelevel::Binding<interrupt::typelevel::ETH, InterruptHandler> + 'd,
         ref_clk: impl Peripheral<P = impl RefClkPin<T>> + 'd,
@@ -123,12 +133,12 @@ impl<'d, T: Instance, P: PHY> Ethernet<'d, T, P> {
             tx_en.map_into(),
         ]);
 
-        Self::new_inner(queue, peri, irq, pins, phy, mac_addr)
+        Self::new_inner(queue.into(), peri, irq, pins, phy, mac_addr)
     }
 
     /// Create a new MII ethernet driver using 14 pins.
-    pub fn new_mii<const TX: usize, const RX: usize>(
-        queue: &'d mut PacketQueue<TX, RX>,
+    pub fn new_mii<const TX: usize, const RX: usize, const TX_SIZE: usize, const RX_SIZE: usize>(
+        queue: impl Into<EthMemory<'d, TX, RX, TX_SIZE, RX_SIZE>>,
         peri: impl Peripheral<P = T> + 'd,
         irq: impl interrupt::typelevel::Binding<interrupt::typelevel::ETH, InterruptHandler> + 'd,
         rx_clk: impl Peripheral<P = impl RXClkPin<T>> + 'd,
@@ -196,17 +206,18 @@ impl<'d, T: Instance, P: PHY> Ethernet<'d, T, P> {
             tx_en.map_into(),
         ]);
 
-        Self::new_inner(queue, peri, irq, pins, phy, mac_addr)
+        Self::new_inner(queue.into(), peri, irq, pins, phy, mac_addr)
     }
 
-    fn new_inner<const TX: usize, const RX: usize>(
-        queue: &'d mut PacketQueue<TX, RX>,
+    fn new_inner<const TX: usize, const RX: usize, const TX_SIZE: usize, const RX_SIZE: usize>(
+        queue: EthMemory<'d, TX, RX, TX_SIZE, RX_SIZE>,
         peri: impl Peripheral<P = T> + 'd,
         _irq: impl interrupt::typelevel::Binding<interrupt::typelevel::ETH, InterruptHandler> + 'd,
         pins: Pins<'d>,
         phy: P,
         mac_addr: [u8; 6],
     ) -> Self {
+        let queue = queue.queue;
         let dma = ETH.ethernet_dma();
         let mac = ETH.ethernet_mac();
         let mtl = ETH.ethernet_mtl();
@@ -262,7 +273,7 @@ impl<'d, T: Instance, P: PHY> Ethernet<'d, T, P> {
         dma.dmactx_cr().modify(|w| w.set_txpbl(1)); // 32 ?
         dma.dmacrx_cr().modify(|w| {
             w.set_rxpbl(1); // 32 ?
-            w.set_rbsz(RX_BUFFER_SIZE as u16);
+            w.set_rbsz(RX_SIZE as u16);
         });
 
         let hclk = <T as RccPeripheral>::frequency();
@@ -283,8 +294,8 @@ impl<'d, T: Instance, P: PHY> Ethernet<'d, T, P> {
 
         let mut this = Self {
             _peri: peri.into_ref(),
-            tx: TDesRing::new(&mut queue.tx_desc, &mut queue.tx_buf),
-            rx: RDesRing::new(&mut queue.rx_desc, &mut queue.rx_buf),
+            tx: TDesRing::new(&mut queue.tx_desc, Packets::new(&mut queue.tx_buf)),
+            rx: RDesRing::new(&mut queue.rx_desc, Packets::new(&mut queue.rx_buf)),
             pins,
             phy,
             station_management: EthernetStationManagement {
@@ -324,6 +335,123 @@ impl<'d, T: Instance, P: PHY> Ethernet<'d, T, P> {
 
         this
     }
+
+    /// Read the Ethernet statistics counters.
+    pub fn stats(&mut self) -> Statistics {
+        let mac = ETH.ethernet_mac();
+
+        Statistics {
+            rx_crc_errors: mac.rx_crc_error_packets().read().0,
+            rx_alignment_errors: mac.rx_alignment_error_packets().read().0,
+            tx_single_collisions: mac.tx_single_collision_good_packets().read().0,
+            tx_multiple_collisions: mac.tx_multiple_collision_good_packets().read().0,
+            rx_dropped_frames: self.rx.dropped_frames(),
+            rx_ring_overruns: self.rx.overruns(),
+        }
+    }
+
+    /// Enable or disable the internal loopback of the MAC.
+    ///
+    /// In loopback, the transmitted frames are received back by the MAC without going through the
+    /// PHY, and nothing is sent on the line. Frames received from the PHY are ignored.
+    pub fn set_loopback(&mut self, enabled: bool) {
+        ETH.ethernet_mac().maccr().modify(|w| w.set_lm(enabled));
+    }
+
+    /// Snapshot the main MAC and DMA registers into `buf`, for bug reports.
+    #[cfg(feature = "debug-dump")]
+    pub fn debug_dump<'b>(&self, buf: &'b mut [crate::debug_dump::Register]) -> &'b [crate::debug_dump::Register] {
+        let mac = ETH.ethernet_mac();
+        let mtl = ETH.ethernet_mtl();
+        let dma = ETH.ethernet_dma();
+        crate::debug_dump::dump(
+            buf,
+            &[
+                ("MACCR", mac.maccr().read().0),
+                ("MTLRX_QOMR", mtl.mtlrx_qomr().read().0),
+                ("MTLTX_QOMR", mtl.mtltx_qomr().read().0),
+                ("DMAMR", dma.dmamr().read().0),
+                ("DMACSR", dma.dmacsr().read().0),
+                ("DMACTX_CR", dma.dmactx_cr().read().0),
+                ("DMACRX_CR", dma.dmacrx_cr().read().0),
+                ("DMACIER", dma.dmacier().read().0),
+            ],
+        )
+    }
+
+    /// Put the MAC in power-down mode and arm Wake-on-LAN detection.
+    ///
+    /// Transmission stops and received frames are discarded, except for the wakeup frames selected
+    /// in `config`. The MAC runs from the kernel clock of the D2 domain, so the core can sleep but
+    /// must not enter Stop mode until [`wait_for_wakeup`](Self::wait_for_wakeup) returns.
+    pub fn enable_wakeup(&mut self, config: WakeupConfig) {
+        let dma = ETH.ethernet_dma();
+        let mac = ETH.ethernet_mac();
+        let mtl = ETH.ethernet_mtl();
+
+        // Disable the TX DMA and wait for any previous transmissions to be completed
+        dma.dmactx_cr().modify(|w| w.set_st(false));
+        while {
+            let txqueue = mtl.mtltx_qdr().read();
+            txqueue.trcsts() == 0b01 || txqueue.txqsts()
+        } {}
+
+        mac.maccr().modify(|w| {
+            w.set_re(false);
+            w.set_te(false);
+        });
+
+        // Reading the register clears the stale wakeup flags
+        mac.macpcsr().read();
+        mac.macpcsr().modify(|w| {
+            w.set_mgkpkten(config.magic_packet);
+            w.set_rwkpkten(config.wakeup_frame);
+            w.set_glblucast(config.global_unicast);
+            w.set_pwrdwn(true);
+        });
+
+        // The receiver must run to detect wakeup frames
+        mac.maccr().modify(|w| w.set_re(true));
+        mac.macier().modify(|w| w.set_pmtie(true));
+    }
+
+    /// Wait for a wakeup frame armed with [`enable_wakeup`](Self::enable_wakeup).
+    ///
+    /// The MAC leaves power-down mode by itself when the frame is received, transmission is
+    /// restarted before returning.
+    pub async fn wait_for_wakeup(&mut self) -> WakeupSource {
+        let dma = ETH.ethernet_dma();
+        let mac = ETH.ethernet_mac();
+
+        let source = poll_fn(|cx| {
+            WAKEUP_WAKER.register(cx.waker());
+
+            // Reading the register clears the received flags
+            let pcsr = mac.macpcsr().read();
+            if pcsr.mgkprcvd() {
+                return Poll::Ready(WakeupSource::MagicPacket);
+            }
+            if pcsr.rwkprcvd() {
+                return Poll::Ready(WakeupSource::WakeupFrame);
+            }
+
+            // The interrupt handler disables the interrupt when it fires.
+            mac.macier().modify(|w| w.set_pmtie(true));
+            Poll::Pending
+        })
+        .await;
+
+        mac.macier().modify(|w| w.set_pmtie(false));
+        mac.macpcsr().modify(|w| {
+            w.set_mgkpkten(false);
+            w.set_rwkpkten(false);
+            w.set_glblucast(false);
+        });
+        mac.maccr().modify(|w| w.set_te(true));
+        dma.dmactx_cr().modify(|w| w.set_st(true));
+
+        source
+    }
 }
 
 /// Ethernet SMI driver.
@@ -1,12 +1,22 @@
 //! External Interrupts (EXTI)
+#[cfg(feature = "time")]
+use core::cell::RefCell;
 use core::convert::Infallible;
+#[cfg(feature = "time")]
+use core::future::poll_fn;
 use core::future::Future;
 use core::marker::PhantomData;
 use core::pin::Pin;
 use core::task::{Context, Poll};
 
 use embassy_hal_internal::{impl_peripheral, into_ref};
+#[cfg(feature = "time")]
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+#[cfg(feature = "time")]
+use embassy_sync::blocking_mutex::Mutex;
 use embassy_sync::waitqueue::AtomicWaker;
+#[cfg(feature = "time")]
+use embassy_time::{Duration, Instant};
 
 use crate::gpio::{AnyPin, Input, Level, Pin as GpioPin, Pull};
 use crate::pac::exti::regs::Lines;
@@ -52,11 +62,17 @@ unsafe fn on_irq() {
     // We don't handle or change any EXTI lines above 16.
     let bits = bits & 0x0000FFFF;
 
+    // The lines of the edge loggers stay unmasked, only the waiting tasks are woken.
+    #[cfg(feature = "time")]
+    let waiting = bits & !log_edges(bits);
+    #[cfg(not(feature = "time"))]
+    let waiting = bits;
+
     // Mask all the channels that fired.
-    cpu_regs().imr(0).modify(|w| w.0 &= !bits);
+    cpu_regs().imr(0).modify(|w| w.0 &= !waiting);
 
     // Wake the tasks
-    for pin in BitIter(bits) {
+    for pin in BitIter(waiting) {
         EXTI_WAKERS[pin as usize].wake();
     }
 
@@ -86,6 +102,18 @@ impl Iterator for BitIter {
     }
 }
 
+/// Edge triggering an EXTI line.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Edge {
+    /// Low to high transition.
+    Rising,
+    /// High to low transition.
+    Falling,
+    /// Both transitions.
+    Any,
+}
+
 /// EXTI input driver.
 ///
 /// This driver augments a GPIO `Input` with EXTI functionality. EXTI is not
@@ -131,45 +159,68 @@ impl<'d> ExtiInput<'d> {
         self.pin.get_level()
     }
 
-    /// Asynchronously wait until the pin is high.
+    /// Asynchronously wait until the pin is at `level`.
     ///
-    /// This returns immediately if the pin is already high.
-    pub async fn wait_for_high(&mut self) {
-        let fut = ExtiInputFuture::new(self.pin.pin.pin.pin(), self.pin.pin.pin.port(), true, false);
-        if self.is_high() {
+    /// This returns immediately if the pin is already at `level`. The EXTI line is armed before
+    /// the level is checked, so a transition happening in between is not missed.
+    pub async fn wait_for_level(&mut self, level: Level) {
+        let edge = match level {
+            Level::High => Edge::Rising,
+            Level::Low => Edge::Falling,
+        };
+        let fut = self.edge_future(edge);
+        if self.get_level() == level {
             return;
         }
         fut.await
     }
 
+    /// Asynchronously wait until the pin is high.
+    ///
+    /// This returns immediately if the pin is already high.
+    pub async fn wait_for_high(&mut self) {
+        self.wait_for_level(Level::High).await
+    }
+
     /// Asynchronously wait until the pin is low.
     ///
     /// This returns immediately if the pin is already low.
     pub async fn wait_for_low(&mut self) {
-        let fut = ExtiInputFuture::new(self.pin.pin.pin.pin(), self.pin.pin.pin.port(), false, true);
-        if self.is_low() {
-            return;
-        }
-        fut.await
+        self.wait_for_level(Level::Low).await
+    }
+
+    /// Asynchronously wait until the pin sees an `edge`.
+    ///
+    /// The edge is selected for this wait only, each wait can select a different one. Edges
+    /// before the call are not seen: if the pin is already high, a rising edge is only seen once
+    /// it went low then back high.
+    pub async fn wait_for_edge(&mut self, edge: Edge) {
+        self.edge_future(edge).await
     }
 
     /// Asynchronously wait until the pin sees a rising edge.
     ///
     /// If the pin is already high, it will wait for it to go low then back high.
     pub async fn wait_for_rising_edge(&mut self) {
-        ExtiInputFuture::new(self.pin.pin.pin.pin(), self.pin.pin.pin.port(), true, false).await
+        self.wait_for_edge(Edge::Rising).await
     }
 
     /// Asynchronously wait until the pin sees a falling edge.
     ///
     /// If the pin is already low, it will wait for it to go high then back low.
     pub async fn wait_for_falling_edge(&mut self) {
-        ExtiInputFuture::new(self.pin.pin.pin.pin(), self.pin.pin.pin.port(), false, true).await
+        self.wait_for_edge(Edge::Falling).await
     }
 
     /// Asynchronously wait until the pin sees any edge (either rising or falling).
     pub async fn wait_for_any_edge(&mut self) {
-        ExtiInputFuture::new(self.pin.pin.pin.pin(), self.pin.pin.pin.port(), true, true).await
+        self.wait_for_edge(Edge::Any).await
+    }
+
+    fn edge_future(&self, edge: Edge) -> ExtiInputFuture<'_> {
+        let rising = matches!(edge, Edge::Rising | Edge::Any);
+        let falling = matches!(edge, Edge::Falling | Edge::Any);
+        ExtiInputFuture::new(self.pin.pin.pin.pin(), self.pin.pin.pin.port(), rising, falling)
     }
 }
 
@@ -234,23 +285,7 @@ struct ExtiInputFuture<'a> {
 
 impl<'a> ExtiInputFuture<'a> {
     fn new(pin: u8, port: u8, rising: bool, falling: bool) -> Self {
-        critical_section::with(|_| {
-            let pin = pin as usize;
-            exticr_regs().exticr(pin / 4).modify(|w| w.set_exti(pin % 4, port));
-            EXTI.rtsr(0).modify(|w| w.set_line(pin, rising));
-            EXTI.ftsr(0).modify(|w| w.set_line(pin, falling));
-
-            // clear pending bit
-            #[cfg(not(any(exti_c0, exti_g0, exti_l5, exti_u5, exti_h5, exti_h50)))]
-            EXTI.pr(0).write(|w| w.set_line(pin, true));
-            #[cfg(any(exti_c0, exti_g0, exti_l5, exti_u5, exti_h5, exti_h50))]
-            {
-                EXTI.rpr(0).write(|w| w.set_line(pin, true));
-                EXTI.fpr(0).write(|w| w.set_line(pin, true));
-            }
-
-            cpu_regs().imr(0).modify(|w| w.set_line(pin, true));
-        });
+        arm(pin, port, rising, falling);
 
         Self {
             pin,
@@ -261,13 +296,39 @@ impl<'a> ExtiInputFuture<'a> {
 
 impl<'a> Drop for ExtiInputFuture<'a> {
     fn drop(&mut self) {
-        critical_section::with(|_| {
-            let pin = self.pin as _;
-            cpu_regs().imr(0).modify(|w| w.set_line(pin, false));
-        });
+        disarm(self.pin);
     }
 }
 
+/// Route the EXTI line `pin` to `port`, and unmask it for the selected edges.
+fn arm(pin: u8, port: u8, rising: bool, falling: bool) {
+    critical_section::with(|_| {
+        let pin = pin as usize;
+        exticr_regs().exticr(pin / 4).modify(|w| w.set_exti(pin % 4, port));
+        EXTI.rtsr(0).modify(|w| w.set_line(pin, rising));
+        EXTI.ftsr(0).modify(|w| w.set_line(pin, falling));
+
+        // clear pending bit
+        #[cfg(not(any(exti_c0, exti_g0, exti_l5, exti_u5, exti_h5, exti_h50)))]
+        EXTI.pr(0).write(|w| w.set_line(pin, true));
+        #[cfg(any(exti_c0, exti_g0, exti_l5, exti_u5, exti_h5, exti_h50))]
+        {
+            EXTI.rpr(0).write(|w| w.set_line(pin, true));
+            EXTI.fpr(0).write(|w| w.set_line(pin, true));
+        }
+
+        cpu_regs().imr(0).modify(|w| w.set_line(pin, true));
+    });
+}
+
+/// Mask the EXTI line `pin`.
+fn disarm(pin: u8) {
+    critical_section::with(|_| {
+        let pin = pin as _;
+        cpu_regs().imr(0).modify(|w| w.set_line(pin, false));
+    });
+}
+
 impl<'a> Future for ExtiInputFuture<'a> {
     type Output = ();
 
@@ -283,6 +344,246 @@ impl<'a> Future for ExtiInputFuture<'a> {
     }
 }
 
+/// Edge of a pin, timestamped by an [`EdgeLogger`].
+#[cfg(feature = "time")]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct EdgeEvent {
+    /// Level of the pin after the edge.
+    pub level: Level,
+    /// Time of the edge, taken in the interrupt handler.
+    pub timestamp: Instant,
+}
+
+/// Queue of the edges timestamped by an [`EdgeLogger`], of up to `N` edges.
+///
+/// The queue is filled by the EXTI interrupt handler, so it is a `static`.
+#[cfg(feature = "time")]
+pub struct EdgeQueue<const N: usize> {
+    state: Mutex<CriticalSectionRawMutex, RefCell<EdgeRing<N>>>,
+    waker: AtomicWaker,
+}
+
+#[cfg(feature = "time")]
+struct EdgeRing<const N: usize> {
+    events: [EdgeEvent; N],
+    start: usize,
+    len: usize,
+    /// Edges dropped as the queue was full.
+    lost: u32,
+    debounce: Duration,
+    last: Option<Instant>,
+}
+
+/// Queue filled by the EXTI interrupt handler.
+#[cfg(feature = "time")]
+trait EdgeSink: Sync {
+    fn push(&self, event: EdgeEvent);
+}
+
+/// Queue and port of the edge logger of each EXTI line.
+#[cfg(feature = "time")]
+static EDGE_SINKS: Mutex<CriticalSectionRawMutex, RefCell<[Option<(&'static dyn EdgeSink, u8)>; EXTI_COUNT]>> =
+    Mutex::new(RefCell::new([None; EXTI_COUNT]));
+
+/// Timestamp the edges of the lines in `bits` having an edge logger, returning these lines.
+#[cfg(feature = "time")]
+fn log_edges(bits: u32) -> u32 {
+    let timestamp = Instant::now();
+    EDGE_SINKS.lock(|sinks| {
+        let sinks = sinks.borrow();
+        let mut logged = 0;
+        for pin in BitIter(bits) {
+            if let Some((sink, port)) = sinks[pin as usize] {
+                let idr = pac::GPIO(port as _).idr().read().idr(pin as _);
+                let level = (idr == pac::gpio::vals::Idr::HIGH).into();
+                sink.push(EdgeEvent { level, timestamp });
+                logged |= 1 << pin;
+            }
+        }
+        logged
+    })
+}
+
+#[cfg(feature = "time")]
+impl<const N: usize> EdgeQueue<N> {
+    /// Create a new empty queue.
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(RefCell::new(EdgeRing {
+                events: [EdgeEvent {
+                    level: Level::Low,
+                    timestamp: Instant::from_ticks(0),
+                }; N],
+                start: 0,
+                len: 0,
+                lost: 0,
+                debounce: Duration::from_ticks(0),
+                last: None,
+            })),
+            waker: AtomicWaker::new(),
+        }
+    }
+
+    fn reset(&self, debounce: Duration) {
+        self.state.lock(|state| {
+            let mut state = state.borrow_mut();
+            state.start = 0;
+            state.len = 0;
+            state.lost = 0;
+            state.debounce = debounce;
+            state.last = None;
+        })
+    }
+
+    fn pop(&self) -> Option<EdgeEvent> {
+        self.state.lock(|state| {
+            let mut state = state.borrow_mut();
+            if state.len == 0 {
+                return None;
+            }
+            let event = state.events[state.start];
+            state.start = (state.start + 1) % N;
+            state.len -= 1;
+            Some(event)
+        })
+    }
+}
+
+#[cfg(feature = "time")]
+impl<const N: usize> EdgeSink for EdgeQueue<N> {
+    fn push(&self, event: EdgeEvent) {
+        self.state.lock(|state| {
+            let mut state = state.borrow_mut();
+            if let Some(last) = state.last {
+                if event.timestamp.saturating_duration_since(last) < state.debounce {
+                    return;
+                }
+            }
+            state.last = Some(event.timestamp);
+
+            if state.len == N {
+                state.lost = state.lost.saturating_add(1);
+                return;
+            }
+            let end = (state.start + state.len) % N;
+            state.events[end] = event;
+            state.len += 1;
+        });
+        self.waker.wake();
+    }
+}
+
+/// Edge logger, timestamping the edges of an EXTI input in the interrupt handler.
+///
+/// The edges are queued in an [`EdgeQueue`] and drained by a task, so bursts of edges faster than
+/// the task scheduling are not lost, for pulse counting and S0 energy meter inputs. Edges closer
+/// than the debounce time to the previous one are ignored, and the edges arriving while the queue
+/// is full are counted, see [`take_lost`](Self::take_lost).
+///
+/// The timestamps have the resolution of the `embassy-time` tick rate, and include the interrupt
+/// latency. A timer input capture is more accurate, when one is available on the pin.
+///
+/// ```rust,ignore
+/// static QUEUE: EdgeQueue<32> = EdgeQueue::new();
+///
+/// let input = ExtiInput::new(p.PA0, p.EXTI0, Pull::Up);
+/// let mut logger = EdgeLogger::new(input, Edge::Falling, Duration::from_millis(5), &QUEUE);
+/// let mut events = [EdgeEvent { level: Level::Low, timestamp: Instant::MIN }; 32];
+/// loop {
+///     let n = logger.drain(&mut events).await;
+///     pulses += n as u32 + logger.take_lost();
+/// }
+/// ```
+#[cfg(feature = "time")]
+pub struct EdgeLogger<'d, const N: usize> {
+    input: ExtiInput<'d>,
+    queue: &'static EdgeQueue<N>,
+}
+
+#[cfg(feature = "time")]
+impl<'d, const N: usize> EdgeLogger<'d, N> {
+    /// Start timestamping the `edge`s of `input` into `queue`, ignoring the edges within
+    /// `debounce` of the previous one.
+    ///
+    /// The queue is emptied. It must not be used by another logger at the same time.
+    pub fn new(input: ExtiInput<'d>, edge: Edge, debounce: Duration, queue: &'static EdgeQueue<N>) -> Self {
+        let pin = input.pin.pin.pin.pin();
+        let port = input.pin.pin.pin.port();
+
+        queue.reset(debounce);
+        EDGE_SINKS.lock(|sinks| sinks.borrow_mut()[pin as usize] = Some((queue, port)));
+        let rising = matches!(edge, Edge::Rising | Edge::Any);
+        let falling = matches!(edge, Edge::Falling | Edge::Any);
+        arm(pin, port, rising, falling);
+
+        Self { input, queue }
+    }
+
+    /// Wait for the next edge.
+    pub async fn next(&mut self) -> EdgeEvent {
+        poll_fn(|cx| {
+            self.queue.waker.register(cx.waker());
+            match self.queue.pop() {
+                Some(event) => Poll::Ready(event),
+                None => Poll::Pending,
+            }
+        })
+        .await
+    }
+
+    /// Wait for at least one edge, then move the queued edges to `events`, returning their number.
+    pub async fn drain(&mut self, events: &mut [EdgeEvent]) -> usize {
+        if events.is_empty() {
+            return 0;
+        }
+
+        events[0] = self.next().await;
+        let mut n = 1;
+        while n < events.len() {
+            match self.queue.pop() {
+                Some(event) => events[n] = event,
+                None => break,
+            }
+            n += 1;
+        }
+        n
+    }
+
+    /// Get the number of edges lost as the queue was full, since the last call.
+    pub fn take_lost(&mut self) -> u32 {
+        self.queue
+            .state
+            .lock(|state| core::mem::take(&mut state.borrow_mut().lost))
+    }
+
+    /// Get whether the pin is high.
+    pub fn is_high(&self) -> bool {
+        self.input.is_high()
+    }
+
+    /// Stop timestamping the edges, and release the input.
+    pub fn free(self) -> ExtiInput<'d> {
+        let this = core::mem::ManuallyDrop::new(self);
+        this.stop();
+        // safety: `this` is not dropped, so the input is moved out once.
+        unsafe { core::ptr::read(&this.input) }
+    }
+
+    fn stop(&self) {
+        let pin = self.input.pin.pin.pin.pin();
+        disarm(pin);
+        EDGE_SINKS.lock(|sinks| sinks.borrow_mut()[pin as usize] = None);
+    }
+}
+
+#[cfg(feature = "time")]
+impl<'d, const N: usize> Drop for EdgeLogger<'d, N> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
 macro_rules! foreach_exti_irq {
     ($action:ident) => {
         foreach_interrupt!(
@@ -45,6 +45,13 @@ impl<'d> Flash<'d, Async> {
     ///
     /// NOTE: `offset` is an offset from the flash start, NOT an absolute address.
     /// For example, to write address `0x0800_1234` you have to use offset `0x1234`.
+    ///
+    /// The reads from the flash stall during the write, unless they are from the other bank of a
+    /// dual bank flash.
+    #[cfg_attr(
+        any(stm32f427, stm32f429, stm32f437, stm32f439, stm32f469, stm32f479),
+        doc = "In dual bank mode, the application keeps running during the write if it runs from the other bank, see [`active_bank`](super::active_bank)."
+    )]
     pub async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Error> {
         unsafe { write_chunked(FLASH_BASE as u32, FLASH_SIZE as u32, offset, bytes).await }
     }
@@ -53,6 +60,13 @@ impl<'d> Flash<'d, Async> {
     ///
     /// NOTE: `from` and `to` are offsets from the flash start, NOT an absolute address.
     /// For example, to erase address `0x0801_0000` you have to use offset `0x1_0000`.
+    ///
+    /// The reads from the flash stall during the erase, unless they are from the other bank of a
+    /// dual bank flash.
+    #[cfg_attr(
+        any(stm32f427, stm32f429, stm32f437, stm32f439, stm32f469, stm32f479),
+        doc = "In dual bank mode, the application keeps running during the erase if it runs from the other bank, see [`active_bank`](super::active_bank)."
+    )]
     pub async fn erase(&mut self, from: u32, to: u32) -> Result<(), Error> {
         unsafe { erase_sectored(FLASH_BASE as u32, from, to).await }
     }
@@ -0,0 +1,396 @@
+//! EEPROM emulation, a wear-levelled key-value store on two flash pages.
+//!
+//! The values are appended to a journal in the active page, the last record of a key holding its
+//! current value. Once the active page is full, the current values are copied to the other page,
+//! which becomes the active one, and the old page is erased, as in ST's EEPROM emulation
+//! application notes. Each record is protected by a CRC, so a write interrupted by a reset is
+//! ignored, and the previous value of the key is read instead.
+//!
+//! ```rust,ignore
+//! let flash = Flash::new_blocking(p.FLASH).into_blocking_regions().bank1_region;
+//! let mut eeprom = Eeprom::new(flash, 0x6_0000, 0x2_0000)?;
+//!
+//! let boots: u32 = eeprom.read(BOOT_COUNT)?.unwrap_or(0);
+//! eeprom.write(BOOT_COUNT, &(boots + 1))?;
+//! ```
+
+use embedded_storage::nor_flash::NorFlash;
+
+/// Maximum size of a value, in bytes.
+pub const MAX_VALUE_SIZE: usize = 64;
+
+/// Marks a page holding a journal.
+const PAGE_MAGIC: u32 = 0x4545_5031;
+/// Size of the page and record headers, before their padding to the write size.
+const HEADER_SIZE: usize = 8;
+/// Size of the record buffer, for a header and a value padded to a write size of up to 32 bytes.
+const BUF_SIZE: usize = 128;
+/// Key and length of the erased flash, ending the journal.
+const ERASED: u16 = 0xFFFF;
+
+/// EEPROM emulation error.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// Flash error.
+    Flash(E),
+    /// The stored value doesn't have the size of the requested type.
+    SizeMismatch,
+    /// The current values don't leave room for the write in a page.
+    Full,
+}
+
+/// Value stored in the [`Eeprom`].
+pub trait Value: Sized {
+    /// Size of the stored value, in bytes, from 1 to [`MAX_VALUE_SIZE`].
+    const SIZE: usize;
+
+    /// Write the value to `buf`, of `SIZE` bytes.
+    fn store(&self, buf: &mut [u8]);
+
+    /// Read the value from `buf`, of `SIZE` bytes.
+    fn load(buf: &[u8]) -> Self;
+}
+
+macro_rules! impl_value_for_number {
+    ($($ty:ty),*) => {
+        $(
+            impl Value for $ty {
+                const SIZE: usize = core::mem::size_of::<$ty>();
+
+                fn store(&self, buf: &mut [u8]) {
+                    buf.copy_from_slice(&self.to_le_bytes());
+                }
+
+                fn load(buf: &[u8]) -> Self {
+                    Self::from_le_bytes(buf.try_into().unwrap())
+                }
+            }
+        )*
+    };
+}
+
+impl_value_for_number!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+impl Value for bool {
+    const SIZE: usize = 1;
+
+    fn store(&self, buf: &mut [u8]) {
+        buf[0] = *self as u8;
+    }
+
+    fn load(buf: &[u8]) -> Self {
+        buf[0] != 0
+    }
+}
+
+impl<const N: usize> Value for [u8; N] {
+    const SIZE: usize = N;
+
+    fn store(&self, buf: &mut [u8]) {
+        buf.copy_from_slice(self);
+    }
+
+    fn load(buf: &[u8]) -> Self {
+        buf.try_into().unwrap()
+    }
+}
+
+/// Record of the journal.
+struct Record {
+    /// Offset in the page.
+    offset: u32,
+    key: u16,
+    /// Length of the value, 0 for a removed key.
+    len: u16,
+    crc: u16,
+}
+
+/// EEPROM emulation on two pages of a flash.
+///
+/// Keys are `u16`, except `0xFFFF`, and values are up to [`MAX_VALUE_SIZE`] bytes.
+pub struct Eeprom<F: NorFlash> {
+    flash: F,
+    base: u32,
+    page_size: u32,
+    /// Index of the active page, 0 or 1.
+    active: u32,
+    generation: u32,
+    /// Offset of the end of the journal in the active page.
+    end: u32,
+}
+
+impl<F: NorFlash> Eeprom<F> {
+    /// Mount the EEPROM on the two pages of `page_size` bytes at offset `base` of `flash`.
+    ///
+    /// `page_size` may span several flash sectors. The pages are formatted if they don't hold a
+    /// journal yet.
+    pub fn new(flash: F, base: u32, page_size: u32) -> Result<Self, Error<F::Error>> {
+        assert!(F::READ_SIZE == 1);
+        assert!(F::WRITE_SIZE <= 32 && BUF_SIZE % F::WRITE_SIZE == 0);
+        assert!(base % F::ERASE_SIZE as u32 == 0 && page_size % F::ERASE_SIZE as u32 == 0);
+        assert!(page_size as usize >= 2 * BUF_SIZE);
+
+        let mut this = Self {
+            flash,
+            base,
+            page_size,
+            active: 0,
+            generation: 0,
+            end: 0,
+        };
+
+        // Both pages hold a journal if a reset happened before the end of a page swap.
+        let active = match (this.page_generation(0)?, this.page_generation(1)?) {
+            (None, None) => return this.format().map(|_| this),
+            (Some(g0), Some(g1)) if (g1.wrapping_sub(g0) as i32) > 0 => (1, g1),
+            (Some(g0), _) => (0, g0),
+            (None, Some(g1)) => (1, g1),
+        };
+        (this.active, this.generation) = active;
+        this.end = this.journal_end(this.active)?;
+        Ok(this)
+    }
+
+    /// Erase both pages, removing all the keys.
+    pub fn format(&mut self) -> Result<(), Error<F::Error>> {
+        self.erase_page(1)?;
+        self.erase_page(0)?;
+        self.write_page_header(0, 0)?;
+        self.active = 0;
+        self.generation = 0;
+        self.end = self.first_record();
+        Ok(())
+    }
+
+    /// Read the value of `key`, `None` if it was never written or was removed.
+    pub fn read<T: Value>(&mut self, key: u16) -> Result<Option<T>, Error<F::Error>> {
+        let mut buf = [0; BUF_SIZE];
+        match self.find(self.active, key, &mut buf)? {
+            None => Ok(None),
+            Some(record) if record.len == 0 => Ok(None),
+            Some(record) if record.len as usize != T::SIZE => Err(Error::SizeMismatch),
+            Some(_) => Ok(Some(T::load(&buf[HEADER_SIZE..][..T::SIZE]))),
+        }
+    }
+
+    /// Write the value of `key`.
+    ///
+    /// Nothing is written if the key already holds this value.
+    pub fn write<T: Value>(&mut self, key: u16, value: &T) -> Result<(), Error<F::Error>> {
+        assert!(T::SIZE > 0 && T::SIZE <= MAX_VALUE_SIZE);
+
+        let mut data = [0; MAX_VALUE_SIZE];
+        value.store(&mut data[..T::SIZE]);
+        self.append(key, &data[..T::SIZE])
+    }
+
+    /// Remove `key`.
+    pub fn remove(&mut self, key: u16) -> Result<(), Error<F::Error>> {
+        self.append(key, &[])
+    }
+
+    /// Release the flash.
+    pub fn release(self) -> F {
+        self.flash
+    }
+
+    fn append(&mut self, key: u16, data: &[u8]) -> Result<(), Error<F::Error>> {
+        assert!(key != ERASED);
+
+        let mut buf = [0; BUF_SIZE];
+        let current = self.find(self.active, key, &mut buf)?;
+        let unchanged = match current {
+            Some(record) => record.len as usize == data.len() && buf[HEADER_SIZE..][..data.len()] == *data,
+            None => data.is_empty(),
+        };
+        if unchanged {
+            return Ok(());
+        }
+
+        let size = record_size::<F>(data.len());
+        if self.end + size > self.page_size {
+            self.swap_pages()?;
+            if self.end + size > self.page_size {
+                return Err(Error::Full);
+            }
+        }
+
+        buf.fill(0xFF);
+        buf[0..2].copy_from_slice(&key.to_le_bytes());
+        buf[2..4].copy_from_slice(&(data.len() as u16).to_le_bytes());
+        buf[4..6].copy_from_slice(&record_crc(key, data).to_le_bytes());
+        buf[HEADER_SIZE..][..data.len()].copy_from_slice(data);
+
+        let address = self.page_address(self.active) + self.end;
+        self.flash.write(address, &buf[..size as usize]).map_err(Error::Flash)?;
+        self.end += size;
+        Ok(())
+    }
+
+    /// Copy the current values to the other page, and activate it.
+    fn swap_pages(&mut self) -> Result<(), Error<F::Error>> {
+        let src = self.active;
+        let dst = 1 - src;
+        self.erase_page(dst)?;
+
+        let mut buf = [0; BUF_SIZE];
+        let mut end = self.first_record();
+        let mut offset = self.first_record();
+        while let Some(record) = self.record_at(src, offset)? {
+            offset += record_size::<F>(record.len as usize);
+            if record.len == 0 {
+                continue;
+            }
+            // Only the last valid record of each key is current.
+            match self.find(src, record.key, &mut buf)? {
+                Some(current) if current.offset == record.offset => {}
+                _ => continue,
+            }
+            let size = record_size::<F>(record.len as usize);
+            self.flash
+                .write(self.page_address(dst) + end, &buf[..size as usize])
+                .map_err(Error::Flash)?;
+            end += size;
+        }
+
+        // The header is written last, so an interrupted swap leaves the source page active.
+        let generation = self.generation.wrapping_add(1);
+        self.write_page_header(dst, generation)?;
+        self.erase_page(src)?;
+
+        self.active = dst;
+        self.generation = generation;
+        self.end = end;
+        Ok(())
+    }
+
+    /// Find the last valid record of `key` in `page`, reading it to `buf`.
+    fn find(&mut self, page: u32, key: u16, buf: &mut [u8; BUF_SIZE]) -> Result<Option<Record>, Error<F::Error>> {
+        let mut found = None;
+        let mut offset = self.first_record();
+        while let Some(record) = self.record_at(page, offset)? {
+            let size = record_size::<F>(record.len as usize);
+            if record.key == key {
+                let mut candidate = [0; BUF_SIZE];
+                self.flash
+                    .read(self.page_address(page) + offset, &mut candidate[..size as usize])
+                    .map_err(Error::Flash)?;
+                if record_crc(key, &candidate[HEADER_SIZE..][..record.len as usize]) == record.crc {
+                    *buf = candidate;
+                    found = Some(record);
+                }
+            }
+            offset += size;
+        }
+        Ok(found)
+    }
+
+    /// Read the header of the record at `offset` in `page`, `None` at the end of the journal.
+    fn record_at(&mut self, page: u32, offset: u32) -> Result<Option<Record>, Error<F::Error>> {
+        if offset + HEADER_SIZE as u32 > self.page_size {
+            return Ok(None);
+        }
+
+        let mut header = [0; HEADER_SIZE];
+        self.flash
+            .read(self.page_address(page) + offset, &mut header)
+            .map_err(Error::Flash)?;
+        let key = u16::from_le_bytes([header[0], header[1]]);
+        let len = u16::from_le_bytes([header[2], header[3]]);
+        let crc = u16::from_le_bytes([header[4], header[5]]);
+
+        // A corrupted length also ends the journal, the page is swapped at the next write.
+        if key == ERASED || len as usize > MAX_VALUE_SIZE {
+            return Ok(None);
+        }
+        if offset + record_size::<F>(len as usize) > self.page_size {
+            return Ok(None);
+        }
+        Ok(Some(Record { offset, key, len, crc }))
+    }
+
+    /// Offset of the end of the journal in `page`.
+    fn journal_end(&mut self, page: u32) -> Result<u32, Error<F::Error>> {
+        let mut offset = self.first_record();
+        while let Some(record) = self.record_at(page, offset)? {
+            offset += record_size::<F>(record.len as usize);
+        }
+
+        // Records can't be appended after a corrupted one.
+        let mut header = [0; HEADER_SIZE];
+        if offset + HEADER_SIZE as u32 <= self.page_size {
+            self.flash
+                .read(self.page_address(page) + offset, &mut header)
+                .map_err(Error::Flash)?;
+        }
+        if header.iter().all(|b| *b == 0xFF) {
+            Ok(offset)
+        } else {
+            Ok(self.page_size)
+        }
+    }
+
+    /// Generation of the journal in `page`, `None` if it doesn't hold one.
+    fn page_generation(&mut self, page: u32) -> Result<Option<u32>, Error<F::Error>> {
+        let mut header = [0; HEADER_SIZE];
+        self.flash
+            .read(self.page_address(page), &mut header)
+            .map_err(Error::Flash)?;
+        let magic = u32::from_le_bytes(header[0..4].try_into().unwrap());
+        let generation = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        Ok((magic == PAGE_MAGIC).then_some(generation))
+    }
+
+    fn write_page_header(&mut self, page: u32, generation: u32) -> Result<(), Error<F::Error>> {
+        let mut buf = [0xFF; BUF_SIZE];
+        buf[0..4].copy_from_slice(&PAGE_MAGIC.to_le_bytes());
+        buf[4..8].copy_from_slice(&generation.to_le_bytes());
+        let size = self.first_record();
+        self.flash
+            .write(self.page_address(page), &buf[..size as usize])
+            .map_err(Error::Flash)
+    }
+
+    fn erase_page(&mut self, page: u32) -> Result<(), Error<F::Error>> {
+        let address = self.page_address(page);
+        self.flash
+            .erase(address, address + self.page_size)
+            .map_err(Error::Flash)
+    }
+
+    fn page_address(&self, page: u32) -> u32 {
+        self.base + page * self.page_size
+    }
+
+    /// Offset of the first record in a page, after the page header.
+    fn first_record(&self) -> u32 {
+        round_up(HEADER_SIZE, F::WRITE_SIZE) as u32
+    }
+}
+
+/// Size of a record holding `len` bytes, padded to the write size.
+fn record_size<F: NorFlash>(len: usize) -> u32 {
+    round_up(HEADER_SIZE + len, F::WRITE_SIZE) as u32
+}
+
+fn round_up(n: usize, align: usize) -> usize {
+    n.div_ceil(align) * align
+}
+
+/// CRC-16/CCITT of the key, length and value of a record.
+fn record_crc(key: u16, data: &[u8]) -> u16 {
+    let header = [key.to_le_bytes(), (data.len() as u16).to_le_bytes()];
+    let mut crc: u16 = 0xFFFF;
+    for byte in header.iter().flatten().chain(data) {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
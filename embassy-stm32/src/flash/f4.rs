@@ -206,6 +206,41 @@ pub fn get_flash_regions() -> &'static [&'static FlashRegion] {
     }
 }
 
+/// Get the bank mapped at the start of the flash, from which the application runs.
+///
+/// In dual bank mode, the other bank is mapped in the second half of the flash. Erasing and
+/// programming it with the async driver does not stall the reads from the active bank, so the
+/// application keeps running meanwhile, e.g. while receiving a firmware update.
+#[cfg(any(stm32f427, stm32f429, stm32f437, stm32f439, stm32f469, stm32f479))]
+pub fn active_bank() -> FlashBank {
+    match pac::SYSCFG.memrmp().read().fb_mode() {
+        true => FlashBank::Bank2,
+        false => FlashBank::Bank1,
+    }
+}
+
+#[cfg(any(stm32f427, stm32f429, stm32f437, stm32f439, stm32f469, stm32f479))]
+impl<MODE> super::Flash<'_, MODE> {
+    /// Swap the banks at the next reset, to boot from the bank in the second half of the flash.
+    ///
+    /// This programs the BFB2 option bit, read by the system bootloader at reset to map the bank
+    /// to boot from at the start of the flash. The flash must be in dual bank mode.
+    pub fn swap_banks(&mut self) -> Result<(), Error> {
+        assert!(get_flash_regions().last().unwrap().bank == FlashBank::Bank2);
+        let bfb2 = active_bank() == FlashBank::Bank1;
+
+        if pac::FLASH.optcr().read().optlock() {
+            pac::FLASH.optkeyr().write_value(0x0819_2A3B);
+            pac::FLASH.optkeyr().write_value(0x4C5D_6E7F);
+        }
+        pac::FLASH.optcr().modify(|w| w.set_bfb2(bfb2));
+        pac::FLASH.optcr().modify(|w| w.set_optstrt(true));
+        let ret = unsafe { blocking_wait_ready() };
+        pac::FLASH.optcr().modify(|w| w.set_optlock(true));
+        ret
+    }
+}
+
 #[cfg(not(any(stm32f427, stm32f429, stm32f437, stm32f439, stm32f469, stm32f479)))]
 pub const fn get_flash_regions() -> &'static [&'static FlashRegion] {
     &FLASH_REGIONS
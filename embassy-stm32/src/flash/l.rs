@@ -84,13 +84,16 @@ pub(crate) unsafe fn blocking_erase_sector(sector: &FlashSector) -> Result<(), E
         write_volatile(sector.start as *mut u32, 0xFFFFFFFF);
     }
 
-    #[cfg(any(flash_wl, flash_wb, flash_l4))]
-    {
-        let idx = (sector.start - super::FLASH_BASE as u32) / super::BANK1_REGION.erase_size as u32;
+    #[cfg(any(flash_wl, flash_wb))]
+    let idx = (sector.start - super::FLASH_BASE as u32) / super::BANK1_REGION.erase_size as u32;
 
-        #[cfg(flash_l4)]
-        let (idx, bank) = if idx > 255 { (idx - 256, true) } else { (idx, false) };
+    // On dual-bank chips, pages are numbered within their bank, and the size of the banks depends
+    // on the chip: the second bank doesn't always start at page 256.
+    #[cfg(flash_l4)]
+    let (idx, bank) = (sector.index_in_bank as u32, sector.bank == super::FlashBank::Bank2);
 
+    #[cfg(any(flash_wl, flash_wb, flash_l4))]
+    {
         pac::FLASH.cr().modify(|w| {
             w.set_per(true);
             w.set_pnb(idx as u8);
@@ -5,6 +5,7 @@ use embedded_storage::nor_flash::{NorFlashError, NorFlashErrorKind};
 mod asynch;
 #[cfg(flash)]
 mod common;
+pub mod eeprom;
 
 #[cfg(flash_f4)]
 pub use asynch::InterruptHandler;
@@ -15,6 +15,9 @@ use crate::{pac, peripherals, Peripheral};
 /// This pin can either be a disconnected, input, or output pin, or both. The level register bit will remain
 /// set while not in output mode, so the pin's level will be 'remembered' when it is not in output
 /// mode.
+///
+/// It can also be put into analog mode, or connected to a peripheral through one of its alternate
+/// functions, for drivers outside of this crate.
 pub struct Flex<'d> {
     pub(crate) pin: PeripheralRef<'d, AnyPin>,
 }
@@ -129,6 +132,45 @@ impl<'d> Flex<'d> {
         });
     }
 
+    /// Put the pin into analog mode.
+    ///
+    /// This disconnects the digital input, for the ADC, DAC or comparators.
+    #[inline]
+    pub fn set_as_analog(&mut self) {
+        critical_section::with(|_| self.pin.set_as_analog());
+    }
+
+    /// Connect the pin to a peripheral input, through the alternate function `af`.
+    ///
+    /// Fails if `af` is not an alternate function of the pin. On STM32F1, the peripherals are
+    /// connected to the pins by the AFIO remaps instead, and `af` is ignored.
+    #[inline]
+    pub fn set_as_af_input(&mut self, af: u8, pull: Pull) -> Result<(), AfError> {
+        check_af(self.pin.pin_port(), af)?;
+        critical_section::with(|_| self.pin.set_as_af_pull(af, sealed::AFType::Input, pull));
+        Ok(())
+    }
+
+    /// Connect the pin to a peripheral output, through the alternate function `af`.
+    ///
+    /// Fails if `af` is not an alternate function of the pin. On STM32F1, the peripherals are
+    /// connected to the pins by the AFIO remaps instead, and `af` is ignored.
+    #[inline]
+    pub fn set_as_af_output(
+        &mut self,
+        af: u8,
+        output_type: OutputType,
+        speed: Speed,
+        pull: Pull,
+    ) -> Result<(), AfError> {
+        check_af(self.pin.pin_port(), af)?;
+        critical_section::with(|_| {
+            self.pin.set_as_af_pull(af, output_type.into(), pull);
+            self.pin.set_speed(speed);
+        });
+        Ok(())
+    }
+
     /// Get whether the pin input level is high.
     #[inline]
     pub fn is_high(&self) -> bool {
@@ -222,6 +264,21 @@ impl<'d> Drop for Flex<'d> {
     }
 }
 
+/// Error returned when connecting a pin to a peripheral through an alternate function it does not have.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AfError;
+
+fn check_af(pin_port: u8, af: u8) -> Result<(), AfError> {
+    #[cfg(gpio_v1)]
+    let _ = (pin_port, af);
+    #[cfg(gpio_v2)]
+    if af >= 16 || crate::_generated::pin_af_mask(pin_port) & (1 << af) == 0 {
+        return Err(AfError);
+    }
+    Ok(())
+}
+
 /// Pull setting for an input.
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -500,6 +557,90 @@ impl<'d> OutputOpenDrain<'d> {
     }
 }
 
+/// GPIO output group, driving several pins of the same port at once.
+///
+/// The pins are changed by a single write to the port's bit set/reset register, so they change at
+/// the same time, for parallel buses and LED matrices. Bit `i` of the values and masks is the
+/// pin at index `i` in the group, whatever its number within the port.
+///
+/// ```rust,ignore
+/// let data = [p.PB0, p.PB1, p.PB2, p.PB3].map(|pin| Output::new(pin, Level::Low, Speed::VeryHigh));
+/// let mut bus = OutputGroup::new(data);
+/// bus.write(0b1010);
+/// ```
+pub struct OutputGroup<'d, const N: usize> {
+    pins: [Output<'d>; N],
+    block: gpio::Gpio,
+}
+
+impl<'d, const N: usize> OutputGroup<'d, N> {
+    /// Group `pins`, which must all be on the same port.
+    pub fn new(pins: [Output<'d>; N]) -> Self {
+        assert!(N > 0 && N <= 16);
+        let port = pins[0].pin.pin._port();
+        assert!(pins.iter().all(|pin| pin.pin.pin._port() == port));
+        let block = pins[0].pin.pin.block();
+        Self { pins, block }
+    }
+
+    /// Set the pins whose bit is set in `mask` high, leaving the others unchanged.
+    #[inline]
+    pub fn set_high(&mut self, mask: u16) {
+        self.bsrr(self.port_mask(mask));
+    }
+
+    /// Set the pins whose bit is set in `mask` low, leaving the others unchanged.
+    #[inline]
+    pub fn set_low(&mut self, mask: u16) {
+        self.bsrr(self.port_mask(mask) << 16);
+    }
+
+    /// Set all the pins of the group, each to its bit in `value`.
+    #[inline]
+    pub fn write(&mut self, value: u16) {
+        self.bsrr(self.port_mask(value) | self.port_mask(!value) << 16);
+    }
+
+    /// Toggle the pins whose bit is set in `mask`, leaving the others unchanged.
+    #[inline]
+    pub fn toggle(&mut self, mask: u16) {
+        let mask = self.port_mask(mask);
+        let high = self.block.odr().read().0 & mask;
+        self.bsrr((mask & !high) | high << 16);
+    }
+
+    /// Get the output levels of the pins of the group, as bits.
+    #[inline]
+    pub fn get_output(&self) -> u16 {
+        let odr = self.block.odr().read().0;
+        self.pins
+            .iter()
+            .enumerate()
+            .filter(|(_, pin)| odr & 1 << pin.pin.pin._pin() != 0)
+            .fold(0, |value, (i, _)| value | 1 << i)
+    }
+
+    /// Release the pins.
+    pub fn free(self) -> [Output<'d>; N] {
+        self.pins
+    }
+
+    /// Port bits of the pins of the group whose bit is set in `mask`.
+    #[inline]
+    fn port_mask(&self, mask: u16) -> u32 {
+        self.pins
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| mask & 1 << i != 0)
+            .fold(0, |bits, (_, pin)| bits | 1 << pin.pin.pin._pin())
+    }
+
+    #[inline]
+    fn bsrr(&self, bits: u32) {
+        self.block.bsrr().write_value(gpio::regs::Bsrr(bits));
+    }
+}
+
 /// GPIO output type
 pub enum OutputType {
     /// Drive the pin both high or low.
@@ -767,6 +908,21 @@ foreach_pin!(
     };
 );
 
+/// Enable the clocks of all GPIO ports.
+///
+/// [`crate::init`] already does this, this is only needed to drive pins before it runs, e.g. in early
+/// boot code. It doesn't need the executor or any interrupt to be set up, and can be called any number
+/// of times. Pins can be taken with `steal()`, which also allows using them in a panic handler:
+///
+/// ```rust,ignore
+/// embassy_stm32::gpio::enable_ports();
+/// let pin = unsafe { embassy_stm32::peripherals::PB7::steal() };
+/// let mut led = Output::new(pin, Level::High, Speed::Low);
+/// ```
+pub fn enable_ports() {
+    critical_section::with(|_| unsafe { crate::_generated::init_gpio() });
+}
+
 pub(crate) unsafe fn init(_cs: CriticalSection) {
     #[cfg(afio)]
     <crate::peripherals::AFIO as crate::rcc::sealed::RccPeripheral>::enable_and_reset_with_cs(_cs);
@@ -0,0 +1,369 @@
+//! Hash processor (HASH), hardware SHA-1 and SHA-2.
+//!
+//! A digest is computed by [`start`](Hash::start)ing it, feeding the message with one or more
+//! updates, and [`finish`](Hash::finish)ing it. The async updates feed the processor with DMA,
+//! the blocking ones from the CPU.
+//!
+//! ```rust,ignore
+//! let mut hash = Hash::new(p.HASH, p.DMA2_CH7, Irqs);
+//! hash.start(Algorithm::Sha256);
+//! hash.update(&firmware[..len]).await;
+//! let mut digest = [0; 32];
+//! hash.finish(&mut digest).await;
+//! ```
+//!
+//! With the `digest` feature, [`Sha1`], [`Sha224`] and [`Sha256`] implement the RustCrypto
+//! `digest` traits over a borrowed driver, for the signature verification crates generic over
+//! the hash function. As they borrow the driver, they don't implement `Default`, and are created
+//! with the `new` methods instead of `Digest::new`.
+#![macro_use]
+
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use embassy_hal_internal::drop::OnDrop;
+use embassy_hal_internal::{into_ref, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::dma::NoDma;
+#[cfg(not(hash_v1))]
+use crate::dma::Transfer;
+use crate::interrupt::typelevel::Interrupt;
+use crate::{interrupt, pac, peripherals, Peripheral};
+
+static HASH_WAKER: AtomicWaker = AtomicWaker::new();
+
+/// Data swapping of 8-bit data, so the message is fed as a byte string.
+const DATATYPE_BYTES: u8 = 2;
+
+/// Number of words moved per DMA transfer, within the transfer length limit of all the DMA
+/// controllers.
+#[cfg(not(hash_v1))]
+const DMA_CHUNK_LEN: usize = 0x3FFF;
+
+/// Hash algorithm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Algorithm {
+    /// SHA-1.
+    Sha1,
+    /// SHA-224.
+    #[cfg(not(hash_v1))]
+    Sha224,
+    /// SHA-256.
+    #[cfg(not(hash_v1))]
+    Sha256,
+}
+
+impl Algorithm {
+    /// Size of the digest, in bytes.
+    pub const fn digest_size(self) -> usize {
+        match self {
+            Algorithm::Sha1 => 20,
+            #[cfg(not(hash_v1))]
+            Algorithm::Sha224 => 28,
+            #[cfg(not(hash_v1))]
+            Algorithm::Sha256 => 32,
+        }
+    }
+
+    fn algo(self) -> u8 {
+        match self {
+            Algorithm::Sha1 => 0b00,
+            #[cfg(not(hash_v1))]
+            Algorithm::Sha224 => 0b10,
+            #[cfg(not(hash_v1))]
+            Algorithm::Sha256 => 0b11,
+        }
+    }
+}
+
+/// HASH interrupt handler.
+pub struct InterruptHandler<T: Instance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        let r = T::regs();
+        if r.sr().read().dcis() {
+            r.imr().modify(|w| w.set_dcie(false));
+            HASH_WAKER.wake();
+        }
+    }
+}
+
+/// HASH driver.
+pub struct Hash<'d, T: Instance, D = NoDma> {
+    _peri: PeripheralRef<'d, T>,
+    dma: PeripheralRef<'d, D>,
+    algorithm: Algorithm,
+    /// Bytes of the message not forming a whole word yet.
+    partial: [u8; 4],
+    partial_len: usize,
+}
+
+impl<'d, T: Instance, D> Hash<'d, T, D> {
+    /// Create a new HASH driver.
+    ///
+    /// The DMA channel is only used by the async updates, pass [`NoDma`] to only use the blocking
+    /// ones.
+    pub fn new(
+        peri: impl Peripheral<P = T> + 'd,
+        dma: impl Peripheral<P = D> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+    ) -> Self {
+        into_ref!(peri, dma);
+        T::enable_and_reset();
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        Self {
+            _peri: peri,
+            dma,
+            algorithm: Algorithm::Sha1,
+            partial: [0; 4],
+            partial_len: 0,
+        }
+    }
+
+    /// Start computing the digest of a new message, abandoning the current one.
+    pub fn start(&mut self, algorithm: Algorithm) {
+        let r = T::regs();
+        r.cr().write(|w| {
+            #[cfg(hash_v1)]
+            w.set_algo(algorithm.algo() != 0);
+            #[cfg(hash_v2)]
+            {
+                w.set_algo0(algorithm.algo() & 0b01 != 0);
+                w.set_algo1(algorithm.algo() & 0b10 != 0);
+            }
+            #[cfg(any(hash_v3, hash_v4))]
+            w.set_algo(algorithm.algo());
+            w.set_datatype(DATATYPE_BYTES);
+        });
+        r.cr().modify(|w| w.set_init(true));
+
+        self.algorithm = algorithm;
+        self.partial_len = 0;
+    }
+
+    /// Feed `data` to the current digest, from the CPU.
+    pub fn blocking_update(&mut self, data: &[u8]) {
+        let rest = self.complete_partial(data);
+        let words = rest.chunks_exact(4);
+        let tail = words.remainder();
+        for word in words {
+            write_word::<T>(word);
+        }
+        self.save_partial(tail);
+    }
+
+    /// Finish the current digest, and write it to `digest`, returning its size.
+    pub fn blocking_finish(&mut self, digest: &mut [u8]) -> usize {
+        self.start_final_computation();
+        while !T::regs().sr().read().dcis() {}
+        self.read_digest(digest)
+    }
+
+    /// Finish the current digest, and write it to `digest`, returning its size.
+    ///
+    /// The final computation runs in the background, signaled by the HASH interrupt.
+    pub async fn finish(&mut self, digest: &mut [u8]) -> usize {
+        let r = T::regs();
+        let _on_drop = OnDrop::new(move || {
+            r.imr().modify(|w| w.set_dcie(false));
+        });
+
+        self.start_final_computation();
+        poll_fn(|cx| {
+            HASH_WAKER.register(cx.waker());
+            if r.sr().read().dcis() {
+                return Poll::Ready(());
+            }
+            // The interrupt handler disables the interrupt when it fires.
+            r.imr().modify(|w| w.set_dcie(true));
+            Poll::Pending
+        })
+        .await;
+
+        self.read_digest(digest)
+    }
+
+    /// Complete the pending partial word with the first bytes of `data`, returning the rest.
+    fn complete_partial<'a>(&mut self, data: &'a [u8]) -> &'a [u8] {
+        if self.partial_len == 0 {
+            return data;
+        }
+
+        let n = data.len().min(4 - self.partial_len);
+        self.partial[self.partial_len..self.partial_len + n].copy_from_slice(&data[..n]);
+        self.partial_len += n;
+        if self.partial_len == 4 {
+            write_word::<T>(&self.partial);
+            self.partial_len = 0;
+        }
+        &data[n..]
+    }
+
+    fn save_partial(&mut self, tail: &[u8]) {
+        self.partial[..tail.len()].copy_from_slice(tail);
+        self.partial_len = tail.len();
+    }
+
+    /// Write the last partial word, and start the final computation.
+    fn start_final_computation(&mut self) {
+        let r = T::regs();
+        r.str().write(|w| w.set_nblw((self.partial_len * 8) as u8));
+        if self.partial_len > 0 {
+            let mut word = [0; 4];
+            word[..self.partial_len].copy_from_slice(&self.partial[..self.partial_len]);
+            write_word::<T>(&word);
+            self.partial_len = 0;
+        }
+        r.str().modify(|w| w.set_dcal(true));
+    }
+
+    fn read_digest(&mut self, digest: &mut [u8]) -> usize {
+        let size = self.algorithm.digest_size();
+        assert!(digest.len() >= size);
+
+        let r = T::regs();
+        for (i, word) in digest[..size].chunks_exact_mut(4).enumerate() {
+            word.copy_from_slice(&r.hr(i).read().to_be_bytes());
+        }
+        size
+    }
+}
+
+#[cfg(not(hash_v1))]
+impl<'d, T: Instance, D: Dma<T>> Hash<'d, T, D> {
+    /// Feed `data` to the current digest, with DMA for its aligned words.
+    ///
+    /// The DMA reads whole words, so the data is fed from the CPU when its alignment in memory
+    /// doesn't match its offset in the message.
+    pub async fn update(&mut self, data: &[u8]) {
+        let rest = self.complete_partial(data);
+
+        // The unaligned head is fed from the CPU, and the DMA transfers the aligned words.
+        let head = rest.as_ptr().align_offset(4).min(rest.len());
+        let (head, rest) = rest.split_at(head);
+        self.blocking_update(head);
+        if self.partial_len > 0 {
+            self.blocking_update(rest);
+            return;
+        }
+
+        let words = rest.len() / 4;
+        // safety: the data is aligned, and only accessed as bytes otherwise.
+        let aligned = unsafe { core::slice::from_raw_parts(rest.as_ptr() as *const u32, words) };
+
+        let r = T::regs();
+        // Several DMA transfers feed the same message, so they don't start the final computation.
+        r.cr().modify(|w| w.set_mdmat(true));
+        for chunk in aligned.chunks(DMA_CHUNK_LEN) {
+            let request = self.dma.request();
+            let transfer =
+                unsafe { Transfer::new_write(&mut self.dma, request, chunk, r.din().as_ptr(), Default::default()) };
+            r.cr().modify(|w| w.set_dmae(true));
+            transfer.await;
+            r.cr().modify(|w| w.set_dmae(false));
+        }
+
+        self.save_partial(&rest[words * 4..]);
+    }
+}
+
+fn write_word<T: Instance>(word: &[u8]) {
+    T::regs()
+        .din()
+        .write_value(u32::from_le_bytes(word.try_into().unwrap()));
+}
+
+#[cfg(feature = "digest")]
+macro_rules! impl_digest {
+    ($name:ident, $algorithm:ident, $size:ident, $doc:literal) => {
+        #[doc = concat!($doc, " digest implementing the RustCrypto `digest` traits, over a borrowed [`Hash`] driver.")]
+        pub struct $name<'a, 'd, T: Instance, D> {
+            hash: &'a mut Hash<'d, T, D>,
+        }
+
+        impl<'a, 'd, T: Instance, D> $name<'a, 'd, T, D> {
+            /// Start computing a digest with `hash`.
+            pub fn new(hash: &'a mut Hash<'d, T, D>) -> Self {
+                hash.start(Algorithm::$algorithm);
+                Self { hash }
+            }
+        }
+
+        impl<T: Instance, D> digest::HashMarker for $name<'_, '_, T, D> {}
+
+        impl<T: Instance, D> digest::OutputSizeUser for $name<'_, '_, T, D> {
+            type OutputSize = digest::consts::$size;
+        }
+
+        impl<T: Instance, D> digest::Update for $name<'_, '_, T, D> {
+            fn update(&mut self, data: &[u8]) {
+                self.hash.blocking_update(data);
+            }
+        }
+
+        impl<T: Instance, D> digest::FixedOutput for $name<'_, '_, T, D> {
+            fn finalize_into(self, out: &mut digest::Output<Self>) {
+                self.hash.blocking_finish(out);
+            }
+        }
+
+        impl<T: Instance, D> digest::Reset for $name<'_, '_, T, D> {
+            fn reset(&mut self) {
+                self.hash.start(Algorithm::$algorithm);
+            }
+        }
+
+        impl<T: Instance, D> digest::FixedOutputReset for $name<'_, '_, T, D> {
+            fn finalize_into_reset(&mut self, out: &mut digest::Output<Self>) {
+                self.hash.blocking_finish(out);
+                self.hash.start(Algorithm::$algorithm);
+            }
+        }
+    };
+}
+
+#[cfg(feature = "digest")]
+impl_digest!(Sha1, Sha1, U20, "SHA-1");
+#[cfg(all(feature = "digest", not(hash_v1)))]
+impl_digest!(Sha224, Sha224, U28, "SHA-224");
+#[cfg(all(feature = "digest", not(hash_v1)))]
+impl_digest!(Sha256, Sha256, U32, "SHA-256");
+
+pub(crate) mod sealed {
+    use super::*;
+
+    pub trait Instance {
+        fn regs() -> pac::hash::Hash;
+    }
+}
+
+/// HASH instance trait.
+pub trait Instance: sealed::Instance + Peripheral<P = Self> + crate::rcc::RccPeripheral + 'static + Send {
+    /// Interrupt for this HASH instance.
+    type Interrupt: interrupt::typelevel::Interrupt;
+}
+
+foreach_interrupt!(
+    ($inst:ident, hash, HASH, GLOBAL, $irq:ident) => {
+        impl Instance for peripherals::$inst {
+            type Interrupt = crate::interrupt::typelevel::$irq;
+        }
+
+        impl sealed::Instance for peripherals::$inst {
+            fn regs() -> crate::pac::hash::Hash {
+                crate::pac::$inst
+            }
+        }
+    };
+);
+
+dma_trait!(Dma, Instance);
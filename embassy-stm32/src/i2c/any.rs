@@ -0,0 +1,264 @@
+//! I2C driver with the instance selected at runtime.
+
+use core::marker::PhantomData;
+
+use embassy_hal_internal::drop::OnDrop;
+use embassy_hal_internal::{into_ref, Peripheral};
+#[cfg(feature = "time")]
+use embassy_time::{Duration, Instant};
+use embedded_hal_1::i2c::Operation;
+
+use super::_version::{init, master_stop, read_internal, transaction_internal, transaction_interrupt, write_internal};
+use super::{
+    sealed, Address, Config, Error, ErrorInterruptHandler, EventInterruptHandler, I2cPin, Instance, SclPin, SdaPin,
+    Timeout,
+};
+use crate::gpio::Pull;
+use crate::interrupt;
+use crate::interrupt::typelevel::Interrupt as _;
+use crate::interrupt::{Interrupt, InterruptExt};
+use crate::mode::{Async, Blocking, Mode};
+use crate::pac::i2c::I2c as Regs;
+use crate::time::Hertz;
+
+/// I2C instance, with its type erased.
+#[derive(Clone, Copy)]
+struct Info {
+    regs: Regs,
+    event_interrupt: Interrupt,
+    error_interrupt: Interrupt,
+    state: &'static sealed::State,
+    frequency: fn() -> Hertz,
+    disable: fn(),
+}
+
+impl Info {
+    fn new<T: Instance>() -> Self {
+        Self {
+            regs: T::regs(),
+            event_interrupt: T::EventInterrupt::IRQ,
+            error_interrupt: T::ErrorInterrupt::IRQ,
+            state: T::state(),
+            frequency: T::frequency,
+            disable: T::disable,
+        }
+    }
+}
+
+/// I2C master driver, with the instance selected at runtime.
+///
+/// [`I2c`](super::I2c) is generic over its instance, so choosing the instance at runtime, for
+/// example from a board configuration table, instantiates the driver and the code using it for
+/// each instance. `AnyI2c` erases the instance instead: only the constructors are generic, and
+/// the driver holds the registers, the clock, the interrupts and the waker of the instance.
+///
+/// The driver created by [`new`](AnyI2c::new) has async transactions, driven by the interrupts of
+/// the instance. The one created by [`new_blocking`](AnyI2c::new_blocking) is blocking only, and
+/// the interrupts stay disabled. The driver doesn't use DMA: use [`I2c`](super::I2c) for DMA
+/// transfers. It is only available on the I2C v2 peripheral.
+pub struct AnyI2c<'d, M: Mode = Blocking> {
+    info: Info,
+    _scl: I2cPin<'d>,
+    _sda: I2cPin<'d>,
+    #[cfg(feature = "time")]
+    timeout: Duration,
+    phantom: PhantomData<(&'d mut (), M)>,
+}
+
+impl<'d> AnyI2c<'d, Blocking> {
+    /// Create a new blocking I2C master driver from any instance.
+    pub fn new_blocking<T: Instance>(
+        peri: impl Peripheral<P = T> + 'd,
+        scl: impl Peripheral<P = impl SclPin<T>> + 'd,
+        sda: impl Peripheral<P = impl SdaPin<T>> + 'd,
+        freq: Hertz,
+        config: Config,
+    ) -> Self {
+        let this = Self::new_inner(peri, scl, sda, freq, config);
+
+        // No interrupt handler is bound.
+        this.info.event_interrupt.disable();
+        this.info.error_interrupt.disable();
+
+        this
+    }
+}
+
+impl<'d> AnyI2c<'d, Async> {
+    /// Create a new I2C master driver from any instance, with async transactions driven by its
+    /// interrupts.
+    pub fn new<T: Instance>(
+        peri: impl Peripheral<P = T> + 'd,
+        scl: impl Peripheral<P = impl SclPin<T>> + 'd,
+        sda: impl Peripheral<P = impl SdaPin<T>> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::EventInterrupt, EventInterruptHandler<T>>
+            + interrupt::typelevel::Binding<T::ErrorInterrupt, ErrorInterruptHandler<T>>
+            + 'd,
+        freq: Hertz,
+        config: Config,
+    ) -> Self {
+        let this = Self::new_inner(peri, scl, sda, freq, config);
+
+        unsafe { this.info.event_interrupt.enable() };
+        unsafe { this.info.error_interrupt.enable() };
+
+        this
+    }
+
+    /// Read, driven by the I2C interrupts.
+    pub async fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Error> {
+        self.transaction(address, &mut [Operation::Read(read)]).await
+    }
+
+    /// Write, driven by the I2C interrupts.
+    pub async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Error> {
+        self.transaction(address, &mut [Operation::Write(write)]).await
+    }
+
+    /// Write, restart, read, driven by the I2C interrupts.
+    pub async fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Error> {
+        self.transaction(address, &mut [Operation::Write(write), Operation::Read(read)])
+            .await
+    }
+
+    /// Transaction, driven by the I2C interrupts.
+    ///
+    /// Adjacent operations of the same direction are merged, a repeated START is sent between
+    /// operations of different directions, and a STOP ends the transaction.
+    pub async fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Error> {
+        if operations.is_empty() {
+            return Ok(());
+        }
+
+        // The transaction ends with a STOP, also when it fails or is cancelled.
+        let regs = self.info.regs;
+        let _stop = OnDrop::new(move || master_stop(regs));
+
+        let timeout = self.timeout();
+        let fut = transaction_interrupt(regs, self.info.state, Address::SevenBit(address), operations, timeout);
+        timeout.with(fut).await
+    }
+}
+
+impl<'d, M: Mode> AnyI2c<'d, M> {
+    fn new_inner<T: Instance>(
+        _peri: impl Peripheral<P = T> + 'd,
+        scl: impl Peripheral<P = impl SclPin<T>> + 'd,
+        sda: impl Peripheral<P = impl SdaPin<T>> + 'd,
+        freq: Hertz,
+        config: Config,
+    ) -> Self {
+        into_ref!(scl, sda);
+
+        T::enable_and_reset();
+
+        let scl = I2cPin {
+            af_num: scl.af_num(),
+            pull: match config.scl_pullup {
+                true => Pull::Up,
+                false => Pull::None,
+            },
+            pin: scl.map_into(),
+        };
+        let sda = I2cPin {
+            af_num: sda.af_num(),
+            pull: match config.sda_pullup {
+                true => Pull::Up,
+                false => Pull::None,
+            },
+            pin: sda.map_into(),
+        };
+        scl.set_as_af();
+        sda.set_as_af();
+
+        let info = Info::new::<T>();
+        init(info.regs, (info.frequency)(), freq);
+
+        Self {
+            info,
+            _scl: scl,
+            _sda: sda,
+            #[cfg(feature = "time")]
+            timeout: config.timeout,
+            phantom: PhantomData,
+        }
+    }
+
+    fn timeout(&self) -> Timeout {
+        Timeout {
+            #[cfg(feature = "time")]
+            deadline: Instant::now() + self.timeout,
+        }
+    }
+
+    /// Blocking read.
+    pub fn blocking_read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Error> {
+        read_internal(self.info.regs, Address::SevenBit(address), read, false, self.timeout())
+    }
+
+    /// Blocking write.
+    pub fn blocking_write(&mut self, address: u8, write: &[u8]) -> Result<(), Error> {
+        write_internal(self.info.regs, Address::SevenBit(address), write, true, self.timeout())
+    }
+
+    /// Blocking write, restart, read.
+    pub fn blocking_write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Error> {
+        let timeout = self.timeout();
+        write_internal(self.info.regs, Address::SevenBit(address), write, false, timeout)?;
+        read_internal(self.info.regs, Address::SevenBit(address), read, true, timeout)
+    }
+
+    /// Blocking transaction.
+    ///
+    /// Adjacent operations of the same direction are merged, a repeated START is sent between
+    /// operations of different directions, and a STOP ends the transaction.
+    pub fn blocking_transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Error> {
+        transaction_internal(self.info.regs, Address::SevenBit(address), operations, self.timeout())
+    }
+}
+
+impl<'d, M: Mode> Drop for AnyI2c<'d, M> {
+    fn drop(&mut self) {
+        (self.info.disable)();
+    }
+}
+
+impl<M: Mode> embedded_hal_1::i2c::ErrorType for AnyI2c<'_, M> {
+    type Error = Error;
+}
+
+impl<M: Mode> embedded_hal_1::i2c::I2c for AnyI2c<'_, M> {
+    fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Self::Error> {
+        self.blocking_read(address, read)
+    }
+
+    fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
+        self.blocking_write(address, write)
+    }
+
+    fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Self::Error> {
+        self.blocking_write_read(address, write, read)
+    }
+
+    fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+        self.blocking_transaction(address, operations)
+    }
+}
+
+impl embedded_hal_async::i2c::I2c for AnyI2c<'_, Async> {
+    async fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Self::Error> {
+        self.read(address, read).await
+    }
+
+    async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
+        self.write(address, write).await
+    }
+
+    async fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Self::Error> {
+        self.write_read(address, write, read).await
+    }
+
+    async fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+        self.transaction(address, operations).await
+    }
+}
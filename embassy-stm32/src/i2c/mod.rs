@@ -4,6 +4,10 @@
 #[cfg_attr(i2c_v1, path = "v1.rs")]
 #[cfg_attr(i2c_v2, path = "v2.rs")]
 mod _version;
+#[cfg(i2c_v2)]
+mod any;
+#[cfg(i2c_v2)]
+mod slave;
 
 use core::future::Future;
 use core::marker::PhantomData;
@@ -14,11 +18,16 @@ use embassy_sync::waitqueue::AtomicWaker;
 use embassy_time::{Duration, Instant};
 
 use crate::dma::NoDma;
-use crate::gpio::sealed::AFType;
-use crate::gpio::Pull;
+use crate::gpio::sealed::{AFType, Pin as _};
+use crate::gpio::{AnyPin, Level, OutputOpenDrain, Pull, Speed};
 use crate::interrupt::typelevel::Interrupt;
+use crate::rcc::AutoIdle;
 use crate::time::Hertz;
 use crate::{interrupt, peripherals};
+#[cfg(i2c_v2)]
+pub use any::AnyI2c;
+#[cfg(i2c_v2)]
+pub use slave::{I2cSlave, SlaveCommand, SlaveConfig};
 
 /// I2C error.
 #[derive(Debug, PartialEq, Eq)]
@@ -40,6 +49,26 @@ pub enum Error {
     ZeroLengthTransfer,
 }
 
+/// I2C slave address.
+///
+/// 10-bit addresses are only supported on the I2C v2 peripheral.
+#[cfg(i2c_v2)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Address {
+    /// 7-bit address.
+    SevenBit(u8),
+    /// 10-bit address.
+    TenBit(u16),
+}
+
+#[cfg(i2c_v2)]
+impl From<u8> for Address {
+    fn from(addr: u8) -> Self {
+        Self::SevenBit(addr)
+    }
+}
+
 /// I2C config
 #[non_exhaustive]
 #[derive(Copy, Clone)]
@@ -73,12 +102,15 @@ impl Default for Config {
 /// I2C driver.
 pub struct I2c<'d, T: Instance, TXDMA = NoDma, RXDMA = NoDma> {
     _peri: PeripheralRef<'d, T>,
+    scl: I2cPin<'d>,
+    sda: I2cPin<'d>,
     #[allow(dead_code)]
     tx_dma: PeripheralRef<'d, TXDMA>,
     #[allow(dead_code)]
     rx_dma: PeripheralRef<'d, RXDMA>,
     #[cfg(feature = "time")]
     timeout: Duration,
+    auto_idle: AutoIdle<T>,
 }
 
 impl<'d, T: Instance, TXDMA, RXDMA> I2c<'d, T, TXDMA, RXDMA> {
@@ -99,32 +131,37 @@ impl<'d, T: Instance, TXDMA, RXDMA> I2c<'d, T, TXDMA, RXDMA> {
 
         T::enable_and_reset();
 
-        scl.set_as_af_pull(
-            scl.af_num(),
-            AFType::OutputOpenDrain,
-            match config.scl_pullup {
+        let scl = I2cPin {
+            af_num: scl.af_num(),
+            pull: match config.scl_pullup {
                 true => Pull::Up,
                 false => Pull::None,
             },
-        );
-        sda.set_as_af_pull(
-            sda.af_num(),
-            AFType::OutputOpenDrain,
-            match config.sda_pullup {
+            pin: scl.map_into(),
+        };
+        let sda = I2cPin {
+            af_num: sda.af_num(),
+            pull: match config.sda_pullup {
                 true => Pull::Up,
                 false => Pull::None,
             },
-        );
+            pin: sda.map_into(),
+        };
+        scl.set_as_af();
+        sda.set_as_af();
 
         unsafe { T::EventInterrupt::enable() };
         unsafe { T::ErrorInterrupt::enable() };
 
         let mut this = Self {
             _peri: peri,
+            scl,
+            sda,
             tx_dma,
             rx_dma,
             #[cfg(feature = "time")]
             timeout: config.timeout,
+            auto_idle: AutoIdle::new(),
         };
 
         this.init(freq, config);
@@ -138,6 +175,119 @@ impl<'d, T: Instance, TXDMA, RXDMA> I2c<'d, T, TXDMA, RXDMA> {
             deadline: Instant::now() + self.timeout,
         }
     }
+
+    /// Enable or disable the auto idle mode.
+    ///
+    /// In the auto idle mode, the clock of the peripheral is gated whenever no transaction is in
+    /// flight, and enabled again by the next one, cutting the idle current. It is disabled by
+    /// default.
+    pub fn set_auto_idle(&mut self, enabled: bool) {
+        self.auto_idle.set(enabled);
+    }
+
+    /// Recover the bus after a glitch left a slave holding SDA low.
+    ///
+    /// The pins are temporarily driven as GPIOs: SCL is toggled up to 9 times until the slave
+    /// releases SDA, then a STOP condition is generated and the peripheral is restarted.
+    ///
+    /// Returns [`Error::Bus`] if SDA is still held low after the 9 clock pulses.
+    pub fn recover_bus(&mut self) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
+        T::regs().cr1().modify(|w| w.set_pe(false));
+
+        let released = {
+            let mut scl = OutputOpenDrain::new(self.scl.pin.reborrow(), Level::High, Speed::Low, self.scl.pull);
+            let mut sda = OutputOpenDrain::new(self.sda.pin.reborrow(), Level::High, Speed::Low, self.sda.pull);
+            recovery_delay();
+
+            for _ in 0..9 {
+                if sda.is_high() {
+                    break;
+                }
+                scl.set_low();
+                recovery_delay();
+                scl.set_high();
+                recovery_delay();
+            }
+            let released = sda.is_high();
+
+            // STOP condition: SDA rising while SCL is high.
+            scl.set_low();
+            recovery_delay();
+            sda.set_low();
+            recovery_delay();
+            scl.set_high();
+            recovery_delay();
+            sda.set_high();
+            recovery_delay();
+
+            released
+        };
+
+        self.scl.set_as_af();
+        self.sda.set_as_af();
+        T::regs().cr1().modify(|w| w.set_pe(true));
+
+        match released {
+            true => Ok(()),
+            false => Err(Error::Bus),
+        }
+    }
+}
+
+struct I2cPin<'d> {
+    pin: PeripheralRef<'d, AnyPin>,
+    af_num: u8,
+    pull: Pull,
+}
+
+impl<'d> I2cPin<'d> {
+    fn set_as_af(&self) {
+        self.pin.set_as_af_pull(self.af_num, AFType::OutputOpenDrain, self.pull);
+    }
+}
+
+/// Wait for half a period of a 100 kHz clock.
+fn recovery_delay() {
+    #[cfg(feature = "time")]
+    embassy_time::block_for(Duration::from_micros(5));
+    // The delay loop counts core cycles: the I2C kernel clock may run at another frequency.
+    #[cfg(not(feature = "time"))]
+    cortex_m::asm::delay(unsafe { crate::rcc::get_freqs() }.sys.unwrap().0 / 200_000);
+}
+
+fn operation_len(op: &embedded_hal_1::i2c::Operation<'_>) -> usize {
+    match op {
+        embedded_hal_1::i2c::Operation::Read(read) => read.len(),
+        embedded_hal_1::i2c::Operation::Write(write) => write.len(),
+    }
+}
+
+/// Iterator over the runs of adjacent operations of the same direction of a transaction.
+struct OperationGroups<'a, 'b> {
+    operations: &'a mut [embedded_hal_1::i2c::Operation<'b>],
+}
+
+impl<'a, 'b> OperationGroups<'a, 'b> {
+    fn new(operations: &'a mut [embedded_hal_1::i2c::Operation<'b>]) -> Self {
+        Self { operations }
+    }
+}
+
+impl<'a, 'b> Iterator for OperationGroups<'a, 'b> {
+    type Item = &'a mut [embedded_hal_1::i2c::Operation<'b>];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let is_read = matches!(self.operations.first()?, embedded_hal_1::i2c::Operation::Read(_));
+        let len = self
+            .operations
+            .iter()
+            .position(|op| matches!(op, embedded_hal_1::i2c::Operation::Read(_)) != is_read)
+            .unwrap_or(self.operations.len());
+        let (group, rest) = core::mem::take(&mut self.operations).split_at_mut(len);
+        self.operations = rest;
+        Some(group)
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -310,34 +460,10 @@ impl<'d, T: Instance> embedded_hal_1::i2c::I2c for I2c<'d, T, NoDma, NoDma> {
     }
 
     fn transaction(
-        &mut self,
-        _address: u8,
-        _operations: &mut [embedded_hal_1::i2c::Operation<'_>],
-    ) -> Result<(), Self::Error> {
-        todo!();
-    }
-}
-
-impl<'d, T: Instance, TXDMA: TxDma<T>, RXDMA: RxDma<T>> embedded_hal_async::i2c::I2c for I2c<'d, T, TXDMA, RXDMA> {
-    async fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Self::Error> {
-        self.read(address, read).await
-    }
-
-    async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
-        self.write(address, write).await
-    }
-
-    async fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Self::Error> {
-        self.write_read(address, write, read).await
-    }
-
-    async fn transaction(
         &mut self,
         address: u8,
         operations: &mut [embedded_hal_1::i2c::Operation<'_>],
     ) -> Result<(), Self::Error> {
-        let _ = address;
-        let _ = operations;
-        todo!()
+        self.blocking_transaction(address, operations)
     }
 }
@@ -0,0 +1,341 @@
+//! I2C slave mode.
+//!
+//! The peripheral listens on its own address, and the application responds to each transaction
+//! started by the master. Data is streamed with DMA, and the clock is stretched while the
+//! application prepares its response.
+use core::future::poll_fn;
+use core::task::Poll;
+
+use embassy_hal_internal::drop::OnDrop;
+
+use super::*;
+use crate::dma::Transfer;
+use crate::pac::i2c;
+
+/// Transaction requested by the master.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SlaveCommand {
+    /// The master reads from this slave, respond with [`I2cSlave::respond_to_read`].
+    Read,
+    /// The master writes to this slave, respond with [`I2cSlave::respond_to_write`].
+    Write,
+}
+
+/// I2C slave config.
+#[non_exhaustive]
+#[derive(Copy, Clone)]
+pub struct SlaveConfig {
+    /// Address the slave listens on.
+    pub address: Address,
+    /// Also respond to the general call address (0x00).
+    pub general_call: bool,
+    /// Enable internal pullup on SDA.
+    pub sda_pullup: bool,
+    /// Enable internal pullup on SCL.
+    pub scl_pullup: bool,
+}
+
+impl SlaveConfig {
+    /// Create a config listening on `address`.
+    pub fn new(address: Address) -> Self {
+        Self {
+            address,
+            general_call: false,
+            sda_pullup: false,
+            scl_pullup: false,
+        }
+    }
+}
+
+/// I2C slave driver.
+pub struct I2cSlave<'d, T: Instance, TXDMA = NoDma, RXDMA = NoDma> {
+    _peri: PeripheralRef<'d, T>,
+    #[allow(dead_code)]
+    tx_dma: PeripheralRef<'d, TXDMA>,
+    #[allow(dead_code)]
+    rx_dma: PeripheralRef<'d, RXDMA>,
+}
+
+impl<'d, T: Instance, TXDMA, RXDMA> I2cSlave<'d, T, TXDMA, RXDMA> {
+    /// Create a new I2C slave driver.
+    pub fn new(
+        peri: impl Peripheral<P = T> + 'd,
+        scl: impl Peripheral<P = impl SclPin<T>> + 'd,
+        sda: impl Peripheral<P = impl SdaPin<T>> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::EventInterrupt, EventInterruptHandler<T>>
+            + interrupt::typelevel::Binding<T::ErrorInterrupt, ErrorInterruptHandler<T>>
+            + 'd,
+        tx_dma: impl Peripheral<P = TXDMA> + 'd,
+        rx_dma: impl Peripheral<P = RXDMA> + 'd,
+        config: SlaveConfig,
+    ) -> Self {
+        into_ref!(peri, scl, sda, tx_dma, rx_dma);
+
+        T::enable_and_reset();
+
+        scl.set_as_af_pull(
+            scl.af_num(),
+            AFType::OutputOpenDrain,
+            match config.scl_pullup {
+                true => Pull::Up,
+                false => Pull::None,
+            },
+        );
+        sda.set_as_af_pull(
+            sda.af_num(),
+            AFType::OutputOpenDrain,
+            match config.sda_pullup {
+                true => Pull::Up,
+                false => Pull::None,
+            },
+        );
+
+        let regs = T::regs();
+        regs.cr1().modify(|w| w.set_pe(false));
+
+        // In slave mode only the data setup and hold times are used. Use a ~125 ns
+        // prescaled clock: 250 ns hold and 625 ns setup time are fine up to fast mode.
+        let presc = (T::frequency().0 / 8_000_000).saturating_sub(1).min(15);
+        regs.timingr().write(|w| {
+            w.set_presc(presc as u8);
+            w.set_sdadel(2);
+            w.set_scldel(4);
+        });
+
+        regs.oar1().write(|w| w.set_oa1en(false));
+        regs.oar1().write(|w| {
+            match config.address {
+                Address::SevenBit(addr) => {
+                    w.set_oa1((addr as u16) << 1);
+                    w.set_oa1mode(i2c::vals::Oamode::BIT7);
+                }
+                Address::TenBit(addr) => {
+                    w.set_oa1(addr & 0x3FF);
+                    w.set_oa1mode(i2c::vals::Oamode::BIT10);
+                }
+            }
+            w.set_oa1en(true);
+        });
+
+        regs.cr1().modify(|w| {
+            w.set_gcen(config.general_call);
+            // Clock stretching is needed to hold the master until the application responds.
+            w.set_nostretch(false);
+            w.set_anfoff(false);
+            w.set_pe(true);
+        });
+
+        unsafe { T::EventInterrupt::enable() };
+        unsafe { T::ErrorInterrupt::enable() };
+
+        Self {
+            _peri: peri,
+            tx_dma,
+            rx_dma,
+        }
+    }
+
+    /// Wait for the master to address this slave.
+    ///
+    /// The clock is stretched until the transaction is answered with
+    /// [`respond_to_read`](Self::respond_to_read) or [`respond_to_write`](Self::respond_to_write).
+    pub async fn listen(&mut self) -> Result<SlaveCommand, Error> {
+        let _on_drop = OnDrop::new(disable_interrupts::<T>);
+
+        poll_fn(|cx| {
+            T::state().waker.register(cx.waker());
+
+            let isr = T::regs().isr().read();
+            if isr.addr() {
+                return Poll::Ready(Ok(match isr.dir() {
+                    i2c::vals::Dir::READ => SlaveCommand::Read,
+                    i2c::vals::Dir::WRITE => SlaveCommand::Write,
+                }));
+            }
+            if let Err(e) = check_errors::<T>(isr) {
+                return Poll::Ready(Err(e));
+            }
+
+            enable_interrupts::<T>(false, false);
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+impl<'d, T: Instance, TXDMA: super::TxDma<T>, RXDMA> I2cSlave<'d, T, TXDMA, RXDMA> {
+    /// Send `data` to the master, after [`listen`](Self::listen) returned [`SlaveCommand::Read`].
+    ///
+    /// If the master reads more bytes than `data` holds, `0xFF` is sent until it ends the
+    /// transaction with a NACK.
+    pub async fn respond_to_read(&mut self, data: &[u8]) -> Result<(), Error> {
+        let regs = T::regs();
+
+        // Discard a byte left in the data register by a previous transaction.
+        regs.isr().modify(|w| w.set_txe(true));
+
+        let _on_drop = OnDrop::new(|| {
+            T::regs().cr1().modify(|w| w.set_txdmaen(false));
+            disable_interrupts::<T>();
+        });
+
+        let mut transfer = match data.is_empty() {
+            true => None,
+            false => unsafe {
+                regs.cr1().modify(|w| w.set_txdmaen(true));
+                let dst = regs.txdr().as_ptr() as *mut u8;
+
+                let ch = &mut self.tx_dma;
+                let request = ch.request();
+                Some(Transfer::new_write(ch, request, data, dst, Default::default()))
+            },
+        };
+
+        // Release the clock.
+        regs.icr().write(|w| w.set_addrcf(true));
+
+        let result = poll_fn(|cx| {
+            T::state().waker.register(cx.waker());
+
+            let isr = regs.isr().read();
+            if isr.stopf() {
+                regs.icr().write(|w| w.set_stopcf(true));
+                return Poll::Ready(Ok(()));
+            }
+            if isr.addr() {
+                // Repeated start
+                return Poll::Ready(Ok(()));
+            }
+            if isr.nackf() {
+                // The master does not want more data, wait for the STOP or repeated start.
+                regs.icr().write(|w| w.set_nackcf(true));
+            }
+            if let Err(e) = check_errors::<T>(isr) {
+                return Poll::Ready(Err(e));
+            }
+
+            let dma_done = transfer.as_mut().map_or(true, |t| !t.is_running());
+            if dma_done && isr.txis() {
+                regs.txdr().write(|w| w.set_txdata(0xFF));
+            }
+
+            enable_interrupts::<T>(dma_done, false);
+            Poll::Pending
+        })
+        .await;
+
+        drop(transfer);
+
+        // Discard the byte loaded by the DMA that the master did not read.
+        regs.isr().modify(|w| w.set_txe(true));
+
+        result
+    }
+}
+
+impl<'d, T: Instance, TXDMA, RXDMA: super::RxDma<T>> I2cSlave<'d, T, TXDMA, RXDMA> {
+    /// Receive data from the master, after [`listen`](Self::listen) returned [`SlaveCommand::Write`].
+    ///
+    /// Returns the number of bytes received, once the master ends the transaction with a STOP or a
+    /// repeated start. Bytes that don't fit in `buffer` are NACKed.
+    pub async fn respond_to_write(&mut self, buffer: &mut [u8]) -> Result<usize, Error> {
+        let regs = T::regs();
+        let len = buffer.len();
+
+        let _on_drop = OnDrop::new(|| {
+            T::regs().cr1().modify(|w| w.set_rxdmaen(false));
+            disable_interrupts::<T>();
+        });
+
+        let mut transfer = match buffer.is_empty() {
+            true => None,
+            false => unsafe {
+                regs.cr1().modify(|w| w.set_rxdmaen(true));
+                let src = regs.rxdr().as_ptr() as *mut u8;
+
+                let ch = &mut self.rx_dma;
+                let request = ch.request();
+                Some(Transfer::new_read(ch, request, src, buffer, Default::default()))
+            },
+        };
+
+        // Release the clock.
+        regs.icr().write(|w| w.set_addrcf(true));
+
+        let result = poll_fn(|cx| {
+            T::state().waker.register(cx.waker());
+
+            let isr = regs.isr().read();
+            if isr.stopf() {
+                regs.icr().write(|w| w.set_stopcf(true));
+                return Poll::Ready(Ok(()));
+            }
+            if isr.addr() {
+                // Repeated start
+                return Poll::Ready(Ok(()));
+            }
+            if let Err(e) = check_errors::<T>(isr) {
+                return Poll::Ready(Err(e));
+            }
+
+            let dma_done = transfer.as_mut().map_or(true, |t| !t.is_running());
+            if dma_done && isr.rxne() {
+                // The buffer is full, drop the byte and NACK the next ones.
+                regs.cr2().modify(|w| w.set_nack(true));
+                let _ = regs.rxdr().read();
+            }
+
+            enable_interrupts::<T>(false, dma_done);
+            Poll::Pending
+        })
+        .await;
+
+        let received = len - transfer.as_ref().map_or(0, |t| t.get_remaining_transfers() as usize);
+        drop(transfer);
+
+        result.map(|_| received)
+    }
+}
+
+impl<'d, T: Instance, TXDMA, RXDMA> Drop for I2cSlave<'d, T, TXDMA, RXDMA> {
+    fn drop(&mut self) {
+        T::disable();
+    }
+}
+
+fn check_errors<T: Instance>(isr: i2c::regs::Isr) -> Result<(), Error> {
+    let regs = T::regs();
+    if isr.berr() {
+        regs.icr().write(|w| w.set_berrcf(true));
+        Err(Error::Bus)
+    } else if isr.ovr() {
+        regs.icr().write(|w| w.set_ovrcf(true));
+        Err(Error::Overrun)
+    } else {
+        Ok(())
+    }
+}
+
+/// Enable the slave interrupts, they are disabled again by the interrupt handler.
+fn enable_interrupts<T: Instance>(tx: bool, rx: bool) {
+    T::regs().cr1().modify(|w| {
+        w.set_addrie(true);
+        w.set_stopie(true);
+        w.set_nackie(true);
+        w.set_errie(true);
+        w.set_txie(tx);
+        w.set_rxie(rx);
+    });
+}
+
+fn disable_interrupts<T: Instance>() {
+    T::regs().cr1().modify(|w| {
+        w.set_addrie(false);
+        w.set_stopie(false);
+        w.set_nackie(false);
+        w.set_errie(false);
+        w.set_txie(false);
+        w.set_rxie(false);
+    });
+}
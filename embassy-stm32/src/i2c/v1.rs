@@ -10,6 +10,7 @@ use core::task::Poll;
 use embassy_embedded_hal::SetConfig;
 use embassy_futures::select::{select, Either};
 use embassy_hal_internal::drop::OnDrop;
+use embedded_hal_1::i2c::Operation;
 
 use super::*;
 use crate::dma::Transfer;
@@ -266,11 +267,13 @@ impl<'d, T: Instance, TXDMA, RXDMA> I2c<'d, T, TXDMA, RXDMA> {
 
     /// Blocking read.
     pub fn blocking_read(&mut self, addr: u8, read: &mut [u8]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
         self.blocking_read_timeout(addr, read, self.timeout())
     }
 
     /// Blocking write.
     pub fn blocking_write(&mut self, addr: u8, write: &[u8]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
         let timeout = self.timeout();
 
         self.write_bytes(addr, write, timeout)?;
@@ -287,6 +290,7 @@ impl<'d, T: Instance, TXDMA, RXDMA> I2c<'d, T, TXDMA, RXDMA> {
 
     /// Blocking write, restart, read.
     pub fn blocking_write_read(&mut self, addr: u8, write: &[u8], read: &mut [u8]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
         let timeout = self.timeout();
 
         self.write_bytes(addr, write, timeout)?;
@@ -295,6 +299,142 @@ impl<'d, T: Instance, TXDMA, RXDMA> I2c<'d, T, TXDMA, RXDMA> {
         Ok(())
     }
 
+    /// Blocking transaction.
+    ///
+    /// Adjacent operations of the same direction are merged, a repeated START is sent between
+    /// operations of different directions, and the transaction ends with a STOP, also on errors.
+    pub fn blocking_transaction(&mut self, addr: u8, operations: &mut [Operation<'_>]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
+        if operations.is_empty() {
+            return Ok(());
+        }
+
+        let timeout = self.timeout();
+        let mut groups = OperationGroups::new(operations).peekable();
+        while let Some(group) = groups.next() {
+            let last = groups.peek().is_none();
+            let result = if matches!(group[0], Operation::Read(_)) {
+                self.read_group_blocking(addr, group, last, timeout)
+            } else {
+                self.write_group_blocking(addr, group, last, timeout)
+            };
+            if let Err(e) = result {
+                T::regs().cr1().modify(|reg| reg.set_stop(true));
+                return Err(e);
+            }
+        }
+
+        // Wait for the STOP to be sent.
+        while T::regs().cr1().read().stop() {
+            timeout.check()?;
+        }
+        Ok(())
+    }
+
+    /// Wait until `ready` returns true for SR1, or an I2C error or the timeout occurs.
+    fn wait_sr1_blocking(&self, ready: impl Fn(i2c::regs::Sr1) -> bool, timeout: Timeout) -> Result<(), Error> {
+        while !ready(Self::check_and_clear_error_flags()?) {
+            timeout.check()?;
+        }
+        Ok(())
+    }
+
+    /// Send a START condition and the address, and wait for it to be acknowledged.
+    ///
+    /// The ADDR flag is left set, for the caller to clear it by reading SR2.
+    fn start_blocking(&self, address: u8, read: bool, timeout: Timeout) -> Result<(), Error> {
+        T::regs().cr1().modify(|reg| {
+            reg.set_start(true);
+            reg.set_ack(true);
+        });
+        self.wait_sr1_blocking(|sr1| sr1.start(), timeout)?;
+
+        T::regs().dr().write(|reg| reg.set_dr((address << 1) | read as u8));
+        // If a NACK occurs, ADDR is never set and the AF error is returned.
+        self.wait_sr1_blocking(|sr1| sr1.addr(), timeout)
+    }
+
+    fn write_group_blocking(
+        &self,
+        address: u8,
+        group: &[Operation<'_>],
+        last: bool,
+        timeout: Timeout,
+    ) -> Result<(), Error> {
+        self.start_blocking(address, false, timeout)?;
+        // Clear ADDR condition by reading SR2
+        T::regs().sr2().read();
+
+        let mut written = false;
+        for op in group {
+            if let Operation::Write(write) = op {
+                for byte in write.iter() {
+                    self.wait_sr1_blocking(|sr1| sr1.txe(), timeout)?;
+                    T::regs().dr().write(|reg| reg.set_dr(*byte));
+                    written = true;
+                }
+            }
+        }
+
+        // Wait until the last byte is transferred, BTF is never set if none was written.
+        if written {
+            self.wait_sr1_blocking(|sr1| sr1.btf(), timeout)?;
+        }
+        if last {
+            T::regs().cr1().modify(|reg| reg.set_stop(true));
+        }
+        Ok(())
+    }
+
+    fn read_group_blocking(
+        &self,
+        address: u8,
+        group: &mut [Operation<'_>],
+        last: bool,
+        timeout: Timeout,
+    ) -> Result<(), Error> {
+        let total_len: usize = group.iter().map(operation_len).sum();
+        if total_len == 0 {
+            return Err(Error::ZeroLengthTransfer);
+        }
+
+        // NACK the last byte, then end the transaction or restart for the next group.
+        let end = || {
+            T::regs().cr1().modify(|reg| {
+                reg.set_ack(false);
+                if last {
+                    reg.set_stop(true);
+                } else {
+                    reg.set_start(true);
+                }
+            })
+        };
+
+        self.start_blocking(address, true, timeout)?;
+        // 18.3.8: When a single byte must be received: the NACK must be programmed during EV6
+        // event, i.e. program ACK=0 when ADDR=1, before clearing ADDR flag.
+        if total_len == 1 {
+            T::regs().cr1().modify(|reg| reg.set_ack(false));
+        }
+        // Clear ADDR condition by reading SR2
+        T::regs().sr2().read();
+
+        let mut remaining = total_len;
+        for op in group {
+            if let Operation::Read(read) = op {
+                for byte in read.iter_mut() {
+                    remaining -= 1;
+                    if remaining == 0 {
+                        end();
+                    }
+                    self.wait_sr1_blocking(|sr1| sr1.rxne(), timeout)?;
+                    *byte = T::regs().dr().read().dr();
+                }
+            }
+        }
+        Ok(())
+    }
+
     // Async
 
     #[inline] // pretty sure this should always be inlined
@@ -305,10 +445,153 @@ impl<'d, T: Instance, TXDMA, RXDMA> I2c<'d, T, TXDMA, RXDMA> {
         });
     }
 
-    async fn write_with_stop(&mut self, address: u8, write: &[u8], send_stop: bool) -> Result<(), Error>
-    where
-        TXDMA: crate::i2c::TxDma<T>,
-    {
+    /// Wait until `ready` returns true for SR1, or an I2C error occurs.
+    async fn wait_sr1(&self, ready: impl Fn(i2c::regs::Sr1) -> bool) -> Result<(), Error> {
+        poll_fn(|cx| {
+            T::state().waker.register(cx.waker());
+
+            if ready(Self::check_and_clear_error_flags()?) {
+                Poll::Ready(Ok(()))
+            } else {
+                // The interrupt handler disabled the interrupts, nothing could wake us up otherwise.
+                Self::enable_interrupts();
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Send a START condition and the address, and wait for it to be acknowledged.
+    ///
+    /// The ADDR flag is left set, for the caller to clear it by reading SR2.
+    async fn start_interrupt(&self, address: u8, read: bool) -> Result<(), Error> {
+        T::regs().cr1().modify(|reg| {
+            reg.set_start(true);
+            reg.set_ack(true);
+        });
+        self.wait_sr1(|sr1| sr1.start()).await?;
+
+        T::regs().dr().write(|reg| reg.set_dr((address << 1) | read as u8));
+        // If a NACK occurs, ADDR is never set and the AF error is returned.
+        self.wait_sr1(|sr1| sr1.addr()).await
+    }
+
+    async fn write_group_interrupt(&self, address: u8, group: &[Operation<'_>], last: bool) -> Result<(), Error> {
+        self.start_interrupt(address, false).await?;
+        // Clear ADDR condition by reading SR2
+        T::regs().sr2().read();
+
+        let mut written = false;
+        for op in group {
+            if let Operation::Write(write) = op {
+                for byte in write.iter() {
+                    self.wait_sr1(|sr1| sr1.txe()).await?;
+                    T::regs().dr().write(|reg| reg.set_dr(*byte));
+                    written = true;
+                }
+            }
+        }
+
+        // Wait until the last byte is transferred, BTF is never set if none was written.
+        if written {
+            self.wait_sr1(|sr1| sr1.btf()).await?;
+        }
+        if last {
+            T::regs().cr1().modify(|reg| reg.set_stop(true));
+        }
+        Ok(())
+    }
+
+    async fn read_group_interrupt(&self, address: u8, group: &mut [Operation<'_>], last: bool) -> Result<(), Error> {
+        let total_len: usize = group.iter().map(operation_len).sum();
+        if total_len == 0 {
+            return Err(Error::ZeroLengthTransfer);
+        }
+
+        // NACK the last byte, then end the transaction or restart for the next group.
+        let end = || {
+            T::regs().cr1().modify(|reg| {
+                reg.set_ack(false);
+                if last {
+                    reg.set_stop(true);
+                } else {
+                    reg.set_start(true);
+                }
+            })
+        };
+
+        self.start_interrupt(address, true).await?;
+        // 18.3.8: When a single byte must be received: the NACK must be programmed during EV6
+        // event, i.e. program ACK=0 when ADDR=1, before clearing ADDR flag.
+        if total_len == 1 {
+            T::regs().cr1().modify(|reg| reg.set_ack(false));
+        }
+        // Clear ADDR condition by reading SR2
+        T::regs().sr2().read();
+
+        let mut remaining = total_len;
+        for op in group {
+            if let Operation::Read(read) = op {
+                for byte in read.iter_mut() {
+                    remaining -= 1;
+                    if remaining == 0 {
+                        end();
+                    }
+                    self.wait_sr1(|sr1| sr1.rxne()).await?;
+                    *byte = T::regs().dr().read().dr();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Transaction, driven by the I2C interrupts, also when the driver has DMA channels.
+    async fn transaction_addr(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
+        if operations.is_empty() {
+            return Ok(());
+        }
+
+        self.timeout()
+            .with(self.transaction_interrupt(address, operations))
+            .await
+    }
+
+    async fn transaction_interrupt(&self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Error> {
+        T::regs().cr2().modify(|w| {
+            w.set_dmaen(false);
+            w.set_itbufen(true);
+        });
+        let _interrupts = OnDrop::new(|| {
+            T::regs().cr2().modify(|w| {
+                w.set_itbufen(false);
+                w.set_iterren(false);
+                w.set_itevten(false);
+            })
+        });
+        // A failed or cancelled transaction ends with a STOP.
+        let stop = OnDrop::new(|| T::regs().cr1().modify(|reg| reg.set_stop(true)));
+
+        let mut groups = OperationGroups::new(operations).peekable();
+        while let Some(group) = groups.next() {
+            let last = groups.peek().is_none();
+            if matches!(group[0], Operation::Read(_)) {
+                self.read_group_interrupt(address, group, last).await?;
+            } else {
+                self.write_group_interrupt(address, group, last).await?;
+            }
+        }
+        stop.defuse();
+
+        // No interrupt signals the end of the STOP condition, which is generated right after the
+        // last byte.
+        while T::regs().cr1().read().stop() {}
+        Ok(())
+    }
+}
+
+impl<'d, T: Instance, TXDMA: crate::i2c::TxDma<T>, RXDMA> I2c<'d, T, TXDMA, RXDMA> {
+    async fn write_with_stop(&mut self, address: u8, write: &[u8], send_stop: bool) -> Result<(), Error> {
         let dma_transfer = unsafe {
             let regs = T::regs();
             regs.cr2().modify(|w| {
@@ -459,10 +742,8 @@ impl<'d, T: Instance, TXDMA, RXDMA> I2c<'d, T, TXDMA, RXDMA> {
     }
 
     /// Write.
-    pub async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Error>
-    where
-        TXDMA: crate::i2c::TxDma<T>,
-    {
+    pub async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
         self.write_with_stop(address, write, true).await?;
 
         // Wait for STOP condition to transmit.
@@ -480,12 +761,12 @@ impl<'d, T: Instance, TXDMA, RXDMA> I2c<'d, T, TXDMA, RXDMA> {
 
         Ok(())
     }
+}
 
+impl<'d, T: Instance, TXDMA, RXDMA: crate::i2c::RxDma<T>> I2c<'d, T, TXDMA, RXDMA> {
     /// Read.
-    pub async fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Error>
-    where
-        RXDMA: crate::i2c::RxDma<T>,
-    {
+    pub async fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
         let state = T::state();
         let buffer_len = buffer.len();
 
@@ -640,20 +921,87 @@ impl<'d, T: Instance, TXDMA, RXDMA> I2c<'d, T, TXDMA, RXDMA> {
         // Fallthrough is success
         Ok(())
     }
+}
 
+impl<'d, T: Instance, TXDMA: crate::i2c::TxDma<T>, RXDMA: crate::i2c::RxDma<T>> I2c<'d, T, TXDMA, RXDMA> {
     /// Write, restart, read.
-    pub async fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Error>
-    where
-        RXDMA: crate::i2c::RxDma<T>,
-        TXDMA: crate::i2c::TxDma<T>,
-    {
+    pub async fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
         self.write_with_stop(address, write, false).await?;
         self.read(address, read).await
     }
 }
 
+impl<'d, T: Instance> I2c<'d, T, NoDma, NoDma> {
+    // =========================
+    //  Async public API, without DMA
+
+    /// Execute the provided operations on the I2C bus, driven by the I2C interrupts.
+    ///
+    /// Adjacent operations of the same direction are merged, a repeated START is sent between
+    /// operations of different directions, and the transaction ends with a STOP.
+    pub async fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Error> {
+        self.transaction_addr(address, operations).await
+    }
+
+    /// Write, driven by the I2C interrupts.
+    pub async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Error> {
+        self.transaction(address, &mut [Operation::Write(write)]).await
+    }
+
+    /// Read, driven by the I2C interrupts.
+    pub async fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Error> {
+        self.transaction(address, &mut [Operation::Read(buffer)]).await
+    }
+
+    /// Write, restart, read, driven by the I2C interrupts.
+    pub async fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Error> {
+        self.transaction(address, &mut [Operation::Write(write), Operation::Read(read)])
+            .await
+    }
+}
+
+impl<'d, T: Instance, TXDMA: crate::i2c::TxDma<T>, RXDMA: crate::i2c::RxDma<T>> embedded_hal_async::i2c::I2c
+    for I2c<'d, T, TXDMA, RXDMA>
+{
+    async fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Self::Error> {
+        self.read(address, read).await
+    }
+
+    async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
+        self.write(address, write).await
+    }
+
+    async fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Self::Error> {
+        self.write_read(address, write, read).await
+    }
+
+    async fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+        self.transaction_addr(address, operations).await
+    }
+}
+
+impl<'d, T: Instance> embedded_hal_async::i2c::I2c for I2c<'d, T, NoDma, NoDma> {
+    async fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Self::Error> {
+        self.read(address, read).await
+    }
+
+    async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
+        self.write(address, write).await
+    }
+
+    async fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Self::Error> {
+        self.write_read(address, write, read).await
+    }
+
+    async fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+        self.transaction(address, operations).await
+    }
+}
+
 impl<'d, T: Instance, TXDMA, RXDMA> Drop for I2c<'d, T, TXDMA, RXDMA> {
     fn drop(&mut self) {
+        self.auto_idle.set(false);
         T::disable();
     }
 }
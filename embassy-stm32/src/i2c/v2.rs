@@ -4,315 +4,177 @@ use core::task::Poll;
 
 use embassy_embedded_hal::SetConfig;
 use embassy_hal_internal::drop::OnDrop;
+use embedded_hal_1::i2c::{Operation, TenBitAddress};
 
 use super::*;
 use crate::dma::Transfer;
 use crate::pac::i2c;
+use crate::pac::i2c::I2c as Regs;
 use crate::time::Hertz;
 
 pub(crate) unsafe fn on_interrupt<T: Instance>() {
     let regs = T::regs();
     let isr = regs.isr().read();
 
-    if isr.tcr() || isr.tc() {
+    if isr.tcr()
+        || isr.tc()
+        || isr.txis()
+        || isr.rxne()
+        || isr.nackf()
+        || isr.berr()
+        || isr.arlo()
+        || isr.ovr()
+        || isr.addr()
+        || isr.stopf()
+    {
         T::state().waker.wake();
     }
-    // The flag can only be cleared by writting to nbytes, we won't do that here, so disable
-    // the interrupt
+    // The flags can only be cleared by writting to nbytes or the data registers, we won't do
+    // that here, so disable the interrupts
     critical_section::with(|_| {
-        regs.cr1().modify(|w| w.set_tcie(false));
+        regs.cr1().modify(|w| {
+            w.set_tcie(false);
+            w.set_txie(false);
+            w.set_rxie(false);
+            w.set_nackie(false);
+            w.set_errie(false);
+            w.set_addrie(false);
+            w.set_stopie(false);
+        });
     });
 }
 
 impl<'d, T: Instance, TXDMA, RXDMA> I2c<'d, T, TXDMA, RXDMA> {
     pub(crate) fn init(&mut self, freq: Hertz, _config: Config) {
-        T::regs().cr1().modify(|reg| {
-            reg.set_pe(false);
-            reg.set_anfoff(false);
-        });
-
-        let timings = Timings::new(T::frequency(), freq.into());
-
-        T::regs().timingr().write(|reg| {
-            reg.set_presc(timings.prescale);
-            reg.set_scll(timings.scll);
-            reg.set_sclh(timings.sclh);
-            reg.set_sdadel(timings.sdadel);
-            reg.set_scldel(timings.scldel);
-        });
-
-        T::regs().cr1().modify(|reg| {
-            reg.set_pe(true);
-        });
-    }
-
-    fn master_stop(&mut self) {
-        T::regs().cr2().write(|w| w.set_stop(true));
-    }
-
-    fn master_read(
-        address: u8,
-        length: usize,
-        stop: Stop,
-        reload: bool,
-        restart: bool,
-        timeout: Timeout,
-    ) -> Result<(), Error> {
-        assert!(length < 256);
-
-        if !restart {
-            // Wait for any previous address sequence to end
-            // automatically. This could be up to 50% of a bus
-            // cycle (ie. up to 0.5/freq)
-            while T::regs().cr2().read().start() {
-                timeout.check()?;
-            }
-        }
-
-        // Set START and prepare to receive bytes into
-        // `buffer`. The START bit can be set even if the bus
-        // is BUSY or I2C is in slave mode.
-
-        let reload = if reload {
-            i2c::vals::Reload::NOTCOMPLETED
-        } else {
-            i2c::vals::Reload::COMPLETED
-        };
-
-        T::regs().cr2().modify(|w| {
-            w.set_sadd((address << 1 | 0) as u16);
-            w.set_add10(i2c::vals::Addmode::BIT7);
-            w.set_dir(i2c::vals::Dir::READ);
-            w.set_nbytes(length as u8);
-            w.set_start(true);
-            w.set_autoend(stop.autoend());
-            w.set_reload(reload);
-        });
-
-        Ok(())
-    }
-
-    fn master_write(address: u8, length: usize, stop: Stop, reload: bool, timeout: Timeout) -> Result<(), Error> {
-        assert!(length < 256);
-
-        // Wait for any previous address sequence to end
-        // automatically. This could be up to 50% of a bus
-        // cycle (ie. up to 0.5/freq)
-        while T::regs().cr2().read().start() {
-            timeout.check()?;
-        }
-
-        let reload = if reload {
-            i2c::vals::Reload::NOTCOMPLETED
-        } else {
-            i2c::vals::Reload::COMPLETED
-        };
-
-        // Set START and prepare to send `bytes`. The
-        // START bit can be set even if the bus is BUSY or
-        // I2C is in slave mode.
-        T::regs().cr2().modify(|w| {
-            w.set_sadd((address << 1 | 0) as u16);
-            w.set_add10(i2c::vals::Addmode::BIT7);
-            w.set_dir(i2c::vals::Dir::WRITE);
-            w.set_nbytes(length as u8);
-            w.set_start(true);
-            w.set_autoend(stop.autoend());
-            w.set_reload(reload);
-        });
-
-        Ok(())
+        init(T::regs(), T::frequency(), freq);
     }
 
-    fn master_continue(length: usize, reload: bool, timeout: Timeout) -> Result<(), Error> {
-        assert!(length < 256 && length > 0);
-
-        while !T::regs().isr().read().tcr() {
-            timeout.check()?;
-        }
-
-        let reload = if reload {
-            i2c::vals::Reload::NOTCOMPLETED
-        } else {
-            i2c::vals::Reload::COMPLETED
-        };
-
-        T::regs().cr2().modify(|w| {
-            w.set_nbytes(length as u8);
-            w.set_reload(reload);
-        });
+    // =========================
+    //  Blocking public API
 
-        Ok(())
+    /// Blocking read.
+    pub fn blocking_read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
+        read_internal(T::regs(), address.into(), read, false, self.timeout())
+        // Automatic Stop
     }
 
-    fn flush_txdr(&self) {
-        if T::regs().isr().read().txis() {
-            T::regs().txdr().write(|w| w.set_txdata(0));
-        }
-        if !T::regs().isr().read().txe() {
-            T::regs().isr().modify(|w| w.set_txe(true))
-        }
+    /// Blocking write.
+    pub fn blocking_write(&mut self, address: u8, write: &[u8]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
+        write_internal(T::regs(), address.into(), write, true, self.timeout())
     }
 
-    fn wait_txe(&self, timeout: Timeout) -> Result<(), Error> {
-        loop {
-            let isr = T::regs().isr().read();
-            if isr.txe() {
-                return Ok(());
-            } else if isr.berr() {
-                T::regs().icr().write(|reg| reg.set_berrcf(true));
-                return Err(Error::Bus);
-            } else if isr.arlo() {
-                T::regs().icr().write(|reg| reg.set_arlocf(true));
-                return Err(Error::Arbitration);
-            } else if isr.nackf() {
-                T::regs().icr().write(|reg| reg.set_nackcf(true));
-                self.flush_txdr();
-                return Err(Error::Nack);
-            }
-
-            timeout.check()?;
-        }
+    /// Blocking write, restart, read.
+    pub fn blocking_write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
+        let timeout = self.timeout();
+        write_internal(T::regs(), address.into(), write, false, timeout)?;
+        read_internal(T::regs(), address.into(), read, true, timeout)
+        // Automatic Stop
     }
 
-    fn wait_rxne(&self, timeout: Timeout) -> Result<(), Error> {
-        loop {
-            let isr = T::regs().isr().read();
-            if isr.rxne() {
-                return Ok(());
-            } else if isr.berr() {
-                T::regs().icr().write(|reg| reg.set_berrcf(true));
-                return Err(Error::Bus);
-            } else if isr.arlo() {
-                T::regs().icr().write(|reg| reg.set_arlocf(true));
-                return Err(Error::Arbitration);
-            } else if isr.nackf() {
-                T::regs().icr().write(|reg| reg.set_nackcf(true));
-                self.flush_txdr();
-                return Err(Error::Nack);
-            }
-
-            timeout.check()?;
-        }
+    /// Blocking transaction.
+    ///
+    /// Adjacent operations of the same direction are merged, a repeated START is sent between
+    /// operations of different directions, and the transaction ends with a STOP, also on errors.
+    pub fn blocking_transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
+        transaction_internal(T::regs(), address.into(), operations, self.timeout())
     }
 
-    fn wait_tc(&self, timeout: Timeout) -> Result<(), Error> {
-        loop {
-            let isr = T::regs().isr().read();
-            if isr.tc() {
-                return Ok(());
-            } else if isr.berr() {
-                T::regs().icr().write(|reg| reg.set_berrcf(true));
-                return Err(Error::Bus);
-            } else if isr.arlo() {
-                T::regs().icr().write(|reg| reg.set_arlocf(true));
-                return Err(Error::Arbitration);
-            } else if isr.nackf() {
-                T::regs().icr().write(|reg| reg.set_nackcf(true));
-                self.flush_txdr();
-                return Err(Error::Nack);
-            }
-
-            timeout.check()?;
+    /// Blocking write multiple buffers.
+    ///
+    /// The buffers are concatenated in a single write transaction.
+    pub fn blocking_write_vectored(&mut self, address: u8, write: &[&[u8]]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
+        if write.is_empty() {
+            return Err(Error::ZeroLengthTransfer);
         }
-    }
-
-    fn read_internal(&mut self, address: u8, read: &mut [u8], restart: bool, timeout: Timeout) -> Result<(), Error> {
-        let completed_chunks = read.len() / 255;
-        let total_chunks = if completed_chunks * 255 == read.len() {
-            completed_chunks
-        } else {
-            completed_chunks + 1
-        };
-        let last_chunk_idx = total_chunks.saturating_sub(1);
-
-        Self::master_read(
-            address,
-            read.len().min(255),
-            Stop::Automatic,
-            last_chunk_idx != 0,
-            restart,
-            timeout,
-        )?;
-
-        for (number, chunk) in read.chunks_mut(255).enumerate() {
-            if number != 0 {
-                Self::master_continue(chunk.len(), number != last_chunk_idx, timeout)?;
-            }
 
-            for byte in chunk {
-                // Wait until we have received something
-                self.wait_rxne(timeout)?;
-
-                *byte = T::regs().rxdr().read().rxdata();
-            }
-        }
-        Ok(())
-    }
+        let timeout = self.timeout();
 
-    fn write_internal(&mut self, address: u8, write: &[u8], send_stop: bool, timeout: Timeout) -> Result<(), Error> {
-        let completed_chunks = write.len() / 255;
-        let total_chunks = if completed_chunks * 255 == write.len() {
-            completed_chunks
-        } else {
-            completed_chunks + 1
-        };
-        let last_chunk_idx = total_chunks.saturating_sub(1);
+        let first_length = write[0].len();
+        let last_slice_index = write.len() - 1;
 
-        // I2C start
-        //
-        // ST SAD+W
-        if let Err(err) = Self::master_write(
-            address,
-            write.len().min(255),
+        if let Err(err) = master_write(
+            T::regs(),
+            address.into(),
+            first_length.min(255),
             Stop::Software,
-            last_chunk_idx != 0,
+            (first_length > 255) || (last_slice_index != 0),
             timeout,
         ) {
-            if send_stop {
-                self.master_stop();
-            }
+            master_stop(T::regs());
             return Err(err);
         }
 
-        for (number, chunk) in write.chunks(255).enumerate() {
-            if number != 0 {
-                Self::master_continue(chunk.len(), number != last_chunk_idx, timeout)?;
+        for (idx, slice) in write.iter().enumerate() {
+            let slice_len = slice.len();
+            let completed_chunks = slice_len / 255;
+            let total_chunks = if completed_chunks * 255 == slice_len {
+                completed_chunks
+            } else {
+                completed_chunks + 1
+            };
+            let last_chunk_idx = total_chunks.saturating_sub(1);
+
+            if idx != 0 {
+                if let Err(err) = master_continue(
+                    T::regs(),
+                    slice_len.min(255),
+                    (idx != last_slice_index) || (slice_len > 255),
+                    timeout,
+                ) {
+                    master_stop(T::regs());
+                    return Err(err);
+                }
             }
 
-            for byte in chunk {
-                // Wait until we are allowed to send data
-                // (START has been ACKed or last byte when
-                // through)
-                if let Err(err) = self.wait_txe(timeout) {
-                    if send_stop {
-                        self.master_stop();
+            for (number, chunk) in slice.chunks(255).enumerate() {
+                if number != 0 {
+                    if let Err(err) = master_continue(
+                        T::regs(),
+                        chunk.len(),
+                        (number != last_chunk_idx) || (idx != last_slice_index),
+                        timeout,
+                    ) {
+                        master_stop(T::regs());
+                        return Err(err);
                     }
-                    return Err(err);
                 }
 
-                T::regs().txdr().write(|w| w.set_txdata(*byte));
+                for byte in chunk {
+                    // Wait until we are allowed to send data
+                    // (START has been ACKed or last byte when
+                    // through)
+                    if let Err(err) = wait_txe(T::regs(), timeout) {
+                        master_stop(T::regs());
+                        return Err(err);
+                    }
+
+                    // Put byte on the wire
+                    //self.i2c.txdr.write(|w| w.txdata().bits(*byte));
+                    T::regs().txdr().write(|w| w.set_txdata(*byte));
+                }
             }
         }
         // Wait until the write finishes
-        let result = self.wait_tc(timeout);
-        if send_stop {
-            self.master_stop();
-        }
+        let result = wait_tc(T::regs(), timeout);
+        master_stop(T::regs());
         result
     }
+}
 
+impl<'d, T: Instance, TXDMA: super::TxDma<T>, RXDMA> I2c<'d, T, TXDMA, RXDMA> {
     async fn write_dma_internal(
         &mut self,
-        address: u8,
+        address: Address,
         write: &[u8],
         first_slice: bool,
         last_slice: bool,
         timeout: Timeout,
-    ) -> Result<(), Error>
-    where
-        TXDMA: crate::i2c::TxDma<T>,
-    {
+    ) -> Result<(), Error> {
         let total_len = write.len();
 
         let dma_transfer = unsafe {
@@ -349,7 +211,8 @@ impl<'d, T: Instance, TXDMA, RXDMA> I2c<'d, T, TXDMA, RXDMA> {
             let isr = T::regs().isr().read();
             if remaining_len == total_len {
                 if first_slice {
-                    Self::master_write(
+                    master_write(
+                        T::regs(),
                         address,
                         total_len.min(255),
                         Stop::Software,
@@ -357,7 +220,7 @@ impl<'d, T: Instance, TXDMA, RXDMA> I2c<'d, T, TXDMA, RXDMA> {
                         timeout,
                     )?;
                 } else {
-                    Self::master_continue(total_len.min(255), (total_len > 255) || !last_slice, timeout)?;
+                    master_continue(T::regs(), total_len.min(255), (total_len > 255) || !last_slice, timeout)?;
                     T::regs().cr1().modify(|w| w.set_tcie(true));
                 }
             } else if !(isr.tcr() || isr.tc()) {
@@ -368,7 +231,7 @@ impl<'d, T: Instance, TXDMA, RXDMA> I2c<'d, T, TXDMA, RXDMA> {
             } else {
                 let last_piece = (remaining_len <= 255) && last_slice;
 
-                if let Err(e) = Self::master_continue(remaining_len.min(255), !last_piece, timeout) {
+                if let Err(e) = master_continue(T::regs(), remaining_len.min(255), !last_piece, timeout) {
                     return Poll::Ready(Err(e));
                 }
                 T::regs().cr1().modify(|w| w.set_tcie(true));
@@ -383,8 +246,8 @@ impl<'d, T: Instance, TXDMA, RXDMA> I2c<'d, T, TXDMA, RXDMA> {
 
         if last_slice {
             // This should be done already
-            self.wait_tc(timeout)?;
-            self.master_stop();
+            wait_tc(T::regs(), timeout)?;
+            master_stop(T::regs());
         }
 
         drop(on_drop);
@@ -392,16 +255,61 @@ impl<'d, T: Instance, TXDMA, RXDMA> I2c<'d, T, TXDMA, RXDMA> {
         Ok(())
     }
 
+    // =========================
+    //  Async public API
+
+    /// Write.
+    pub async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Error> {
+        self.write_addr(address.into(), write).await
+    }
+
+    async fn write_addr(&mut self, address: Address, write: &[u8]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
+        let timeout = self.timeout();
+        if write.is_empty() {
+            write_internal(T::regs(), address, write, true, timeout)
+        } else {
+            timeout
+                .with(self.write_dma_internal(address, write, true, true, timeout))
+                .await
+        }
+    }
+
+    /// Write multiple buffers.
+    ///
+    /// The buffers are concatenated in a single write transaction.
+    pub async fn write_vectored(&mut self, address: u8, write: &[&[u8]]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
+        let timeout = self.timeout();
+
+        if write.is_empty() {
+            return Err(Error::ZeroLengthTransfer);
+        }
+        let mut iter = write.iter();
+
+        let mut first = true;
+        let mut current = iter.next();
+        while let Some(c) = current {
+            let next = iter.next();
+            let is_last = next.is_none();
+
+            let fut = self.write_dma_internal(address.into(), c, first, is_last, timeout);
+            timeout.with(fut).await?;
+            first = false;
+            current = next;
+        }
+        Ok(())
+    }
+}
+
+impl<'d, T: Instance, TXDMA, RXDMA: super::RxDma<T>> I2c<'d, T, TXDMA, RXDMA> {
     async fn read_dma_internal(
         &mut self,
-        address: u8,
+        address: Address,
         buffer: &mut [u8],
         restart: bool,
         timeout: Timeout,
-    ) -> Result<(), Error>
-    where
-        RXDMA: crate::i2c::RxDma<T>,
-    {
+    ) -> Result<(), Error> {
         let total_len = buffer.len();
 
         let dma_transfer = unsafe {
@@ -433,7 +341,8 @@ impl<'d, T: Instance, TXDMA, RXDMA> I2c<'d, T, TXDMA, RXDMA> {
 
             let isr = T::regs().isr().read();
             if remaining_len == total_len {
-                Self::master_read(
+                master_read(
+                    T::regs(),
                     address,
                     total_len.min(255),
                     Stop::Software,
@@ -449,7 +358,7 @@ impl<'d, T: Instance, TXDMA, RXDMA> I2c<'d, T, TXDMA, RXDMA> {
             } else {
                 let last_piece = remaining_len <= 255;
 
-                if let Err(e) = Self::master_continue(remaining_len.min(255), !last_piece, timeout) {
+                if let Err(e) = master_continue(T::regs(), remaining_len.min(255), !last_piece, timeout) {
                     return Poll::Ready(Err(e));
                 }
                 T::regs().cr1().modify(|w| w.set_tcie(true));
@@ -463,8 +372,8 @@ impl<'d, T: Instance, TXDMA, RXDMA> I2c<'d, T, TXDMA, RXDMA> {
         dma_transfer.await;
 
         // This should be done already
-        self.wait_tc(timeout)?;
-        self.master_stop();
+        wait_tc(T::regs(), timeout)?;
+        master_stop(T::regs());
 
         drop(on_drop);
 
@@ -474,81 +383,46 @@ impl<'d, T: Instance, TXDMA, RXDMA> I2c<'d, T, TXDMA, RXDMA> {
     // =========================
     //  Async public API
 
-    /// Write.
-    pub async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Error>
-    where
-        TXDMA: crate::i2c::TxDma<T>,
-    {
-        let timeout = self.timeout();
-        if write.is_empty() {
-            self.write_internal(address, write, true, timeout)
-        } else {
-            timeout
-                .with(self.write_dma_internal(address, write, true, true, timeout))
-                .await
-        }
-    }
-
-    /// Write multiple buffers.
-    ///
-    /// The buffers are concatenated in a single write transaction.
-    pub async fn write_vectored(&mut self, address: u8, write: &[&[u8]]) -> Result<(), Error>
-    where
-        TXDMA: crate::i2c::TxDma<T>,
-    {
-        let timeout = self.timeout();
-
-        if write.is_empty() {
-            return Err(Error::ZeroLengthTransfer);
-        }
-        let mut iter = write.iter();
-
-        let mut first = true;
-        let mut current = iter.next();
-        while let Some(c) = current {
-            let next = iter.next();
-            let is_last = next.is_none();
-
-            let fut = self.write_dma_internal(address, c, first, is_last, timeout);
-            timeout.with(fut).await?;
-            first = false;
-            current = next;
-        }
-        Ok(())
+    /// Read.
+    pub async fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Error> {
+        self.read_addr(address.into(), buffer).await
     }
 
-    /// Read.
-    pub async fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Error>
-    where
-        RXDMA: crate::i2c::RxDma<T>,
-    {
+    async fn read_addr(&mut self, address: Address, buffer: &mut [u8]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
         let timeout = self.timeout();
 
         if buffer.is_empty() {
-            self.read_internal(address, buffer, false, timeout)
+            read_internal(T::regs(), address, buffer, false, timeout)
         } else {
             let fut = self.read_dma_internal(address, buffer, false, timeout);
             timeout.with(fut).await
         }
     }
+}
+
+impl<'d, T: Instance, TXDMA: super::TxDma<T>, RXDMA: super::RxDma<T>> I2c<'d, T, TXDMA, RXDMA> {
+    // =========================
+    //  Async public API
 
     /// Write, restart, read.
-    pub async fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Error>
-    where
-        TXDMA: super::TxDma<T>,
-        RXDMA: super::RxDma<T>,
-    {
+    pub async fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Error> {
+        self.write_read_addr(address.into(), write, read).await
+    }
+
+    async fn write_read_addr(&mut self, address: Address, write: &[u8], read: &mut [u8]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
         let timeout = self.timeout();
 
         if write.is_empty() {
-            self.write_internal(address, write, false, timeout)?;
+            write_internal(T::regs(), address, write, false, timeout)?;
         } else {
             let fut = self.write_dma_internal(address, write, true, true, timeout);
             timeout.with(fut).await?;
         }
 
         if read.is_empty() {
-            self.read_internal(address, read, true, timeout)?;
+            read_internal(T::regs(), address, read, true, timeout)?;
         } else {
             let fut = self.read_dma_internal(address, read, true, timeout);
             timeout.with(fut).await?;
@@ -556,110 +430,832 @@ impl<'d, T: Instance, TXDMA, RXDMA> I2c<'d, T, TXDMA, RXDMA> {
 
         Ok(())
     }
+}
 
-    // =========================
-    //  Blocking public API
-
-    /// Blocking read.
-    pub fn blocking_read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Error> {
-        self.read_internal(address, read, false, self.timeout())
-        // Automatic Stop
+impl<'d, T: Instance, TXDMA, RXDMA> I2c<'d, T, TXDMA, RXDMA> {
+    /// Wait until `ready` returns true for the ISR register, or a bus error occurs.
+    async fn wait_isr(&self, ready: fn(i2c::regs::Isr) -> bool) -> Result<(), Error> {
+        wait_isr(T::regs(), T::state(), ready).await
     }
 
-    /// Blocking write.
-    pub fn blocking_write(&mut self, address: u8, write: &[u8]) -> Result<(), Error> {
-        self.write_internal(address, write, true, self.timeout())
-    }
+    /// Transaction, driven by the I2C interrupts, also when the driver has DMA channels.
+    async fn transaction_addr(&mut self, address: Address, operations: &mut [Operation<'_>]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
+        if operations.is_empty() {
+            return Ok(());
+        }
+
+        // The transaction ends with a STOP, also when it fails or is cancelled.
+        let _stop = OnDrop::new(|| master_stop(T::regs()));
 
-    /// Blocking write, restart, read.
-    pub fn blocking_write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Error> {
         let timeout = self.timeout();
-        self.write_internal(address, write, false, timeout)?;
-        self.read_internal(address, read, true, timeout)
-        // Automatic Stop
+        let fut = transaction_interrupt(T::regs(), T::state(), address, operations, timeout);
+        timeout.with(fut).await
     }
+}
 
-    /// Blocking write multiple buffers.
+/// Wait until `ready` returns true for the ISR register, or a bus error occurs.
+///
+/// The interrupts are enabled while waiting, and disabled again by the interrupt handler.
+async fn wait_isr(regs: Regs, state: &sealed::State, ready: fn(i2c::regs::Isr) -> bool) -> Result<(), Error> {
+    let _on_drop = OnDrop::new(move || {
+        regs.cr1().modify(|w| {
+            w.set_tcie(false);
+            w.set_txie(false);
+            w.set_rxie(false);
+            w.set_nackie(false);
+            w.set_errie(false);
+        })
+    });
+
+    poll_fn(|cx| {
+        state.waker.register(cx.waker());
+
+        let isr = regs.isr().read();
+        if ready(isr) {
+            return Poll::Ready(Ok(()));
+        } else if isr.berr() {
+            regs.icr().write(|reg| reg.set_berrcf(true));
+            return Poll::Ready(Err(Error::Bus));
+        } else if isr.arlo() {
+            regs.icr().write(|reg| reg.set_arlocf(true));
+            return Poll::Ready(Err(Error::Arbitration));
+        } else if isr.nackf() {
+            regs.icr().write(|reg| reg.set_nackcf(true));
+            flush_txdr(regs);
+            return Poll::Ready(Err(Error::Nack));
+        }
+
+        regs.cr1().modify(|w| {
+            w.set_tcie(true);
+            w.set_txie(true);
+            w.set_rxie(true);
+            w.set_nackie(true);
+            w.set_errie(true);
+        });
+        Poll::Pending
+    })
+    .await
+}
+
+/// Run the operations of a transaction, driven by the I2C interrupts.
+///
+/// Adjacent operations of the same direction are merged, and a repeated START is sent between
+/// operations of different directions. The caller sends the STOP.
+pub(super) async fn transaction_interrupt(
+    regs: Regs,
+    state: &sealed::State,
+    address: Address,
+    operations: &mut [Operation<'_>],
+    timeout: Timeout,
+) -> Result<(), Error> {
+    let mut restart = false;
+    for group in OperationGroups::new(operations) {
+        let is_read = matches!(group[0], Operation::Read(_));
+        let total_len: usize = group.iter().map(operation_len).sum();
+        let mut chunk_len = total_len.min(255);
+        let mut remaining_len = total_len - chunk_len;
+
+        if is_read {
+            master_read(
+                regs,
+                address,
+                chunk_len,
+                Stop::Software,
+                remaining_len > 0,
+                restart,
+                timeout,
+            )?;
+        } else {
+            master_write(regs, address, chunk_len, Stop::Software, remaining_len > 0, timeout)?;
+        }
+
+        for op in group.iter_mut() {
+            match op {
+                Operation::Read(read) => {
+                    for byte in read.iter_mut() {
+                        if chunk_len == 0 {
+                            // Wait for the reload of the next chunk
+                            wait_isr(regs, state, |isr| isr.tcr()).await?;
+                            chunk_len = remaining_len.min(255);
+                            remaining_len -= chunk_len;
+                            master_continue(regs, chunk_len, remaining_len > 0, timeout)?;
+                        }
+
+                        // Wait until we have received something
+                        wait_isr(regs, state, |isr| isr.rxne()).await?;
+
+                        *byte = regs.rxdr().read().rxdata();
+                        chunk_len -= 1;
+                    }
+                }
+                Operation::Write(write) => {
+                    for byte in write.iter() {
+                        if chunk_len == 0 {
+                            // Wait for the reload of the next chunk
+                            wait_isr(regs, state, |isr| isr.tcr()).await?;
+                            chunk_len = remaining_len.min(255);
+                            remaining_len -= chunk_len;
+                            master_continue(regs, chunk_len, remaining_len > 0, timeout)?;
+                        }
+
+                        // Wait until we are allowed to send data
+                        wait_isr(regs, state, |isr| isr.txis()).await?;
+
+                        regs.txdr().write(|w| w.set_txdata(*byte));
+                        chunk_len -= 1;
+                    }
+                }
+            }
+        }
+
+        // Wait until the group finishes, before the repeated START or the STOP.
+        wait_isr(regs, state, |isr| isr.tc()).await?;
+        restart = true;
+    }
+
+    Ok(())
+}
+
+impl<'d, T: Instance> I2c<'d, T, NoDma, NoDma> {
+    async fn write_interrupt_internal(
+        &mut self,
+        address: Address,
+        write: &[&[u8]],
+        send_stop: bool,
+        timeout: Timeout,
+    ) -> Result<(), Error> {
+        let total_len: usize = write.iter().map(|w| w.len()).sum();
+        let mut chunk_len = total_len.min(255);
+        let mut remaining_len = total_len - chunk_len;
+
+        // A failed or cancelled write ends the transaction.
+        let stop = OnDrop::new(|| master_stop(T::regs()));
+
+        master_write(
+            T::regs(),
+            address,
+            chunk_len,
+            Stop::Software,
+            remaining_len > 0,
+            timeout,
+        )?;
+
+        for byte in write.iter().flat_map(|w| w.iter()) {
+            if chunk_len == 0 {
+                // Wait for the reload of the next chunk
+                self.wait_isr(|isr| isr.tcr()).await?;
+                chunk_len = remaining_len.min(255);
+                remaining_len -= chunk_len;
+                master_continue(T::regs(), chunk_len, remaining_len > 0, timeout)?;
+            }
+
+            // Wait until we are allowed to send data
+            self.wait_isr(|isr| isr.txis()).await?;
+
+            T::regs().txdr().write(|w| w.set_txdata(*byte));
+            chunk_len -= 1;
+        }
+
+        // Wait until the write finishes
+        self.wait_isr(|isr| isr.tc()).await?;
+        if !send_stop {
+            stop.defuse();
+        }
+        Ok(())
+    }
+
+    async fn read_interrupt_internal(
+        &mut self,
+        address: Address,
+        buffer: &mut [u8],
+        restart: bool,
+        timeout: Timeout,
+    ) -> Result<(), Error> {
+        let total_len = buffer.len();
+        let mut chunk_len = total_len.min(255);
+        let mut remaining_len = total_len - chunk_len;
+
+        // The read ends the transaction, also when it fails or is cancelled.
+        let _stop = OnDrop::new(|| master_stop(T::regs()));
+
+        master_read(
+            T::regs(),
+            address,
+            chunk_len,
+            Stop::Software,
+            remaining_len > 0,
+            restart,
+            timeout,
+        )?;
+
+        for byte in buffer {
+            if chunk_len == 0 {
+                // Wait for the reload of the next chunk
+                self.wait_isr(|isr| isr.tcr()).await?;
+                chunk_len = remaining_len.min(255);
+                remaining_len -= chunk_len;
+                master_continue(T::regs(), chunk_len, remaining_len > 0, timeout)?;
+            }
+
+            // Wait until we have received something
+            self.wait_isr(|isr| isr.rxne()).await?;
+
+            *byte = T::regs().rxdr().read().rxdata();
+            chunk_len -= 1;
+        }
+
+        self.wait_isr(|isr| isr.tc()).await
+    }
+
+    // =========================
+    //  Async public API, without DMA
+
+    /// Write, driven by the I2C interrupts.
+    pub async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Error> {
+        self.write_addr(address.into(), write).await
+    }
+
+    async fn write_addr(&mut self, address: Address, write: &[u8]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
+        let timeout = self.timeout();
+        if write.is_empty() {
+            write_internal(T::regs(), address, write, true, timeout)
+        } else {
+            timeout
+                .with(self.write_interrupt_internal(address, &[write], true, timeout))
+                .await
+        }
+    }
+
+    /// Write multiple buffers, driven by the I2C interrupts.
     ///
     /// The buffers are concatenated in a single write transaction.
-    pub fn blocking_write_vectored(&mut self, address: u8, write: &[&[u8]]) -> Result<(), Error> {
-        if write.is_empty() {
+    pub async fn write_vectored(&mut self, address: u8, write: &[&[u8]]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
+        let timeout = self.timeout();
+
+        if write.iter().all(|w| w.is_empty()) {
             return Err(Error::ZeroLengthTransfer);
         }
 
+        timeout
+            .with(self.write_interrupt_internal(address.into(), write, true, timeout))
+            .await
+    }
+
+    /// Read, driven by the I2C interrupts.
+    pub async fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Error> {
+        self.read_addr(address.into(), buffer).await
+    }
+
+    async fn read_addr(&mut self, address: Address, buffer: &mut [u8]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
         let timeout = self.timeout();
 
-        let first_length = write[0].len();
-        let last_slice_index = write.len() - 1;
+        if buffer.is_empty() {
+            read_internal(T::regs(), address, buffer, false, timeout)
+        } else {
+            let fut = self.read_interrupt_internal(address, buffer, false, timeout);
+            timeout.with(fut).await
+        }
+    }
 
-        if let Err(err) = Self::master_write(
-            address,
-            first_length.min(255),
-            Stop::Software,
-            (first_length > 255) || (last_slice_index != 0),
-            timeout,
-        ) {
-            self.master_stop();
-            return Err(err);
+    /// Write, restart, read, driven by the I2C interrupts.
+    pub async fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Error> {
+        self.write_read_addr(address.into(), write, read).await
+    }
+
+    async fn write_read_addr(&mut self, address: Address, write: &[u8], read: &mut [u8]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
+        let timeout = self.timeout();
+
+        if write.is_empty() {
+            write_internal(T::regs(), address, write, false, timeout)?;
+        } else {
+            let fut = self.write_interrupt_internal(address, &[write], false, timeout);
+            timeout.with(fut).await?;
         }
 
-        for (idx, slice) in write.iter().enumerate() {
-            let slice_len = slice.len();
-            let completed_chunks = slice_len / 255;
-            let total_chunks = if completed_chunks * 255 == slice_len {
-                completed_chunks
-            } else {
-                completed_chunks + 1
-            };
-            let last_chunk_idx = total_chunks.saturating_sub(1);
+        if read.is_empty() {
+            read_internal(T::regs(), address, read, true, timeout)?;
+        } else {
+            let fut = self.read_interrupt_internal(address, read, true, timeout);
+            timeout.with(fut).await?;
+        }
 
-            if idx != 0 {
-                if let Err(err) = Self::master_continue(
-                    slice_len.min(255),
-                    (idx != last_slice_index) || (slice_len > 255),
-                    timeout,
-                ) {
-                    self.master_stop();
-                    return Err(err);
+        Ok(())
+    }
+}
+
+impl<'d, T: Instance, TXDMA: super::TxDma<T>, RXDMA: super::RxDma<T>> embedded_hal_async::i2c::I2c
+    for I2c<'d, T, TXDMA, RXDMA>
+{
+    async fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Self::Error> {
+        self.read(address, read).await
+    }
+
+    async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
+        self.write(address, write).await
+    }
+
+    async fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Self::Error> {
+        self.write_read(address, write, read).await
+    }
+
+    async fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+        self.transaction_addr(address.into(), operations).await
+    }
+}
+
+impl<'d, T: Instance> embedded_hal_async::i2c::I2c for I2c<'d, T, NoDma, NoDma> {
+    async fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Self::Error> {
+        self.read(address, read).await
+    }
+
+    async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
+        self.write(address, write).await
+    }
+
+    async fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Self::Error> {
+        self.write_read(address, write, read).await
+    }
+
+    async fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+        self.transaction_addr(address.into(), operations).await
+    }
+}
+
+impl<'d, T: Instance> embedded_hal_1::i2c::I2c<TenBitAddress> for I2c<'d, T, NoDma, NoDma> {
+    fn read(&mut self, address: u16, read: &mut [u8]) -> Result<(), Self::Error> {
+        let _clock = self.auto_idle.wake();
+        read_internal(T::regs(), Address::TenBit(address), read, false, self.timeout())
+    }
+
+    fn write(&mut self, address: u16, write: &[u8]) -> Result<(), Self::Error> {
+        let _clock = self.auto_idle.wake();
+        write_internal(T::regs(), Address::TenBit(address), write, true, self.timeout())
+    }
+
+    fn write_read(&mut self, address: u16, write: &[u8], read: &mut [u8]) -> Result<(), Self::Error> {
+        let _clock = self.auto_idle.wake();
+        let timeout = self.timeout();
+        write_internal(T::regs(), Address::TenBit(address), write, false, timeout)?;
+        read_internal(T::regs(), Address::TenBit(address), read, true, timeout)
+    }
+
+    fn transaction(&mut self, address: u16, operations: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+        let _clock = self.auto_idle.wake();
+        transaction_internal(T::regs(), Address::TenBit(address), operations, self.timeout())
+    }
+}
+
+impl<'d, T: Instance, TXDMA: super::TxDma<T>, RXDMA: super::RxDma<T>> embedded_hal_async::i2c::I2c<TenBitAddress>
+    for I2c<'d, T, TXDMA, RXDMA>
+{
+    async fn read(&mut self, address: u16, read: &mut [u8]) -> Result<(), Self::Error> {
+        self.read_addr(Address::TenBit(address), read).await
+    }
+
+    async fn write(&mut self, address: u16, write: &[u8]) -> Result<(), Self::Error> {
+        self.write_addr(Address::TenBit(address), write).await
+    }
+
+    async fn write_read(&mut self, address: u16, write: &[u8], read: &mut [u8]) -> Result<(), Self::Error> {
+        self.write_read_addr(Address::TenBit(address), write, read).await
+    }
+
+    async fn transaction(&mut self, address: u16, operations: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+        self.transaction_addr(Address::TenBit(address), operations).await
+    }
+}
+
+impl<'d, T: Instance> embedded_hal_async::i2c::I2c<TenBitAddress> for I2c<'d, T, NoDma, NoDma> {
+    async fn read(&mut self, address: u16, read: &mut [u8]) -> Result<(), Self::Error> {
+        self.read_addr(Address::TenBit(address), read).await
+    }
+
+    async fn write(&mut self, address: u16, write: &[u8]) -> Result<(), Self::Error> {
+        self.write_addr(Address::TenBit(address), write).await
+    }
+
+    async fn write_read(&mut self, address: u16, write: &[u8], read: &mut [u8]) -> Result<(), Self::Error> {
+        self.write_read_addr(Address::TenBit(address), write, read).await
+    }
+
+    async fn transaction(&mut self, address: u16, operations: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+        self.transaction_addr(Address::TenBit(address), operations).await
+    }
+}
+
+pub(super) fn init(regs: Regs, i2cclk: Hertz, freq: Hertz) {
+    regs.cr1().modify(|reg| {
+        reg.set_pe(false);
+        reg.set_anfoff(false);
+    });
+
+    let timings = Timings::new(i2cclk, freq);
+
+    regs.timingr().write(|reg| {
+        reg.set_presc(timings.prescale);
+        reg.set_scll(timings.scll);
+        reg.set_sclh(timings.sclh);
+        reg.set_sdadel(timings.sdadel);
+        reg.set_scldel(timings.scldel);
+    });
+
+    regs.cr1().modify(|reg| {
+        reg.set_pe(true);
+    });
+}
+
+pub(super) fn master_stop(regs: Regs) {
+    regs.cr2().write(|w| w.set_stop(true));
+}
+
+fn master_read(
+    regs: Regs,
+    address: Address,
+    length: usize,
+    stop: Stop,
+    reload: bool,
+    restart: bool,
+    timeout: Timeout,
+) -> Result<(), Error> {
+    assert!(length < 256);
+
+    if !restart {
+        // Wait for any previous address sequence to end
+        // automatically. This could be up to 50% of a bus
+        // cycle (ie. up to 0.5/freq)
+        while regs.cr2().read().start() {
+            timeout.check()?;
+        }
+    }
+
+    // Set START and prepare to receive bytes into
+    // `buffer`. The START bit can be set even if the bus
+    // is BUSY or I2C is in slave mode.
+
+    let reload = if reload {
+        i2c::vals::Reload::NOTCOMPLETED
+    } else {
+        i2c::vals::Reload::COMPLETED
+    };
+
+    regs.cr2().modify(|w| {
+        w.set_sadd(address.sadd());
+        w.set_add10(address.addmode());
+        w.set_dir(i2c::vals::Dir::READ);
+        w.set_nbytes(length as u8);
+        w.set_start(true);
+        w.set_autoend(stop.autoend());
+        w.set_reload(reload);
+    });
+
+    Ok(())
+}
+
+fn master_write(
+    regs: Regs,
+    address: Address,
+    length: usize,
+    stop: Stop,
+    reload: bool,
+    timeout: Timeout,
+) -> Result<(), Error> {
+    assert!(length < 256);
+
+    // Wait for any previous address sequence to end
+    // automatically. This could be up to 50% of a bus
+    // cycle (ie. up to 0.5/freq)
+    while regs.cr2().read().start() {
+        timeout.check()?;
+    }
+
+    let reload = if reload {
+        i2c::vals::Reload::NOTCOMPLETED
+    } else {
+        i2c::vals::Reload::COMPLETED
+    };
+
+    // Set START and prepare to send `bytes`. The
+    // START bit can be set even if the bus is BUSY or
+    // I2C is in slave mode.
+    regs.cr2().modify(|w| {
+        w.set_sadd(address.sadd());
+        w.set_add10(address.addmode());
+        w.set_dir(i2c::vals::Dir::WRITE);
+        w.set_nbytes(length as u8);
+        w.set_start(true);
+        w.set_autoend(stop.autoend());
+        w.set_reload(reload);
+    });
+
+    Ok(())
+}
+
+fn master_continue(regs: Regs, length: usize, reload: bool, timeout: Timeout) -> Result<(), Error> {
+    assert!(length < 256 && length > 0);
+
+    while !regs.isr().read().tcr() {
+        timeout.check()?;
+    }
+
+    let reload = if reload {
+        i2c::vals::Reload::NOTCOMPLETED
+    } else {
+        i2c::vals::Reload::COMPLETED
+    };
+
+    regs.cr2().modify(|w| {
+        w.set_nbytes(length as u8);
+        w.set_reload(reload);
+    });
+
+    Ok(())
+}
+
+fn flush_txdr(regs: Regs) {
+    if regs.isr().read().txis() {
+        regs.txdr().write(|w| w.set_txdata(0));
+    }
+    if !regs.isr().read().txe() {
+        regs.isr().modify(|w| w.set_txe(true))
+    }
+}
+
+fn wait_txe(regs: Regs, timeout: Timeout) -> Result<(), Error> {
+    loop {
+        let isr = regs.isr().read();
+        if isr.txe() {
+            return Ok(());
+        } else if isr.berr() {
+            regs.icr().write(|reg| reg.set_berrcf(true));
+            return Err(Error::Bus);
+        } else if isr.arlo() {
+            regs.icr().write(|reg| reg.set_arlocf(true));
+            return Err(Error::Arbitration);
+        } else if isr.nackf() {
+            regs.icr().write(|reg| reg.set_nackcf(true));
+            flush_txdr(regs);
+            return Err(Error::Nack);
+        }
+
+        timeout.check()?;
+    }
+}
+
+fn wait_rxne(regs: Regs, timeout: Timeout) -> Result<(), Error> {
+    loop {
+        let isr = regs.isr().read();
+        if isr.rxne() {
+            return Ok(());
+        } else if isr.berr() {
+            regs.icr().write(|reg| reg.set_berrcf(true));
+            return Err(Error::Bus);
+        } else if isr.arlo() {
+            regs.icr().write(|reg| reg.set_arlocf(true));
+            return Err(Error::Arbitration);
+        } else if isr.nackf() {
+            regs.icr().write(|reg| reg.set_nackcf(true));
+            flush_txdr(regs);
+            return Err(Error::Nack);
+        }
+
+        timeout.check()?;
+    }
+}
+
+fn wait_tc(regs: Regs, timeout: Timeout) -> Result<(), Error> {
+    loop {
+        let isr = regs.isr().read();
+        if isr.tc() {
+            return Ok(());
+        } else if isr.berr() {
+            regs.icr().write(|reg| reg.set_berrcf(true));
+            return Err(Error::Bus);
+        } else if isr.arlo() {
+            regs.icr().write(|reg| reg.set_arlocf(true));
+            return Err(Error::Arbitration);
+        } else if isr.nackf() {
+            regs.icr().write(|reg| reg.set_nackcf(true));
+            flush_txdr(regs);
+            return Err(Error::Nack);
+        }
+
+        timeout.check()?;
+    }
+}
+
+pub(super) fn read_internal(
+    regs: Regs,
+    address: Address,
+    read: &mut [u8],
+    restart: bool,
+    timeout: Timeout,
+) -> Result<(), Error> {
+    let completed_chunks = read.len() / 255;
+    let total_chunks = if completed_chunks * 255 == read.len() {
+        completed_chunks
+    } else {
+        completed_chunks + 1
+    };
+    let last_chunk_idx = total_chunks.saturating_sub(1);
+
+    master_read(
+        regs,
+        address,
+        read.len().min(255),
+        Stop::Automatic,
+        last_chunk_idx != 0,
+        restart,
+        timeout,
+    )?;
+
+    for (number, chunk) in read.chunks_mut(255).enumerate() {
+        if number != 0 {
+            master_continue(regs, chunk.len(), number != last_chunk_idx, timeout)?;
+        }
+
+        for byte in chunk {
+            // Wait until we have received something
+            wait_rxne(regs, timeout)?;
+
+            *byte = regs.rxdr().read().rxdata();
+        }
+    }
+    Ok(())
+}
+
+pub(super) fn write_internal(
+    regs: Regs,
+    address: Address,
+    write: &[u8],
+    send_stop: bool,
+    timeout: Timeout,
+) -> Result<(), Error> {
+    let completed_chunks = write.len() / 255;
+    let total_chunks = if completed_chunks * 255 == write.len() {
+        completed_chunks
+    } else {
+        completed_chunks + 1
+    };
+    let last_chunk_idx = total_chunks.saturating_sub(1);
+
+    // I2C start
+    //
+    // ST SAD+W
+    if let Err(err) = master_write(
+        regs,
+        address,
+        write.len().min(255),
+        Stop::Software,
+        last_chunk_idx != 0,
+        timeout,
+    ) {
+        if send_stop {
+            master_stop(regs);
+        }
+        return Err(err);
+    }
+
+    for (number, chunk) in write.chunks(255).enumerate() {
+        if number != 0 {
+            master_continue(regs, chunk.len(), number != last_chunk_idx, timeout)?;
+        }
+
+        for byte in chunk {
+            // Wait until we are allowed to send data
+            // (START has been ACKed or last byte when
+            // through)
+            if let Err(err) = wait_txe(regs, timeout) {
+                if send_stop {
+                    master_stop(regs);
                 }
+                return Err(err);
             }
 
-            for (number, chunk) in slice.chunks(255).enumerate() {
-                if number != 0 {
-                    if let Err(err) = Self::master_continue(
-                        chunk.len(),
-                        (number != last_chunk_idx) || (idx != last_slice_index),
-                        timeout,
-                    ) {
-                        self.master_stop();
-                        return Err(err);
+            regs.txdr().write(|w| w.set_txdata(*byte));
+        }
+    }
+    // Wait until the write finishes
+    let result = wait_tc(regs, timeout);
+    if send_stop {
+        master_stop(regs);
+    }
+    result
+}
+
+/// Run the operations of a transaction.
+///
+/// Adjacent operations of the same direction are merged, a repeated START is sent between
+/// operations of different directions, and a STOP ends the transaction, also on errors.
+pub(super) fn transaction_internal(
+    regs: Regs,
+    address: Address,
+    operations: &mut [Operation<'_>],
+    timeout: Timeout,
+) -> Result<(), Error> {
+    if operations.is_empty() {
+        return Ok(());
+    }
+
+    let result = transaction_groups(regs, address, operations, timeout);
+    master_stop(regs);
+    result
+}
+
+fn transaction_groups(
+    regs: Regs,
+    address: Address,
+    operations: &mut [Operation<'_>],
+    timeout: Timeout,
+) -> Result<(), Error> {
+    let mut restart = false;
+    for group in OperationGroups::new(operations) {
+        let is_read = matches!(group[0], Operation::Read(_));
+        let total_len: usize = group.iter().map(operation_len).sum();
+        let mut chunk_len = total_len.min(255);
+        let mut remaining_len = total_len - chunk_len;
+
+        if is_read {
+            master_read(
+                regs,
+                address,
+                chunk_len,
+                Stop::Software,
+                remaining_len > 0,
+                restart,
+                timeout,
+            )?;
+        } else {
+            master_write(regs, address, chunk_len, Stop::Software, remaining_len > 0, timeout)?;
+        }
+
+        for op in group.iter_mut() {
+            match op {
+                Operation::Read(read) => {
+                    for byte in read.iter_mut() {
+                        if chunk_len == 0 {
+                            chunk_len = remaining_len.min(255);
+                            remaining_len -= chunk_len;
+                            master_continue(regs, chunk_len, remaining_len > 0, timeout)?;
+                        }
+
+                        // Wait until we have received something
+                        wait_rxne(regs, timeout)?;
+
+                        *byte = regs.rxdr().read().rxdata();
+                        chunk_len -= 1;
                     }
                 }
-
-                for byte in chunk {
-                    // Wait until we are allowed to send data
-                    // (START has been ACKed or last byte when
-                    // through)
-                    if let Err(err) = self.wait_txe(timeout) {
-                        self.master_stop();
-                        return Err(err);
+                Operation::Write(write) => {
+                    for byte in write.iter() {
+                        if chunk_len == 0 {
+                            chunk_len = remaining_len.min(255);
+                            remaining_len -= chunk_len;
+                            master_continue(regs, chunk_len, remaining_len > 0, timeout)?;
+                        }
+
+                        // Wait until we are allowed to send data
+                        wait_txe(regs, timeout)?;
+
+                        regs.txdr().write(|w| w.set_txdata(*byte));
+                        chunk_len -= 1;
                     }
-
-                    // Put byte on the wire
-                    //self.i2c.txdr.write(|w| w.txdata().bits(*byte));
-                    T::regs().txdr().write(|w| w.set_txdata(*byte));
                 }
             }
         }
-        // Wait until the write finishes
-        let result = self.wait_tc(timeout);
-        self.master_stop();
-        result
+
+        // Wait until the group finishes, before the repeated START or the STOP.
+        wait_tc(regs, timeout)?;
+        restart = true;
+    }
+
+    Ok(())
+}
+
+impl Address {
+    fn sadd(self) -> u16 {
+        match self {
+            // In 7-bit mode, SADD[7:1] holds the address.
+            Address::SevenBit(addr) => (addr as u16) << 1,
+            Address::TenBit(addr) => addr & 0x3FF,
+        }
+    }
+
+    fn addmode(self) -> i2c::vals::Addmode {
+        match self {
+            Address::SevenBit(_) => i2c::vals::Addmode::BIT7,
+            Address::TenBit(_) => i2c::vals::Addmode::BIT10,
+        }
     }
 }
 
 impl<'d, T: Instance, TXDMA, RXDMA> Drop for I2c<'d, T, TXDMA, RXDMA> {
     fn drop(&mut self) {
+        self.auto_idle.set(false);
         T::disable();
     }
 }
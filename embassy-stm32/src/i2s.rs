@@ -1,6 +1,10 @@
 //! Inter-IC Sound (I2S)
+//!
+//! I2S mode of the SPI peripheral, for the parts without SAI.
 use embassy_hal_internal::into_ref;
 
+use crate::dma::word::WordSize;
+use crate::dma::{NoDma, Transfer};
 use crate::gpio::sealed::{AFType, Pin as _};
 use crate::gpio::AnyPin;
 use crate::pac::spi::vals;
@@ -63,6 +67,13 @@ impl Standard {
 }
 
 /// I2S data format.
+///
+/// The samples are `u16` words with the 16 bit data lengths, and `u32` words with the 24 and 32 bit
+/// data lengths. 24 bit samples are left aligned in the 32 bits.
+///
+/// The data register is 16 bit wide, so the DMA transfers the `u32` samples as two half-words, in
+/// memory order. The I2S sends the most significant half-word first, which the `u32` samples must
+/// hold in their low half-word: convert them with `sample.rotate_left(16)`.
 #[derive(Copy, Clone)]
 pub enum Format {
     /// 16 bit data length on 16 bit wide channel
@@ -76,6 +87,14 @@ pub enum Format {
 }
 
 impl Format {
+    /// Size of the samples read and written.
+    const fn word_size(&self) -> WordSize {
+        match self {
+            Format::Data16Channel16 | Format::Data16Channel32 => WordSize::TwoBytes,
+            Format::Data24Channel32 | Format::Data32Channel32 => WordSize::FourBytes,
+        }
+    }
+
     #[cfg(any(spi_v1, spi_f1))]
     const fn datlen(&self) -> vals::Datlen {
         match self {
@@ -154,15 +173,20 @@ impl Default for Config {
 
 /// I2S driver.
 pub struct I2S<'d, T: Instance, Tx, Rx> {
-    _peri: Spi<'d, T, Tx, Rx>,
+    _peri: Spi<'d, T, NoDma, NoDma>,
+    txdma: PeripheralRef<'d, Tx>,
+    rxdma: PeripheralRef<'d, Rx>,
     sd: Option<PeripheralRef<'d, AnyPin>>,
     ws: Option<PeripheralRef<'d, AnyPin>>,
     ck: Option<PeripheralRef<'d, AnyPin>>,
     mck: Option<PeripheralRef<'d, AnyPin>>,
+    format: Format,
 }
 
 impl<'d, T: Instance, Tx, Rx> I2S<'d, T, Tx, Rx> {
-    /// Note: Full-Duplex modes are not supported at this time
+    /// Create a new I2S driver, with the master clock output on `mck`.
+    ///
+    /// `freq` is the sample rate in master mode. Note: Full-Duplex modes are not supported at this time
     pub fn new(
         peri: impl Peripheral<P = T> + 'd,
         sd: impl Peripheral<P = impl MosiPin<T>> + 'd,
@@ -174,31 +198,60 @@ impl<'d, T: Instance, Tx, Rx> I2S<'d, T, Tx, Rx> {
         freq: Hertz,
         config: Config,
     ) -> Self {
-        into_ref!(sd, ws, ck, mck);
+        into_ref!(mck);
+
+        mck.set_as_af(mck.af_num(), AFType::OutputPushPull);
+        mck.set_speed(crate::gpio::Speed::VeryHigh);
+
+        Self::new_inner(peri, sd, ws, ck, Some(mck.map_into()), txdma, rxdma, freq, config)
+    }
+
+    /// Create a new I2S driver without master clock output.
+    ///
+    /// `freq` is the sample rate in master mode. Note: Full-Duplex modes are not supported at this time
+    pub fn new_no_mck(
+        peri: impl Peripheral<P = T> + 'd,
+        sd: impl Peripheral<P = impl MosiPin<T>> + 'd,
+        ws: impl Peripheral<P = impl WsPin<T>> + 'd,
+        ck: impl Peripheral<P = impl CkPin<T>> + 'd,
+        txdma: impl Peripheral<P = Tx> + 'd,
+        rxdma: impl Peripheral<P = Rx> + 'd,
+        freq: Hertz,
+        mut config: Config,
+    ) -> Self {
+        config.master_clock = false;
+        Self::new_inner(peri, sd, ws, ck, None, txdma, rxdma, freq, config)
+    }
 
-        sd.set_as_af(sd.af_num(), AFType::OutputPushPull);
+    fn new_inner(
+        peri: impl Peripheral<P = T> + 'd,
+        sd: impl Peripheral<P = impl MosiPin<T>> + 'd,
+        ws: impl Peripheral<P = impl WsPin<T>> + 'd,
+        ck: impl Peripheral<P = impl CkPin<T>> + 'd,
+        mck: Option<PeripheralRef<'d, AnyPin>>,
+        txdma: impl Peripheral<P = Tx> + 'd,
+        rxdma: impl Peripheral<P = Rx> + 'd,
+        freq: Hertz,
+        config: Config,
+    ) -> Self {
+        into_ref!(sd, ws, ck, txdma, rxdma);
+
+        let (sd_af_type, ck_af_type) = get_af_types(config.mode, config.function);
+
+        sd.set_as_af(sd.af_num(), sd_af_type);
         sd.set_speed(crate::gpio::Speed::VeryHigh);
 
-        ws.set_as_af(ws.af_num(), AFType::OutputPushPull);
+        ws.set_as_af(ws.af_num(), ck_af_type);
         ws.set_speed(crate::gpio::Speed::VeryHigh);
 
-        ck.set_as_af(ck.af_num(), AFType::OutputPushPull);
+        ck.set_as_af(ck.af_num(), ck_af_type);
         ck.set_speed(crate::gpio::Speed::VeryHigh);
 
-        mck.set_as_af(mck.af_num(), AFType::OutputPushPull);
-        mck.set_speed(crate::gpio::Speed::VeryHigh);
-
         let mut spi_cfg = SpiConfig::default();
         spi_cfg.frequency = freq;
-        let spi = Spi::new_internal(peri, txdma, rxdma, spi_cfg);
+        let spi = Spi::new_internal(peri, NoDma, NoDma, spi_cfg);
 
-        // TODO move i2s to the new mux infra.
-        //#[cfg(all(rcc_f4, not(stm32f410)))]
-        //let pclk = unsafe { get_freqs() }.plli2s1_q.unwrap();
-        //#[cfg(stm32f410)]
-        let pclk = T::frequency();
-
-        let (odd, div) = compute_baud_rate(pclk, freq, config.master_clock, config.format);
+        let (odd, div) = compute_baud_rate(i2s_clock::<T>(), freq, config.master_clock, config.format);
 
         #[cfg(any(spi_v1, spi_f1))]
         {
@@ -258,33 +311,88 @@ impl<'d, T: Instance, Tx, Rx> I2S<'d, T, Tx, Rx> {
 
         Self {
             _peri: spi,
+            txdma,
+            rxdma,
             sd: Some(sd.map_into()),
             ws: Some(ws.map_into()),
             ck: Some(ck.map_into()),
-            mck: Some(mck.map_into()),
+            mck,
+            format: config.format,
         }
     }
 
-    /// Write audio data.
+    /// Write audio data, using DMA.
+    ///
+    /// The samples of the left and right channels alternate, see [`Format`] for the 24 and 32 bit
+    /// samples and their word type `W`. This waits until the DMA transfer completes, and the next
+    /// call starts a new one, so playback is not gapless: the I2S keeps running between the calls,
+    /// and sends the underrun data until the next transfer starts. Write whole buffers to limit the
+    /// gaps.
+    ///
+    /// Panics if `W` doesn't match the configured format.
     pub async fn write<W: Word>(&mut self, data: &[W]) -> Result<(), Error>
     where
         Tx: TxDma<T>,
     {
-        self._peri.write(data).await
+        assert!(W::size() == self.format.word_size());
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let tx_request = self.txdma.request();
+        let tx_dst = T::REGS.dr().as_ptr() as *mut u16;
+        let tx_src = core::ptr::slice_from_raw_parts(data.as_ptr() as *const u16, core::mem::size_of_val(data) / 2);
+        let tx_f = unsafe { Transfer::new_write_raw(&mut self.txdma, tx_request, tx_src, tx_dst, Default::default()) };
+
+        T::REGS.cr2().modify(|w| w.set_txdmaen(true));
+        tx_f.await;
+        T::REGS.cr2().modify(|w| w.set_txdmaen(false));
+
+        Ok(())
     }
 
-    /// Read audio data.
+    /// Read audio data, using DMA.
+    ///
+    /// The samples of the left and right channels alternate, see [`Format`] for the 24 and 32 bit
+    /// samples and their word type `W`. The I2S keeps receiving between the calls, and the samples
+    /// received in the meantime are lost.
+    ///
+    /// Panics if `W` doesn't match the configured format.
     pub async fn read<W: Word>(&mut self, data: &mut [W]) -> Result<(), Error>
     where
-        Tx: TxDma<T>,
         Rx: RxDma<T>,
     {
-        self._peri.read(data).await
+        assert!(W::size() == self.format.word_size());
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        // Drop the sample received before this call, and clear the overrun flag.
+        let _ = T::REGS.dr().read();
+        let _ = T::REGS.sr().read();
+
+        let rx_request = self.rxdma.request();
+        let rx_src = T::REGS.dr().as_ptr() as *mut u16;
+        let rx_dst =
+            core::ptr::slice_from_raw_parts_mut(data.as_mut_ptr() as *mut u16, core::mem::size_of_val(data) / 2);
+        let rx_f = unsafe { Transfer::new_read_raw(&mut self.rxdma, rx_request, rx_src, rx_dst, Default::default()) };
+
+        T::REGS.cr2().modify(|w| w.set_rxdmaen(true));
+        rx_f.await;
+        T::REGS.cr2().modify(|w| w.set_rxdmaen(false));
+
+        if T::REGS.sr().read().ovr() {
+            return Err(Error::Overrun);
+        }
+        Ok(())
     }
 }
 
 impl<'d, T: Instance, Tx, Rx> Drop for I2S<'d, T, Tx, Rx> {
     fn drop(&mut self) {
+        #[cfg(any(spi_v1, spi_f1))]
+        T::REGS.i2scfgr().modify(|w| w.set_i2se(false));
+
         self.sd.as_ref().map(|x| x.set_as_disconnected());
         self.ws.as_ref().map(|x| x.set_as_disconnected());
         self.ck.as_ref().map(|x| x.set_as_disconnected());
@@ -292,6 +400,36 @@ impl<'d, T: Instance, Tx, Rx> Drop for I2S<'d, T, Tx, Rx> {
     }
 }
 
+// return the type for (sd, ck and ws)
+fn get_af_types(mode: Mode, function: Function) -> (AFType, AFType) {
+    (
+        // sd is defined by the function
+        match function {
+            Function::Transmit => AFType::OutputPushPull,
+            Function::Receive => AFType::Input,
+        },
+        // clocks are defined by master/slave
+        match mode {
+            Mode::Master => AFType::OutputPushPull,
+            Mode::Slave => AFType::Input,
+        },
+    )
+}
+
+/// Clock of the I2S clock generator.
+fn i2s_clock<T: Instance>() -> Hertz {
+    cfg_if::cfg_if! {
+        if #[cfg(any(stm32f2, all(stm32f4, not(stm32f410))))] {
+            // PLLI2S R is the default I2S clock source on all parts with a PLLI2S.
+            unwrap!(unsafe { crate::rcc::get_freqs() }.plli2s1_r, "I2S requires the PLLI2S R output")
+        } else if #[cfg(any(stm32f1, stm32l1))] {
+            unwrap!(unsafe { crate::rcc::get_freqs() }.sys)
+        } else {
+            T::frequency()
+        }
+    }
+}
+
 // Note, calculation details:
 // Fs = i2s_clock / [256 * ((2 * div) + odd)] when master clock is enabled
 // Fs = i2s_clock / [(channel_length * 2) * ((2 * div) + odd)]` when master clock is disabled
@@ -15,6 +15,12 @@ mod fmt;
 include!(concat!(env!("OUT_DIR"), "/_macros.rs"));
 
 // Utilities
+#[cfg(feature = "time")]
+pub mod bitbang;
+#[cfg(feature = "debug-dump")]
+pub mod debug_dump;
+pub mod error;
+pub mod mode;
 pub mod time;
 mod traits;
 
@@ -23,6 +29,7 @@ pub mod dma;
 pub mod gpio;
 pub mod rcc;
 #[cfg(feature = "_time-driver")]
+#[cfg_attr(time_driver_lptim1, path = "time_driver_lptim.rs")]
 mod time_driver;
 pub mod timer;
 
@@ -34,6 +41,8 @@ pub mod adc;
 pub mod can;
 #[cfg(crc)]
 pub mod crc;
+#[cfg(cryp)]
+pub mod cryp;
 #[cfg(dac)]
 pub mod dac;
 #[cfg(dcmi)]
@@ -45,11 +54,13 @@ pub mod exti;
 pub mod flash;
 #[cfg(fmc)]
 pub mod fmc;
+#[cfg(hash)]
+pub mod hash;
 #[cfg(hrtim)]
 pub mod hrtim;
 #[cfg(i2c)]
 pub mod i2c;
-#[cfg(all(spi_v1, rcc_f4))]
+#[cfg(any(all(spi_v1, any(rcc_f4, rcc_f400, rcc_f410)), spi_f1))]
 pub mod i2s;
 #[cfg(stm32wb)]
 pub mod ipcc;
@@ -69,6 +80,8 @@ pub mod sai;
 pub mod sdmmc;
 #[cfg(spi)]
 pub mod spi;
+#[cfg(all(stm32l4_nonplus, dac, adc_v3))]
+pub mod trigger;
 #[cfg(uid)]
 pub mod uid;
 #[cfg(usart)]
@@ -146,6 +159,7 @@ macro_rules! bind_interrupts {
 // Reexports
 pub use _generated::{peripherals, Peripherals};
 pub use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
+pub use error::{Error, ErrorKind};
 #[cfg(feature = "unstable-pac")]
 pub use stm32_metapac as pac;
 #[cfg(not(feature = "unstable-pac"))]
@@ -14,7 +14,12 @@
 //!
 //! Since entering and leaving low-power modes typically incurs a significant latency, the
 //! low-power executor will only attempt to enter when the next timer event is at least
-//! [`time_driver::MIN_STOP_PAUSE`] in the future.
+//! [`time_driver::MIN_STOP_PAUSE`] in the future. With the `time-driver-lptim1` feature, the time
+//! keeps running in stop mode, so the executor enters it whenever it is idle.
+//!
+//! The HSE, the PLLs and the system clock are restored when the executor resumes after a stop mode,
+//! as configured by `embassy_stm32::init`. The interrupt waking the core up still runs on the wakeup
+//! clock of the stop mode (HSI or MSI).
 //!
 //! Currently there is no macro analogous to `embassy_executor::main` for this executor;
 //! consequently one must define their entrypoint manually. Moveover, you must relinquish control
@@ -59,6 +64,7 @@ use cortex_m::peripheral::SCB;
 use embassy_executor::*;
 
 use crate::interrupt;
+use crate::rcc::StopClocks;
 use crate::time_driver::{get_driver, RtcDriver};
 
 const THREAD_PENDER: usize = usize::MAX;
@@ -137,6 +143,7 @@ pub struct Executor {
     not_send: PhantomData<*mut ()>,
     scb: SCB,
     time_driver: &'static RtcDriver,
+    stop_clocks: Option<StopClocks>,
 }
 
 impl Executor {
@@ -150,6 +157,7 @@ impl Executor {
                 not_send: PhantomData,
                 scb: cortex_m::Peripherals::steal().SCB,
                 time_driver: get_driver(),
+                stop_clocks: None,
             });
 
             EXECUTOR.as_mut().unwrap()
@@ -204,7 +212,20 @@ impl Executor {
             self.configure_stop(stop_mode);
 
             #[cfg(not(feature = "low-power-debug-with-sleep"))]
-            self.scb.set_sleepdeep();
+            {
+                self.stop_clocks = Some(StopClocks::save());
+                self.scb.set_sleepdeep();
+            }
+        }
+    }
+
+    /// Restore the clocks after a stop mode.
+    ///
+    /// The interrupt waking the core up runs before, with the wakeup clock of the stop mode.
+    fn restore_clocks(&mut self) {
+        if let Some(clocks) = self.stop_clocks.take() {
+            clocks.restore();
+            trace!("low power: clocks restored");
         }
     }
 
@@ -234,6 +255,7 @@ impl Executor {
                 EXECUTOR.as_mut().unwrap().inner.poll();
                 self.configure_pwr();
                 asm!("wfe");
+                self.restore_clocks();
             };
         }
     }
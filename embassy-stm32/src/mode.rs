@@ -0,0 +1,43 @@
+//! Operating modes of the drivers.
+//!
+//! Drivers taking a mode parameter only have their async methods in [`Async`] mode, which their
+//! constructors binding the interrupt return: an async method of a driver without interrupt is a
+//! compile error, instead of a future that never completes.
+//!
+//! The modes are also passed in place of the DMA channels of the drivers falling back to
+//! interrupts without DMA: the USART drivers created by `new_blocking` take [`Blocking`], and the
+//! SPI driver created by [`Spi::new_interrupt`](crate::spi::Spi::new_interrupt) takes [`Async`].
+
+use embassy_hal_internal::impl_peripheral;
+
+pub(crate) mod sealed {
+    pub trait Mode {}
+}
+
+/// Operating mode of a driver.
+pub trait Mode: sealed::Mode {}
+
+/// Blocking mode: the driver has no interrupt bound, and only its blocking methods are available.
+pub struct Blocking;
+
+/// Async mode: the interrupt of the driver is bound, and its async methods are available as well.
+///
+/// Only the drivers can create it, when they bind the interrupt.
+pub struct Async {
+    _private: (),
+}
+
+impl Async {
+    pub(crate) const fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+impl_peripheral!(Blocking);
+impl_peripheral!(Async);
+
+impl sealed::Mode for Blocking {}
+impl Mode for Blocking {}
+
+impl sealed::Mode for Async {}
+impl Mode for Async {}
@@ -3,18 +3,36 @@
 #![macro_use]
 
 pub mod enums;
+mod xip;
 
+use embassy_futures::yield_now;
 use embassy_hal_internal::{into_ref, PeripheralRef};
 use enums::*;
+pub use xip::{XipConfig, XipError, XipFlash};
 
 use crate::dma::Transfer;
 use crate::gpio::sealed::AFType;
 use crate::gpio::{AnyPin, Pull};
+use crate::pac::quadspi::regs::Ccr;
 use crate::pac::quadspi::Quadspi as Regs;
 use crate::rcc::RccPeripheral;
 use crate::{peripherals, Peripheral};
 
+/// Address of the flash memory in memory-mapped mode.
+pub const MEMORY_MAPPED_BASE: usize = 0x9000_0000;
+
 /// QSPI transfer configuration.
+///
+/// This describes any command of a NOR flash chip: build it with [`TransferConfig::instruction`]
+/// and the `with_*` methods, e.g. a quad output fast read:
+///
+/// ```rust,ignore
+/// let read = TransferConfig::instruction(0x6B, QspiWidth::SING)
+///     .with_address(addr, QspiWidth::SING)
+///     .with_dummy(DummyCycles::_8)
+///     .with_data(buf.len(), QspiWidth::QUAD);
+/// ```
+#[derive(Copy, Clone)]
 pub struct TransferConfig {
     /// Instraction width (IMODE)
     pub iwidth: QspiWidth,
@@ -46,6 +64,68 @@ impl Default for TransferConfig {
     }
 }
 
+impl TransferConfig {
+    /// Transfer of `instruction` alone, sent on `width` lanes.
+    pub fn instruction(instruction: u8, width: QspiWidth) -> Self {
+        Self {
+            iwidth: width,
+            instruction,
+            ..Default::default()
+        }
+    }
+
+    /// Send `address` after the instruction, on `width` lanes.
+    pub fn with_address(self, address: u32, width: QspiWidth) -> Self {
+        Self {
+            awidth: width,
+            address: Some(address),
+            ..self
+        }
+    }
+
+    /// Insert dummy cycles before the data phase.
+    pub fn with_dummy(self, dummy: DummyCycles) -> Self {
+        Self { dummy, ..self }
+    }
+
+    /// Transfer `len` bytes of data on `width` lanes.
+    pub fn with_data(self, len: usize, width: QspiWidth) -> Self {
+        Self {
+            dwidth: width,
+            data_len: Some(len),
+            ..self
+        }
+    }
+}
+
+/// Status polling configuration.
+///
+/// The status register is read with `transaction` until `status & mask == value`.
+#[derive(Copy, Clone)]
+pub struct StatusPolling {
+    /// Command reading the status register, with a data length of 1 to 4 bytes.
+    pub transaction: TransferConfig,
+    /// Bits of the status to check.
+    pub mask: u32,
+    /// Expected value of the checked bits.
+    pub value: u32,
+    /// Number of clock cycles between two reads of the status.
+    pub interval: u16,
+}
+
+impl StatusPolling {
+    /// Wait for the busy bit (bit 0) of the status register of most NOR flash chips, read with
+    /// instruction 0x05, to clear.
+    pub fn busy_bit() -> Self {
+        Self {
+            transaction: TransferConfig::instruction(0x05, QspiWidth::SING).with_data(1, QspiWidth::SING),
+            mask: 0x01,
+            value: 0x00,
+            interval: 16,
+        }
+    }
+}
+
 /// QSPI driver configuration.
 pub struct Config {
     /// Flash memory size representend as 2^[0-32], as reasonable minimum 1KiB(9) was chosen.
@@ -288,6 +368,22 @@ impl<'d, T: Instance, Dma> Qspi<'d, T, Dma> {
 
     /// Blocking read data, using DMA.
     pub fn blocking_read_dma(&mut self, buf: &mut [u8], transaction: TransferConfig)
+    where
+        Dma: QuadDma<T>,
+    {
+        self.start_read_dma(buf, transaction).blocking_wait();
+    }
+
+    /// Read data, using DMA.
+    pub async fn read_dma(&mut self, buf: &mut [u8], transaction: TransferConfig)
+    where
+        Dma: QuadDma<T>,
+    {
+        self.start_read_dma(buf, transaction).await;
+        Self::finish_transaction().await;
+    }
+
+    fn start_read_dma<'a>(&'a mut self, buf: &'a mut [u8], transaction: TransferConfig) -> Transfer<'a, Dma>
     where
         Dma: QuadDma<T>,
     {
@@ -316,11 +412,27 @@ impl<'d, T: Instance, Dma> Qspi<'d, T, Dma> {
         #[cfg(not(stm32h7))]
         T::REGS.cr().modify(|v| v.set_dmaen(true));
 
-        transfer.blocking_wait();
+        transfer
     }
 
     /// Blocking write data, using DMA.
     pub fn blocking_write_dma(&mut self, buf: &[u8], transaction: TransferConfig)
+    where
+        Dma: QuadDma<T>,
+    {
+        self.start_write_dma(buf, transaction).blocking_wait();
+    }
+
+    /// Write data, using DMA.
+    pub async fn write_dma(&mut self, buf: &[u8], transaction: TransferConfig)
+    where
+        Dma: QuadDma<T>,
+    {
+        self.start_write_dma(buf, transaction).await;
+        Self::finish_transaction().await;
+    }
+
+    fn start_write_dma<'a>(&'a mut self, buf: &'a [u8], transaction: TransferConfig) -> Transfer<'a, Dma>
     where
         Dma: QuadDma<T>,
     {
@@ -345,7 +457,66 @@ impl<'d, T: Instance, Dma> Qspi<'d, T, Dma> {
         #[cfg(not(stm32h7))]
         T::REGS.cr().modify(|v| v.set_dmaen(true));
 
-        transfer.blocking_wait();
+        transfer
+    }
+
+    /// Poll a status register until it matches, using the automatic status-polling mode.
+    ///
+    /// The peripheral has no interrupt bound, so this yields to the executor between checks.
+    pub async fn poll_status(&mut self, polling: StatusPolling) {
+        #[cfg(not(stm32h7))]
+        T::REGS.cr().modify(|v| v.set_dmaen(false));
+
+        T::REGS.psmkr().write(|v| v.set_mask(polling.mask));
+        T::REGS.psmar().write(|v| v.set_match_(polling.value));
+        T::REGS.pir().write(|v| v.set_interval(polling.interval));
+        T::REGS.cr().modify(|v| {
+            // Stop at the first match
+            v.set_apms(true);
+            v.set_pmm(false);
+        });
+        self.setup_transaction(QspiMode::AutoPolling, &polling.transaction);
+
+        while !T::REGS.sr().read().smf() {
+            yield_now().await;
+        }
+        T::REGS.fcr().modify(|v| v.set_csmf(true));
+        Self::finish_transaction().await;
+    }
+
+    /// Erase a sector, a block or the whole chip, and wait for the end of the erase.
+    ///
+    /// `write_enable` is sent before the `erase` command, then the status is polled with `polling`.
+    pub async fn erase(&mut self, write_enable: TransferConfig, erase: TransferConfig, polling: StatusPolling) {
+        self.command(write_enable);
+        self.command(erase);
+        self.poll_status(polling).await;
+    }
+
+    /// Enable the memory-mapped mode, for execute-in-place.
+    ///
+    /// The flash can then be read at [`MEMORY_MAPPED_BASE`], each access being done with
+    /// `transaction`, without address and data length. Indirect transfers must not be used until
+    /// [`disable_memory_mapped_mode`](Self::disable_memory_mapped_mode) is called.
+    pub fn enable_memory_mapped_mode(&mut self, transaction: TransferConfig) {
+        #[cfg(not(stm32h7))]
+        T::REGS.cr().modify(|v| v.set_dmaen(false));
+
+        self.setup_transaction(QspiMode::MemoryMapped, &transaction);
+    }
+
+    /// Leave the memory-mapped mode.
+    pub fn disable_memory_mapped_mode(&mut self) {
+        T::REGS.cr().modify(|v| v.set_abort(true));
+        while T::REGS.cr().read().abort() {}
+        while T::REGS.sr().read().busy() {}
+    }
+
+    async fn finish_transaction() {
+        while !T::REGS.sr().read().tcf() {
+            yield_now().await;
+        }
+        T::REGS.fcr().modify(|v| v.set_ctcf(true));
     }
 
     fn setup_transaction(&mut self, fmode: QspiMode, transaction: &TransferConfig) {
@@ -362,16 +533,7 @@ impl<'d, T: Instance, Dma> Qspi<'d, T, Dma> {
             T::REGS.dlr().write(|v| v.set_dl(len as u32 - 1));
         }
 
-        T::REGS.ccr().write(|v| {
-            v.set_fmode(fmode.into());
-            v.set_imode(transaction.iwidth.into());
-            v.set_instruction(transaction.instruction);
-            v.set_admode(transaction.awidth.into());
-            v.set_adsize(self.config.address_size.into());
-            v.set_dmode(transaction.dwidth.into());
-            v.set_abmode(QspiWidth::NONE.into());
-            v.set_dcyc(transaction.dummy.into());
-        });
+        T::REGS.ccr().write_value(self.ccr(fmode, transaction));
 
         if let Some(addr) = transaction.address {
             T::REGS.ar().write(|v| {
@@ -379,6 +541,19 @@ impl<'d, T: Instance, Dma> Qspi<'d, T, Dma> {
             });
         }
     }
+
+    fn ccr(&self, fmode: QspiMode, transaction: &TransferConfig) -> Ccr {
+        let mut v = Ccr::default();
+        v.set_fmode(fmode.into());
+        v.set_imode(transaction.iwidth.into());
+        v.set_instruction(transaction.instruction);
+        v.set_admode(transaction.awidth.into());
+        v.set_adsize(self.config.address_size.into());
+        v.set_dmode(transaction.dwidth.into());
+        v.set_abmode(QspiWidth::NONE.into());
+        v.set_dcyc(transaction.dummy.into());
+        v
+    }
 }
 
 pub(crate) mod sealed {
@@ -0,0 +1,313 @@
+//! Flash writes and erases while executing in place.
+//!
+//! The flash can't be read while it is programmed or erased, so the memory-mapped mode is
+//! suspended during these operations, which then run from RAM with the interrupts disabled: any
+//! code executed from the QSPI flash in the meantime would hard fault.
+
+use embedded_storage::nor_flash::{ErrorType, NorFlash, NorFlashError, NorFlashErrorKind, ReadNorFlash};
+
+use super::enums::{DummyCycles, QspiMode, QspiWidth};
+use super::{Instance, Qspi, StatusPolling, TransferConfig, MEMORY_MAPPED_BASE};
+use crate::pac::quadspi::regs::{Ar, Ccr, Dlr, Fcr, Pir, Psmar, Psmkr};
+use crate::pac::quadspi::Quadspi as Regs;
+
+/// Maximum page size, the pages are copied to RAM before being programmed.
+const MAX_PAGE_SIZE: usize = 256;
+/// Size of the sectors erased by [`XipConfig::sector_erase`].
+const SECTOR_SIZE: usize = 4096;
+
+/// XIP flash configuration, describing the commands of the NOR flash chip.
+#[derive(Copy, Clone)]
+pub struct XipConfig {
+    /// Memory-mapped read command. Its address and data length are ignored.
+    pub read: TransferConfig,
+    /// Write enable command, sent before each page program or sector erase.
+    pub write_enable: TransferConfig,
+    /// Page program command. Its address and data length are set for each page.
+    pub page_program: TransferConfig,
+    /// Erase command of 4 KiB sectors. Its address is set for each sector.
+    pub sector_erase: TransferConfig,
+    /// Polling of the status until the end of the page programs and sector erases.
+    pub status: StatusPolling,
+    /// Page size, in bytes, at most 256 bytes.
+    pub page_size: usize,
+}
+
+impl Default for XipConfig {
+    /// Commands supported by most NOR flash chips, on a single lane: fast read, page program
+    /// of 256-byte pages and erase of 4 KiB sectors.
+    fn default() -> Self {
+        Self {
+            read: TransferConfig {
+                iwidth: QspiWidth::SING,
+                awidth: QspiWidth::SING,
+                dwidth: QspiWidth::SING,
+                instruction: 0x0B,
+                dummy: DummyCycles::_8,
+                ..Default::default()
+            },
+            write_enable: TransferConfig::instruction(0x06, QspiWidth::SING),
+            page_program: TransferConfig::instruction(0x02, QspiWidth::SING)
+                .with_address(0, QspiWidth::SING)
+                .with_data(1, QspiWidth::SING),
+            sector_erase: TransferConfig::instruction(0x20, QspiWidth::SING).with_address(0, QspiWidth::SING),
+            status: StatusPolling::busy_bit(),
+            page_size: 256,
+        }
+    }
+}
+
+/// XIP flash error.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum XipError {
+    /// The operation is outside of the flash.
+    OutOfBounds,
+    /// The erase is not aligned to sectors.
+    NotAligned,
+}
+
+impl NorFlashError for XipError {
+    fn kind(&self) -> NorFlashErrorKind {
+        match self {
+            Self::OutOfBounds => NorFlashErrorKind::OutOfBounds,
+            Self::NotAligned => NorFlashErrorKind::NotAligned,
+        }
+    }
+}
+
+/// Flash executed in place, which can still be written and erased.
+///
+/// The flash stays in memory-mapped mode, and each page program or sector erase suspends it,
+/// runs from RAM, then resumes it. The interrupts are disabled during each operation, up to the
+/// sector erase time of the chip, typically tens of milliseconds. The DMA channels must not read
+/// the flash during the operations either.
+///
+/// The cached contents of the flash are not invalidated: if the data cache of the core is enabled,
+/// invalidate the modified range before reading it back.
+pub struct XipFlash<'d, T: Instance, Dma> {
+    qspi: Qspi<'d, T, Dma>,
+    config: XipConfig,
+}
+
+impl<'d, T: Instance, Dma> XipFlash<'d, T, Dma> {
+    /// Create a new XIP flash, enabling the memory-mapped mode.
+    ///
+    /// The flash chip must already be configured for the commands of `config`, for example with
+    /// its quad mode enabled.
+    pub fn new(mut qspi: Qspi<'d, T, Dma>, config: XipConfig) -> Self {
+        assert!(config.page_size <= MAX_PAGE_SIZE);
+        qspi.enable_memory_mapped_mode(config.read);
+        Self { qspi, config }
+    }
+
+    /// Size of the flash, in bytes, from the memory size of the QSPI configuration.
+    pub fn capacity(&self) -> usize {
+        1 << (T::REGS.dcr().read().fsize() as u32 + 1)
+    }
+
+    /// Read from the flash, through the memory-mapped mode.
+    pub fn blocking_read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), XipError> {
+        self.check_bounds(offset, bytes.len())?;
+        let src = (MEMORY_MAPPED_BASE + offset as usize) as *const u8;
+        bytes.copy_from_slice(unsafe { core::slice::from_raw_parts(src, bytes.len()) });
+        Ok(())
+    }
+
+    /// Program the flash, page by page. The bytes must have been erased.
+    pub fn blocking_write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), XipError> {
+        self.check_bounds(offset, bytes.len())?;
+
+        let mut offset = offset as usize;
+        let mut bytes = bytes;
+        while !bytes.is_empty() {
+            let len = bytes.len().min(self.config.page_size - offset % self.config.page_size);
+
+            // The data may be in the flash, which can't be read during the operation.
+            let mut page = [0; MAX_PAGE_SIZE];
+            page[..len].copy_from_slice(&bytes[..len]);
+
+            let program = TransferConfig {
+                address: Some(offset as u32),
+                data_len: Some(len),
+                ..self.config.page_program
+            };
+            self.run(&program, &page[..len]);
+
+            offset += len;
+            bytes = &bytes[len..];
+        }
+        Ok(())
+    }
+
+    /// Erase the sectors from `from` to `to`, both aligned to sectors.
+    pub fn blocking_erase(&mut self, from: u32, to: u32) -> Result<(), XipError> {
+        if to < from {
+            return Err(XipError::OutOfBounds);
+        }
+        self.check_bounds(from, (to - from) as usize)?;
+        if from as usize % SECTOR_SIZE != 0 || to as usize % SECTOR_SIZE != 0 {
+            return Err(XipError::NotAligned);
+        }
+
+        for address in (from..to).step_by(SECTOR_SIZE) {
+            let erase = TransferConfig {
+                address: Some(address),
+                ..self.config.sector_erase
+            };
+            self.run(&erase, &[]);
+        }
+        Ok(())
+    }
+
+    /// Leave the memory-mapped mode, and release the QSPI driver.
+    pub fn release(mut self) -> Qspi<'d, T, Dma> {
+        self.qspi.disable_memory_mapped_mode();
+        self.qspi
+    }
+
+    fn check_bounds(&self, offset: u32, len: usize) -> Result<(), XipError> {
+        match (offset as usize).checked_add(len) {
+            Some(end) if end <= self.capacity() => Ok(()),
+            _ => Err(XipError::OutOfBounds),
+        }
+    }
+
+    /// Run `transaction` with the memory-mapped mode suspended, then wait for its end.
+    fn run(&mut self, transaction: &TransferConfig, data: &[u8]) {
+        let status = &self.config.status;
+        let op = RamOperation {
+            write_enable: self.qspi.ccr(QspiMode::IndirectWrite, &self.config.write_enable),
+            operation: self.qspi.ccr(QspiMode::IndirectWrite, transaction),
+            address: transaction.address.unwrap_or(0),
+            data: data.as_ptr(),
+            len: data.len(),
+            poll: self.qspi.ccr(QspiMode::AutoPolling, &status.transaction),
+            poll_len: status.transaction.data_len.unwrap_or(1),
+            poll_mask: status.mask,
+            poll_value: status.value,
+            poll_interval: status.interval,
+            memory_mapped: self.qspi.ccr(QspiMode::MemoryMapped, &self.config.read),
+        };
+
+        critical_section::with(|_| unsafe { run_in_ram(T::REGS, &op) });
+    }
+}
+
+impl<'d, T: Instance, Dma> ErrorType for XipFlash<'d, T, Dma> {
+    type Error = XipError;
+}
+
+impl<'d, T: Instance, Dma> ReadNorFlash for XipFlash<'d, T, Dma> {
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        self.blocking_read(offset, bytes)
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<'d, T: Instance, Dma> NorFlash for XipFlash<'d, T, Dma> {
+    const WRITE_SIZE: usize = 1;
+    const ERASE_SIZE: usize = SECTOR_SIZE;
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.blocking_write(offset, bytes)
+    }
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        self.blocking_erase(from, to)
+    }
+}
+
+/// Operation run from RAM, with the register values prepared beforehand.
+struct RamOperation {
+    write_enable: Ccr,
+    operation: Ccr,
+    address: u32,
+    data: *const u8,
+    len: usize,
+    poll: Ccr,
+    poll_len: usize,
+    poll_mask: u32,
+    poll_value: u32,
+    poll_interval: u16,
+    memory_mapped: Ccr,
+}
+
+/// Suspend the memory-mapped mode, run the operation, wait for its end, and resume.
+///
+/// Everything here must run from RAM: only the inlined register accesses are used, and no
+/// functions that could be placed in flash, including the iterators and the slice indexing.
+///
+/// # Safety
+///
+/// The interrupts must be disabled, and `op.data` must point to `op.len` bytes in RAM.
+#[inline(never)]
+#[link_section = ".data.ram_func"]
+unsafe fn run_in_ram(regs: Regs, op: &RamOperation) {
+    // Leave the memory-mapped mode.
+    let mut cr = regs.cr().read();
+    cr.set_abort(true);
+    regs.cr().write_value(cr);
+    while regs.cr().read().abort() {}
+    while regs.sr().read().busy() {}
+
+    clear_flags(regs);
+    regs.ccr().write_value(op.write_enable);
+    wait_transfer_complete(regs);
+
+    clear_flags(regs);
+    if op.len > 0 {
+        regs.dlr().write_value(Dlr(op.len as u32 - 1));
+    }
+    regs.ccr().write_value(op.operation);
+    regs.ar().write_value(Ar(op.address));
+    let mut i = 0;
+    while i < op.len {
+        while !regs.sr().read().ftf() {}
+        (regs.dr().as_ptr() as *mut u8).write_volatile(op.data.add(i).read_volatile());
+        i += 1;
+    }
+    wait_transfer_complete(regs);
+
+    // Wait for the end of the operation, stopping at the first match.
+    regs.psmkr().write_value(Psmkr(op.poll_mask));
+    regs.psmar().write_value(Psmar(op.poll_value));
+    let mut pir = Pir(0);
+    pir.set_interval(op.poll_interval);
+    regs.pir().write_value(pir);
+    let mut cr = regs.cr().read();
+    cr.set_apms(true);
+    cr.set_pmm(false);
+    regs.cr().write_value(cr);
+    clear_flags(regs);
+    regs.dlr().write_value(Dlr(op.poll_len as u32 - 1));
+    regs.ccr().write_value(op.poll);
+    while !regs.sr().read().smf() {}
+    while regs.sr().read().busy() {}
+
+    clear_flags(regs);
+    regs.ccr().write_value(op.memory_mapped);
+}
+
+#[inline(always)]
+unsafe fn clear_flags(regs: Regs) {
+    let mut fcr = Fcr(0);
+    fcr.set_csmf(true);
+    fcr.set_ctcf(true);
+    fcr.set_ctef(true);
+    fcr.set_ctof(true);
+    regs.fcr().write_value(fcr);
+}
+
+#[inline(always)]
+unsafe fn wait_transfer_complete(regs: Regs) {
+    while !regs.sr().read().tcf() {}
+    let mut fcr = Fcr(0);
+    fcr.set_ctcf(true);
+    regs.fcr().write_value(fcr);
+}
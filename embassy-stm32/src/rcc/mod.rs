@@ -3,6 +3,7 @@
 #![macro_use]
 #![allow(missing_docs)] // TODO
 
+use core::marker::PhantomData;
 use core::mem::MaybeUninit;
 
 mod bd;
@@ -73,11 +74,16 @@ pub(crate) mod sealed {
     pub trait RccPeripheral {
         fn frequency() -> crate::time::Hertz;
         fn enable_and_reset_with_cs(cs: CriticalSection);
+        /// Enable the clock without resetting the peripheral, which keeps its configuration.
+        fn enable_with_cs(cs: CriticalSection);
         fn disable_with_cs(cs: CriticalSection);
 
         fn enable_and_reset() {
             critical_section::with(|cs| Self::enable_and_reset_with_cs(cs))
         }
+        fn enable() {
+            critical_section::with(|cs| Self::enable_with_cs(cs))
+        }
         fn disable() {
             critical_section::with(|cs| Self::disable_with_cs(cs))
         }
@@ -86,6 +92,123 @@ pub(crate) mod sealed {
 
 pub trait RccPeripheral: sealed::RccPeripheral + 'static {}
 
+/// Clock gating of a peripheral between the transactions of its driver.
+///
+/// In the auto idle mode, the clock of the peripheral is only enabled while a transaction is in
+/// flight. The registers keep their values while the clock is gated, so the peripheral doesn't
+/// need to be configured again.
+pub(crate) struct AutoIdle<T: RccPeripheral> {
+    enabled: bool,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: RccPeripheral> AutoIdle<T> {
+    pub(crate) const fn new() -> Self {
+        Self {
+            enabled: false,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Enable or disable the auto idle mode, gating or ungating the clock now.
+    pub(crate) fn set(&mut self, enabled: bool) {
+        match (self.enabled, enabled) {
+            (false, true) => T::disable(),
+            (true, false) => T::enable(),
+            _ => {}
+        }
+        self.enabled = enabled;
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Enable the clock for a transaction, until the returned guard is dropped.
+    ///
+    /// The guard must be dropped after any access to the registers, so it is declared first. The
+    /// guards nest, as the clocks of the peripherals with an auto idle mode are reference counted.
+    #[must_use]
+    pub(crate) fn wake(&self) -> ClockGuard<T> {
+        if self.enabled {
+            T::enable();
+        }
+        ClockGuard {
+            gate: self.enabled,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// Clock enabled for a transaction in the auto idle mode, gated again when dropped.
+pub(crate) struct ClockGuard<T: RccPeripheral> {
+    gate: bool,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: RccPeripheral> Drop for ClockGuard<T> {
+    fn drop(&mut self) {
+        if self.gate {
+            T::disable();
+        }
+    }
+}
+
+/// Core clocks to restore after a stop mode.
+///
+/// Waking up from stop switches the system clock to the HSI or MSI, and turns off the HSE and the
+/// PLLs. Their configuration is retained, so they only need to be turned on again.
+#[cfg(feature = "low-power")]
+pub(crate) struct StopClocks {
+    cr: crate::pac::rcc::regs::Cr,
+    sw: crate::pac::rcc::vals::Sw,
+}
+
+#[cfg(feature = "low-power")]
+impl StopClocks {
+    /// Save the clocks before entering a stop mode.
+    pub(crate) fn save() -> Self {
+        let rcc = crate::pac::RCC;
+        Self {
+            cr: rcc.cr().read(),
+            sw: rcc.cfgr().read().sw(),
+        }
+    }
+
+    /// Turn the clocks on again, and switch back to the system clock.
+    pub(crate) fn restore(&self) {
+        let rcc = crate::pac::RCC;
+
+        if self.cr.hsion() && !rcc.cr().read().hsirdy() {
+            rcc.cr().modify(|w| w.set_hsion(true));
+            while !rcc.cr().read().hsirdy() {}
+        }
+        if self.cr.hseon() && !rcc.cr().read().hserdy() {
+            rcc.cr().modify(|w| w.set_hseon(true));
+            while !rcc.cr().read().hserdy() {}
+        }
+        if self.cr.pllon() && !rcc.cr().read().pllrdy() {
+            rcc.cr().modify(|w| w.set_pllon(true));
+            while !rcc.cr().read().pllrdy() {}
+        }
+        #[cfg(all(stm32f4, not(stm32f410)))]
+        if self.cr.plli2son() && !rcc.cr().read().plli2srdy() {
+            rcc.cr().modify(|w| w.set_plli2son(true));
+            while !rcc.cr().read().plli2srdy() {}
+        }
+        #[cfg(stm32l5)]
+        if self.cr.pllsai1on() && !rcc.cr().read().pllsai1rdy() {
+            rcc.cr().modify(|w| w.set_pllsai1on(true));
+            while !rcc.cr().read().pllsai1rdy() {}
+        }
+
+        if rcc.cfgr().read().sws() != self.sw {
+            rcc.cfgr().modify(|w| w.set_sw(self.sw));
+            while rcc.cfgr().read().sws() != self.sw {}
+        }
+    }
+}
+
 #[allow(unused)]
 mod util {
     use crate::time::Hertz;
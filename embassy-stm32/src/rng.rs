@@ -3,6 +3,7 @@
 
 use core::future::poll_fn;
 use core::marker::PhantomData;
+use core::num::NonZeroU32;
 use core::task::Poll;
 
 use embassy_hal_internal::{into_ref, PeripheralRef};
@@ -14,8 +15,11 @@ use crate::{interrupt, pac, peripherals, Peripheral};
 
 static RNG_WAKER: AtomicWaker = AtomicWaker::new();
 
+/// Number of consecutive restarts of the RNG after errors, before giving up on a word.
+const MAX_RESTARTS: u32 = 3;
+
 /// RNG error
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Error {
     /// Seed error.
@@ -26,6 +30,16 @@ pub enum Error {
     ClockError,
 }
 
+impl From<Error> for rand_core::Error {
+    fn from(err: Error) -> Self {
+        let code = match err {
+            Error::SeedError => 1,
+            Error::ClockError => 2,
+        };
+        rand_core::Error::from(unwrap!(NonZeroU32::new(rand_core::Error::CUSTOM_START + code)))
+    }
+}
+
 /// RNG interrupt handler.
 pub struct InterruptHandler<T: Instance> {
     _phantom: PhantomData<T>,
@@ -130,71 +144,115 @@ impl<'d, T: Instance> Rng<'d, T> {
     }
 
     /// Fill the given slice with random values.
+    ///
+    /// The RNG is restarted after a seed or clock error, and the failed word is retried. An error
+    /// is only returned when the errors persist after several restarts.
     pub async fn async_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
         for chunk in dest.chunks_mut(4) {
-            let mut bits = T::regs().sr().read();
-            if !bits.seis() && !bits.ceis() && !bits.drdy() {
-                // wait for interrupt
-                poll_fn(|cx| {
-                    // quick check to avoid registration if already done.
-                    let bits = T::regs().sr().read();
-                    if bits.drdy() || bits.seis() || bits.ceis() {
-                        return Poll::Ready(());
-                    }
-                    RNG_WAKER.register(cx.waker());
-                    T::regs().cr().modify(|reg| reg.set_ie(true));
-                    // Need to check condition **after** `register` to avoid a race
-                    // condition that would result in lost notifications.
-                    let bits = T::regs().sr().read();
-                    if bits.drdy() || bits.seis() || bits.ceis() {
-                        Poll::Ready(())
-                    } else {
-                        Poll::Pending
-                    }
-                })
-                .await;
-
-                // Re-read the status register after wait.
-                bits = T::regs().sr().read()
+            let mut restarts = 0;
+            let random_word = loop {
+                if let Some(word) = self.poll_word(&mut restarts)? {
+                    break word;
+                }
+                Self::wait_for_status().await;
+            };
+            // write bytes to chunk
+            for (dest, src) in chunk.iter_mut().zip(random_word.to_ne_bytes().iter()) {
+                *dest = *src
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Wait for a random word or an error.
+    async fn wait_for_status() {
+        poll_fn(|cx| {
+            // quick check to avoid registration if already done.
+            let bits = T::regs().sr().read();
+            if bits.drdy() || bits.seis() || bits.ceis() {
+                return Poll::Ready(());
+            }
+            RNG_WAKER.register(cx.waker());
+            T::regs().cr().modify(|reg| reg.set_ie(true));
+            // Need to check condition **after** `register` to avoid a race
+            // condition that would result in lost notifications.
+            let bits = T::regs().sr().read();
+            if bits.drdy() || bits.seis() || bits.ceis() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Read a random word if one is ready, restarting the RNG on errors.
+    ///
+    /// `restarts` counts the restarts for the current word, the error is returned once it reaches
+    /// [`MAX_RESTARTS`].
+    fn poll_word(&mut self, restarts: &mut u32) -> Result<Option<u32>, Error> {
+        let bits = T::regs().sr().read();
+        let err = if bits.seis() {
+            Error::SeedError
+        } else if bits.ceis() {
+            Error::ClockError
+        } else if bits.drdy() {
+            // DR can be read up to four times until the output buffer is empty
+            // DRDY is cleared automatically when that happens
+            let random_word = T::regs().dr().read();
+            // reference manual: always check if DR is zero, which means a seed error
+            if random_word != 0 {
+                return Ok(Some(random_word));
             }
-            if bits.seis() {
-                // in case of noise-source or seed error we try to recover here
-                // but we must not use the data in DR and we return an error
-                // to leave retry-logic to the application
-                self.recover_seed_error();
-                return Err(Error::SeedError);
-            } else if bits.ceis() {
-                // clock error detected, DR could still be used but keep it safe,
-                // clear the error and abort
+            Error::SeedError
+        } else {
+            return Ok(None);
+        };
+
+        if *restarts >= MAX_RESTARTS {
+            return Err(err);
+        }
+        *restarts += 1;
+        warn!("rng: {:?}, restarting", err);
+
+        match err {
+            // in case of noise-source or seed error the data in DR must not be used, the RNG is
+            // reset and the data is generated again
+            Error::SeedError => self.recover_seed_error(),
+            // the clock error doesn't affect the data, but keep it safe and generate it again
+            Error::ClockError => {
                 T::regs().sr().modify(|sr| sr.set_ceis(false));
-                return Err(Error::ClockError);
-            } else if bits.drdy() {
-                // DR can be read up to four times until the output buffer is empty
-                // DRDY is cleared automatically when that happens
-                let random_word = T::regs().dr().read();
-                // reference manual: always check if DR is zero
-                if random_word == 0 {
-                    return Err(Error::SeedError);
-                }
-                // write bytes to chunk
-                for (dest, src) in chunk.iter_mut().zip(random_word.to_ne_bytes().iter()) {
-                    *dest = *src
-                }
+                self.reset();
             }
         }
+        Ok(None)
+    }
 
+    /// Fill the given slice with random values, blocking.
+    fn blocking_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        for chunk in dest.chunks_mut(4) {
+            let mut restarts = 0;
+            let rand = loop {
+                if let Some(word) = self.poll_word(&mut restarts)? {
+                    break word;
+                }
+            };
+            for (slot, num) in chunk.iter_mut().zip(rand.to_ne_bytes().iter()) {
+                *slot = *num
+            }
+        }
         Ok(())
     }
 }
 
 impl<'d, T: Instance> RngCore for Rng<'d, T> {
     fn next_u32(&mut self) -> u32 {
+        // `next_u32` can't fail, so keep restarting the RNG until it works.
         loop {
-            let sr = T::regs().sr().read();
-            if sr.seis() | sr.ceis() {
-                self.reset();
-            } else if sr.drdy() {
-                return T::regs().dr().read();
+            let mut restarts = 0;
+            if let Ok(Some(word)) = self.poll_word(&mut restarts) {
+                return word;
             }
         }
     }
@@ -215,8 +273,7 @@ impl<'d, T: Instance> RngCore for Rng<'d, T> {
     }
 
     fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
-        self.fill_bytes(dest);
-        Ok(())
+        Ok(self.blocking_fill_bytes(dest)?)
     }
 }
 
@@ -0,0 +1,337 @@
+//! RTC alarms and periodic wakeup timer.
+//!
+//! Unavailable with the `low-power` feature, which uses the wakeup timer and its interrupt.
+use core::future::poll_fn;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::Poll;
+
+use embassy_sync::waitqueue::AtomicWaker;
+
+use super::datetime::day_of_week_to_u8;
+use super::{DateTimeError, DayOfWeek, Rtc, RtcError};
+use crate::interrupt;
+use crate::interrupt::typelevel::{Binding, Interrupt};
+use crate::pac::rtc::regs::Alrmr;
+use crate::pac::EXTI;
+use crate::peripherals::RTC;
+use crate::rtc::sealed::Instance;
+
+/// Interrupt of the RTC alarms.
+#[cfg(any(stm32f4, stm32g4))]
+pub type AlarmInterrupt = crate::interrupt::typelevel::RTC_ALARM;
+/// Interrupt of the RTC alarms.
+#[cfg(any(stm32l0, stm32l5))]
+pub type AlarmInterrupt = crate::interrupt::typelevel::RTC;
+/// Interrupt of the RTC alarms.
+#[cfg(stm32g0)]
+pub type AlarmInterrupt = crate::interrupt::typelevel::RTC_TAMP;
+
+/// Interrupt of the RTC wakeup timer.
+#[cfg(any(stm32f4, stm32g4))]
+pub type WakeupInterrupt = crate::interrupt::typelevel::RTC_WKUP;
+/// Interrupt of the RTC wakeup timer.
+#[cfg(any(stm32l0, stm32l5))]
+pub type WakeupInterrupt = crate::interrupt::typelevel::RTC;
+/// Interrupt of the RTC wakeup timer.
+#[cfg(stm32g0)]
+pub type WakeupInterrupt = crate::interrupt::typelevel::RTC_TAMP;
+
+#[cfg(any(stm32f4, stm32l0, stm32g4, stm32l5))]
+const EXTI_ALARM_LINE: usize = 17;
+#[cfg(stm32g0)]
+const EXTI_ALARM_LINE: usize = 19;
+
+#[cfg(stm32f4)]
+const EXTI_WAKEUP_LINE: usize = 22;
+#[cfg(any(stm32l0, stm32g4))]
+const EXTI_WAKEUP_LINE: usize = 20;
+#[cfg(any(stm32g0, stm32l5))]
+const EXTI_WAKEUP_LINE: usize = EXTI_ALARM_LINE;
+
+const NEW_AW: AtomicWaker = AtomicWaker::new();
+static ALARM_WAKERS: [AtomicWaker; 2] = [NEW_AW; 2];
+const NEW_FIRED: AtomicBool = AtomicBool::new(false);
+static ALARM_FIRED: [AtomicBool; 2] = [NEW_FIRED; 2];
+
+static WAKEUP_WAKER: AtomicWaker = AtomicWaker::new();
+static WAKEUP_FIRED: AtomicBool = AtomicBool::new(false);
+
+/// RTC interrupt handler, for the alarms and the wakeup timer.
+pub struct InterruptHandler {
+    _private: (),
+}
+
+impl<I: Interrupt> interrupt::typelevel::Handler<I> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        let r = RTC::regs();
+
+        for n in 0..2 {
+            #[cfg(any(stm32f4, stm32l0))]
+            let fired = r.isr().read().alrf(n);
+            #[cfg(not(any(stm32f4, stm32l0)))]
+            let fired = r.sr().read().alrf(n);
+
+            if fired {
+                clear_alarm_flag(n);
+                ALARM_FIRED[n].store(true, Ordering::Release);
+                ALARM_WAKERS[n].wake();
+            }
+        }
+        clear_exti_line(EXTI_ALARM_LINE);
+
+        #[cfg(any(stm32f4, stm32l0))]
+        let fired = r.isr().read().wutf();
+        #[cfg(not(any(stm32f4, stm32l0)))]
+        let fired = r.sr().read().wutf();
+
+        if fired {
+            clear_wakeup_flag();
+            WAKEUP_FIRED.store(true, Ordering::Release);
+            WAKEUP_WAKER.wake();
+        }
+        clear_exti_line(EXTI_WAKEUP_LINE);
+    }
+}
+
+/// RTC alarm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Alarm {
+    /// Alarm A.
+    A = 0,
+    /// Alarm B.
+    B = 1,
+}
+
+/// Day matched by an alarm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlarmDay {
+    /// Day of the month, `1..=31`.
+    Date(u8),
+    /// Day of the week.
+    Weekday(DayOfWeek),
+}
+
+/// Time matched by an alarm.
+///
+/// The fields left to `None` match any value: for example, an alarm with only `second` set fires
+/// every minute.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AlarmTime {
+    /// Day, or every day if `None`.
+    pub day: Option<AlarmDay>,
+    /// Hour, `0..=23`.
+    pub hour: Option<u8>,
+    /// Minute, `0..=59`.
+    pub minute: Option<u8>,
+    /// Second, `0..=59`.
+    pub second: Option<u8>,
+}
+
+impl AlarmTime {
+    /// Value of the alarm register.
+    fn to_alrmr(&self) -> Result<Alrmr, DateTimeError> {
+        fn field(value: Option<u8>, max: u8, err: DateTimeError) -> Result<u32, DateTimeError> {
+            match value {
+                // Masked: the field is not compared.
+                None => Ok(0x80),
+                Some(v) if v <= max => Ok(((v / 10) << 4 | v % 10) as u32),
+                Some(_) => Err(err),
+            }
+        }
+
+        let second = field(self.second, 59, DateTimeError::InvalidSecond)?;
+        let minute = field(self.minute, 59, DateTimeError::InvalidMinute)?;
+        let hour = field(self.hour, 23, DateTimeError::InvalidHour)?;
+        let day = match self.day {
+            None => 0x80,
+            Some(AlarmDay::Date(0)) => return Err(DateTimeError::InvalidDay),
+            Some(AlarmDay::Date(d)) => field(Some(d), 31, DateTimeError::InvalidDay)?,
+            // WDSEL: the units are the day of the week.
+            Some(AlarmDay::Weekday(d)) => 0x40 | day_of_week_to_u8(d) as u32,
+        };
+
+        Ok(Alrmr(day << 24 | hour << 16 | minute << 8 | second))
+    }
+}
+
+impl Rtc {
+    /// Set `alarm` to fire at `time`, and enable it.
+    ///
+    /// The alarm fires each time the calendar matches `time`, until it is disabled.
+    pub fn set_alarm(
+        &mut self,
+        alarm: Alarm,
+        time: AlarmTime,
+        _irq: impl Binding<AlarmInterrupt, InterruptHandler>,
+    ) -> Result<(), RtcError> {
+        let n = alarm as usize;
+        let alrmr = time.to_alrmr().map_err(RtcError::InvalidDateTime)?;
+
+        self.write(false, |regs| {
+            regs.cr().modify(|w| {
+                w.set_alre(n, false);
+                w.set_alrie(n, false);
+            });
+            #[cfg(any(stm32f4, stm32l0))]
+            while !regs.isr().read().alrwf(n) {}
+
+            regs.alrmr(n).write_value(alrmr);
+            clear_alarm_flag(n);
+            ALARM_FIRED[n].store(false, Ordering::Relaxed);
+
+            regs.cr().modify(|w| {
+                w.set_alre(n, true);
+                w.set_alrie(n, true);
+            });
+        });
+
+        enable_exti_line(EXTI_ALARM_LINE);
+        AlarmInterrupt::unpend();
+        unsafe { AlarmInterrupt::enable() };
+
+        Ok(())
+    }
+
+    /// Disable `alarm`.
+    pub fn disable_alarm(&mut self, alarm: Alarm) {
+        let n = alarm as usize;
+        self.write(false, |regs| {
+            regs.cr().modify(|w| {
+                w.set_alre(n, false);
+                w.set_alrie(n, false);
+            });
+        });
+        ALARM_FIRED[n].store(false, Ordering::Relaxed);
+    }
+
+    /// Wait for `alarm` to fire.
+    ///
+    /// This returns immediately if the alarm fired since the last call, the alarm must have been
+    /// set with [`set_alarm`](Self::set_alarm).
+    pub async fn wait_for_alarm(&self, alarm: Alarm) {
+        let n = alarm as usize;
+        poll_fn(|cx| {
+            ALARM_WAKERS[n].register(cx.waker());
+            if ALARM_FIRED[n].swap(false, Ordering::Acquire) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Start the periodic wakeup timer, with a period of `period_ms` milliseconds.
+    ///
+    /// The timer counts the RTC clock divided by 2 to 16, the longest period is then 32 seconds with
+    /// a 32.768 kHz clock.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the period is zero or too long for the RTC clock.
+    pub fn start_wakeup_timer(&mut self, period_ms: u32, _irq: impl Binding<WakeupInterrupt, InterruptHandler>) {
+        use super::WakeupPrescaler;
+
+        let rtc_ticks = period_ms as u64 * Self::frequency().0 as u64 / 1000;
+        let prescaler = [
+            WakeupPrescaler::Div2,
+            WakeupPrescaler::Div4,
+            WakeupPrescaler::Div8,
+            WakeupPrescaler::Div16,
+        ]
+        .into_iter()
+        .find(|psc| rtc_ticks / *psc as u64 <= 1 << 16)
+        .expect("wakeup period too long");
+        let ticks = rtc_ticks / prescaler as u64;
+        assert!(ticks > 0, "wakeup period too short");
+
+        self.write(false, |regs| {
+            regs.cr().modify(|w| {
+                w.set_wute(false);
+                w.set_wutie(false);
+            });
+            #[cfg(any(stm32f4, stm32l0))]
+            while !regs.isr().read().wutwf() {}
+            #[cfg(not(any(stm32f4, stm32l0)))]
+            while !regs.icsr().read().wutwf() {}
+
+            regs.cr().modify(|w| w.set_wucksel(prescaler.into()));
+            regs.wutr().write(|w| w.set_wut((ticks - 1) as u16));
+            clear_wakeup_flag();
+            WAKEUP_FIRED.store(false, Ordering::Relaxed);
+
+            regs.cr().modify(|w| {
+                w.set_wute(true);
+                w.set_wutie(true);
+            });
+        });
+
+        enable_exti_line(EXTI_WAKEUP_LINE);
+        WakeupInterrupt::unpend();
+        unsafe { WakeupInterrupt::enable() };
+    }
+
+    /// Stop the periodic wakeup timer.
+    pub fn stop_wakeup_timer(&mut self) {
+        self.write(false, |regs| {
+            regs.cr().modify(|w| {
+                w.set_wute(false);
+                w.set_wutie(false);
+            });
+        });
+        WAKEUP_FIRED.store(false, Ordering::Relaxed);
+    }
+
+    /// Wait for the next period of the wakeup timer.
+    ///
+    /// This returns immediately if a period ended since the last call.
+    pub async fn wait_for_wakeup(&self) {
+        poll_fn(|cx| {
+            WAKEUP_WAKER.register(cx.waker());
+            if WAKEUP_FIRED.swap(false, Ordering::Acquire) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+// The flags can be cleared without disabling the write protection.
+
+fn clear_alarm_flag(n: usize) {
+    #[cfg(any(stm32f4, stm32l0))]
+    RTC::regs().isr().modify(|w| w.set_alrf(n, false));
+    #[cfg(not(any(stm32f4, stm32l0)))]
+    RTC::regs()
+        .scr()
+        .write(|w| w.set_calrf(n, crate::pac::rtc::vals::Calrf::CLEAR));
+}
+
+fn clear_wakeup_flag() {
+    #[cfg(any(stm32f4, stm32l0))]
+    RTC::regs().isr().modify(|w| w.set_wutf(false));
+    #[cfg(not(any(stm32f4, stm32l0)))]
+    RTC::regs()
+        .scr()
+        .write(|w| w.set_cwutf(crate::pac::rtc::vals::Calrf::CLEAR));
+}
+
+fn enable_exti_line(line: usize) {
+    critical_section::with(|_| {
+        EXTI.rtsr(0).modify(|w| w.set_line(line, true));
+        EXTI.imr(0).modify(|w| w.set_line(line, true));
+    });
+}
+
+fn clear_exti_line(line: usize) {
+    #[cfg(not(any(stm32g0, stm32l5)))]
+    EXTI.pr(0).write(|w| w.set_line(line, true));
+    #[cfg(any(stm32g0, stm32l5))]
+    {
+        EXTI.rpr(0).write(|w| w.set_line(line, true));
+        EXTI.fpr(0).write(|w| w.set_line(line, true));
+    }
+}
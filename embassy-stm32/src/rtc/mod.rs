@@ -1,4 +1,6 @@
 //! Real Time Clock (RTC)
+#[cfg(all(not(feature = "low-power"), any(stm32f4, stm32l0, stm32g4, stm32g0, stm32l5)))]
+mod alarm;
 mod datetime;
 
 #[cfg(feature = "low-power")]
@@ -9,6 +11,8 @@ use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 #[cfg(feature = "low-power")]
 use embassy_sync::blocking_mutex::Mutex;
 
+#[cfg(all(not(feature = "low-power"), any(stm32f4, stm32l0, stm32g4, stm32g0, stm32l5)))]
+pub use self::alarm::{Alarm, AlarmDay, AlarmInterrupt, AlarmTime, InterruptHandler, WakeupInterrupt};
 #[cfg(not(rtc_v2f2))]
 use self::datetime::RtcInstant;
 use self::datetime::{day_of_week_from_u8, day_of_week_to_u8};
@@ -12,6 +12,7 @@ use crate::gpio::sealed::{AFType, Pin as _};
 use crate::gpio::AnyPin;
 use crate::pac::sai::{vals, Sai as Regs};
 use crate::rcc::RccPeripheral;
+use crate::time::Hertz;
 use crate::{peripherals, Peripheral};
 
 /// SAI error
@@ -26,6 +27,16 @@ pub enum Error {
     Overrun,
 }
 
+/// SAI configuration error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigError {
+    /// The TDM frame must have 1 to 16 slots.
+    InvalidSlotCount,
+    /// The TDM frame length must be a power of two, of at most 256 bit clocks.
+    InvalidFrameLength,
+}
+
 impl From<ringbuffer::OverrunError> for Error {
     fn from(_: ringbuffer::OverrunError) -> Self {
         Self::Overrun
@@ -99,6 +110,21 @@ pub enum DataSize {
 }
 
 impl DataSize {
+    /// Width of the slots holding this data size in the I2S and TDM frames.
+    const fn slot_bits(&self) -> u8 {
+        match self {
+            DataSize::Data8 | DataSize::Data10 | DataSize::Data16 => 16,
+            DataSize::Data20 | DataSize::Data24 | DataSize::Data32 => 32,
+        }
+    }
+
+    const fn slot_size(&self) -> SlotSize {
+        match self.slot_bits() {
+            16 => SlotSize::Channel16,
+            _ => SlotSize::Channel32,
+        }
+    }
+
     #[cfg(any(sai_v1, sai_v2, sai_v3, sai_v4))]
     const fn ds(&self) -> vals::Ds {
         match self {
@@ -404,28 +430,82 @@ pub enum MasterClockDivider {
 }
 
 impl MasterClockDivider {
-    #[cfg(any(sai_v1, sai_v2, sai_v3, sai_v4))]
-    const fn mckdiv(&self) -> u8 {
+    const ALL: [MasterClockDivider; 16] = [
+        MasterClockDivider::Div1,
+        MasterClockDivider::Div2,
+        MasterClockDivider::Div4,
+        MasterClockDivider::Div6,
+        MasterClockDivider::Div8,
+        MasterClockDivider::Div10,
+        MasterClockDivider::Div12,
+        MasterClockDivider::Div14,
+        MasterClockDivider::Div16,
+        MasterClockDivider::Div18,
+        MasterClockDivider::Div20,
+        MasterClockDivider::Div22,
+        MasterClockDivider::Div24,
+        MasterClockDivider::Div26,
+        MasterClockDivider::Div28,
+        MasterClockDivider::Div30,
+    ];
+
+    /// Divider giving the sample rate closest to `sample_rate` from a kernel clock of `kernel_clock`.
+    ///
+    /// The master clock is the kernel clock divided by the divider, and the sample rate is the
+    /// master clock divided by 256. Returns `None` if no divider is within 1% of `sample_rate`.
+    pub fn for_sample_rate(kernel_clock: Hertz, sample_rate: Hertz) -> Option<Self> {
+        let (divider, rate) = Self::ALL
+            .iter()
+            .map(|d| (*d, kernel_clock.0 / (d.divider() as u32 * 256)))
+            .min_by_key(|(_, rate)| rate.abs_diff(sample_rate.0))?;
+
+        if rate.abs_diff(sample_rate.0) > sample_rate.0 / 100 {
+            return None;
+        }
+        Some(divider)
+    }
+
+    const fn divider(&self) -> u8 {
         match self {
-            MasterClockDivider::MasterClockDisabled => 0,
-            MasterClockDivider::Div1 => 0,
-            MasterClockDivider::Div2 => 1,
-            MasterClockDivider::Div4 => 2,
-            MasterClockDivider::Div6 => 3,
-            MasterClockDivider::Div8 => 4,
-            MasterClockDivider::Div10 => 5,
-            MasterClockDivider::Div12 => 6,
-            MasterClockDivider::Div14 => 7,
-            MasterClockDivider::Div16 => 8,
-            MasterClockDivider::Div18 => 9,
-            MasterClockDivider::Div20 => 10,
-            MasterClockDivider::Div22 => 11,
-            MasterClockDivider::Div24 => 12,
-            MasterClockDivider::Div26 => 13,
-            MasterClockDivider::Div28 => 14,
-            MasterClockDivider::Div30 => 15,
+            MasterClockDivider::MasterClockDisabled => 1,
+            MasterClockDivider::Div1 => 1,
+            MasterClockDivider::Div2 => 2,
+            MasterClockDivider::Div4 => 4,
+            MasterClockDivider::Div6 => 6,
+            MasterClockDivider::Div8 => 8,
+            MasterClockDivider::Div10 => 10,
+            MasterClockDivider::Div12 => 12,
+            MasterClockDivider::Div14 => 14,
+            MasterClockDivider::Div16 => 16,
+            MasterClockDivider::Div18 => 18,
+            MasterClockDivider::Div20 => 20,
+            MasterClockDivider::Div22 => 22,
+            MasterClockDivider::Div24 => 24,
+            MasterClockDivider::Div26 => 26,
+            MasterClockDivider::Div28 => 28,
+            MasterClockDivider::Div30 => 30,
         }
     }
+
+    /// MCKDIV divides by twice its value, 0 divides by 1.
+    #[cfg(any(sai_v1, sai_v2))]
+    const fn mckdiv(&self) -> u8 {
+        self.divider() / 2
+    }
+
+    /// MCKDIV divides by its value, 0 divides by 1.
+    #[cfg(any(sai_v3, sai_v4))]
+    const fn mckdiv(&self) -> u8 {
+        self.divider()
+    }
+}
+
+/// Master clock divider giving the sample rate closest to `sample_rate`, from the current kernel
+/// clock of `T`, usually one of the RCC PLLs.
+///
+/// See [`MasterClockDivider::for_sample_rate`].
+pub fn master_clock_divider<T: Instance>(sample_rate: Hertz) -> Option<MasterClockDivider> {
+    MasterClockDivider::for_sample_rate(T::frequency(), sample_rate)
 }
 
 /// [`SAI`] configuration.
@@ -449,7 +529,7 @@ pub struct Config {
     pub frame_sync_polarity: FrameSyncPolarity,
     pub frame_sync_active_level_length: word::U7,
     pub frame_sync_definition: FrameSyncDefinition,
-    pub frame_length: u8,
+    pub frame_length: u16,
     pub clock_strobe: ClockStrobe,
     pub output_drive: OutputDrive,
     pub master_clock_divider: MasterClockDivider,
@@ -499,6 +579,65 @@ impl Config {
     pub fn new() -> Self {
         return Default::default();
     }
+
+    /// Create a config for standard (Philips) I2S frames.
+    ///
+    /// The frames have two slots, MSB first, with the left channel while FS is low and the data
+    /// starting one bit clock after the FS edge. The slots are 16 bit wide up to
+    /// [`DataSize::Data16`], and 32 bit wide otherwise.
+    pub fn i2s(data_size: DataSize) -> Self {
+        let slot_bits = data_size.slot_bits();
+        Self {
+            data_size,
+            slot_size: data_size.slot_size(),
+            slot_count: word::U4(2),
+            slot_enable: 0b11,
+            first_bit_offset: word::U5(0),
+            stereo_mono: StereoMono::Stereo,
+            bit_order: BitOrder::MsbFirst,
+            frame_sync_offset: FrameSyncOffset::BeforeFirstBit,
+            frame_sync_polarity: FrameSyncPolarity::ActiveLow,
+            frame_sync_active_level_length: word::U7(slot_bits),
+            frame_sync_definition: FrameSyncDefinition::ChannelIdentification,
+            frame_length: 2 * slot_bits as u16,
+            clock_strobe: ClockStrobe::Rising,
+            ..Default::default()
+        }
+    }
+
+    /// Create a config for TDM frames of `slot_count` slots, all enabled.
+    ///
+    /// The frames start with a one bit clock high pulse on FS, one bit clock before the first
+    /// bit of slot 0, and the data is MSB first. The slots are sized as in [`Config::i2s`]. The
+    /// frame length must be a power of two of at most 256 bit clocks, so `slot_count` is a power
+    /// of two, up to 16 slots of 16 bits or 8 slots of 32 bits.
+    pub fn tdm(slot_count: u8, data_size: DataSize) -> Result<Self, ConfigError> {
+        if !(1..=16).contains(&slot_count) {
+            return Err(ConfigError::InvalidSlotCount);
+        }
+        let slot_bits = data_size.slot_bits();
+        let frame_length = slot_count as u16 * slot_bits as u16;
+        if !frame_length.is_power_of_two() || frame_length > 256 {
+            return Err(ConfigError::InvalidFrameLength);
+        }
+
+        Ok(Self {
+            data_size,
+            slot_size: data_size.slot_size(),
+            slot_count: word::U4(slot_count),
+            slot_enable: ((1u32 << slot_count) - 1) as u16,
+            first_bit_offset: word::U5(0),
+            stereo_mono: StereoMono::Stereo,
+            bit_order: BitOrder::MsbFirst,
+            frame_sync_offset: FrameSyncOffset::BeforeFirstBit,
+            frame_sync_polarity: FrameSyncPolarity::ActiveHigh,
+            frame_sync_active_level_length: word::U7(1),
+            frame_sync_definition: FrameSyncDefinition::StartOfFrame,
+            frame_length,
+            clock_strobe: ClockStrobe::Rising,
+            ..Default::default()
+        })
+    }
 }
 
 enum RingBuffer<'d, C: Channel, W: word::Word> {
@@ -637,6 +776,9 @@ impl<'d, T: Instance, C: Channel, W: word::Word> Sai<'d, T, C, W> {
     /// Create a new SAI driver in asynchronous mode without MCLK.
     ///
     /// You can obtain the [`SubBlock`] with [`split_subblocks`].
+    ///
+    /// `dma_buf` is transferred by a circular DMA in two halves: while the DMA plays or captures
+    /// one half, [`write`](Self::write) and [`read`](Self::read) access the other one.
     pub fn new_asynchronous<S: SubBlockInstance>(
         peri: SubBlock<'d, T, S>,
         sck: impl Peripheral<P = impl SckPin<T, S>> + 'd,
@@ -790,7 +932,7 @@ impl<'d, T: Instance, C: Channel, W: word::Word> Sai<'d, T, C, W> {
                 w.set_fspol(config.frame_sync_polarity.fspol());
                 w.set_fsdef(config.frame_sync_definition.fsdef());
                 w.set_fsall(config.frame_sync_active_level_length.0 as u8 - 1);
-                w.set_frl(config.frame_length - 1);
+                w.set_frl((config.frame_length - 1) as u8);
             });
 
             ch.slotr().modify(|w| {
@@ -4,9 +4,9 @@
 use core::default::Default;
 use core::future::poll_fn;
 use core::marker::PhantomData;
-use core::ops::{Deref, DerefMut};
 use core::task::Poll;
 
+use embassy_embedded_hal::block::BlockDevice;
 use embassy_hal_internal::drop::OnDrop;
 use embassy_hal_internal::{into_ref, PeripheralRef};
 use embassy_sync::waitqueue::AtomicWaker;
@@ -52,6 +52,14 @@ impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandl
 /// Frequency used for SD Card initialization. Must be no higher than 400 kHz.
 const SD_INIT_FREQ: Hertz = Hertz(400_000);
 
+/// Maximum number of blocks of a single data transfer.
+///
+/// The DMA transfers at most 65535 words, while the data length register of the IDMA is 25 bits.
+#[cfg(sdmmc_v1)]
+const MAX_TRANSFER_BLOCKS: usize = 0xFFFF / 128;
+#[cfg(sdmmc_v2)]
+const MAX_TRANSFER_BLOCKS: usize = 0x01FF_FFFF / 512;
+
 /// The signalling scheme used on the SDMMC bus
 #[non_exhaustive]
 #[allow(missing_docs)]
@@ -73,25 +81,9 @@ impl Default for Signalling {
 
 /// Aligned data block for SDMMC transfers.
 ///
-/// This is a 512-byte array, aligned to 4 bytes to satisfy DMA requirements.
-#[repr(align(4))]
-#[derive(Debug, Clone, PartialEq, Eq)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
-pub struct DataBlock(pub [u8; 512]);
-
-impl Deref for DataBlock {
-    type Target = [u8; 512];
-
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
-
-impl DerefMut for DataBlock {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
-    }
-}
+/// This is the block type of the [`BlockDevice`] trait, so filesystem code works with both this
+/// driver and the SPI-mode SD card driver of `embassy-embedded-hal`.
+pub use embassy_embedded_hal::block::Block as DataBlock;
 
 /// Errors
 #[non_exhaustive]
@@ -106,8 +98,10 @@ pub enum Error {
     UnsupportedCardVersion,
     /// Unsupported card type.
     UnsupportedCardType,
-    /// CRC error.
-    Crc,
+    /// CRC error in a command response.
+    CmdCrc,
+    /// CRC error in a data block.
+    DataCrc,
     /// No card inserted.
     NoCard,
     /// Bad clock supplied to the SDMMC peripheral.
@@ -724,7 +718,7 @@ impl<'d, T: Instance, Dma: SdmmcDma<T> + 'd> Sdmmc<'d, T, Dma> {
             let status = regs.star().read();
 
             if status.dcrcfail() {
-                return Poll::Ready(Err(Error::Crc));
+                return Poll::Ready(Err(Error::DataCrc));
             }
             if status.dtimeout() {
                 return Poll::Ready(Err(Error::Timeout));
@@ -805,7 +799,7 @@ impl<'d, T: Instance, Dma: SdmmcDma<T> + 'd> Sdmmc<'d, T, Dma> {
             let status = regs.star().read();
 
             if status.dcrcfail() {
-                return Poll::Ready(Err(Error::Crc));
+                return Poll::Ready(Err(Error::DataCrc));
             }
             if status.dtimeout() {
                 return Poll::Ready(Err(Error::Timeout));
@@ -904,7 +898,7 @@ impl<'d, T: Instance, Dma: SdmmcDma<T> + 'd> Sdmmc<'d, T, Dma> {
             let status = regs.star().read();
 
             if status.dcrcfail() {
-                return Poll::Ready(Err(Error::Crc));
+                return Poll::Ready(Err(Error::DataCrc));
             }
             if status.dtimeout() {
                 return Poll::Ready(Err(Error::Timeout));
@@ -981,7 +975,7 @@ impl<'d, T: Instance, Dma: SdmmcDma<T> + 'd> Sdmmc<'d, T, Dma> {
         if status.ctimeout() {
             return Err(Error::Timeout);
         } else if status.ccrcfail() {
-            return Err(Error::Crc);
+            return Err(Error::CmdCrc);
         }
         Ok(())
     }
@@ -1071,7 +1065,8 @@ impl<'d, T: Instance, Dma: SdmmcDma<T> + 'd> Sdmmc<'d, T, Dma> {
             match Self::cmd(Cmd::app_op_cmd(arg), false) {
                 // ACMD41
                 Ok(_) => (),
-                Err(Error::Crc) => (),
+                // The R3 response has no CRC
+                Err(Error::CmdCrc) => (),
                 Err(err) => return Err(err),
             }
             let ocr: OCR = regs.respr(0).read().cardstatus().into();
@@ -1166,35 +1161,61 @@ impl<'d, T: Instance, Dma: SdmmcDma<T> + 'd> Sdmmc<'d, T, Dma> {
         Ok(())
     }
 
+    /// SDSC cards are byte addressed, the others are block addressed.
+    fn block_address(&self, block_idx: u32) -> Result<u32, Error> {
+        Ok(match self.card()?.card_type {
+            CardCapacity::SDSC => block_idx * 512,
+            _ => block_idx,
+        })
+    }
+
     /// Read a data block.
     #[inline]
     pub async fn read_block(&mut self, block_idx: u32, buffer: &mut DataBlock) -> Result<(), Error> {
-        let card_capacity = self.card()?.card_type;
+        self.read_blocks(block_idx, core::slice::from_mut(buffer)).await
+    }
 
-        // NOTE(unsafe) DataBlock uses align 4
-        let buffer = unsafe { &mut *((&mut buffer.0) as *mut [u8; 512] as *mut [u32; 128]) };
+    /// Read consecutive data blocks, starting at block `block_idx`.
+    ///
+    /// Several blocks are read with a single multiple block read command, which is much faster
+    /// than reading them one by one. A single data transfer holds at most 511 blocks with DMA
+    /// (SDMMC v1), and 65535 blocks with IDMA (SDMMC v2), so more blocks are read with several
+    /// commands.
+    pub async fn read_blocks(&mut self, block_idx: u32, blocks: &mut [DataBlock]) -> Result<(), Error> {
+        let mut block_idx = block_idx;
+        for chunk in blocks.chunks_mut(MAX_TRANSFER_BLOCKS) {
+            self.read_blocks_transfer(block_idx, chunk).await?;
+            block_idx += chunk.len() as u32;
+        }
+        Ok(())
+    }
+
+    async fn read_blocks_transfer(&mut self, block_idx: u32, blocks: &mut [DataBlock]) -> Result<(), Error> {
+        let address = self.block_address(block_idx)?;
+        let count = blocks.len();
+
+        // NOTE(unsafe) DataBlock uses align 4, and the blocks are contiguous
+        let buffer = unsafe { core::slice::from_raw_parts_mut(blocks.as_mut_ptr() as *mut u32, count * 128) };
 
-        // Always read 1 block of 512 bytes
-        // SDSC cards are byte addressed hence the blockaddress is in multiples of 512 bytes
-        let address = match card_capacity {
-            CardCapacity::SDSC => block_idx * 512,
-            _ => block_idx,
-        };
         Self::cmd(Cmd::set_block_length(512), false)?; // CMD16
 
         let regs = T::regs();
         let on_drop = OnDrop::new(|| Self::on_drop());
 
-        let transfer = self.prepare_datapath_read(buffer, 512, 9);
+        let transfer = self.prepare_datapath_read(buffer, 512 * count as u32, 9);
         InterruptHandler::<T>::data_interrupts(true);
-        Self::cmd(Cmd::read_single_block(address), true)?;
+        if count == 1 {
+            Self::cmd(Cmd::read_single_block(address), true)?; // CMD17
+        } else {
+            Self::cmd(Cmd::read_multiple_blocks(address), true)?; // CMD18
+        }
 
         let res = poll_fn(|cx| {
             T::state().register(cx.waker());
             let status = regs.star().read();
 
             if status.dcrcfail() {
-                return Poll::Ready(Err(Error::Crc));
+                return Poll::Ready(Err(Error::DataCrc));
             }
             if status.dtimeout() {
                 return Poll::Ready(Err(Error::Timeout));
@@ -1211,48 +1232,73 @@ impl<'d, T: Instance, Dma: SdmmcDma<T> + 'd> Sdmmc<'d, T, Dma> {
         .await;
         Self::clear_interrupt_flags();
 
-        if res.is_ok() {
-            on_drop.defuse();
-            Self::stop_datapath();
-            drop(transfer);
+        on_drop.defuse();
+        Self::stop_datapath();
+        drop(transfer);
+
+        if count > 1 {
+            // Sent after an error as well, so the card leaves the data state.
+            let stop = Self::cmd(Cmd::stop_transmission(), false); // CMD12
+            res?;
+            stop?;
         }
         res
     }
 
     /// Write a data block.
     pub async fn write_block(&mut self, block_idx: u32, buffer: &DataBlock) -> Result<(), Error> {
-        let card = self.card.as_mut().ok_or(Error::NoCard)?;
+        self.write_blocks(block_idx, core::slice::from_ref(buffer)).await
+    }
 
-        // NOTE(unsafe) DataBlock uses align 4
-        let buffer = unsafe { &*((&buffer.0) as *const [u8; 512] as *const [u32; 128]) };
+    /// Write consecutive data blocks, starting at block `block_idx`.
+    ///
+    /// Several blocks are written with a single multiple block write command, which is much faster
+    /// than writing them one by one. A single data transfer holds at most 511 blocks with DMA
+    /// (SDMMC v1), and 65535 blocks with IDMA (SDMMC v2), so more blocks are written with several
+    /// commands.
+    pub async fn write_blocks(&mut self, block_idx: u32, blocks: &[DataBlock]) -> Result<(), Error> {
+        let mut block_idx = block_idx;
+        for chunk in blocks.chunks(MAX_TRANSFER_BLOCKS) {
+            self.write_blocks_transfer(block_idx, chunk).await?;
+            block_idx += chunk.len() as u32;
+        }
+        Ok(())
+    }
+
+    async fn write_blocks_transfer(&mut self, block_idx: u32, blocks: &[DataBlock]) -> Result<(), Error> {
+        let address = self.block_address(block_idx)?;
+        let count = blocks.len();
+
+        // NOTE(unsafe) DataBlock uses align 4, and the blocks are contiguous
+        let buffer = unsafe { core::slice::from_raw_parts(blocks.as_ptr() as *const u32, count * 128) };
 
-        // Always read 1 block of 512 bytes
-        // SDSC cards are byte addressed hence the blockaddress is in multiples of 512 bytes
-        let address = match card.card_type {
-            CardCapacity::SDSC => block_idx * 512,
-            _ => block_idx,
-        };
         Self::cmd(Cmd::set_block_length(512), false)?; // CMD16
 
+        let cmd = if count == 1 {
+            Cmd::write_single_block(address) // CMD24
+        } else {
+            Cmd::write_multiple_blocks(address) // CMD25
+        };
+
         let regs = T::regs();
         let on_drop = OnDrop::new(|| Self::on_drop());
 
         // sdmmc_v1 uses different cmd/dma order than v2, but only for writes
         #[cfg(sdmmc_v1)]
-        Self::cmd(Cmd::write_single_block(address), true)?;
+        Self::cmd(cmd, true)?;
 
-        let transfer = self.prepare_datapath_write(buffer, 512, 9);
+        let transfer = self.prepare_datapath_write(buffer, 512 * count as u32, 9);
         InterruptHandler::<T>::data_interrupts(true);
 
         #[cfg(sdmmc_v2)]
-        Self::cmd(Cmd::write_single_block(address), true)?;
+        Self::cmd(cmd, true)?;
 
         let res = poll_fn(|cx| {
             T::state().register(cx.waker());
             let status = regs.star().read();
 
             if status.dcrcfail() {
-                return Poll::Ready(Err(Error::Crc));
+                return Poll::Ready(Err(Error::DataCrc));
             }
             if status.dtimeout() {
                 return Poll::Ready(Err(Error::Timeout));
@@ -1269,28 +1315,31 @@ impl<'d, T: Instance, Dma: SdmmcDma<T> + 'd> Sdmmc<'d, T, Dma> {
         .await;
         Self::clear_interrupt_flags();
 
-        match res {
-            Ok(_) => {
-                on_drop.defuse();
-                Self::stop_datapath();
-                drop(transfer);
+        on_drop.defuse();
+        Self::stop_datapath();
+        drop(transfer);
 
-                // TODO: Make this configurable
-                let mut timeout: u32 = 0x00FF_FFFF;
-
-                // Try to read card status (ACMD13)
-                while timeout > 0 {
-                    match self.read_sd_status().await {
-                        Ok(_) => return Ok(()),
-                        Err(Error::Timeout) => (), // Try again
-                        Err(e) => return Err(e),
-                    }
-                    timeout -= 1;
-                }
-                Err(Error::SoftwareTimeout)
+        if count > 1 {
+            // Sent after an error as well, so the card leaves the data state.
+            let stop = Self::cmd(Cmd::stop_transmission(), false); // CMD12
+            res?;
+            stop?;
+        }
+        res?;
+
+        // TODO: Make this configurable
+        let mut timeout: u32 = 0x00FF_FFFF;
+
+        // Try to read card status (ACMD13)
+        while timeout > 0 {
+            match self.read_sd_status().await {
+                Ok(_) => return Ok(()),
+                Err(Error::Timeout) => (), // Try again
+                Err(e) => return Err(e),
             }
-            Err(e) => Err(e),
+            timeout -= 1;
         }
+        Err(Error::SoftwareTimeout)
     }
 
     /// Get a reference to the initialized card
@@ -1310,6 +1359,22 @@ impl<'d, T: Instance, Dma: SdmmcDma<T> + 'd> Sdmmc<'d, T, Dma> {
     }
 }
 
+impl<'d, T: Instance, Dma: SdmmcDma<T> + 'd> BlockDevice for Sdmmc<'d, T, Dma> {
+    type Error = Error;
+
+    async fn num_blocks(&mut self) -> Result<u32, Error> {
+        Ok(self.card()?.csd.block_count() as u32)
+    }
+
+    async fn read(&mut self, block_idx: u32, blocks: &mut [DataBlock]) -> Result<(), Error> {
+        self.read_blocks(block_idx, blocks).await
+    }
+
+    async fn write(&mut self, block_idx: u32, blocks: &[DataBlock]) -> Result<(), Error> {
+        self.write_blocks(block_idx, blocks).await
+    }
+}
+
 impl<'d, T: Instance, Dma: SdmmcDma<T> + 'd> Drop for Sdmmc<'d, T, Dma> {
     fn drop(&mut self) {
         T::Interrupt::disable();
@@ -1374,10 +1439,10 @@ impl Cmd {
         Cmd::new(9, rca, Response::Long)
     }
 
-    /// CMD12:
-    //const fn stop_transmission() -> Cmd {
-    //    Cmd::new(12, 0, Response::Short)
-    //}
+    /// CMD12: Stop Transmission
+    const fn stop_transmission() -> Cmd {
+        Cmd::new(12, 0, Response::Short)
+    }
 
     /// CMD13: Ask card to send status register
     /// ACMD13: SD Status
@@ -1396,15 +1461,20 @@ impl Cmd {
     }
 
     /// CMD18: Multiple Block Read
-    //const fn read_multiple_blocks(addr: u32) -> Cmd {
-    //    Cmd::new(18, addr, Response::Short)
-    //}
+    const fn read_multiple_blocks(addr: u32) -> Cmd {
+        Cmd::new(18, addr, Response::Short)
+    }
 
     /// CMD24: Block Write
     const fn write_single_block(addr: u32) -> Cmd {
         Cmd::new(24, addr, Response::Short)
     }
 
+    /// CMD25: Multiple Block Write
+    const fn write_multiple_blocks(addr: u32) -> Cmd {
+        Cmd::new(25, addr, Response::Short)
+    }
+
     const fn app_op_cmd(arg: u32) -> Cmd {
         Cmd::new(41, arg, Response::Short)
     }
@@ -0,0 +1,289 @@
+//! SPI driver with the instance selected at runtime.
+
+use core::marker::PhantomData;
+
+use embassy_hal_internal::drop::OnDrop;
+use embassy_hal_internal::{into_ref, PeripheralRef};
+
+use super::{
+    configure, flush_rx_fifo, sealed, set_config, set_interrupts, set_word_size, transfer_word,
+    transfer_word_interrupt, word_impl, Config, Error, Instance, InterruptHandler, InterruptInstance, MisoPin, MosiPin,
+    Polarity, Regs, SckPin, Word,
+};
+use crate::gpio::sealed::{AFType, Pin as _};
+use crate::gpio::{AnyPin, Pull};
+use crate::interrupt::typelevel::Interrupt as _;
+use crate::mode::{Async, Blocking, Mode};
+use crate::time::Hertz;
+use crate::{interrupt, Peripheral};
+
+/// SPI instance, with its type erased.
+#[derive(Clone, Copy)]
+struct Info {
+    regs: Regs,
+    state: &'static sealed::State,
+    frequency: fn() -> Hertz,
+    disable: fn(),
+}
+
+impl Info {
+    fn new<T: Instance>() -> Self {
+        Self {
+            regs: T::REGS,
+            state: T::state(),
+            frequency: T::frequency,
+            disable: T::disable,
+        }
+    }
+}
+
+/// SPI driver, with the instance selected at runtime.
+///
+/// [`Spi`](super::Spi) is generic over its instance, so choosing the instance at runtime, for
+/// example from a board configuration table, instantiates the driver and the code using it for
+/// each instance. `AnySpi` erases the instance instead: only the constructors are generic, and
+/// the driver holds the registers, the clock and the waker of the instance.
+///
+/// The driver created by [`new`](AnySpi::new) has async transfers, driven by the interrupt of the
+/// instance. The one created by [`new_blocking`](AnySpi::new_blocking) is blocking only. The
+/// driver doesn't use DMA: use [`Spi`](super::Spi) for DMA transfers.
+pub struct AnySpi<'d, M: Mode = Blocking> {
+    info: Info,
+    sck: PeripheralRef<'d, AnyPin>,
+    mosi: PeripheralRef<'d, AnyPin>,
+    miso: PeripheralRef<'d, AnyPin>,
+    current_word_size: word_impl::Config,
+    phantom: PhantomData<(&'d mut (), M)>,
+}
+
+impl<'d> AnySpi<'d, Blocking> {
+    /// Create a new blocking SPI driver from any instance.
+    pub fn new_blocking<T: Instance>(
+        peri: impl Peripheral<P = T> + 'd,
+        sck: impl Peripheral<P = impl SckPin<T>> + 'd,
+        mosi: impl Peripheral<P = impl MosiPin<T>> + 'd,
+        miso: impl Peripheral<P = impl MisoPin<T>> + 'd,
+        config: Config,
+    ) -> Self {
+        Self::new_inner(peri, sck, mosi, miso, config)
+    }
+}
+
+impl<'d> AnySpi<'d, Async> {
+    /// Create a new SPI driver from any instance, with async transfers driven by its interrupt.
+    ///
+    /// Every word costs an interrupt, like with [`Spi::new_interrupt`](super::Spi::new_interrupt).
+    pub fn new<T: InterruptInstance>(
+        peri: impl Peripheral<P = T> + 'd,
+        sck: impl Peripheral<P = impl SckPin<T>> + 'd,
+        mosi: impl Peripheral<P = impl MosiPin<T>> + 'd,
+        miso: impl Peripheral<P = impl MisoPin<T>> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        config: Config,
+    ) -> Self {
+        let this = Self::new_inner(peri, sck, mosi, miso, config);
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        this
+    }
+
+    /// Enable the peripheral for an interrupt-driven transfer, disabling the interrupts when the
+    /// returned guard is dropped, also if the transfer is cancelled.
+    fn start_interrupt<W: Word>(&mut self) -> OnDrop<impl FnOnce()> {
+        self.start::<W>();
+        let regs = self.info.regs;
+        OnDrop::new(move || set_interrupts(regs, false, false))
+    }
+
+    /// SPI write, driven by the SPI interrupt.
+    pub async fn write<W: Word>(&mut self, data: &[W]) -> Result<(), Error> {
+        self.transfer(&mut [], data).await
+    }
+
+    /// SPI read, driven by the SPI interrupt.
+    pub async fn read<W: Word>(&mut self, data: &mut [W]) -> Result<(), Error> {
+        self.transfer(data, &[]).await
+    }
+
+    /// Bidirectional transfer, driven by the SPI interrupt.
+    ///
+    /// The transfer runs for `max(read.len(), write.len())` words, like
+    /// [`Spi::transfer`](super::Spi::transfer).
+    pub async fn transfer<W: Word>(&mut self, read: &mut [W], write: &[W]) -> Result<(), Error> {
+        let _interrupts = self.start_interrupt::<W>();
+        let len = read.len().max(write.len());
+        for i in 0..len {
+            let wb = write.get(i).copied().unwrap_or_default();
+            let rb = transfer_word_interrupt(self.info.regs, self.info.state, wb).await?;
+            if let Some(r) = read.get_mut(i) {
+                *r = rb;
+            }
+        }
+        Ok(())
+    }
+
+    /// In-place bidirectional transfer, driven by the SPI interrupt.
+    pub async fn transfer_in_place<W: Word>(&mut self, data: &mut [W]) -> Result<(), Error> {
+        let _interrupts = self.start_interrupt::<W>();
+        for word in data.iter_mut() {
+            *word = transfer_word_interrupt(self.info.regs, self.info.state, *word).await?;
+        }
+        Ok(())
+    }
+}
+
+impl<'d, M: Mode> AnySpi<'d, M> {
+    fn new_inner<T: Instance>(
+        _peri: impl Peripheral<P = T> + 'd,
+        sck: impl Peripheral<P = impl SckPin<T>> + 'd,
+        mosi: impl Peripheral<P = impl MosiPin<T>> + 'd,
+        miso: impl Peripheral<P = impl MisoPin<T>> + 'd,
+        config: Config,
+    ) -> Self {
+        into_ref!(sck, mosi, miso);
+
+        let sck_pull_mode = match config.mode.polarity {
+            Polarity::IdleLow => Pull::Down,
+            Polarity::IdleHigh => Pull::Up,
+        };
+
+        sck.set_as_af_pull(sck.af_num(), AFType::OutputPushPull, sck_pull_mode);
+        sck.set_speed(crate::gpio::Speed::VeryHigh);
+        mosi.set_as_af(mosi.af_num(), AFType::OutputPushPull);
+        mosi.set_speed(crate::gpio::Speed::VeryHigh);
+        miso.set_as_af(miso.af_num(), AFType::Input);
+        miso.set_speed(crate::gpio::Speed::VeryHigh);
+
+        T::enable_and_reset();
+        let info = Info::new::<T>();
+        configure(info.regs, (info.frequency)(), &config, false);
+
+        Self {
+            info,
+            sck: sck.map_into(),
+            mosi: mosi.map_into(),
+            miso: miso.map_into(),
+            current_word_size: <u8 as super::sealed::Word>::CONFIG,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Reconfigure the driver.
+    pub fn set_config(&mut self, config: &Config) {
+        set_config(self.info.regs, (self.info.frequency)(), config);
+    }
+
+    fn start<W: Word>(&mut self) {
+        let regs = self.info.regs;
+        regs.cr1().modify(|w| w.set_spe(true));
+        flush_rx_fifo(regs);
+        if self.current_word_size != W::CONFIG {
+            set_word_size(regs, W::CONFIG);
+            self.current_word_size = W::CONFIG;
+        }
+    }
+
+    /// Blocking write.
+    pub fn blocking_write<W: Word>(&mut self, words: &[W]) -> Result<(), Error> {
+        self.start::<W>();
+        for word in words.iter() {
+            let _ = transfer_word(self.info.regs, *word)?;
+        }
+        Ok(())
+    }
+
+    /// Blocking read.
+    pub fn blocking_read<W: Word>(&mut self, words: &mut [W]) -> Result<(), Error> {
+        self.start::<W>();
+        for word in words.iter_mut() {
+            *word = transfer_word(self.info.regs, W::default())?;
+        }
+        Ok(())
+    }
+
+    /// Blocking in-place bidirectional transfer.
+    pub fn blocking_transfer_in_place<W: Word>(&mut self, words: &mut [W]) -> Result<(), Error> {
+        self.start::<W>();
+        for word in words.iter_mut() {
+            *word = transfer_word(self.info.regs, *word)?;
+        }
+        Ok(())
+    }
+
+    /// Blocking bidirectional transfer.
+    ///
+    /// The transfer runs for `max(read.len(), write.len())` words, like
+    /// [`Spi::blocking_transfer`](super::Spi::blocking_transfer).
+    pub fn blocking_transfer<W: Word>(&mut self, read: &mut [W], write: &[W]) -> Result<(), Error> {
+        self.start::<W>();
+        let len = read.len().max(write.len());
+        for i in 0..len {
+            let wb = write.get(i).copied().unwrap_or_default();
+            let rb = transfer_word(self.info.regs, wb)?;
+            if let Some(r) = read.get_mut(i) {
+                *r = rb;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'d, M: Mode> Drop for AnySpi<'d, M> {
+    fn drop(&mut self) {
+        self.sck.set_as_disconnected();
+        self.mosi.set_as_disconnected();
+        self.miso.set_as_disconnected();
+
+        (self.info.disable)();
+    }
+}
+
+impl<M: Mode> embedded_hal_1::spi::ErrorType for AnySpi<'_, M> {
+    type Error = Error;
+}
+
+impl<M: Mode, W: Word> embedded_hal_1::spi::SpiBus<W> for AnySpi<'_, M> {
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn read(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        self.blocking_read(words)
+    }
+
+    fn write(&mut self, words: &[W]) -> Result<(), Self::Error> {
+        self.blocking_write(words)
+    }
+
+    fn transfer(&mut self, read: &mut [W], write: &[W]) -> Result<(), Self::Error> {
+        self.blocking_transfer(read, write)
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        self.blocking_transfer_in_place(words)
+    }
+}
+
+impl<W: Word> embedded_hal_async::spi::SpiBus<W> for AnySpi<'_, Async> {
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn write(&mut self, words: &[W]) -> Result<(), Self::Error> {
+        self.write(words).await
+    }
+
+    async fn read(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        self.read(words).await
+    }
+
+    async fn transfer(&mut self, read: &mut [W], write: &[W]) -> Result<(), Self::Error> {
+        self.transfer(read, write).await
+    }
+
+    async fn transfer_in_place(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        self.transfer_in_place(words).await
+    }
+}
@@ -1,20 +1,30 @@
 //! Serial Peripheral Interface (SPI)
 #![macro_use]
 
+use core::future::poll_fn;
+use core::marker::PhantomData;
 use core::ptr;
+use core::task::Poll;
 
 use embassy_embedded_hal::SetConfig;
 use embassy_futures::join::join;
+use embassy_hal_internal::drop::OnDrop;
 use embassy_hal_internal::{into_ref, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
 pub use embedded_hal_02::spi::{Mode, Phase, Polarity, MODE_0, MODE_1, MODE_2, MODE_3};
 
-use crate::dma::{slice_ptr_parts, word, Transfer};
+#[cfg(feature = "debug-dump")]
+use crate::debug_dump::Register;
+use crate::dma::buffer::{DmaReadBuffer, DmaWriteBuffer};
+use crate::dma::{slice_ptr_parts, wait_timeout, word, Transfer, TransferTimeout};
 use crate::gpio::sealed::{AFType, Pin as _};
 use crate::gpio::{AnyPin, Pull};
+use crate::interrupt::typelevel::Interrupt;
+use crate::mode::Async;
 use crate::pac::spi::{regs, vals, Spi as Regs};
-use crate::rcc::RccPeripheral;
+use crate::rcc::{AutoIdle, RccPeripheral};
 use crate::time::Hertz;
-use crate::{peripherals, Peripheral};
+use crate::{interrupt, peripherals, Peripheral};
 
 /// SPI error.
 #[derive(Debug, PartialEq, Eq)]
@@ -28,6 +38,8 @@ pub enum Error {
     ModeFault,
     /// Overrun.
     Overrun,
+    /// DMA transfer not finished before the timeout set by `set_timeout`.
+    Timeout,
 }
 
 /// SPI bit order
@@ -93,6 +105,8 @@ pub struct Spi<'d, T: Instance, Tx, Rx> {
     txdma: PeripheralRef<'d, Tx>,
     rxdma: PeripheralRef<'d, Rx>,
     current_word_size: word_impl::Config,
+    auto_idle: AutoIdle<T>,
+    timeout: TransferTimeout,
 }
 
 impl<'d, T: Instance, Tx, Rx> Spi<'d, T, Tx, Rx> {
@@ -240,94 +254,8 @@ impl<'d, T: Instance, Tx, Rx> Spi<'d, T, Tx, Rx> {
     ) -> Self {
         into_ref!(peri, txdma, rxdma);
 
-        let pclk = T::frequency();
-        let freq = config.frequency;
-        let br = compute_baud_rate(pclk, freq);
-
-        let cpha = config.raw_phase();
-        let cpol = config.raw_polarity();
-
-        let lsbfirst = config.raw_byte_order();
-
         T::enable_and_reset();
-
-        #[cfg(any(spi_v1, spi_f1))]
-        {
-            T::REGS.cr2().modify(|w| {
-                w.set_ssoe(false);
-            });
-            T::REGS.cr1().modify(|w| {
-                w.set_cpha(cpha);
-                w.set_cpol(cpol);
-
-                w.set_mstr(vals::Mstr::MASTER);
-                w.set_br(br);
-                w.set_spe(true);
-                w.set_lsbfirst(lsbfirst);
-                w.set_ssi(true);
-                w.set_ssm(true);
-                w.set_crcen(false);
-                w.set_bidimode(vals::Bidimode::UNIDIRECTIONAL);
-                if mosi.is_none() {
-                    w.set_rxonly(vals::Rxonly::OUTPUTDISABLED);
-                }
-                w.set_dff(<u8 as sealed::Word>::CONFIG)
-            });
-        }
-        #[cfg(spi_v2)]
-        {
-            T::REGS.cr2().modify(|w| {
-                let (ds, frxth) = <u8 as sealed::Word>::CONFIG;
-                w.set_frxth(frxth);
-                w.set_ds(ds);
-                w.set_ssoe(false);
-            });
-            T::REGS.cr1().modify(|w| {
-                w.set_cpha(cpha);
-                w.set_cpol(cpol);
-
-                w.set_mstr(vals::Mstr::MASTER);
-                w.set_br(br);
-                w.set_lsbfirst(lsbfirst);
-                w.set_ssi(true);
-                w.set_ssm(true);
-                w.set_crcen(false);
-                w.set_bidimode(vals::Bidimode::UNIDIRECTIONAL);
-                w.set_spe(true);
-            });
-        }
-        #[cfg(any(spi_v3, spi_v4, spi_v5))]
-        {
-            T::REGS.ifcr().write(|w| w.0 = 0xffff_ffff);
-            T::REGS.cfg2().modify(|w| {
-                //w.set_ssoe(true);
-                w.set_ssoe(false);
-                w.set_cpha(cpha);
-                w.set_cpol(cpol);
-                w.set_lsbfirst(lsbfirst);
-                w.set_ssm(true);
-                w.set_master(vals::Master::MASTER);
-                w.set_comm(vals::Comm::FULLDUPLEX);
-                w.set_ssom(vals::Ssom::ASSERTED);
-                w.set_midi(0);
-                w.set_mssi(0);
-                w.set_afcntr(true);
-                w.set_ssiop(vals::Ssiop::ACTIVEHIGH);
-            });
-            T::REGS.cfg1().modify(|w| {
-                w.set_crcen(false);
-                w.set_mbr(br);
-                w.set_dsize(<u8 as sealed::Word>::CONFIG);
-                w.set_fthlv(vals::Fthlv::ONEFRAME);
-            });
-            T::REGS.cr2().modify(|w| {
-                w.set_tsize(0);
-            });
-            T::REGS.cr1().modify(|w| {
-                w.set_ssi(false);
-                w.set_spe(true);
-            });
-        }
+        configure(T::REGS, T::frequency(), &config, mosi.is_none());
 
         Self {
             _peri: peri,
@@ -337,44 +265,39 @@ impl<'d, T: Instance, Tx, Rx> Spi<'d, T, Tx, Rx> {
             txdma,
             rxdma,
             current_word_size: <u8 as sealed::Word>::CONFIG,
+            auto_idle: AutoIdle::new(),
+            timeout: None,
         }
     }
 
-    /// Reconfigures it with the supplied config.
-    pub fn set_config(&mut self, config: &Config) -> Result<(), ()> {
-        let cpha = config.raw_phase();
-        let cpol = config.raw_polarity();
-
-        let lsbfirst = config.raw_byte_order();
-
-        let pclk = T::frequency();
-        let freq = config.frequency;
-        let br = compute_baud_rate(pclk, freq);
+    /// Enable or disable the auto idle mode.
+    ///
+    /// In the auto idle mode, the clock of the peripheral is gated whenever no transfer is in
+    /// flight, and enabled again by the next one, cutting the idle current. It is disabled by
+    /// default.
+    pub fn set_auto_idle(&mut self, enabled: bool) {
+        self.auto_idle.set(enabled);
+    }
 
-        #[cfg(any(spi_v1, spi_f1, spi_v2))]
-        T::REGS.cr1().modify(|w| {
-            w.set_cpha(cpha);
-            w.set_cpol(cpol);
-            w.set_br(br);
-            w.set_lsbfirst(lsbfirst);
-        });
+    /// Set the timeout of the DMA transfers, or `None` to wait forever, which is the default.
+    ///
+    /// A transfer that doesn't finish in time stops the DMA channels and returns
+    /// [`Error::Timeout`].
+    #[cfg(feature = "time")]
+    pub fn set_timeout(&mut self, timeout: Option<embassy_time::Duration>) {
+        self.timeout = timeout;
+    }
 
-        #[cfg(any(spi_v3, spi_v4, spi_v5))]
-        {
-            T::REGS.cfg2().modify(|w| {
-                w.set_cpha(cpha);
-                w.set_cpol(cpol);
-                w.set_lsbfirst(lsbfirst);
-            });
-            T::REGS.cfg1().modify(|w| {
-                w.set_mbr(br);
-            });
-        }
+    /// Reconfigures it with the supplied config.
+    pub fn set_config(&mut self, config: &Config) -> Result<(), ()> {
+        let _clock = self.auto_idle.wake();
+        set_config(T::REGS, T::frequency(), config);
         Ok(())
     }
 
     /// Get current SPI configuration.
     pub fn get_current_config(&self) -> Config {
+        let _clock = self.auto_idle.wake();
         #[cfg(any(spi_v1, spi_f1, spi_v2))]
         let cfg = T::REGS.cr1().read();
         #[cfg(any(spi_v3, spi_v4, spi_v5))]
@@ -419,59 +342,180 @@ impl<'d, T: Instance, Tx, Rx> Spi<'d, T, Tx, Rx> {
             return;
         }
 
-        #[cfg(any(spi_v1, spi_f1))]
-        {
-            T::REGS.cr1().modify(|reg| {
-                reg.set_spe(false);
-                reg.set_dff(word_size)
-            });
-            T::REGS.cr1().modify(|reg| {
-                reg.set_spe(true);
-            });
-        }
-        #[cfg(spi_v2)]
-        {
-            T::REGS.cr1().modify(|w| {
-                w.set_spe(false);
-            });
-            T::REGS.cr2().modify(|w| {
-                w.set_frxth(word_size.1);
-                w.set_ds(word_size.0);
-            });
-            T::REGS.cr1().modify(|w| {
-                w.set_spe(true);
-            });
-        }
-        #[cfg(any(spi_v3, spi_v4, spi_v5))]
-        {
-            T::REGS.cr1().modify(|w| {
-                w.set_csusp(true);
-            });
-            while T::REGS.sr().read().eot() {}
-            T::REGS.cr1().modify(|w| {
-                w.set_spe(false);
-            });
-            T::REGS.cfg1().modify(|w| {
-                w.set_dsize(word_size);
-            });
-            T::REGS.cr1().modify(|w| {
-                w.set_csusp(false);
-                w.set_spe(true);
-            });
+        set_word_size(T::REGS, word_size);
+        self.current_word_size = word_size;
+    }
+
+    /// SPI read in receive-only mode, using the RX DMA only.
+    ///
+    /// The peripheral generates the clock for exactly `data.len()` words by itself, so unlike
+    /// [`read`](Self::read) no TX DMA channel is needed and nothing is sent on MOSI. This suits
+    /// SPI ADCs and sensors which only send data. At most 65535 words are read at once.
+    #[cfg(any(spi_v3, spi_v4, spi_v5))]
+    pub async fn read_clocked<W: Word>(&mut self, data: &mut [W]) -> Result<(), Error>
+    where
+        Rx: RxDma<T>,
+    {
+        if data.is_empty() {
+            return Ok(());
         }
+        assert!(data.len() <= 0xFFFF);
 
-        self.current_word_size = word_size;
+        let _clock = self.auto_idle.wake();
+        self.set_word_size(W::CONFIG);
+        T::REGS.cr1().modify(|w| {
+            w.set_spe(false);
+        });
+
+        T::REGS.cfg2().modify(|w| w.set_comm(vals::Comm::RECEIVER));
+        T::REGS.cr2().modify(|w| w.set_tsize(data.len() as u16));
+        T::REGS.ifcr().write(|w| w.0 = 0xffff_ffff);
+
+        set_rxdmaen(T::REGS, true);
+
+        let rx_request = self.rxdma.request();
+        let rx_src = T::REGS.rx_ptr();
+        let mut rx_f = unsafe { Transfer::new_read(&mut self.rxdma, rx_request, rx_src, data, Default::default()) };
+
+        T::REGS.cr1().modify(|w| {
+            w.set_spe(true);
+        });
+        T::REGS.cr1().modify(|w| {
+            w.set_cstart(true);
+        });
+
+        let result = match wait_timeout(&mut rx_f, self.timeout).await {
+            Ok(()) => {
+                // The clock stops by itself after the last word.
+                while !T::REGS.sr().read().eot() {}
+                check_error_flags(T::REGS.sr().read())
+            }
+            Err(_) => Err(Error::Timeout),
+        };
+
+        T::REGS.cr1().modify(|w| {
+            w.set_spe(false);
+        });
+        set_rxdmaen(T::REGS, false);
+        T::REGS.ifcr().write(|w| w.0 = 0xffff_ffff);
+        T::REGS.cr2().modify(|w| w.set_tsize(0));
+        T::REGS.cfg2().modify(|w| w.set_comm(vals::Comm::FULLDUPLEX));
+
+        result
     }
 
-    /// SPI write, using DMA.
-    pub async fn write<W: Word>(&mut self, data: &[W]) -> Result<(), Error>
+    /// SPI write of a buffer owned by the transfer, using DMA, giving the buffer back.
+    ///
+    /// Unlike [`write`](Self::write), the buffer stays valid if the future is leaked, see
+    /// [`crate::dma::buffer`].
+    pub async fn write_buffer<B>(&mut self, data: B) -> (Result<(), Error>, B)
+    where
+        B: DmaWriteBuffer,
+        B::Word: Word,
+        Tx: TxDma<T>,
+    {
+        let (ptr, len) = data.dma_write_buffer();
+        // Safety: the buffer is owned by this future, so it outlives the transfer.
+        let result = self.write_dma(unsafe { core::slice::from_raw_parts(ptr, len) }).await;
+        (result, data)
+    }
+
+    /// SPI read into a buffer owned by the transfer, using DMA, giving the buffer back.
+    ///
+    /// Unlike [`read`](Self::read), the buffer stays valid if the future is leaked, see
+    /// [`crate::dma::buffer`].
+    pub async fn read_buffer<B>(&mut self, mut data: B) -> (Result<(), Error>, B)
     where
+        B: DmaReadBuffer,
+        B::Word: Word,
         Tx: TxDma<T>,
+        Rx: RxDma<T>,
     {
+        let (ptr, len) = data.dma_read_buffer();
+        // Safety: the buffer is owned by this future, so it outlives the transfer.
+        let result = self
+            .read_dma(unsafe { core::slice::from_raw_parts_mut(ptr, len) })
+            .await;
+        (result, data)
+    }
+
+    /// Blocking write.
+    pub fn blocking_write<W: Word>(&mut self, words: &[W]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
+        T::REGS.cr1().modify(|w| w.set_spe(true));
+        flush_rx_fifo(T::REGS);
+        self.set_word_size(W::CONFIG);
+        for word in words.iter() {
+            let _ = transfer_word(T::REGS, *word)?;
+        }
+        Ok(())
+    }
+
+    /// Blocking read.
+    pub fn blocking_read<W: Word>(&mut self, words: &mut [W]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
+        T::REGS.cr1().modify(|w| w.set_spe(true));
+        flush_rx_fifo(T::REGS);
+        self.set_word_size(W::CONFIG);
+        for word in words.iter_mut() {
+            *word = transfer_word(T::REGS, W::default())?;
+        }
+        Ok(())
+    }
+
+    /// Blocking in-place bidirectional transfer.
+    ///
+    /// This writes the contents of `data` on MOSI, and puts the received data on MISO in `data`, at the same time.
+    pub fn blocking_transfer_in_place<W: Word>(&mut self, words: &mut [W]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
+        T::REGS.cr1().modify(|w| w.set_spe(true));
+        flush_rx_fifo(T::REGS);
+        self.set_word_size(W::CONFIG);
+        for word in words.iter_mut() {
+            *word = transfer_word(T::REGS, *word)?;
+        }
+        Ok(())
+    }
+
+    /// Blocking bidirectional transfer.
+    ///
+    /// This transfers both buffers at the same time, so it is NOT equivalent to `write` followed by `read`.
+    ///
+    /// The transfer runs for `max(read.len(), write.len())` bytes. If `read` is shorter extra bytes are ignored.
+    /// If `write` is shorter it is padded with zero bytes.
+    pub fn blocking_transfer<W: Word>(&mut self, read: &mut [W], write: &[W]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
+        T::REGS.cr1().modify(|w| w.set_spe(true));
+        flush_rx_fifo(T::REGS);
+        self.set_word_size(W::CONFIG);
+        let len = read.len().max(write.len());
+        for i in 0..len {
+            let wb = write.get(i).copied().unwrap_or_default();
+            let rb = transfer_word(T::REGS, wb)?;
+            if let Some(r) = read.get_mut(i) {
+                *r = rb;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'d, T: Instance, Tx: TxDma<T>, Rx> Spi<'d, T, Tx, Rx> {
+    /// SPI write, using DMA.
+    ///
+    /// The DMA keeps reading `data` if this future is leaked instead of dropped, prefer
+    /// [`write_buffer`](Self::write_buffer).
+    #[deprecated(note = "the DMA keeps reading `data` if the future is leaked, use `write_buffer`")]
+    pub async fn write<W: Word>(&mut self, data: &[W]) -> Result<(), Error> {
+        self.write_dma(data).await
+    }
+
+    async fn write_dma<W: Word>(&mut self, data: &[W]) -> Result<(), Error> {
         if data.is_empty() {
             return Ok(());
         }
 
+        let _clock = self.auto_idle.wake();
         self.set_word_size(W::CONFIG);
         T::REGS.cr1().modify(|w| {
             w.set_spe(false);
@@ -479,7 +523,7 @@ impl<'d, T: Instance, Tx, Rx> Spi<'d, T, Tx, Rx> {
 
         let tx_request = self.txdma.request();
         let tx_dst = T::REGS.tx_ptr();
-        let tx_f = unsafe { Transfer::new_write(&mut self.txdma, tx_request, data, tx_dst, Default::default()) };
+        let mut tx_f = unsafe { Transfer::new_write(&mut self.txdma, tx_request, data, tx_dst, Default::default()) };
 
         set_txdmaen(T::REGS, true);
         T::REGS.cr1().modify(|w| {
@@ -490,23 +534,30 @@ impl<'d, T: Instance, Tx, Rx> Spi<'d, T, Tx, Rx> {
             w.set_cstart(true);
         });
 
-        tx_f.await;
+        let result = wait_timeout(&mut tx_f, self.timeout).await;
 
         finish_dma(T::REGS);
 
-        Ok(())
+        result.map_err(|_| Error::Timeout)
     }
+}
 
+impl<'d, T: Instance, Tx: TxDma<T>, Rx: RxDma<T>> Spi<'d, T, Tx, Rx> {
     /// SPI read, using DMA.
-    pub async fn read<W: Word>(&mut self, data: &mut [W]) -> Result<(), Error>
-    where
-        Tx: TxDma<T>,
-        Rx: RxDma<T>,
-    {
+    ///
+    /// The DMA keeps writing `data` if this future is leaked instead of dropped, prefer
+    /// [`read_buffer`](Self::read_buffer).
+    #[deprecated(note = "the DMA keeps writing `data` if the future is leaked, use `read_buffer`")]
+    pub async fn read<W: Word>(&mut self, data: &mut [W]) -> Result<(), Error> {
+        self.read_dma(data).await
+    }
+
+    async fn read_dma<W: Word>(&mut self, data: &mut [W]) -> Result<(), Error> {
         if data.is_empty() {
             return Ok(());
         }
 
+        let _clock = self.auto_idle.wake();
         self.set_word_size(W::CONFIG);
         T::REGS.cr1().modify(|w| {
             w.set_spe(false);
@@ -518,21 +569,22 @@ impl<'d, T: Instance, Tx, Rx> Spi<'d, T, Tx, Rx> {
 
         set_rxdmaen(T::REGS, true);
 
-        let clock_byte_count = data.len();
+        let clock_word_count = data.len();
 
         let rx_request = self.rxdma.request();
         let rx_src = T::REGS.rx_ptr();
-        let rx_f = unsafe { Transfer::new_read(&mut self.rxdma, rx_request, rx_src, data, Default::default()) };
+        let mut rx_f = unsafe { Transfer::new_read(&mut self.rxdma, rx_request, rx_src, data, Default::default()) };
 
         let tx_request = self.txdma.request();
         let tx_dst = T::REGS.tx_ptr();
-        let clock_byte = 0x00u8;
-        let tx_f = unsafe {
+        // Clock out words of the frame size, a byte write would only fill half a 16-bit frame.
+        let clock_word = W::default();
+        let mut tx_f = unsafe {
             Transfer::new_write_repeated(
                 &mut self.txdma,
                 tx_request,
-                &clock_byte,
-                clock_byte_count,
+                &clock_word,
+                clock_word_count,
                 tx_dst,
                 Default::default(),
             )
@@ -547,18 +599,18 @@ impl<'d, T: Instance, Tx, Rx> Spi<'d, T, Tx, Rx> {
             w.set_cstart(true);
         });
 
-        join(tx_f, rx_f).await;
+        let (tx_result, rx_result) = join(
+            wait_timeout(&mut tx_f, self.timeout),
+            wait_timeout(&mut rx_f, self.timeout),
+        )
+        .await;
 
         finish_dma(T::REGS);
 
-        Ok(())
+        tx_result.and(rx_result).map_err(|_| Error::Timeout)
     }
 
-    async fn transfer_inner<W: Word>(&mut self, read: *mut [W], write: *const [W]) -> Result<(), Error>
-    where
-        Tx: TxDma<T>,
-        Rx: RxDma<T>,
-    {
+    async fn transfer_inner<W: Word>(&mut self, read: *mut [W], write: *const [W]) -> Result<(), Error> {
         let (_, rx_len) = slice_ptr_parts(read);
         let (_, tx_len) = slice_ptr_parts(write);
         assert_eq!(rx_len, tx_len);
@@ -566,6 +618,7 @@ impl<'d, T: Instance, Tx, Rx> Spi<'d, T, Tx, Rx> {
             return Ok(());
         }
 
+        let _clock = self.auto_idle.wake();
         self.set_word_size(W::CONFIG);
         T::REGS.cr1().modify(|w| {
             w.set_spe(false);
@@ -579,11 +632,12 @@ impl<'d, T: Instance, Tx, Rx> Spi<'d, T, Tx, Rx> {
 
         let rx_request = self.rxdma.request();
         let rx_src = T::REGS.rx_ptr();
-        let rx_f = unsafe { Transfer::new_read_raw(&mut self.rxdma, rx_request, rx_src, read, Default::default()) };
+        let mut rx_f = unsafe { Transfer::new_read_raw(&mut self.rxdma, rx_request, rx_src, read, Default::default()) };
 
         let tx_request = self.txdma.request();
         let tx_dst = T::REGS.tx_ptr();
-        let tx_f = unsafe { Transfer::new_write_raw(&mut self.txdma, tx_request, write, tx_dst, Default::default()) };
+        let mut tx_f =
+            unsafe { Transfer::new_write_raw(&mut self.txdma, tx_request, write, tx_dst, Default::default()) };
 
         set_txdmaen(T::REGS, true);
         T::REGS.cr1().modify(|w| {
@@ -594,11 +648,15 @@ impl<'d, T: Instance, Tx, Rx> Spi<'d, T, Tx, Rx> {
             w.set_cstart(true);
         });
 
-        join(tx_f, rx_f).await;
+        let (tx_result, rx_result) = join(
+            wait_timeout(&mut tx_f, self.timeout),
+            wait_timeout(&mut rx_f, self.timeout),
+        )
+        .await;
 
         finish_dma(T::REGS);
 
-        Ok(())
+        tx_result.and(rx_result).map_err(|_| Error::Timeout)
     }
 
     /// Bidirectional transfer, using DMA.
@@ -607,80 +665,162 @@ impl<'d, T: Instance, Tx, Rx> Spi<'d, T, Tx, Rx> {
     ///
     /// The transfer runs for `max(read.len(), write.len())` bytes. If `read` is shorter extra bytes are ignored.
     /// If `write` is shorter it is padded with zero bytes.
-    pub async fn transfer<W: Word>(&mut self, read: &mut [W], write: &[W]) -> Result<(), Error>
-    where
-        Tx: TxDma<T>,
-        Rx: RxDma<T>,
-    {
-        self.transfer_inner(read, write).await
+    pub async fn transfer<W: Word>(&mut self, read: &mut [W], write: &[W]) -> Result<(), Error> {
+        let len = read.len().min(write.len());
+        let (read, read_rest) = read.split_at_mut(len);
+        let (write, write_rest) = write.split_at(len);
+
+        self.transfer_inner(read, write).await?;
+
+        // At most one of these is non-empty.
+        if !read_rest.is_empty() {
+            self.read_dma(read_rest).await?;
+        }
+        if !write_rest.is_empty() {
+            self.write_dma(write_rest).await?;
+        }
+
+        Ok(())
     }
 
     /// In-place bidirectional transfer, using DMA.
     ///
     /// This writes the contents of `data` on MOSI, and puts the received data on MISO in `data`, at the same time.
-    pub async fn transfer_in_place<W: Word>(&mut self, data: &mut [W]) -> Result<(), Error>
-    where
-        Tx: TxDma<T>,
-        Rx: RxDma<T>,
-    {
+    pub async fn transfer_in_place<W: Word>(&mut self, data: &mut [W]) -> Result<(), Error> {
         self.transfer_inner(data, data).await
     }
+}
 
-    /// Blocking write.
-    pub fn blocking_write<W: Word>(&mut self, words: &[W]) -> Result<(), Error> {
-        T::REGS.cr1().modify(|w| w.set_spe(true));
-        flush_rx_fifo(T::REGS);
-        self.set_word_size(W::CONFIG);
-        for word in words.iter() {
-            let _ = transfer_word(T::REGS, *word)?;
-        }
-        Ok(())
+impl<'d, T: InterruptInstance> Spi<'d, T, Async, Async> {
+    /// Create a new SPI driver without DMA, with an async API driven by the SPI interrupt.
+    ///
+    /// Every word costs an interrupt, so this suits short transfers, on chips or SPI instances
+    /// without a free DMA channel.
+    pub fn new_interrupt(
+        peri: impl Peripheral<P = T> + 'd,
+        sck: impl Peripheral<P = impl SckPin<T>> + 'd,
+        mosi: impl Peripheral<P = impl MosiPin<T>> + 'd,
+        miso: impl Peripheral<P = impl MisoPin<T>> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        config: Config,
+    ) -> Self {
+        let this = Self::new(peri, sck, mosi, miso, Async::new(), Async::new(), config);
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        this
     }
 
-    /// Blocking read.
-    pub fn blocking_read<W: Word>(&mut self, words: &mut [W]) -> Result<(), Error> {
+    /// Enable the peripheral for an interrupt-driven transfer, disabling the interrupts when the
+    /// returned guard is dropped, also if the transfer is cancelled.
+    fn start_interrupt<W: Word>(&mut self) -> OnDrop<impl FnOnce()> {
         T::REGS.cr1().modify(|w| w.set_spe(true));
         flush_rx_fifo(T::REGS);
         self.set_word_size(W::CONFIG);
-        for word in words.iter_mut() {
-            *word = transfer_word(T::REGS, W::default())?;
-        }
-        Ok(())
+
+        OnDrop::new(|| set_interrupts(T::REGS, false, false))
     }
 
-    /// Blocking in-place bidirectional transfer.
-    ///
-    /// This writes the contents of `data` on MOSI, and puts the received data on MISO in `data`, at the same time.
-    pub fn blocking_transfer_in_place<W: Word>(&mut self, words: &mut [W]) -> Result<(), Error> {
-        T::REGS.cr1().modify(|w| w.set_spe(true));
-        flush_rx_fifo(T::REGS);
-        self.set_word_size(W::CONFIG);
-        for word in words.iter_mut() {
-            *word = transfer_word(T::REGS, *word)?;
-        }
-        Ok(())
+    /// SPI write, driven by the SPI interrupt.
+    pub async fn write<W: Word>(&mut self, data: &[W]) -> Result<(), Error> {
+        self.transfer(&mut [], data).await
     }
 
-    /// Blocking bidirectional transfer.
+    /// SPI read, driven by the SPI interrupt.
+    pub async fn read<W: Word>(&mut self, data: &mut [W]) -> Result<(), Error> {
+        self.transfer(data, &[]).await
+    }
+
+    /// Bidirectional transfer, driven by the SPI interrupt.
     ///
     /// This transfers both buffers at the same time, so it is NOT equivalent to `write` followed by `read`.
     ///
     /// The transfer runs for `max(read.len(), write.len())` bytes. If `read` is shorter extra bytes are ignored.
     /// If `write` is shorter it is padded with zero bytes.
-    pub fn blocking_transfer<W: Word>(&mut self, read: &mut [W], write: &[W]) -> Result<(), Error> {
-        T::REGS.cr1().modify(|w| w.set_spe(true));
-        flush_rx_fifo(T::REGS);
-        self.set_word_size(W::CONFIG);
+    pub async fn transfer<W: Word>(&mut self, read: &mut [W], write: &[W]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
+        let _interrupts = self.start_interrupt::<W>();
         let len = read.len().max(write.len());
         for i in 0..len {
             let wb = write.get(i).copied().unwrap_or_default();
-            let rb = transfer_word(T::REGS, wb)?;
+            let rb = transfer_word_interrupt(T::REGS, T::state(), wb).await?;
             if let Some(r) = read.get_mut(i) {
                 *r = rb;
             }
         }
         Ok(())
     }
+
+    /// In-place bidirectional transfer, driven by the SPI interrupt.
+    ///
+    /// This writes the contents of `data` on MOSI, and puts the received data on MISO in `data`, at the same time.
+    pub async fn transfer_in_place<W: Word>(&mut self, data: &mut [W]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
+        let _interrupts = self.start_interrupt::<W>();
+        for word in data.iter_mut() {
+            *word = transfer_word_interrupt(T::REGS, T::state(), *word).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Wait until the TX data register can be written if `tx`, or until the RX data register can be
+/// read otherwise.
+async fn wait_ready(regs: Regs, state: &sealed::State, tx: bool) -> Result<(), Error> {
+    poll_fn(|cx| {
+        state.waker.register(cx.waker());
+
+        let sr = regs.sr().read();
+        check_error_flags(sr)?;
+        if (tx && tx_ready(sr)) || (!tx && rx_ready(sr)) {
+            return Poll::Ready(Ok(()));
+        }
+
+        // The interrupt handler disables the interrupts when they fire.
+        set_interrupts(regs, tx, !tx);
+        Poll::Pending
+    })
+    .await
+}
+
+/// Transfer a word, driven by the SPI interrupt.
+async fn transfer_word_interrupt<W: Word>(regs: Regs, state: &sealed::State, tx_word: W) -> Result<W, Error> {
+    wait_ready(regs, state, true).await?;
+
+    unsafe {
+        ptr::write_volatile(regs.tx_ptr(), tx_word);
+
+        #[cfg(any(spi_v3, spi_v4, spi_v5))]
+        regs.cr1().modify(|reg| reg.set_cstart(true));
+    }
+
+    wait_ready(regs, state, false).await?;
+
+    Ok(unsafe { ptr::read_volatile(regs.rx_ptr()) })
+}
+
+#[cfg(feature = "debug-dump")]
+impl<'d, T: Instance, Tx, Rx> Spi<'d, T, Tx, Rx> {
+    /// Snapshot the registers of the peripheral into `buf`, for bug reports.
+    pub fn debug_dump<'b>(&self, buf: &'b mut [Register]) -> &'b [Register] {
+        let _clock = self.auto_idle.wake();
+        let r = T::REGS;
+        crate::debug_dump::dump(
+            buf,
+            &[
+                ("CR1", r.cr1().read().0),
+                ("CR2", r.cr2().read().0),
+                #[cfg(any(spi_v3, spi_v4, spi_v5))]
+                ("CFG1", r.cfg1().read().0),
+                #[cfg(any(spi_v3, spi_v4, spi_v5))]
+                ("CFG2", r.cfg2().read().0),
+                ("SR", r.sr().read().0),
+                #[cfg(any(spi_v3, spi_v4, spi_v5))]
+                ("IER", r.ier().read().0),
+            ],
+        )
+    }
 }
 
 impl<'d, T: Instance, Tx, Rx> Drop for Spi<'d, T, Tx, Rx> {
@@ -689,6 +829,7 @@ impl<'d, T: Instance, Tx, Rx> Drop for Spi<'d, T, Tx, Rx> {
         self.mosi.as_ref().map(|x| x.set_as_disconnected());
         self.miso.as_ref().map(|x| x.set_as_disconnected());
 
+        self.auto_idle.set(false);
         T::disable();
     }
 }
@@ -698,6 +839,166 @@ use vals::Br;
 #[cfg(any(spi_v3, spi_v4, spi_v5))]
 use vals::Mbr as Br;
 
+fn configure(regs: Regs, pclk: Hertz, config: &Config, _rx_only: bool) {
+    let freq = config.frequency;
+    let br = compute_baud_rate(pclk, freq);
+
+    let cpha = config.raw_phase();
+    let cpol = config.raw_polarity();
+
+    let lsbfirst = config.raw_byte_order();
+
+    #[cfg(any(spi_v1, spi_f1))]
+    {
+        regs.cr2().modify(|w| {
+            w.set_ssoe(false);
+        });
+        regs.cr1().modify(|w| {
+            w.set_cpha(cpha);
+            w.set_cpol(cpol);
+
+            w.set_mstr(vals::Mstr::MASTER);
+            w.set_br(br);
+            w.set_spe(true);
+            w.set_lsbfirst(lsbfirst);
+            w.set_ssi(true);
+            w.set_ssm(true);
+            w.set_crcen(false);
+            w.set_bidimode(vals::Bidimode::UNIDIRECTIONAL);
+            if _rx_only {
+                w.set_rxonly(vals::Rxonly::OUTPUTDISABLED);
+            }
+            w.set_dff(<u8 as sealed::Word>::CONFIG)
+        });
+    }
+    #[cfg(spi_v2)]
+    {
+        regs.cr2().modify(|w| {
+            let (ds, frxth) = <u8 as sealed::Word>::CONFIG;
+            w.set_frxth(frxth);
+            w.set_ds(ds);
+            w.set_ssoe(false);
+        });
+        regs.cr1().modify(|w| {
+            w.set_cpha(cpha);
+            w.set_cpol(cpol);
+
+            w.set_mstr(vals::Mstr::MASTER);
+            w.set_br(br);
+            w.set_lsbfirst(lsbfirst);
+            w.set_ssi(true);
+            w.set_ssm(true);
+            w.set_crcen(false);
+            w.set_bidimode(vals::Bidimode::UNIDIRECTIONAL);
+            w.set_spe(true);
+        });
+    }
+    #[cfg(any(spi_v3, spi_v4, spi_v5))]
+    {
+        regs.ifcr().write(|w| w.0 = 0xffff_ffff);
+        regs.cfg2().modify(|w| {
+            //w.set_ssoe(true);
+            w.set_ssoe(false);
+            w.set_cpha(cpha);
+            w.set_cpol(cpol);
+            w.set_lsbfirst(lsbfirst);
+            w.set_ssm(true);
+            w.set_master(vals::Master::MASTER);
+            w.set_comm(vals::Comm::FULLDUPLEX);
+            w.set_ssom(vals::Ssom::ASSERTED);
+            w.set_midi(0);
+            w.set_mssi(0);
+            w.set_afcntr(true);
+            w.set_ssiop(vals::Ssiop::ACTIVEHIGH);
+        });
+        regs.cfg1().modify(|w| {
+            w.set_crcen(false);
+            w.set_mbr(br);
+            w.set_dsize(<u8 as sealed::Word>::CONFIG);
+            w.set_fthlv(vals::Fthlv::ONEFRAME);
+        });
+        regs.cr2().modify(|w| {
+            w.set_tsize(0);
+        });
+        regs.cr1().modify(|w| {
+            w.set_ssi(false);
+            w.set_spe(true);
+        });
+    }
+}
+
+fn set_config(regs: Regs, pclk: Hertz, config: &Config) {
+    let cpha = config.raw_phase();
+    let cpol = config.raw_polarity();
+
+    let lsbfirst = config.raw_byte_order();
+
+    let br = compute_baud_rate(pclk, config.frequency);
+
+    #[cfg(any(spi_v1, spi_f1, spi_v2))]
+    regs.cr1().modify(|w| {
+        w.set_cpha(cpha);
+        w.set_cpol(cpol);
+        w.set_br(br);
+        w.set_lsbfirst(lsbfirst);
+    });
+
+    #[cfg(any(spi_v3, spi_v4, spi_v5))]
+    {
+        regs.cfg2().modify(|w| {
+            w.set_cpha(cpha);
+            w.set_cpol(cpol);
+            w.set_lsbfirst(lsbfirst);
+        });
+        regs.cfg1().modify(|w| {
+            w.set_mbr(br);
+        });
+    }
+}
+
+fn set_word_size(regs: Regs, word_size: word_impl::Config) {
+    #[cfg(any(spi_v1, spi_f1))]
+    {
+        regs.cr1().modify(|reg| {
+            reg.set_spe(false);
+            reg.set_dff(word_size)
+        });
+        regs.cr1().modify(|reg| {
+            reg.set_spe(true);
+        });
+    }
+    #[cfg(spi_v2)]
+    {
+        regs.cr1().modify(|w| {
+            w.set_spe(false);
+        });
+        regs.cr2().modify(|w| {
+            w.set_frxth(word_size.1);
+            w.set_ds(word_size.0);
+        });
+        regs.cr1().modify(|w| {
+            w.set_spe(true);
+        });
+    }
+    #[cfg(any(spi_v3, spi_v4, spi_v5))]
+    {
+        regs.cr1().modify(|w| {
+            w.set_csusp(true);
+        });
+        while regs.sr().read().eot() {}
+        regs.cr1().modify(|w| {
+            w.set_spe(false);
+        });
+        regs.cfg1().modify(|w| {
+            w.set_dsize(word_size);
+        });
+        regs.cr1().modify(|w| {
+            w.set_csusp(false);
+            w.set_spe(true);
+        });
+    }
+}
+
 fn compute_baud_rate(clocks: Hertz, freq: Hertz) -> Br {
     let val = match clocks.0 / freq.0 {
         0 => unreachable!(),
@@ -779,18 +1080,33 @@ fn check_error_flags(sr: regs::Sr) -> Result<(), Error> {
     Ok(())
 }
 
+#[cfg(not(any(spi_v3, spi_v4, spi_v5)))]
+fn tx_ready(sr: regs::Sr) -> bool {
+    sr.txe()
+}
+
+#[cfg(any(spi_v3, spi_v4, spi_v5))]
+fn tx_ready(sr: regs::Sr) -> bool {
+    sr.txp()
+}
+
+#[cfg(not(any(spi_v3, spi_v4, spi_v5)))]
+fn rx_ready(sr: regs::Sr) -> bool {
+    sr.rxne()
+}
+
+#[cfg(any(spi_v3, spi_v4, spi_v5))]
+fn rx_ready(sr: regs::Sr) -> bool {
+    sr.rxp()
+}
+
 fn spin_until_tx_ready(regs: Regs) -> Result<(), Error> {
     loop {
         let sr = regs.sr().read();
 
         check_error_flags(sr)?;
 
-        #[cfg(not(any(spi_v3, spi_v4, spi_v5)))]
-        if sr.txe() {
-            return Ok(());
-        }
-        #[cfg(any(spi_v3, spi_v4, spi_v5))]
-        if sr.txp() {
+        if tx_ready(sr) {
             return Ok(());
         }
     }
@@ -802,17 +1118,31 @@ fn spin_until_rx_ready(regs: Regs) -> Result<(), Error> {
 
         check_error_flags(sr)?;
 
-        #[cfg(not(any(spi_v3, spi_v4, spi_v5)))]
-        if sr.rxne() {
-            return Ok(());
-        }
-        #[cfg(any(spi_v3, spi_v4, spi_v5))]
-        if sr.rxp() {
+        if rx_ready(sr) {
             return Ok(());
         }
     }
 }
 
+/// Enable the TX or RX data interrupts, with the error interrupts, or disable them all.
+fn set_interrupts(regs: Regs, tx: bool, rx: bool) {
+    #[cfg(not(any(spi_v3, spi_v4, spi_v5)))]
+    regs.cr2().modify(|w| {
+        w.set_txeie(tx);
+        w.set_rxneie(rx);
+        w.set_errie(tx || rx);
+    });
+    #[cfg(any(spi_v3, spi_v4, spi_v5))]
+    regs.ier().modify(|w| {
+        w.set_txpie(tx);
+        w.set_rxpie(rx);
+        w.set_ovrie(tx || rx);
+        w.set_modfie(tx || rx);
+        w.set_tifreie(tx || rx);
+        w.set_crceie(tx || rx);
+    });
+}
+
 fn flush_rx_fifo(regs: Regs) {
     #[cfg(not(any(spi_v3, spi_v4, spi_v5)))]
     while regs.sr().read().rxne() {
@@ -949,6 +1279,7 @@ impl embedded_hal_1::spi::Error for Error {
             Self::Crc => embedded_hal_1::spi::ErrorKind::Other,
             Self::ModeFault => embedded_hal_1::spi::ErrorKind::ModeFault,
             Self::Overrun => embedded_hal_1::spi::ErrorKind::Overrun,
+            Self::Timeout => embedded_hal_1::spi::ErrorKind::Other,
         }
     }
 }
@@ -958,6 +1289,28 @@ impl<'d, T: Instance, Tx: TxDma<T>, Rx: RxDma<T>, W: Word> embedded_hal_async::s
         Ok(())
     }
 
+    async fn write(&mut self, words: &[W]) -> Result<(), Self::Error> {
+        self.write_dma(words).await
+    }
+
+    async fn read(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        self.read_dma(words).await
+    }
+
+    async fn transfer(&mut self, read: &mut [W], write: &[W]) -> Result<(), Self::Error> {
+        self.transfer(read, write).await
+    }
+
+    async fn transfer_in_place(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        self.transfer_in_place(words).await
+    }
+}
+
+impl<'d, T: InterruptInstance, W: Word> embedded_hal_async::spi::SpiBus<W> for Spi<'d, T, Async, Async> {
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
     async fn write(&mut self, words: &[W]) -> Result<(), Self::Error> {
         self.write(words).await
     }
@@ -975,11 +1328,42 @@ impl<'d, T: Instance, Tx: TxDma<T>, Rx: RxDma<T>, W: Word> embedded_hal_async::s
     }
 }
 
+/// Interrupt handler, for the async API of the drivers without DMA.
+pub struct InterruptHandler<T: InterruptInstance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: InterruptInstance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        // The flags are cleared by the task accessing the data registers: disable the interrupts,
+        // the task enables them again when it waits.
+        set_interrupts(T::REGS, false, false);
+        T::state().waker.wake();
+    }
+}
+
+mod any;
+pub use any::AnySpi;
+
 pub(crate) mod sealed {
     use super::*;
 
     pub trait Instance {
         const REGS: Regs;
+
+        fn state() -> &'static State;
+    }
+
+    pub struct State {
+        pub waker: AtomicWaker,
+    }
+
+    impl State {
+        pub const fn new() -> Self {
+            Self {
+                waker: AtomicWaker::new(),
+            }
+        }
     }
 
     pub trait Word {
@@ -1070,6 +1454,12 @@ mod word_impl {
 /// SPI instance trait.
 pub trait Instance: Peripheral<P = Self> + sealed::Instance + RccPeripheral {}
 
+/// SPI instance with an interrupt, for the async API of the drivers without DMA.
+pub trait InterruptInstance: Instance {
+    /// Interrupt for this instance.
+    type Interrupt: interrupt::typelevel::Interrupt;
+}
+
 pin_trait!(SckPin, Instance);
 pin_trait!(MosiPin, Instance);
 pin_trait!(MisoPin, Instance);
@@ -1084,12 +1474,25 @@ foreach_peripheral!(
     (spi, $inst:ident) => {
         impl sealed::Instance for peripherals::$inst {
             const REGS: Regs = crate::pac::$inst;
+
+            fn state() -> &'static sealed::State {
+                static STATE: sealed::State = sealed::State::new();
+                &STATE
+            }
         }
 
         impl Instance for peripherals::$inst {}
     };
 );
 
+foreach_interrupt!(
+    ($inst:ident, spi, $block:ident, GLOBAL, $irq:ident) => {
+        impl InterruptInstance for peripherals::$inst {
+            type Interrupt = crate::interrupt::typelevel::$irq;
+        }
+    };
+);
+
 impl<'d, T: Instance, Tx, Rx> SetConfig for Spi<'d, T, Tx, Rx> {
     type Config = Config;
     type ConfigError = ();
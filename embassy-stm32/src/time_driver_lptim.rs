@@ -0,0 +1,264 @@
+//! Time driver on LPTIM1, clocked by the LSE.
+//!
+//! Unlike the timers, LPTIM1 keeps counting in Stop mode, and its interrupt wakes the core up, so
+//! the time doesn't have to be paused and resumed with the RTC around the Stop periods.
+//!
+//! The counter is 16 bits, so the driver counts its overflows in `period`. LPTIM1 has a single
+//! compare register, which holds the only alarm: it matches once per overflow cycle, and the
+//! interrupt only fires the alarm when its timestamp has been reached.
+
+use core::cell::Cell;
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering};
+use core::{mem, ptr};
+
+use critical_section::CriticalSection;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time_driver::{AlarmHandle, Driver, TICK_HZ};
+
+use crate::interrupt::typelevel::Interrupt;
+use crate::pac::lptim::vals;
+use crate::pac::{LPTIM1, RCC};
+use crate::rcc::sealed::RccPeripheral;
+#[cfg(feature = "low-power")]
+use crate::rtc::Rtc;
+use crate::{interrupt, peripherals};
+
+/// Frequency of the LSE clocking the counter.
+const LSE_HZ: u64 = 32768;
+
+/// Minimum distance of an alarm, covering the synchronization of the compare register writes.
+const MIN_ALARM_TICKS: u64 = 3;
+
+#[cfg(feature = "rt")]
+#[interrupt]
+fn LPTIM1() {
+    DRIVER.on_interrupt()
+}
+
+struct AlarmState {
+    timestamp: Cell<u64>,
+
+    // This is really a Option<(fn(*mut ()), *mut ())>
+    // but fn pointers aren't allowed in const yet
+    callback: Cell<*const ()>,
+    ctx: Cell<*mut ()>,
+}
+
+unsafe impl Send for AlarmState {}
+
+impl AlarmState {
+    const fn new() -> Self {
+        Self {
+            timestamp: Cell::new(u64::MAX),
+            callback: Cell::new(ptr::null()),
+            ctx: Cell::new(ptr::null_mut()),
+        }
+    }
+}
+
+pub(crate) struct RtcDriver {
+    /// Number of 2^16 overflow cycles elapsed since boot.
+    period: AtomicU32,
+    alarm_allocated: AtomicU8,
+    /// Whether a compare register write may still be in progress.
+    cmp_written: AtomicBool,
+    /// Timestamp at which to fire the alarm. u64::MAX if no alarm is scheduled.
+    alarm: Mutex<CriticalSectionRawMutex, AlarmState>,
+}
+
+embassy_time_driver::time_driver_impl!(static DRIVER: RtcDriver = RtcDriver {
+    period: AtomicU32::new(0),
+    alarm_allocated: AtomicU8::new(0),
+    cmp_written: AtomicBool::new(false),
+    alarm: Mutex::const_new(CriticalSectionRawMutex::new(), AlarmState::new()),
+});
+
+impl RtcDriver {
+    fn init(&'static self, cs: critical_section::CriticalSection) {
+        assert!(
+            RCC.bdcr().read().lserdy(),
+            "the LSE must be enabled for the LPTIM1 time driver"
+        );
+
+        assert_eq!(LSE_HZ % TICK_HZ, 0, "TICK_HZ must divide 32768");
+        let presc = match LSE_HZ / TICK_HZ {
+            1 => vals::Presc::DIV1,
+            2 => vals::Presc::DIV2,
+            4 => vals::Presc::DIV4,
+            8 => vals::Presc::DIV8,
+            16 => vals::Presc::DIV16,
+            32 => vals::Presc::DIV32,
+            64 => vals::Presc::DIV64,
+            128 => vals::Presc::DIV128,
+            _ => panic!("TICK_HZ must be 32768 divided by a power of two up to 128"),
+        };
+
+        #[cfg(stm32l4)]
+        RCC.ccipr()
+            .modify(|w| w.set_lptim1sel(crate::pac::rcc::vals::Lptim1sel::LSE));
+        #[cfg(stm32l5)]
+        RCC.ccipr1()
+            .modify(|w| w.set_lptim1sel(crate::pac::rcc::vals::Lptim1sel::LSE));
+
+        <peripherals::LPTIM1 as RccPeripheral>::enable_and_reset_with_cs(cs);
+
+        let r = LPTIM1;
+
+        // The configuration and interrupt enable registers can only be written while disabled.
+        r.cfgr().write(|w| w.set_presc(presc));
+        r.ier().write(|w| {
+            w.set_arrmie(true);
+            w.set_cmpmie(true);
+        });
+        r.cr().write(|w| w.set_enable(true));
+
+        r.arr().write(|w| w.set_arr(u16::MAX));
+        while !r.isr().read().arrok() {}
+        r.icr().write(|w| w.set_arrokcf(true));
+
+        interrupt::typelevel::LPTIM1::unpend();
+        unsafe { interrupt::typelevel::LPTIM1::enable() };
+
+        r.cr().modify(|w| w.set_cntstrt(true));
+    }
+
+    fn on_interrupt(&self) {
+        let r = LPTIM1;
+
+        critical_section::with(|cs| {
+            let isr = r.isr().read();
+
+            // Overflow. `now` accounts for a pending overflow, so the period is incremented with
+            // the flag cleared.
+            if isr.arrm() {
+                self.period
+                    .store(self.period.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+                r.icr().write(|w| w.set_arrmcf(true));
+            }
+
+            if isr.cmpm() {
+                r.icr().write(|w| w.set_cmpmcf(true));
+            }
+
+            // The compare register matches once per overflow cycle, so fire the alarm only once its
+            // timestamp has been reached. The overflow also catches an alarm missed by a late write.
+            let alarm = self.alarm.borrow(cs);
+            if alarm.timestamp.get() <= self.now() {
+                self.trigger_alarm(cs);
+            }
+        })
+    }
+
+    /// Read the counter, twice, as it runs asynchronously to the bus.
+    fn counter(&self) -> u16 {
+        let r = LPTIM1;
+        loop {
+            let cnt = r.cnt().read().cnt();
+            if r.cnt().read().cnt() == cnt {
+                return cnt;
+            }
+        }
+    }
+
+    fn trigger_alarm(&self, cs: CriticalSection) {
+        let alarm = self.alarm.borrow(cs);
+        alarm.timestamp.set(u64::MAX);
+
+        // Call after clearing alarm, so the callback can set another alarm.
+
+        // safety:
+        // - we can ignore the possibility of `f` being unset (null) because of the safety contract of `allocate_alarm`.
+        // - other than that we only store valid function pointers into alarm.callback
+        let f: fn(*mut ()) = unsafe { mem::transmute(alarm.callback.get()) };
+        f(alarm.ctx.get());
+    }
+
+    #[cfg(feature = "low-power")]
+    /// The RTC isn't needed, the time keeps running in Stop mode.
+    pub(crate) fn set_rtc(&self, _rtc: &'static Rtc) {}
+
+    #[cfg(feature = "low-power")]
+    /// The time keeps running in Stop mode, so it never needs to be paused.
+    pub(crate) fn pause_time(&self) -> Result<(), ()> {
+        Ok(())
+    }
+
+    #[cfg(feature = "low-power")]
+    /// The time keeps running in Stop mode, so it never needs to be resumed.
+    pub(crate) fn resume_time(&self) {}
+}
+
+impl Driver for RtcDriver {
+    fn now(&self) -> u64 {
+        critical_section::with(|_| {
+            let period = self.period.load(Ordering::Relaxed);
+            let counter = self.counter();
+
+            // The counter may have overflowed before the interrupt incremented the period. An
+            // overflow flagged after the counter was read leaves it at the top of the cycle.
+            let period = if LPTIM1.isr().read().arrm() && counter < 0x8000 {
+                period + 1
+            } else {
+                period
+            };
+            ((period as u64) << 16) + counter as u64
+        })
+    }
+
+    unsafe fn allocate_alarm(&self) -> Option<AlarmHandle> {
+        critical_section::with(|_| {
+            if self.alarm_allocated.load(Ordering::Relaxed) == 0 {
+                self.alarm_allocated.store(1, Ordering::Relaxed);
+                Some(AlarmHandle::new(0))
+            } else {
+                None
+            }
+        })
+    }
+
+    fn set_alarm_callback(&self, _alarm: AlarmHandle, callback: fn(*mut ()), ctx: *mut ()) {
+        critical_section::with(|cs| {
+            let alarm = self.alarm.borrow(cs);
+
+            alarm.callback.set(callback as *const ());
+            alarm.ctx.set(ctx);
+        })
+    }
+
+    fn set_alarm(&self, _alarm: AlarmHandle, timestamp: u64) -> bool {
+        critical_section::with(|cs| {
+            let r = LPTIM1;
+            let alarm = self.alarm.borrow(cs);
+
+            // The compare register only takes its new value a few ticks after the write, an alarm
+            // closer than that is reported as passed, and retried by the caller.
+            if timestamp <= self.now() + MIN_ALARM_TICKS {
+                alarm.timestamp.set(u64::MAX);
+                return false;
+            }
+
+            // The compare register must not be written before the end of the previous write.
+            if self.cmp_written.load(Ordering::Relaxed) {
+                while !r.isr().read().cmpok() {}
+            }
+            r.icr().write(|w| w.set_cmpokcf(true));
+            r.cmp().write(|w| w.set_cmp(timestamp as u16));
+            self.cmp_written.store(true, Ordering::Relaxed);
+
+            alarm.timestamp.set(timestamp);
+
+            // We're confident the alarm will ring in the future.
+            true
+        })
+    }
+}
+
+#[cfg(feature = "low-power")]
+pub(crate) fn get_driver() -> &'static RtcDriver {
+    &DRIVER
+}
+
+pub(crate) fn init(cs: CriticalSection) {
+    DRIVER.init(cs)
+}
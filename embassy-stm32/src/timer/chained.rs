@@ -0,0 +1,166 @@
+//! Chained timers, counting ticks on 64 bits.
+//!
+//! Two 16-bit timers are chained into a 32-bit counter: the master timer counts the ticks and
+//! emits its trigger output on each update, and the slave timer counts these updates through an
+//! internal trigger (ITRx). The overflows of the slave timer extend the counter to 64 bits, so the
+//! counter provides high-resolution timestamps over long durations, independently of the time
+//! driver:
+//!
+//! ```rust,ignore
+//! bind_interrupts!(struct Irqs {
+//!     TIM3 => chained::UpdateInterruptHandler<TIM3>;
+//! });
+//!
+//! // On STM32F4, TIM3 is triggered by TIM2 through ITR1.
+//! let counter = ChainedTimer::new(p.TIM2, p.TIM3, TriggerSource::Itr1, Irqs, Hertz::mhz(10));
+//! let start = counter.now();
+//! // ...
+//! info!("elapsed: {} ticks", counter.now() - start);
+//! ```
+
+use core::marker::PhantomData;
+use core::sync::atomic::Ordering;
+
+use embassy_hal_internal::{into_ref, PeripheralRef};
+
+use super::*;
+use crate::interrupt::typelevel::Interrupt;
+use crate::time::Hertz;
+use crate::Peripheral;
+
+/// Update interrupt handler of the slave timer, counting its overflows.
+pub struct UpdateInterruptHandler<T: CaptureCompare16bitInstance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: CaptureCompare16bitInstance> interrupt::typelevel::Handler<T::Interrupt> for UpdateInterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        critical_section::with(|_| {
+            let regs = T::regs_gp16();
+            if regs.sr().read().uif() {
+                regs.sr().modify(|w| w.set_uif(false));
+                let state = T::state();
+                let overflows = state.overflows.load(Ordering::Relaxed);
+                state.overflows.store(overflows.wrapping_add(1), Ordering::Relaxed);
+            }
+        });
+    }
+}
+
+/// Chained timers, counting ticks on 64 bits.
+pub struct ChainedTimer<'d, M: Basic16bitInstance, S: CaptureCompare16bitInstance> {
+    master: PeripheralRef<'d, M>,
+    slave: PeripheralRef<'d, S>,
+}
+
+impl<'d, M: Basic16bitInstance, S: CaptureCompare16bitInstance> ChainedTimer<'d, M, S> {
+    /// Chain `master` and `slave`, counting at `tick_freq`.
+    ///
+    /// `trigger` is the internal trigger connecting the trigger output of `master` to `slave`, see
+    /// the "TIMx internal trigger connection" table of the reference manual.
+    pub fn new(
+        master: impl Peripheral<P = M> + 'd,
+        slave: impl Peripheral<P = S> + 'd,
+        trigger: TriggerSource,
+        _irq: impl interrupt::typelevel::Binding<S::Interrupt, UpdateInterruptHandler<S>> + 'd,
+        tick_freq: Hertz,
+    ) -> Self {
+        into_ref!(master, slave);
+
+        M::enable_and_reset();
+        S::enable_and_reset();
+
+        let mut this = Self { master, slave };
+
+        let psc = M::frequency().0 / tick_freq.0;
+        assert!(psc > 0);
+        let regs = M::regs();
+        regs.psc().write(|w| w.set_psc(unwrap!(u16::try_from(psc - 1))));
+        regs.arr().write(|w| w.set_arr(u16::MAX));
+        regs.cr1().modify(|r| r.set_urs(vals::Urs::COUNTERONLY));
+        regs.egr().write(|r| r.set_ug(true));
+        // Set after the update above, so the slave only counts the overflows.
+        this.master.set_master_mode(MasterMode::Update);
+
+        let regs = S::regs_gp16();
+        regs.psc().write(|w| w.set_psc(0));
+        regs.arr().write(|w| w.set_arr(u16::MAX));
+        regs.cr1().modify(|r| r.set_urs(vals::Urs::COUNTERONLY));
+        regs.egr().write(|r| r.set_ug(true));
+        this.slave.set_trigger_source(trigger);
+        this.slave.set_slave_mode(SlaveMode::ExternalClock);
+
+        critical_section::with(|_| S::state().overflows.store(0, Ordering::Relaxed));
+
+        this.slave.enable_update_interrupt(true);
+
+        S::Interrupt::unpend();
+        unsafe { S::Interrupt::enable() };
+
+        this.slave.start();
+        this.master.start();
+
+        this
+    }
+
+    /// Get the tick frequency of the counter.
+    pub fn tick_frequency(&self) -> Hertz {
+        let psc = M::regs().psc().read().psc();
+        M::frequency() / (psc as u32 + 1)
+    }
+
+    /// Get the number of ticks since the creation of the counter.
+    pub fn now(&self) -> u64 {
+        critical_section::with(|_| {
+            let master = M::regs();
+            let slave = S::regs_gp16();
+            // The high part is the slave counter and its pending overflow. Read it before and after
+            // the low half, and retry until it is stable: the master may wrap during the read, and
+            // the slave only counts the wrap a few clocks later, so a single read of the high part
+            // could be combined with a low half that already wrapped.
+            let (hi, uif, lo) = loop {
+                let hi = slave.cnt().read().cnt();
+                let uif = slave.sr().read().uif();
+                let lo = master.cnt().read().cnt();
+                if slave.cnt().read().cnt() == hi && slave.sr().read().uif() == uif {
+                    break (hi, uif, lo);
+                }
+            };
+            combine(S::state().overflows.load(Ordering::Relaxed), uif, hi, lo)
+        })
+    }
+}
+
+impl<'d, M: Basic16bitInstance, S: CaptureCompare16bitInstance> Drop for ChainedTimer<'d, M, S> {
+    fn drop(&mut self) {
+        self.master.stop();
+        self.slave.stop();
+        S::regs_gp16().dier().write(|_| {});
+    }
+}
+
+/// Combine the halves of the counter with the overflows counted so far and the pending update
+/// flag read after the high half.
+fn combine(overflows: u32, uif: bool, hi: u16, lo: u16) -> u64 {
+    // An overflow not counted yet happened before the read if the high half is small.
+    let overflows = match uif && hi < 0x8000 {
+        true => overflows.wrapping_add(1),
+        false => overflows,
+    };
+    ((overflows as u64) << 32) | ((hi as u64) << 16) | lo as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::combine;
+
+    #[test]
+    fn combine_counts_pending_overflow() {
+        assert_eq!(combine(0, false, 0x1234, 0x5678), 0x1234_5678);
+        assert_eq!(combine(2, false, 0xFFFF, 0xFFFF), 0x2_FFFF_FFFF);
+        // Overflow pending, read after the wrap.
+        assert_eq!(combine(2, true, 0x0000, 0x0001), 0x3_0000_0001);
+        // Overflow pending, read before the wrap.
+        assert_eq!(combine(2, true, 0xFFFF, 0xFFFE), 0x2_FFFF_FFFE);
+    }
+}
@@ -9,7 +9,7 @@ use super::simple_pwm::*;
 use super::*;
 #[allow(unused_imports)]
 use crate::gpio::sealed::{AFType, Pin};
-use crate::gpio::{AnyPin, OutputType};
+use crate::gpio::{AnyPin, OutputType, Pull};
 use crate::time::Hertz;
 use crate::Peripheral;
 
@@ -47,6 +47,28 @@ complementary_channel_impl!(new_ch2, Ch2, Channel2ComplementaryPin);
 complementary_channel_impl!(new_ch3, Ch3, Channel3ComplementaryPin);
 complementary_channel_impl!(new_ch4, Ch4, Channel4ComplementaryPin);
 
+/// Break input pin wrapper.
+///
+/// This wraps a pin to make it usable as the break input of a complementary PWM.
+pub struct BreakPin<'d, T> {
+    _pin: PeripheralRef<'d, AnyPin>,
+    phantom: PhantomData<T>,
+}
+
+impl<'d, T: ComplementaryCaptureCompare16bitInstance> BreakPin<'d, T> {
+    /// Create a new break input pin instance.
+    pub fn new(pin: impl Peripheral<P = impl BreakInputPin<T>> + 'd, pull: Pull) -> Self {
+        into_ref!(pin);
+        critical_section::with(|_| {
+            pin.set_as_af_pull(pin.af_num(), AFType::Input, pull);
+        });
+        BreakPin {
+            _pin: pin.map_into(),
+            phantom: PhantomData,
+        }
+    }
+}
+
 /// PWM driver with support for standard and complementary outputs.
 pub struct ComplementaryPwm<'d, T> {
     inner: PeripheralRef<'d, T>,
@@ -148,6 +170,81 @@ impl<'d, T: ComplementaryCaptureCompare16bitInstance> ComplementaryPwm<'d, T> {
         self.inner.set_dead_time_clock_division(ckd);
         self.inner.set_dead_time_value(value);
     }
+
+    /// Enable the break input.
+    ///
+    /// When the break input becomes active, the hardware switches all the outputs to their
+    /// inactive state immediately, without CPU intervention. They stay off until
+    /// [`resume_outputs`](Self::resume_outputs) is called, or with `automatic_resume` until the
+    /// next update event with the break input inactive.
+    pub fn enable_break(&mut self, _pin: BreakPin<'d, T>, polarity: BreakPolarity, automatic_resume: bool) {
+        self.inner.clear_break_interrupt();
+        self.inner.set_automatic_output(automatic_resume);
+        self.inner.set_break(true, polarity);
+    }
+
+    /// Disable the break input.
+    pub fn disable_break(&mut self) {
+        self.inner.set_break(false, BreakPolarity::ActiveLow);
+        self.inner.set_automatic_output(false);
+    }
+
+    /// Check whether a break occurred since the last call, clearing the flag.
+    pub fn take_break(&mut self) -> bool {
+        self.inner.clear_break_interrupt()
+    }
+
+    /// Check whether the outputs are enabled, or switched off by a break.
+    pub fn outputs_enabled(&self) -> bool {
+        self.inner.get_outputs_enable_state()
+    }
+
+    /// Enable the outputs again after a break.
+    ///
+    /// The outputs stay off while the break input is active.
+    pub fn resume_outputs(&mut self) {
+        self.inner.enable_outputs();
+    }
+
+    /// Configure the event sent to slave timers on the trigger output (TRGO).
+    pub fn set_master_mode(&mut self, mode: MasterMode) {
+        self.inner.set_master_mode(mode);
+    }
+
+    /// Enable/disable master/slave mode.
+    ///
+    /// When enabled, the trigger output of this timer is delayed so it starts in lockstep with its slaves.
+    pub fn set_master_slave_mode(&mut self, enable: bool) {
+        self.inner.set_master_slave_mode(enable);
+    }
+
+    /// Make this timer a slave of the given trigger input.
+    ///
+    /// In [`SlaveMode::Trigger`] the counter is stopped and reset, so that it starts
+    /// counting on the next trigger, e.g. when the master timer is started.
+    pub fn set_slave_mode(&mut self, mode: SlaveMode, source: TriggerSource) {
+        if mode == SlaveMode::Trigger {
+            self.inner.stop();
+            self.inner.reset();
+        }
+        self.inner.set_trigger_source(source);
+        self.inner.set_slave_mode(mode);
+    }
+
+    /// Start the timer.
+    pub fn start(&mut self) {
+        self.inner.start();
+    }
+
+    /// Stop the timer.
+    pub fn stop(&mut self) {
+        self.inner.stop();
+    }
+
+    /// Reset the counter value to 0.
+    pub fn reset(&mut self) {
+        self.inner.reset();
+    }
 }
 
 impl<'d, T: ComplementaryCaptureCompare16bitInstance> embedded_hal_02::Pwm for ComplementaryPwm<'d, T> {
@@ -0,0 +1,541 @@
+//! Input capture driver.
+//!
+//! The timer counts at the tick frequency, and each channel captures the counter on the edges of
+//! its input. The 16-bit counter is extended to 32 bits by counting its overflows, so the
+//! captures only wrap around after 2^32 ticks, 71 minutes at 1 MHz. Pulse widths and periods are
+//! the wrapping differences of the captures:
+//!
+//! ```rust,ignore
+//! bind_interrupts!(struct Irqs {
+//!     TIM2 => input_capture::UpdateInterruptHandler<TIM2>, input_capture::CaptureCompareInterruptHandler<TIM2>;
+//! });
+//!
+//! let ch1 = CapturePin::new_ch1(p.PA0, Pull::None);
+//! let mut capture = InputCapture::new(p.TIM2, Some(ch1), None, None, None, Irqs, Hertz::mhz(1));
+//! capture.set_input_capture_mode(Channel::Ch1, InputCaptureMode::Rising);
+//! capture.enable(Channel::Ch1);
+//! let mut last = capture.wait_for_capture(Channel::Ch1).await;
+//! loop {
+//!     let edge = capture.wait_for_capture(Channel::Ch1).await;
+//!     info!("period: {} us", edge.wrapping_sub(last));
+//!     last = edge;
+//! }
+//! ```
+//!
+//! [`InputCapture::measure_frequency`] measures the frequency of a signal against the timer
+//! clock, for example to trim the RTC from the deviation of the LSI or LSE, or to check the HSE
+//! crystal in production. On some chips the inputs of TIM14, TIM16 and TIM17 can be connected to
+//! internal clocks instead of the pin, see [`InternalInput`].
+
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::sync::atomic::Ordering;
+use core::task::Poll;
+
+use embassy_hal_internal::{into_ref, PeripheralRef};
+
+use super::*;
+use crate::gpio::sealed::{AFType, Pin as _};
+use crate::gpio::{AnyPin, Pull};
+use crate::interrupt::typelevel::Interrupt;
+use crate::time::Hertz;
+use crate::Peripheral;
+
+/// Channel 1 marker type.
+pub enum Ch1 {}
+/// Channel 2 marker type.
+pub enum Ch2 {}
+/// Channel 3 marker type.
+pub enum Ch3 {}
+/// Channel 4 marker type.
+pub enum Ch4 {}
+
+/// Capture pin wrapper.
+///
+/// This wraps a pin to make it usable with input capture.
+pub struct CapturePin<'d, T, C> {
+    _pin: PeripheralRef<'d, AnyPin>,
+    phantom: PhantomData<(T, C)>,
+}
+
+macro_rules! channel_impl {
+    ($new_chx:ident, $channel:ident, $pin_trait:ident) => {
+        impl<'d, T: CaptureCompare16bitInstance> CapturePin<'d, T, $channel> {
+            #[doc = concat!("Create a new ", stringify!($channel), " capture pin instance.")]
+            pub fn $new_chx(pin: impl Peripheral<P = impl $pin_trait<T>> + 'd, pull: Pull) -> Self {
+                into_ref!(pin);
+                critical_section::with(|_| {
+                    pin.set_as_af_pull(pin.af_num(), AFType::Input, pull);
+                });
+                CapturePin {
+                    _pin: pin.map_into(),
+                    phantom: PhantomData,
+                }
+            }
+        }
+    };
+}
+
+channel_impl!(new_ch1, Ch1, Channel1Pin);
+channel_impl!(new_ch2, Ch2, Channel2Pin);
+channel_impl!(new_ch3, Ch3, Channel3Pin);
+channel_impl!(new_ch4, Ch4, Channel4Pin);
+
+/// Update interrupt handler, counting the overflows of the counter.
+pub struct UpdateInterruptHandler<T: CaptureCompare16bitInstance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: CaptureCompare16bitInstance> interrupt::typelevel::Handler<T::Interrupt> for UpdateInterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        on_interrupt::<T>();
+    }
+}
+
+/// Capture/compare interrupt handler, reading the captures.
+pub struct CaptureCompareInterruptHandler<T: CaptureCompare16bitInstance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: CaptureCompare16bitInstance> interrupt::typelevel::Handler<T::CaptureCompareInterrupt>
+    for CaptureCompareInterruptHandler<T>
+{
+    unsafe fn on_interrupt() {
+        on_interrupt::<T>();
+    }
+}
+
+// The update and capture/compare interrupts are the same on most timers, so both handlers handle
+// both, to order the captures and the overflows.
+fn on_interrupt<T: CaptureCompare16bitInstance>() {
+    critical_section::with(|_| {
+        let regs = T::regs_gp16();
+        let state = T::state();
+        let sr = regs.sr().read();
+        let dier = regs.dier().read();
+        let overflows = state.overflows.load(Ordering::Relaxed);
+
+        let mut pending = state.pending.load(Ordering::Relaxed);
+        for n in 0..4 {
+            if sr.ccif(n) && dier.ccie(n) {
+                // Reading the capture clears the flag.
+                let capture = regs.ccr(n).read().ccr();
+                let uif = regs.sr().read().uif();
+                state.captures[n].store(extend(overflows, uif, capture), Ordering::Relaxed);
+                pending |= 1 << n;
+                state.cc_wakers[n].wake();
+            }
+        }
+        state.pending.store(pending, Ordering::Relaxed);
+
+        if sr.uif() {
+            regs.sr().modify(|w| w.set_uif(false));
+            state.overflows.store(overflows.wrapping_add(1), Ordering::Relaxed);
+        }
+    });
+}
+
+/// Extend `count` to 32 bits, with the overflows counted so far and the pending overflow flag
+/// read after `count`.
+fn extend(overflows: u32, uif: bool, count: u16) -> u32 {
+    // A count at the start of the period with a pending overflow comes after the overflow.
+    let overflows = if uif && count < 0x8000 {
+        overflows.wrapping_add(1)
+    } else {
+        overflows
+    };
+    (overflows << 16) | count as u32
+}
+
+/// Frequency measurement, from [`InputCapture::measure_frequency`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Measurement {
+    /// Number of periods of the signal measured.
+    pub periods: u32,
+    /// Number of timer ticks during the periods.
+    pub ticks: u32,
+    /// Tick frequency of the timer.
+    pub tick_frequency: Hertz,
+}
+
+impl Measurement {
+    /// Get the measured frequency.
+    ///
+    /// Returns `None` if no timer tick was counted, when the signal is too fast for the timer.
+    pub fn frequency(&self) -> Option<Hertz> {
+        if self.ticks == 0 {
+            return None;
+        }
+        let frequency = self.tick_frequency.0 as u64 * self.periods as u64 / self.ticks as u64;
+        Some(Hertz(u32::try_from(frequency).ok()?))
+    }
+
+    /// Get the deviation of the measured frequency from `nominal`, in parts per million.
+    ///
+    /// The resolution is `1_000_000 / ticks` ppm: the longer the measurement, the finer. Returns
+    /// `None` if no timer tick was counted, or if `nominal` is zero.
+    pub fn ppm(&self, nominal: Hertz) -> Option<i32> {
+        // measured / nominal - 1, with measured = tick_frequency * periods / ticks
+        let measured = self.tick_frequency.0 as i128 * self.periods as i128;
+        let expected = nominal.0 as i128 * self.ticks as i128;
+        if expected == 0 {
+            return None;
+        }
+        i32::try_from((measured - expected) * 1_000_000 / expected).ok()
+    }
+}
+
+/// Internal clock connected to the channel 1 input of TIM14, instead of its pin.
+#[cfg(all(peri_tim14, any(stm32f0, stm32g0, stm32c0)))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InternalInput {
+    /// The channel 1 pin.
+    Pin,
+    /// The RTC clock, for example the LSI.
+    RtcClock,
+    /// The HSE, divided by 32.
+    HseDiv32,
+    /// The MCO output.
+    Mco,
+}
+
+/// Internal clock connected to the channel 1 input of TIM16, instead of its pin.
+#[cfg(all(peri_tim16, any(stm32g0, stm32c0)))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Tim16InternalInput {
+    /// The channel 1 pin.
+    Pin,
+    /// The LSI.
+    Lsi,
+    /// The LSE.
+    Lse,
+    /// The RTC wakeup interrupt.
+    RtcWakeup,
+}
+
+/// Internal clock connected to the channel 1 input of TIM17, instead of its pin.
+#[cfg(all(peri_tim17, any(stm32g0, stm32c0)))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Tim17InternalInput {
+    /// The channel 1 pin.
+    Pin,
+    /// The HSE, divided by 32.
+    HseDiv32,
+    /// The MCO output.
+    Mco,
+}
+
+/// Input selection register of TIM14, TIM16 and TIM17.
+///
+/// This is TIMx_TISEL on G0 and C0, and TIM14_OR on F0. The PAC describes the registers common to
+/// the general purpose timers only, so it is declared here with the PAC register types.
+#[cfg(any(stm32f0, stm32g0, stm32c0))]
+mod tisel {
+    use crate::pac::common::{Reg, RW};
+
+    #[cfg(stm32f0)]
+    const OFFSET: usize = 0x50;
+    #[cfg(any(stm32g0, stm32c0))]
+    const OFFSET: usize = 0x68;
+
+    /// Input selection register value.
+    #[repr(transparent)]
+    #[derive(Copy, Clone, Default)]
+    pub struct Tisel(pub u32);
+
+    impl Tisel {
+        /// Set the channel 1 input: TI1SEL on G0 and C0, TI1_RMP on F0.
+        pub fn set_ti1sel(&mut self, val: u8) {
+            self.0 = (self.0 & !0x0F) | (val as u32 & 0x0F);
+        }
+    }
+
+    /// Input selection register of the timer whose registers start at `base`.
+    pub fn tisel(base: *mut ()) -> Reg<Tisel, RW> {
+        unsafe { Reg::from_ptr((base as *mut u8).add(OFFSET) as *mut Tisel) }
+    }
+}
+
+/// Input capture driver.
+pub struct InputCapture<'d, T: CaptureCompare16bitInstance> {
+    inner: PeripheralRef<'d, T>,
+}
+
+impl<'d, T: CaptureCompare16bitInstance> InputCapture<'d, T> {
+    /// Create a new input capture driver, counting at `tick_freq`.
+    ///
+    /// The channels capture rising edges, and are disabled until [`enable`](Self::enable)d.
+    pub fn new(
+        tim: impl Peripheral<P = T> + 'd,
+        _ch1: Option<CapturePin<'d, T, Ch1>>,
+        _ch2: Option<CapturePin<'d, T, Ch2>>,
+        _ch3: Option<CapturePin<'d, T, Ch3>>,
+        _ch4: Option<CapturePin<'d, T, Ch4>>,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, UpdateInterruptHandler<T>>
+            + interrupt::typelevel::Binding<T::CaptureCompareInterrupt, CaptureCompareInterruptHandler<T>>
+            + 'd,
+        tick_freq: Hertz,
+    ) -> Self {
+        into_ref!(tim);
+
+        T::enable_and_reset();
+
+        let mut this = Self { inner: tim };
+
+        let psc = T::frequency().0 / tick_freq.0;
+        assert!(psc > 0);
+        let regs = T::regs_gp16();
+        regs.psc().write(|w| w.set_psc(unwrap!(u16::try_from(psc - 1))));
+        regs.arr().write(|w| w.set_arr(u16::MAX));
+        regs.cr1().modify(|r| r.set_urs(vals::Urs::COUNTERONLY));
+        regs.egr().write(|r| r.set_ug(true));
+        regs.cr1().modify(|r| r.set_urs(vals::Urs::ANYEVENT));
+
+        for channel in [Channel::Ch1, Channel::Ch2, Channel::Ch3, Channel::Ch4] {
+            this.inner.set_input_ti_selection(channel, InputTISelection::Normal);
+            this.inner.set_input_capture_mode(channel, InputCaptureMode::Rising);
+        }
+
+        let state = T::state();
+        critical_section::with(|_| {
+            state.overflows.store(0, Ordering::Relaxed);
+            state.pending.store(0, Ordering::Relaxed);
+        });
+        this.inner.enable_update_interrupt(true);
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+        T::CaptureCompareInterrupt::unpend();
+        unsafe { T::CaptureCompareInterrupt::enable() };
+
+        this.inner.start();
+
+        this
+    }
+
+    /// Get the tick frequency of the captures.
+    pub fn tick_frequency(&self) -> Hertz {
+        let psc = T::regs_gp16().psc().read().psc();
+        T::frequency() / (psc as u32 + 1)
+    }
+
+    /// Get the current count, extended to 32 bits like the captures.
+    pub fn count(&self) -> u32 {
+        critical_section::with(|_| {
+            let regs = T::regs_gp16();
+            let count = regs.cnt().read().cnt();
+            let uif = regs.sr().read().uif();
+            extend(T::state().overflows.load(Ordering::Relaxed), uif, count)
+        })
+    }
+
+    /// Enable the capture on `channel`.
+    pub fn enable(&mut self, channel: Channel) {
+        critical_section::with(|_| {
+            self.inner.enable_channel(channel, true);
+            self.inner.enable_input_interrupt(channel, true);
+        });
+    }
+
+    /// Disable the capture on `channel`.
+    pub fn disable(&mut self, channel: Channel) {
+        critical_section::with(|_| {
+            self.inner.enable_input_interrupt(channel, false);
+            self.inner.enable_channel(channel, false);
+        });
+    }
+
+    /// Check whether the capture on `channel` is enabled.
+    pub fn is_enabled(&self, channel: Channel) -> bool {
+        self.inner.get_channel_enable_state(channel)
+    }
+
+    /// Set the edges captured on `channel`, discarding its capture not read yet.
+    pub fn set_input_capture_mode(&mut self, channel: Channel, mode: InputCaptureMode) {
+        critical_section::with(|_| {
+            self.inner.set_input_capture_mode(channel, mode);
+            let state = T::state();
+            let pending = state.pending.load(Ordering::Relaxed);
+            state
+                .pending
+                .store(pending & !(1 << channel.index()), Ordering::Relaxed);
+        });
+    }
+
+    /// Capture only every `events` edges on `channel`, 1, 2, 4 or 8.
+    ///
+    /// This is useful to measure the period of high frequency signals.
+    pub fn set_prescaler(&mut self, channel: Channel, events: u8) {
+        assert!(matches!(events, 1 | 2 | 4 | 8));
+        self.inner
+            .set_input_capture_prescaler(channel, events.trailing_zeros() as u8);
+    }
+
+    /// Set the input filter of `channel`, as the sampling frequency and the number of samples
+    /// encoded in the ICF field of the reference manual, from 0 (no filter) to 15.
+    pub fn set_filter(&mut self, channel: Channel, filter: u8) {
+        assert!(filter < 16);
+        self.inner
+            .set_input_capture_filter(channel, vals::Icf::from_bits(filter));
+    }
+
+    /// Wait for the next capture on `channel`, and get its count.
+    ///
+    /// A capture since the last one read is returned immediately, and only the last of several
+    /// captures is kept. The channel must be enabled.
+    pub async fn wait_for_capture(&mut self, channel: Channel) -> u32 {
+        let n = channel.index();
+        let state = T::state();
+        poll_fn(|cx| {
+            state.cc_wakers[n].register(cx.waker());
+            critical_section::with(|_| {
+                let pending = state.pending.load(Ordering::Relaxed);
+                if pending & (1 << n) != 0 {
+                    state.pending.store(pending & !(1 << n), Ordering::Relaxed);
+                    Poll::Ready(state.captures[n].load(Ordering::Relaxed))
+                } else {
+                    Poll::Pending
+                }
+            })
+        })
+        .await
+    }
+
+    /// Enable `channel` to capture its rising edges, and wait for the next one.
+    pub async fn wait_for_rising_edge(&mut self, channel: Channel) -> u32 {
+        self.wait_for_edge(channel, InputCaptureMode::Rising).await
+    }
+
+    /// Enable `channel` to capture its falling edges, and wait for the next one.
+    pub async fn wait_for_falling_edge(&mut self, channel: Channel) -> u32 {
+        self.wait_for_edge(channel, InputCaptureMode::Falling).await
+    }
+
+    /// Enable `channel` to capture all its edges, and wait for the next one.
+    pub async fn wait_for_any_edge(&mut self, channel: Channel) -> u32 {
+        self.wait_for_edge(channel, InputCaptureMode::BothEdges).await
+    }
+
+    async fn wait_for_edge(&mut self, channel: Channel, mode: InputCaptureMode) -> u32 {
+        self.set_input_capture_mode(channel, mode);
+        self.enable(channel);
+        self.wait_for_capture(channel).await
+    }
+
+    /// Measure the frequency of the signal on `channel` over `periods` periods, a multiple of 8.
+    ///
+    /// The channel captures every 8 rising edges during the measurement, and is disabled after
+    /// it. The period of the signal times 8 must be longer than the interrupt latency, and the
+    /// measurement shorter than 2^32 ticks.
+    pub async fn measure_frequency(&mut self, channel: Channel, periods: u32) -> Measurement {
+        assert!(periods > 0 && periods % 8 == 0);
+
+        self.set_prescaler(channel, 8);
+        let start = self.wait_for_rising_edge(channel).await;
+        let mut end = start;
+        for _ in 0..periods / 8 {
+            end = self.wait_for_capture(channel).await;
+        }
+        self.disable(channel);
+        self.set_prescaler(channel, 1);
+
+        Measurement {
+            periods,
+            ticks: end.wrapping_sub(start),
+            tick_frequency: self.tick_frequency(),
+        }
+    }
+}
+
+#[cfg(all(peri_tim14, any(stm32f0, stm32g0, stm32c0)))]
+impl<'d> InputCapture<'d, crate::peripherals::TIM14> {
+    /// Connect the channel 1 input to an internal clock, to measure it.
+    pub fn set_internal_input(&mut self, input: InternalInput) {
+        let val = match input {
+            InternalInput::Pin => 0,
+            InternalInput::RtcClock => 1,
+            InternalInput::HseDiv32 => 2,
+            InternalInput::Mco => 3,
+        };
+        tisel::tisel(crate::pac::TIM14.as_ptr()).modify(|w| w.set_ti1sel(val));
+    }
+}
+
+#[cfg(all(peri_tim16, any(stm32g0, stm32c0)))]
+impl<'d> InputCapture<'d, crate::peripherals::TIM16> {
+    /// Connect the channel 1 input to an internal clock, to measure it.
+    pub fn set_internal_input(&mut self, input: Tim16InternalInput) {
+        let val = match input {
+            Tim16InternalInput::Pin => 0,
+            Tim16InternalInput::Lsi => 1,
+            Tim16InternalInput::Lse => 2,
+            Tim16InternalInput::RtcWakeup => 3,
+        };
+        tisel::tisel(crate::pac::TIM16.as_ptr()).modify(|w| w.set_ti1sel(val));
+    }
+}
+
+#[cfg(all(peri_tim17, any(stm32g0, stm32c0)))]
+impl<'d> InputCapture<'d, crate::peripherals::TIM17> {
+    /// Connect the channel 1 input to an internal clock, to measure it.
+    pub fn set_internal_input(&mut self, input: Tim17InternalInput) {
+        let val = match input {
+            Tim17InternalInput::Pin => 0,
+            Tim17InternalInput::HseDiv32 => 1,
+            Tim17InternalInput::Mco => 2,
+        };
+        tisel::tisel(crate::pac::TIM17.as_ptr()).modify(|w| w.set_ti1sel(val));
+    }
+}
+
+impl<'d, T: CaptureCompare16bitInstance> Drop for InputCapture<'d, T> {
+    fn drop(&mut self) {
+        self.inner.stop();
+        T::regs_gp16().dier().write(|_| {});
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extend, Measurement};
+    use crate::time::Hertz;
+
+    #[test]
+    fn extend_orders_captures_and_overflows() {
+        assert_eq!(extend(2, false, 0x1234), 0x0002_1234);
+        // Pending overflow, the capture came after it.
+        assert_eq!(extend(2, true, 0x0010), 0x0003_0010);
+        // Pending overflow, the capture came before it.
+        assert_eq!(extend(2, true, 0xFFF0), 0x0002_FFF0);
+        assert_eq!(extend(0xFFFF, true, 0x0001), 0x0000_0001);
+    }
+
+    #[test]
+    fn measurement() {
+        // 32 kHz LSI against a 1 MHz timer, 0.1 % fast.
+        let m = Measurement {
+            periods: 32_032,
+            ticks: 1_000_000,
+            tick_frequency: Hertz(1_000_000),
+        };
+        assert_eq!(m.frequency(), Some(Hertz(32_032)));
+        assert_eq!(m.ppm(Hertz(32_000)), Some(1000));
+        assert_eq!(m.ppm(Hertz(32_032)), Some(0));
+        assert_eq!(m.ppm(Hertz(0)), None);
+    }
+
+    #[test]
+    fn measurement_without_ticks() {
+        let m = Measurement {
+            periods: 8,
+            ticks: 0,
+            tick_frequency: Hertz(1_000_000),
+        };
+        assert_eq!(m.frequency(), None);
+        assert_eq!(m.ppm(Hertz(32_000)), None);
+    }
+}
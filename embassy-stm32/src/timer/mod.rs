@@ -1,9 +1,19 @@
-//! Timers, PWM, quadrature decoder.
+//! Timers, periodic ticks, PWM, quadrature decoder, one-pulse, chained counters, synchronized start, servo and stepper motors.
 
+pub mod chained;
 pub mod complementary_pwm;
+pub mod input_capture;
+pub mod motion;
+pub mod one_pulse;
+pub mod periodic;
+pub mod pwm_input;
 pub mod qei;
 pub mod simple_pwm;
+pub mod sync;
 
+use core::sync::atomic::{AtomicU32, AtomicU8};
+
+use embassy_sync::waitqueue::AtomicWaker;
 use stm32_metapac::timer::vals;
 
 use crate::interrupt;
@@ -19,11 +29,63 @@ pub mod low_level {
 pub(crate) mod sealed {
     use super::*;
 
+    /// Capture/compare state of a timer.
+    pub struct State {
+        /// Number of counter overflows since the start of the input capture, or signed number of
+        /// counter wraparounds of the quadrature decoder.
+        pub overflows: AtomicU32,
+        /// Last capture of each channel, extended to 32 bits with the overflows.
+        pub captures: [AtomicU32; 4],
+        /// Mask of the channels with a capture not read yet.
+        pub pending: AtomicU8,
+        /// Waker of each channel.
+        pub cc_wakers: [AtomicWaker; 4],
+    }
+
+    impl State {
+        /// Create a new state.
+        pub const fn new() -> Self {
+            const NEW_WAKER: AtomicWaker = AtomicWaker::new();
+            Self {
+                overflows: AtomicU32::new(0),
+                captures: [
+                    AtomicU32::new(0),
+                    AtomicU32::new(0),
+                    AtomicU32::new(0),
+                    AtomicU32::new(0),
+                ],
+                pending: AtomicU8::new(0),
+                cc_wakers: [NEW_WAKER; 4],
+            }
+        }
+    }
+
+    /// Update state of a timer.
+    pub struct UpdateState {
+        /// Number of update events not read yet.
+        pub ticks: AtomicU32,
+        /// Waker of the update events.
+        pub waker: AtomicWaker,
+    }
+
+    impl UpdateState {
+        /// Create a new state.
+        pub const fn new() -> Self {
+            Self {
+                ticks: AtomicU32::new(0),
+                waker: AtomicWaker::new(),
+            }
+        }
+    }
+
     /// Basic 16-bit timer instance.
     pub trait Basic16bitInstance: RccPeripheral {
         /// Interrupt for this timer.
         type Interrupt: interrupt::typelevel::Interrupt;
 
+        /// Get the update state of this timer.
+        fn update_state() -> &'static UpdateState;
+
         /// Get access to the basic 16bit timer registers.
         ///
         /// Note: This works even if the timer is more capable, because registers
@@ -54,16 +116,12 @@ pub(crate) mod sealed {
         /// In center-aligned mode (which not all timers support), the wrap-around frequency is effectively halved
         /// because it needs to count up and down.
         fn set_frequency(&mut self, frequency: Hertz) {
-            let f = frequency.0;
-            let timer_f = Self::frequency().0;
-            assert!(f > 0);
-            let pclk_ticks_per_timer_period = timer_f / f;
-            let psc: u16 = unwrap!(((pclk_ticks_per_timer_period - 1) / (1 << 16)).try_into());
-            let divide_by = pclk_ticks_per_timer_period / (u32::from(psc) + 1);
-
-            // the timer counts `0..=arr`, we want it to count `0..divide_by`
-            let arr = unwrap!(u16::try_from(divide_by - 1));
+            let (psc, arr) = compute_psc_arr(Self::frequency(), frequency);
+            self.set_psc_arr(psc, arr);
+        }
 
+        /// Set the prescaler and the auto-reload values, e.g. computed by [`compute_psc_arr`].
+        fn set_psc_arr(&mut self, psc: u16, arr: u16) {
             let regs = Self::regs();
             regs.psc().write(|r| r.set_psc(psc));
             regs.arr().write(|r| r.set_arr(arr));
@@ -109,6 +167,11 @@ pub(crate) mod sealed {
             Self::regs().cr1().modify(|r| r.set_arpe(enable));
         }
 
+        /// Set the master mode, selecting which event is sent to slave timers on TRGO.
+        fn set_master_mode(&mut self, mode: MasterMode) {
+            Self::regs().cr2().modify(|r| r.set_mms(mode.into()));
+        }
+
         /// Get the timer frequency.
         fn get_frequency(&self) -> Hertz {
             let timer_f = Self::frequency();
@@ -154,6 +217,25 @@ pub(crate) mod sealed {
         fn set_clock_division(&mut self, ckd: vals::Ckd) {
             Self::regs_gp16().cr1().modify(|r| r.set_ckd(ckd));
         }
+
+        /// Set slave mode.
+        fn set_slave_mode(&mut self, mode: SlaveMode) {
+            Self::regs_gp16().smcr().modify(|r| r.set_sms(mode.into()));
+        }
+
+        /// Set the trigger input used to synchronize the counter in slave mode.
+        fn set_trigger_source(&mut self, source: TriggerSource) {
+            Self::regs_gp16().smcr().modify(|r| r.set_ts(source.into()));
+        }
+
+        /// Enable/disable master/slave mode.
+        ///
+        /// When enabled, the effect of the trigger input is delayed to allow a perfect
+        /// synchronization between this timer and its slaves.
+        fn set_master_slave_mode(&mut self, enable: bool) {
+            let msm = if enable { vals::Msm::SYNC } else { vals::Msm::NOSYNC };
+            Self::regs_gp16().smcr().modify(|r| r.set_msm(msm));
+        }
     }
 
     /// Gneral-purpose 32-bit timer instance.
@@ -204,6 +286,12 @@ pub(crate) mod sealed {
 
     /// Capture/Compare 16-bit timer instance.
     pub trait CaptureCompare16bitInstance: GeneralPurpose16bitInstance {
+        /// Capture/compare interrupt for this timer.
+        type CaptureCompareInterrupt: interrupt::typelevel::Interrupt;
+
+        /// Get the capture/compare state of this timer.
+        fn state() -> &'static State;
+
         /// Set input capture filter.
         fn set_input_capture_filter(&mut self, channel: Channel, icf: vals::Icf) {
             let raw_channel = channel.index();
@@ -358,6 +446,34 @@ pub(crate) mod sealed {
                 .ccer()
                 .modify(|w| w.set_ccne(channel.index(), enable));
         }
+
+        /// Enable/disable the break input, with its polarity.
+        fn set_break(&mut self, enable: bool, polarity: BreakPolarity) {
+            Self::regs_advanced().bdtr().modify(|w| {
+                w.set_bke(enable);
+                w.set_bkp(polarity.into());
+            });
+        }
+
+        /// Enable/disable the automatic output enable, at the next update event after a break.
+        fn set_automatic_output(&mut self, enable: bool) {
+            Self::regs_advanced().bdtr().modify(|w| w.set_aoe(enable));
+        }
+
+        /// Get the main output enable state, cleared by a break.
+        fn get_outputs_enable_state(&self) -> bool {
+            Self::regs_advanced().bdtr().read().moe()
+        }
+
+        /// Clear the break interrupt flag, returning whether it was set.
+        fn clear_break_interrupt(&mut self) -> bool {
+            let regs = Self::regs_advanced();
+            let bif = regs.sr().read().bif();
+            if bif {
+                regs.sr().modify(|w| w.set_bif(false));
+            }
+            bif
+        }
     }
 
     /// Capture/Compare 32-bit timer instance.
@@ -508,6 +624,116 @@ impl From<(vals::Cms, vals::Dir)> for CountingMode {
     }
 }
 
+/// Master mode: event sent to slave timers on the trigger output (TRGO).
+///
+/// To start several timers at exactly the same time, put the master in [`MasterMode::Enable`]
+/// and the slaves in [`SlaveMode::Trigger`] with the internal trigger (ITRx) connected to the
+/// master. The slaves then start counting as soon as the master is enabled. This is done by
+/// [`sync::start_synchronized`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MasterMode {
+    /// The UG bit is used as trigger output.
+    Reset,
+    /// The counter enable signal is used as trigger output.
+    Enable,
+    /// The update event is used as trigger output.
+    Update,
+    /// A pulse is sent on every capture or compare match of channel 1.
+    ComparePulse,
+    /// OC1REF is used as trigger output.
+    CompareOc1,
+    /// OC2REF is used as trigger output.
+    CompareOc2,
+    /// OC3REF is used as trigger output.
+    CompareOc3,
+    /// OC4REF is used as trigger output.
+    CompareOc4,
+}
+
+impl From<MasterMode> for vals::Mms {
+    fn from(mode: MasterMode) -> Self {
+        match mode {
+            MasterMode::Reset => vals::Mms::RESET,
+            MasterMode::Enable => vals::Mms::ENABLE,
+            MasterMode::Update => vals::Mms::UPDATE,
+            MasterMode::ComparePulse => vals::Mms::COMPAREPULSE,
+            MasterMode::CompareOc1 => vals::Mms::COMPAREOC1,
+            MasterMode::CompareOc2 => vals::Mms::COMPAREOC2,
+            MasterMode::CompareOc3 => vals::Mms::COMPAREOC3,
+            MasterMode::CompareOc4 => vals::Mms::COMPAREOC4,
+        }
+    }
+}
+
+/// Slave mode: how the counter reacts to the selected [`TriggerSource`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SlaveMode {
+    /// Slave mode disabled, the counter is clocked by the internal clock.
+    Disabled,
+    /// A rising edge of the trigger reinitializes the counter.
+    Reset,
+    /// The counter is clocked while the trigger is high, and stops while it is low.
+    Gated,
+    /// The counter starts on a rising edge of the trigger.
+    Trigger,
+    /// Rising edges of the trigger clock the counter.
+    ExternalClock,
+}
+
+impl From<SlaveMode> for vals::Sms {
+    fn from(mode: SlaveMode) -> Self {
+        match mode {
+            SlaveMode::Disabled => vals::Sms::DISABLED,
+            SlaveMode::Reset => vals::Sms::RESET_MODE,
+            SlaveMode::Gated => vals::Sms::GATED_MODE,
+            SlaveMode::Trigger => vals::Sms::TRIGGER_MODE,
+            SlaveMode::ExternalClock => vals::Sms::EXT_CLOCK_MODE,
+        }
+    }
+}
+
+/// Trigger input used in slave mode.
+///
+/// Which timer is connected to each internal trigger (ITRx) depends on the chip,
+/// see the "TIMx internal trigger connection" table in the reference manual.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TriggerSource {
+    /// Internal trigger 0.
+    Itr0,
+    /// Internal trigger 1.
+    Itr1,
+    /// Internal trigger 2.
+    Itr2,
+    /// Internal trigger 3.
+    Itr3,
+    /// TI1 edge detector.
+    Ti1fEd,
+    /// Filtered timer input 1.
+    Ti1fp1,
+    /// Filtered timer input 2.
+    Ti2fp2,
+    /// External trigger input.
+    Etrf,
+}
+
+impl From<TriggerSource> for vals::Ts {
+    fn from(source: TriggerSource) -> Self {
+        match source {
+            TriggerSource::Itr0 => vals::Ts::ITR0,
+            TriggerSource::Itr1 => vals::Ts::ITR1,
+            TriggerSource::Itr2 => vals::Ts::ITR2,
+            TriggerSource::Itr3 => vals::Ts::ITR3,
+            TriggerSource::Ti1fEd => vals::Ts::TI1F_ED,
+            TriggerSource::Ti1fp1 => vals::Ts::TI1FP1,
+            TriggerSource::Ti2fp2 => vals::Ts::TI2FP2,
+            TriggerSource::Etrf => vals::Ts::ETRF,
+        }
+    }
+}
+
 /// Output compare mode.
 #[derive(Clone, Copy)]
 pub enum OutputCompareMode {
@@ -571,6 +797,24 @@ impl From<OutputPolarity> for bool {
     }
 }
 
+/// Break input polarity.
+#[derive(Clone, Copy)]
+pub enum BreakPolarity {
+    /// The break is active when the input is low.
+    ActiveLow,
+    /// The break is active when the input is high.
+    ActiveHigh,
+}
+
+impl From<BreakPolarity> for bool {
+    fn from(polarity: BreakPolarity) -> Self {
+        match polarity {
+            BreakPolarity::ActiveLow => false,
+            BreakPolarity::ActiveHigh => true,
+        }
+    }
+}
+
 /// Basic 16-bit timer instance.
 pub trait Basic16bitInstance: sealed::Basic16bitInstance + 'static {}
 
@@ -626,6 +870,11 @@ macro_rules! impl_basic_16bit_timer {
         impl sealed::Basic16bitInstance for crate::peripherals::$inst {
             type Interrupt = crate::interrupt::typelevel::$irq;
 
+            fn update_state() -> &'static sealed::UpdateState {
+                static STATE: sealed::UpdateState = sealed::UpdateState::new();
+                &STATE
+            }
+
             fn regs() -> crate::pac::timer::TimBasic {
                 unsafe { crate::pac::timer::TimBasic::from_ptr(crate::pac::$inst.as_ptr()) }
             }
@@ -646,8 +895,15 @@ macro_rules! impl_32bit_timer {
 
 #[allow(unused)]
 macro_rules! impl_compare_capable_16bit {
-    ($inst:ident) => {
+    ($inst:ident, $irq:ident) => {
         impl sealed::CaptureCompare16bitInstance for crate::peripherals::$inst {
+            type CaptureCompareInterrupt = crate::interrupt::typelevel::$irq;
+
+            fn state() -> &'static sealed::State {
+                static STATE: sealed::State = sealed::State::new();
+                &STATE
+            }
+
             fn enable_outputs(&mut self) {}
         }
     };
@@ -660,7 +916,6 @@ foreach_interrupt! {
     };
     ($inst:ident, timer, TIM_GP16, UP, $irq:ident) => {
         impl_basic_16bit_timer!($inst, $irq);
-        impl_compare_capable_16bit!($inst);
         impl Basic16bitInstance for crate::peripherals::$inst {}
         impl GeneralPurpose16bitInstance for crate::peripherals::$inst {}
         impl CaptureCompare16bitInstance for crate::peripherals::$inst {}
@@ -672,10 +927,13 @@ foreach_interrupt! {
         }
     };
 
+    ($inst:ident, timer, TIM_GP16, CC, $irq:ident) => {
+        impl_compare_capable_16bit!($inst, $irq);
+    };
+
     ($inst:ident, timer, TIM_GP32, UP, $irq:ident) => {
         impl_basic_16bit_timer!($inst, $irq);
         impl_32bit_timer!($inst);
-        impl_compare_capable_16bit!($inst);
         impl Basic16bitInstance for crate::peripherals::$inst {}
         impl CaptureCompare16bitInstance for crate::peripherals::$inst {}
         impl CaptureCompare32bitInstance for crate::peripherals::$inst {}
@@ -690,6 +948,10 @@ foreach_interrupt! {
         }
     };
 
+    ($inst:ident, timer, TIM_GP32, CC, $irq:ident) => {
+        impl_compare_capable_16bit!($inst, $irq);
+    };
+
     ($inst:ident, timer, TIM_ADV, UP, $irq:ident) => {
         impl_basic_16bit_timer!($inst, $irq);
 
@@ -698,13 +960,6 @@ foreach_interrupt! {
         impl CaptureCompare16bitInstance for crate::peripherals::$inst {}
         impl ComplementaryCaptureCompare16bitInstance for crate::peripherals::$inst {}
         impl AdvancedControlInstance for crate::peripherals::$inst {}
-        impl sealed::CaptureCompare16bitInstance for crate::peripherals::$inst {
-            fn enable_outputs(&mut self) {
-                use crate::timer::sealed::AdvancedControlInstance;
-                let r = Self::regs_advanced();
-                r.bdtr().modify(|w| w.set_moe(true));
-            }
-        }
         impl sealed::ComplementaryCaptureCompare16bitInstance for crate::peripherals::$inst {}
         impl sealed::GeneralPurpose16bitInstance for crate::peripherals::$inst {
             fn regs_gp16() -> crate::pac::timer::TimGp16 {
@@ -718,6 +973,43 @@ foreach_interrupt! {
             }
         }
     };
+
+    ($inst:ident, timer, TIM_ADV, CC, $irq:ident) => {
+        impl sealed::CaptureCompare16bitInstance for crate::peripherals::$inst {
+            type CaptureCompareInterrupt = crate::interrupt::typelevel::$irq;
+
+            fn state() -> &'static sealed::State {
+                static STATE: sealed::State = sealed::State::new();
+                &STATE
+            }
+
+            fn enable_outputs(&mut self) {
+                use crate::timer::sealed::AdvancedControlInstance;
+                let r = Self::regs_advanced();
+                r.bdtr().modify(|w| w.set_moe(true));
+            }
+        }
+    };
+}
+
+/// Compute the prescaler and auto-reload values of a 16-bit timer clocked at `timer_f`, so that
+/// its counter wraps around at `frequency`.
+///
+/// This is a `const fn`: when the clocks are known, the values can be computed at compile time,
+/// and an unachievable frequency then fails the build:
+///
+/// ```rust,ignore
+/// const PSC_ARR: (u16, u16) = compute_psc_arr(Hertz::mhz(84), Hertz::hz(50));
+/// ```
+pub const fn compute_psc_arr(timer_f: Hertz, frequency: Hertz) -> (u16, u16) {
+    assert!(frequency.0 > 0 && frequency.0 <= timer_f.0);
+    let pclk_ticks_per_timer_period = timer_f.0 / frequency.0;
+    let psc = (pclk_ticks_per_timer_period - 1) / (1 << 16);
+    assert!(psc <= u16::MAX as u32);
+    let divide_by = pclk_ticks_per_timer_period / (psc + 1);
+
+    // the timer counts `0..=arr`, we want it to count `0..divide_by`
+    (psc as u16, (divide_by - 1) as u16)
 }
 
 // Update Event trigger DMA for every timer
@@ -727,3 +1019,16 @@ dma_trait!(Ch1Dma, CaptureCompare16bitInstance);
 dma_trait!(Ch2Dma, CaptureCompare16bitInstance);
 dma_trait!(Ch3Dma, CaptureCompare16bitInstance);
 dma_trait!(Ch4Dma, CaptureCompare16bitInstance);
+
+#[cfg(test)]
+mod tests {
+    use super::compute_psc_arr;
+    use crate::time::Hertz;
+
+    #[test]
+    fn can_compute_psc_arr() {
+        assert_eq!(compute_psc_arr(Hertz::mhz(84), Hertz::khz(1)), (1, 41_999));
+        assert_eq!(compute_psc_arr(Hertz::mhz(84), Hertz::hz(50)), (25, 64_614));
+        assert_eq!(compute_psc_arr(Hertz::mhz(84), Hertz::mhz(84)), (0, 0));
+    }
+}
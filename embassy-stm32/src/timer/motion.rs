@@ -0,0 +1,329 @@
+//! Servo and stepper motor drivers.
+
+use embassy_hal_internal::drop::OnDrop;
+use embassy_hal_internal::into_ref;
+use stm32_metapac::timer::vals;
+
+use super::simple_pwm::SimplePwm;
+use super::*;
+use crate::dma::{Transfer, TransferOptions};
+use crate::gpio::{Level, Output};
+use crate::time::Hertz;
+use crate::Peripheral;
+
+/// Period of the servo control pulses, in microseconds.
+const SERVO_PERIOD_US: u32 = 20_000;
+
+/// Servo calibration, mapping the angles to pulse widths.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ServoCalibration {
+    /// Pulse width at angle 0, in microseconds.
+    pub min_pulse_us: u32,
+    /// Pulse width at angle [`range_degrees`](Self::range_degrees), in microseconds.
+    pub max_pulse_us: u32,
+    /// Angle range of the servo, in degrees.
+    pub range_degrees: f32,
+}
+
+impl Default for ServoCalibration {
+    fn default() -> Self {
+        Self {
+            min_pulse_us: 1000,
+            max_pulse_us: 2000,
+            range_degrees: 180.0,
+        }
+    }
+}
+
+/// Hobby servo driver, controlling a servo on each channel of a PWM timer.
+///
+/// The servos are driven with a pulse every 20 ms, whose width sets their angle.
+pub struct Servo<'d, T: CaptureCompare16bitInstance> {
+    pwm: SimplePwm<'d, T>,
+    calibrations: [ServoCalibration; 4],
+}
+
+impl<'d, T: CaptureCompare16bitInstance> Servo<'d, T> {
+    /// Create a new servo driver, setting the PWM frequency to 50 Hz.
+    ///
+    /// The channels are disabled until their angle or pulse width is set.
+    pub fn new(mut pwm: SimplePwm<'d, T>) -> Self {
+        pwm.set_frequency(Hertz(1_000_000 / SERVO_PERIOD_US));
+        for channel in [Channel::Ch1, Channel::Ch2, Channel::Ch3, Channel::Ch4] {
+            pwm.disable(channel);
+        }
+
+        Self {
+            pwm,
+            calibrations: [ServoCalibration::default(); 4],
+        }
+    }
+
+    /// Set the calibration of the servo on `channel`.
+    pub fn set_calibration(&mut self, channel: Channel, calibration: ServoCalibration) {
+        self.calibrations[channel.index()] = calibration;
+    }
+
+    /// Set the angle of the servo on `channel`, in degrees.
+    ///
+    /// The angle is clamped to the range of the calibration.
+    pub fn set_angle(&mut self, channel: Channel, degrees: f32) {
+        let cal = self.calibrations[channel.index()];
+        let ratio = (degrees / cal.range_degrees).clamp(0.0, 1.0);
+        let span = cal.max_pulse_us as f32 - cal.min_pulse_us as f32;
+        let pulse_us = cal.min_pulse_us as f32 + span * ratio;
+        self.set_pulse_width_us(channel, pulse_us as u32);
+    }
+
+    /// Set the pulse width of the servo on `channel`, in microseconds.
+    pub fn set_pulse_width_us(&mut self, channel: Channel, pulse_us: u32) {
+        let pulse_us = pulse_us.min(SERVO_PERIOD_US) as u64;
+        let duty = pulse_us * self.pwm.get_max_duty() as u64 / SERVO_PERIOD_US as u64;
+        self.pwm.set_duty(channel, duty as u16);
+        self.pwm.enable(channel);
+    }
+
+    /// Stop the pulses on `channel`, the servo stops holding its position.
+    pub fn disable(&mut self, channel: Channel) {
+        self.pwm.disable(channel);
+    }
+
+    /// Release the PWM driver.
+    pub fn into_inner(self) -> SimplePwm<'d, T> {
+        self.pwm
+    }
+}
+
+/// Stepper motor driver configuration.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct StepperConfig {
+    /// Maximum speed, in steps per second.
+    pub max_speed: f32,
+    /// Acceleration and deceleration, in steps per second squared.
+    pub acceleration: f32,
+    /// Low time of the step output before each step, in microseconds.
+    ///
+    /// Each step is a rising edge of the step output, which stays high until the next step.
+    pub step_low_time_us: u32,
+    /// Invert the direction output: it is high for positive moves by default.
+    pub invert_direction: bool,
+}
+
+impl Default for StepperConfig {
+    fn default() -> Self {
+        Self {
+            max_speed: 1000.0,
+            acceleration: 2000.0,
+            step_low_time_us: 2,
+            invert_direction: false,
+        }
+    }
+}
+
+/// Stepper motor driver, generating the step pulses of a step/direction driver with a timer.
+///
+/// The period of each step is written to the timer by DMA, following a trapezoidal speed profile:
+/// the motor accelerates up to the maximum speed, and decelerates to stop exactly on the target.
+/// The CPU only refills the DMA buffer every few steps, so the step timing doesn't depend on the
+/// interrupt latency. The DMA refills must still be faster than one step period, which is the
+/// case up to tens of kHz.
+pub struct Stepper<'d, T: CaptureCompare16bitInstance> {
+    pwm: SimplePwm<'d, T>,
+    channel: Channel,
+    dir: Output<'d>,
+    config: StepperConfig,
+    position: i32,
+}
+
+impl<'d, T: CaptureCompare16bitInstance> Stepper<'d, T> {
+    /// Create a new stepper driver, with the step output on `channel` of `pwm`.
+    ///
+    /// The timer of `pwm` is dedicated to the stepper, its other channels can't be used.
+    pub fn new(mut pwm: SimplePwm<'d, T>, channel: Channel, dir: Output<'d>, config: StepperConfig) -> Self {
+        pwm.stop();
+        pwm.reset();
+        // Low until the compare value, then high until the end of the period.
+        pwm.set_output_compare_mode(channel, OutputCompareMode::PwmMode2);
+        pwm.set_duty(channel, 1);
+        pwm.enable(channel);
+
+        Self {
+            pwm,
+            channel,
+            dir,
+            config,
+            position: 0,
+        }
+    }
+
+    /// Set the configuration, used by the next moves.
+    pub fn set_config(&mut self, config: StepperConfig) {
+        self.config = config;
+    }
+
+    /// Get the position, in steps.
+    pub fn position(&self) -> i32 {
+        self.position
+    }
+
+    /// Set the position, in steps, for example after homing.
+    pub fn set_position(&mut self, position: i32) {
+        self.position = position;
+    }
+
+    /// Move to `target`, in steps from position 0.
+    ///
+    /// Note:
+    /// you will need to provide corresponding TIMx_UP DMA channel to use this method.
+    pub async fn move_to(&mut self, dma: impl Peripheral<P = impl UpDma<T>>, target: i32) {
+        self.move_by(dma, target - self.position).await
+    }
+
+    /// Move by `steps`, positive or negative.
+    ///
+    /// If the future is dropped, the motor stops abruptly after the current step, and the position
+    /// is no longer accurate.
+    ///
+    /// Note:
+    /// you will need to provide corresponding TIMx_UP DMA channel to use this method.
+    pub async fn move_by(&mut self, dma: impl Peripheral<P = impl UpDma<T>>, steps: i32) {
+        if steps == 0 {
+            return;
+        }
+
+        into_ref!(dma);
+
+        #[allow(clippy::let_unit_value)] // eg. stm32f334
+        let req = dma.request();
+
+        self.dir
+            .set_level(Level::from((steps > 0) != self.config.invert_direction));
+
+        let count = steps.unsigned_abs();
+        let profile = Profile::new(&self.config, T::frequency(), count);
+        let regs = T::regs_gp16();
+
+        // Stop the timer at the next update if the future is dropped.
+        let on_drop = OnDrop::new(|| {
+            regs.dier().modify(|w| w.set_ude(false));
+            regs.cr1().modify(|w| w.set_opm(true));
+        });
+
+        // Load the prescaler, the first period and the step low time, without a DMA request.
+        regs.cr1().modify(|w| {
+            w.set_opm(false);
+            w.set_arpe(true);
+            w.set_urs(vals::Urs::COUNTERONLY);
+        });
+        regs.psc().write(|w| w.set_psc(profile.psc));
+        regs.arr().write(|w| w.set_arr(profile.period(0)));
+        regs.ccr(self.channel.index()).write(|w| w.set_ccr(profile.low_ticks));
+        regs.egr().write(|w| w.set_ug(true));
+
+        // The DMA request of each update event writes the period after the next one, since the
+        // autoreload register is preloaded: preload the second period now.
+        if count > 1 {
+            regs.arr().write(|w| w.set_arr(profile.period(1)));
+        }
+
+        regs.dier().modify(|w| w.set_ude(true));
+        self.pwm.start();
+
+        // The last period is written once more, so the last transfer completes during the last step.
+        let mut buf = [0u16; 32];
+        let mut next = 2;
+        while next <= count {
+            let n = (count + 1 - next).min(buf.len() as u32) as usize;
+            for (i, period) in buf[..n].iter_mut().enumerate() {
+                *period = profile.period((next + i as u32).min(count - 1));
+            }
+            unsafe {
+                Transfer::new_write(
+                    &mut dma,
+                    req,
+                    &buf[..n],
+                    regs.arr().as_ptr() as *mut u16,
+                    TransferOptions::default(),
+                )
+                .await
+            };
+            next += n as u32;
+        }
+
+        // Now in the last step, stop at its end.
+        regs.dier().modify(|w| w.set_ude(false));
+        regs.cr1().modify(|w| w.set_opm(true));
+        while regs.cr1().read().cen() {
+            embassy_futures::yield_now().await;
+        }
+        on_drop.defuse();
+
+        regs.cr1().modify(|w| w.set_urs(vals::Urs::ANYEVENT));
+        self.position += steps;
+    }
+
+    /// Release the PWM driver and the direction output.
+    pub fn into_inner(self) -> (SimplePwm<'d, T>, Output<'d>) {
+        (self.pwm, self.dir)
+    }
+}
+
+/// Trapezoidal speed profile of a move.
+struct Profile {
+    psc: u16,
+    count: u32,
+    /// Period of the first step, in timer ticks.
+    first_ticks: f32,
+    /// Period at the maximum speed, in timer ticks.
+    min_ticks: f32,
+    low_ticks: u16,
+}
+
+impl Profile {
+    fn new(config: &StepperConfig, timer_f: Hertz, count: u32) -> Self {
+        assert!(config.max_speed > 0.0 && config.acceleration > 0.0);
+
+        // Time from standstill to the first step at constant acceleration.
+        let first_s = sqrt(2.0 / config.acceleration);
+
+        // Lowest prescaler fitting the first period in 16 bits.
+        let psc: u16 = unwrap!(((timer_f.0 as f32 * first_s / 65536.0) as u32).try_into());
+        let tick_hz = timer_f.0 as f32 / (psc as f32 + 1.0);
+        let low_ticks = (config.step_low_time_us as f32 * tick_hz / 1_000_000.0) as u16;
+
+        Self {
+            psc,
+            count,
+            first_ticks: tick_hz * first_s,
+            min_ticks: tick_hz / config.max_speed,
+            low_ticks: low_ticks.max(1),
+        }
+    }
+
+    /// Autoreload value of step `i`.
+    fn period(&self, i: u32) -> u16 {
+        // The deceleration mirrors the acceleration. At constant acceleration, step `n` happens at
+        // `first_s * sqrt(n + 1)` seconds.
+        let n = i.min(self.count - 1 - i) as f32;
+        let ticks = (self.first_ticks * (sqrt(n + 1.0) - sqrt(n))).max(self.min_ticks);
+        let ticks = ticks.clamp(self.low_ticks as f32 + 2.0, 65536.0);
+        (ticks as u32 - 1) as u16
+    }
+}
+
+/// Square root, precise enough for the speed profiles.
+fn sqrt(x: f32) -> f32 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+
+    // Initial estimate from the exponent, refined with Newton's method.
+    let mut y = f32::from_bits((x.to_bits() >> 1) + 0x1fbd_1df5);
+    for _ in 0..3 {
+        y = 0.5 * (y + x / y);
+    }
+    y
+}
@@ -0,0 +1,295 @@
+//! One-pulse driver.
+//!
+//! The timer generates a single pulse on the output of channel 1 or 2, after a delay, each time it
+//! is triggered. The pulse is triggered by software, by an edge on the input of the other channel,
+//! or by another timer through an internal trigger:
+//!
+//! ```rust,ignore
+//! bind_interrupts!(struct Irqs {
+//!     TIM3 => one_pulse::UpdateInterruptHandler<TIM3>;
+//! });
+//!
+//! // Pulses of 10 us, 2 us after the trigger.
+//! let mut pulse = OnePulse::new_ch1(p.TIM3, p.PA6, OutputType::PushPull, Irqs, Hertz::mhz(1));
+//! pulse.set_pulse(2, 10);
+//! loop {
+//!     pulse.pulse().await;
+//!     Timer::after_millis(60).await;
+//! }
+//! ```
+
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::sync::atomic::Ordering;
+use core::task::Poll;
+
+use embassy_hal_internal::{into_ref, PeripheralRef};
+
+use super::*;
+use crate::gpio::sealed::{AFType, Pin as _};
+use crate::gpio::{AnyPin, OutputType, Pull};
+use crate::interrupt::typelevel::Interrupt;
+use crate::time::Hertz;
+use crate::Peripheral;
+
+/// Update interrupt handler, signaling the end of each pulse.
+pub struct UpdateInterruptHandler<T: CaptureCompare16bitInstance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: CaptureCompare16bitInstance> interrupt::typelevel::Handler<T::Interrupt> for UpdateInterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        critical_section::with(|_| {
+            let regs = T::regs_gp16();
+            if regs.sr().read().uif() {
+                regs.sr().modify(|w| w.set_uif(false));
+                let state = T::state();
+                state.pending.store(1, Ordering::Relaxed);
+                state.cc_wakers[0].wake();
+            }
+        });
+    }
+}
+
+/// One-pulse driver.
+pub struct OnePulse<'d, T: CaptureCompare16bitInstance> {
+    inner: PeripheralRef<'d, T>,
+    channel: Channel,
+    _pin: PeripheralRef<'d, AnyPin>,
+    _trigger_pin: Option<PeripheralRef<'d, AnyPin>>,
+}
+
+impl<'d, T: CaptureCompare16bitInstance> OnePulse<'d, T> {
+    /// Create a new one-pulse driver on the output of channel 1, counting at `tick_freq`.
+    ///
+    /// The pulses are triggered by software, or by [`set_trigger`](Self::set_trigger).
+    pub fn new_ch1(
+        tim: impl Peripheral<P = T> + 'd,
+        pin: impl Peripheral<P = impl Channel1Pin<T>> + 'd,
+        output_type: OutputType,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, UpdateInterruptHandler<T>> + 'd,
+        tick_freq: Hertz,
+    ) -> Self {
+        into_ref!(pin);
+        configure_output(&*pin, pin.af_num(), output_type);
+
+        Self::new_inner(tim, pin.map_into(), Channel::Ch1, None, tick_freq)
+    }
+
+    /// Create a new one-pulse driver on the output of channel 2, counting at `tick_freq`.
+    ///
+    /// The pulses are triggered by software, or by [`set_trigger`](Self::set_trigger).
+    pub fn new_ch2(
+        tim: impl Peripheral<P = T> + 'd,
+        pin: impl Peripheral<P = impl Channel2Pin<T>> + 'd,
+        output_type: OutputType,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, UpdateInterruptHandler<T>> + 'd,
+        tick_freq: Hertz,
+    ) -> Self {
+        into_ref!(pin);
+        configure_output(&*pin, pin.af_num(), output_type);
+
+        Self::new_inner(tim, pin.map_into(), Channel::Ch2, None, tick_freq)
+    }
+
+    /// Create a new one-pulse driver on the output of channel 1, triggered by the rising edges of
+    /// the input of channel 2.
+    pub fn new_ch1_triggered(
+        tim: impl Peripheral<P = T> + 'd,
+        pin: impl Peripheral<P = impl Channel1Pin<T>> + 'd,
+        output_type: OutputType,
+        trigger_pin: impl Peripheral<P = impl Channel2Pin<T>> + 'd,
+        pull: Pull,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, UpdateInterruptHandler<T>> + 'd,
+        tick_freq: Hertz,
+    ) -> Self {
+        into_ref!(pin, trigger_pin);
+        configure_output(&*pin, pin.af_num(), output_type);
+        critical_section::with(|_| {
+            trigger_pin.set_as_af_pull(trigger_pin.af_num(), AFType::Input, pull);
+        });
+
+        let trigger = Some((trigger_pin.map_into(), Channel::Ch2));
+        Self::new_inner(tim, pin.map_into(), Channel::Ch1, trigger, tick_freq)
+    }
+
+    /// Create a new one-pulse driver on the output of channel 2, triggered by the rising edges of
+    /// the input of channel 1.
+    pub fn new_ch2_triggered(
+        tim: impl Peripheral<P = T> + 'd,
+        pin: impl Peripheral<P = impl Channel2Pin<T>> + 'd,
+        output_type: OutputType,
+        trigger_pin: impl Peripheral<P = impl Channel1Pin<T>> + 'd,
+        pull: Pull,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, UpdateInterruptHandler<T>> + 'd,
+        tick_freq: Hertz,
+    ) -> Self {
+        into_ref!(pin, trigger_pin);
+        configure_output(&*pin, pin.af_num(), output_type);
+        critical_section::with(|_| {
+            trigger_pin.set_as_af_pull(trigger_pin.af_num(), AFType::Input, pull);
+        });
+
+        let trigger = Some((trigger_pin.map_into(), Channel::Ch1));
+        Self::new_inner(tim, pin.map_into(), Channel::Ch2, trigger, tick_freq)
+    }
+
+    fn new_inner(
+        tim: impl Peripheral<P = T> + 'd,
+        pin: PeripheralRef<'d, AnyPin>,
+        channel: Channel,
+        trigger: Option<(PeripheralRef<'d, AnyPin>, Channel)>,
+        tick_freq: Hertz,
+    ) -> Self {
+        into_ref!(tim);
+
+        T::enable_and_reset();
+
+        let (trigger_pin, trigger_channel) = match trigger {
+            Some((pin, channel)) => (Some(pin), Some(channel)),
+            None => (None, None),
+        };
+        let mut this = Self {
+            inner: tim,
+            channel,
+            _pin: pin,
+            _trigger_pin: trigger_pin,
+        };
+
+        let psc = T::frequency().0 / tick_freq.0;
+        assert!(psc > 0);
+        let regs = T::regs_gp16();
+        regs.psc().write(|w| w.set_psc(unwrap!(u16::try_from(psc - 1))));
+        regs.cr1().modify(|r| {
+            r.set_urs(vals::Urs::COUNTERONLY);
+            // The counter stops at the update event ending the pulse.
+            r.set_opm(true);
+        });
+        regs.egr().write(|r| r.set_ug(true));
+
+        // The output is inactive until the counter reaches the compare value, then active until
+        // the update event.
+        this.inner.set_output_compare_mode(channel, OutputCompareMode::PwmMode2);
+        this.set_pulse(1, 1);
+        this.inner.enable_channel(channel, true);
+        this.inner.enable_outputs();
+
+        if let Some(trigger_channel) = trigger_channel {
+            this.inner
+                .set_input_ti_selection(trigger_channel, InputTISelection::Normal);
+            this.inner
+                .set_input_capture_mode(trigger_channel, InputCaptureMode::Rising);
+            let source = match trigger_channel {
+                Channel::Ch1 => TriggerSource::Ti1fp1,
+                _ => TriggerSource::Ti2fp2,
+            };
+            this.set_trigger(Some(source));
+        }
+
+        critical_section::with(|_| T::state().pending.store(0, Ordering::Relaxed));
+
+        this.inner.enable_update_interrupt(true);
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        this
+    }
+
+    /// Get the tick frequency of the delay and the width of the pulses.
+    pub fn tick_frequency(&self) -> Hertz {
+        let psc = T::regs_gp16().psc().read().psc();
+        T::frequency() / (psc as u32 + 1)
+    }
+
+    /// Set the delay from the trigger to the start of the pulse, and the width of the pulse, in
+    /// ticks.
+    ///
+    /// The delay must be at least 1 tick, and the sum of the delay and the width at most 2^16.
+    /// The new values apply from the next pulse.
+    pub fn set_pulse(&mut self, delay: u16, width: u16) {
+        assert!(delay > 0 && width > 0);
+        let arr = delay as u32 + width as u32 - 1;
+        assert!(arr <= u16::MAX as u32);
+
+        T::regs_gp16().arr().write(|w| w.set_arr(arr as u16));
+        self.inner.set_compare_value(self.channel, delay);
+    }
+
+    /// Set the polarity of the output: the level during the pulse.
+    pub fn set_polarity(&mut self, polarity: OutputPolarity) {
+        self.inner.set_output_polarity(self.channel, polarity);
+    }
+
+    /// Trigger the pulses with `source`, or only by software with `None`.
+    ///
+    /// An internal trigger starts the pulse when another timer emits its trigger output, see
+    /// [`MasterMode`] and the "TIMx internal trigger connection" table of the reference manual.
+    pub fn set_trigger(&mut self, source: Option<TriggerSource>) {
+        match source {
+            Some(source) => {
+                self.inner.set_trigger_source(source);
+                self.inner.set_slave_mode(SlaveMode::Trigger);
+            }
+            None => self.inner.set_slave_mode(SlaveMode::Disabled),
+        }
+    }
+
+    /// Check if a pulse is in progress, from the trigger to the end of the pulse.
+    pub fn is_running(&self) -> bool {
+        T::regs_gp16().cr1().read().cen()
+    }
+
+    /// Trigger a pulse by software.
+    ///
+    /// Does nothing if a pulse is already in progress.
+    pub fn trigger(&mut self) {
+        critical_section::with(|_| {
+            if !self.is_running() {
+                T::state().pending.store(0, Ordering::Relaxed);
+                self.inner.start();
+            }
+        });
+    }
+
+    /// Wait for the end of the next pulse.
+    ///
+    /// A pulse ended since the last one waited for returns immediately.
+    pub async fn wait_for_pulse(&mut self) {
+        let state = T::state();
+        poll_fn(|cx| {
+            state.cc_wakers[0].register(cx.waker());
+            critical_section::with(|_| {
+                if state.pending.load(Ordering::Relaxed) != 0 {
+                    state.pending.store(0, Ordering::Relaxed);
+                    Poll::Ready(())
+                } else {
+                    Poll::Pending
+                }
+            })
+        })
+        .await
+    }
+
+    /// Trigger a pulse by software, and wait for its end.
+    pub async fn pulse(&mut self) {
+        self.trigger();
+        self.wait_for_pulse().await
+    }
+}
+
+impl<'d, T: CaptureCompare16bitInstance> Drop for OnePulse<'d, T> {
+    fn drop(&mut self) {
+        self.inner.stop();
+        T::regs_gp16().dier().write(|_| {});
+    }
+}
+
+fn configure_output(pin: &impl crate::gpio::sealed::Pin, af: u8, output_type: OutputType) {
+    critical_section::with(|_| {
+        pin.set_low();
+        pin.set_as_af(af, output_type.into());
+        #[cfg(gpio_v2)]
+        pin.set_speed(crate::gpio::Speed::VeryHigh);
+    });
+}
@@ -0,0 +1,127 @@
+//! Periodic timer.
+//!
+//! Any timer, including the basic timers (TIM6, TIM7), ticks at a fixed frequency. The state is
+//! held by the driver, so only the interrupt has to be bound:
+//!
+//! ```rust,ignore
+//! bind_interrupts!(struct Irqs {
+//!     TIM6_DAC => periodic::UpdateInterruptHandler<TIM6>;
+//! });
+//!
+//! let mut timer = PeriodicTimer::new(p.TIM6, Irqs, Hertz::khz(1));
+//! loop {
+//!     timer.tick().await;
+//!     // Runs every millisecond.
+//! }
+//! ```
+
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::sync::atomic::Ordering;
+use core::task::Poll;
+
+use embassy_hal_internal::{into_ref, PeripheralRef};
+
+use super::*;
+use crate::interrupt::typelevel::Interrupt;
+use crate::time::Hertz;
+use crate::Peripheral;
+
+/// Update interrupt handler, counting the ticks.
+pub struct UpdateInterruptHandler<T: Basic16bitInstance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Basic16bitInstance> interrupt::typelevel::Handler<T::Interrupt> for UpdateInterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        let regs = T::regs();
+        if regs.sr().read().uif() {
+            regs.sr().modify(|w| w.set_uif(false));
+            let state = T::update_state();
+            critical_section::with(|_| {
+                let ticks = state.ticks.load(Ordering::Relaxed);
+                state.ticks.store(ticks.saturating_add(1), Ordering::Relaxed);
+            });
+            state.waker.wake();
+        }
+    }
+}
+
+/// Periodic timer.
+pub struct PeriodicTimer<'d, T: Basic16bitInstance> {
+    inner: PeripheralRef<'d, T>,
+}
+
+impl<'d, T: Basic16bitInstance> PeriodicTimer<'d, T> {
+    /// Create a new periodic timer, ticking at `freq`.
+    pub fn new(
+        tim: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, UpdateInterruptHandler<T>> + 'd,
+        freq: Hertz,
+    ) -> Self {
+        into_ref!(tim);
+
+        T::enable_and_reset();
+
+        let mut this = Self { inner: tim };
+
+        this.inner.set_frequency(freq);
+        critical_section::with(|_| T::update_state().ticks.store(0, Ordering::Relaxed));
+        this.inner.enable_update_interrupt(true);
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        this.inner.start();
+
+        this
+    }
+
+    /// Set the tick frequency.
+    ///
+    /// The timer may be running: the current period restarts at the new frequency.
+    pub fn set_frequency(&mut self, freq: Hertz) {
+        self.inner.set_frequency(freq);
+    }
+
+    /// Set the prescaler and the auto-reload values directly.
+    ///
+    /// With values computed at compile time by [`compute_psc_arr`](super::compute_psc_arr), this
+    /// avoids the divisions of [`set_frequency`](Self::set_frequency).
+    pub fn set_psc_arr(&mut self, psc: u16, arr: u16) {
+        self.inner.set_psc_arr(psc, arr);
+    }
+
+    /// Get the tick frequency.
+    pub fn get_frequency(&self) -> Hertz {
+        self.inner.get_frequency()
+    }
+
+    /// Wait for the next tick, and get the number of ticks since the last call.
+    ///
+    /// Ticks elapsed since the last call return immediately, so the ticks are not lost when the
+    /// task is late. They are counted instead, and the count is at least 1.
+    pub async fn tick(&mut self) -> u32 {
+        let state = T::update_state();
+        poll_fn(|cx| {
+            state.waker.register(cx.waker());
+            let ticks = critical_section::with(|_| {
+                let ticks = state.ticks.load(Ordering::Relaxed);
+                state.ticks.store(0, Ordering::Relaxed);
+                ticks
+            });
+            match ticks {
+                0 => Poll::Pending,
+                n => Poll::Ready(n),
+            }
+        })
+        .await
+    }
+}
+
+impl<'d, T: Basic16bitInstance> Drop for PeriodicTimer<'d, T> {
+    fn drop(&mut self) {
+        self.inner.stop();
+        self.inner.enable_update_interrupt(false);
+    }
+}
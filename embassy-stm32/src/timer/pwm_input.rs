@@ -0,0 +1,214 @@
+//! PWM input driver.
+//!
+//! The timer measures the period and the width of the pulses of a PWM signal on the input of
+//! channel 1 or 2. The two channels capture the input, one on its rising edges and the other on
+//! its falling edges, and each rising edge resets the counter:
+//!
+//! ```rust,ignore
+//! bind_interrupts!(struct Irqs {
+//!     TIM2 => pwm_input::CaptureCompareInterruptHandler<TIM2>;
+//! });
+//!
+//! let mut pwm = PwmInput::new_ch1(p.TIM2, p.PA0, Pull::None, Irqs, Hertz::mhz(1));
+//! loop {
+//!     let cycle = pwm.wait_for_cycle().await;
+//!     info!("period: {} us, duty cycle: {}", cycle.period_ticks, cycle.duty_cycle());
+//! }
+//! ```
+
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::sync::atomic::Ordering;
+use core::task::Poll;
+
+use embassy_hal_internal::{into_ref, PeripheralRef};
+
+use super::*;
+use crate::gpio::sealed::{AFType, Pin as _};
+use crate::gpio::{AnyPin, Pull};
+use crate::interrupt::typelevel::Interrupt;
+use crate::time::Hertz;
+use crate::Peripheral;
+
+/// Capture/compare interrupt handler, reading the period and the width of each cycle.
+pub struct CaptureCompareInterruptHandler<T: CaptureCompare16bitInstance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: CaptureCompare16bitInstance> interrupt::typelevel::Handler<T::CaptureCompareInterrupt>
+    for CaptureCompareInterruptHandler<T>
+{
+    unsafe fn on_interrupt() {
+        critical_section::with(|_| {
+            let regs = T::regs_gp16();
+            let state = T::state();
+            // The period channel is the one with the interrupt enabled.
+            let dier = regs.dier().read();
+            let (period, width) = if dier.ccie(0) { (0, 1) } else { (1, 0) };
+
+            if regs.sr().read().ccif(period) {
+                // Reading the captures clears the flags.
+                let period_ticks = regs.ccr(period).read().ccr();
+                let width_ticks = regs.ccr(width).read().ccr();
+                state.captures[0].store(period_ticks as u32, Ordering::Relaxed);
+                state.captures[1].store(width_ticks as u32, Ordering::Relaxed);
+                state.pending.store(1, Ordering::Relaxed);
+                state.cc_wakers[0].wake();
+            }
+        });
+    }
+}
+
+/// Cycle of a PWM signal, measured in timer ticks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PwmCycle {
+    /// Period of the cycle.
+    pub period_ticks: u16,
+    /// Width of the high pulse of the cycle.
+    pub width_ticks: u16,
+}
+
+impl PwmCycle {
+    /// Get the duty cycle, from 0.0 to 1.0.
+    pub fn duty_cycle(&self) -> f32 {
+        if self.period_ticks == 0 {
+            return 0.0;
+        }
+        self.width_ticks as f32 / self.period_ticks as f32
+    }
+}
+
+/// PWM input driver.
+pub struct PwmInput<'d, T: CaptureCompare16bitInstance> {
+    inner: PeripheralRef<'d, T>,
+    _pin: PeripheralRef<'d, AnyPin>,
+}
+
+impl<'d, T: CaptureCompare16bitInstance> PwmInput<'d, T> {
+    /// Create a new PWM input driver measuring the input of channel 1, counting at `tick_freq`.
+    pub fn new_ch1(
+        tim: impl Peripheral<P = T> + 'd,
+        pin: impl Peripheral<P = impl Channel1Pin<T>> + 'd,
+        pull: Pull,
+        _irq: impl interrupt::typelevel::Binding<T::CaptureCompareInterrupt, CaptureCompareInterruptHandler<T>> + 'd,
+        tick_freq: Hertz,
+    ) -> Self {
+        into_ref!(pin);
+        critical_section::with(|_| {
+            pin.set_as_af_pull(pin.af_num(), AFType::Input, pull);
+        });
+
+        Self::new_inner(tim, pin.map_into(), Channel::Ch1, Channel::Ch2, tick_freq)
+    }
+
+    /// Create a new PWM input driver measuring the input of channel 2, counting at `tick_freq`.
+    pub fn new_ch2(
+        tim: impl Peripheral<P = T> + 'd,
+        pin: impl Peripheral<P = impl Channel2Pin<T>> + 'd,
+        pull: Pull,
+        _irq: impl interrupt::typelevel::Binding<T::CaptureCompareInterrupt, CaptureCompareInterruptHandler<T>> + 'd,
+        tick_freq: Hertz,
+    ) -> Self {
+        into_ref!(pin);
+        critical_section::with(|_| {
+            pin.set_as_af_pull(pin.af_num(), AFType::Input, pull);
+        });
+
+        Self::new_inner(tim, pin.map_into(), Channel::Ch2, Channel::Ch1, tick_freq)
+    }
+
+    fn new_inner(
+        tim: impl Peripheral<P = T> + 'd,
+        pin: PeripheralRef<'d, AnyPin>,
+        period: Channel,
+        width: Channel,
+        tick_freq: Hertz,
+    ) -> Self {
+        into_ref!(tim);
+
+        T::enable_and_reset();
+
+        let mut this = Self { inner: tim, _pin: pin };
+
+        let psc = T::frequency().0 / tick_freq.0;
+        assert!(psc > 0);
+        let regs = T::regs_gp16();
+        regs.psc().write(|w| w.set_psc(unwrap!(u16::try_from(psc - 1))));
+        regs.arr().write(|w| w.set_arr(u16::MAX));
+        regs.cr1().modify(|r| r.set_urs(vals::Urs::COUNTERONLY));
+        regs.egr().write(|r| r.set_ug(true));
+
+        // The period channel captures the rising edges of its input, and the width channel the
+        // falling edges of the same input.
+        this.inner.set_input_ti_selection(period, InputTISelection::Normal);
+        this.inner.set_input_capture_mode(period, InputCaptureMode::Rising);
+        this.inner.set_input_ti_selection(width, InputTISelection::Alternate);
+        this.inner.set_input_capture_mode(width, InputCaptureMode::Falling);
+
+        let trigger = match period {
+            Channel::Ch1 => TriggerSource::Ti1fp1,
+            _ => TriggerSource::Ti2fp2,
+        };
+        this.inner.set_trigger_source(trigger);
+        this.inner.set_slave_mode(SlaveMode::Reset);
+
+        critical_section::with(|_| T::state().pending.store(0, Ordering::Relaxed));
+
+        this.inner.enable_channel(period, true);
+        this.inner.enable_channel(width, true);
+        this.inner.enable_input_interrupt(period, true);
+
+        T::CaptureCompareInterrupt::unpend();
+        unsafe { T::CaptureCompareInterrupt::enable() };
+
+        this.inner.start();
+
+        this
+    }
+
+    /// Get the tick frequency of the measurements.
+    pub fn tick_frequency(&self) -> Hertz {
+        let psc = T::regs_gp16().psc().read().psc();
+        T::frequency() / (psc as u32 + 1)
+    }
+
+    /// Set the input filter, as the sampling frequency and the number of samples encoded in the
+    /// ICF field of the reference manual, from 0 (no filter) to 15.
+    pub fn set_filter(&mut self, filter: u8) {
+        assert!(filter < 16);
+        let icf = vals::Icf::from_bits(filter);
+        self.inner.set_input_capture_filter(Channel::Ch1, icf);
+        self.inner.set_input_capture_filter(Channel::Ch2, icf);
+    }
+
+    /// Wait for the end of the next cycle, and get its period and width.
+    ///
+    /// A cycle ended since the last one read is returned immediately, and only the last of
+    /// several cycles is kept. The period must be shorter than 2^16 ticks.
+    pub async fn wait_for_cycle(&mut self) -> PwmCycle {
+        let state = T::state();
+        poll_fn(|cx| {
+            state.cc_wakers[0].register(cx.waker());
+            critical_section::with(|_| {
+                if state.pending.load(Ordering::Relaxed) != 0 {
+                    state.pending.store(0, Ordering::Relaxed);
+                    Poll::Ready(PwmCycle {
+                        period_ticks: state.captures[0].load(Ordering::Relaxed) as u16,
+                        width_ticks: state.captures[1].load(Ordering::Relaxed) as u16,
+                    })
+                } else {
+                    Poll::Pending
+                }
+            })
+        })
+        .await
+    }
+}
+
+impl<'d, T: CaptureCompare16bitInstance> Drop for PwmInput<'d, T> {
+    fn drop(&mut self) {
+        self.inner.stop();
+        T::regs_gp16().dier().write(|_| {});
+    }
+}
@@ -1,12 +1,33 @@
 //! Quadrature decoder using a timer.
+//!
+//! The timer counts the edges of the two encoder signals, up or down depending on their phase.
+//! The 16-bit counter is extended to a signed 64-bit position by counting its wraparounds in the
+//! update interrupt:
+//!
+//! ```rust,ignore
+//! bind_interrupts!(struct Irqs {
+//!     TIM3 => qei::UpdateInterruptHandler<TIM3>, qei::CaptureCompareInterruptHandler<TIM3>;
+//! });
+//!
+//! let mut qei = Qei::new(p.TIM3, QeiPin::new_ch1(p.PA6), QeiPin::new_ch2(p.PA7), Irqs);
+//! loop {
+//!     // One revolution of a 1024 lines encoder, 4 counts per line.
+//!     let position = qei.wait_for_count(4096).await;
+//!     info!("position: {}", position);
+//! }
+//! ```
 
+use core::future::poll_fn;
 use core::marker::PhantomData;
+use core::sync::atomic::Ordering;
+use core::task::Poll;
 
 use embassy_hal_internal::{into_ref, PeripheralRef};
 
 use super::*;
 use crate::gpio::sealed::AFType;
 use crate::gpio::AnyPin;
+use crate::interrupt::typelevel::Interrupt;
 use crate::Peripheral;
 
 /// Counting direction
@@ -52,14 +73,95 @@ macro_rules! channel_impl {
 channel_impl!(new_ch1, Ch1, Channel1Pin);
 channel_impl!(new_ch2, Ch2, Channel2Pin);
 
+/// Update interrupt handler, counting the wraparounds of the counter.
+pub struct UpdateInterruptHandler<T: CaptureCompare16bitInstance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: CaptureCompare16bitInstance> interrupt::typelevel::Handler<T::Interrupt> for UpdateInterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        on_interrupt::<T>();
+    }
+}
+
+/// Capture/compare interrupt handler, waking [`Qei::wait_for_count`].
+pub struct CaptureCompareInterruptHandler<T: CaptureCompare16bitInstance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: CaptureCompare16bitInstance> interrupt::typelevel::Handler<T::CaptureCompareInterrupt>
+    for CaptureCompareInterruptHandler<T>
+{
+    unsafe fn on_interrupt() {
+        on_interrupt::<T>();
+    }
+}
+
+// Channels 1 and 2 capture the encoder signals, and the compare of channel 3 signals the target
+// count of `wait_for_count`.
+const TARGET_CHANNEL: Channel = Channel::Ch3;
+
+fn on_interrupt<T: CaptureCompare16bitInstance>() {
+    critical_section::with(|_| {
+        let regs = T::regs_gp16();
+        let state = T::state();
+        let sr = regs.sr().read();
+        let n = TARGET_CHANNEL.index();
+
+        if sr.uif() {
+            regs.sr().modify(|w| w.set_uif(false));
+            // The counter is close to 0 after counting up past the top, and close to the top after
+            // counting down past 0.
+            let wraparounds = state.overflows.load(Ordering::Relaxed) as i32;
+            let wraparounds = match regs.cnt().read().cnt() < 0x8000 {
+                true => wraparounds.wrapping_add(1),
+                false => wraparounds.wrapping_sub(1),
+            };
+            state.overflows.store(wraparounds as u32, Ordering::Relaxed);
+            state.cc_wakers[n].wake();
+        }
+
+        if sr.ccif(n) && regs.dier().read().ccie(n) {
+            regs.dier().modify(|w| w.set_ccie(n, false));
+            state.cc_wakers[n].wake();
+        }
+    });
+}
+
+/// Extend `count` to a signed position, with the wraparounds counted so far and the pending
+/// update flag read after `count`.
+fn extend(wraparounds: i32, uif: bool, count: u16) -> i64 {
+    let wraparounds = match (uif, count < 0x8000) {
+        (false, _) => wraparounds,
+        (true, true) => wraparounds.wrapping_add(1),
+        (true, false) => wraparounds.wrapping_sub(1),
+    };
+    ((wraparounds as i64) << 16) | count as i64
+}
+
+/// Read the position, in a critical section.
+fn position<T: CaptureCompare16bitInstance>() -> i64 {
+    let regs = T::regs_gp16();
+    let count = regs.cnt().read().cnt();
+    let uif = regs.sr().read().uif();
+    extend(T::state().overflows.load(Ordering::Relaxed) as i32, uif, count)
+}
+
 /// Quadrature decoder driver.
-pub struct Qei<'d, T> {
-    _inner: PeripheralRef<'d, T>,
+pub struct Qei<'d, T: CaptureCompare16bitInstance> {
+    inner: PeripheralRef<'d, T>,
 }
 
 impl<'d, T: CaptureCompare16bitInstance> Qei<'d, T> {
     /// Create a new quadrature decoder driver.
-    pub fn new(tim: impl Peripheral<P = T> + 'd, _ch1: QeiPin<'d, T, Ch1>, _ch2: QeiPin<'d, T, Ch2>) -> Self {
+    pub fn new(
+        tim: impl Peripheral<P = T> + 'd,
+        _ch1: QeiPin<'d, T, Ch1>,
+        _ch2: QeiPin<'d, T, Ch2>,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, UpdateInterruptHandler<T>>
+            + interrupt::typelevel::Binding<T::CaptureCompareInterrupt, CaptureCompareInterruptHandler<T>>
+            + 'd,
+    ) -> Self {
         Self::new_inner(tim)
     }
 
@@ -68,6 +170,8 @@ impl<'d, T: CaptureCompare16bitInstance> Qei<'d, T> {
 
         T::enable_and_reset();
 
+        critical_section::with(|_| T::state().overflows.store(0, Ordering::Relaxed));
+
         // Configure TxC1 and TxC2 as captures
         T::regs_gp16().ccmr_input(0).modify(|w| {
             w.set_ccs(0, vals::CcmrInputCcs::TI4);
@@ -88,9 +192,18 @@ impl<'d, T: CaptureCompare16bitInstance> Qei<'d, T> {
         });
 
         T::regs_gp16().arr().modify(|w| w.set_arr(u16::MAX));
+
+        let mut this = Self { inner: tim };
+        this.inner.enable_update_interrupt(true);
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+        T::CaptureCompareInterrupt::unpend();
+        unsafe { T::CaptureCompareInterrupt::enable() };
+
         T::regs_gp16().cr1().modify(|w| w.set_cen(true));
 
-        Self { _inner: tim }
+        this
     }
 
     /// Get direction.
@@ -105,4 +218,71 @@ impl<'d, T: CaptureCompare16bitInstance> Qei<'d, T> {
     pub fn count(&self) -> u16 {
         T::regs_gp16().cnt().read().cnt()
     }
+
+    /// Get the position, the count extended with the wraparounds of the counter.
+    ///
+    /// The position starts at 0 and goes negative when the encoder turns backwards.
+    pub fn position(&self) -> i64 {
+        critical_section::with(|_| position::<T>())
+    }
+
+    /// Wait until the position moved by `delta` counts from the current one, and get the new
+    /// position.
+    ///
+    /// The position is reached when it crosses `position() + delta`, up for a positive `delta` and
+    /// down for a negative one: the encoder may turn back and forth before.
+    pub async fn wait_for_count(&mut self, delta: i64) -> i64 {
+        let start = self.position();
+        if delta == 0 {
+            return start;
+        }
+        let target = start.wrapping_add(delta);
+
+        let regs = T::regs_gp16();
+        let n = TARGET_CHANNEL.index();
+        regs.ccr(n).write(|w| w.set_ccr(target as u16));
+
+        let state = T::state();
+        let position = poll_fn(|cx| {
+            state.cc_wakers[n].register(cx.waker());
+            critical_section::with(|_| {
+                // A compare from now on raises the interrupt again.
+                self.inner.clear_input_interrupt(TARGET_CHANNEL);
+                let position = position::<T>();
+                if (delta > 0 && position >= target) || (delta < 0 && position <= target) {
+                    Poll::Ready(position)
+                } else {
+                    self.inner.enable_input_interrupt(TARGET_CHANNEL, true);
+                    Poll::Pending
+                }
+            })
+        })
+        .await;
+
+        self.inner.enable_input_interrupt(TARGET_CHANNEL, false);
+        position
+    }
+}
+
+impl<'d, T: CaptureCompare16bitInstance> Drop for Qei<'d, T> {
+    fn drop(&mut self) {
+        self.inner.stop();
+        T::regs_gp16().dier().write(|_| {});
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::extend;
+
+    #[test]
+    fn extend_signed_position() {
+        assert_eq!(extend(0, false, 0x1234), 0x1234);
+        assert_eq!(extend(-1, false, 0xFFFF), -1);
+        assert_eq!(extend(2, false, 0x0010), 0x2_0010);
+        // Pending wraparound counting up.
+        assert_eq!(extend(2, true, 0x0010), 0x3_0010);
+        // Pending wraparound counting down.
+        assert_eq!(extend(0, true, 0xFFF0), -0x10);
+    }
 }
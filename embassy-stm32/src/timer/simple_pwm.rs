@@ -156,8 +156,57 @@ impl<'d, T: CaptureCompare16bitInstance> SimplePwm<'d, T> {
         self.inner.set_output_compare_mode(channel, mode);
     }
 
+    /// Configure the event sent to slave timers on the trigger output (TRGO).
+    ///
+    /// Use [`MasterMode::Enable`] together with [`set_master_slave_mode`](Self::set_master_slave_mode)
+    /// to start all slave timers on this timer's enable.
+    pub fn set_master_mode(&mut self, mode: MasterMode) {
+        self.inner.set_master_mode(mode);
+    }
+
+    /// Enable/disable master/slave mode.
+    ///
+    /// When enabled, the trigger output of this timer is delayed so it starts in lockstep with its slaves.
+    pub fn set_master_slave_mode(&mut self, enable: bool) {
+        self.inner.set_master_slave_mode(enable);
+    }
+
+    /// Make this timer a slave of the given trigger input.
+    ///
+    /// In [`SlaveMode::Trigger`] the counter is stopped and reset, so that it starts
+    /// counting on the next trigger, e.g. when the master timer is started.
+    pub fn set_slave_mode(&mut self, mode: SlaveMode, source: TriggerSource) {
+        if mode == SlaveMode::Trigger {
+            self.inner.stop();
+            self.inner.reset();
+        }
+        self.inner.set_trigger_source(source);
+        self.inner.set_slave_mode(mode);
+    }
+
+    /// Start the timer.
+    pub fn start(&mut self) {
+        self.inner.start();
+    }
+
+    /// Stop the timer.
+    pub fn stop(&mut self) {
+        self.inner.stop();
+    }
+
+    /// Reset the counter value to 0.
+    pub fn reset(&mut self) {
+        self.inner.reset();
+    }
+
     /// Generate a sequence of PWM waveform
     ///
+    /// One duty value is written to the channel on each update event, so WS2812 LED strips or
+    /// arbitrary waveforms are generated without the CPU. Sequences longer than 0xFFFF values are
+    /// streamed in chunks: with a double-buffered transfer on DMA, and with sequential transfers of
+    /// at most 0xFFFF values on BDMA and GPDMA. The switch from one transfer to the next happens in
+    /// the task, so an update event occurring meanwhile repeats the current value.
+    ///
     /// Note:  
     /// you will need to provide corresponding TIMx_UP DMA channel to use this method.
     pub async fn waveform_up(
@@ -185,6 +234,173 @@ impl<'d, T: CaptureCompare16bitInstance> SimplePwm<'d, T> {
             self.enable(channel);
         }
 
+        let dst = T::regs_gp16().ccr(channel.index()).as_ptr() as *mut u16;
+        if duty.len() > 0xFFFF {
+            waveform_giant(&mut dma, req, dst, duty).await;
+        } else {
+            unsafe {
+                #[cfg(not(any(bdma, gpdma)))]
+                use crate::dma::{Burst, FifoThreshold};
+                use crate::dma::{Transfer, TransferOptions};
+
+                let dma_transfer_option = TransferOptions {
+                    #[cfg(not(any(bdma, gpdma)))]
+                    fifo_threshold: Some(FifoThreshold::Full),
+                    #[cfg(not(any(bdma, gpdma)))]
+                    mburst: Burst::Incr8,
+                    ..Default::default()
+                };
+
+                Transfer::new_write(&mut dma, req, duty, dst, dma_transfer_option).await
+            };
+        }
+
+        // restore output compare state
+        if !original_enable_state {
+            self.disable(channel);
+        }
+
+        self.set_duty(channel, original_duty_state);
+
+        // Since DMA is closed before timer update event trigger DMA is turn off,
+        // this can almost always trigger a DMA FIFO error.
+        //
+        // optional TODO:
+        // clean FEIF after disable UDE
+        if !original_update_dma_state {
+            self.inner.enable_update_dma(false);
+        }
+    }
+}
+
+/// Stream `duty` to `dst` with sequential transfers of at most 0xFFFF values.
+#[cfg(not(dma))]
+async fn waveform_giant<C: crate::dma::Channel>(
+    dma: &mut PeripheralRef<'_, C>,
+    request: crate::dma::Request,
+    dst: *mut u16,
+    duty: &[u16],
+) {
+    use crate::dma::{Transfer, TransferOptions};
+
+    for chunk in duty.chunks(0xFFFF) {
+        unsafe { Transfer::new_write(&mut **dma, request, chunk, dst, TransferOptions::default()) }.await;
+    }
+}
+
+/// Stream `duty` to `dst` in fixed-size chunks with a double-buffered transfer, reloading the
+/// buffer not used by the DMA with the next chunk each time the DMA switches buffers.
+///
+/// Once the last whole chunk is being transferred, the double-buffered transfer is stopped, and the
+/// rest of that chunk and the shorter tail are sent with a single transfer, which stops by itself.
+#[cfg(dma)]
+async fn waveform_giant<C: crate::dma::Channel>(
+    dma: &mut PeripheralRef<'_, C>,
+    request: crate::dma::Request,
+    dst: *mut u16,
+    duty: &[u16],
+) {
+    use core::future::poll_fn;
+    use core::task::Poll;
+
+    use crate::dma::{DoubleBuffered, FifoThreshold, Transfer, TransferOptions};
+
+    const CHUNK: usize = 0x8000;
+
+    // More than 0xFFFF values, so at least 2 whole chunks.
+    let chunks = duty.len() / CHUNK;
+    // The DMA only reads the buffers of a write transfer.
+    let base = duty.as_ptr() as *mut u16;
+
+    let options = TransferOptions {
+        fifo_threshold: Some(FifoThreshold::Full),
+        ..Default::default()
+    };
+    let mut transfer =
+        unsafe { DoubleBuffered::new_write(&mut **dma, request, base, base.add(CHUNK), dst, CHUNK, options) };
+
+    let mut next_chunk = 2;
+    let mut buffer0_last_accessible = false;
+    poll_fn(|cx| {
+        transfer.set_waker(cx.waker());
+
+        let buffer0_currently_accessible = transfer.is_buffer0_accessible();
+
+        // check if the accessible buffer changed since last poll
+        if buffer0_last_accessible == buffer0_currently_accessible {
+            return Poll::Pending;
+        }
+        buffer0_last_accessible = buffer0_currently_accessible;
+
+        if next_chunk == chunks {
+            // The last whole chunk is being transferred.
+            return Poll::Ready(());
+        }
+
+        let chunk = unsafe { base.add(next_chunk * CHUNK) };
+        if buffer0_currently_accessible {
+            unsafe { transfer.set_buffer0(chunk) }
+        } else {
+            unsafe { transfer.set_buffer1(chunk) }
+        }
+        next_chunk += 1;
+        Poll::Pending
+    })
+    .await;
+
+    transfer.request_stop();
+    while transfer.is_running() {}
+    let sent = chunks * CHUNK - transfer.get_remaining_transfers() as usize;
+    drop(transfer);
+
+    // At most 2 * CHUNK - 1 values.
+    unsafe { Transfer::new_write(&mut **dma, request, &duty[sent..], dst, options) }.await;
+}
+
+impl<'d, T: CaptureCompare16bitInstance> SimplePwm<'d, T> {
+    /// Stream a buffer of duty values to a channel, one value per timer update event.
+    ///
+    /// The timer frequency is set to `update_rate` for the duration of the stream, so the PWM period
+    /// equals the sample period and every duty value must be at most [`get_max_duty`](Self::get_max_duty)
+    /// at that rate. The original frequency and duty are restored once the stream is done.
+    ///
+    /// In `circular` mode the buffer is replayed forever and the returned future never completes: drop it
+    /// to stop the stream. The timer is then left running at `update_rate`.
+    ///
+    /// Note:
+    /// you will need to provide corresponding TIMx_UP DMA channel to use this method.
+    pub async fn set_duty_stream(
+        &mut self,
+        dma: impl Peripheral<P = impl super::UpDma<T>>,
+        channel: Channel,
+        duty: &[u16],
+        update_rate: Hertz,
+        circular: bool,
+    ) {
+        #[cfg(gpdma)]
+        assert!(!circular, "circular duty streams are not supported on GPDMA");
+
+        into_ref!(dma);
+
+        #[allow(clippy::let_unit_value)] // eg. stm32f334
+        let req = dma.request();
+
+        let original_frequency = self.inner.get_frequency();
+        let original_duty_state = self.get_duty(channel);
+        let original_enable_state = self.is_enabled(channel);
+        let original_update_dma_state = self.inner.get_update_dma_state();
+
+        self.set_frequency(update_rate);
+        assert!(duty.iter().all(|v| *v <= self.get_max_duty()));
+
+        if !original_update_dma_state {
+            self.inner.enable_update_dma(true);
+        }
+
+        if !original_enable_state {
+            self.enable(channel);
+        }
+
         unsafe {
             #[cfg(not(any(bdma, gpdma)))]
             use crate::dma::{Burst, FifoThreshold};
@@ -195,6 +411,8 @@ impl<'d, T: CaptureCompare16bitInstance> SimplePwm<'d, T> {
                 fifo_threshold: Some(FifoThreshold::Full),
                 #[cfg(not(any(bdma, gpdma)))]
                 mburst: Burst::Incr8,
+                #[cfg(not(gpdma))]
+                circular,
                 ..Default::default()
             };
 
@@ -213,13 +431,9 @@ impl<'d, T: CaptureCompare16bitInstance> SimplePwm<'d, T> {
             self.disable(channel);
         }
 
+        self.inner.set_frequency(original_frequency);
         self.set_duty(channel, original_duty_state);
 
-        // Since DMA is closed before timer update event trigger DMA is turn off,
-        // this can almost always trigger a DMA FIFO error.
-        //
-        // optional TODO:
-        // clean FEIF after disable UDE
         if !original_update_dma_state {
             self.inner.enable_update_dma(false);
         }
@@ -0,0 +1,101 @@
+//! Synchronized start of several timers.
+//!
+//! The master timer sends its counter enable on its trigger output (TRGO), and each slave timer
+//! is put in trigger mode on the internal trigger (ITRx) connected to it. All the counters are
+//! stopped and reset, then enabling the master starts the slaves on the same clock, which keeps
+//! the PWM phases of the timers aligned, e.g. for interleaved power stages:
+//!
+//! ```rust,ignore
+//! let mut master = SimplePwm::new(p.TIM1, Some(ch1), None, None, None, khz(100), Default::default());
+//! let mut slave = SimplePwm::new(p.TIM3, Some(ch1), None, None, None, khz(100), Default::default());
+//! master.set_duty(Channel::Ch1, master.get_max_duty() / 2);
+//! slave.set_duty(Channel::Ch1, slave.get_max_duty() / 2);
+//! // On STM32F4, TIM3 is triggered by TIM1 through ITR0.
+//! start_synchronized(&mut master, &mut [Slave::new(&mut slave, TriggerSource::Itr0)]);
+//! ```
+
+use super::complementary_pwm::ComplementaryPwm;
+use super::simple_pwm::SimplePwm;
+use super::*;
+
+pub(crate) mod sealed {
+    use crate::timer::TriggerSource;
+
+    pub trait SyncTimer {
+        /// Stop and reset the counter, and send its enable on the trigger output.
+        fn arm_master(&mut self);
+        /// Stop and reset the counter, and start it on the next rising edge of `trigger`.
+        fn arm_slave(&mut self, trigger: TriggerSource);
+        /// Start the counter.
+        fn start_counter(&mut self);
+    }
+}
+
+/// Timer driver that can be started in sync with other timers, see [`start_synchronized`].
+pub trait SyncTimer: sealed::SyncTimer {}
+
+/// Slave timer of [`start_synchronized`].
+pub struct Slave<'a> {
+    timer: &'a mut dyn SyncTimer,
+    trigger: TriggerSource,
+}
+
+impl<'a> Slave<'a> {
+    /// Start `timer` on `trigger`, the internal trigger connected to the trigger output of the
+    /// master.
+    ///
+    /// See the "TIMx internal trigger connection" table of the reference manual.
+    pub fn new(timer: &'a mut dyn SyncTimer, trigger: TriggerSource) -> Self {
+        Self { timer, trigger }
+    }
+}
+
+/// Start `master` and `slaves` on the same clock.
+///
+/// The counters are reset to 0 first. The slaves are left in trigger mode, so they can only be
+/// restarted along with the master, by calling this function again.
+pub fn start_synchronized(master: &mut dyn SyncTimer, slaves: &mut [Slave<'_>]) {
+    master.arm_master();
+    for slave in slaves.iter_mut() {
+        slave.timer.arm_slave(slave.trigger);
+    }
+    master.start_counter();
+}
+
+impl<'d, T: CaptureCompare16bitInstance> sealed::SyncTimer for SimplePwm<'d, T> {
+    fn arm_master(&mut self) {
+        self.stop();
+        self.reset();
+        self.set_master_mode(MasterMode::Enable);
+        self.set_master_slave_mode(true);
+    }
+
+    fn arm_slave(&mut self, trigger: TriggerSource) {
+        self.set_slave_mode(SlaveMode::Trigger, trigger);
+    }
+
+    fn start_counter(&mut self) {
+        self.start();
+    }
+}
+
+impl<'d, T: CaptureCompare16bitInstance> SyncTimer for SimplePwm<'d, T> {}
+
+impl<'d, T: ComplementaryCaptureCompare16bitInstance> sealed::SyncTimer for ComplementaryPwm<'d, T> {
+    fn arm_master(&mut self) {
+        self.stop();
+        self.reset();
+        self.set_master_mode(MasterMode::Enable);
+        self.set_master_slave_mode(true);
+    }
+
+    fn arm_slave(&mut self, trigger: TriggerSource) {
+        self.set_slave_mode(SlaveMode::Trigger, trigger);
+    }
+
+    fn start_counter(&mut self) {
+        self.start();
+    }
+}
+
+impl<'d, T: ComplementaryCaptureCompare16bitInstance> SyncTimer for ComplementaryPwm<'d, T> {}
@@ -0,0 +1,174 @@
+//! Trigger interconnect between the peripherals.
+//!
+//! The timers and EXTI lines start the DAC and ADC conversions through a fixed interconnect
+//! matrix, where each destination numbers its sources differently: TIM6_TRGO is trigger 0 of the
+//! DAC, but external event 13 of the regular ADC conversions and 14 of the injected ones. This
+//! module holds the matrix of the family as a graph, from typed [`Source`]s to typed
+//! [`Destination`]s, so a route is checked and selected in one place:
+//!
+//! ```rust,ignore
+//! let source = Source::Tim6Trgo;
+//! source.check(&[Destination::Dac, Destination::AdcInjected])?;
+//! dac.set_trigger(source.dac()?);
+//! adc.configure_injected(&mut sequence, source.adc_injected(TriggerEdge::Rising)?);
+//! ```
+//!
+//! The timer must output its TRGO on its update event, with `MasterMode::Update`.
+
+use crate::adc::{InjectedTrigger, TriggerEdge};
+use crate::dac::TriggerSel;
+
+/// Trigger source.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Source {
+    /// TIM1 TRGO.
+    Tim1Trgo,
+    /// TIM1 TRGO2.
+    Tim1Trgo2,
+    /// TIM2 TRGO.
+    Tim2Trgo,
+    /// TIM3 TRGO.
+    Tim3Trgo,
+    /// TIM4 TRGO.
+    Tim4Trgo,
+    /// TIM5 TRGO.
+    Tim5Trgo,
+    /// TIM6 TRGO.
+    Tim6Trgo,
+    /// TIM7 TRGO.
+    #[cfg(not(any(stm32l45x, stm32l46x)))]
+    Tim7Trgo,
+    /// TIM8 TRGO.
+    Tim8Trgo,
+    /// TIM8 TRGO2.
+    Tim8Trgo2,
+    /// TIM15 TRGO.
+    Tim15Trgo,
+    /// EXTI line 9.
+    Exti9,
+    /// EXTI line 11.
+    Exti11,
+    /// EXTI line 15.
+    Exti15,
+}
+
+/// Trigger destination.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Destination {
+    /// DAC channels, `TSEL` in the reference manual.
+    Dac,
+    /// ADC regular conversions, `EXTSEL` in the reference manual.
+    AdcRegular,
+    /// ADC injected conversions, `JEXTSEL` in the reference manual.
+    AdcInjected,
+}
+
+/// Routing error.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The source is not connected to the destination.
+    NotRouted {
+        /// Trigger source.
+        source: Source,
+        /// Trigger destination.
+        destination: Destination,
+    },
+}
+
+/// Selection of a source by a destination.
+#[derive(Copy, Clone)]
+enum Selection {
+    Dac(TriggerSel),
+    AdcRegular(u8),
+    AdcInjected(u8),
+}
+
+impl Selection {
+    fn destination(self) -> Destination {
+        match self {
+            Self::Dac(_) => Destination::Dac,
+            Self::AdcRegular(_) => Destination::AdcRegular,
+            Self::AdcInjected(_) => Destination::AdcInjected,
+        }
+    }
+}
+
+/// Edges of the interconnect matrix, from RM0351 and RM0394.
+const ROUTES: &[(Source, Selection)] = &[
+    (Source::Tim1Trgo, Selection::AdcRegular(9)),
+    (Source::Tim1Trgo, Selection::AdcInjected(0)),
+    (Source::Tim1Trgo2, Selection::AdcRegular(10)),
+    (Source::Tim1Trgo2, Selection::AdcInjected(8)),
+    (Source::Tim2Trgo, Selection::Dac(TriggerSel::Tim2)),
+    (Source::Tim2Trgo, Selection::AdcRegular(11)),
+    (Source::Tim2Trgo, Selection::AdcInjected(2)),
+    (Source::Tim3Trgo, Selection::AdcRegular(4)),
+    (Source::Tim3Trgo, Selection::AdcInjected(12)),
+    (Source::Tim4Trgo, Selection::Dac(TriggerSel::Tim4)),
+    (Source::Tim4Trgo, Selection::AdcRegular(12)),
+    (Source::Tim4Trgo, Selection::AdcInjected(5)),
+    (Source::Tim5Trgo, Selection::Dac(TriggerSel::Tim5)),
+    (Source::Tim6Trgo, Selection::Dac(TriggerSel::Tim6)),
+    (Source::Tim6Trgo, Selection::AdcRegular(13)),
+    (Source::Tim6Trgo, Selection::AdcInjected(14)),
+    #[cfg(not(any(stm32l45x, stm32l46x)))]
+    (Source::Tim7Trgo, Selection::Dac(TriggerSel::Tim7)),
+    (Source::Tim8Trgo, Selection::Dac(TriggerSel::Tim8)),
+    (Source::Tim8Trgo, Selection::AdcRegular(7)),
+    (Source::Tim8Trgo, Selection::AdcInjected(9)),
+    (Source::Tim8Trgo2, Selection::AdcRegular(8)),
+    (Source::Tim8Trgo2, Selection::AdcInjected(10)),
+    (Source::Tim15Trgo, Selection::AdcRegular(14)),
+    (Source::Tim15Trgo, Selection::AdcInjected(15)),
+    (Source::Exti9, Selection::Dac(TriggerSel::Exti9)),
+    (Source::Exti11, Selection::AdcRegular(6)),
+    (Source::Exti15, Selection::AdcInjected(6)),
+];
+
+impl Source {
+    fn select(self, destination: Destination) -> Result<Selection, Error> {
+        ROUTES
+            .iter()
+            .find(|(source, selection)| *source == self && selection.destination() == destination)
+            .map(|(_, selection)| *selection)
+            .ok_or(Error::NotRouted {
+                source: self,
+                destination,
+            })
+    }
+
+    /// Check that this source is connected to all the `destinations`.
+    pub fn check(self, destinations: &[Destination]) -> Result<(), Error> {
+        for destination in destinations {
+            self.select(*destination)?;
+        }
+        Ok(())
+    }
+
+    /// Number of this source in the selection register of `destination`.
+    pub fn selection(self, destination: Destination) -> Result<u8, Error> {
+        Ok(match self.select(destination)? {
+            Selection::Dac(sel) => sel as u8,
+            Selection::AdcRegular(sel) | Selection::AdcInjected(sel) => sel,
+        })
+    }
+
+    /// DAC trigger of this source, for `DacChannel::set_trigger`.
+    pub fn dac(self) -> Result<TriggerSel, Error> {
+        match self.select(Destination::Dac)? {
+            Selection::Dac(sel) => Ok(sel),
+            _ => unreachable!(),
+        }
+    }
+
+    /// ADC injected trigger of this source, on `edge`, for `Adc::configure_injected`.
+    pub fn adc_injected(self, edge: TriggerEdge) -> Result<InjectedTrigger, Error> {
+        Ok(InjectedTrigger::External {
+            source: self.selection(Destination::AdcInjected)?,
+            edge,
+        })
+    }
+}
@@ -0,0 +1,265 @@
+//! UART driver with the instance selected at runtime.
+
+use core::marker::PhantomData;
+
+use embassy_hal_internal::{into_ref, PeripheralRef};
+
+#[cfg(any(usart_v1, usart_v2))]
+use super::regs;
+use super::{
+    check_rx_flags, configure, rdr, read_interrupt, sealed, sr, tdr, write_interrupt, BasicInstance, Config,
+    ConfigError, Error, InterruptHandler, Kind, Regs, RxPin, TxPin,
+};
+use crate::gpio::sealed::{AFType, Pin as _};
+use crate::gpio::AnyPin;
+use crate::interrupt::typelevel::Interrupt as _;
+use crate::interrupt::{Interrupt, InterruptExt};
+use crate::mode::{Async, Blocking, Mode};
+use crate::time::Hertz;
+use crate::{interrupt, Peripheral};
+
+/// USART instance, with its type erased.
+#[derive(Clone, Copy)]
+struct Info {
+    regs: Regs,
+    kind: Kind,
+    interrupt: Interrupt,
+    state: &'static sealed::State,
+    frequency: fn() -> Hertz,
+    disable: fn(),
+}
+
+impl Info {
+    fn new<T: BasicInstance>() -> Self {
+        Self {
+            regs: T::regs(),
+            kind: T::KIND,
+            interrupt: T::Interrupt::IRQ,
+            state: T::state(),
+            frequency: T::frequency,
+            disable: T::disable,
+        }
+    }
+}
+
+/// UART driver, with the instance selected at runtime.
+///
+/// [`Uart`](super::Uart) is generic over its instance, so choosing the instance at runtime, for
+/// example from a board configuration table, instantiates the driver and the code using it for
+/// each instance. `AnyUart` erases the instance instead: only the constructors are generic, and
+/// the driver holds the registers, the clock, the interrupt and the wakers of the instance.
+///
+/// The driver created by [`new`](AnyUart::new) has async reads and writes, driven by the
+/// interrupt of the instance. The one created by [`new_blocking`](AnyUart::new_blocking) is
+/// blocking only, and the interrupt stays disabled. The driver doesn't use DMA: use
+/// [`Uart`](super::Uart) for DMA transfers.
+pub struct AnyUart<'d, M: Mode = Blocking> {
+    info: Info,
+    _rx: PeripheralRef<'d, AnyPin>,
+    _tx: PeripheralRef<'d, AnyPin>,
+    #[cfg(any(usart_v1, usart_v2))]
+    buffered_sr: regs::Sr,
+    phantom: PhantomData<(&'d mut (), M)>,
+}
+
+impl<'d> AnyUart<'d, Blocking> {
+    /// Create a new blocking bidirectional UART from any instance.
+    pub fn new_blocking<T: BasicInstance>(
+        peri: impl Peripheral<P = T> + 'd,
+        rx: impl Peripheral<P = impl RxPin<T>> + 'd,
+        tx: impl Peripheral<P = impl TxPin<T>> + 'd,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        let this = Self::new_inner(peri, rx, tx, config)?;
+
+        // No interrupt handler is bound.
+        this.info.interrupt.disable();
+
+        Ok(this)
+    }
+}
+
+impl<'d> AnyUart<'d, Async> {
+    /// Create a new bidirectional UART from any instance, with async reads and writes driven by
+    /// its interrupt.
+    pub fn new<T: BasicInstance>(
+        peri: impl Peripheral<P = T> + 'd,
+        rx: impl Peripheral<P = impl RxPin<T>> + 'd,
+        tx: impl Peripheral<P = impl TxPin<T>> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        let this = Self::new_inner(peri, rx, tx, config)?;
+
+        this.info.interrupt.unpend();
+        unsafe { this.info.interrupt.enable() };
+
+        Ok(this)
+    }
+
+    /// Write `buffer`, driven by the TXE interrupt.
+    pub async fn write(&mut self, buffer: &[u8]) -> Result<(), Error> {
+        write_interrupt(self.info.regs, self.info.state, buffer).await;
+        Ok(())
+    }
+
+    /// Read until `buffer` is full, driven by the RXNE interrupt.
+    pub async fn read(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        self.interrupt_read(buffer, false).await?;
+        Ok(())
+    }
+
+    /// Read until `buffer` is full, or until the line goes idle after a byte, driven by the RXNE
+    /// and IDLE interrupts.
+    ///
+    /// Returns the number of bytes read.
+    pub async fn read_until_idle(&mut self, buffer: &mut [u8]) -> Result<usize, Error> {
+        self.interrupt_read(buffer, true).await
+    }
+
+    async fn interrupt_read(&mut self, buffer: &mut [u8], enable_idle_line_detection: bool) -> Result<usize, Error> {
+        let (regs, state) = (self.info.regs, self.info.state);
+        read_interrupt(regs, state, buffer, enable_idle_line_detection, || {
+            self.check_rx_flags()
+        })
+        .await
+    }
+}
+
+impl<'d, M: Mode> AnyUart<'d, M> {
+    fn new_inner<T: BasicInstance>(
+        _peri: impl Peripheral<P = T> + 'd,
+        rx: impl Peripheral<P = impl RxPin<T>> + 'd,
+        tx: impl Peripheral<P = impl TxPin<T>> + 'd,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        into_ref!(rx, tx);
+
+        T::enable_and_reset();
+
+        rx.set_as_af(rx.af_num(), AFType::Input);
+        tx.set_as_af(tx.af_num(), AFType::OutputPushPull);
+
+        let info = Info::new::<T>();
+        configure(info.regs, &config, (info.frequency)(), info.kind, true, true)?;
+
+        Ok(Self {
+            info,
+            _rx: rx.map_into(),
+            _tx: tx.map_into(),
+            #[cfg(any(usart_v1, usart_v2))]
+            buffered_sr: regs::Sr(0),
+            phantom: PhantomData,
+        })
+    }
+
+    /// Reconfigure the driver
+    pub fn set_config(&mut self, config: &Config) -> Result<(), ConfigError> {
+        configure(
+            self.info.regs,
+            config,
+            (self.info.frequency)(),
+            self.info.kind,
+            true,
+            true,
+        )
+    }
+
+    /// Perform a blocking write
+    pub fn blocking_write(&mut self, buffer: &[u8]) -> Result<(), Error> {
+        let r = self.info.regs;
+        for &b in buffer {
+            while !sr(r).read().txe() {}
+            unsafe { tdr(r).write_volatile(b) };
+        }
+        Ok(())
+    }
+
+    /// Block until transmission complete
+    pub fn blocking_flush(&mut self) -> Result<(), Error> {
+        while !sr(self.info.regs).read().tc() {}
+        Ok(())
+    }
+
+    /// Read a single u8 if there is one available, otherwise return WouldBlock
+    pub fn nb_read(&mut self) -> Result<u8, nb::Error<Error>> {
+        if self.check_rx_flags()? {
+            Ok(unsafe { rdr(self.info.regs).read_volatile() })
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Perform a blocking read into `buffer`
+    pub fn blocking_read(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        for b in buffer {
+            while !self.check_rx_flags()? {}
+            unsafe { *b = rdr(self.info.regs).read_volatile() }
+        }
+        Ok(())
+    }
+
+    #[cfg(any(usart_v1, usart_v2))]
+    fn check_rx_flags(&mut self) -> Result<bool, Error> {
+        check_rx_flags(self.info.regs, &mut self.buffered_sr)
+    }
+
+    #[cfg(any(usart_v3, usart_v4))]
+    fn check_rx_flags(&mut self) -> Result<bool, Error> {
+        check_rx_flags(self.info.regs)
+    }
+}
+
+impl<'d, M: Mode> Drop for AnyUart<'d, M> {
+    fn drop(&mut self) {
+        (self.info.disable)();
+    }
+}
+
+impl<M: Mode> embedded_io::ErrorType for AnyUart<'_, M> {
+    type Error = Error;
+}
+
+impl<M: Mode> embedded_io::Read for AnyUart<'_, M> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        // Block for the first byte, then read the bytes already received.
+        let mut n = 0;
+        while n < buf.len() {
+            match self.nb_read() {
+                Ok(b) => {
+                    buf[n] = b;
+                    n += 1;
+                }
+                Err(nb::Error::WouldBlock) if n > 0 => break,
+                Err(nb::Error::WouldBlock) => {}
+                Err(nb::Error::Other(e)) => return Err(e),
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl<M: Mode> embedded_io::Write for AnyUart<'_, M> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.blocking_write(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.blocking_flush()
+    }
+}
+
+impl embedded_io_async::Write for AnyUart<'_, Async> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.write(buf).await?;
+        Ok(buf.len())
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.blocking_flush()
+    }
+}
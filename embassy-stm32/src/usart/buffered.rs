@@ -1,6 +1,6 @@
 use core::future::poll_fn;
 use core::slice;
-use core::sync::atomic::{AtomicBool, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 use core::task::Poll;
 
 use embassy_hal_internal::atomic_ring_buffer::RingBuffer;
@@ -31,17 +31,30 @@ impl<T: BasicInstance> interrupt::typelevel::Handler<T::Interrupt> for Interrupt
         };
         clear_interrupt_flags(r, sr_val);
 
+        let mut errors = 0;
         if sr_val.pe() {
             warn!("Parity error");
+            errors |= RX_ERROR_PARITY;
         }
         if sr_val.fe() {
             warn!("Framing error");
+            errors |= RX_ERROR_FRAMING;
         }
         if sr_val.ne() {
             warn!("Noise error");
+            errors |= RX_ERROR_NOISE;
         }
         if sr_val.ore() {
             warn!("Overrun error");
+            errors |= RX_ERROR_OVERRUN;
+        }
+        if errors != 0 {
+            // Reported by the next read.
+            critical_section::with(|_| {
+                let pending = state.rx_error.load(Ordering::Relaxed);
+                state.rx_error.store(pending | errors, Ordering::Relaxed);
+            });
+            state.rx_waker.wake();
         }
         if sr_val.rxne() {
             let mut rx_writer = state.rx_buf.writer();
@@ -108,6 +121,32 @@ impl<T: BasicInstance> interrupt::typelevel::Handler<T::Interrupt> for Interrupt
     }
 }
 
+const RX_ERROR_PARITY: u8 = 1 << 0;
+const RX_ERROR_FRAMING: u8 = 1 << 1;
+const RX_ERROR_NOISE: u8 = 1 << 2;
+const RX_ERROR_OVERRUN: u8 = 1 << 3;
+
+/// Take the receive errors latched by the interrupt handler, reporting the first one.
+fn take_rx_error(state: &State) -> Result<(), Error> {
+    let errors = critical_section::with(|_| {
+        let errors = state.rx_error.load(Ordering::Relaxed);
+        state.rx_error.store(0, Ordering::Relaxed);
+        errors
+    });
+
+    if errors & RX_ERROR_PARITY != 0 {
+        Err(Error::Parity)
+    } else if errors & RX_ERROR_FRAMING != 0 {
+        Err(Error::Framing)
+    } else if errors & RX_ERROR_NOISE != 0 {
+        Err(Error::Noise)
+    } else if errors & RX_ERROR_OVERRUN != 0 {
+        Err(Error::Overrun)
+    } else {
+        Ok(())
+    }
+}
+
 pub(crate) use sealed::State;
 pub(crate) mod sealed {
     use super::*;
@@ -117,6 +156,7 @@ pub(crate) mod sealed {
         pub(crate) tx_waker: AtomicWaker,
         pub(crate) tx_buf: RingBuffer,
         pub(crate) tx_done: AtomicBool,
+        pub(crate) rx_error: AtomicU8,
     }
 
     impl State {
@@ -128,12 +168,17 @@ pub(crate) mod sealed {
                 rx_waker: AtomicWaker::new(),
                 tx_waker: AtomicWaker::new(),
                 tx_done: AtomicBool::new(true),
+                rx_error: AtomicU8::new(0),
             }
         }
     }
 }
 
 /// Bidirectional buffered UART
+///
+/// Receive errors (parity, framing, noise, overrun) are latched by the interrupt handler and
+/// returned by the next read. The bytes received so far stay buffered, and are returned by the
+/// following reads.
 pub struct BufferedUart<'d, T: BasicInstance> {
     rx: BufferedUartRx<'d, T>,
     tx: BufferedUartTx<'d, T>,
@@ -223,6 +268,9 @@ impl<'d, T: BasicInstance> BufferedUart<'d, T> {
     }
 
     /// Create a new bidirectional buffered UART driver with a driver-enable pin
+    ///
+    /// The delays around the transmission are set by [`Config::de_assertion_time`] and
+    /// [`Config::de_deassertion_time`].
     #[cfg(not(any(usart_v1, usart_v2)))]
     pub fn new_with_de(
         peri: impl Peripheral<P = T> + 'd,
@@ -263,6 +311,7 @@ impl<'d, T: BasicInstance> BufferedUart<'d, T> {
         unsafe { state.tx_buf.init(tx_buffer.as_mut_ptr(), len) };
         let len = rx_buffer.len();
         unsafe { state.rx_buf.init(rx_buffer.as_mut_ptr(), len) };
+        state.rx_error.store(0, Ordering::Relaxed);
 
         let r = T::regs();
         rx.set_as_af(rx.af_num(), AFType::Input);
@@ -306,6 +355,9 @@ impl<'d, T: BasicInstance> BufferedUartRx<'d, T> {
     async fn read(&self, buf: &mut [u8]) -> Result<usize, Error> {
         poll_fn(move |cx| {
             let state = T::buffered_state();
+            if let Err(e) = take_rx_error(state) {
+                return Poll::Ready(Err(e));
+            }
             let mut rx_reader = unsafe { state.rx_buf.reader() };
             let data = rx_reader.pop_slice();
 
@@ -332,6 +384,7 @@ impl<'d, T: BasicInstance> BufferedUartRx<'d, T> {
     fn blocking_read(&self, buf: &mut [u8]) -> Result<usize, Error> {
         loop {
             let state = T::buffered_state();
+            take_rx_error(state)?;
             let mut rx_reader = unsafe { state.rx_buf.reader() };
             let data = rx_reader.pop_slice();
 
@@ -354,6 +407,9 @@ impl<'d, T: BasicInstance> BufferedUartRx<'d, T> {
     async fn fill_buf(&self) -> Result<&[u8], Error> {
         poll_fn(move |cx| {
             let state = T::buffered_state();
+            if let Err(e) = take_rx_error(state) {
+                return Poll::Ready(Err(e));
+            }
             let mut rx_reader = unsafe { state.rx_buf.reader() };
             let (p, n) = rx_reader.pop_buf();
             if n == 0 {
@@ -0,0 +1,221 @@
+//! DMX512 lighting transmitter and receiver, over an RS-485 transceiver.
+//!
+//! A DMX512 packet is a break of at least 88 µs, a mark after break of at least 8 µs, then the
+//! start code and up to 512 slots, sent at 250 kbaud with 2 stop bits. The 44 µs break character
+//! of the USART is too short, so the transmitter sends it at a lower baud rate, and switches back
+//! to 250 kbaud for the slots. The receiver detects the break with the LIN break detection.
+//!
+//! The slots sent by the transmitter are written by the application to a [`Universe`], and copied
+//! once per packet, so the transmitter never sends a half-updated universe:
+//!
+//! ```rust,ignore
+//! static UNIVERSE: Universe = Universe::new();
+//!
+//! #[embassy_executor::task]
+//! async fn dmx(mut dmx: DmxTransmitter<'static, USART2, DMA1_CH7>) {
+//!     let error = dmx.run(&UNIVERSE).await;
+//!     panic!("DMX error: {:?}", error);
+//! }
+//!
+//! let tx = UartTx::new(p.USART2, Irqs, p.PA2, p.DMA1_CH7, Default::default())?;
+//! spawner.spawn(dmx(DmxTransmitter::new(tx, 512, Duration::from_hz(40))?))?;
+//! UNIVERSE.update(|slots| slots[..3].copy_from_slice(&[255, 128, 0]));
+//! ```
+
+use core::cell::RefCell;
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Ticker};
+
+use super::lin::{set_lin_mode, wait_break, BreakLength};
+use super::{
+    reconfigure, BasicInstance, Config, ConfigError, Error, FullInstance, Parity, RxDma, StopBits, TxDma, UartRx,
+    UartTx,
+};
+
+/// Number of slots of a universe.
+pub const SLOTS: usize = 512;
+
+/// Start code of the packets of dimmer levels.
+pub const NULL_START_CODE: u8 = 0x00;
+
+/// Baud rate of the slots.
+const BAUDRATE: u32 = 250_000;
+
+/// Baud rate of the break character, held low for at least 10 bits, 100 µs.
+const BREAK_BAUDRATE: u32 = 100_000;
+
+/// Minimum time between the starts of two packets.
+const MIN_PACKET_PERIOD: Duration = Duration::from_micros(1204);
+
+/// Time on the line of a packet of `slots` slots, with the break and the mark after break.
+fn packet_duration(slots: usize) -> Duration {
+    // 11 bits of 4 µs per slot, and the start code.
+    Duration::from_micros(120 + 44 * (slots as u64 + 1))
+}
+
+/// UART configuration of the DMX512 slots.
+pub fn config() -> Config {
+    let mut config = Config::default();
+    config.baudrate = BAUDRATE;
+    config.stop_bits = StopBits::STOP2;
+    config.parity = Parity::ParityNone;
+    config
+}
+
+/// Slots of a DMX512 universe, shared between the application and a [`DmxTransmitter`].
+pub struct Universe {
+    slots: Mutex<CriticalSectionRawMutex, RefCell<[u8; SLOTS]>>,
+}
+
+impl Universe {
+    /// Create a new universe, with all the slots at 0.
+    pub const fn new() -> Self {
+        Self {
+            slots: Mutex::new(RefCell::new([0; SLOTS])),
+        }
+    }
+
+    /// Get the value of `slot`, numbered from 0.
+    pub fn get(&self, slot: usize) -> u8 {
+        self.slots.lock(|slots| slots.borrow()[slot])
+    }
+
+    /// Set the value of `slot`, numbered from 0.
+    pub fn set(&self, slot: usize, value: u8) {
+        self.slots.lock(|slots| slots.borrow_mut()[slot] = value)
+    }
+
+    /// Update several slots at once, so they are sent in the same packet.
+    ///
+    /// `f` runs in a critical section, and should be short.
+    pub fn update<R>(&self, f: impl FnOnce(&mut [u8; SLOTS]) -> R) -> R {
+        self.slots.lock(|slots| f(&mut slots.borrow_mut()))
+    }
+
+    fn copy_to(&self, dst: &mut [u8]) {
+        self.slots
+            .lock(|slots| dst.copy_from_slice(&slots.borrow()[..dst.len()]))
+    }
+}
+
+impl Default for Universe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// DMX512 transmitter.
+pub struct DmxTransmitter<'d, T: BasicInstance, TxDma> {
+    tx: UartTx<'d, T, TxDma>,
+    /// Packet being sent, the start code and the slots.
+    packet: [u8; SLOTS + 1],
+    slots: usize,
+    period: Duration,
+}
+
+impl<'d, T: BasicInstance, Tx: TxDma<T>> DmxTransmitter<'d, T, Tx> {
+    /// Create a new transmitter sending `slots` slots, from 24 to 512, every `period`.
+    ///
+    /// The UART is reconfigured for DMX512. `period` is extended to the time on the line of the
+    /// packets when shorter.
+    pub fn new(mut tx: UartTx<'d, T, Tx>, slots: usize, period: Duration) -> Result<Self, ConfigError> {
+        assert!((24..=SLOTS).contains(&slots));
+
+        tx.set_config(&break_config())?;
+        tx.set_config(&config())?;
+
+        let mut packet = [0; SLOTS + 1];
+        packet[0] = NULL_START_CODE;
+        let period = period.max(packet_duration(slots)).max(MIN_PACKET_PERIOD);
+        Ok(Self {
+            tx,
+            packet,
+            slots,
+            period,
+        })
+    }
+
+    /// Set the start code of the next packets, [`NULL_START_CODE`] by default.
+    pub fn set_start_code(&mut self, start_code: u8) {
+        self.packet[0] = start_code;
+    }
+
+    /// Send a packet with the current slots of `universe`.
+    ///
+    /// Returns once the slots are queued, the packet being sent in the background.
+    pub async fn send_packet(&mut self, universe: &Universe) -> Result<(), Error> {
+        universe.copy_to(&mut self.packet[1..self.slots + 1]);
+
+        // Break and mark after break, its stop bits, at the break baud rate. The configurations
+        // were checked by `new`.
+        self.tx.blocking_flush()?;
+        unwrap!(reconfigure::<T>(&break_config()));
+        self.tx.send_break();
+        while self.tx.is_sending_break() {}
+        unwrap!(reconfigure::<T>(&config()));
+
+        self.tx.inner_write(&self.packet[..self.slots + 1]).await
+    }
+
+    /// Send packets with the current slots of `universe` forever, paced by the period.
+    ///
+    /// Only returns on an error.
+    pub async fn run(&mut self, universe: &Universe) -> Error {
+        let mut ticker = Ticker::every(self.period);
+        loop {
+            if let Err(e) = self.send_packet(universe).await {
+                return e;
+            }
+            ticker.next().await;
+        }
+    }
+
+    /// Release the UART.
+    pub fn free(self) -> UartTx<'d, T, Tx> {
+        self.tx
+    }
+}
+
+fn break_config() -> Config {
+    let mut config = config();
+    config.baudrate = BREAK_BAUDRATE;
+    config
+}
+
+/// DMX512 receiver.
+pub struct DmxReceiver<'d, T: BasicInstance + FullInstance, RxDma> {
+    rx: UartRx<'d, T, RxDma>,
+}
+
+impl<'d, T: BasicInstance + FullInstance, Rx: RxDma<T>> DmxReceiver<'d, T, Rx> {
+    /// Create a new receiver, reconfiguring the UART for DMX512 and enabling the break detection.
+    pub fn new(mut rx: UartRx<'d, T, Rx>) -> Result<Self, ConfigError> {
+        // The LIN mode detecting the break requires a single stop bit, which also receives two.
+        let mut config = config();
+        config.stop_bits = StopBits::STOP1;
+        rx.set_config(&config)?;
+        set_lin_mode::<T>(true, BreakLength::Bits11);
+        Ok(Self { rx })
+    }
+
+    /// Wait for the next packet, reading its first `slots.len()` slots, and return its start code.
+    ///
+    /// The following slots of the packet are ignored.
+    pub async fn receive(&mut self, slots: &mut [u8]) -> Result<u8, Error> {
+        assert!(slots.len() <= SLOTS);
+
+        wait_break::<T>().await;
+        let mut packet = [0; SLOTS + 1];
+        self.rx.inner_read(&mut packet[..slots.len() + 1], false).await?;
+        slots.copy_from_slice(&packet[1..slots.len() + 1]);
+        Ok(packet[0])
+    }
+
+    /// Release the UART, disabling the break detection.
+    pub fn free(self) -> UartRx<'d, T, Rx> {
+        set_lin_mode::<T>(false, BreakLength::Bits11);
+        self.rx
+    }
+}
@@ -0,0 +1,506 @@
+//! LIN (Local Interconnect Network) support.
+//!
+//! The USART is put in LIN mode so it detects the break field sent by the master at the start of
+//! every frame, see [`UartRx::enable_lin_mode`] and [`UartRx::wait_for_break`]. The master sends the
+//! break field with [`UartTx::send_break`](super::UartTx::send_break).
+//!
+//! [`LinSlave`] is a slave responder built on top: it reads the sync byte and the protected
+//! identifier, looks the frame up in its schedule table and either publishes a response or receives
+//! the data sent by another node.
+//!
+//! The multiprocessor wake of the USART is supported as well: in mute mode, see
+//! [`UartRx::enable_mute_mode`], the receiver ignores the bytes until the line goes idle or an
+//! address byte matching its own address is received, so the nodes of a bus only receive the
+//! messages addressed to them.
+use core::future::poll_fn;
+use core::task::Poll;
+
+use embassy_hal_internal::drop::OnDrop;
+
+use super::{clear_interrupt_flags, rdr, sr, BasicInstance, FullInstance, RxDma, TxDma, Uart, UartRx};
+
+/// Sync byte sent by the master after the break field.
+const SYNC: u8 = 0x55;
+
+// Multiprocessor wake bits, accessed raw since their fields differ between the USART versions.
+const CR1_WAKE: u32 = 1 << 11;
+#[cfg(any(usart_v1, usart_v2))]
+const CR1_RWU: u32 = 1 << 1;
+#[cfg(any(usart_v3, usart_v4))]
+const CR1_MME: u32 = 1 << 13;
+#[cfg(any(usart_v1, usart_v2))]
+const CR2_ADD: u32 = 0xF;
+#[cfg(any(usart_v3, usart_v4))]
+const CR2_ADD: u32 = 0xFF << 24;
+#[cfg(any(usart_v3, usart_v4))]
+const CR2_ADDM7: u32 = 1 << 4;
+#[cfg(any(usart_v3, usart_v4))]
+const RQR_MMRQ: u32 = 1 << 2;
+
+/// Multiprocessor wake method, leaving mute mode.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Wake {
+    /// Leave mute mode when the line goes idle.
+    IdleLine,
+    /// Leave mute mode when an address byte, with its most significant bit set, is received with
+    /// this 4-bit address in its low bits.
+    Address4(u8),
+    /// Leave mute mode when an address byte, with its most significant bit set, is received with
+    /// this 7-bit address in its low bits.
+    #[cfg(any(usart_v3, usart_v4))]
+    Address7(u8),
+}
+
+/// LIN error.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// Error on the underlying UART.
+    Uart(super::Error),
+    /// The byte following the break field was not the sync byte.
+    Sync,
+    /// The protected identifier has wrong parity bits.
+    Parity,
+    /// The checksum of a received frame is wrong.
+    Checksum,
+}
+
+impl From<super::Error> for Error {
+    fn from(e: super::Error) -> Self {
+        Self::Uart(e)
+    }
+}
+
+/// Minimum length of a detected break field, in bits.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BreakLength {
+    /// 10-bit break detection.
+    Bits10,
+    /// 11-bit break detection, as required by the LIN specification.
+    Bits11,
+}
+
+/// Checksum model of a frame.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Checksum {
+    /// LIN 1.x checksum, over the data bytes only.
+    Classic,
+    /// LIN 2.x checksum, over the protected identifier and the data bytes.
+    Enhanced,
+}
+
+/// Direction of a frame, from the point of view of this slave.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Direction {
+    /// This slave sends the response.
+    Publish,
+    /// Another node sends the response, this slave receives it.
+    Subscribe,
+}
+
+/// Entry of the schedule table.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Frame {
+    /// Frame identifier (0..=63), without parity bits.
+    pub id: u8,
+    /// Number of data bytes (1..=8).
+    pub len: u8,
+    /// Whether this slave publishes or subscribes to the frame.
+    pub direction: Direction,
+    /// Checksum model.
+    pub checksum: Checksum,
+}
+
+impl Frame {
+    /// Create a schedule table entry.
+    ///
+    /// Diagnostic frames (0x3C and 0x3D) always use the classic checksum, other frames use `checksum`.
+    pub const fn new(id: u8, len: u8, direction: Direction, checksum: Checksum) -> Self {
+        assert!(id < 64);
+        assert!(len >= 1 && len <= 8);
+        let checksum = if id == 0x3C || id == 0x3D {
+            Checksum::Classic
+        } else {
+            checksum
+        };
+        Self {
+            id,
+            len,
+            direction,
+            checksum,
+        }
+    }
+}
+
+/// Handler for the frames of the schedule table.
+pub trait Handler {
+    /// Fill the response of a frame published by this slave.
+    ///
+    /// `data` has the length of the frame in the schedule table.
+    async fn publish(&mut self, id: u8, data: &mut [u8]);
+
+    /// Handle a frame received from another node.
+    async fn subscribe(&mut self, id: u8, data: &[u8]);
+}
+
+/// Compute the protected identifier (identifier with parity bits) of a frame identifier.
+pub const fn protected_id(id: u8) -> u8 {
+    let p0 = (id ^ (id >> 1) ^ (id >> 2) ^ (id >> 4)) & 1;
+    let p1 = !((id >> 1) ^ (id >> 3) ^ (id >> 4) ^ (id >> 5)) & 1;
+    (id & 0x3F) | (p0 << 6) | (p1 << 7)
+}
+
+/// Compute the checksum of a frame.
+pub fn checksum(model: Checksum, pid: u8, data: &[u8]) -> u8 {
+    let init = match model {
+        Checksum::Classic => 0,
+        Checksum::Enhanced => pid as u16,
+    };
+    let sum = data.iter().fold(init, |acc, &b| {
+        // Sum with carry: add the carry back into the low byte.
+        let acc = acc + b as u16;
+        (acc & 0xFF) + (acc >> 8)
+    });
+    !(sum as u8)
+}
+
+/// LIN slave responder.
+pub struct LinSlave<'d, T: BasicInstance + FullInstance, TxDma, RxDma> {
+    uart: Uart<'d, T, TxDma, RxDma>,
+}
+
+impl<'d, T: BasicInstance + FullInstance, Tx: TxDma<T>, Rx: RxDma<T>> LinSlave<'d, T, Tx, Rx> {
+    /// Create a new LIN slave, putting the UART in LIN mode.
+    ///
+    /// The UART must be configured with 8 data bits, no parity and 1 stop bit.
+    pub fn new(uart: Uart<'d, T, Tx, Rx>) -> Self {
+        set_lin_mode::<T>(true, BreakLength::Bits11);
+        Self { uart }
+    }
+
+    /// Wait for the next frame header, and handle the frame if it is in the schedule table.
+    ///
+    /// Returns the identifier of the handled frame, or `None` if the frame is not in the schedule table.
+    pub async fn respond<H: Handler>(&mut self, schedule: &[Frame], handler: &mut H) -> Result<Option<u8>, Error> {
+        wait_break::<T>().await;
+
+        let mut header = [0u8; 2];
+        self.uart.rx.inner_read(&mut header, false).await?;
+        if header[0] != SYNC {
+            return Err(Error::Sync);
+        }
+
+        let pid = header[1];
+        let id = pid & 0x3F;
+        if protected_id(id) != pid {
+            return Err(Error::Parity);
+        }
+
+        let Some(frame) = schedule.iter().find(|f| f.id == id) else {
+            return Ok(None);
+        };
+
+        let len = frame.len as usize;
+        let mut buf = [0u8; 9];
+        match frame.direction {
+            Direction::Publish => {
+                handler.publish(id, &mut buf[..len]).await;
+                buf[len] = checksum(frame.checksum, pid, &buf[..len]);
+                self.uart.tx.inner_write(&buf[..len + 1]).await?;
+            }
+            Direction::Subscribe => {
+                self.uart.rx.inner_read(&mut buf[..len + 1], false).await?;
+                if checksum(frame.checksum, pid, &buf[..len]) != buf[len] {
+                    return Err(Error::Checksum);
+                }
+                handler.subscribe(id, &buf[..len]).await;
+            }
+        }
+
+        Ok(Some(id))
+    }
+
+    /// Handle frames of the schedule table forever.
+    ///
+    /// Errors on individual frames are logged and the slave waits for the next break.
+    pub async fn run<H: Handler>(&mut self, schedule: &[Frame], handler: &mut H) -> ! {
+        loop {
+            if let Err(e) = self.respond(schedule, handler).await {
+                warn!("LIN frame error: {:?}", e);
+            }
+        }
+    }
+
+    /// Release the UART, leaving LIN mode.
+    pub fn free(self) -> Uart<'d, T, Tx, Rx> {
+        set_lin_mode::<T>(false, BreakLength::Bits11);
+        self.uart
+    }
+}
+
+impl<'d, T: BasicInstance + FullInstance, RxDma> UartRx<'d, T, RxDma> {
+    /// Enable LIN mode, detecting the break fields of at least `break_length` low bits.
+    ///
+    /// The UART must be configured with 8 data bits, no parity and 1 stop bit.
+    pub fn enable_lin_mode(&mut self, break_length: BreakLength) {
+        let _clock = self.auto_idle.wake();
+        set_lin_mode::<T>(true, break_length);
+    }
+
+    /// Disable LIN mode.
+    pub fn disable_lin_mode(&mut self) {
+        let _clock = self.auto_idle.wake();
+        set_lin_mode::<T>(false, BreakLength::Bits11);
+    }
+
+    /// Wait for a break field, in LIN mode.
+    ///
+    /// The break field is also received as a 0x00 byte with a framing error, which is discarded.
+    pub async fn wait_for_break(&mut self) {
+        let _clock = self.auto_idle.wake();
+        assert!(T::regs_uart().cr2().read().linen());
+        wait_break::<T>().await
+    }
+}
+
+impl<'d, T: BasicInstance + FullInstance, RxDma> UartRx<'d, T, RxDma> {
+    /// Enable mute mode, with the multiprocessor wake method `wake`, and enter it.
+    ///
+    /// The receiver then ignores the received bytes until it is woken. With an address wake, the
+    /// UART must be configured with 8 data bits, and the address byte is received.
+    pub fn enable_mute_mode(&mut self, wake: Wake) {
+        let _clock = self.auto_idle.wake();
+        set_mute_mode::<T>(Some(wake));
+        self.enter_mute_mode();
+    }
+
+    /// Enter mute mode again, once the message addressed to this node is received.
+    pub fn enter_mute_mode(&mut self) {
+        let _clock = self.auto_idle.wake();
+        let r = T::regs_uart();
+        #[cfg(any(usart_v1, usart_v2))]
+        r.cr1().modify(|w| w.0 |= CR1_RWU);
+        #[cfg(any(usart_v3, usart_v4))]
+        r.rqr().write(|w| w.0 = RQR_MMRQ);
+    }
+
+    /// Disable mute mode.
+    pub fn disable_mute_mode(&mut self) {
+        let _clock = self.auto_idle.wake();
+        set_mute_mode::<T>(None);
+    }
+}
+
+impl<'d, T: BasicInstance + FullInstance, TxDma, RxDma> Uart<'d, T, TxDma, RxDma> {
+    /// Enable mute mode, with the multiprocessor wake method `wake`, and enter it.
+    ///
+    /// The receiver then ignores the received bytes until it is woken. With an address wake, the
+    /// UART must be configured with 8 data bits, and the address byte is received.
+    pub fn enable_mute_mode(&mut self, wake: Wake) {
+        self.rx.enable_mute_mode(wake)
+    }
+
+    /// Enter mute mode again, once the message addressed to this node is received.
+    pub fn enter_mute_mode(&mut self) {
+        self.rx.enter_mute_mode()
+    }
+
+    /// Disable mute mode.
+    pub fn disable_mute_mode(&mut self) {
+        self.rx.disable_mute_mode()
+    }
+
+    /// Enable LIN mode, detecting the break fields of at least `break_length` low bits.
+    ///
+    /// The UART must be configured with 8 data bits, no parity and 1 stop bit.
+    pub fn enable_lin_mode(&mut self, break_length: BreakLength) {
+        self.rx.enable_lin_mode(break_length)
+    }
+
+    /// Disable LIN mode.
+    pub fn disable_lin_mode(&mut self) {
+        self.rx.disable_lin_mode()
+    }
+
+    /// Wait for a break field, in LIN mode.
+    ///
+    /// The break field is also received as a 0x00 byte with a framing error, which is discarded.
+    pub async fn wait_for_break(&mut self) {
+        self.rx.wait_for_break().await
+    }
+}
+
+/// Enable or disable LIN mode, and its break detection.
+pub(super) fn set_lin_mode<T: FullInstance>(enabled: bool, break_length: BreakLength) {
+    let r = T::regs_uart();
+
+    // LIN mode can only be enabled while the USART is disabled.
+    r.cr1().modify(|w| w.set_ue(false));
+    r.cr2().modify(|w| {
+        w.set_linen(enabled);
+        w.set_lbdl(break_length == BreakLength::Bits11);
+    });
+    r.cr1().modify(|w| w.set_ue(true));
+}
+
+/// Enable mute mode with the wake method `wake`, or disable it.
+fn set_mute_mode<T: FullInstance>(wake: Option<Wake>) {
+    let r = T::regs_uart();
+
+    // The wake method and the address can only be written while the USART is disabled.
+    r.cr1().modify(|w| w.set_ue(false));
+    let (cr1, cr2) = mute_mode_bits(r.cr1().read().0, r.cr2().read().0, wake);
+    r.cr2().modify(|w| w.0 = cr2);
+    r.cr1().modify(|w| w.0 = cr1);
+    r.cr1().modify(|w| w.set_ue(true));
+}
+
+/// Values of CR1 and CR2 with the mute mode bits set for `wake`, or cleared.
+fn mute_mode_bits(mut cr1: u32, mut cr2: u32, wake: Option<Wake>) -> (u32, u32) {
+    #[cfg(any(usart_v1, usart_v2))]
+    {
+        cr1 &= !(CR1_WAKE | CR1_RWU);
+        cr2 &= !CR2_ADD;
+    }
+    #[cfg(any(usart_v3, usart_v4))]
+    {
+        cr1 &= !(CR1_WAKE | CR1_MME);
+        cr2 &= !(CR2_ADD | CR2_ADDM7);
+        if wake.is_some() {
+            cr1 |= CR1_MME;
+        }
+    }
+
+    match wake {
+        None | Some(Wake::IdleLine) => {}
+        #[cfg(any(usart_v1, usart_v2))]
+        Some(Wake::Address4(address)) => {
+            cr1 |= CR1_WAKE;
+            cr2 |= (address as u32) & 0xF;
+        }
+        #[cfg(any(usart_v3, usart_v4))]
+        Some(Wake::Address4(address)) => {
+            cr1 |= CR1_WAKE;
+            cr2 |= ((address as u32) & 0xF) << 24;
+        }
+        #[cfg(any(usart_v3, usart_v4))]
+        Some(Wake::Address7(address)) => {
+            cr1 |= CR1_WAKE;
+            cr2 |= CR2_ADDM7 | ((address as u32) & 0x7F) << 24;
+        }
+    }
+
+    (cr1, cr2)
+}
+
+/// Wait for a break field, in LIN mode.
+pub(super) async fn wait_break<T: BasicInstance + FullInstance>() {
+    let r = T::regs_uart();
+
+    let _on_drop = OnDrop::new(move || {
+        r.cr2().modify(|w| w.set_lbdie(false));
+    });
+
+    clear_break_flag(r);
+    r.cr2().modify(|w| w.set_lbdie(true));
+
+    poll_fn(|cx| {
+        T::state().rx_waker.register(cx.waker());
+
+        if break_detected(r) {
+            clear_break_flag(r);
+            return Poll::Ready(());
+        }
+
+        // The interrupt handler disables the interrupt when it fires.
+        r.cr2().modify(|w| w.set_lbdie(true));
+        Poll::Pending
+    })
+    .await;
+
+    // The break field is also received as a 0x00 byte with a framing error, discard it.
+    let r = T::regs();
+    let sr = sr(r).read();
+    unsafe { rdr(r).read_volatile() };
+    clear_interrupt_flags(r, sr);
+}
+
+#[cfg(any(usart_v1, usart_v2))]
+fn break_detected(r: crate::pac::usart::Usart) -> bool {
+    r.sr().read().lbd()
+}
+
+#[cfg(any(usart_v1, usart_v2))]
+fn clear_break_flag(r: crate::pac::usart::Usart) {
+    r.sr().modify(|w| w.set_lbd(false));
+}
+
+#[cfg(any(usart_v3, usart_v4))]
+fn break_detected(r: crate::pac::usart::Usart) -> bool {
+    r.isr().read().lbdf()
+}
+
+#[cfg(any(usart_v3, usart_v4))]
+fn clear_break_flag(r: crate::pac::usart::Usart) {
+    r.icr().write(|w| w.set_lbd(true));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protected_id() {
+        assert_eq!(protected_id(0x00), 0x80);
+        assert_eq!(protected_id(0x01), 0xC1);
+        assert_eq!(protected_id(0x10), 0x50);
+        assert_eq!(protected_id(0x3C), 0x3C);
+        assert_eq!(protected_id(0x3D), 0x7D);
+    }
+
+    #[test]
+    fn test_checksum() {
+        // Examples from the LIN 2.1 specification, section 2.8.3.
+        let data = [0x55, 0x93, 0xE5];
+        assert_eq!(checksum(Checksum::Classic, 0x4A, &data), 0x31);
+        assert_eq!(checksum(Checksum::Enhanced, 0x4A, &data), 0xE6);
+    }
+
+    #[test]
+    fn test_mute_mode_bits() {
+        // The other bits are kept.
+        let (cr1, cr2) = mute_mode_bits(0x0000_000D, 0x0000_0020, Some(Wake::IdleLine));
+        assert_eq!(cr1 & 0xD, 0xD);
+        assert_eq!(cr1 & CR1_WAKE, 0);
+        assert_eq!(cr2 & 0x20, 0x20);
+        assert_eq!(cr2 & CR2_ADD, 0);
+
+        let (cr1, cr2) = mute_mode_bits(0, 0, Some(Wake::Address4(0x1A)));
+        assert_eq!(cr1 & CR1_WAKE, CR1_WAKE);
+        #[cfg(any(usart_v1, usart_v2))]
+        assert_eq!(cr2, 0xA);
+        #[cfg(any(usart_v3, usart_v4))]
+        {
+            assert_eq!(cr1 & CR1_MME, CR1_MME);
+            assert_eq!(cr2, 0x0A00_0000);
+        }
+
+        #[cfg(any(usart_v3, usart_v4))]
+        {
+            let (cr1, cr2) = mute_mode_bits(0, 0, Some(Wake::Address7(0x5A)));
+            assert_eq!(cr1, CR1_WAKE | CR1_MME);
+            assert_eq!(cr2, CR2_ADDM7 | 0x5A00_0000);
+        }
+
+        // Disabling clears all of them.
+        let (cr1, cr2) = mute_mode_bits(cr1, cr2, None);
+        assert_eq!(cr1, 0);
+        assert_eq!(cr2, 0);
+    }
+}
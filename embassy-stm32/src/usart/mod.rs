@@ -12,9 +12,13 @@ use embassy_hal_internal::drop::OnDrop;
 use embassy_hal_internal::{into_ref, PeripheralRef};
 use futures::future::{select, Either};
 
-use crate::dma::{NoDma, Transfer};
+#[cfg(feature = "debug-dump")]
+use crate::debug_dump::Register;
+use crate::dma::buffer::{DmaReadBuffer, DmaWriteBuffer};
+use crate::dma::{wait_timeout, NoDma, Transfer, TransferTimeout};
 use crate::gpio::sealed::AFType;
 use crate::interrupt::typelevel::Interrupt;
+use crate::mode::Blocking;
 #[allow(unused_imports)]
 #[cfg(not(any(usart_v1, usart_v2)))]
 use crate::pac::usart::regs::Isr as Sr;
@@ -25,6 +29,7 @@ use crate::pac::usart::Lpuart as Regs;
 #[cfg(any(usart_v1, usart_v2))]
 use crate::pac::usart::Usart as Regs;
 use crate::pac::usart::{regs, vals};
+use crate::rcc::AutoIdle;
 use crate::time::Hertz;
 use crate::{interrupt, peripherals, Peripheral};
 
@@ -38,7 +43,21 @@ impl<T: BasicInstance> interrupt::typelevel::Handler<T::Interrupt> for Interrupt
         let r = T::regs();
         let s = T::state();
 
-        let (sr, cr1, cr3) = (sr(r).read(), r.cr1().read(), r.cr3().read());
+        let (sr, cr1, cr2, cr3) = (sr(r).read(), r.cr1().read(), r.cr2().read(), r.cr3().read());
+
+        if cr1.txeie() && sr.txe() {
+            // TXE is cleared by the next write, leave it to the writer
+            r.cr1().modify(|w| {
+                w.set_txeie(false);
+            });
+            compiler_fence(Ordering::SeqCst);
+            s.tx_waker.wake();
+        }
+
+        if cr1.tcie() && sr.tc() {
+            // The transfer of a queue is complete, start the next one.
+            s.tx_queue.on_transmission_complete();
+        }
 
         let has_errors = (sr.pe() && cr1.peie()) || ((sr.fe() || sr.ne() || sr.ore()) && cr3.eie());
         if has_errors {
@@ -63,10 +82,18 @@ impl<T: BasicInstance> interrupt::typelevel::Handler<T::Interrupt> for Interrupt
                 // disable idle line detection
                 w.set_idleie(false);
             });
+        } else if cr2.0 & LIN_CR2_LBDIE != 0 && sr.0 & LIN_SR_LBD != 0 {
+            // LIN break detected: the flag is cleared by the LIN driver
+            r.cr2().modify(|w| {
+                w.0 &= !LIN_CR2_LBDIE;
+            });
         } else if cr1.rxneie() {
             // We cannot check the RXNE flag as it is auto-cleared by the DMA controller
 
-            // It is up to the listener to determine if this in fact was a RX event and disable the RXNE detection
+            // It is up to the listener to determine if this in fact was a RX event and re-enable the RXNE detection
+            r.cr1().modify(|w| {
+                w.set_rxneie(false);
+            });
         } else {
             return;
         }
@@ -76,6 +103,10 @@ impl<T: BasicInstance> interrupt::typelevel::Handler<T::Interrupt> for Interrupt
     }
 }
 
+// LIN break detection bits, accessed raw because LPUART register blocks don't have them.
+const LIN_CR2_LBDIE: u32 = 1 << 6;
+const LIN_SR_LBD: u32 = 1 << 8;
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 /// Number of data bits
@@ -123,6 +154,9 @@ pub enum ConfigError {
     BaudrateTooHigh,
     /// Rx or Tx not enabled
     RxOrTxNotEnabled,
+    /// Driver-enable assertion or deassertion time longer than 31 sample times
+    #[cfg(not(any(usart_v1, usart_v2)))]
+    DeTimeTooLong,
 }
 
 #[non_exhaustive]
@@ -161,6 +195,18 @@ pub struct Config {
     /// Set this to true to invert RX pin signal values (V<sub>DD</sub> =0/mark, Gnd = 1/idle).
     #[cfg(any(usart_v3, usart_v4))]
     pub invert_rx: bool,
+
+    /// Time between the activation of the driver-enable pin and the start bit, in sample times
+    /// (1/16 or 1/8 bit depending on the oversampling), from 0 to 31.
+    ///
+    /// Only used with a driver-enable pin, to let an RS-485 transceiver turn on.
+    #[cfg(not(any(usart_v1, usart_v2)))]
+    pub de_assertion_time: u8,
+
+    /// Time between the end of the last stop bit and the deactivation of the driver-enable pin,
+    /// in sample times, from 0 to 31.
+    #[cfg(not(any(usart_v1, usart_v2)))]
+    pub de_deassertion_time: u8,
 }
 
 impl Default for Config {
@@ -180,6 +226,10 @@ impl Default for Config {
             invert_tx: false,
             #[cfg(any(usart_v3, usart_v4))]
             invert_rx: false,
+            #[cfg(not(any(usart_v1, usart_v2)))]
+            de_assertion_time: 0,
+            #[cfg(not(any(usart_v1, usart_v2)))]
+            de_deassertion_time: 0,
         }
     }
 }
@@ -199,6 +249,8 @@ pub enum Error {
     Parity,
     /// Buffer too large for DMA
     BufferTooLong,
+    /// DMA transfer not finished before the timeout set by `set_timeout`
+    Timeout,
 }
 
 enum ReadCompletionEvent {
@@ -228,6 +280,8 @@ impl<'d, T: BasicInstance, TxDma, RxDma> SetConfig for Uart<'d, T, TxDma, RxDma>
 pub struct UartTx<'d, T: BasicInstance, TxDma = NoDma> {
     phantom: PhantomData<&'d mut T>,
     tx_dma: PeripheralRef<'d, TxDma>,
+    auto_idle: AutoIdle<T>,
+    timeout: TransferTimeout,
 }
 
 impl<'d, T: BasicInstance, TxDma> SetConfig for UartTx<'d, T, TxDma> {
@@ -246,6 +300,8 @@ pub struct UartRx<'d, T: BasicInstance, RxDma = NoDma> {
     detect_previous_overrun: bool,
     #[cfg(any(usart_v1, usart_v2))]
     buffered_sr: stm32_metapac::usart::regs::Sr,
+    auto_idle: AutoIdle<T>,
+    timeout: TransferTimeout,
 }
 
 impl<'d, T: BasicInstance, RxDma> SetConfig for UartRx<'d, T, RxDma> {
@@ -261,6 +317,7 @@ impl<'d, T: BasicInstance, TxDma> UartTx<'d, T, TxDma> {
     /// Useful if you only want Uart Tx. It saves 1 pin and consumes a little less power.
     pub fn new(
         peri: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
         tx: impl Peripheral<P = impl TxPin<T>> + 'd,
         tx_dma: impl Peripheral<P = TxDma> + 'd,
         config: Config,
@@ -273,6 +330,7 @@ impl<'d, T: BasicInstance, TxDma> UartTx<'d, T, TxDma> {
     /// Create a new tx-only UART with a clear-to-send pin
     pub fn new_with_cts(
         peri: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
         tx: impl Peripheral<P = impl TxPin<T>> + 'd,
         cts: impl Peripheral<P = impl CtsPin<T>> + 'd,
         tx_dma: impl Peripheral<P = TxDma> + 'd,
@@ -303,53 +361,169 @@ impl<'d, T: BasicInstance, TxDma> UartTx<'d, T, TxDma> {
 
         configure(r, &config, T::frequency(), T::KIND, false, true)?;
 
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
         // create state once!
         let _s = T::state();
 
         Ok(Self {
             tx_dma,
             phantom: PhantomData,
+            auto_idle: AutoIdle::new(),
+            timeout: None,
         })
     }
 
+    /// Enable or disable the auto idle mode.
+    ///
+    /// In the auto idle mode, the clock of the peripheral is gated whenever no write is in flight,
+    /// and enabled again by the next one, cutting the idle current. The writes then return once
+    /// the data is transmitted, rather than queued. It is disabled by default.
+    pub fn set_auto_idle(&mut self, enabled: bool) {
+        self.auto_idle.set(enabled);
+    }
+
+    /// Set the timeout of the DMA writes, or `None` to wait forever, which is the default.
+    ///
+    /// A write that doesn't finish in time, e.g. because the CTS line stays deasserted, stops the
+    /// DMA channel and returns [`Error::Timeout`].
+    #[cfg(feature = "time")]
+    pub fn set_timeout(&mut self, timeout: Option<embassy_time::Duration>) {
+        self.timeout = timeout;
+    }
+
     /// Reconfigure the driver
     pub fn set_config(&mut self, config: &Config) -> Result<(), ConfigError> {
+        let _clock = self.auto_idle.wake();
         reconfigure::<T>(config)
     }
 
-    /// Initiate an asynchronous UART write
-    pub async fn write(&mut self, buffer: &[u8]) -> Result<(), Error>
-    where
-        TxDma: crate::usart::TxDma<T>,
-    {
-        let ch = &mut self.tx_dma;
-        let request = ch.request();
-        T::regs().cr3().modify(|reg| {
-            reg.set_dmat(true);
-        });
-        // If we don't assign future to a variable, the data register pointer
-        // is held across an await and makes the future non-Send.
-        let transfer = unsafe { Transfer::new_write(ch, request, buffer, tdr(T::regs()), Default::default()) };
-        transfer.await;
-        Ok(())
-    }
-
     /// Perform a blocking UART write
     pub fn blocking_write(&mut self, buffer: &[u8]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
         let r = T::regs();
         for &b in buffer {
             while !sr(r).read().txe() {}
             unsafe { tdr(r).write_volatile(b) };
         }
+        self.flush_before_idle();
         Ok(())
     }
 
     /// Block until transmission complete
     pub fn blocking_flush(&mut self) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
         let r = T::regs();
         while !sr(r).read().tc() {}
         Ok(())
     }
+
+    /// Wait for the end of the transmission before the clock is gated, in the auto idle mode.
+    fn flush_before_idle(&self) {
+        if self.auto_idle.is_enabled() {
+            while !sr(T::regs()).read().tc() {}
+        }
+    }
+
+    /// Send a break character, after the character being transmitted.
+    ///
+    /// The line is held low for a whole character, and the break is sent in the background. It is
+    /// complete once [`is_sending_break`](Self::is_sending_break) returns `false`.
+    ///
+    /// In the auto idle mode, this waits for the end of the break.
+    pub fn send_break(&mut self) {
+        let _clock = self.auto_idle.wake();
+        let r = T::regs();
+        while is_sending_break(r) {}
+
+        #[cfg(any(usart_v1, usart_v2))]
+        r.cr1().modify(|w| w.set_sbk(true));
+        #[cfg(any(usart_v3, usart_v4))]
+        r.rqr().write(|w| w.set_sbkrq(true));
+
+        if self.auto_idle.is_enabled() {
+            while is_sending_break(r) {}
+        }
+    }
+
+    /// Whether a break character requested by [`send_break`](Self::send_break) is still being sent.
+    pub fn is_sending_break(&self) -> bool {
+        let _clock = self.auto_idle.wake();
+        is_sending_break(T::regs())
+    }
+}
+
+impl<'d, T: BasicInstance, TxDma: crate::usart::TxDma<T>> UartTx<'d, T, TxDma> {
+    /// Initiate an asynchronous UART write
+    ///
+    /// The DMA keeps reading `buffer` if this future is leaked instead of dropped, prefer
+    /// [`write_buffer`](Self::write_buffer).
+    #[deprecated(note = "the DMA keeps reading `buffer` if the future is leaked, use `write_buffer`")]
+    pub async fn write(&mut self, buffer: &[u8]) -> Result<(), Error> {
+        self.inner_write(buffer).await
+    }
+
+    /// Initiate an asynchronous UART write of a buffer owned by the transfer, and give it back
+    ///
+    /// Unlike [`write`](Self::write), the buffer stays valid if the future is leaked, see
+    /// [`crate::dma::buffer`].
+    pub async fn write_buffer<B: DmaWriteBuffer<Word = u8>>(&mut self, buffer: B) -> (Result<(), Error>, B) {
+        let (ptr, len) = buffer.dma_write_buffer();
+        // Safety: the buffer is owned by this future, so it outlives the transfer.
+        let result = self.inner_write(unsafe { core::slice::from_raw_parts(ptr, len) }).await;
+        (result, buffer)
+    }
+
+    async fn inner_write(&mut self, buffer: &[u8]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
+        let ch = &mut self.tx_dma;
+        let request = ch.request();
+        T::regs().cr3().modify(|reg| {
+            reg.set_dmat(true);
+        });
+        // If we don't assign future to a variable, the data register pointer
+        // is held across an await and makes the future non-Send.
+        let mut transfer = unsafe { Transfer::new_write(ch, request, buffer, tdr(T::regs()), Default::default()) };
+        let result = wait_timeout(&mut transfer, self.timeout).await;
+        drop(transfer);
+        result.map_err(|_| Error::Timeout)?;
+        self.flush_before_idle();
+        Ok(())
+    }
+}
+
+impl<'d, T: BasicInstance> UartTx<'d, T, Blocking> {
+    /// Create a new tx-only UART for blocking use.
+    ///
+    /// The driver uses neither interrupts nor DMA, so it has no async methods. It needs the clocks
+    /// configured by [`crate::init`], but not the executor, the time driver or any interrupt, so it
+    /// can be used right after `init`, before the executor is started, or to print a crash log from
+    /// a panic or hardfault handler, with peripherals taken with `steal()`.
+    pub fn new_blocking(
+        peri: impl Peripheral<P = T> + 'd,
+        tx: impl Peripheral<P = impl TxPin<T>> + 'd,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        T::enable_and_reset();
+
+        let this = Self::new_inner(peri, tx, Blocking, config)?;
+
+        // No interrupt handler is bound.
+        T::Interrupt::disable();
+
+        Ok(this)
+    }
+}
+
+impl<'d, T: BasicInstance> UartTx<'d, T, NoDma> {
+    /// Initiate an asynchronous UART write, driven by the TXE interrupt
+    pub async fn write(&mut self, buffer: &[u8]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
+        write_interrupt(T::regs(), T::state(), buffer).await;
+        self.flush_before_idle();
+        Ok(())
+    }
 }
 
 impl<'d, T: BasicInstance, RxDma> UartRx<'d, T, RxDma> {
@@ -413,79 +587,48 @@ impl<'d, T: BasicInstance, RxDma> UartRx<'d, T, RxDma> {
             detect_previous_overrun: config.detect_previous_overrun,
             #[cfg(any(usart_v1, usart_v2))]
             buffered_sr: stm32_metapac::usart::regs::Sr(0),
+            auto_idle: AutoIdle::new(),
+            timeout: None,
         })
     }
 
+    /// Enable or disable the auto idle mode.
+    ///
+    /// In the auto idle mode, the clock of the peripheral is gated whenever no read is in flight,
+    /// and enabled again by the next one, cutting the idle current. The data received between the
+    /// reads is lost. It is disabled by default.
+    pub fn set_auto_idle(&mut self, enabled: bool) {
+        self.auto_idle.set(enabled);
+    }
+
+    /// Set the timeout of the DMA reads, or `None` to wait forever, which is the default.
+    ///
+    /// A read that doesn't finish in time, e.g. because the peer never sends, stops the DMA channel
+    /// and returns [`Error::Timeout`]. The bytes received until then are in the buffer.
+    #[cfg(feature = "time")]
+    pub fn set_timeout(&mut self, timeout: Option<embassy_time::Duration>) {
+        self.timeout = timeout;
+    }
+
     /// Reconfigure the driver
     pub fn set_config(&mut self, config: &Config) -> Result<(), ConfigError> {
+        let _clock = self.auto_idle.wake();
         reconfigure::<T>(config)
     }
 
     #[cfg(any(usart_v1, usart_v2))]
     fn check_rx_flags(&mut self) -> Result<bool, Error> {
-        let r = T::regs();
-        loop {
-            // Handle all buffered error flags.
-            if self.buffered_sr.pe() {
-                self.buffered_sr.set_pe(false);
-                return Err(Error::Parity);
-            } else if self.buffered_sr.fe() {
-                self.buffered_sr.set_fe(false);
-                return Err(Error::Framing);
-            } else if self.buffered_sr.ne() {
-                self.buffered_sr.set_ne(false);
-                return Err(Error::Noise);
-            } else if self.buffered_sr.ore() {
-                self.buffered_sr.set_ore(false);
-                return Err(Error::Overrun);
-            } else if self.buffered_sr.rxne() {
-                self.buffered_sr.set_rxne(false);
-                return Ok(true);
-            } else {
-                // No error flags from previous iterations were set: Check the actual status register
-                let sr = r.sr().read();
-                if !sr.rxne() {
-                    return Ok(false);
-                }
-
-                // Buffer the status register and let the loop handle the error flags.
-                self.buffered_sr = sr;
-            }
-        }
+        check_rx_flags(T::regs(), &mut self.buffered_sr)
     }
 
     #[cfg(any(usart_v3, usart_v4))]
     fn check_rx_flags(&mut self) -> Result<bool, Error> {
-        let r = T::regs();
-        let sr = r.isr().read();
-        if sr.pe() {
-            r.icr().write(|w| w.set_pe(true));
-            return Err(Error::Parity);
-        } else if sr.fe() {
-            r.icr().write(|w| w.set_fe(true));
-            return Err(Error::Framing);
-        } else if sr.ne() {
-            r.icr().write(|w| w.set_ne(true));
-            return Err(Error::Noise);
-        } else if sr.ore() {
-            r.icr().write(|w| w.set_ore(true));
-            return Err(Error::Overrun);
-        }
-        Ok(sr.rxne())
-    }
-
-    /// Initiate an asynchronous UART read
-    pub async fn read(&mut self, buffer: &mut [u8]) -> Result<(), Error>
-    where
-        RxDma: crate::usart::RxDma<T>,
-    {
-        self.inner_read(buffer, false).await?;
-
-        Ok(())
+        check_rx_flags(T::regs())
     }
 
     /// Read a single u8 if there is one available, otherwise return WouldBlock
     pub fn nb_read(&mut self) -> Result<u8, nb::Error<Error>> {
+        let _clock = self.auto_idle.wake();
         let r = T::regs();
         if self.check_rx_flags()? {
             Ok(unsafe { rdr(r).read_volatile() })
@@ -496,6 +639,7 @@ impl<'d, T: BasicInstance, RxDma> UartRx<'d, T, RxDma> {
 
     /// Perform a blocking read into `buffer`
     pub fn blocking_read(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        let _clock = self.auto_idle.wake();
         let r = T::regs();
         for b in buffer {
             while !self.check_rx_flags()? {}
@@ -503,23 +647,46 @@ impl<'d, T: BasicInstance, RxDma> UartRx<'d, T, RxDma> {
         }
         Ok(())
     }
+}
+
+impl<'d, T: BasicInstance, RxDma: crate::usart::RxDma<T>> UartRx<'d, T, RxDma> {
+    /// Initiate an asynchronous UART read
+    ///
+    /// The DMA keeps writing `buffer` if this future is leaked instead of dropped, prefer
+    /// [`read_buffer`](Self::read_buffer).
+    #[deprecated(note = "the DMA keeps writing `buffer` if the future is leaked, use `read_buffer`")]
+    pub async fn read(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        self.inner_read(buffer, false).await?;
+
+        Ok(())
+    }
 
     /// Initiate an asynchronous read with idle line detection enabled
-    pub async fn read_until_idle(&mut self, buffer: &mut [u8]) -> Result<usize, Error>
-    where
-        RxDma: crate::usart::RxDma<T>,
-    {
+    ///
+    /// When the line goes idle, the DMA is stopped before the number of bytes received is
+    /// returned, so that no byte is written to `buffer` after it.
+    pub async fn read_until_idle(&mut self, buffer: &mut [u8]) -> Result<usize, Error> {
         self.inner_read(buffer, true).await
     }
 
+    /// Initiate an asynchronous UART read into a buffer owned by the transfer, and give it back
+    ///
+    /// Unlike [`read`](Self::read), the buffer stays valid if the future is leaked, see
+    /// [`crate::dma::buffer`].
+    pub async fn read_buffer<B: DmaReadBuffer<Word = u8>>(&mut self, mut buffer: B) -> (Result<(), Error>, B) {
+        let (ptr, len) = buffer.dma_read_buffer();
+        // Safety: the buffer is owned by this future, so it outlives the transfer.
+        let result = self
+            .inner_read(unsafe { core::slice::from_raw_parts_mut(ptr, len) }, false)
+            .await;
+        (result.map(drop), buffer)
+    }
+
     async fn inner_read_run(
         &mut self,
         buffer: &mut [u8],
         enable_idle_line_detection: bool,
-    ) -> Result<ReadCompletionEvent, Error>
-    where
-        RxDma: crate::usart::RxDma<T>,
-    {
+    ) -> Result<ReadCompletionEvent, Error> {
         let r = T::regs();
 
         // make sure USART state is restored to neutral state when this future is dropped
@@ -550,7 +717,7 @@ impl<'d, T: BasicInstance, RxDma> UartRx<'d, T, RxDma> {
         // Start USART DMA
         // will not do anything yet because DMAR is not yet set
         // future which will complete when DMA Read request completes
-        let transfer = unsafe { Transfer::new_read(ch, request, rdr(T::regs()), buffer, Default::default()) };
+        let mut transfer = unsafe { Transfer::new_read(ch, request, rdr(T::regs()), buffer, Default::default()) };
 
         // clear ORE flag just before enabling DMA Rx Request: can be mandatory for the second transfer
         if !self.detect_previous_overrun {
@@ -673,30 +840,42 @@ impl<'d, T: BasicInstance, RxDma> UartRx<'d, T, RxDma> {
         });
 
         // wait for the first of DMA request or idle line detected to completes
-        // select consumes its arguments
         // when transfer is dropped, it will stop the DMA request
-        let r = match select(transfer, abort).await {
+        let idle = match select(wait_timeout(&mut transfer, self.timeout), abort).await {
             // DMA transfer completed first
-            Either::Left(((), _)) => Ok(ReadCompletionEvent::DmaCompleted),
+            Either::Left((Ok(()), _)) => Ok(false),
+
+            // DMA transfer timed out, and was stopped
+            Either::Left((Err(_), _)) => Err(Error::Timeout),
 
             // Idle line detected first
-            Either::Right((Ok(()), transfer)) => Ok(ReadCompletionEvent::Idle(
-                buffer_len - transfer.get_remaining_transfers() as usize,
-            )),
+            Either::Right((Ok(()), _)) => Ok(true),
 
             // error occurred
             Either::Right((Err(e), _)) => Err(e),
         };
 
+        let r = match idle {
+            Ok(false) => Ok(ReadCompletionEvent::DmaCompleted),
+            Ok(true) => {
+                // Stop the DMA before counting the received bytes, so none is written meanwhile.
+                transfer.request_stop();
+                while transfer.is_running() {}
+                Ok(ReadCompletionEvent::Idle(
+                    buffer_len - transfer.get_remaining_transfers() as usize,
+                ))
+            }
+            Err(e) => Err(e),
+        };
+
+        drop(transfer);
         drop(on_drop);
 
         r
     }
 
-    async fn inner_read(&mut self, buffer: &mut [u8], enable_idle_line_detection: bool) -> Result<usize, Error>
-    where
-        RxDma: crate::usart::RxDma<T>,
-    {
+    async fn inner_read(&mut self, buffer: &mut [u8], enable_idle_line_detection: bool) -> Result<usize, Error> {
+        let _clock = self.auto_idle.wake();
         if buffer.is_empty() {
             return Ok(0);
         } else if buffer.len() > 0xFFFF {
@@ -716,14 +895,162 @@ impl<'d, T: BasicInstance, RxDma> UartRx<'d, T, RxDma> {
     }
 }
 
+impl<'d, T: BasicInstance> UartRx<'d, T, Blocking> {
+    /// Create a new rx-only UART for blocking use.
+    ///
+    /// The driver uses neither interrupts nor DMA, so it has no async methods. It needs the clocks
+    /// configured by [`crate::init`], but not the executor, the time driver or any interrupt, so it
+    /// can be used right after `init`, before the executor is started, or to print a crash log from
+    /// a panic or hardfault handler, with peripherals taken with `steal()`.
+    pub fn new_blocking(
+        peri: impl Peripheral<P = T> + 'd,
+        rx: impl Peripheral<P = impl RxPin<T>> + 'd,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        T::enable_and_reset();
+
+        let this = Self::new_inner(peri, rx, Blocking, config)?;
+
+        // No interrupt handler is bound.
+        T::Interrupt::disable();
+
+        Ok(this)
+    }
+}
+
+impl<'d, T: BasicInstance> UartRx<'d, T, NoDma> {
+    /// Initiate an asynchronous UART read, driven by the RXNE interrupt
+    pub async fn read(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        self.interrupt_read(buffer, false).await?;
+
+        Ok(())
+    }
+
+    /// Initiate an asynchronous read with idle line detection enabled, driven by the RXNE interrupt
+    pub async fn read_until_idle(&mut self, buffer: &mut [u8]) -> Result<usize, Error> {
+        self.interrupt_read(buffer, true).await
+    }
+
+    async fn interrupt_read(&mut self, buffer: &mut [u8], enable_idle_line_detection: bool) -> Result<usize, Error> {
+        let _clock = self.auto_idle.wake();
+        read_interrupt(T::regs(), T::state(), buffer, enable_idle_line_detection, || {
+            self.check_rx_flags()
+        })
+        .await
+    }
+}
+
+/// Write `buffer`, driven by the TXE interrupt.
+async fn write_interrupt(r: Regs, s: &sealed::State, buffer: &[u8]) {
+    // make sure the TXE interrupt is disabled when this future is dropped
+    let _on_drop = OnDrop::new(move || {
+        r.cr1().modify(|w| w.set_txeie(false));
+    });
+
+    let mut written = 0;
+    poll_fn(|cx| {
+        s.tx_waker.register(cx.waker());
+
+        while written < buffer.len() && sr(r).read().txe() {
+            unsafe { tdr(r).write_volatile(buffer[written]) };
+            written += 1;
+        }
+        if written == buffer.len() {
+            return Poll::Ready(());
+        }
+
+        // The interrupt handler disables the interrupt when it fires.
+        r.cr1().modify(|w| w.set_txeie(true));
+        Poll::Pending
+    })
+    .await
+}
+
+/// Read into `buffer`, driven by the RXNE interrupt, until it is full, or until the line goes idle
+/// after a byte if `enable_idle_line_detection`.
+async fn read_interrupt(
+    r: Regs,
+    s: &sealed::State,
+    buffer: &mut [u8],
+    enable_idle_line_detection: bool,
+    mut check_rx_flags: impl FnMut() -> Result<bool, Error>,
+) -> Result<usize, Error> {
+    if buffer.is_empty() {
+        return Ok(0);
+    }
+
+    // make sure USART state is restored to neutral state when this future is dropped
+    let _on_drop = OnDrop::new(move || {
+        r.cr1().modify(|w| {
+            // disable RXNE interrupt
+            w.set_rxneie(false);
+            // disable idle line interrupt
+            w.set_idleie(false);
+        });
+    });
+
+    if enable_idle_line_detection {
+        // clear a stale idle flag, unless a byte is waiting
+        let sr = sr(r).read();
+        if sr.idle() && !sr.rxne() {
+            // This read also clears the idle flag on v1.
+            unsafe { rdr(r).read_volatile() };
+            clear_interrupt_flags(r, sr);
+        }
+    }
+
+    let mut received = 0;
+    poll_fn(|cx| {
+        s.rx_waker.register(cx.waker());
+
+        // Sample the idle flag first: the bytes received before it are still in the data register.
+        let idle = enable_idle_line_detection && sr(r).read().idle();
+
+        while received < buffer.len() {
+            match check_rx_flags() {
+                Ok(true) => {
+                    buffer[received] = unsafe { rdr(r).read_volatile() };
+                    received += 1;
+                }
+                Ok(false) => break,
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+
+        if received == buffer.len() {
+            return Poll::Ready(Ok(received));
+        }
+
+        if idle && received > 0 {
+            let sr = sr(r).read();
+            if !sr.rxne() {
+                // This read also clears the idle flag on v1.
+                unsafe { rdr(r).read_volatile() };
+                clear_interrupt_flags(r, sr);
+            }
+            return Poll::Ready(Ok(received));
+        }
+
+        // The interrupt handler disables the interrupts when they fire.
+        r.cr1().modify(|w| {
+            w.set_rxneie(true);
+            w.set_idleie(enable_idle_line_detection);
+        });
+        Poll::Pending
+    })
+    .await
+}
+
 impl<'d, T: BasicInstance, TxDma> Drop for UartTx<'d, T, TxDma> {
     fn drop(&mut self) {
+        self.auto_idle.set(false);
         T::disable();
     }
 }
 
 impl<'d, T: BasicInstance, TxDma> Drop for UartRx<'d, T, TxDma> {
     fn drop(&mut self) {
+        self.auto_idle.set(false);
         T::disable();
     }
 }
@@ -775,6 +1102,9 @@ impl<'d, T: BasicInstance, TxDma, RxDma> Uart<'d, T, TxDma, RxDma> {
 
     #[cfg(not(any(usart_v1, usart_v2)))]
     /// Create a new bidirectional UART with a driver-enable pin
+    ///
+    /// The pin is active while transmitting, to drive an RS-485 transceiver. The delays around
+    /// the transmission are set by [`Config::de_assertion_time`] and [`Config::de_deassertion_time`].
     pub fn new_with_de(
         peri: impl Peripheral<P = T> + 'd,
         rx: impl Peripheral<P = impl RxPin<T>> + 'd,
@@ -914,6 +1244,8 @@ impl<'d, T: BasicInstance, TxDma, RxDma> Uart<'d, T, TxDma, RxDma> {
             tx: UartTx {
                 tx_dma,
                 phantom: PhantomData,
+                auto_idle: AutoIdle::new(),
+                timeout: None,
             },
             rx: UartRx {
                 _peri: peri,
@@ -921,18 +1253,12 @@ impl<'d, T: BasicInstance, TxDma, RxDma> Uart<'d, T, TxDma, RxDma> {
                 detect_previous_overrun: config.detect_previous_overrun,
                 #[cfg(any(usart_v1, usart_v2))]
                 buffered_sr: stm32_metapac::usart::regs::Sr(0),
+                auto_idle: AutoIdle::new(),
+                timeout: None,
             },
         })
     }
 
-    /// Initiate an asynchronous write
-    pub async fn write(&mut self, buffer: &[u8]) -> Result<(), Error>
-    where
-        TxDma: crate::usart::TxDma<T>,
-    {
-        self.tx.write(buffer).await
-    }
-
     /// Perform a blocking write
     pub fn blocking_write(&mut self, buffer: &[u8]) -> Result<(), Error> {
         self.tx.blocking_write(buffer)
@@ -943,12 +1269,9 @@ impl<'d, T: BasicInstance, TxDma, RxDma> Uart<'d, T, TxDma, RxDma> {
         self.tx.blocking_flush()
     }
 
-    /// Initiate an asynchronous read into `buffer`
-    pub async fn read(&mut self, buffer: &mut [u8]) -> Result<(), Error>
-    where
-        RxDma: crate::usart::RxDma<T>,
-    {
-        self.rx.read(buffer).await
+    /// Send a break character, after the character being transmitted.
+    pub fn send_break(&mut self) {
+        self.tx.send_break()
     }
 
     /// Read a single `u8` or return `WouldBlock`
@@ -961,12 +1284,21 @@ impl<'d, T: BasicInstance, TxDma, RxDma> Uart<'d, T, TxDma, RxDma> {
         self.rx.blocking_read(buffer)
     }
 
-    /// Initiate an an asynchronous read with idle line detection enabled
-    pub async fn read_until_idle(&mut self, buffer: &mut [u8]) -> Result<usize, Error>
-    where
-        RxDma: crate::usart::RxDma<T>,
-    {
-        self.rx.read_until_idle(buffer).await
+    /// Enable or disable the auto idle mode, of both halves.
+    ///
+    /// See [`UartTx::set_auto_idle`] and [`UartRx::set_auto_idle`].
+    pub fn set_auto_idle(&mut self, enabled: bool) {
+        self.tx.set_auto_idle(enabled);
+        self.rx.set_auto_idle(enabled);
+    }
+
+    /// Set the timeout of the DMA transfers, of both halves.
+    ///
+    /// See [`UartTx::set_timeout`] and [`UartRx::set_timeout`].
+    #[cfg(feature = "time")]
+    pub fn set_timeout(&mut self, timeout: Option<embassy_time::Duration>) {
+        self.tx.set_timeout(timeout);
+        self.rx.set_timeout(timeout);
     }
 
     /// Split the Uart into a transmitter and receiver, which is
@@ -977,6 +1309,127 @@ impl<'d, T: BasicInstance, TxDma, RxDma> Uart<'d, T, TxDma, RxDma> {
     }
 }
 
+impl<'d, T: BasicInstance> Uart<'d, T, Blocking, Blocking> {
+    /// Create a new bidirectional UART for blocking use.
+    ///
+    /// The driver uses neither interrupts nor DMA, so it has no async methods. It needs the clocks
+    /// configured by [`crate::init`], but not the executor, the time driver or any interrupt, so it
+    /// can be used right after `init`, before the executor is started, or to print a crash log from
+    /// a panic or hardfault handler, with peripherals taken with `steal()`.
+    pub fn new_blocking(
+        peri: impl Peripheral<P = T> + 'd,
+        rx: impl Peripheral<P = impl RxPin<T>> + 'd,
+        tx: impl Peripheral<P = impl TxPin<T>> + 'd,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        // UartRx and UartTx have one refcount ea.
+        T::enable_and_reset();
+        T::enable_and_reset();
+
+        let this = Self::new_inner_configure(peri, rx, tx, Blocking, Blocking, config)?;
+
+        // No interrupt handler is bound.
+        T::Interrupt::disable();
+
+        Ok(this)
+    }
+}
+
+impl<'d, T: BasicInstance, TxDma: crate::usart::TxDma<T>, RxDma> Uart<'d, T, TxDma, RxDma> {
+    /// Initiate an asynchronous write
+    ///
+    /// The DMA keeps reading `buffer` if this future is leaked instead of dropped, prefer
+    /// [`write_buffer`](Self::write_buffer).
+    #[deprecated(note = "the DMA keeps reading `buffer` if the future is leaked, use `write_buffer`")]
+    pub async fn write(&mut self, buffer: &[u8]) -> Result<(), Error> {
+        self.tx.inner_write(buffer).await
+    }
+
+    /// Initiate an asynchronous write of a buffer owned by the transfer, and give it back
+    pub async fn write_buffer<B: DmaWriteBuffer<Word = u8>>(&mut self, buffer: B) -> (Result<(), Error>, B) {
+        self.tx.write_buffer(buffer).await
+    }
+}
+
+impl<'d, T: BasicInstance, RxDma> Uart<'d, T, NoDma, RxDma> {
+    /// Initiate an asynchronous write, driven by the TXE interrupt
+    pub async fn write(&mut self, buffer: &[u8]) -> Result<(), Error> {
+        self.tx.write(buffer).await
+    }
+}
+
+impl<'d, T: BasicInstance, TxDma, RxDma: crate::usart::RxDma<T>> Uart<'d, T, TxDma, RxDma> {
+    /// Initiate an asynchronous read into `buffer`
+    ///
+    /// The DMA keeps writing `buffer` if this future is leaked instead of dropped, prefer
+    /// [`read_buffer`](Self::read_buffer).
+    #[deprecated(note = "the DMA keeps writing `buffer` if the future is leaked, use `read_buffer`")]
+    pub async fn read(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        self.rx.inner_read(buffer, false).await?;
+        Ok(())
+    }
+
+    /// Initiate an an asynchronous read with idle line detection enabled
+    pub async fn read_until_idle(&mut self, buffer: &mut [u8]) -> Result<usize, Error> {
+        self.rx.read_until_idle(buffer).await
+    }
+
+    /// Initiate an asynchronous read into a buffer owned by the transfer, and give it back
+    pub async fn read_buffer<B: DmaReadBuffer<Word = u8>>(&mut self, buffer: B) -> (Result<(), Error>, B) {
+        self.rx.read_buffer(buffer).await
+    }
+}
+
+impl<'d, T: BasicInstance, TxDma> Uart<'d, T, TxDma, NoDma> {
+    /// Initiate an asynchronous read into `buffer`, driven by the RXNE interrupt
+    pub async fn read(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        self.rx.read(buffer).await
+    }
+
+    /// Initiate an an asynchronous read with idle line detection enabled, driven by the RXNE interrupt
+    pub async fn read_until_idle(&mut self, buffer: &mut [u8]) -> Result<usize, Error> {
+        self.rx.read_until_idle(buffer).await
+    }
+}
+
+const fn calculate_brr(baud: u32, pclk: u32, presc: u32, mul: u32) -> u32 {
+    // The calculation to be done to get the BRR is `mul * pclk / presc / baud`
+    // To do this in 32-bit only we can't multiply `mul` and `pclk`
+    let clock = pclk / presc;
+
+    // The mul is applied as the last operation to prevent overflow
+    let brr = clock / baud * mul;
+
+    // The BRR calculation will be a bit off because of integer rounding.
+    // Because we multiplied our inaccuracy with mul, our rounding now needs to be in proportion to mul.
+    let rounding = ((clock % baud) * mul + (baud / 2)) / baud;
+
+    brr + rounding
+}
+
+/// Compute the BRR value of a UART (not LPUART) clocked at `pclk`, with 16x oversampling and no
+/// prescaler.
+///
+/// This is a `const fn`: when the clocks are known, the value can be computed at compile time, and
+/// an unachievable baudrate then fails the build, as does a too large error:
+///
+/// ```rust,ignore
+/// const BRR: u32 = usart::brr(Hertz::mhz(80), 115_200);
+/// const _: () = assert!(usart::baudrate_error_ppm(Hertz::mhz(80), 115_200) <= 1_000);
+/// ```
+pub const fn brr(pclk: Hertz, baudrate: u32) -> u32 {
+    assert!(baudrate > 0);
+    let brr = calculate_brr(baudrate, pclk.0, 1, 1);
+    assert!(brr >= 0x10 && brr < 0x1_0000);
+    brr
+}
+
+/// Compute the error of the actual baudrate from `baudrate`, in ppm, with the BRR value of [`brr`].
+pub const fn baudrate_error_ppm(pclk: Hertz, baudrate: u32) -> u32 {
+    let actual = pclk.0 / brr(pclk, baudrate);
+    (actual.abs_diff(baudrate) as u64 * 1_000_000 / baudrate as u64) as u32
+}
+
 fn reconfigure<T: BasicInstance>(config: &Config) -> Result<(), ConfigError> {
     T::Interrupt::disable();
     let r = T::regs();
@@ -1002,6 +1455,11 @@ fn configure(
         return Err(ConfigError::RxOrTxNotEnabled);
     }
 
+    #[cfg(not(any(usart_v1, usart_v2)))]
+    if config.de_assertion_time > 31 || config.de_deassertion_time > 31 {
+        return Err(ConfigError::DeTimeTooLong);
+    }
+
     #[cfg(not(usart_v4))]
     static DIVS: [(u16, ()); 1] = [(1, ())];
 
@@ -1027,21 +1485,6 @@ fn configure(
         Kind::Uart => (1, 0x10, 0x1_0000),
     };
 
-    fn calculate_brr(baud: u32, pclk: u32, presc: u32, mul: u32) -> u32 {
-        // The calculation to be done to get the BRR is `mul * pclk / presc / baud`
-        // To do this in 32-bit only we can't multiply `mul` and `pclk`
-        let clock = pclk / presc;
-
-        // The mul is applied as the last operation to prevent overflow
-        let brr = clock / baud * mul;
-
-        // The BRR calculation will be a bit off because of integer rounding.
-        // Because we multiplied our inaccuracy with mul, our rounding now needs to be in proportion to mul.
-        let rounding = ((clock % baud) * mul + (baud / 2)) / baud;
-
-        brr + rounding
-    }
-
     // UART must be disabled during configuration.
     r.cr1().modify(|w| {
         w.set_ue(false);
@@ -1141,6 +1584,11 @@ fn configure(
         w.set_over8(vals::Over8::from_bits(over8 as _));
         #[cfg(usart_v4)]
         w.set_fifoen(true);
+        #[cfg(not(any(usart_v1, usart_v2)))]
+        {
+            w.set_deat(config.de_assertion_time);
+            w.set_dedt(config.de_deassertion_time);
+        }
     });
 
     Ok(())
@@ -1188,6 +1636,7 @@ impl embedded_hal_nb::serial::Error for Error {
             Self::Overrun => embedded_hal_nb::serial::ErrorKind::Overrun,
             Self::Parity => embedded_hal_nb::serial::ErrorKind::Parity,
             Self::BufferTooLong => embedded_hal_nb::serial::ErrorKind::Other,
+            Self::Timeout => embedded_hal_nb::serial::ErrorKind::Other,
         }
     }
 }
@@ -1292,7 +1741,7 @@ where
     TxDma: self::TxDma<T>,
 {
     async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
-        self.write(buf).await?;
+        self.tx.inner_write(buf).await?;
         Ok(buf.len())
     }
 
@@ -1305,6 +1754,34 @@ impl<T, TxDma> embedded_io_async::Write for UartTx<'_, T, TxDma>
 where
     T: BasicInstance,
     TxDma: self::TxDma<T>,
+{
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.inner_write(buf).await?;
+        Ok(buf.len())
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.blocking_flush()
+    }
+}
+
+impl<T, RxDma> embedded_io_async::Write for Uart<'_, T, NoDma, RxDma>
+where
+    T: BasicInstance,
+{
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.write(buf).await?;
+        Ok(buf.len())
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.blocking_flush()
+    }
+}
+
+impl<T> embedded_io_async::Write for UartTx<'_, T, NoDma>
+where
+    T: BasicInstance,
 {
     async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
         self.write(buf).await?;
@@ -1318,6 +1795,19 @@ where
 
 pub use buffered::*;
 
+mod any;
+pub use any::AnyUart;
+
+mod queue;
+pub use queue::UartTxQueue;
+
+pub mod lin;
+
+#[cfg(feature = "time")]
+pub mod dmx;
+
+pub mod rc;
+
 pub use crate::usart::buffered::InterruptHandler as BufferedInterruptHandler;
 mod buffered;
 
@@ -1328,6 +1818,52 @@ pub use ringbuffered::RingBufferedUartRx;
 
 use self::sealed::Kind;
 
+#[cfg(feature = "debug-dump")]
+impl<'d, T: BasicInstance, TxDma, RxDma> Uart<'d, T, TxDma, RxDma> {
+    /// Snapshot the registers of the peripheral into `buf`, for bug reports.
+    pub fn debug_dump<'b>(&self, buf: &'b mut [Register]) -> &'b [Register] {
+        let _clock = self.tx.auto_idle.wake();
+        debug_dump(T::regs(), buf)
+    }
+}
+
+#[cfg(feature = "debug-dump")]
+impl<'d, T: BasicInstance, TxDma> UartTx<'d, T, TxDma> {
+    /// Snapshot the registers of the peripheral into `buf`, for bug reports.
+    pub fn debug_dump<'b>(&self, buf: &'b mut [Register]) -> &'b [Register] {
+        let _clock = self.auto_idle.wake();
+        debug_dump(T::regs(), buf)
+    }
+}
+
+#[cfg(feature = "debug-dump")]
+impl<'d, T: BasicInstance, RxDma> UartRx<'d, T, RxDma> {
+    /// Snapshot the registers of the peripheral into `buf`, for bug reports.
+    pub fn debug_dump<'b>(&self, buf: &'b mut [Register]) -> &'b [Register] {
+        let _clock = self.auto_idle.wake();
+        debug_dump(T::regs(), buf)
+    }
+}
+
+#[cfg(feature = "debug-dump")]
+fn debug_dump(r: Regs, buf: &mut [Register]) -> &[Register] {
+    #[cfg(any(usart_v1, usart_v2))]
+    let sr_name = "SR";
+    #[cfg(any(usart_v3, usart_v4))]
+    let sr_name = "ISR";
+
+    crate::debug_dump::dump(
+        buf,
+        &[
+            (sr_name, sr(r).read().0),
+            ("CR1", r.cr1().read().0),
+            ("CR2", r.cr2().read().0),
+            ("CR3", r.cr3().read().0),
+            ("BRR", r.brr().read().0),
+        ],
+    )
+}
+
 #[cfg(any(usart_v1, usart_v2))]
 fn tdr(r: crate::pac::usart::Usart) -> *mut u8 {
     r.dr().as_ptr() as _
@@ -1370,6 +1906,68 @@ fn clear_interrupt_flags(r: Regs, sr: regs::Isr) {
     r.icr().write(|w| *w = regs::Icr(sr.0));
 }
 
+#[cfg(any(usart_v1, usart_v2))]
+fn check_rx_flags(r: Regs, buffered_sr: &mut regs::Sr) -> Result<bool, Error> {
+    loop {
+        // Handle all buffered error flags.
+        if buffered_sr.pe() {
+            buffered_sr.set_pe(false);
+            return Err(Error::Parity);
+        } else if buffered_sr.fe() {
+            buffered_sr.set_fe(false);
+            return Err(Error::Framing);
+        } else if buffered_sr.ne() {
+            buffered_sr.set_ne(false);
+            return Err(Error::Noise);
+        } else if buffered_sr.ore() {
+            buffered_sr.set_ore(false);
+            return Err(Error::Overrun);
+        } else if buffered_sr.rxne() {
+            buffered_sr.set_rxne(false);
+            return Ok(true);
+        } else {
+            // No error flags from previous iterations were set: Check the actual status register
+            let sr = r.sr().read();
+            if !sr.rxne() {
+                return Ok(false);
+            }
+
+            // Buffer the status register and let the loop handle the error flags.
+            *buffered_sr = sr;
+        }
+    }
+}
+
+#[cfg(any(usart_v3, usart_v4))]
+fn check_rx_flags(r: Regs) -> Result<bool, Error> {
+    let sr = r.isr().read();
+    if sr.pe() {
+        r.icr().write(|w| w.set_pe(true));
+        return Err(Error::Parity);
+    } else if sr.fe() {
+        r.icr().write(|w| w.set_fe(true));
+        return Err(Error::Framing);
+    } else if sr.ne() {
+        r.icr().write(|w| w.set_ne(true));
+        return Err(Error::Noise);
+    } else if sr.ore() {
+        r.icr().write(|w| w.set_ore(true));
+        return Err(Error::Overrun);
+    }
+    Ok(sr.rxne())
+}
+
+#[cfg(any(usart_v1, usart_v2))]
+fn is_sending_break(r: Regs) -> bool {
+    // SBK is cleared by hardware during the stop bit of the break character.
+    r.cr1().read().sbk()
+}
+
+#[cfg(any(usart_v3, usart_v4))]
+fn is_sending_break(r: Regs) -> bool {
+    r.isr().read().sbkf()
+}
+
 pub(crate) mod sealed {
     use embassy_sync::waitqueue::AtomicWaker;
 
@@ -1385,6 +1983,7 @@ pub(crate) mod sealed {
     pub struct State {
         pub rx_waker: AtomicWaker,
         pub tx_waker: AtomicWaker,
+        pub tx_queue: super::queue::QueueSlot,
     }
 
     impl State {
@@ -1392,6 +1991,7 @@ pub(crate) mod sealed {
             Self {
                 rx_waker: AtomicWaker::new(),
                 tx_waker: AtomicWaker::new(),
+                tx_queue: super::queue::QueueSlot::new(),
             }
         }
     }
@@ -1469,3 +2069,16 @@ foreach_interrupt!(
         impl FullInstance for peripherals::$inst {}
     };
 );
+
+#[cfg(test)]
+mod tests {
+    use super::{baudrate_error_ppm, brr};
+    use crate::time::Hertz;
+
+    #[test]
+    fn can_compute_brr() {
+        assert_eq!(brr(Hertz::mhz(80), 115_200), 694);
+        assert_eq!(brr(Hertz::mhz(16), 9_600), 1_667);
+        assert_eq!(baudrate_error_ppm(Hertz::mhz(80), 115_200), 633);
+    }
+}
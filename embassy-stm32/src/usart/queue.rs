@@ -0,0 +1,224 @@
+//! Queue of UART writes, chained in back-to-back DMA transfers.
+
+use core::cell::{Cell, RefCell, UnsafeCell};
+use core::future::poll_fn;
+use core::task::Poll;
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex as BlockingMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_sync::waitqueue::AtomicWaker;
+
+#[cfg(any(usart_v1, usart_v2))]
+use super::sr;
+use super::{tdr, BasicInstance, Regs, TxDma, UartTx};
+use crate::dma::Transfer;
+
+/// Queue of UART writes, shared by several tasks.
+///
+/// A [`UartTx`] handed over to the queue with [`UartTx::into_queue`] sends the writes queued by
+/// any task with DMA. The writes are copied once, to the queue, and the DMA reads them from there.
+/// When a transfer completes, the transmission complete (TC) interrupt starts the next one right
+/// away with all the bytes queued meanwhile, so many small frames are sent back to back, without
+/// waiting for a task to be polled, and the writers don't wait for the end of their frame.
+///
+/// ```rust,ignore
+/// static QUEUE: UartTxQueue<USART1, DMA1_CH4, 256> = UartTxQueue::new();
+///
+/// let tx = UartTx::new(p.USART1, Irqs, p.PA9, p.DMA1_CH4, Config::default()).unwrap();
+/// tx.into_queue(&QUEUE);
+///
+/// // In any task:
+/// QUEUE.write(b"frame").await;
+/// ```
+pub struct UartTxQueue<T: BasicInstance, Dma: TxDma<T>, const N: usize> {
+    buf: UnsafeCell<[u8; N]>,
+    state: BlockingMutex<CriticalSectionRawMutex, RefCell<QueueState<T, Dma>>>,
+    writer: Mutex<CriticalSectionRawMutex, ()>,
+    space: AtomicWaker,
+}
+
+// Safety: `buf` is only accessed in critical sections, except for the bytes being sent, which
+// are only read, by the DMA.
+unsafe impl<T: BasicInstance, Dma: TxDma<T>, const N: usize> Sync for UartTxQueue<T, Dma, N> {}
+
+struct QueueState<T: BasicInstance, Dma: TxDma<T>> {
+    tx: Option<UartTx<'static, T, Dma>>,
+    transfer: Option<Transfer<'static, Dma>>,
+    /// Position of the queued bytes in `buf`, wrapping around.
+    start: usize,
+    len: usize,
+    /// Number of queued bytes sent by `transfer`.
+    in_flight: usize,
+}
+
+impl<T: BasicInstance, Dma: TxDma<T>, const N: usize> UartTxQueue<T, Dma, N> {
+    /// Create a new queue of `N` bytes.
+    pub const fn new() -> Self {
+        Self {
+            buf: UnsafeCell::new([0; N]),
+            state: BlockingMutex::new(RefCell::new(QueueState {
+                tx: None,
+                transfer: None,
+                start: 0,
+                len: 0,
+                in_flight: 0,
+            })),
+            writer: Mutex::new(()),
+            space: AtomicWaker::new(),
+        }
+    }
+
+    /// Queue a write, waiting for room in the queue.
+    ///
+    /// The bytes of a write are never interleaved with the bytes of a concurrent one. The write is
+    /// queued at once when there is room for all of it, so it is cancel safe: if the future is
+    /// dropped, none of `buffer` is sent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffer` is longer than the queue.
+    pub async fn write(&self, buffer: &[u8]) {
+        assert!(buffer.len() <= N, "write longer than the queue");
+        // Only the writer holding the lock waits for room.
+        let _writer = self.writer.lock().await;
+        poll_fn(|cx| {
+            self.space.register(cx.waker());
+            self.state.lock(|state| {
+                let mut state = state.borrow_mut();
+                if N - state.len < buffer.len() {
+                    return Poll::Pending;
+                }
+
+                let buf = self.buf.get() as *mut u8;
+                let end = (state.start + state.len) % N;
+                let first = buffer.len().min(N - end);
+                // Safety: the bytes after the queued ones are not read by the DMA.
+                unsafe {
+                    core::ptr::copy_nonoverlapping(buffer.as_ptr(), buf.add(end), first);
+                    core::ptr::copy_nonoverlapping(buffer[first..].as_ptr(), buf, buffer.len() - first);
+                }
+                state.len += buffer.len();
+
+                if state.transfer.is_none() {
+                    self.start_next(&mut state);
+                }
+                Poll::Ready(())
+            })
+        })
+        .await
+    }
+
+    /// Get the number of bytes waiting in the queue, including the ones being sent.
+    pub fn len(&self) -> usize {
+        self.state.lock(|state| state.borrow().len)
+    }
+
+    /// Check whether the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Start a transfer of the queued bytes up to the end of `buf`, if any.
+    fn start_next(&self, state: &mut QueueState<T, Dma>) {
+        // The previous transfer is complete, dropping it releases the channel.
+        state.transfer = None;
+
+        let r = T::regs();
+        let Some(tx) = state.tx.as_mut() else {
+            return;
+        };
+        if state.len == 0 {
+            r.cr1().modify(|w| w.set_tcie(false));
+            return;
+        }
+
+        let n = state.len.min(N - state.start);
+        // Safety: these bytes are not written until the transfer completes.
+        let bytes = unsafe { core::slice::from_raw_parts((self.buf.get() as *const u8).add(state.start), n) };
+        state.in_flight = n;
+
+        clear_tc(r);
+        r.cr1().modify(|w| w.set_tcie(true));
+        let request = tx.tx_dma.request();
+        // Safety: the channel belongs to `tx`, which is owned by the queue for good.
+        let channel = unsafe { tx.tx_dma.clone_unchecked() };
+        state.transfer = Some(unsafe { Transfer::new_write(channel, request, bytes, tdr(r), Default::default()) });
+    }
+}
+
+impl<T: BasicInstance, Dma: TxDma<T>, const N: usize> Default for UartTxQueue<T, Dma, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Queue of an instance, kicked by its transmission complete interrupt.
+pub(crate) trait TxQueue: Sync {
+    fn on_transmission_complete(&self);
+}
+
+impl<T: BasicInstance, Dma: TxDma<T>, const N: usize> TxQueue for UartTxQueue<T, Dma, N> {
+    fn on_transmission_complete(&self) {
+        self.state.lock(|state| {
+            let mut state = state.borrow_mut();
+            if state.transfer.is_none() {
+                return;
+            }
+            state.start = (state.start + state.in_flight) % N;
+            state.len -= state.in_flight;
+            state.in_flight = 0;
+            self.start_next(&mut state);
+        });
+        self.space.wake();
+    }
+}
+
+/// Queue registered for an instance, see [`UartTx::into_queue`].
+pub struct QueueSlot(BlockingMutex<CriticalSectionRawMutex, Cell<Option<&'static dyn TxQueue>>>);
+
+impl QueueSlot {
+    pub const fn new() -> Self {
+        Self(BlockingMutex::new(Cell::new(None)))
+    }
+
+    /// Called by the interrupt handler on transmission complete, with the TC interrupt enabled.
+    pub fn on_transmission_complete(&self) {
+        if let Some(queue) = self.0.lock(|queue| queue.get()) {
+            queue.on_transmission_complete();
+        }
+    }
+}
+
+#[cfg(any(usart_v1, usart_v2))]
+fn clear_tc(r: Regs) {
+    sr(r).modify(|w| w.set_tc(false));
+}
+
+#[cfg(not(any(usart_v1, usart_v2)))]
+fn clear_tc(r: Regs) {
+    r.icr().write(|w| w.set_tc(true));
+}
+
+impl<T: BasicInstance, Dma: TxDma<T>> UartTx<'static, T, Dma> {
+    /// Hand the transmitter over to `queue`, which sends the writes queued by any task.
+    ///
+    /// The auto idle mode is disabled, since the transfers are started from the interrupt.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another transmitter was handed over to `queue`.
+    pub fn into_queue<const N: usize>(mut self, queue: &'static UartTxQueue<T, Dma, N>) {
+        self.auto_idle.set(false);
+        T::regs().cr3().modify(|w| w.set_dmat(true));
+
+        queue.state.lock(|state| {
+            let mut state = state.borrow_mut();
+            assert!(state.tx.is_none(), "the queue already has a transmitter");
+            state.tx = Some(self);
+            // Send the writes queued before.
+            queue.start_next(&mut state);
+        });
+        T::state().tx_queue.0.lock(|slot| slot.set(Some(queue)));
+    }
+}
@@ -0,0 +1,386 @@
+//! RC receiver protocols: SBUS, IBUS and CRSF.
+//!
+//! An [`RcReceiver`] reads the frames sent by a radio control receiver, delimited by the idle
+//! line between them, and decodes them with a [`Protocol`]: [`Sbus`], [`Ibus`] or [`Crsf`].
+//!
+//! SBUS is an inverted signal. On the USARTs able to invert their RX pin, [`Sbus::config`]
+//! enables it. Otherwise, the signal must be inverted externally, with a transistor or an
+//! inverter gate, or taken from the uninverted pad some receivers provide.
+//!
+//! Only SBUS signals a failsafe in its frames. IBUS and CRSF receivers stop sending channel frames
+//! when the link is lost, so a timeout on [`next_frame`](RcReceiver::next_frame) should trigger
+//! the failsafe:
+//!
+//! ```rust,ignore
+//! let rx = UartRx::new(p.USART2, Irqs, p.PA3, p.DMA1_CH5, Sbus::config())?;
+//! let mut receiver = RcReceiver::new(rx, Sbus)?;
+//! loop {
+//!     match with_timeout(Duration::from_millis(100), receiver.next_frame()).await {
+//!         Ok(Ok(frame)) if !frame.failsafe => mixer.set(&frame.channels),
+//!         Ok(Ok(_)) | Err(_) => mixer.failsafe(),
+//!         Ok(Err(e)) => warn!("rc: {:?}", e),
+//!     }
+//! }
+//! ```
+
+use super::{BasicInstance, Config, ConfigError, Parity, RxDma, StopBits, UartRx};
+
+/// Size of the receive buffer, the maximum length of a CRSF frame.
+const BUF_LEN: usize = 64;
+
+/// RC receiver error.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// Error on the underlying UART.
+    Uart(super::Error),
+    /// The frame is shorter or longer than expected, usually a partial frame.
+    Length,
+    /// The header or footer bytes of the frame are wrong.
+    Delimiter,
+    /// The checksum of the frame is wrong.
+    Checksum,
+}
+
+impl From<super::Error> for Error {
+    fn from(e: super::Error) -> Self {
+        Self::Uart(e)
+    }
+}
+
+/// RC link protocol.
+pub trait Protocol {
+    /// Frame decoded by the protocol.
+    type Frame;
+
+    /// UART configuration of the protocol.
+    fn config() -> Config;
+
+    /// Decode the frame at the start of `buf`, returning it and its length.
+    fn decode(&mut self, buf: &[u8]) -> Result<(Self::Frame, usize), Error>;
+}
+
+/// Receiver of an RC link protocol.
+pub struct RcReceiver<'d, T: BasicInstance, RxDma, P> {
+    rx: UartRx<'d, T, RxDma>,
+    protocol: P,
+    buf: [u8; BUF_LEN],
+    /// Received bytes not decoded yet, `buf[start..end]`.
+    start: usize,
+    end: usize,
+}
+
+impl<'d, T: BasicInstance, Rx: RxDma<T>, P: Protocol> RcReceiver<'d, T, Rx, P> {
+    /// Create a new receiver, reconfiguring the UART for `protocol`.
+    pub fn new(mut rx: UartRx<'d, T, Rx>, protocol: P) -> Result<Self, ConfigError> {
+        rx.set_config(&P::config())?;
+        Ok(Self {
+            rx,
+            protocol,
+            buf: [0; BUF_LEN],
+            start: 0,
+            end: 0,
+        })
+    }
+
+    /// Wait for the next frame.
+    ///
+    /// On an error, the bytes received with the invalid frame are dropped, and the next call
+    /// waits for a new frame.
+    pub async fn next_frame(&mut self) -> Result<P::Frame, Error> {
+        if self.start == self.end {
+            self.start = 0;
+            self.end = 0;
+            self.end = self.rx.read_until_idle(&mut self.buf).await?;
+        }
+
+        match self.protocol.decode(&self.buf[self.start..self.end]) {
+            Ok((frame, len)) => {
+                self.start += len;
+                Ok(frame)
+            }
+            Err(e) => {
+                self.start = self.end;
+                Err(e)
+            }
+        }
+    }
+
+    /// Release the UART.
+    pub fn free(self) -> UartRx<'d, T, Rx> {
+        self.rx
+    }
+}
+
+/// Convert SBUS or CRSF channel ticks, from 172 to 1811, to a pulse width in microseconds, from
+/// 988 to 2012.
+pub fn ticks_to_micros(ticks: u16) -> u16 {
+    (880 + ticks as u32 * 5 / 8) as u16
+}
+
+/// Unpack 16 channels of 11 bits, least significant bit first.
+fn unpack_channels(data: &[u8]) -> [u16; 16] {
+    let mut channels = [0; 16];
+    let mut bits = 0u32;
+    let mut len = 0;
+    let mut channel = 0;
+    for byte in &data[..22] {
+        bits |= (*byte as u32) << len;
+        len += 8;
+        if len >= 11 {
+            channels[channel] = (bits & 0x7FF) as u16;
+            bits >>= 11;
+            len -= 11;
+            channel += 1;
+        }
+    }
+    channels
+}
+
+/// Futaba SBUS, 100 kbaud 8E2 inverted, frames of 16 channels every 7 or 14 ms.
+pub struct Sbus;
+
+/// SBUS frame.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SbusFrame {
+    /// Proportional channels, in ticks from 172 to 1811.
+    pub channels: [u16; 16],
+    /// Digital channel 17.
+    pub ch17: bool,
+    /// Digital channel 18.
+    pub ch18: bool,
+    /// A frame was lost by the radio link.
+    pub frame_lost: bool,
+    /// The receiver lost the link, and sends its failsafe positions.
+    pub failsafe: bool,
+}
+
+impl Sbus {
+    const FRAME_LEN: usize = 25;
+    const HEADER: u8 = 0x0F;
+}
+
+impl Protocol for Sbus {
+    type Frame = SbusFrame;
+
+    fn config() -> Config {
+        let mut config = Config::default();
+        config.baudrate = 100_000;
+        config.parity = Parity::ParityEven;
+        config.stop_bits = StopBits::STOP2;
+        #[cfg(any(usart_v3, usart_v4))]
+        {
+            config.invert_rx = true;
+        }
+        config
+    }
+
+    fn decode(&mut self, buf: &[u8]) -> Result<(SbusFrame, usize), Error> {
+        let frame = buf.get(..Self::FRAME_LEN).ok_or(Error::Length)?;
+        // SBUS2 cycles the footer through 0x04, 0x14, 0x24 and 0x34.
+        let footer = frame[24];
+        if frame[0] != Self::HEADER || (footer != 0x00 && footer & 0x0F != 0x04) {
+            return Err(Error::Delimiter);
+        }
+
+        let flags = frame[23];
+        let frame = SbusFrame {
+            channels: unpack_channels(&frame[1..23]),
+            ch17: flags & 0x01 != 0,
+            ch18: flags & 0x02 != 0,
+            frame_lost: flags & 0x04 != 0,
+            failsafe: flags & 0x08 != 0,
+        };
+        Ok((frame, Self::FRAME_LEN))
+    }
+}
+
+/// FlySky IBUS, 115200 baud 8N1, frames of 14 channels every 7 ms.
+pub struct Ibus;
+
+/// IBUS frame.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct IbusFrame {
+    /// Channels, in microseconds from 1000 to 2000.
+    pub channels: [u16; 14],
+}
+
+impl Ibus {
+    const FRAME_LEN: usize = 32;
+    const HEADER: [u8; 2] = [0x20, 0x40];
+}
+
+impl Protocol for Ibus {
+    type Frame = IbusFrame;
+
+    fn config() -> Config {
+        let mut config = Config::default();
+        config.baudrate = 115_200;
+        config
+    }
+
+    fn decode(&mut self, buf: &[u8]) -> Result<(IbusFrame, usize), Error> {
+        let frame = buf.get(..Self::FRAME_LEN).ok_or(Error::Length)?;
+        if frame[..2] != Self::HEADER {
+            return Err(Error::Delimiter);
+        }
+        let sum = frame[..30].iter().fold(0xFFFFu16, |sum, b| sum.wrapping_sub(*b as u16));
+        if sum.to_le_bytes() != frame[30..] {
+            return Err(Error::Checksum);
+        }
+
+        let mut channels = [0; 14];
+        for (channel, bytes) in channels.iter_mut().zip(frame[2..30].chunks_exact(2)) {
+            *channel = u16::from_le_bytes([bytes[0], bytes[1]]) & 0x0FFF;
+        }
+        Ok((IbusFrame { channels }, Self::FRAME_LEN))
+    }
+}
+
+/// TBS Crossfire and ExpressLRS CRSF, 420 kbaud 8N1.
+pub struct Crsf;
+
+/// CRSF frame.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CrsfFrame {
+    /// RC channels, in ticks from 172 to 1811.
+    Channels([u16; 16]),
+    /// Statistics of the radio link.
+    LinkStatistics(LinkStatistics),
+    /// Frame of another type, such as telemetry.
+    Other {
+        /// Type of the frame.
+        frame_type: u8,
+    },
+}
+
+/// CRSF link statistics.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LinkStatistics {
+    /// RSSI of the first antenna of the receiver, in -dBm.
+    pub uplink_rssi_1: u8,
+    /// RSSI of the second antenna of the receiver, in -dBm.
+    pub uplink_rssi_2: u8,
+    /// Link quality of the receiver, in percent. 0 when the link is lost.
+    pub uplink_link_quality: u8,
+    /// SNR of the receiver, in dB.
+    pub uplink_snr: i8,
+    /// Antenna of the receiver in use.
+    pub active_antenna: u8,
+    /// RF mode, the packet rate.
+    pub rf_mode: u8,
+    /// Power of the transmitter, as an index.
+    pub uplink_tx_power: u8,
+    /// RSSI of the transmitter, in -dBm.
+    pub downlink_rssi: u8,
+    /// Link quality of the transmitter, in percent.
+    pub downlink_link_quality: u8,
+    /// SNR of the transmitter, in dB.
+    pub downlink_snr: i8,
+}
+
+impl Crsf {
+    const SYNC: [u8; 3] = [0xC8, 0xEA, 0xEE];
+    const MAX_LEN: usize = BUF_LEN - 2;
+    const LINK_STATISTICS: u8 = 0x14;
+    const RC_CHANNELS_PACKED: u8 = 0x16;
+}
+
+impl Protocol for Crsf {
+    type Frame = CrsfFrame;
+
+    fn config() -> Config {
+        let mut config = Config::default();
+        config.baudrate = 420_000;
+        config
+    }
+
+    fn decode(&mut self, buf: &[u8]) -> Result<(CrsfFrame, usize), Error> {
+        let [sync, len, ..] = *buf else {
+            return Err(Error::Length);
+        };
+        if !Self::SYNC.contains(&sync) {
+            return Err(Error::Delimiter);
+        }
+        // The length covers the type, the payload and the CRC.
+        let len = len as usize;
+        if len < 2 || len > Self::MAX_LEN || buf.len() < len + 2 {
+            return Err(Error::Length);
+        }
+        let (data, crc) = buf[2..len + 2].split_at(len - 1);
+        if crc8_dvb_s2(data) != crc[0] {
+            return Err(Error::Checksum);
+        }
+
+        let (frame_type, payload) = (data[0], &data[1..]);
+        let frame = match (frame_type, payload.len()) {
+            (Self::RC_CHANNELS_PACKED, 22) => CrsfFrame::Channels(unpack_channels(payload)),
+            (Self::LINK_STATISTICS, 10) => CrsfFrame::LinkStatistics(LinkStatistics {
+                uplink_rssi_1: payload[0],
+                uplink_rssi_2: payload[1],
+                uplink_link_quality: payload[2],
+                uplink_snr: payload[3] as i8,
+                active_antenna: payload[4],
+                rf_mode: payload[5],
+                uplink_tx_power: payload[6],
+                downlink_rssi: payload[7],
+                downlink_link_quality: payload[8],
+                downlink_snr: payload[9] as i8,
+            }),
+            (Self::RC_CHANNELS_PACKED | Self::LINK_STATISTICS, _) => return Err(Error::Length),
+            (frame_type, _) => CrsfFrame::Other { frame_type },
+        };
+        Ok((frame, len + 2))
+    }
+}
+
+/// CRC-8/DVB-S2 of the CRSF frames.
+fn crc8_dvb_s2(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for byte in data {
+        crc ^= *byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ 0xD5 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sbus() {
+        let mut frame = [0; 25];
+        frame[0] = 0x0F;
+        frame[1..4].copy_from_slice(&[0xFF, 0xFF, 0x3F]);
+        frame[23] = 0x0C;
+        let (frame, len) = Sbus.decode(&frame).unwrap();
+        assert_eq!(len, 25);
+        assert_eq!(frame.channels[..3], [0x7FF, 0x7FF, 0]);
+        assert!(frame.frame_lost && frame.failsafe);
+    }
+
+    #[test]
+    fn test_ibus_checksum() {
+        let mut frame = [0; 32];
+        frame[..2].copy_from_slice(&[0x20, 0x40]);
+        for channel in frame[2..30].chunks_exact_mut(2) {
+            channel.copy_from_slice(&1500u16.to_le_bytes());
+        }
+        frame[30..].copy_from_slice(&[0x51, 0xF3]);
+        let (frame, _) = Ibus.decode(&frame).unwrap();
+        assert_eq!(frame.channels, [1500; 14]);
+    }
+
+    #[test]
+    fn test_crc8_dvb_s2() {
+        assert_eq!(crc8_dvb_s2(b"123456789"), 0xBC);
+    }
+}
@@ -294,11 +294,33 @@ pub struct Config {
     /// If you set this to true, you must connect VBUS to PA9 for FS, PB13 for HS, possibly with a
     /// voltage divider. See ST application note AN4879 and the reference manual for more details.
     pub vbus_detection: bool,
+
+    /// Size of the shared RX FIFO, in words (u32).
+    ///
+    /// By default the RX FIFO is sized to fit one packet of every OUT endpoint, plus some room
+    /// for SETUP and status entries. A larger FIFO lets the core receive several packets before
+    /// the driver reads them, which improves throughput of bulk OUT endpoints.
+    ///
+    /// Sizes smaller than the default are ignored.
+    pub rx_fifo_size_words: Option<u16>,
+
+    /// Size of the TX FIFO of each IN endpoint, in words (u32), indexed by endpoint number.
+    ///
+    /// By default an IN endpoint gets a TX FIFO fitting one packet, with a minimum of 16 words.
+    /// Sizes smaller than the default are ignored.
+    ///
+    /// Endpoint allocation fails if the total of the FIFO sizes exceeds the FIFO RAM of the
+    /// peripheral, for example 320 words on OTG_FS of most chips and 1024 words on OTG_HS.
+    pub tx_fifo_size_words: [Option<u16>; MAX_EP_COUNT],
 }
 
 impl Default for Config {
     fn default() -> Self {
-        Self { vbus_detection: true }
+        Self {
+            vbus_detection: true,
+            rx_fifo_size_words: None,
+            tx_fifo_size_words: [None; MAX_EP_COUNT],
+        }
     }
 }
 
@@ -390,7 +412,7 @@ impl<'d, T: Instance> Driver<'d, T> {
 
     // Returns total amount of words (u32) allocated in dedicated FIFO
     fn allocated_fifo_words(&self) -> u16 {
-        RX_FIFO_EXTRA_SIZE_WORDS + ep_fifo_size(&self.ep_out) + ep_fifo_size(&self.ep_in)
+        rx_fifo_size(&self.config, ep_fifo_size(&self.ep_out)) + ep_fifo_size(&self.ep_in)
     }
 
     fn alloc_endpoint<D: Dir>(
@@ -414,25 +436,14 @@ impl<'d, T: Instance> Driver<'d, T> {
             }
         };
 
-        let fifo_size_words = match D::dir() {
-            Direction::Out => (max_packet_size + 3) / 4,
-            // INEPTXFD requires minimum size of 16 words
-            Direction::In => u16::max((max_packet_size + 3) / 4, 16),
-        };
-
-        if fifo_size_words + self.allocated_fifo_words() > T::FIFO_DEPTH_WORDS {
-            error!("Not enough FIFO capacity");
-            return Err(EndpointAllocError);
-        }
-
         let eps = match D::dir() {
-            Direction::Out => &mut self.ep_out,
-            Direction::In => &mut self.ep_in,
+            Direction::Out => &self.ep_out,
+            Direction::In => &self.ep_in,
         };
 
         // Find free endpoint slot
-        let slot = eps.iter_mut().enumerate().find(|(i, ep)| {
-            if *i == 0 && ep_type != EndpointType::Control {
+        let slot = eps.iter().enumerate().position(|(i, ep)| {
+            if i == 0 && ep_type != EndpointType::Control {
                 // reserved for control pipe
                 false
             } else {
@@ -441,20 +452,46 @@ impl<'d, T: Instance> Driver<'d, T> {
         });
 
         let index = match slot {
-            Some((index, ep)) => {
-                *ep = Some(EndpointData {
-                    ep_type,
-                    max_packet_size,
-                    fifo_size_words,
-                });
-                index
-            }
+            Some(index) => index,
             None => {
                 error!("No free endpoints available");
                 return Err(EndpointAllocError);
             }
         };
 
+        let fifo_size_words = match D::dir() {
+            Direction::Out => (max_packet_size + 3) / 4,
+            // INEPTXFD requires minimum size of 16 words
+            Direction::In => {
+                let min_size_words = u16::max((max_packet_size + 3) / 4, 16);
+                u16::max(self.config.tx_fifo_size_words[index].unwrap_or(0), min_size_words)
+            }
+        };
+
+        // The RX FIFO is shared, it only grows if the configured size is too small.
+        let required_words = match D::dir() {
+            Direction::Out => {
+                let ep_out_words = ep_fifo_size(&self.ep_out);
+                rx_fifo_size(&self.config, ep_out_words + fifo_size_words) - rx_fifo_size(&self.config, ep_out_words)
+            }
+            Direction::In => fifo_size_words,
+        };
+
+        if required_words + self.allocated_fifo_words() > T::FIFO_DEPTH_WORDS {
+            error!("Not enough FIFO capacity");
+            return Err(EndpointAllocError);
+        }
+
+        let eps = match D::dir() {
+            Direction::Out => &mut self.ep_out,
+            Direction::In => &mut self.ep_in,
+        };
+        eps[index] = Some(EndpointData {
+            ep_type,
+            max_packet_size,
+            fifo_size_words,
+        });
+
         trace!("  index={}", index);
 
         if D::dir() == Direction::Out {
@@ -746,7 +783,7 @@ impl<'d, T: Instance> Bus<'d, T> {
         let r = T::regs();
 
         // Configure RX fifo size. All endpoints share the same FIFO area.
-        let rx_fifo_size_words = RX_FIFO_EXTRA_SIZE_WORDS + ep_fifo_size(&self.ep_out);
+        let rx_fifo_size_words = rx_fifo_size(&self.config, ep_fifo_size(&self.ep_out));
         trace!("configuring rx fifo size={}", rx_fifo_size_words);
 
         r.grxfsiz().modify(|w| w.set_rxfd(rx_fifo_size_words));
@@ -1425,6 +1462,14 @@ fn ep_fifo_size(eps: &[Option<EndpointData>]) -> u16 {
     eps.iter().map(|ep| ep.map(|ep| ep.fifo_size_words).unwrap_or(0)).sum()
 }
 
+/// Calculates RX FIFO size in words, from the OUT endpoints FIFO size and the configured size
+fn rx_fifo_size(config: &Config, ep_out_words: u16) -> u16 {
+    u16::max(
+        config.rx_fifo_size_words.unwrap_or(0),
+        RX_FIFO_EXTRA_SIZE_WORDS + ep_out_words,
+    )
+}
+
 /// Generates IRQ mask for enabled endpoints
 fn ep_irq_mask(eps: &[Option<EndpointData>]) -> u16 {
     eps.iter().enumerate().fold(
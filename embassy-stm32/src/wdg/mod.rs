@@ -6,6 +6,11 @@ use stm32_metapac::iwdg::vals::{Key, Pr};
 
 use crate::rcc::LSI_FREQ;
 
+#[cfg(feature = "time")]
+mod supervisor;
+#[cfg(feature = "time")]
+pub use supervisor::{take_crash_report, CrashReport, Heartbeat, Supervisor};
+
 /// Independent watchdog (IWDG) driver.
 pub struct IndependentWatchdog<'d, T: Instance> {
     wdg: PhantomData<&'d mut T>,
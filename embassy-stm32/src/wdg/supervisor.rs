@@ -0,0 +1,171 @@
+//! Task supervisor, refreshing the watchdog only while all the supervised tasks are alive.
+use core::cell::Cell;
+use core::mem::MaybeUninit;
+use core::ptr::{addr_of, addr_of_mut};
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Instant, Timer};
+
+use super::{IndependentWatchdog, Instance};
+
+/// Marks a valid crash report, which is otherwise left uninitialized at boot.
+const REPORT_MAGIC: u32 = 0x5744_4C54;
+
+#[repr(C)]
+struct RawReport {
+    magic: u32,
+    task: u32,
+    overdue_ms: u32,
+}
+
+/// Crash report, kept across the watchdog reset in a section left uninitialized by the runtime.
+#[link_section = ".uninit.embassy_stm32_wdg"]
+static mut CRASH_REPORT: MaybeUninit<RawReport> = MaybeUninit::uninit();
+
+/// Task reported late by the [`Supervisor`] before the watchdog reset.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CrashReport {
+    /// Index of the late task in the heartbeats of the supervisor.
+    pub task: usize,
+    /// Time elapsed since the deadline of the task, when it was reported.
+    pub overdue_ms: u32,
+}
+
+/// Take the crash report left by the [`Supervisor`] before the last reset, if any.
+///
+/// The report is cleared, so later calls return `None` until the next watchdog reset.
+pub fn take_crash_report() -> Option<CrashReport> {
+    critical_section::with(|_| unsafe {
+        let report = addr_of_mut!(CRASH_REPORT) as *mut RawReport;
+        if addr_of!((*report).magic).read_volatile() != REPORT_MAGIC {
+            return None;
+        }
+        addr_of_mut!((*report).magic).write_volatile(0);
+        Some(CrashReport {
+            task: addr_of!((*report).task).read_volatile() as usize,
+            overdue_ms: addr_of!((*report).overdue_ms).read_volatile(),
+        })
+    })
+}
+
+fn write_crash_report(report: CrashReport) {
+    critical_section::with(|_| unsafe {
+        let raw = addr_of_mut!(CRASH_REPORT) as *mut RawReport;
+        addr_of_mut!((*raw).task).write_volatile(report.task as u32);
+        addr_of_mut!((*raw).overdue_ms).write_volatile(report.overdue_ms);
+        addr_of_mut!((*raw).magic).write_volatile(REPORT_MAGIC);
+    })
+}
+
+/// Heartbeat of a supervised task, which must beat at least once per deadline.
+///
+/// ```rust,ignore
+/// static NET: Heartbeat = Heartbeat::new(Duration::from_secs(1));
+///
+/// #[embassy_executor::task]
+/// async fn net() {
+///     loop {
+///         poll_network().await;
+///         NET.beat();
+///     }
+/// }
+/// ```
+pub struct Heartbeat {
+    deadline: Duration,
+    last: Mutex<CriticalSectionRawMutex, Cell<Instant>>,
+}
+
+impl Heartbeat {
+    /// Create a new heartbeat, with a deadline between two beats.
+    pub const fn new(deadline: Duration) -> Self {
+        Self {
+            deadline,
+            last: Mutex::const_new(CriticalSectionRawMutex::new(), Cell::new(Instant::from_ticks(0))),
+        }
+    }
+
+    /// Signal that the task is alive.
+    pub fn beat(&self) {
+        self.last.lock(|last| last.set(Instant::now()));
+    }
+
+    /// Deadline between two beats.
+    pub fn deadline(&self) -> Duration {
+        self.deadline
+    }
+
+    /// Time elapsed since the deadline at `now`, if it has passed.
+    fn overdue(&self, now: Instant) -> Option<Duration> {
+        let due = self.last.lock(|last| last.get()) + self.deadline;
+        now.checked_duration_since(due)
+            .filter(|overdue| *overdue > Duration::from_ticks(0))
+    }
+}
+
+/// Task supervisor.
+///
+/// The supervisor refreshes the watchdog only while all its heartbeats beat within their
+/// deadlines. Once a task is late, the watchdog is left to reset the chip, and the late task is
+/// written to a crash report, read back after the reset with [`take_crash_report`].
+///
+/// The watchdog timeout must be longer than the check period of [`run`](Self::run).
+pub struct Supervisor<'d, 'h, T: Instance> {
+    wdg: IndependentWatchdog<'d, T>,
+    heartbeats: &'h [&'h Heartbeat],
+    late: Option<usize>,
+}
+
+impl<'d, 'h, T: Instance> Supervisor<'d, 'h, T> {
+    /// Create a new supervisor of `heartbeats`, which all start fresh.
+    pub fn new(wdg: IndependentWatchdog<'d, T>, heartbeats: &'h [&'h Heartbeat]) -> Self {
+        for heartbeat in heartbeats {
+            heartbeat.beat();
+        }
+        Self {
+            wdg,
+            heartbeats,
+            late: None,
+        }
+    }
+
+    /// Check the heartbeats, refreshing the watchdog if they are all fresh.
+    ///
+    /// Returns the index of the first late task otherwise, after writing it to the crash report.
+    /// The watchdog is not refreshed anymore after that, even if the task beats again.
+    pub fn check(&mut self) -> Result<(), usize> {
+        if let Some(task) = self.late {
+            return Err(task);
+        }
+
+        let now = Instant::now();
+        for (task, heartbeat) in self.heartbeats.iter().enumerate() {
+            if let Some(overdue) = heartbeat.overdue(now) {
+                warn!("wdg: task {} is {} ms late", task, overdue.as_millis());
+                write_crash_report(CrashReport {
+                    task,
+                    overdue_ms: overdue.as_millis().min(u32::MAX as u64) as u32,
+                });
+                self.late = Some(task);
+                return Err(task);
+            }
+        }
+
+        self.wdg.pet();
+        Ok(())
+    }
+
+    /// Start the watchdog, and check the heartbeats every `period` until a task is late.
+    ///
+    /// Returns the index of the late task, with the watchdog about to reset the chip.
+    pub async fn run(&mut self, period: Duration) -> usize {
+        self.wdg.unleash();
+        loop {
+            if let Err(task) = self.check() {
+                return task;
+            }
+            Timer::after(period).await;
+        }
+    }
+}
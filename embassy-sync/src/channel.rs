@@ -21,8 +21,9 @@
 use core::cell::RefCell;
 use core::future::Future;
 use core::pin::Pin;
-use core::task::{Context, Poll};
+use core::task::{ready, Context, Poll};
 
+use embassy_futures::budget::poll_budget;
 use heapless::Deque;
 
 use crate::blocking_mutex::raw::RawMutex;
@@ -242,6 +243,7 @@ where
     type Output = T;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        ready!(poll_budget(cx));
         self.channel.poll_receive(cx)
     }
 }
@@ -256,6 +258,7 @@ impl<'ch, T> Future for DynamicReceiveFuture<'ch, T> {
     type Output = T;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        ready!(poll_budget(cx));
         match self.channel.try_receive_with_context(Some(cx)) {
             Ok(v) => Poll::Ready(v),
             Err(TryReceiveError::Empty) => Poll::Pending,
@@ -280,6 +283,7 @@ where
     type Output = ();
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        ready!(poll_budget(cx));
         match self.message.take() {
             Some(m) => match self.channel.try_send_with_context(m, Some(cx)) {
                 Ok(..) => Poll::Ready(()),
@@ -306,6 +310,7 @@ impl<'ch, T> Future for DynamicSendFuture<'ch, T> {
     type Output = ();
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        ready!(poll_budget(cx));
         match self.message.take() {
             Some(m) => match self.channel.try_send_with_context(m, Some(cx)) {
                 Ok(..) => Poll::Ready(()),
@@ -18,4 +18,5 @@ pub mod priority_channel;
 pub mod pubsub;
 pub mod signal;
 pub mod waitqueue;
+pub mod watch;
 pub mod zerocopy_channel;
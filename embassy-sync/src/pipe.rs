@@ -5,7 +5,9 @@ use core::convert::Infallible;
 use core::future::Future;
 use core::ops::Range;
 use core::pin::Pin;
-use core::task::{Context, Poll};
+use core::task::{ready, Context, Poll};
+
+use embassy_futures::budget::poll_budget;
 
 use crate::blocking_mutex::raw::RawMutex;
 use crate::blocking_mutex::Mutex;
@@ -67,6 +69,7 @@ where
     type Output = usize;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        ready!(poll_budget(cx));
         match self.pipe.try_write_with_context(Some(cx), self.buf) {
             Ok(n) => Poll::Ready(n),
             Err(TryWriteError::Full) => Poll::Pending,
@@ -143,6 +146,7 @@ where
     type Output = usize;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        ready!(poll_budget(cx));
         match self.pipe.try_read_with_context(Some(cx), self.buf) {
             Ok(n) => Poll::Ready(n),
             Err(TryReadError::Empty) => Poll::Pending,
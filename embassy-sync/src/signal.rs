@@ -1,7 +1,9 @@
 //! A synchronization primitive for passing the latest value to a task.
 use core::cell::Cell;
 use core::future::{poll_fn, Future};
-use core::task::{Context, Poll, Waker};
+use core::task::{ready, Context, Poll, Waker};
+
+use embassy_futures::budget::poll_budget;
 
 use crate::blocking_mutex::raw::RawMutex;
 use crate::blocking_mutex::Mutex;
@@ -85,6 +87,7 @@ where
     }
 
     fn poll_wait(&self, cx: &mut Context<'_>) -> Poll<T> {
+        ready!(poll_budget(cx));
         self.state.lock(|cell| {
             let state = cell.replace(State::None);
             match state {
@@ -0,0 +1,229 @@
+//! A synchronization primitive for broadcasting the latest value to multiple tasks.
+use core::cell::RefCell;
+use core::future::poll_fn;
+use core::task::{Context, Poll};
+
+use crate::blocking_mutex::raw::RawMutex;
+use crate::blocking_mutex::Mutex;
+use crate::waitqueue::MultiWakerRegistration;
+
+/// Broadcast of the latest value to up to `N` receivers.
+///
+/// This is similar to a [`Signal`](crate::signal::Signal), except the value is cloned to every
+/// [`Receiver`] instead of being taken by a single consumer, and stays available after it has
+/// been received. Each receiver tracks which values it has already seen, so it can wait for the
+/// next change.
+///
+/// It is useful for sharing state, such as the latest measurement of a sensor, with several tasks.
+///
+/// ```
+/// use embassy_sync::watch::Watch;
+/// use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+///
+/// static TEMPERATURE: Watch<CriticalSectionRawMutex, i32, 2> = Watch::new();
+///
+/// # futures_executor::block_on(async {
+/// let mut receiver = TEMPERATURE.receiver().unwrap();
+/// TEMPERATURE.send(25);
+/// assert_eq!(receiver.changed().await, 25);
+/// assert_eq!(receiver.try_changed(), None);
+/// # });
+/// ```
+pub struct Watch<M: RawMutex, T: Clone, const N: usize> {
+    state: Mutex<M, RefCell<WatchState<T, N>>>,
+}
+
+struct WatchState<T: Clone, const N: usize> {
+    data: Option<T>,
+    /// Incremented on every sent value, 0 before the first one.
+    id: u32,
+    receivers: usize,
+    wakers: MultiWakerRegistration<N>,
+}
+
+impl<M: RawMutex, T: Clone, const N: usize> Watch<M, T, N> {
+    /// Create a new `Watch`, without a value.
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(RefCell::new(WatchState {
+                data: None,
+                id: 0,
+                receivers: 0,
+                wakers: MultiWakerRegistration::new(),
+            })),
+        }
+    }
+
+    /// Create a new receiver, or return `None` if there are already `N` receivers.
+    ///
+    /// The values sent before the receiver was created are considered unseen.
+    pub fn receiver(&self) -> Option<Receiver<'_, M, T, N>> {
+        self.state.lock(|s| {
+            let mut s = s.borrow_mut();
+            if s.receivers < N {
+                s.receivers += 1;
+                Some(Receiver { watch: self, at_id: 0 })
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Replace the value, and wake all the receivers.
+    pub fn send(&self, val: T) {
+        self.state.lock(|s| {
+            let mut s = s.borrow_mut();
+            s.data = Some(val);
+            s.id = s.id.wrapping_add(1);
+            s.wakers.wake();
+        })
+    }
+
+    /// Modify the value in place, and wake all the receivers.
+    ///
+    /// The value is `None` if nothing was sent yet, or if it was cleared.
+    pub fn send_modify(&self, f: impl FnOnce(&mut Option<T>)) {
+        self.state.lock(|s| {
+            let mut s = s.borrow_mut();
+            f(&mut s.data);
+            s.id = s.id.wrapping_add(1);
+            s.wakers.wake();
+        })
+    }
+
+    /// Remove the value, the receivers wait for the next one.
+    pub fn clear(&self) {
+        self.state.lock(|s| s.borrow_mut().data = None)
+    }
+
+    /// Return a copy of the value, if any, without marking it as seen by any receiver.
+    pub fn try_get(&self) -> Option<T> {
+        self.state.lock(|s| s.borrow().data.clone())
+    }
+
+    /// Return whether there is a value.
+    pub fn contains_value(&self) -> bool {
+        self.state.lock(|s| s.borrow().data.is_some())
+    }
+
+    fn poll_get(&self, at_id: &mut u32, only_changed: bool, cx: Option<&mut Context<'_>>) -> Poll<T> {
+        self.state.lock(|s| {
+            let s = &mut *s.borrow_mut();
+            match &s.data {
+                Some(data) if !only_changed || s.id != *at_id => {
+                    *at_id = s.id;
+                    Poll::Ready(data.clone())
+                }
+                _ => {
+                    if let Some(cx) = cx {
+                        s.wakers.register(cx.waker());
+                    }
+                    Poll::Pending
+                }
+            }
+        })
+    }
+}
+
+impl<M: RawMutex, T: Clone, const N: usize> Default for Watch<M, T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Receiver of a [`Watch`].
+pub struct Receiver<'a, M: RawMutex, T: Clone, const N: usize> {
+    watch: &'a Watch<M, T, N>,
+    at_id: u32,
+}
+
+impl<'a, M: RawMutex, T: Clone, const N: usize> Receiver<'a, M, T, N> {
+    /// Wait for a value, and return a copy of it, marking it as seen.
+    ///
+    /// Returns immediately if there is a value, even if it was already seen.
+    pub async fn get(&mut self) -> T {
+        poll_fn(|cx| self.watch.poll_get(&mut self.at_id, false, Some(cx))).await
+    }
+
+    /// Return a copy of the value, if any, marking it as seen.
+    pub fn try_get(&mut self) -> Option<T> {
+        match self.watch.poll_get(&mut self.at_id, false, None) {
+            Poll::Ready(val) => Some(val),
+            Poll::Pending => None,
+        }
+    }
+
+    /// Wait for a value not seen yet by this receiver, and return a copy of it.
+    pub async fn changed(&mut self) -> T {
+        poll_fn(|cx| self.watch.poll_get(&mut self.at_id, true, Some(cx))).await
+    }
+
+    /// Return a copy of the value if it was not seen yet by this receiver, marking it as seen.
+    pub fn try_changed(&mut self) -> Option<T> {
+        match self.watch.poll_get(&mut self.at_id, true, None) {
+            Poll::Ready(val) => Some(val),
+            Poll::Pending => None,
+        }
+    }
+
+    /// Return whether there is a value.
+    pub fn contains_value(&self) -> bool {
+        self.watch.contains_value()
+    }
+}
+
+impl<'a, M: RawMutex, T: Clone, const N: usize> Drop for Receiver<'a, M, T, N> {
+    fn drop(&mut self) {
+        self.watch.state.lock(|s| s.borrow_mut().receivers -= 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_executor::block_on;
+
+    use super::*;
+    use crate::blocking_mutex::raw::CriticalSectionRawMutex;
+
+    #[test]
+    fn receivers_limit() {
+        let watch: Watch<CriticalSectionRawMutex, u8, 2> = Watch::new();
+        let first = watch.receiver();
+        let _second = watch.receiver().unwrap();
+        assert!(watch.receiver().is_none());
+
+        drop(first);
+        assert!(watch.receiver().is_some());
+    }
+
+    #[test]
+    fn every_receiver_sees_value() {
+        block_on(async {
+            let watch: Watch<CriticalSectionRawMutex, u8, 2> = Watch::new();
+            let mut first = watch.receiver().unwrap();
+            let mut second = watch.receiver().unwrap();
+            assert_eq!(first.try_get(), None);
+
+            watch.send(1);
+            assert_eq!(first.changed().await, 1);
+            assert_eq!(second.changed().await, 1);
+            assert_eq!(first.try_changed(), None);
+            assert_eq!(first.get().await, 1);
+
+            watch.send_modify(|val| *val = val.map(|val| val + 1));
+            assert_eq!(first.try_changed(), Some(2));
+            assert_eq!(second.try_changed(), Some(2));
+        })
+    }
+
+    #[test]
+    fn clear() {
+        let watch: Watch<CriticalSectionRawMutex, u8, 1> = Watch::new();
+        let mut receiver = watch.receiver().unwrap();
+        watch.send(1);
+        watch.clear();
+        assert!(!receiver.contains_value());
+        assert_eq!(receiver.try_changed(), None);
+        assert_eq!(watch.try_get(), None);
+    }
+}
@@ -39,7 +39,7 @@ impl<'d, STATE: NorFlash, RST: Reset> Control<'d, STATE, RST> {
 
 impl<'d, STATE: NorFlash, RST: Reset> Handler for Control<'d, STATE, RST> {
     fn reset(&mut self) {
-        if let Some(start) = self.detach_start {
+        if let Some(start) = self.detach_start.take() {
             let delta = Instant::now() - start;
             let timeout = self.timeout.unwrap();
             trace!(
@@ -53,6 +53,8 @@ impl<'d, STATE: NorFlash, RST: Reset> Handler for Control<'d, STATE, RST> {
                     .expect("Failed to mark DFU mode in bootloader");
                 RST::sys_reset()
             }
+            // The detach timed out, keep running the application.
+            self.state = State::AppIdle;
         }
     }
 
@@ -93,7 +95,11 @@ impl<'d, STATE: NorFlash, RST: Reset> Handler for Control<'d, STATE, RST> {
         match Request::try_from(req.request) {
             Ok(Request::GetStatus) => {
                 buf[0..6].copy_from_slice(&[Status::Ok as u8, 0x32, 0x00, 0x00, self.state as u8, 0x00]);
-                Some(InResponse::Accepted(buf))
+                Some(InResponse::Accepted(&buf[0..6]))
+            }
+            Ok(Request::GetState) => {
+                buf[0] = self.state as u8;
+                Some(InResponse::Accepted(&buf[0..1]))
             }
             _ => None,
         }
@@ -103,7 +109,7 @@ impl<'d, STATE: NorFlash, RST: Reset> Handler for Control<'d, STATE, RST> {
 /// An implementation of the USB DFU 1.1 runtime protocol
 ///
 /// This function will add a DFU interface descriptor to the provided Builder, and register the provided Control as a handler for the USB device. The USB builder can be used as normal once this is complete.
-/// The handler is responsive to DFU GetStatus and Detach commands.
+/// The handler is responsive to DFU GetStatus, GetState and Detach commands.
 ///
 /// Once a detach command, followed by a USB reset is received by the host, a magic number will be written into the bootloader state partition to indicate that
 /// it should expose a DFU device, and a software reset will be issued.
@@ -19,6 +19,7 @@ pub struct Control<'d, DFU: NorFlash, STATE: NorFlash, RST: Reset, const BLOCK_S
     state: State,
     status: Status,
     offset: usize,
+    manifested: bool,
     _rst: PhantomData<RST>,
 }
 
@@ -31,6 +32,7 @@ impl<'d, DFU: NorFlash, STATE: NorFlash, RST: Reset, const BLOCK_SIZE: usize> Co
             state: State::DfuIdle,
             status: Status::Ok,
             offset: 0,
+            manifested: false,
             _rst: PhantomData,
         }
     }
@@ -45,6 +47,13 @@ impl<'d, DFU: NorFlash, STATE: NorFlash, RST: Reset, const BLOCK_SIZE: usize> Co
 impl<'d, DFU: NorFlash, STATE: NorFlash, RST: Reset, const BLOCK_SIZE: usize> Handler
     for Control<'d, DFU, STATE, RST, BLOCK_SIZE>
 {
+    fn reset(&mut self) {
+        // Manifestation tolerant devices wait for the host to reset them into the new firmware.
+        if self.manifested {
+            RST::sys_reset()
+        }
+    }
+
     fn control_out(
         &mut self,
         req: embassy_usb::control::Request,
@@ -64,6 +73,13 @@ impl<'d, DFU: NorFlash, STATE: NorFlash, RST: Reset, const BLOCK_SIZE: usize> Ha
                     self.offset = 0;
                 }
 
+                if data.len() > BLOCK_SIZE {
+                    warn!("DNLOAD block of {} bytes larger than the transfer size", data.len());
+                    self.status = Status::ErrUnknown;
+                    self.state = State::Error;
+                    return Some(OutResponse::Rejected);
+                }
+
                 let mut buf = AlignedBuffer([0; BLOCK_SIZE]);
                 buf.as_mut()[..data.len()].copy_from_slice(data);
 
@@ -139,6 +155,11 @@ impl<'d, DFU: NorFlash, STATE: NorFlash, RST: Reset, const BLOCK_SIZE: usize> Ha
                 buf[0..6].copy_from_slice(&[self.status as u8, 0x32, 0x00, 0x00, self.state as u8, 0x00]);
                 match self.state {
                     State::DlSync => self.state = State::Download,
+                    State::ManifestSync if self.attrs.contains(DfuAttributes::MANIFESTATION_TOLERANT) => {
+                        // The update is marked, it is applied on the next reset.
+                        self.manifested = true;
+                        self.state = State::DfuIdle;
+                    }
                     State::ManifestSync => RST::sys_reset(),
                     _ => {}
                 }
@@ -165,6 +186,8 @@ impl<'d, DFU: NorFlash, STATE: NorFlash, RST: Reset, const BLOCK_SIZE: usize> Ha
 ///
 /// Once the host has initiated a DFU download operation, the chunks sent by the host will be written to the DFU partition.
 /// Once the final sync in the manifestation phase has been received, the handler will trigger a system reset to swap the new firmware.
+/// If the attributes include [`DfuAttributes::MANIFESTATION_TOLERANT`], the device instead returns to the idle state, and
+/// the system reset is triggered by the next USB reset, so the host can still query the device before resetting it.
 pub fn usb_dfu<'d, D: Driver<'d>, DFU: NorFlash, STATE: NorFlash, RST: Reset, const BLOCK_SIZE: usize>(
     builder: &mut Builder<'d, D>,
     handler: &'d mut Control<'d, DFU, STATE, RST, BLOCK_SIZE>,
@@ -2,9 +2,11 @@
 #![doc = include_str!("../README.md")]
 #![warn(missing_docs)]
 
+use core::cell::Cell;
 use core::fmt::Write as _;
 
 use embassy_futures::join::join;
+use embassy_sync::blocking_mutex::Mutex;
 use embassy_sync::pipe::Pipe;
 use embassy_usb::class::cdc_acm::{CdcAcmClass, State};
 use embassy_usb::driver::Driver;
@@ -13,6 +15,9 @@ use log::{Metadata, Record};
 
 type CS = embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 
+/// Maximum length of a log message, longer messages are truncated.
+const MAX_LINE_LEN: usize = 256;
+
 /// The logger state containing buffers that must live as long as the USB peripheral.
 pub struct LoggerState<'d> {
     state: State<'d>,
@@ -38,14 +43,70 @@ impl<'d> LoggerState<'d> {
 }
 
 /// The logger handle, which contains a pipe with configurable size for buffering log messages.
+///
+/// The messages are written whole to the pipe, from any context including interrupts, or dropped
+/// if it is full. The number of dropped messages is reported in the output once there is room.
 pub struct UsbLogger<const N: usize> {
     buffer: Pipe<CS, N>,
+    /// Number of dropped messages, and number of them already reported.
+    dropped: Mutex<CS, Cell<(u32, u32)>>,
 }
 
 impl<const N: usize> UsbLogger<N> {
     /// Create a new logger instance.
     pub const fn new() -> Self {
-        Self { buffer: Pipe::new() }
+        Self {
+            buffer: Pipe::new(),
+            dropped: Mutex::new(Cell::new((0, 0))),
+        }
+    }
+
+    /// Number of messages dropped because the buffer was full.
+    pub fn dropped(&self) -> u32 {
+        self.dropped.lock(|d| d.get().0)
+    }
+
+    /// Drain the log messages into `writer`, for example a UART, instead of USB. Never returns.
+    pub async fn drain<W: embedded_io_async::Write>(&self, mut writer: W) -> ! {
+        let mut buf = [0; 64];
+        loop {
+            let len = self.read(&mut buf).await;
+            let _ = writer.write_all(&buf[..len]).await;
+        }
+    }
+
+    /// Read the next log output, starting with a notice if messages were dropped.
+    async fn read(&self, buf: &mut [u8]) -> usize {
+        let unreported = self.dropped.lock(|d| {
+            let (dropped, reported) = d.get();
+            d.set((dropped, dropped));
+            dropped.wrapping_sub(reported)
+        });
+        if unreported > 0 {
+            let mut line = Line::new();
+            let _ = write!(line, "[{} log messages dropped]\r\n", unreported);
+            let len = line.len.min(buf.len());
+            buf[..len].copy_from_slice(&line.buf[..len]);
+            return len;
+        }
+        self.buffer.read(buf).await
+    }
+
+    fn push(&self, line: &Line) {
+        let bytes = &line.buf[..line.len];
+        // Concurrent writers could take the free space between the check and the write, a message
+        // may then be cut, but is counted as dropped.
+        let written = if self.buffer.free_capacity() >= bytes.len() {
+            self.buffer.try_write(bytes).unwrap_or(0)
+        } else {
+            0
+        };
+        if written < bytes.len() {
+            self.dropped.lock(|d| {
+                let (dropped, reported) = d.get();
+                d.set((dropped.wrapping_add(1), reported));
+            });
+        }
     }
 
     /// Run the USB logger using the state and USB driver. Never returns.
@@ -91,7 +152,7 @@ impl<const N: usize> UsbLogger<N> {
                 let mut rx: [u8; MAX_PACKET_SIZE as usize] = [0; MAX_PACKET_SIZE as usize];
                 sender.wait_connection().await;
                 loop {
-                    let len = self.buffer.read(&mut rx[..]).await;
+                    let len = self.read(&mut rx[..]).await;
                     let _ = sender.write_packet(&rx[..len]).await;
                 }
             };
@@ -114,18 +175,43 @@ impl<const N: usize> log::Log for UsbLogger<N> {
 
     fn log(&self, record: &Record) {
         if self.enabled(record.metadata()) {
-            let _ = write!(Writer(&self.buffer), "{}\r\n", record.args());
+            let mut line = Line::new();
+            let _ = write!(line, "{}", record.args());
+            line.finish();
+            self.push(&line);
         }
     }
 
     fn flush(&self) {}
 }
 
-struct Writer<'d, const N: usize>(&'d Pipe<CS, N>);
+/// Log message being formatted.
+struct Line {
+    buf: [u8; MAX_LINE_LEN],
+    len: usize,
+}
+
+impl Line {
+    fn new() -> Self {
+        Self {
+            buf: [0; MAX_LINE_LEN],
+            len: 0,
+        }
+    }
+
+    /// Terminate the message, truncating it if needed.
+    fn finish(&mut self) {
+        self.len = self.len.min(MAX_LINE_LEN - 2);
+        self.buf[self.len..self.len + 2].copy_from_slice(b"\r\n");
+        self.len += 2;
+    }
+}
 
-impl<'d, const N: usize> core::fmt::Write for Writer<'d, N> {
+impl core::fmt::Write for Line {
     fn write_str(&mut self, s: &str) -> Result<(), core::fmt::Error> {
-        let _ = self.0.try_write(s.as_bytes());
+        let len = s.len().min(MAX_LINE_LEN - self.len);
+        self.buf[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+        self.len += len;
         Ok(())
     }
 }
@@ -153,3 +239,27 @@ macro_rules! run {
         let _ = LOGGER.run(&mut ::embassy_usb_logger::LoggerState::new(), $p).await;
     };
 }
+
+/// Initialize the logger and drain it into a writer such as a UART, never returns.
+///
+/// Arguments specify the buffer size, log level and the `embedded_io_async::Write` writer, respectively.
+///
+/// # Usage
+///
+/// ```
+/// embassy_usb_logger::drain!(1024, log::LevelFilter::Info, uart_tx);
+/// ```
+///
+/// # Safety
+///
+/// This macro should only be invoked only once since it is setting the global logging state of the application.
+#[macro_export]
+macro_rules! drain {
+    ( $x:expr, $l:expr, $w:expr ) => {
+        static LOGGER: ::embassy_usb_logger::UsbLogger<$x> = ::embassy_usb_logger::UsbLogger::new();
+        unsafe {
+            let _ = ::log::set_logger_racy(&LOGGER).map(|()| log::set_max_level_racy($l));
+        }
+        let _ = LOGGER.drain($w).await;
+    };
+}
@@ -6,6 +6,7 @@ use core::mem::{self, MaybeUninit};
 use core::sync::atomic::{AtomicBool, Ordering};
 use core::task::Poll;
 
+use embassy_futures::poll_once;
 use embassy_sync::blocking_mutex::CriticalSectionMutex;
 use embassy_sync::waitqueue::WakerRegistration;
 
@@ -67,6 +68,9 @@ impl<'a> State<'a> {
 ///   host operating system until a subsequent shorter packet is sent. A zero-length packet (ZLP)
 ///   can be sent if there is no other data to send. This is because USB bulk transactions must be
 ///   terminated with a short packet, even if the bulk endpoint is used for stream-like data.
+///
+/// For throughput, use [`read`](Self::read) and [`write`](Self::write), which transfer several
+/// packets per call and take care of the ZLP. On high-speed devices, use 512-byte packets.
 pub struct CdcAcmClass<'d, D: Driver<'d>> {
     _comm_ep: D::EndpointIn,
     _data_if: InterfaceNumber,
@@ -212,7 +216,8 @@ impl<'d> Handler for Control<'d> {
 
 impl<'d, D: Driver<'d>> CdcAcmClass<'d, D> {
     /// Creates a new CdcAcmClass with the provided UsbBus and `max_packet_size` in bytes. For
-    /// full-speed devices, `max_packet_size` has to be one of 8, 16, 32 or 64.
+    /// full-speed devices, `max_packet_size` has to be one of 8, 16, 32 or 64. For high-speed
+    /// devices, it has to be 512.
     pub fn new(builder: &mut Builder<'d, D>, state: &'d mut State<'d>, max_packet_size: u16) -> Self {
         assert!(builder.control_buf_len() >= 7);
 
@@ -311,6 +316,23 @@ impl<'d, D: Driver<'d>> CdcAcmClass<'d, D> {
         self.read_ep.read(data).await
     }
 
+    /// Writes data into the IN endpoint, as many packets as needed.
+    ///
+    /// The transfer is terminated with a zero-length packet if needed, so the host gets the data
+    /// right away.
+    pub async fn write(&mut self, data: &[u8]) -> Result<(), EndpointError> {
+        write_transfer(&mut self.write_ep, data).await
+    }
+
+    /// Reads data from the OUT endpoint, packet by packet.
+    ///
+    /// This waits for a first packet, then also reads the packets already received by the
+    /// driver, until `data` cannot hold another packet. Must be called with a buffer large enough
+    /// to hold `max_packet_size` bytes.
+    pub async fn read(&mut self, data: &mut [u8]) -> Result<usize, EndpointError> {
+        read_transfer(&mut self.read_ep, data).await
+    }
+
     /// Waits for the USB host to enable this interface
     pub async fn wait_connection(&mut self) {
         self.read_ep.wait_enabled().await;
@@ -401,6 +423,13 @@ impl<'d, D: Driver<'d>> Sender<'d, D> {
         self.write_ep.write(data).await
     }
 
+    /// Writes data into the IN endpoint, as many packets as needed.
+    ///
+    /// See [`CdcAcmClass::write`].
+    pub async fn write(&mut self, data: &[u8]) -> Result<(), EndpointError> {
+        write_transfer(&mut self.write_ep, data).await
+    }
+
     /// Waits for the USB host to enable this interface
     pub async fn wait_connection(&mut self) {
         self.write_ep.wait_enabled().await;
@@ -444,12 +473,48 @@ impl<'d, D: Driver<'d>> Receiver<'d, D> {
         self.read_ep.read(data).await
     }
 
+    /// Reads data from the OUT endpoint, packet by packet.
+    ///
+    /// See [`CdcAcmClass::read`].
+    pub async fn read(&mut self, data: &mut [u8]) -> Result<usize, EndpointError> {
+        read_transfer(&mut self.read_ep, data).await
+    }
+
     /// Waits for the USB host to enable this interface
     pub async fn wait_connection(&mut self) {
         self.read_ep.wait_enabled().await;
     }
 }
 
+async fn write_transfer<E: EndpointIn>(ep: &mut E, data: &[u8]) -> Result<(), EndpointError> {
+    let max_packet_size = ep.info().max_packet_size as usize;
+    for chunk in data.chunks(max_packet_size) {
+        ep.write(chunk).await?;
+    }
+    // A full last packet doesn't end the transfer.
+    if data.len() % max_packet_size == 0 {
+        ep.write(&[]).await?;
+    }
+    Ok(())
+}
+
+async fn read_transfer<E: EndpointOut>(ep: &mut E, data: &mut [u8]) -> Result<usize, EndpointError> {
+    let max_packet_size = ep.info().max_packet_size as usize;
+    let mut len = ep.read(data).await?;
+    let mut last = len;
+
+    // Keep reading while the packets are full, but only the ones already there: a full packet
+    // doesn't tell whether more data is coming.
+    while last == max_packet_size && data.len() - len >= max_packet_size {
+        match poll_once(ep.read(&mut data[len..])) {
+            Poll::Ready(r) => last = r?,
+            Poll::Pending => break,
+        }
+        len += last;
+    }
+    Ok(len)
+}
+
 /// Number of stop bits for LineCoding
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
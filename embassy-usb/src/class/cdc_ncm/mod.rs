@@ -14,9 +14,8 @@
 //!   This is due to regex spaghetti: <https://android.googlesource.com/platform/frameworks/base/+/refs/tags/android-mainline-12.0.0_r84/core/res/res/values/config.xml#417>
 //!   and this nonsense in the linux kernel: <https://github.com/torvalds/linux/blob/c00c5e1d157bec0ef0b0b59aa5482eb8dc7e8e49/drivers/net/usb/usbnet.c#L1751-L1757>
 
-use core::intrinsics::copy_nonoverlapping;
 use core::mem::{size_of, MaybeUninit};
-use core::ptr::addr_of;
+use core::ptr::{addr_of, copy_nonoverlapping};
 
 use crate::control::{self, InResponse, OutResponse, Recipient, Request, RequestType};
 use crate::driver::{Driver, Endpoint, EndpointError, EndpointIn, EndpointOut};
@@ -52,7 +51,7 @@ const REQ_GET_NTB_PARAMETERS: u8 = 0x80;
 //const REQ_SET_NET_ADDRESS: u8 = 0x82;
 //const REQ_GET_NTB_FORMAT: u8 = 0x83;
 //const REQ_SET_NTB_FORMAT: u8 = 0x84;
-//const REQ_GET_NTB_INPUT_SIZE: u8 = 0x85;
+const REQ_GET_NTB_INPUT_SIZE: u8 = 0x85;
 const REQ_SET_NTB_INPUT_SIZE: u8 = 0x86;
 //const REQ_GET_MAX_DATAGRAM_SIZE: u8 = 0x87;
 //const REQ_SET_MAX_DATAGRAM_SIZE: u8 = 0x88;
@@ -63,9 +62,13 @@ const REQ_SET_NTB_INPUT_SIZE: u8 = 0x86;
 //const NOTIF_POLL_INTERVAL: u8 = 20;
 
 const NTB_MAX_SIZE: usize = 2048;
+// Largest IN NTB we send: the header and one datagram of wMaxSegmentSize.
+const NTB_IN_MIN_SIZE: u32 = 28 + 1514;
 const SIG_NTH: u32 = 0x484d_434e;
 const SIG_NDP_NO_FCS: u32 = 0x304d_434e;
 const SIG_NDP_WITH_FCS: u32 = 0x314d_434e;
+/// Maximum number of NDPs processed per NTB, bounding the work done on a malformed NDP chain.
+const MAX_NDPS: usize = 8;
 
 const ALTERNATE_SETTING_DISABLED: u8 = 0x00;
 const ALTERNATE_SETTING_ENABLED: u8 = 0x01;
@@ -150,6 +153,7 @@ struct Control<'a> {
     mac_addr_str: [u8; 12],
     comm_if: InterfaceNumber,
     data_if: InterfaceNumber,
+    ntb_input_size: u32,
 }
 
 impl<'d> Handler for Control<'d> {
@@ -165,7 +169,7 @@ impl<'d> Handler for Control<'d> {
         }
     }
 
-    fn control_out(&mut self, req: control::Request, _data: &[u8]) -> Option<OutResponse> {
+    fn control_out(&mut self, req: control::Request, data: &[u8]) -> Option<OutResponse> {
         if (req.request_type, req.recipient, req.index)
             != (RequestType::Class, Recipient::Interface, self.comm_if.0 as u16)
         {
@@ -179,7 +183,17 @@ impl<'d> Handler for Control<'d> {
                 Some(OutResponse::Accepted)
             }
             REQ_SET_NTB_INPUT_SIZE => {
-                // TODO
+                // dwNtbInMaxSize, optionally followed by wNtbInMaxDatagrams which we ignore since
+                // we only send one datagram per NTB.
+                let Some(size) = data.get(..4) else {
+                    return Some(OutResponse::Rejected);
+                };
+                let size = u32::from_le_bytes(size.try_into().unwrap());
+                if size < NTB_IN_MIN_SIZE {
+                    warn!("ncm: NTB input size {} too small", size);
+                    return Some(OutResponse::Rejected);
+                }
+                self.ntb_input_size = size.min(NTB_MAX_SIZE as u32);
                 Some(OutResponse::Accepted)
             }
             _ => Some(OutResponse::Rejected),
@@ -210,11 +224,15 @@ impl<'d> Handler for Control<'d> {
                         divisor: 4,
                         payload_remainder: 0,
                         out_alignment: 4,
-                        max_datagram_count: 1, // We only decode 1 packet per NTB
+                        max_datagram_count: 0, // no limit, all the datagrams of an NTB are decoded
                     },
                 };
                 Some(InResponse::Accepted(byteify(buf, res)))
             }
+            REQ_GET_NTB_INPUT_SIZE => {
+                buf[..4].copy_from_slice(&self.ntb_input_size.to_le_bytes());
+                Some(InResponse::Accepted(&buf[..4]))
+            }
             _ => Some(InResponse::Rejected),
         }
     }
@@ -332,6 +350,7 @@ impl<'d, D: Driver<'d>> CdcNcmClass<'d, D> {
             mac_addr_str: [0; 12],
             comm_if,
             data_if,
+            ntb_input_size: NTB_MAX_SIZE as u32,
         });
         builder.handler(control);
 
@@ -360,6 +379,9 @@ impl<'d, D: Driver<'d>> CdcNcmClass<'d, D> {
                 data_if: self.data_if,
                 comm_ep: self.comm_ep,
                 read_ep: self.read_ep,
+                ntb: [0; NTB_MAX_SIZE],
+                ntb_len: 0,
+                ndp_pos: None,
             },
         )
     }
@@ -438,27 +460,47 @@ pub struct Receiver<'d, D: Driver<'d>> {
     data_if: InterfaceNumber,
     comm_ep: D::EndpointIn,
     read_ep: D::EndpointOut,
+
+    /// Last received NTB. The host may pack several datagrams in one NTB.
+    ntb: [u8; NTB_MAX_SIZE],
+    ntb_len: usize,
+    /// Position of the next datagram pointer entry to process in `ntb`, and of its NDP.
+    ndp_pos: Option<NdpPosition>,
+}
+
+#[derive(Clone, Copy)]
+struct NdpPosition {
+    ndp: usize,
+    /// End of the NDP, as given by its `wLength`.
+    end: usize,
+    entry: usize,
+    /// Number of NDPs of the current NTB processed so far, including this one.
+    count: usize,
 }
 
 impl<'d, D: Driver<'d>> Receiver<'d, D> {
-    /// Write a network packet.
+    /// Read a network packet.
     ///
     /// This waits until a packet is successfully received from the endpoint buffers.
     pub async fn read_packet(&mut self, buf: &mut [u8]) -> Result<usize, EndpointError> {
         // Retry loop
         loop {
+            if let Some(n) = self.next_datagram(buf) {
+                return Ok(n);
+            }
+
             // read NTB
-            let mut ntb = [0u8; NTB_MAX_SIZE];
             let mut pos = 0;
             loop {
-                let n = self.read_ep.read(&mut ntb[pos..]).await?;
+                let n = self.read_ep.read(&mut self.ntb[pos..]).await?;
                 pos += n;
                 if n < self.read_ep.info().max_packet_size as usize || pos == NTB_MAX_SIZE {
                     break;
                 }
             }
+            self.ntb_len = pos;
 
-            let ntb = &ntb[..pos];
+            let ntb = &self.ntb[..pos];
 
             // Process NTB header (NTH)
             let Some(nth) = ntb.get(..12) else {
@@ -471,33 +513,85 @@ impl<'d, D: Driver<'d>> Receiver<'d, D> {
                 continue;
             }
             let ndp_idx = u16::from_le_bytes(nth[10..12].try_into().unwrap()) as usize;
+            self.ndp_pos = self.check_ndp(ndp_idx, 1);
+        }
+    }
 
-            // Process NTB Datagram Pointer (NDP)
-            let Some(ndp) = ntb.get(ndp_idx..ndp_idx + 12) else {
-                warn!("NTH has an NDP pointer out of range.");
-                continue;
+    /// Check the NDP at `ndp_idx`, the `count`th of the NTB, returning the position of its first
+    /// datagram pointer entry.
+    fn check_ndp(&self, ndp_idx: usize, count: usize) -> Option<NdpPosition> {
+        let ntb = &self.ntb[..self.ntb_len];
+
+        // Process NTB Datagram Pointer (NDP)
+        let Some(ndp) = ntb.get(ndp_idx..ndp_idx + 8) else {
+            warn!("NTH has an NDP pointer out of range.");
+            return None;
+        };
+        let sig = u32::from_le_bytes(ndp[0..4].try_into().unwrap());
+        if sig != SIG_NDP_NO_FCS && sig != SIG_NDP_WITH_FCS {
+            warn!("Received bad NDP sig.");
+            return None;
+        }
+        let len = u16::from_le_bytes(ndp[4..6].try_into().unwrap()) as usize;
+        if len < 8 || ndp_idx + len > ntb.len() {
+            warn!("Received NDP with a bad length.");
+            return None;
+        }
+
+        Some(NdpPosition {
+            ndp: ndp_idx,
+            end: ndp_idx + len,
+            entry: ndp_idx + 8,
+            count,
+        })
+    }
+
+    /// Copy the next datagram of the current NTB to `buf`, following the NDP chain.
+    fn next_datagram(&mut self, buf: &mut [u8]) -> Option<usize> {
+        loop {
+            let pos = self.ndp_pos?;
+            let ntb = &self.ntb[..self.ntb_len];
+
+            // Entries past the NDP length are not read, a table without terminator ends there.
+            let (datagram_index, datagram_len) = match ntb.get(pos.entry..(pos.entry + 4).min(pos.end)) {
+                Some(&[i0, i1, l0, l1]) => (
+                    u16::from_le_bytes([i0, i1]) as usize,
+                    u16::from_le_bytes([l0, l1]) as usize,
+                ),
+                _ => (0, 0),
             };
-            let sig = u32::from_le_bytes(ndp[0..4].try_into().unwrap());
-            if sig != SIG_NDP_NO_FCS && sig != SIG_NDP_WITH_FCS {
-                warn!("Received bad NDP sig.");
-                continue;
-            }
-            let datagram_index = u16::from_le_bytes(ndp[8..10].try_into().unwrap()) as usize;
-            let datagram_len = u16::from_le_bytes(ndp[10..12].try_into().unwrap()) as usize;
 
             if datagram_index == 0 || datagram_len == 0 {
-                // empty, ignore. This is allowed by the spec, so don't warn.
+                // End of this NDP, continue with the next one if any. The chain must go forward
+                // in the NTB, so that a malformed one can't loop forever.
+                let next_ndp_idx = u16::from_le_bytes(ntb[pos.ndp + 6..pos.ndp + 8].try_into().unwrap()) as usize;
+                self.ndp_pos = match next_ndp_idx {
+                    0 => None,
+                    _ if next_ndp_idx <= pos.ndp || pos.count >= MAX_NDPS => {
+                        warn!("NDP has a bad next NDP pointer.");
+                        None
+                    }
+                    _ => self.check_ndp(next_ndp_idx, pos.count + 1),
+                };
                 continue;
             }
+            self.ndp_pos = Some(NdpPosition {
+                entry: pos.entry + 4,
+                ..pos
+            });
 
             // Process actual datagram, finally.
             let Some(datagram) = ntb.get(datagram_index..datagram_index + datagram_len) else {
                 warn!("NDP has a datagram pointer out of range.");
                 continue;
             };
+            if datagram_len > buf.len() {
+                warn!("Received datagram larger than the buffer, dropping.");
+                continue;
+            }
             buf[..datagram_len].copy_from_slice(datagram);
 
-            return Ok(datagram_len);
+            return Some(datagram_len);
         }
     }
 
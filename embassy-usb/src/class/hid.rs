@@ -2,8 +2,11 @@
 
 use core::mem::MaybeUninit;
 use core::ops::Range;
-use core::sync::atomic::{AtomicUsize, Ordering};
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
 
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
 #[cfg(feature = "usbd-hid")]
 use ssmarshal::serialize;
 #[cfg(feature = "usbd-hid")]
@@ -14,9 +17,12 @@ use crate::driver::{Driver, Endpoint, EndpointError, EndpointIn, EndpointOut};
 use crate::types::InterfaceNumber;
 use crate::{Builder, Handler};
 
+pub mod boot;
+pub mod descriptor;
+
 const USB_CLASS_HID: u8 = 0x03;
 const USB_SUBCLASS_NONE: u8 = 0x00;
-const USB_PROTOCOL_NONE: u8 = 0x00;
+const USB_SUBCLASS_BOOT: u8 = 0x01;
 
 // HID
 const HID_DESC_DESCTYPE_HID: u8 = 0x21;
@@ -31,6 +37,9 @@ const HID_REQ_SET_REPORT: u8 = 0x09;
 const HID_REQ_GET_PROTOCOL: u8 = 0x03;
 const HID_REQ_SET_PROTOCOL: u8 = 0x0b;
 
+/// Maximum length of the output reports sent with `SET_REPORT` delivered to [`HidReader::recv_report`].
+pub const MAX_SET_REPORT_LEN: usize = 64;
+
 /// Configuration for the HID class.
 pub struct Config<'d> {
     /// HID report descriptor.
@@ -48,6 +57,46 @@ pub struct Config<'d> {
 
     /// Max packet size for both the IN and OUT endpoints.
     pub max_packet_size: u16,
+
+    /// Boot protocol supported by the device.
+    ///
+    /// Anything but [`HidBootProtocol::None`] declares the interface as a boot device, so that
+    /// BIOSes can use it. The report descriptor must then start with the boot report layout, see
+    /// the [`boot`] module.
+    pub boot_protocol: HidBootProtocol,
+}
+
+/// Boot protocol of a HID interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum HidBootProtocol {
+    /// Not a boot device.
+    None = 0,
+    /// Boot keyboard.
+    Keyboard = 1,
+    /// Boot mouse.
+    Mouse = 2,
+}
+
+/// Protocol selected by the host with `SET_PROTOCOL`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum HidProtocol {
+    /// Boot protocol, reports use the fixed boot layout.
+    Boot = 0,
+    /// Report protocol, reports use the layout of the report descriptor.
+    Report = 1,
+}
+
+impl HidProtocol {
+    fn from_bits(value: u8) -> Self {
+        match value {
+            0 => HidProtocol::Boot,
+            _ => HidProtocol::Report,
+        }
+    }
 }
 
 /// Report ID
@@ -77,6 +126,37 @@ impl ReportId {
 pub struct State<'d> {
     control: MaybeUninit<Control<'d>>,
     out_report_offset: AtomicUsize,
+    shared: Shared,
+}
+
+/// State shared between the control handler and the endpoints.
+struct Shared {
+    protocol: AtomicU8,
+    /// Idle rate of all input reports, in units of 4 ms. 0 is indefinite.
+    idle: AtomicU8,
+    /// Last output report sent with `SET_REPORT`, when there is no request handler.
+    set_report: Signal<CriticalSectionRawMutex, heapless::Vec<u8, MAX_SET_REPORT_LEN>>,
+}
+
+impl Shared {
+    const fn new() -> Self {
+        Self {
+            protocol: AtomicU8::new(HidProtocol::Report as u8),
+            idle: AtomicU8::new(0),
+            set_report: Signal::new(),
+        }
+    }
+
+    fn protocol(&self) -> HidProtocol {
+        HidProtocol::from_bits(self.protocol.load(Ordering::Relaxed))
+    }
+
+    fn idle_ms(&self) -> Option<u32> {
+        match self.idle.load(Ordering::Relaxed) {
+            0 => None,
+            idle => Some(4 * u32::from(idle)),
+        }
+    }
 }
 
 impl<'d> Default for State<'d> {
@@ -91,6 +171,7 @@ impl<'d> State<'d> {
         State {
             control: MaybeUninit::uninit(),
             out_report_offset: AtomicUsize::new(0),
+            shared: Shared::new(),
         }
     }
 }
@@ -106,13 +187,18 @@ fn build<'d, D: Driver<'d>>(
     state: &'d mut State<'d>,
     config: Config<'d>,
     with_out_endpoint: bool,
-) -> (Option<D::EndpointOut>, D::EndpointIn, &'d AtomicUsize) {
+) -> (Option<D::EndpointOut>, D::EndpointIn, &'d AtomicUsize, &'d Shared) {
     let len = config.report_descriptor.len();
+    let subclass = match config.boot_protocol {
+        HidBootProtocol::None => USB_SUBCLASS_NONE,
+        _ => USB_SUBCLASS_BOOT,
+    };
+    let protocol = config.boot_protocol as u8;
 
-    let mut func = builder.function(USB_CLASS_HID, USB_SUBCLASS_NONE, USB_PROTOCOL_NONE);
+    let mut func = builder.function(USB_CLASS_HID, subclass, protocol);
     let mut iface = func.interface();
     let if_num = iface.interface_number();
-    let mut alt = iface.alt_setting(USB_CLASS_HID, USB_SUBCLASS_NONE, USB_PROTOCOL_NONE, None);
+    let mut alt = iface.alt_setting(USB_CLASS_HID, subclass, protocol, None);
 
     // HID descriptor
     alt.descriptor(
@@ -147,10 +233,12 @@ fn build<'d, D: Driver<'d>>(
         config.report_descriptor,
         config.request_handler,
         &state.out_report_offset,
+        &state.shared,
+        config.boot_protocol != HidBootProtocol::None,
     ));
     builder.handler(control);
 
-    (ep_out, ep_in, &state.out_report_offset)
+    (ep_out, ep_in, &state.out_report_offset, &state.shared)
 }
 
 impl<'d, D: Driver<'d>, const READ_N: usize, const WRITE_N: usize> HidReaderWriter<'d, D, READ_N, WRITE_N> {
@@ -160,14 +248,15 @@ impl<'d, D: Driver<'d>, const READ_N: usize, const WRITE_N: usize> HidReaderWrit
     /// HID reports, consider using [`HidWriter::new`] instead, which allocates an IN endpoint only.
     ///
     pub fn new(builder: &mut Builder<'d, D>, state: &'d mut State<'d>, config: Config<'d>) -> Self {
-        let (ep_out, ep_in, offset) = build(builder, state, config, true);
+        let (ep_out, ep_in, offset, shared) = build(builder, state, config, true);
 
         Self {
             reader: HidReader {
                 ep_out: ep_out.unwrap(),
                 offset,
+                shared,
             },
-            writer: HidWriter { ep_in },
+            writer: HidWriter { ep_in, shared },
         }
     }
 
@@ -182,6 +271,18 @@ impl<'d, D: Driver<'d>, const READ_N: usize, const WRITE_N: usize> HidReaderWrit
         self.writer.ready().await;
     }
 
+    /// Protocol currently selected by the host.
+    pub fn protocol(&self) -> HidProtocol {
+        self.writer.protocol()
+    }
+
+    /// Idle rate of the input reports set by the host, `None` if indefinite.
+    ///
+    /// See [`HidWriter::idle_ms`].
+    pub fn idle_ms(&self) -> Option<u32> {
+        self.writer.idle_ms()
+    }
+
     /// Writes an input report by serializing the given report structure.
     #[cfg(feature = "usbd-hid")]
     pub async fn write_serialize<IR: AsInputReport>(&mut self, r: &IR) -> Result<(), EndpointError> {
@@ -199,6 +300,13 @@ impl<'d, D: Driver<'d>, const READ_N: usize, const WRITE_N: usize> HidReaderWrit
     pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, ReadError> {
         self.reader.read(buf).await
     }
+
+    /// Receives an output report from the Interrupt Out pipe or from a `SET_REPORT` request.
+    ///
+    /// See [`HidReader::recv_report`].
+    pub async fn recv_report(&mut self, buf: &mut [u8]) -> Result<usize, ReadError> {
+        self.reader.recv_report(buf).await
+    }
 }
 
 /// USB HID writer.
@@ -206,6 +314,7 @@ impl<'d, D: Driver<'d>, const READ_N: usize, const WRITE_N: usize> HidReaderWrit
 /// You can obtain a `HidWriter` using [`HidReaderWriter::split`].
 pub struct HidWriter<'d, D: Driver<'d>, const N: usize> {
     ep_in: D::EndpointIn,
+    shared: &'d Shared,
 }
 
 /// USB HID reader.
@@ -214,6 +323,7 @@ pub struct HidWriter<'d, D: Driver<'d>, const N: usize> {
 pub struct HidReader<'d, D: Driver<'d>, const N: usize> {
     ep_out: D::EndpointOut,
     offset: &'d AtomicUsize,
+    shared: &'d Shared,
 }
 
 /// Error when reading a HID report.
@@ -249,11 +359,11 @@ impl<'d, D: Driver<'d>, const N: usize> HidWriter<'d, D, N> {
     /// of CPU on the device & bandwidth on the bus. A value of 10 is reasonable for
     /// high performance uses, and a value of 255 is good for best-effort usecases.
     pub fn new(builder: &mut Builder<'d, D>, state: &'d mut State<'d>, config: Config<'d>) -> Self {
-        let (ep_out, ep_in, _offset) = build(builder, state, config, false);
+        let (ep_out, ep_in, _offset, shared) = build(builder, state, config, false);
 
         assert!(ep_out.is_none());
 
-        Self { ep_in }
+        Self { ep_in, shared }
     }
 
     /// Waits for the interrupt in endpoint to be enabled.
@@ -261,6 +371,22 @@ impl<'d, D: Driver<'d>, const N: usize> HidWriter<'d, D, N> {
         self.ep_in.wait_enabled().await;
     }
 
+    /// Protocol currently selected by the host.
+    ///
+    /// When it is [`HidProtocol::Boot`], input reports must use the boot layout.
+    pub fn protocol(&self) -> HidProtocol {
+        self.shared.protocol()
+    }
+
+    /// Idle rate of the input reports set by the host, `None` if indefinite.
+    ///
+    /// While the idle rate is not indefinite, the last report should be sent again at that rate
+    /// even if it did not change. The idle rate is only tracked for all the reports at once,
+    /// requests for single report IDs are passed to the [`RequestHandler`].
+    pub fn idle_ms(&self) -> Option<u32> {
+        self.shared.idle_ms()
+    }
+
     /// Writes an input report by serializing the given report structure.
     #[cfg(feature = "usbd-hid")]
     pub async fn write_serialize<IR: AsInputReport>(&mut self, r: &IR) -> Result<(), EndpointError> {
@@ -371,6 +497,30 @@ impl<'d, D: Driver<'d>, const N: usize> HidReader<'d, D, N> {
             Ok(total)
         }
     }
+
+    /// Receives an output report from the Interrupt Out pipe or from a `SET_REPORT` request.
+    ///
+    /// Hosts send some output reports over the control pipe even when there is an Interrupt Out
+    /// pipe, for example the LEDs of boot keyboards. They are delivered here unless the
+    /// [`RequestHandler`] accepts them, and if they are at most [`MAX_SET_REPORT_LEN`] bytes long.
+    /// If several are received before this is called, only the last one is kept.
+    ///
+    /// See [`read`](Self::read) about dropping the future while a report is partially read.
+    pub async fn recv_report(&mut self, buf: &mut [u8]) -> Result<usize, ReadError> {
+        // Borrowed separately from `self`, which the read borrows mutably.
+        let shared = self.shared;
+        let res = select(self.read(buf), shared.set_report.wait()).await;
+        match res {
+            Either::First(res) => res,
+            Either::Second(report) => {
+                let Some(buf) = buf.get_mut(..report.len()) else {
+                    return Err(ReadError::BufferOverflow);
+                };
+                buf.copy_from_slice(&report);
+                Ok(report.len())
+            }
+        }
+    }
 }
 
 /// Handler for HID-related control requests.
@@ -406,6 +556,11 @@ pub trait RequestHandler {
     fn set_idle_ms(&self, id: Option<ReportId>, duration_ms: u32) {
         let _ = (id, duration_ms);
     }
+
+    /// Called when the host selects the protocol of a boot device.
+    fn set_protocol(&self, protocol: HidProtocol) {
+        let _ = protocol;
+    }
 }
 
 struct Control<'d> {
@@ -413,6 +568,8 @@ struct Control<'d> {
     report_descriptor: &'d [u8],
     request_handler: Option<&'d dyn RequestHandler>,
     out_report_offset: &'d AtomicUsize,
+    shared: &'d Shared,
+    boot_device: bool,
     hid_descriptor: [u8; 9],
 }
 
@@ -422,12 +579,16 @@ impl<'d> Control<'d> {
         report_descriptor: &'d [u8],
         request_handler: Option<&'d dyn RequestHandler>,
         out_report_offset: &'d AtomicUsize,
+        shared: &'d Shared,
+        boot_device: bool,
     ) -> Self {
         Control {
             if_num,
             report_descriptor,
             request_handler,
             out_report_offset,
+            shared,
+            boot_device,
             hid_descriptor: [
                 // Length of buf inclusive of size prefix
                 9,
@@ -453,6 +614,9 @@ impl<'d> Control<'d> {
 impl<'d> Handler for Control<'d> {
     fn reset(&mut self) {
         self.out_report_offset.store(0, Ordering::Release);
+        self.shared.protocol.store(HidProtocol::Report as u8, Ordering::Relaxed);
+        self.shared.idle.store(0, Ordering::Relaxed);
+        self.shared.set_report.reset();
     }
 
     fn control_out(&mut self, req: Request, data: &[u8]) -> Option<OutResponse> {
@@ -468,8 +632,11 @@ impl<'d> Handler for Control<'d> {
         trace!("HID control_out {:?} {=[u8]:x}", req, data);
         match req.request {
             HID_REQ_SET_IDLE => {
+                let id = req.value as u8;
+                if id == 0 {
+                    self.shared.idle.store((req.value >> 8) as u8, Ordering::Relaxed);
+                }
                 if let Some(handler) = self.request_handler {
-                    let id = req.value as u8;
                     let id = (id != 0).then_some(ReportId::In(id));
                     let dur = u32::from(req.value >> 8);
                     let dur = if dur == 0 { u32::MAX } else { 4 * dur };
@@ -477,16 +644,35 @@ impl<'d> Handler for Control<'d> {
                 }
                 Some(OutResponse::Accepted)
             }
-            HID_REQ_SET_REPORT => match (ReportId::try_from(req.value), self.request_handler) {
-                (Ok(id), Some(handler)) => Some(handler.set_report(id, data)),
-                _ => Some(OutResponse::Rejected),
-            },
+            HID_REQ_SET_REPORT => {
+                let Ok(id) = ReportId::try_from(req.value) else {
+                    return Some(OutResponse::Rejected);
+                };
+                let response = match self.request_handler {
+                    Some(handler) => handler.set_report(id, data),
+                    None => OutResponse::Rejected,
+                };
+
+                // Output reports not accepted by the handler are delivered to `HidReader::recv_report`.
+                match (response, id, heapless::Vec::from_slice(data)) {
+                    (OutResponse::Rejected, ReportId::Out(_), Ok(report)) => {
+                        self.shared.set_report.signal(report);
+                        Some(OutResponse::Accepted)
+                    }
+                    (response, _, _) => Some(response),
+                }
+            }
             HID_REQ_SET_PROTOCOL => {
-                if req.value == 1 {
+                let protocol = HidProtocol::from_bits(req.value as u8);
+                if req.value <= 1 && (protocol == HidProtocol::Report || self.boot_device) {
+                    self.shared.protocol.store(protocol as u8, Ordering::Relaxed);
+                    if let Some(handler) = self.request_handler {
+                        handler.set_protocol(protocol);
+                    }
                     Some(OutResponse::Accepted)
                 } else {
                     warn!("HID Boot Protocol is unsupported.");
-                    Some(OutResponse::Rejected) // UNSUPPORTED: Boot Protocol
+                    Some(OutResponse::Rejected)
                 }
             }
             _ => Some(OutResponse::Rejected),
@@ -524,23 +710,23 @@ impl<'d> Handler for Control<'d> {
                         }
                     }
                     HID_REQ_GET_IDLE => {
-                        if let Some(handler) = self.request_handler {
-                            let id = req.value as u8;
-                            let id = (id != 0).then_some(ReportId::In(id));
-                            if let Some(dur) = handler.get_idle_ms(id) {
-                                let dur = u8::try_from(dur / 4).unwrap_or(0);
-                                buf[0] = dur;
+                        let id = req.value as u8;
+                        let id = (id != 0).then_some(ReportId::In(id));
+                        let dur = self.request_handler.and_then(|x| x.get_idle_ms(id));
+                        match (dur, id) {
+                            (Some(dur), _) => {
+                                buf[0] = u8::try_from(dur / 4).unwrap_or(0);
                                 Some(InResponse::Accepted(&buf[0..1]))
-                            } else {
-                                Some(InResponse::Rejected)
                             }
-                        } else {
-                            Some(InResponse::Rejected)
+                            (None, None) => {
+                                buf[0] = self.shared.idle.load(Ordering::Relaxed);
+                                Some(InResponse::Accepted(&buf[0..1]))
+                            }
+                            (None, Some(_)) => Some(InResponse::Rejected),
                         }
                     }
                     HID_REQ_GET_PROTOCOL => {
-                        // UNSUPPORTED: Boot Protocol
-                        buf[0] = 1;
+                        buf[0] = self.shared.protocol.load(Ordering::Relaxed);
                         Some(InResponse::Accepted(&buf[0..1]))
                     }
                     _ => Some(InResponse::Rejected),
@@ -550,3 +736,199 @@ impl<'d> Handler for Control<'d> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use core::future::pending;
+    use core::sync::atomic::AtomicUsize;
+
+    use embassy_futures::block_on;
+
+    use super::*;
+    use crate::driver::{
+        Bus, ControlPipe, EndpointAddress, EndpointAllocError, EndpointInfo, EndpointType, Event, Unsupported,
+    };
+
+    /// OUT endpoint receiving `packet` once, then nothing.
+    struct MockEndpointOut {
+        info: EndpointInfo,
+        packet: Option<&'static [u8]>,
+    }
+
+    impl Endpoint for MockEndpointOut {
+        fn info(&self) -> &EndpointInfo {
+            &self.info
+        }
+
+        async fn wait_enabled(&mut self) {}
+    }
+
+    impl EndpointOut for MockEndpointOut {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, EndpointError> {
+            match self.packet.take() {
+                Some(packet) => {
+                    buf[..packet.len()].copy_from_slice(packet);
+                    Ok(packet.len())
+                }
+                None => pending().await,
+            }
+        }
+    }
+
+    /// Uninhabited type for the parts of the driver the tests do not use.
+    enum Never {}
+
+    impl Endpoint for Never {
+        fn info(&self) -> &EndpointInfo {
+            match *self {}
+        }
+
+        async fn wait_enabled(&mut self) {
+            match *self {}
+        }
+    }
+
+    impl EndpointIn for Never {
+        async fn write(&mut self, _buf: &[u8]) -> Result<(), EndpointError> {
+            match *self {}
+        }
+    }
+
+    impl ControlPipe for Never {
+        fn max_packet_size(&self) -> usize {
+            match *self {}
+        }
+
+        async fn setup(&mut self) -> [u8; 8] {
+            match *self {}
+        }
+
+        async fn data_out(&mut self, _buf: &mut [u8], _first: bool, _last: bool) -> Result<usize, EndpointError> {
+            match *self {}
+        }
+
+        async fn data_in(&mut self, _data: &[u8], _first: bool, _last: bool) -> Result<(), EndpointError> {
+            match *self {}
+        }
+
+        async fn accept(&mut self) {
+            match *self {}
+        }
+
+        async fn reject(&mut self) {
+            match *self {}
+        }
+
+        async fn accept_set_address(&mut self, _addr: u8) {
+            match *self {}
+        }
+    }
+
+    impl Bus for Never {
+        async fn enable(&mut self) {
+            match *self {}
+        }
+
+        async fn disable(&mut self) {
+            match *self {}
+        }
+
+        async fn poll(&mut self) -> Event {
+            match *self {}
+        }
+
+        fn endpoint_set_enabled(&mut self, _ep_addr: EndpointAddress, _enabled: bool) {
+            match *self {}
+        }
+
+        fn endpoint_set_stalled(&mut self, _ep_addr: EndpointAddress, _stalled: bool) {
+            match *self {}
+        }
+
+        fn endpoint_is_stalled(&mut self, _ep_addr: EndpointAddress) -> bool {
+            match *self {}
+        }
+
+        async fn remote_wakeup(&mut self) -> Result<(), Unsupported> {
+            match *self {}
+        }
+    }
+
+    enum MockDriver {}
+
+    impl<'a> Driver<'a> for MockDriver {
+        type EndpointOut = MockEndpointOut;
+        type EndpointIn = Never;
+        type ControlPipe = Never;
+        type Bus = Never;
+
+        fn alloc_endpoint_out(
+            &mut self,
+            _ep_type: EndpointType,
+            _max_packet_size: u16,
+            _interval_ms: u8,
+        ) -> Result<Self::EndpointOut, EndpointAllocError> {
+            match *self {}
+        }
+
+        fn alloc_endpoint_in(
+            &mut self,
+            _ep_type: EndpointType,
+            _max_packet_size: u16,
+            _interval_ms: u8,
+        ) -> Result<Self::EndpointIn, EndpointAllocError> {
+            match *self {}
+        }
+
+        fn start(self, _control_max_packet_size: u16) -> (Self::Bus, Self::ControlPipe) {
+            match self {}
+        }
+    }
+
+    fn reader<'d>(
+        offset: &'d AtomicUsize,
+        shared: &'d Shared,
+        packet: Option<&'static [u8]>,
+    ) -> HidReader<'d, MockDriver, 4> {
+        HidReader {
+            ep_out: MockEndpointOut {
+                info: EndpointInfo {
+                    addr: EndpointAddress::from(0x01),
+                    ep_type: EndpointType::Interrupt,
+                    max_packet_size: 8,
+                    interval_ms: 1,
+                },
+                packet,
+            },
+            offset,
+            shared,
+        }
+    }
+
+    #[test]
+    fn recv_report_from_interrupt_out() {
+        let offset = AtomicUsize::new(0);
+        let shared = Shared::new();
+        let mut reader = reader(&offset, &shared, Some(&[1, 2, 3]));
+
+        let mut buf = [0; 4];
+        assert_eq!(block_on(reader.recv_report(&mut buf)), Ok(3));
+        assert_eq!(buf[..3], [1, 2, 3]);
+    }
+
+    #[test]
+    fn recv_report_from_set_report() {
+        let offset = AtomicUsize::new(0);
+        let shared = Shared::new();
+        shared.set_report.signal(heapless::Vec::from_slice(&[4, 5]).unwrap());
+        let mut reader = reader(&offset, &shared, None);
+
+        let mut buf = [0; 4];
+        assert_eq!(block_on(reader.recv_report(&mut buf)), Ok(2));
+        assert_eq!(buf[..2], [4, 5]);
+
+        shared.set_report.signal(heapless::Vec::from_slice(&[0; 8]).unwrap());
+        let mut buf = [0; 4];
+        assert_eq!(block_on(reader.recv_report(&mut buf)), Err(ReadError::BufferOverflow));
+    }
+}
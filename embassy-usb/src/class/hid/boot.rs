@@ -0,0 +1,171 @@
+//! Boot keyboard and mouse presets.
+//!
+//! The report descriptors are the ones of the HID 1.11 specification (appendix B), so the same
+//! reports are valid in both the boot and the report protocol.
+
+use super::descriptor::{generic_desktop, Collection, ItemFlags, ReportDescriptor, ReportDescriptorBuilder, UsagePage};
+use super::{Config, HidBootProtocol, RequestHandler};
+
+/// Report descriptor of a boot keyboard.
+///
+/// Input report: [`KeyboardReport`]. Output report: one byte of LED states, see [`KeyboardLeds`].
+pub static KEYBOARD_REPORT_DESCRIPTOR: ReportDescriptor<64> = ReportDescriptorBuilder::new()
+    .usage_page(UsagePage::GenericDesktop)
+    .usage(generic_desktop::KEYBOARD)
+    .collection(Collection::Application)
+    // Modifiers
+    .usage_page(UsagePage::Keyboard)
+    .usage_minimum(0xE0)
+    .usage_maximum(0xE7)
+    .logical_minimum(0)
+    .logical_maximum(1)
+    .report_size(1)
+    .report_count(8)
+    .input(ItemFlags::DATA_VARIABLE_ABSOLUTE)
+    // Reserved
+    .report_count(1)
+    .report_size(8)
+    .input(ItemFlags::CONSTANT)
+    // LEDs
+    .report_count(5)
+    .report_size(1)
+    .usage_page(UsagePage::Leds)
+    .usage_minimum(1)
+    .usage_maximum(5)
+    .output(ItemFlags::DATA_VARIABLE_ABSOLUTE)
+    .report_count(1)
+    .report_size(3)
+    .output(ItemFlags::CONSTANT)
+    // Key codes
+    .report_count(6)
+    .report_size(8)
+    .logical_minimum(0)
+    .logical_maximum(101)
+    .usage_page(UsagePage::Keyboard)
+    .usage_minimum(0)
+    .usage_maximum(101)
+    .input(ItemFlags::DATA_ARRAY_ABSOLUTE)
+    .end_collection()
+    .build();
+
+/// Report descriptor of a boot mouse with three buttons.
+///
+/// Input report: [`MouseReport`].
+pub static MOUSE_REPORT_DESCRIPTOR: ReportDescriptor<64> = ReportDescriptorBuilder::new()
+    .usage_page(UsagePage::GenericDesktop)
+    .usage(generic_desktop::MOUSE)
+    .collection(Collection::Application)
+    .usage(generic_desktop::POINTER)
+    .collection(Collection::Physical)
+    // Buttons
+    .report_count(3)
+    .report_size(1)
+    .usage_page(UsagePage::Button)
+    .usage_minimum(1)
+    .usage_maximum(3)
+    .logical_minimum(0)
+    .logical_maximum(1)
+    .input(ItemFlags::DATA_VARIABLE_ABSOLUTE)
+    // Padding
+    .report_count(1)
+    .report_size(5)
+    .input(ItemFlags::CONSTANT)
+    // Movement
+    .report_size(8)
+    .report_count(2)
+    .usage_page(UsagePage::GenericDesktop)
+    .usage(generic_desktop::X)
+    .usage(generic_desktop::Y)
+    .logical_minimum(-127)
+    .logical_maximum(127)
+    .input(ItemFlags::DATA_VARIABLE_RELATIVE)
+    .end_collection()
+    .end_collection()
+    .build();
+
+/// Configuration of a boot keyboard, with an 8-byte input report and a 1-byte output report.
+///
+/// Use it with `HidReaderWriter<_, 1, 8>`.
+pub fn keyboard_config<'d>(request_handler: Option<&'d dyn RequestHandler>, poll_ms: u8) -> Config<'d> {
+    Config {
+        report_descriptor: KEYBOARD_REPORT_DESCRIPTOR.as_bytes(),
+        request_handler,
+        poll_ms,
+        max_packet_size: 8,
+        boot_protocol: HidBootProtocol::Keyboard,
+    }
+}
+
+/// Configuration of a boot mouse, with a 3-byte input report.
+///
+/// Use it with `HidWriter<_, 3>`.
+pub fn mouse_config<'d>(request_handler: Option<&'d dyn RequestHandler>, poll_ms: u8) -> Config<'d> {
+    Config {
+        report_descriptor: MOUSE_REPORT_DESCRIPTOR.as_bytes(),
+        request_handler,
+        poll_ms,
+        max_packet_size: 8,
+        boot_protocol: HidBootProtocol::Mouse,
+    }
+}
+
+/// Input report of a boot keyboard.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct KeyboardReport {
+    /// Modifier keys, bit 0 is left control up to bit 7 for right GUI.
+    pub modifiers: u8,
+    /// Key codes of the pressed keys, 0 for no key.
+    pub keycodes: [u8; 6],
+}
+
+impl KeyboardReport {
+    /// Serialize the report.
+    pub fn to_bytes(&self) -> [u8; 8] {
+        let mut buf = [0; 8];
+        buf[0] = self.modifiers;
+        buf[2..].copy_from_slice(&self.keycodes);
+        buf
+    }
+}
+
+/// Output report of a boot keyboard.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct KeyboardLeds(pub u8);
+
+impl KeyboardLeds {
+    /// Num lock LED.
+    pub fn num_lock(&self) -> bool {
+        self.0 & 0x01 != 0
+    }
+
+    /// Caps lock LED.
+    pub fn caps_lock(&self) -> bool {
+        self.0 & 0x02 != 0
+    }
+
+    /// Scroll lock LED.
+    pub fn scroll_lock(&self) -> bool {
+        self.0 & 0x04 != 0
+    }
+}
+
+/// Input report of a boot mouse.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MouseReport {
+    /// Buttons, bit 0 is the left button, bit 1 the right one and bit 2 the middle one.
+    pub buttons: u8,
+    /// Horizontal movement.
+    pub x: i8,
+    /// Vertical movement.
+    pub y: i8,
+}
+
+impl MouseReport {
+    /// Serialize the report.
+    pub fn to_bytes(&self) -> [u8; 3] {
+        [self.buttons, self.x as u8, self.y as u8]
+    }
+}
@@ -0,0 +1,322 @@
+//! HID report descriptor builder.
+//!
+//! The builder writes the short items of the HID 1.11 specification (section 6.2.2) into a fixed
+//! size buffer. All methods are `const fn`, so descriptors can be built at compile time:
+//!
+//! ```
+//! use embassy_usb::class::hid::descriptor::*;
+//!
+//! // 8 buttons and a vendor defined 16-bit value.
+//! static DESCRIPTOR: ReportDescriptor<64> = ReportDescriptorBuilder::new()
+//!     .usage_page(UsagePage::GenericDesktop)
+//!     .usage(generic_desktop::GAMEPAD)
+//!     .collection(Collection::Application)
+//!     .usage_page(UsagePage::Button)
+//!     .usage_minimum(1)
+//!     .usage_maximum(8)
+//!     .logical_minimum(0)
+//!     .logical_maximum(1)
+//!     .report_size(1)
+//!     .report_count(8)
+//!     .input(ItemFlags::DATA_VARIABLE_ABSOLUTE)
+//!     .usage_page(UsagePage::Vendor(0xFF00))
+//!     .usage(0x01)
+//!     .logical_minimum(i16::MIN as i32)
+//!     .logical_maximum(i16::MAX as i32)
+//!     .report_size(16)
+//!     .report_count(1)
+//!     .input(ItemFlags::DATA_VARIABLE_ABSOLUTE)
+//!     .end_collection()
+//!     .build();
+//!
+//! let report_descriptor: &[u8] = DESCRIPTOR.as_bytes();
+//! ```
+
+/// Usage page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum UsagePage {
+    /// Generic desktop controls, see [`generic_desktop`].
+    GenericDesktop,
+    /// Simulation controls.
+    Simulation,
+    /// Keyboard and keypad key codes.
+    Keyboard,
+    /// LEDs.
+    Leds,
+    /// Buttons, the usage is the button number.
+    Button,
+    /// Consumer controls.
+    Consumer,
+    /// Vendor defined page, in `0xFF00..=0xFFFF`.
+    Vendor(u16),
+}
+
+impl UsagePage {
+    const fn value(self) -> u16 {
+        match self {
+            UsagePage::GenericDesktop => 0x01,
+            UsagePage::Simulation => 0x02,
+            UsagePage::Keyboard => 0x07,
+            UsagePage::Leds => 0x08,
+            UsagePage::Button => 0x09,
+            UsagePage::Consumer => 0x0C,
+            UsagePage::Vendor(page) => page,
+        }
+    }
+}
+
+/// Usages of the [`UsagePage::GenericDesktop`] page.
+pub mod generic_desktop {
+    /// Pointer.
+    pub const POINTER: u16 = 0x01;
+    /// Mouse.
+    pub const MOUSE: u16 = 0x02;
+    /// Joystick.
+    pub const JOYSTICK: u16 = 0x04;
+    /// Gamepad.
+    pub const GAMEPAD: u16 = 0x05;
+    /// Keyboard.
+    pub const KEYBOARD: u16 = 0x06;
+    /// Keypad.
+    pub const KEYPAD: u16 = 0x07;
+    /// X axis.
+    pub const X: u16 = 0x30;
+    /// Y axis.
+    pub const Y: u16 = 0x31;
+    /// Z axis.
+    pub const Z: u16 = 0x32;
+    /// Rotation around the X axis.
+    pub const RX: u16 = 0x33;
+    /// Rotation around the Y axis.
+    pub const RY: u16 = 0x34;
+    /// Rotation around the Z axis.
+    pub const RZ: u16 = 0x35;
+    /// Wheel.
+    pub const WHEEL: u16 = 0x38;
+    /// Hat switch.
+    pub const HAT_SWITCH: u16 = 0x39;
+}
+
+/// Collection type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum Collection {
+    /// Physical collection, items measured at one geometric point.
+    Physical = 0x00,
+    /// Application collection, groups the items of a device.
+    Application = 0x01,
+    /// Logical collection, items forming a composite data structure.
+    Logical = 0x02,
+    /// Report collection, wraps the items of a report.
+    Report = 0x03,
+}
+
+/// Flags of the input, output and feature items.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ItemFlags(u8);
+
+impl ItemFlags {
+    /// Data, array, absolute. Used for key code arrays.
+    pub const DATA_ARRAY_ABSOLUTE: Self = Self(0x00);
+    /// Data, variable, absolute. Used for buttons and absolute axes.
+    pub const DATA_VARIABLE_ABSOLUTE: Self = Self(0x02);
+    /// Data, variable, relative. Used for relative axes like mouse movement.
+    pub const DATA_VARIABLE_RELATIVE: Self = Self(0x06);
+    /// Constant. Used for padding.
+    pub const CONSTANT: Self = Self(0x01);
+
+    /// Flags from the raw bits of the item.
+    pub const fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+
+    /// Raw bits of the item.
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+}
+
+// Item prefixes with the size bits cleared, HID 1.11 section 6.2.2.
+const MAIN_INPUT: u8 = 0x80;
+const MAIN_OUTPUT: u8 = 0x90;
+const MAIN_FEATURE: u8 = 0xB0;
+const MAIN_COLLECTION: u8 = 0xA0;
+const MAIN_END_COLLECTION: u8 = 0xC0;
+const GLOBAL_USAGE_PAGE: u8 = 0x04;
+const GLOBAL_LOGICAL_MINIMUM: u8 = 0x14;
+const GLOBAL_LOGICAL_MAXIMUM: u8 = 0x24;
+const GLOBAL_REPORT_SIZE: u8 = 0x74;
+const GLOBAL_REPORT_ID: u8 = 0x84;
+const GLOBAL_REPORT_COUNT: u8 = 0x94;
+const LOCAL_USAGE: u8 = 0x08;
+const LOCAL_USAGE_MINIMUM: u8 = 0x18;
+const LOCAL_USAGE_MAXIMUM: u8 = 0x28;
+
+/// Builder for a HID report descriptor of at most `N` bytes.
+///
+/// Every value is encoded with the smallest item size that can hold it. Building panics if the
+/// descriptor does not fit in `N` bytes or if the collections are not balanced.
+pub struct ReportDescriptorBuilder<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+    depth: usize,
+}
+
+impl<const N: usize> ReportDescriptorBuilder<N> {
+    /// Create an empty builder.
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            len: 0,
+            depth: 0,
+        }
+    }
+
+    const fn item(mut self, prefix: u8, data: u32, size: usize) -> Self {
+        assert!(self.len + 1 + size <= N, "HID report descriptor buffer too small");
+        let size_bits = match size {
+            0 => 0,
+            1 => 1,
+            2 => 2,
+            _ => 3,
+        };
+        self.buf[self.len] = prefix | size_bits;
+        let mut i = 0;
+        while i < size {
+            self.buf[self.len + 1 + i] = (data >> (8 * i)) as u8;
+            i += 1;
+        }
+        self.len += 1 + size;
+        self
+    }
+
+    const fn unsigned(self, prefix: u8, value: u32) -> Self {
+        let size = if value <= 0xFF {
+            1
+        } else if value <= 0xFFFF {
+            2
+        } else {
+            4
+        };
+        self.item(prefix, value, size)
+    }
+
+    const fn signed(self, prefix: u8, value: i32) -> Self {
+        let size = if value >= i8::MIN as i32 && value <= i8::MAX as i32 {
+            1
+        } else if value >= i16::MIN as i32 && value <= i16::MAX as i32 {
+            2
+        } else {
+            4
+        };
+        self.item(prefix, value as u32, size)
+    }
+
+    /// Usage page of the following usages.
+    pub const fn usage_page(self, page: UsagePage) -> Self {
+        self.unsigned(GLOBAL_USAGE_PAGE, page.value() as u32)
+    }
+
+    /// Usage of the next control or collection.
+    pub const fn usage(self, usage: u16) -> Self {
+        self.unsigned(LOCAL_USAGE, usage as u32)
+    }
+
+    /// First usage of a range assigned to the next controls.
+    pub const fn usage_minimum(self, usage: u16) -> Self {
+        self.unsigned(LOCAL_USAGE_MINIMUM, usage as u32)
+    }
+
+    /// Last usage of a range assigned to the next controls.
+    pub const fn usage_maximum(self, usage: u16) -> Self {
+        self.unsigned(LOCAL_USAGE_MAXIMUM, usage as u32)
+    }
+
+    /// Minimum value reported by the following controls.
+    pub const fn logical_minimum(self, value: i32) -> Self {
+        self.signed(GLOBAL_LOGICAL_MINIMUM, value)
+    }
+
+    /// Maximum value reported by the following controls.
+    pub const fn logical_maximum(self, value: i32) -> Self {
+        self.signed(GLOBAL_LOGICAL_MAXIMUM, value)
+    }
+
+    /// Size of each following control, in bits.
+    pub const fn report_size(self, bits: u8) -> Self {
+        self.unsigned(GLOBAL_REPORT_SIZE, bits as u32)
+    }
+
+    /// Number of following controls.
+    pub const fn report_count(self, count: u8) -> Self {
+        self.unsigned(GLOBAL_REPORT_COUNT, count as u32)
+    }
+
+    /// Report ID of the following controls.
+    ///
+    /// When report IDs are used, every report starts with its ID byte.
+    pub const fn report_id(self, id: u8) -> Self {
+        assert!(id != 0, "HID report ID 0 is reserved");
+        self.unsigned(GLOBAL_REPORT_ID, id as u32)
+    }
+
+    /// Input controls, sent to the host.
+    pub const fn input(self, flags: ItemFlags) -> Self {
+        self.item(MAIN_INPUT, flags.bits() as u32, 1)
+    }
+
+    /// Output controls, sent by the host.
+    pub const fn output(self, flags: ItemFlags) -> Self {
+        self.item(MAIN_OUTPUT, flags.bits() as u32, 1)
+    }
+
+    /// Feature controls, accessed with control requests.
+    pub const fn feature(self, flags: ItemFlags) -> Self {
+        self.item(MAIN_FEATURE, flags.bits() as u32, 1)
+    }
+
+    /// Start a collection, ended by [`end_collection`](Self::end_collection).
+    pub const fn collection(self, collection: Collection) -> Self {
+        let mut this = self.item(MAIN_COLLECTION, collection as u32, 1);
+        this.depth += 1;
+        this
+    }
+
+    /// End the innermost collection.
+    pub const fn end_collection(mut self) -> Self {
+        assert!(self.depth > 0, "HID end collection without collection");
+        self.depth -= 1;
+        self.item(MAIN_END_COLLECTION, 0, 0)
+    }
+
+    /// Finish the descriptor.
+    pub const fn build(self) -> ReportDescriptor<N> {
+        assert!(self.depth == 0, "HID collection not ended");
+        ReportDescriptor {
+            buf: self.buf,
+            len: self.len,
+        }
+    }
+}
+
+impl<const N: usize> Default for ReportDescriptorBuilder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// HID report descriptor built by [`ReportDescriptorBuilder`].
+pub struct ReportDescriptor<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> ReportDescriptor<N> {
+    /// Descriptor bytes, to use as [`Config::report_descriptor`](super::Config::report_descriptor).
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
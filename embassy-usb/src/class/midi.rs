@@ -1,4 +1,8 @@
 //! MIDI class implementation.
+//!
+//! Besides raw USB packets, the class can send and receive USB-MIDI event packets (USB MIDI 1.0
+//! section 4) and typed MIDI [`Message`]s. Each embedded jack is a virtual cable, numbered from 0,
+//! which lets one device expose several MIDI ports.
 
 use crate::driver::{Driver, Endpoint, EndpointError, EndpointIn, EndpointOut};
 use crate::Builder;
@@ -21,6 +25,26 @@ const PROTOCOL_NONE: u8 = 0x00;
 const MIDI_IN_SIZE: u8 = 0x06;
 const MIDI_OUT_SIZE: u8 = 0x09;
 
+// Code index numbers, USB MIDI 1.0 table 4-1.
+const CIN_SYSTEM_COMMON_2: u8 = 0x2;
+const CIN_SYSTEM_COMMON_3: u8 = 0x3;
+const CIN_SYSEX_START: u8 = 0x4;
+const CIN_SYSEX_END_1: u8 = 0x5;
+const CIN_SYSEX_END_2: u8 = 0x6;
+const CIN_SYSEX_END_3: u8 = 0x7;
+const CIN_PROGRAM_CHANGE: u8 = 0xC;
+const CIN_CHANNEL_PRESSURE: u8 = 0xD;
+const CIN_SINGLE_BYTE: u8 = 0xF;
+
+const SYSEX_START: u8 = 0xF0;
+const SYSEX_END: u8 = 0xF7;
+
+/// Size of a USB-MIDI event packet, in bytes.
+pub const EVENT_PACKET_SIZE: usize = 4;
+
+/// Size of the buffer used to read event packets. Larger packets can only be read with `read_packet`.
+const EVENT_BUF_SIZE: usize = 64;
+
 /// Packet level implementation of a USB MIDI device.
 ///
 /// This class can be used directly and it has the least overhead due to directly reading and
@@ -36,6 +60,7 @@ const MIDI_OUT_SIZE: u8 = 0x09;
 pub struct MidiClass<'d, D: Driver<'d>> {
     read_ep: D::EndpointOut,
     write_ep: D::EndpointIn,
+    events: EventBuffer,
 }
 
 impl<'d, D: Driver<'d>> MidiClass<'d, D> {
@@ -139,7 +164,11 @@ impl<'d, D: Driver<'d>> MidiClass<'d, D> {
         let write_ep = alt.endpoint_bulk_in(max_packet_size);
         alt.descriptor(CS_ENDPOINT, &endpoint_data[0..2 + n_in_jacks as usize]);
 
-        MidiClass { read_ep, write_ep }
+        MidiClass {
+            read_ep,
+            write_ep,
+            events: EventBuffer::new(),
+        }
     }
 
     /// Gets the maximum packet size in bytes.
@@ -158,6 +187,36 @@ impl<'d, D: Driver<'d>> MidiClass<'d, D> {
         self.read_ep.read(data).await
     }
 
+    /// Writes event packets, batched in as few USB packets as possible.
+    pub async fn write_events(&mut self, events: &[EventPacket]) -> Result<(), EndpointError> {
+        write_events(&mut self.write_ep, events.iter().copied()).await
+    }
+
+    /// Writes a MIDI message on virtual cable `cable`.
+    pub async fn write_message(&mut self, cable: u8, message: &Message) -> Result<(), EndpointError> {
+        write_events(
+            &mut self.write_ep,
+            [EventPacket::from_message(cable, message)].into_iter(),
+        )
+        .await
+    }
+
+    /// Writes a system exclusive message on virtual cable `cable`.
+    ///
+    /// `data` is the whole message, from the `0xF0` start byte to the `0xF7` end byte.
+    pub async fn write_sysex(&mut self, cable: u8, data: &[u8]) -> Result<(), EndpointError> {
+        write_events(&mut self.write_ep, sysex_events(cable, data)).await
+    }
+
+    /// Reads the next event packet.
+    ///
+    /// Event packets are read from USB packets of up to 64 bytes, and the remaining ones are
+    /// returned by the next calls. Don't mix with [`read_packet`](Self::read_packet), which would
+    /// skip the buffered events.
+    pub async fn read_event(&mut self) -> Result<EventPacket, EndpointError> {
+        self.events.read(&mut self.read_ep).await
+    }
+
     /// Waits for the USB host to enable this interface
     pub async fn wait_connection(&mut self) {
         self.read_ep.wait_enabled().await;
@@ -171,7 +230,10 @@ impl<'d, D: Driver<'d>> MidiClass<'d, D> {
             Sender {
                 write_ep: self.write_ep,
             },
-            Receiver { read_ep: self.read_ep },
+            Receiver {
+                read_ep: self.read_ep,
+                events: self.events,
+            },
         )
     }
 }
@@ -195,6 +257,27 @@ impl<'d, D: Driver<'d>> Sender<'d, D> {
         self.write_ep.write(data).await
     }
 
+    /// Writes event packets, batched in as few USB packets as possible.
+    pub async fn write_events(&mut self, events: &[EventPacket]) -> Result<(), EndpointError> {
+        write_events(&mut self.write_ep, events.iter().copied()).await
+    }
+
+    /// Writes a MIDI message on virtual cable `cable`.
+    pub async fn write_message(&mut self, cable: u8, message: &Message) -> Result<(), EndpointError> {
+        write_events(
+            &mut self.write_ep,
+            [EventPacket::from_message(cable, message)].into_iter(),
+        )
+        .await
+    }
+
+    /// Writes a system exclusive message on virtual cable `cable`.
+    ///
+    /// See [`MidiClass::write_sysex`].
+    pub async fn write_sysex(&mut self, cable: u8, data: &[u8]) -> Result<(), EndpointError> {
+        write_events(&mut self.write_ep, sysex_events(cable, data)).await
+    }
+
     /// Waits for the USB host to enable this interface
     pub async fn wait_connection(&mut self) {
         self.write_ep.wait_enabled().await;
@@ -206,6 +289,7 @@ impl<'d, D: Driver<'d>> Sender<'d, D> {
 /// You can obtain a `Receiver` with [`MidiClass::split`]
 pub struct Receiver<'d, D: Driver<'d>> {
     read_ep: D::EndpointOut,
+    events: EventBuffer,
 }
 
 impl<'d, D: Driver<'d>> Receiver<'d, D> {
@@ -220,8 +304,377 @@ impl<'d, D: Driver<'d>> Receiver<'d, D> {
         self.read_ep.read(data).await
     }
 
+    /// Reads the next event packet.
+    ///
+    /// See [`MidiClass::read_event`].
+    pub async fn read_event(&mut self) -> Result<EventPacket, EndpointError> {
+        self.events.read(&mut self.read_ep).await
+    }
+
     /// Waits for the USB host to enable this interface
     pub async fn wait_connection(&mut self) {
         self.read_ep.wait_enabled().await;
     }
 }
+
+/// Event packets received but not read yet.
+struct EventBuffer {
+    buf: [u8; EVENT_BUF_SIZE],
+    pos: usize,
+    len: usize,
+}
+
+impl EventBuffer {
+    const fn new() -> Self {
+        Self {
+            buf: [0; EVENT_BUF_SIZE],
+            pos: 0,
+            len: 0,
+        }
+    }
+
+    async fn read<E: EndpointOut>(&mut self, ep: &mut E) -> Result<EventPacket, EndpointError> {
+        loop {
+            while self.pos + EVENT_PACKET_SIZE <= self.len {
+                let mut bytes = [0; EVENT_PACKET_SIZE];
+                bytes.copy_from_slice(&self.buf[self.pos..self.pos + EVENT_PACKET_SIZE]);
+                self.pos += EVENT_PACKET_SIZE;
+
+                // Some hosts pad packets with empty events.
+                if bytes != [0; EVENT_PACKET_SIZE] {
+                    return Ok(EventPacket(bytes));
+                }
+            }
+
+            self.pos = 0;
+            self.len = 0;
+            self.len = ep.read(&mut self.buf).await?;
+        }
+    }
+}
+
+/// Writes events, filling each USB packet.
+async fn write_events<E: EndpointIn>(
+    ep: &mut E,
+    events: impl Iterator<Item = EventPacket>,
+) -> Result<(), EndpointError> {
+    let max_packet_size = usize::from(ep.info().max_packet_size).min(EVENT_BUF_SIZE);
+    let mut buf = [0; EVENT_BUF_SIZE];
+    let mut len = 0;
+    let mut last_full = false;
+
+    for event in events {
+        buf[len..len + EVENT_PACKET_SIZE].copy_from_slice(&event.0);
+        len += EVENT_PACKET_SIZE;
+        if len + EVENT_PACKET_SIZE > max_packet_size {
+            ep.write(&buf[..len]).await?;
+            last_full = len == max_packet_size;
+            len = 0;
+        }
+    }
+
+    if len > 0 {
+        ep.write(&buf[..len]).await?;
+    } else if last_full {
+        // End the transfer, the host doesn't process full packets until a short one.
+        ep.write(&[]).await?;
+    }
+    Ok(())
+}
+
+/// Splits a system exclusive message in event packets.
+fn sysex_events(cable: u8, data: &[u8]) -> impl Iterator<Item = EventPacket> + '_ {
+    assert!(data.len() >= 2 && data[0] == SYSEX_START && data[data.len() - 1] == SYSEX_END);
+
+    let count = (data.len() + 2) / 3;
+    data.chunks(3).enumerate().map(move |(i, chunk)| {
+        let cin = match (i + 1 == count, chunk.len()) {
+            (false, _) => CIN_SYSEX_START,
+            (true, 1) => CIN_SYSEX_END_1,
+            (true, 2) => CIN_SYSEX_END_2,
+            (true, _) => CIN_SYSEX_END_3,
+        };
+        let mut bytes = [header(cable, cin), 0, 0, 0];
+        bytes[1..1 + chunk.len()].copy_from_slice(chunk);
+        EventPacket(bytes)
+    })
+}
+
+fn header(cable: u8, cin: u8) -> u8 {
+    assert!(cable < 16);
+    cable << 4 | cin
+}
+
+/// USB-MIDI event packet: a MIDI message, or part of a system exclusive message, on a virtual cable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct EventPacket([u8; EVENT_PACKET_SIZE]);
+
+impl EventPacket {
+    /// Event packet from its raw bytes.
+    pub const fn from_bytes(bytes: [u8; EVENT_PACKET_SIZE]) -> Self {
+        Self(bytes)
+    }
+
+    /// Raw bytes of the event packet.
+    pub const fn to_bytes(&self) -> [u8; EVENT_PACKET_SIZE] {
+        self.0
+    }
+
+    /// Event packet carrying `message` on virtual cable `cable`.
+    pub fn from_message(cable: u8, message: &Message) -> Self {
+        let mut midi = [0; 3];
+        let len = message.encode(&mut midi);
+        let cin = match midi[0] {
+            0x80..=0xEF => midi[0] >> 4,
+            0xF8..=0xFF => CIN_SINGLE_BYTE,
+            _ => match len {
+                1 => CIN_SYSEX_END_1,
+                2 => CIN_SYSTEM_COMMON_2,
+                _ => CIN_SYSTEM_COMMON_3,
+            },
+        };
+        Self([header(cable, cin), midi[0], midi[1], midi[2]])
+    }
+
+    /// Virtual cable of the event.
+    pub fn cable(&self) -> u8 {
+        self.0[0] >> 4
+    }
+
+    /// Code index number, the kind of event.
+    pub fn code_index(&self) -> u8 {
+        self.0[0] & 0x0F
+    }
+
+    /// MIDI bytes carried by the event.
+    pub fn midi(&self) -> &[u8] {
+        let len = match self.code_index() {
+            CIN_SYSEX_END_1 | CIN_SINGLE_BYTE => 1,
+            CIN_SYSTEM_COMMON_2 | CIN_SYSEX_END_2 | CIN_PROGRAM_CHANGE | CIN_CHANNEL_PRESSURE => 2,
+            CIN_SYSTEM_COMMON_3 | CIN_SYSEX_START | CIN_SYSEX_END_3 | 0x8..=0xE => 3,
+            // Reserved code index numbers.
+            _ => 0,
+        };
+        &self.0[1..1 + len]
+    }
+
+    /// Whether the event is part of a system exclusive message.
+    ///
+    /// The message is made of the [`midi`](Self::midi) bytes of consecutive events on the same
+    /// cable, from the one starting with `0xF0` to the one ending with `0xF7`.
+    pub fn is_sysex(&self) -> bool {
+        match self.code_index() {
+            CIN_SYSEX_START | CIN_SYSEX_END_2 | CIN_SYSEX_END_3 => true,
+            CIN_SYSEX_END_1 => self.0[1] == SYSEX_END,
+            _ => false,
+        }
+    }
+
+    /// MIDI message carried by the event, `None` for system exclusive and invalid events.
+    pub fn message(&self) -> Option<Message> {
+        if self.is_sysex() {
+            return None;
+        }
+        Message::decode(self.midi())
+    }
+}
+
+/// MIDI message, other than system exclusive.
+///
+/// Channels are numbered from 0 to 15, data values are 7 bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Message {
+    /// Note off.
+    NoteOff {
+        /// Channel.
+        channel: u8,
+        /// Note number.
+        note: u8,
+        /// Release velocity.
+        velocity: u8,
+    },
+    /// Note on. A velocity of 0 is usually handled as note off.
+    NoteOn {
+        /// Channel.
+        channel: u8,
+        /// Note number.
+        note: u8,
+        /// Velocity.
+        velocity: u8,
+    },
+    /// Polyphonic key pressure.
+    PolyPressure {
+        /// Channel.
+        channel: u8,
+        /// Note number.
+        note: u8,
+        /// Pressure.
+        pressure: u8,
+    },
+    /// Control change.
+    ControlChange {
+        /// Channel.
+        channel: u8,
+        /// Controller number.
+        control: u8,
+        /// Value.
+        value: u8,
+    },
+    /// Program change.
+    ProgramChange {
+        /// Channel.
+        channel: u8,
+        /// Program number.
+        program: u8,
+    },
+    /// Channel pressure.
+    ChannelPressure {
+        /// Channel.
+        channel: u8,
+        /// Pressure.
+        pressure: u8,
+    },
+    /// Pitch bend.
+    PitchBend {
+        /// Channel.
+        channel: u8,
+        /// 14-bit value, 0x2000 is the center.
+        value: u16,
+    },
+    /// MIDI time code quarter frame.
+    TimeCodeQuarterFrame(u8),
+    /// Song position pointer, in MIDI beats.
+    SongPosition(u16),
+    /// Song select.
+    SongSelect(u8),
+    /// Tune request.
+    TuneRequest,
+    /// Timing clock, 24 per quarter note.
+    TimingClock,
+    /// Start.
+    Start,
+    /// Continue.
+    Continue,
+    /// Stop.
+    Stop,
+    /// Active sensing.
+    ActiveSensing,
+    /// System reset.
+    Reset,
+}
+
+impl Message {
+    /// Encodes the message in `buf`, returning its length.
+    pub fn encode(&self, buf: &mut [u8; 3]) -> usize {
+        let (bytes, len) = match self.masked() {
+            Message::NoteOff {
+                channel,
+                note,
+                velocity,
+            } => ([0x80 | channel, note, velocity], 3),
+            Message::NoteOn {
+                channel,
+                note,
+                velocity,
+            } => ([0x90 | channel, note, velocity], 3),
+            Message::PolyPressure {
+                channel,
+                note,
+                pressure,
+            } => ([0xA0 | channel, note, pressure], 3),
+            Message::ControlChange {
+                channel,
+                control,
+                value,
+            } => ([0xB0 | channel, control, value], 3),
+            Message::ProgramChange { channel, program } => ([0xC0 | channel, program, 0], 2),
+            Message::ChannelPressure { channel, pressure } => ([0xD0 | channel, pressure, 0], 2),
+            Message::PitchBend { channel, value } => ([0xE0 | channel, value as u8 & 0x7F, (value >> 7) as u8], 3),
+            Message::TimeCodeQuarterFrame(value) => ([0xF1, value, 0], 2),
+            Message::SongPosition(value) => ([0xF2, value as u8 & 0x7F, (value >> 7) as u8], 3),
+            Message::SongSelect(song) => ([0xF3, song, 0], 2),
+            Message::TuneRequest => ([0xF6, 0, 0], 1),
+            Message::TimingClock => ([0xF8, 0, 0], 1),
+            Message::Start => ([0xFA, 0, 0], 1),
+            Message::Continue => ([0xFB, 0, 0], 1),
+            Message::Stop => ([0xFC, 0, 0], 1),
+            Message::ActiveSensing => ([0xFE, 0, 0], 1),
+            Message::Reset => ([0xFF, 0, 0], 1),
+        };
+        buf[0] = bytes[0];
+        buf[1] = bytes[1] & 0x7F;
+        buf[2] = bytes[2] & 0x7F;
+        len
+    }
+
+    /// The message with the channel kept in range.
+    fn masked(&self) -> Self {
+        let mut message = *self;
+        match &mut message {
+            Message::NoteOff { channel, .. }
+            | Message::NoteOn { channel, .. }
+            | Message::PolyPressure { channel, .. }
+            | Message::ControlChange { channel, .. }
+            | Message::ProgramChange { channel, .. }
+            | Message::ChannelPressure { channel, .. }
+            | Message::PitchBend { channel, .. } => *channel &= 0x0F,
+            _ => {}
+        }
+        message
+    }
+
+    /// Decodes a message from its MIDI bytes, `None` if they are not a complete message.
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let status = *bytes.first()?;
+        let channel = status & 0x0F;
+        let data = |i: usize| bytes.get(i).copied().filter(|b| *b < 0x80);
+        let message = match status {
+            0x80..=0x8F => Message::NoteOff {
+                channel,
+                note: data(1)?,
+                velocity: data(2)?,
+            },
+            0x90..=0x9F => Message::NoteOn {
+                channel,
+                note: data(1)?,
+                velocity: data(2)?,
+            },
+            0xA0..=0xAF => Message::PolyPressure {
+                channel,
+                note: data(1)?,
+                pressure: data(2)?,
+            },
+            0xB0..=0xBF => Message::ControlChange {
+                channel,
+                control: data(1)?,
+                value: data(2)?,
+            },
+            0xC0..=0xCF => Message::ProgramChange {
+                channel,
+                program: data(1)?,
+            },
+            0xD0..=0xDF => Message::ChannelPressure {
+                channel,
+                pressure: data(1)?,
+            },
+            0xE0..=0xEF => Message::PitchBend {
+                channel,
+                value: u16::from(data(1)?) | u16::from(data(2)?) << 7,
+            },
+            0xF1 => Message::TimeCodeQuarterFrame(data(1)?),
+            0xF2 => Message::SongPosition(u16::from(data(1)?) | u16::from(data(2)?) << 7),
+            0xF3 => Message::SongSelect(data(1)?),
+            0xF6 => Message::TuneRequest,
+            0xF8 => Message::TimingClock,
+            0xFA => Message::Start,
+            0xFB => Message::Continue,
+            0xFC => Message::Stop,
+            0xFE => Message::ActiveSensing,
+            0xFF => Message::Reset,
+            _ => return None,
+        };
+        Some(message)
+    }
+}
@@ -0,0 +1,476 @@
+//! Mass Storage Class implementation, exposing a block device as a USB drive.
+//!
+//! This implements the Bulk-Only Transport with the SCSI transparent command set, which all the
+//! major operating systems support without drivers. The storage is any
+//! [`BlockDevice`](embassy_embedded_hal::block::BlockDevice), such as an SD card.
+
+use core::mem::MaybeUninit;
+
+use embassy_embedded_hal::block::{Block, BlockDevice, BLOCK_SIZE};
+
+use crate::control::{InResponse, OutResponse, Recipient, Request, RequestType};
+use crate::driver::{Driver, Endpoint, EndpointError, EndpointIn, EndpointOut};
+use crate::types::InterfaceNumber;
+use crate::{Builder, Handler};
+
+/// This should be used as `device_class` when building the `UsbDevice`.
+pub const USB_CLASS_MSC: u8 = 0x08;
+
+const MSC_SUBCLASS_SCSI: u8 = 0x06;
+const MSC_PROTOCOL_BOT: u8 = 0x50;
+
+const REQ_BULK_ONLY_RESET: u8 = 0xFF;
+const REQ_GET_MAX_LUN: u8 = 0xFE;
+
+const CBW_SIGNATURE: u32 = 0x4342_5355;
+const CBW_LEN: usize = 31;
+const CSW_SIGNATURE: u32 = 0x5342_5355;
+const CSW_LEN: usize = 13;
+
+const SCSI_TEST_UNIT_READY: u8 = 0x00;
+const SCSI_REQUEST_SENSE: u8 = 0x03;
+const SCSI_INQUIRY: u8 = 0x12;
+const SCSI_MODE_SENSE_6: u8 = 0x1A;
+const SCSI_START_STOP_UNIT: u8 = 0x1B;
+const SCSI_PREVENT_ALLOW_MEDIUM_REMOVAL: u8 = 0x1E;
+const SCSI_READ_FORMAT_CAPACITIES: u8 = 0x23;
+const SCSI_READ_CAPACITY_10: u8 = 0x25;
+const SCSI_READ_10: u8 = 0x28;
+const SCSI_WRITE_10: u8 = 0x2A;
+const SCSI_VERIFY_10: u8 = 0x2F;
+const SCSI_SYNCHRONIZE_CACHE_10: u8 = 0x35;
+const SCSI_MODE_SENSE_10: u8 = 0x5A;
+
+/// Sense data of the last failed command: sense key, additional sense code and qualifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Sense(u8, u8, u8);
+
+impl Sense {
+    const NONE: Sense = Sense(0x00, 0x00, 0x00);
+    const READ_ERROR: Sense = Sense(0x03, 0x11, 0x00);
+    const WRITE_ERROR: Sense = Sense(0x03, 0x0C, 0x00);
+    const INVALID_COMMAND: Sense = Sense(0x05, 0x20, 0x00);
+    const LBA_OUT_OF_RANGE: Sense = Sense(0x05, 0x21, 0x00);
+    const INVALID_FIELD: Sense = Sense(0x05, 0x24, 0x00);
+    const WRITE_PROTECTED: Sense = Sense(0x07, 0x27, 0x00);
+}
+
+/// Status of a command, reported in the Command Status Wrapper.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum Status {
+    Passed = 0x00,
+    Failed = 0x01,
+    PhaseError = 0x02,
+}
+
+/// Command Block Wrapper, sent by the host before each command.
+struct Cbw {
+    tag: u32,
+    data_len: u32,
+    data_in: bool,
+    cb: [u8; 16],
+}
+
+impl Cbw {
+    fn parse(buf: &[u8]) -> Option<Self> {
+        if buf.len() != CBW_LEN || u32::from_le_bytes(buf[0..4].try_into().unwrap()) != CBW_SIGNATURE {
+            return None;
+        }
+        Some(Self {
+            tag: u32::from_le_bytes(buf[4..8].try_into().unwrap()),
+            data_len: u32::from_le_bytes(buf[8..12].try_into().unwrap()),
+            data_in: buf[12] & 0x80 != 0,
+            cb: buf[15..31].try_into().unwrap(),
+        })
+    }
+}
+
+/// Configuration of the drive, reported to the host.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy)]
+pub struct Config<'a> {
+    /// Vendor identification, up to 8 ASCII characters.
+    pub vendor: &'a str,
+    /// Product identification, up to 16 ASCII characters.
+    pub product: &'a str,
+    /// Product revision, up to 4 ASCII characters.
+    pub revision: &'a str,
+    /// Report the medium as removable, like an SD card reader.
+    pub removable: bool,
+    /// Reject the writes of the host.
+    pub read_only: bool,
+}
+
+impl<'a> Default for Config<'a> {
+    fn default() -> Self {
+        Self {
+            vendor: "Embassy",
+            product: "Mass Storage",
+            revision: "0.1",
+            removable: true,
+            read_only: false,
+        }
+    }
+}
+
+/// Internal state for the Mass Storage Class.
+pub struct State {
+    control: MaybeUninit<Control>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl State {
+    /// Create a new `State`.
+    pub fn new() -> Self {
+        Self {
+            control: MaybeUninit::uninit(),
+        }
+    }
+}
+
+struct Control {
+    if_num: InterfaceNumber,
+}
+
+impl Handler for Control {
+    fn control_out(&mut self, req: Request, _data: &[u8]) -> Option<OutResponse> {
+        if (req.request_type, req.recipient, req.index)
+            != (RequestType::Class, Recipient::Interface, self.if_num.0 as u16)
+        {
+            return None;
+        }
+
+        match req.request {
+            // The class is always ready for the next command, there is nothing to reset.
+            REQ_BULK_ONLY_RESET => Some(OutResponse::Accepted),
+            _ => Some(OutResponse::Rejected),
+        }
+    }
+
+    fn control_in<'a>(&'a mut self, req: Request, buf: &'a mut [u8]) -> Option<InResponse<'a>> {
+        if (req.request_type, req.recipient, req.index)
+            != (RequestType::Class, Recipient::Interface, self.if_num.0 as u16)
+        {
+            return None;
+        }
+
+        match req.request {
+            REQ_GET_MAX_LUN => {
+                // A single logical unit.
+                buf[0] = 0;
+                Some(InResponse::Accepted(&buf[..1]))
+            }
+            _ => Some(InResponse::Rejected),
+        }
+    }
+}
+
+/// Mass Storage Class, exposing a single block device as a USB drive.
+///
+/// The driver can't stall the bulk endpoints, so the data stages the host expects but the
+/// command doesn't fill are ended with a short packet, and reported in the residue of the status.
+/// This is accepted by the hosts, but the Bulk-Only Mass Storage Reset recovery isn't supported.
+pub struct MscClass<'d, D: Driver<'d>> {
+    read_ep: D::EndpointOut,
+    write_ep: D::EndpointIn,
+    config: Config<'d>,
+    sense: Sense,
+}
+
+impl<'d, D: Driver<'d>> MscClass<'d, D> {
+    /// Creates a new MscClass with the provided UsbBus and `max_packet_size` in bytes. For
+    /// full-speed devices, `max_packet_size` has to be one of 8, 16, 32 or 64. For high-speed
+    /// devices, it has to be 512.
+    pub fn new(builder: &mut Builder<'d, D>, state: &'d mut State, config: Config<'d>, max_packet_size: u16) -> Self {
+        let mut func = builder.function(USB_CLASS_MSC, MSC_SUBCLASS_SCSI, MSC_PROTOCOL_BOT);
+        let mut iface = func.interface();
+        let if_num = iface.interface_number();
+        let mut alt = iface.alt_setting(USB_CLASS_MSC, MSC_SUBCLASS_SCSI, MSC_PROTOCOL_BOT, None);
+        let read_ep = alt.endpoint_bulk_out(max_packet_size);
+        let write_ep = alt.endpoint_bulk_in(max_packet_size);
+        drop(func);
+
+        let control = state.control.write(Control { if_num });
+        builder.handler(control);
+
+        Self {
+            read_ep,
+            write_ep,
+            config,
+            sense: Sense::NONE,
+        }
+    }
+
+    /// Serve the commands of the host on `device`, forever.
+    ///
+    /// The host caches the filesystem of the drive, so the device must not be modified by anything
+    /// else while it is exposed.
+    pub async fn run<B: BlockDevice>(&mut self, mut device: B) -> ! {
+        let mut block = Block::new();
+        loop {
+            self.read_ep.wait_enabled().await;
+            self.sense = Sense::NONE;
+            debug!("Mass storage enabled");
+
+            loop {
+                match self.handle_command(&mut device, &mut block).await {
+                    Ok(()) => {}
+                    Err(EndpointError::Disabled) => break,
+                    Err(EndpointError::BufferOverflow) => warn!("Mass storage packet too large"),
+                }
+            }
+            debug!("Mass storage disabled");
+        }
+    }
+
+    async fn handle_command<B: BlockDevice>(&mut self, device: &mut B, block: &mut Block) -> Result<(), EndpointError> {
+        let n = self.read_ep.read(&mut block[..]).await?;
+        let Some(cbw) = Cbw::parse(&block[..n]) else {
+            warn!("Invalid command block wrapper");
+            return Ok(());
+        };
+
+        let (status, residue) = self.execute(&cbw, device, block).await?;
+        if status != Status::Passed {
+            debug!("SCSI command {:02x} failed", cbw.cb[0]);
+        }
+
+        let mut csw = [0; CSW_LEN];
+        csw[0..4].copy_from_slice(&CSW_SIGNATURE.to_le_bytes());
+        csw[4..8].copy_from_slice(&cbw.tag.to_le_bytes());
+        csw[8..12].copy_from_slice(&residue.to_le_bytes());
+        csw[12] = status as u8;
+        self.write_ep.write(&csw).await
+    }
+
+    /// Execute a command, returning its status and the residue of its data stage.
+    async fn execute<B: BlockDevice>(
+        &mut self,
+        cbw: &Cbw,
+        device: &mut B,
+        block: &mut Block,
+    ) -> Result<(Status, u32), EndpointError> {
+        let cb = &cbw.cb;
+        match cb[0] {
+            SCSI_TEST_UNIT_READY
+            | SCSI_START_STOP_UNIT
+            | SCSI_PREVENT_ALLOW_MEDIUM_REMOVAL
+            | SCSI_VERIFY_10
+            | SCSI_SYNCHRONIZE_CACHE_10 => self.no_data(cbw, Status::Passed, block).await,
+            SCSI_REQUEST_SENSE => {
+                let Sense(key, asc, ascq) = self.sense;
+                self.sense = Sense::NONE;
+                let data = [0x70, 0, key, 0, 0, 0, 0, 10, 0, 0, 0, 0, asc, ascq, 0, 0, 0, 0];
+                self.data_in(cbw, &data, cb[4] as usize, block).await
+            }
+            SCSI_INQUIRY => {
+                // Vital product data pages are not supported.
+                if cb[1] & 0x01 != 0 {
+                    return self.fail(cbw, Sense::INVALID_FIELD, block).await;
+                }
+                let mut data = [b' '; 36];
+                data[0..8].copy_from_slice(&[0x00, (self.config.removable as u8) << 7, 0x04, 0x02, 31, 0, 0, 0]);
+                copy_padded(&mut data[8..16], self.config.vendor);
+                copy_padded(&mut data[16..32], self.config.product);
+                copy_padded(&mut data[32..36], self.config.revision);
+                let alloc_len = u16::from_be_bytes([cb[3], cb[4]]);
+                self.data_in(cbw, &data, alloc_len as usize, block).await
+            }
+            SCSI_MODE_SENSE_6 => {
+                // Header only, no block descriptor and no mode pages.
+                let data = [3, 0, (self.config.read_only as u8) << 7, 0];
+                self.data_in(cbw, &data, cb[4] as usize, block).await
+            }
+            SCSI_MODE_SENSE_10 => {
+                let data = [0, 6, 0, (self.config.read_only as u8) << 7, 0, 0, 0, 0];
+                let alloc_len = u16::from_be_bytes([cb[7], cb[8]]);
+                self.data_in(cbw, &data, alloc_len as usize, block).await
+            }
+            SCSI_READ_CAPACITY_10 => {
+                let Ok(num_blocks) = device.num_blocks().await else {
+                    return self.fail(cbw, Sense::READ_ERROR, block).await;
+                };
+                let mut data = [0; 8];
+                data[0..4].copy_from_slice(&num_blocks.saturating_sub(1).to_be_bytes());
+                data[4..8].copy_from_slice(&(BLOCK_SIZE as u32).to_be_bytes());
+                self.data_in(cbw, &data, data.len(), block).await
+            }
+            SCSI_READ_FORMAT_CAPACITIES => {
+                let Ok(num_blocks) = device.num_blocks().await else {
+                    return self.fail(cbw, Sense::READ_ERROR, block).await;
+                };
+                let mut data = [0; 12];
+                data[3] = 8;
+                data[4..8].copy_from_slice(&num_blocks.to_be_bytes());
+                // Formatted media, followed by the 24-bit block length.
+                data[8..12].copy_from_slice(&(0x0200_0000 | BLOCK_SIZE as u32).to_be_bytes());
+                let alloc_len = u16::from_be_bytes([cb[7], cb[8]]);
+                self.data_in(cbw, &data, alloc_len as usize, block).await
+            }
+            SCSI_READ_10 | SCSI_WRITE_10 => {
+                let lba = u32::from_be_bytes(cb[2..6].try_into().unwrap());
+                let count = u16::from_be_bytes([cb[7], cb[8]]) as u32;
+                if cb[0] == SCSI_READ_10 {
+                    self.read_blocks(cbw, device, lba, count, block).await
+                } else {
+                    self.write_blocks(cbw, device, lba, count, block).await
+                }
+            }
+            _ => {
+                debug!("Unsupported SCSI command {:02x}", cb[0]);
+                self.fail(cbw, Sense::INVALID_COMMAND, block).await
+            }
+        }
+    }
+
+    async fn read_blocks<B: BlockDevice>(
+        &mut self,
+        cbw: &Cbw,
+        device: &mut B,
+        lba: u32,
+        count: u32,
+        block: &mut Block,
+    ) -> Result<(Status, u32), EndpointError> {
+        let len = count * BLOCK_SIZE as u32;
+        if (count > 0 && !cbw.data_in) || cbw.data_len < len {
+            return self.no_data(cbw, Status::PhaseError, block).await;
+        }
+        if !self.in_range(device, lba, count).await {
+            return self.fail(cbw, Sense::LBA_OUT_OF_RANGE, block).await;
+        }
+
+        for i in 0..count {
+            if device.read(lba + i, core::slice::from_mut(block)).await.is_err() {
+                self.sense = Sense::READ_ERROR;
+                // All the blocks sent so far are full packets, end the transfer.
+                self.write_ep.write(&[]).await?;
+                return Ok((Status::Failed, cbw.data_len - i * BLOCK_SIZE as u32));
+            }
+            self.write_packets(&block[..], false).await?;
+        }
+
+        if cbw.data_len > len {
+            self.write_ep.write(&[]).await?;
+        }
+        Ok((Status::Passed, cbw.data_len - len))
+    }
+
+    async fn write_blocks<B: BlockDevice>(
+        &mut self,
+        cbw: &Cbw,
+        device: &mut B,
+        lba: u32,
+        count: u32,
+        block: &mut Block,
+    ) -> Result<(Status, u32), EndpointError> {
+        let len = count * BLOCK_SIZE as u32;
+        if (count > 0 && cbw.data_in) || cbw.data_len < len {
+            return self.no_data(cbw, Status::PhaseError, block).await;
+        }
+        if self.config.read_only {
+            return self.fail(cbw, Sense::WRITE_PROTECTED, block).await;
+        }
+        if !self.in_range(device, lba, count).await {
+            return self.fail(cbw, Sense::LBA_OUT_OF_RANGE, block).await;
+        }
+
+        for i in 0..count {
+            let mut received = 0;
+            while received < BLOCK_SIZE {
+                received += self.read_ep.read(&mut block[received..]).await?;
+            }
+            if device.write(lba + i, core::slice::from_ref(block)).await.is_err() {
+                self.sense = Sense::WRITE_ERROR;
+                let residue = cbw.data_len - (i + 1) * BLOCK_SIZE as u32;
+                self.drain(residue, block).await?;
+                return Ok((Status::Failed, residue));
+            }
+        }
+
+        self.drain(cbw.data_len - len, block).await?;
+        Ok((Status::Passed, cbw.data_len - len))
+    }
+
+    async fn in_range<B: BlockDevice>(&mut self, device: &mut B, lba: u32, count: u32) -> bool {
+        match device.num_blocks().await {
+            Ok(num_blocks) => lba.checked_add(count).map_or(false, |end| end <= num_blocks),
+            Err(_) => false,
+        }
+    }
+
+    /// Send the response of a command, truncated to `alloc_len` bytes.
+    async fn data_in(
+        &mut self,
+        cbw: &Cbw,
+        data: &[u8],
+        alloc_len: usize,
+        block: &mut Block,
+    ) -> Result<(Status, u32), EndpointError> {
+        let len = data.len().min(alloc_len);
+        if len == 0 {
+            return self.no_data(cbw, Status::Passed, block).await;
+        }
+        if !cbw.data_in || cbw.data_len == 0 {
+            return self.no_data(cbw, Status::PhaseError, block).await;
+        }
+
+        let len = len.min(cbw.data_len as usize);
+        self.write_packets(&data[..len], len < cbw.data_len as usize).await?;
+        Ok((Status::Passed, cbw.data_len - len as u32))
+    }
+
+    /// Fail the command, skipping its data stage.
+    async fn fail(&mut self, cbw: &Cbw, sense: Sense, block: &mut Block) -> Result<(Status, u32), EndpointError> {
+        self.sense = sense;
+        self.no_data(cbw, Status::Failed, block).await
+    }
+
+    /// Skip the data stage expected by the host, if any.
+    async fn no_data(&mut self, cbw: &Cbw, status: Status, block: &mut Block) -> Result<(Status, u32), EndpointError> {
+        if cbw.data_len > 0 {
+            if cbw.data_in {
+                self.write_ep.write(&[]).await?;
+            } else {
+                self.drain(cbw.data_len, block).await?;
+            }
+        }
+        Ok((status, cbw.data_len))
+    }
+
+    /// Write `data`, ending the transfer with a zero-length packet if `terminate` is set and the
+    /// last packet is full.
+    async fn write_packets(&mut self, data: &[u8], terminate: bool) -> Result<(), EndpointError> {
+        let max_packet_size = self.write_ep.info().max_packet_size as usize;
+        for chunk in data.chunks(max_packet_size) {
+            self.write_ep.write(chunk).await?;
+        }
+        if terminate && data.len() % max_packet_size == 0 {
+            self.write_ep.write(&[]).await?;
+        }
+        Ok(())
+    }
+
+    /// Read and discard `len` bytes sent by the host.
+    async fn drain(&mut self, mut len: u32, block: &mut Block) -> Result<(), EndpointError> {
+        while len > 0 {
+            let n = self.read_ep.read(&mut block[..]).await?;
+            if n == 0 {
+                break;
+            }
+            len = len.saturating_sub(n as u32);
+        }
+        Ok(())
+    }
+}
+
+/// Copy an ASCII string into a field padded with spaces.
+fn copy_padded(field: &mut [u8], s: &str) {
+    let len = s.len().min(field.len());
+    field[..len].copy_from_slice(&s.as_bytes()[..len]);
+}
@@ -0,0 +1,101 @@
+//! Composite devices, combining any set of classes.
+//!
+//! A [`CompositeBuilder`] configures the device for interface association descriptors, so that
+//! each class is a separate function for the host, and returns the handle of each class as it is
+//! added. Classes not implemented in this crate are added with [`CompositeBuilder::add_class`].
+//!
+//! The [`UsbDevice`] built from it dispatches the control requests and the bus events to the
+//! class they belong to, while each handle services the endpoints of its class. The handles are
+//! independent: they can be used concurrently with the device, from the same task or from
+//! different tasks.
+
+use crate::class::cdc_acm::{self, CdcAcmClass};
+use crate::class::hid::{self, HidReaderWriter, HidWriter};
+use crate::class::msc::{self, MscClass};
+use crate::driver::Driver;
+use crate::{Builder, Config, UsbDevice};
+
+/// Builder of a composite [`UsbDevice`].
+pub struct CompositeBuilder<'d, D: Driver<'d>> {
+    builder: Builder<'d, D>,
+}
+
+impl<'d, D: Driver<'d>> CompositeBuilder<'d, D> {
+    /// Creates a builder for a composite [`UsbDevice`].
+    ///
+    /// The device class of `config` is overwritten to declare a device made of functions with
+    /// interface association descriptors. The buffers are the same as for [`Builder::new`].
+    pub fn new(
+        driver: D,
+        mut config: Config<'d>,
+        device_descriptor_buf: &'d mut [u8],
+        config_descriptor_buf: &'d mut [u8],
+        bos_descriptor_buf: &'d mut [u8],
+        msos_descriptor_buf: &'d mut [u8],
+        control_buf: &'d mut [u8],
+    ) -> Self {
+        // Magic values specified in USB-IF ECN on IADs.
+        config.device_class = 0xEF;
+        config.device_sub_class = 0x02;
+        config.device_protocol = 0x01;
+        config.composite_with_iads = true;
+
+        Self {
+            builder: Builder::new(
+                driver,
+                config,
+                device_descriptor_buf,
+                config_descriptor_buf,
+                bos_descriptor_buf,
+                msos_descriptor_buf,
+                control_buf,
+            ),
+        }
+    }
+
+    /// Adds a CDC-ACM serial port, see [`CdcAcmClass::new`].
+    pub fn add_cdc_acm(&mut self, state: &'d mut cdc_acm::State<'d>, max_packet_size: u16) -> CdcAcmClass<'d, D> {
+        CdcAcmClass::new(&mut self.builder, state, max_packet_size)
+    }
+
+    /// Adds a HID interface with IN and OUT endpoints, see [`HidReaderWriter::new`].
+    pub fn add_hid<const READ_N: usize, const WRITE_N: usize>(
+        &mut self,
+        state: &'d mut hid::State<'d>,
+        config: hid::Config<'d>,
+    ) -> HidReaderWriter<'d, D, READ_N, WRITE_N> {
+        HidReaderWriter::new(&mut self.builder, state, config)
+    }
+
+    /// Adds a HID interface with an IN endpoint only, see [`HidWriter::new`].
+    pub fn add_hid_writer<const N: usize>(
+        &mut self,
+        state: &'d mut hid::State<'d>,
+        config: hid::Config<'d>,
+    ) -> HidWriter<'d, D, N> {
+        HidWriter::new(&mut self.builder, state, config)
+    }
+
+    /// Adds a Mass Storage drive, see [`MscClass::new`].
+    pub fn add_msc(
+        &mut self,
+        state: &'d mut msc::State,
+        config: msc::Config<'d>,
+        max_packet_size: u16,
+    ) -> MscClass<'d, D> {
+        MscClass::new(&mut self.builder, state, config, max_packet_size)
+    }
+
+    /// Adds any other class, created by `f` on the underlying [`Builder`].
+    ///
+    /// The class should add its interfaces in a single [`Builder::function`], so that the host
+    /// associates them with each other.
+    pub fn add_class<C>(&mut self, f: impl FnOnce(&mut Builder<'d, D>) -> C) -> C {
+        f(&mut self.builder)
+    }
+
+    /// Creates the [`UsbDevice`], which has to be run for the classes to work.
+    pub fn build(self) -> UsbDevice<'d, D> {
+        self.builder.build()
+    }
+}
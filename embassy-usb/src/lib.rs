@@ -9,6 +9,7 @@ pub use embassy_usb_driver as driver;
 
 mod builder;
 pub mod class;
+pub mod composite;
 pub mod control;
 pub mod descriptor;
 mod descriptor_reader;
@@ -0,0 +1,38 @@
+#![no_std]
+#![no_main]
+
+use defmt::info;
+use embassy_executor::Spawner;
+use embassy_nrf::bind_interrupts;
+use embassy_nrf::clock::{self, Clock};
+use embassy_time::{Duration, Timer};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    POWER_CLOCK => clock::InterruptHandler;
+});
+
+#[embassy_executor::task]
+async fn calibration(clock: Clock) {
+    clock.run_calibration(Duration::from_secs(4)).await
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let _p = embassy_nrf::init(Default::default());
+    let clock = Clock::new(Irqs);
+    info!("clocks: {}", clock.clocks());
+
+    spawner.spawn(calibration(clock)).unwrap();
+
+    loop {
+        {
+            // Keep the HFXO running only while it's needed, for example by the radio.
+            let _hfxo = clock.request_hfxo().await;
+            info!("clocks: {}", clock.clocks());
+            Timer::after_millis(100).await;
+        }
+        info!("clocks: {}", clock.clocks());
+        Timer::after_secs(1).await;
+    }
+}
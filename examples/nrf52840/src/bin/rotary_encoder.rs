@@ -0,0 +1,34 @@
+#![no_std]
+#![no_main]
+
+use defmt::{info, unwrap};
+use embassy_embedded_hal::encoder::{Detent, RotaryEncoder};
+use embassy_executor::Spawner;
+use embassy_futures::select::{select, Either};
+use embassy_nrf::gpio::{Input, Pull};
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_nrf::init(Default::default());
+
+    // Encoder with a push button, the common part connected to ground.
+    let a = Input::new(p.P0_31, Pull::Up);
+    let b = Input::new(p.P0_30, Pull::Up);
+    let mut button = Input::new(p.P0_11, Pull::Up);
+
+    let mut encoder = unwrap!(RotaryEncoder::new(a, b, Detent::Full));
+
+    loop {
+        match select(encoder.wait_step(), button.wait_for_falling_edge()).await {
+            Either::First(direction) => {
+                let direction = unwrap!(direction);
+                info!("{}: position {}", direction, encoder.position());
+            }
+            Either::Second(()) => {
+                encoder.set_position(0);
+                info!("Button pressed: position reset");
+            }
+        }
+    }
+}
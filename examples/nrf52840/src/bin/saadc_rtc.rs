@@ -0,0 +1,43 @@
+#![no_std]
+#![no_main]
+
+use defmt::info;
+use embassy_executor::Spawner;
+use embassy_nrf::saadc::{CallbackResult, ChannelConfig, Config, Saadc};
+use embassy_nrf::{bind_interrupts, saadc};
+use {defmt_rtt as _, panic_probe as _};
+
+// Demonstrates low power sampling driven by a PPI linked RTC: the CPU only wakes up when a buffer is full.
+
+bind_interrupts!(struct Irqs {
+    SAADC => saadc::InterruptHandler;
+});
+
+#[embassy_executor::main]
+async fn main(_p: Spawner) {
+    let mut p = embassy_nrf::init(Default::default());
+    let config = Config::default();
+    let channel_config = ChannelConfig::single_ended(&mut p.P0_02);
+    let mut saadc = Saadc::new(p.SAADC, Irqs, config, [channel_config]);
+
+    saadc.calibrate().await;
+
+    let mut bufs = [[[0; 1]; 100]; 2];
+
+    saadc
+        .run_rtc_sampler(
+            &mut p.RTC2,
+            &mut p.PPI_CH0,
+            &mut p.PPI_CH1,
+            327,       // 100Hz RTC tick
+            10,        // We want to sample at 10Hz
+            Some(600), // Calibrate every 600 buffers, that is every 100 minutes
+            &mut bufs,
+            move |buf| {
+                let sum: i32 = buf.iter().map(|b| b[0] as i32).sum();
+                info!("channel 1: {=i32}", sum / buf.len() as i32);
+                CallbackResult::Continue
+            },
+        )
+        .await;
+}
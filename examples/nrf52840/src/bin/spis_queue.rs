@@ -0,0 +1,47 @@
+#![no_std]
+#![no_main]
+
+use defmt::{info, unwrap};
+use embassy_executor::Spawner;
+use embassy_nrf::spis::{Config, QueueSlot, Spis, SpisQueue};
+use embassy_nrf::{bind_interrupts, peripherals, spis};
+use static_cell::StaticCell;
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    SPIM2_SPIS2_SPI2 => spis::InterruptHandler<peripherals::SPI2>;
+});
+
+const FRAMES: usize = 4;
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_nrf::init(Default::default());
+    info!("Running!");
+
+    let spis = Spis::new(p.SPI2, Irqs, p.P0_31, p.P0_29, p.P0_28, p.P0_30, Config::default());
+
+    static SLOTS: StaticCell<[QueueSlot; FRAMES]> = StaticCell::new();
+    static RX: StaticCell<[[u8; 16]; FRAMES]> = StaticCell::new();
+    static TX: StaticCell<[[u8; 16]; FRAMES]> = StaticCell::new();
+    let slots = SLOTS.init([QueueSlot::new(); FRAMES]);
+    let rx = RX.init([[0; 16]; FRAMES]);
+    let tx = TX.init([[0; 16]; FRAMES]);
+
+    let mut queue = SpisQueue::new(spis, slots);
+    for (rx, tx) in rx.iter_mut().zip(tx.iter_mut()) {
+        unwrap!(queue.push(rx, tx));
+    }
+
+    let mut sample = 0u8;
+    loop {
+        let mut frame = queue.wait_frame().await;
+        info!("RX: {:?}, TX: {} bytes", frame.rx[..frame.n_rx], frame.n_tx);
+
+        // The buffers are back, queue them again with the next sample.
+        frame.tx.fill(sample);
+        sample = sample.wrapping_add(1);
+
+        unwrap!(queue.push(frame.rx, frame.tx));
+    }
+}
@@ -0,0 +1,74 @@
+#![no_std]
+#![no_main]
+
+use defmt::{info, unwrap, warn};
+use embassy_embedded_hal::adc::AdcChannel;
+use embassy_embedded_hal::supervisor::{self, InternalSensors, Supervisor};
+use embassy_executor::Spawner;
+use embassy_nrf::bind_interrupts;
+use embassy_nrf::saadc::{self, ChannelConfig, Saadc, VddInput};
+use embassy_nrf::temp::{self, Temp};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    SAADC => saadc::InterruptHandler;
+    TEMP => temp::InterruptHandler;
+});
+
+static SUPERVISOR: Supervisor<CriticalSectionRawMutex, 2> = Supervisor::new();
+
+struct Sensors {
+    temp: Temp<'static>,
+    saadc: Saadc<'static, 1>,
+}
+
+impl InternalSensors for Sensors {
+    type Error = saadc::Error;
+
+    async fn read_temperature_mc(&mut self) -> Result<i32, saadc::Error> {
+        // The TEMP peripheral has a resolution of 0.25°C.
+        Ok(self.temp.read().await.to_bits() * 250)
+    }
+
+    async fn read_vdd_mv(&mut self) -> Result<u32, saadc::Error> {
+        Ok(self.saadc.read_average_mv(4).await?.max(0) as u32)
+    }
+}
+
+#[embassy_executor::task]
+async fn supervise(sensors: Sensors) {
+    let mut config = supervisor::Config::default();
+    config.over_temperature_mc = Some(60_000);
+    config.undervoltage_mv = Some(2_000);
+
+    let err = SUPERVISOR.run(sensors, config).await.unwrap_err();
+    warn!("supervisor stopped: {:?}", err);
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let p = embassy_nrf::init(Default::default());
+
+    let temp = Temp::new(p.TEMP, Irqs);
+    let saadc = Saadc::new(
+        p.SAADC,
+        Irqs,
+        saadc::Config::default(),
+        [ChannelConfig::single_ended(VddInput)],
+    );
+    saadc.calibrate().await;
+
+    unwrap!(spawner.spawn(supervise(Sensors { temp, saadc })));
+
+    let mut alarms = unwrap!(SUPERVISOR.alarm_changes());
+    loop {
+        let alarms = alarms.changed().await;
+        if alarms.any() {
+            // Derate the application here, for example lower the radio TX power.
+            warn!("alarms: {:?}, reading: {:?}", alarms, SUPERVISOR.reading());
+        } else {
+            info!("no alarm, reading: {:?}", SUPERVISOR.reading());
+        }
+    }
+}
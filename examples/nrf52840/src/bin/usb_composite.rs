@@ -0,0 +1,155 @@
+#![no_std]
+#![no_main]
+
+use core::convert::Infallible;
+use core::mem;
+
+use defmt::{info, warn};
+use embassy_embedded_hal::block::{Block, BlockDevice};
+use embassy_executor::Spawner;
+use embassy_futures::join::join4;
+use embassy_nrf::gpio::{Input, Pull};
+use embassy_nrf::usb::vbus_detect::HardwareVbusDetect;
+use embassy_nrf::usb::Driver;
+use embassy_nrf::{bind_interrupts, pac, peripherals, usb};
+use embassy_usb::class::cdc_acm;
+use embassy_usb::class::hid::{self, HidBootProtocol};
+use embassy_usb::class::msc;
+use embassy_usb::composite::CompositeBuilder;
+use embassy_usb::Config;
+use static_cell::StaticCell;
+use usbd_hid::descriptor::{KeyboardReport, SerializedDescriptor};
+use {defmt_rtt as _, panic_probe as _};
+
+// Composite device with a serial port, a keyboard and a RAM disk. Each class is added to the
+// composite builder, which returns its handle, and runs concurrently with the device.
+
+bind_interrupts!(struct Irqs {
+    USBD => usb::InterruptHandler<peripherals::USBD>;
+    POWER_CLOCK => usb::vbus_detect::InterruptHandler;
+});
+
+const DISK_BLOCKS: usize = 128;
+
+static DISK: StaticCell<[Block; DISK_BLOCKS]> = StaticCell::new();
+
+struct RamDisk(&'static mut [Block; DISK_BLOCKS]);
+
+impl BlockDevice for RamDisk {
+    type Error = Infallible;
+
+    async fn num_blocks(&mut self) -> Result<u32, Infallible> {
+        Ok(DISK_BLOCKS as u32)
+    }
+
+    async fn read(&mut self, block_idx: u32, blocks: &mut [Block]) -> Result<(), Infallible> {
+        let start = block_idx as usize;
+        blocks.clone_from_slice(&self.0[start..start + blocks.len()]);
+        Ok(())
+    }
+
+    async fn write(&mut self, block_idx: u32, blocks: &[Block]) -> Result<(), Infallible> {
+        let start = block_idx as usize;
+        self.0[start..start + blocks.len()].clone_from_slice(blocks);
+        Ok(())
+    }
+}
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_nrf::init(Default::default());
+    let clock: pac::CLOCK = unsafe { mem::transmute(()) };
+
+    info!("Enabling ext hfosc...");
+    clock.tasks_hfclkstart.write(|w| unsafe { w.bits(1) });
+    while clock.events_hfclkstarted.read().bits() != 1 {}
+
+    // Create the driver, from the HAL.
+    let driver = Driver::new(p.USBD, Irqs, HardwareVbusDetect::new(Irqs));
+
+    // Create embassy-usb Config
+    let mut config = Config::new(0xc0de, 0xcafe);
+    config.manufacturer = Some("Embassy");
+    config.product = Some("USB composite example");
+    config.serial_number = Some("12345678");
+    config.max_power = 100;
+    config.max_packet_size_0 = 64;
+
+    // Create embassy-usb CompositeBuilder using the driver and config.
+    // It needs some buffers for building the descriptors.
+    let mut device_descriptor = [0; 256];
+    let mut config_descriptor = [0; 256];
+    let mut bos_descriptor = [0; 256];
+    let mut msos_descriptor = [0; 256];
+    let mut control_buf = [0; 64];
+
+    let mut cdc_state = cdc_acm::State::new();
+    let mut hid_state = hid::State::new();
+    let mut msc_state = msc::State::new();
+
+    let mut builder = CompositeBuilder::new(
+        driver,
+        config,
+        &mut device_descriptor,
+        &mut config_descriptor,
+        &mut bos_descriptor,
+        &mut msos_descriptor,
+        &mut control_buf,
+    );
+
+    // Add the classes, each one is a function grouped by an interface association descriptor.
+    let mut serial = builder.add_cdc_acm(&mut cdc_state, 64);
+    let hid_config = hid::Config {
+        report_descriptor: KeyboardReport::desc(),
+        request_handler: None,
+        poll_ms: 60,
+        max_packet_size: 8,
+        boot_protocol: HidBootProtocol::None,
+    };
+    let mut keyboard = builder.add_hid_writer::<8>(&mut hid_state, hid_config);
+    let mut disk = builder.add_msc(&mut msc_state, msc::Config::default(), 64);
+
+    // Build the builder.
+    let mut usb = builder.build();
+
+    let serial_fut = async {
+        let mut buf = [0; 64];
+        loop {
+            serial.wait_connection().await;
+            info!("Serial connected");
+            while let Ok(n) = serial.read_packet(&mut buf).await {
+                if serial.write_packet(&buf[..n]).await.is_err() {
+                    break;
+                }
+            }
+            info!("Serial disconnected");
+        }
+    };
+
+    let mut button = Input::new(p.P0_11, Pull::Up);
+    let keyboard_fut = async {
+        loop {
+            button.wait_for_low().await;
+            for keycodes in [[4, 0, 0, 0, 0, 0], [0; 6]] {
+                let report = KeyboardReport {
+                    keycodes,
+                    leds: 0,
+                    modifier: 0,
+                    reserved: 0,
+                };
+                if let Err(e) = keyboard.write_serialize(&report).await {
+                    warn!("Failed to send report: {:?}", e);
+                }
+            }
+            button.wait_for_high().await;
+        }
+    };
+
+    const EMPTY: Block = Block::new();
+    let ram_disk = RamDisk(DISK.init([EMPTY; DISK_BLOCKS]));
+    let disk_fut = disk.run(ram_disk);
+
+    // Run everything concurrently.
+    // If we had made everything `'static` above instead, we could do this using separate tasks instead.
+    join4(usb.run(), serial_fut, keyboard_fut, disk_fut).await;
+}
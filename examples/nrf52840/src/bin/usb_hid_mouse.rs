@@ -10,7 +10,7 @@ use embassy_nrf::usb::vbus_detect::HardwareVbusDetect;
 use embassy_nrf::usb::Driver;
 use embassy_nrf::{bind_interrupts, pac, peripherals, usb};
 use embassy_time::Timer;
-use embassy_usb::class::hid::{HidWriter, ReportId, RequestHandler, State};
+use embassy_usb::class::hid::{HidBootProtocol, HidWriter, ReportId, RequestHandler, State};
 use embassy_usb::control::OutResponse;
 use embassy_usb::{Builder, Config};
 use usbd_hid::descriptor::{MouseReport, SerializedDescriptor};
@@ -68,6 +68,7 @@ async fn main(_spawner: Spawner) {
         request_handler: Some(&request_handler),
         poll_ms: 60,
         max_packet_size: 8,
+        boot_protocol: HidBootProtocol::None,
     };
 
     let mut writer = HidWriter::<_, 5>::new(&mut builder, &mut state, config);
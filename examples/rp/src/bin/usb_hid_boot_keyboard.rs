@@ -0,0 +1,105 @@
+//! Boot keyboard using the HID presets of embassy-usb, usable from a BIOS.
+//!
+//! Types `a` while pin 16 is high, and logs the caps lock LED.
+
+#![no_std]
+#![no_main]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_futures::join::join;
+use embassy_rp::bind_interrupts;
+use embassy_rp::gpio::{Input, Pull};
+use embassy_rp::peripherals::USB;
+use embassy_rp::usb::{Driver, InterruptHandler};
+use embassy_usb::class::hid::boot::{keyboard_config, KeyboardLeds, KeyboardReport};
+use embassy_usb::class::hid::{HidProtocol, HidReaderWriter, RequestHandler, State};
+use embassy_usb::{Builder, Config};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    USBCTRL_IRQ => InterruptHandler<USB>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+    let driver = Driver::new(p.USB, Irqs);
+
+    let mut config = Config::new(0xc0de, 0xcafe);
+    config.manufacturer = Some("Embassy");
+    config.product = Some("HID boot keyboard example");
+    config.serial_number = Some("12345678");
+    config.max_power = 100;
+    config.max_packet_size_0 = 64;
+
+    let mut device_descriptor = [0; 256];
+    let mut config_descriptor = [0; 256];
+    let mut bos_descriptor = [0; 256];
+    let mut control_buf = [0; 64];
+    let request_handler = MyRequestHandler {};
+    let mut state = State::new();
+
+    let mut builder = Builder::new(
+        driver,
+        config,
+        &mut device_descriptor,
+        &mut config_descriptor,
+        &mut bos_descriptor,
+        &mut [], // no msos descriptors
+        &mut control_buf,
+    );
+
+    let hid = HidReaderWriter::<_, 1, 8>::new(&mut builder, &mut state, keyboard_config(Some(&request_handler), 10));
+
+    let mut usb = builder.build();
+    let usb_fut = usb.run();
+
+    let mut signal_pin = Input::new(p.PIN_16, Pull::None);
+    signal_pin.set_schmitt(true);
+
+    let (mut reader, mut writer) = hid.split();
+
+    let in_fut = async {
+        loop {
+            signal_pin.wait_for_high().await;
+            info!("key down, protocol {:?}", writer.protocol());
+            let report = KeyboardReport {
+                modifiers: 0,
+                keycodes: [4, 0, 0, 0, 0, 0],
+            };
+            if let Err(e) = writer.write(&report.to_bytes()).await {
+                warn!("Failed to send report: {:?}", e);
+            }
+
+            signal_pin.wait_for_low().await;
+            if let Err(e) = writer.write(&KeyboardReport::default().to_bytes()).await {
+                warn!("Failed to send report: {:?}", e);
+            }
+        }
+    };
+
+    // The LEDs are sent with SET_REPORT or on the Interrupt Out pipe, depending on the host.
+    let out_fut = async {
+        let mut buf = [0; 1];
+        loop {
+            match reader.recv_report(&mut buf).await {
+                Ok(_) => info!("caps lock: {}", KeyboardLeds(buf[0]).caps_lock()),
+                Err(e) => {
+                    warn!("Failed to receive report: {:?}", e);
+                    reader.ready().await;
+                }
+            }
+        }
+    };
+
+    join(usb_fut, join(in_fut, out_fut)).await;
+}
+
+struct MyRequestHandler {}
+
+impl RequestHandler for MyRequestHandler {
+    fn set_protocol(&self, protocol: HidProtocol) {
+        info!("protocol set to {:?}", protocol);
+    }
+}
@@ -100,11 +100,12 @@ impl From<EndpointError> for Disconnected {
 }
 
 async fn midi_echo<'d, T: Instance + 'd>(class: &mut MidiClass<'d, Driver<'d, T>>) -> Result<(), Disconnected> {
-    let mut buf = [0; 64];
     loop {
-        let n = class.read_packet(&mut buf).await?;
-        let data = &buf[..n];
-        info!("data: {:x}", data);
-        class.write_packet(data).await?;
+        let event = class.read_event().await?;
+        match event.message() {
+            Some(message) => info!("cable {}: {:?}", event.cable(), message),
+            None => info!("cable {}: data {:x}", event.cable(), event.midi()),
+        }
+        class.write_events(&[event]).await?;
     }
 }
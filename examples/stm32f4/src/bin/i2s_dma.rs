@@ -1,20 +1,35 @@
 #![no_std]
 #![no_main]
 
-use core::fmt::Write;
-
 use defmt::*;
 use embassy_executor::Spawner;
-use embassy_stm32::i2s::{Config, I2S};
+use embassy_stm32::i2s::{Config, Format, I2S};
 use embassy_stm32::time::Hertz;
-use heapless::String;
 use {defmt_rtt as _, panic_probe as _};
 
+// 1 kHz square wave at 48 kHz, stereo.
+const PERIOD: usize = 48;
+
 #[embassy_executor::main]
 async fn main(_spawner: Spawner) {
-    let p = embassy_stm32::init(Default::default());
+    let mut config = embassy_stm32::Config::default();
+    {
+        use embassy_stm32::rcc::*;
+        // I2S clock, 86Mhz (16 / 16 * 258 / 3), for 48khz with the master clock enabled.
+        config.rcc.plli2s = Some(Pll {
+            prediv: PllPreDiv::DIV16,
+            mul: PllMul::MUL258,
+            divp: None,
+            divq: None,
+            divr: Some(PllRDiv::DIV3),
+        });
+    }
+    let p = embassy_stm32::init(config);
     info!("Hello World!");
 
+    let mut i2s_config = Config::default();
+    i2s_config.format = Format::Data32Channel32;
+
     let mut i2s = I2S::new(
         p.SPI2,
         p.PC3,  // sd
@@ -23,13 +38,18 @@ async fn main(_spawner: Spawner) {
         p.PC6,  // mck
         p.DMA1_CH4,
         p.DMA1_CH3,
-        Hertz(1_000_000),
-        Config::default(),
+        Hertz(48_000),
+        i2s_config,
     );
 
-    for n in 0u32.. {
-        let mut write: String<128> = String::new();
-        core::write!(&mut write, "Hello DMA World {}!\r\n", n).unwrap();
-        i2s.write(&mut write.as_bytes()).await.ok();
+    // The DMA sends the low half-word of the samples first, the I2S expects the high one.
+    let mut samples = [0u32; PERIOD * 2];
+    for (i, frame) in samples.chunks_mut(2).enumerate() {
+        let value: u32 = if i < PERIOD / 2 { 0x2000_0000 } else { 0xE000_0000 };
+        frame.fill(value.rotate_left(16));
+    }
+
+    loop {
+        unwrap!(i2s.write(&samples).await);
     }
 }
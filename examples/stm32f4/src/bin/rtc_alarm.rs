@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+
+use chrono::{NaiveDate, NaiveDateTime};
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::rtc::{self, Alarm, AlarmTime, Rtc, RtcConfig};
+use embassy_stm32::{bind_interrupts, Config};
+use futures::future::{select, Either};
+use futures::pin_mut;
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    RTC_ALARM => rtc::InterruptHandler;
+    RTC_WKUP => rtc::InterruptHandler;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let config = Config::default();
+    let p = embassy_stm32::init(config);
+
+    info!("Hello World!");
+
+    let now = NaiveDate::from_ymd_opt(2020, 5, 15)
+        .unwrap()
+        .and_hms_opt(10, 30, 15)
+        .unwrap();
+
+    let mut rtc = Rtc::new(p.RTC, RtcConfig::default());
+    rtc.set_datetime(now.into()).expect("datetime not set");
+
+    // Every minute, at second 30.
+    let time = AlarmTime {
+        second: Some(30),
+        ..Default::default()
+    };
+    unwrap!(rtc.set_alarm(Alarm::A, time, Irqs));
+    rtc.start_wakeup_timer(5000, Irqs);
+
+    loop {
+        let alarm = rtc.wait_for_alarm(Alarm::A);
+        let wakeup = rtc.wait_for_wakeup();
+        pin_mut!(alarm, wakeup);
+        match select(alarm, wakeup).await {
+            Either::Left(_) => info!("alarm"),
+            Either::Right(_) => info!("wakeup"),
+        }
+
+        let now: NaiveDateTime = rtc.now().unwrap().into();
+        info!("{}", now.timestamp());
+    }
+}
@@ -0,0 +1,40 @@
+#![no_std]
+#![no_main]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::gpio::{Level, Output, OutputType, Speed};
+use embassy_stm32::time::khz;
+use embassy_stm32::timer::motion::{Stepper, StepperConfig};
+use embassy_stm32::timer::simple_pwm::{PwmPin, SimplePwm};
+use embassy_stm32::timer::Channel;
+use embassy_time::Timer;
+use {defmt_rtt as _, panic_probe as _};
+
+// Drives a step/direction stepper driver: STEP on PB4 (TIM3 CH1), DIR on PB5.
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let mut p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    let step = PwmPin::new_ch1(p.PB4, OutputType::PushPull);
+    let pwm = SimplePwm::new(p.TIM3, Some(step), None, None, None, khz(1), Default::default());
+    let dir = Output::new(p.PB5, Level::Low, Speed::Low);
+
+    let mut config = StepperConfig::default();
+    config.max_speed = 3200.0;
+    config.acceleration = 6400.0;
+    let mut stepper = Stepper::new(pwm, Channel::Ch1, dir, config);
+
+    loop {
+        // One turn of a 200 steps motor with 16 microsteps, and back.
+        stepper.move_to(&mut p.DMA1_CH2, 3200).await;
+        info!("position: {}", stepper.position());
+        Timer::after_millis(500).await;
+
+        stepper.move_to(&mut p.DMA1_CH2, 0).await;
+        info!("position: {}", stepper.position());
+        Timer::after_millis(500).await;
+    }
+}
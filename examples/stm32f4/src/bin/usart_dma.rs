@@ -5,10 +5,12 @@ use core::fmt::Write;
 
 use defmt::*;
 use embassy_executor::Spawner;
+use embassy_stm32::dma::buffer::Prefix;
 use embassy_stm32::dma::NoDma;
 use embassy_stm32::usart::{Config, Uart};
 use embassy_stm32::{bind_interrupts, peripherals, usart};
 use heapless::String;
+use static_cell::StaticCell;
 use {defmt_rtt as _, panic_probe as _};
 
 bind_interrupts!(struct Irqs {
@@ -23,11 +25,17 @@ async fn main(_spawner: Spawner) {
     let config = Config::default();
     let mut usart = Uart::new(p.USART3, p.PD9, p.PD8, Irqs, p.DMA1_CH3, NoDma, config).unwrap();
 
+    static BUF: StaticCell<[u8; 128]> = StaticCell::new();
+    let mut buf = BUF.init([0; 128]);
+
     for n in 0u32.. {
         let mut s: String<128> = String::new();
         core::write!(&mut s, "Hello DMA World {}!\r\n", n).unwrap();
+        buf[..s.len()].copy_from_slice(s.as_bytes());
 
-        unwrap!(usart.write(s.as_bytes()).await);
+        let (result, b) = usart.write_buffer(Prefix::new(buf, s.len())).await;
+        buf = b.into_inner();
+        unwrap!(result);
         info!("wrote DMA");
     }
 }
@@ -85,7 +85,7 @@ async fn main(_spawner: embassy_executor::Spawner) {
 
     loop {
         for &color in COLOR_LIST {
-            ws2812_spi.write(color).await.unwrap();
+            ws2812_spi.write_buffer(color).await.0.unwrap();
             // ws2812 need at least 50 us low level input to confirm the input data and change it's state
             Timer::after_micros(50).await;
             // wait until ticker tick
@@ -8,6 +8,7 @@ use embassy_stm32::dma::NoDma;
 use embassy_stm32::spi::{Config, Spi};
 use embassy_stm32::time::Hertz;
 use embassy_time::Timer;
+use static_cell::StaticCell;
 use {defmt_rtt as _, panic_probe as _};
 
 const NR_PIXELS: usize = 15;
@@ -80,6 +81,8 @@ async fn main(_spawner: Spawner) {
     let mut spi = Spi::new_txonly_nosck(p.SPI1, p.PB5, p.DMA1_CH3, NoDma, config);
 
     let mut neopixels = Ws2812::new();
+    static BUF: StaticCell<[U5; TOTAL_BITS]> = StaticCell::new();
+    let mut buf = BUF.init([U5(0); TOTAL_BITS]);
 
     loop {
         let mut cnt: usize = 0;
@@ -94,7 +97,10 @@ async fn main(_spawner: Spawner) {
             }
             cnt += 1;
             // start sending the neopixel bit patters over spi to the neopixel string
-            spi.write(&neopixels.bitbuffer).await.ok();
+            buf.copy_from_slice(&neopixels.bitbuffer);
+            let (result, b) = spi.write_buffer(buf).await;
+            buf = b;
+            result.ok();
             Timer::after_millis(500).await;
         }
         Timer::after_millis(1000).await;
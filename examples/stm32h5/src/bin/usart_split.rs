@@ -9,6 +9,7 @@ use embassy_stm32::usart::{Config, Uart, UartRx};
 use embassy_stm32::{bind_interrupts, peripherals, usart};
 use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
 use embassy_sync::channel::Channel;
+use static_cell::StaticCell;
 use {defmt_rtt as _, panic_probe as _};
 
 bind_interrupts!(struct Irqs {
@@ -42,19 +43,26 @@ async fn main(spawner: Spawner) -> ! {
 
     unwrap!(spawner.spawn(reader(rx)));
 
+    static BUF: StaticCell<[u8; 8]> = StaticCell::new();
+    let mut buf = BUF.init([0; 8]);
     loop {
-        let buf = CHANNEL.receive().await;
+        *buf = CHANNEL.receive().await;
         info!("writing...");
-        unwrap!(tx.write(&buf).await);
+        let (result, b) = tx.write_buffer(buf).await;
+        buf = b;
+        unwrap!(result);
     }
 }
 
 #[embassy_executor::task]
 async fn reader(mut rx: UartRx<'static, UART7, GPDMA1_CH1>) {
-    let mut buf = [0; 8];
+    static BUF: StaticCell<[u8; 8]> = StaticCell::new();
+    let mut buf = BUF.init([0; 8]);
     loop {
         info!("reading...");
-        unwrap!(rx.read(&mut buf).await);
-        CHANNEL.send(buf).await;
+        let (result, b) = rx.read_buffer(buf).await;
+        buf = b;
+        unwrap!(result);
+        CHANNEL.send(*buf).await;
     }
 }
@@ -0,0 +1,212 @@
+//! TCP throughput benchmark.
+//!
+//! Receive: run `iperf -c <board ip> -p 5001 -t 10` (iperf2) on the host.
+//! Transmit: run `nc <board ip> 5002 | pv > /dev/null` on the host.
+//!
+//! Throughput and the driver performance counters are logged every second, so changes to the
+//! descriptor rings can be compared with numbers.
+
+#![no_std]
+#![no_main]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_net::tcp::TcpSocket;
+use embassy_net::{Stack, StackResources};
+use embassy_stm32::eth::generic_smi::GenericSMI;
+use embassy_stm32::eth::{perf_counters, Ethernet, PacketQueue};
+use embassy_stm32::peripherals::ETH;
+use embassy_stm32::rng::Rng;
+use embassy_stm32::{bind_interrupts, eth, peripherals, rng, Config};
+use embassy_time::{Duration, Ticker};
+use embedded_io_async::{Read, Write};
+use rand_core::RngCore;
+use static_cell::StaticCell;
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    ETH => eth::InterruptHandler;
+    RNG => rng::InterruptHandler<peripherals::RNG>;
+});
+
+type Device = Ethernet<'static, ETH, GenericSMI>;
+
+const RX_PORT: u16 = 5001;
+const TX_PORT: u16 = 5002;
+const SOCKET_BUFFER_SIZE: usize = 16 * 1024;
+
+#[embassy_executor::task]
+async fn net_task(stack: &'static Stack<Device>) -> ! {
+    stack.run().await
+}
+
+#[embassy_executor::task]
+async fn report_task() -> ! {
+    let mut ticker = Ticker::every(Duration::from_secs(1));
+    let mut last = perf_counters();
+    loop {
+        ticker.next().await;
+        let now = perf_counters();
+        let d = now.delta(&last);
+        last = now;
+        info!(
+            "rx {} kbit/s, tx {} kbit/s | irq/s {} | rx desc/s {} | tx desc/s {}",
+            d.rx_bytes / 125,
+            d.tx_bytes / 125,
+            d.interrupts,
+            d.rx_descriptors,
+            d.tx_descriptors,
+        );
+    }
+}
+
+#[embassy_executor::task]
+async fn rx_task(stack: &'static Stack<Device>) -> ! {
+    static RX_BUFFER: StaticCell<[u8; SOCKET_BUFFER_SIZE]> = StaticCell::new();
+    static TX_BUFFER: StaticCell<[u8; 1024]> = StaticCell::new();
+    let rx_buffer = RX_BUFFER.init([0; SOCKET_BUFFER_SIZE]);
+    let tx_buffer = TX_BUFFER.init([0; 1024]);
+    let mut buf = [0; 4096];
+
+    loop {
+        let mut socket = TcpSocket::new(stack, rx_buffer, tx_buffer);
+        if let Err(e) = socket.accept(RX_PORT).await {
+            warn!("rx accept error: {:?}", e);
+            continue;
+        }
+        info!("rx: connected");
+
+        let mut total: u64 = 0;
+        loop {
+            match socket.read(&mut buf).await {
+                Ok(0) => break,
+                Ok(n) => total += n as u64,
+                Err(e) => {
+                    warn!("rx read error: {:?}", e);
+                    break;
+                }
+            }
+        }
+        info!("rx: done, {} bytes", total);
+        socket.close();
+    }
+}
+
+#[embassy_executor::task]
+async fn tx_task(stack: &'static Stack<Device>) -> ! {
+    static RX_BUFFER: StaticCell<[u8; 1024]> = StaticCell::new();
+    static TX_BUFFER: StaticCell<[u8; SOCKET_BUFFER_SIZE]> = StaticCell::new();
+    let rx_buffer = RX_BUFFER.init([0; 1024]);
+    let tx_buffer = TX_BUFFER.init([0; SOCKET_BUFFER_SIZE]);
+    let buf = [0x55; 4096];
+
+    loop {
+        let mut socket = TcpSocket::new(stack, rx_buffer, tx_buffer);
+        if let Err(e) = socket.accept(TX_PORT).await {
+            warn!("tx accept error: {:?}", e);
+            continue;
+        }
+        info!("tx: connected");
+
+        let mut total: u64 = 0;
+        loop {
+            match socket.write(&buf).await {
+                Ok(n) => total += n as u64,
+                Err(e) => {
+                    info!("tx: closed ({:?})", e);
+                    break;
+                }
+            }
+        }
+        info!("tx: done, {} bytes", total);
+        socket.abort();
+        let _ = socket.flush().await;
+    }
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) -> ! {
+    let mut config = Config::default();
+    {
+        use embassy_stm32::rcc::*;
+        config.rcc.hsi = Some(HSIPrescaler::DIV1);
+        config.rcc.csi = true;
+        config.rcc.hsi48 = Some(Default::default()); // needed for RNG
+        config.rcc.pll1 = Some(Pll {
+            source: PllSource::HSI,
+            prediv: PllPreDiv::DIV4,
+            mul: PllMul::MUL50,
+            divp: Some(PllDiv::DIV2),
+            divq: None,
+            divr: None,
+        });
+        config.rcc.sys = Sysclk::PLL1_P; // 400 Mhz
+        config.rcc.ahb_pre = AHBPrescaler::DIV2; // 200 Mhz
+        config.rcc.apb1_pre = APBPrescaler::DIV2; // 100 Mhz
+        config.rcc.apb2_pre = APBPrescaler::DIV2; // 100 Mhz
+        config.rcc.apb3_pre = APBPrescaler::DIV2; // 100 Mhz
+        config.rcc.apb4_pre = APBPrescaler::DIV2; // 100 Mhz
+        config.rcc.voltage_scale = VoltageScale::Scale1;
+    }
+    let p = embassy_stm32::init(config);
+    info!("Hello World!");
+
+    // Generate random seed.
+    let mut rng = Rng::new(p.RNG, Irqs);
+    let mut seed = [0; 8];
+    rng.fill_bytes(&mut seed);
+    let seed = u64::from_le_bytes(seed);
+
+    let mac_addr = [0x00, 0x00, 0xDE, 0xAD, 0xBE, 0xEF];
+
+    // Bigger queues than the other examples, the benchmark is about throughput.
+    static PACKETS: StaticCell<PacketQueue<8, 8>> = StaticCell::new();
+    let device = Ethernet::new(
+        PACKETS.init(PacketQueue::<8, 8>::new()),
+        p.ETH,
+        Irqs,
+        p.PA1,
+        p.PA2,
+        p.PC1,
+        p.PA7,
+        p.PC4,
+        p.PC5,
+        p.PG13,
+        p.PB13,
+        p.PG11,
+        GenericSMI::new(0),
+        mac_addr,
+    );
+
+    let config = embassy_net::Config::dhcpv4(Default::default());
+
+    // Init network stack
+    static STACK: StaticCell<Stack<Device>> = StaticCell::new();
+    static RESOURCES: StaticCell<StackResources<4>> = StaticCell::new();
+    let stack = &*STACK.init(Stack::new(
+        device,
+        config,
+        RESOURCES.init(StackResources::<4>::new()),
+        seed,
+    ));
+
+    // Launch network task
+    unwrap!(spawner.spawn(net_task(stack)));
+
+    // Ensure DHCP configuration is up before accepting connections
+    stack.wait_config_up().await;
+    if let Some(config) = stack.config_v4() {
+        info!(
+            "listening on {}, ports {} (rx) and {} (tx)",
+            config.address, RX_PORT, TX_PORT
+        );
+    }
+
+    unwrap!(spawner.spawn(rx_task(stack)));
+    unwrap!(spawner.spawn(tx_task(stack)));
+    unwrap!(spawner.spawn(report_task()));
+
+    loop {
+        embassy_time::Timer::after_secs(3600).await;
+    }
+}
@@ -6,6 +6,7 @@ use core::fmt::Write;
 use cortex_m_rt::entry;
 use defmt::*;
 use embassy_executor::Executor;
+use embassy_stm32::dma::buffer::Prefix;
 use embassy_stm32::dma::NoDma;
 use embassy_stm32::usart::{Config, Uart};
 use embassy_stm32::{bind_interrupts, peripherals, usart};
@@ -24,11 +25,17 @@ async fn main_task() {
     let config = Config::default();
     let mut usart = Uart::new(p.UART7, p.PF6, p.PF7, Irqs, p.DMA1_CH0, NoDma, config).unwrap();
 
+    static BUF: StaticCell<[u8; 128]> = StaticCell::new();
+    let mut buf = BUF.init([0; 128]);
+
     for n in 0u32.. {
         let mut s: String<128> = String::new();
         core::write!(&mut s, "Hello DMA World {}!\r\n", n).unwrap();
+        buf[..s.len()].copy_from_slice(s.as_bytes());
 
-        usart.write(s.as_bytes()).await.ok();
+        let (result, b) = usart.write_buffer(Prefix::new(buf, s.len())).await;
+        buf = b.into_inner();
+        result.ok();
 
         info!("wrote DMA");
     }
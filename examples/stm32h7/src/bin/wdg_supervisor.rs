@@ -0,0 +1,48 @@
+#![no_std]
+#![no_main]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::wdg::{take_crash_report, Heartbeat, IndependentWatchdog, Supervisor};
+use embassy_time::{Duration, Timer};
+use {defmt_rtt as _, panic_probe as _};
+
+static FAST: Heartbeat = Heartbeat::new(Duration::from_millis(200));
+static SLOW: Heartbeat = Heartbeat::new(Duration::from_secs(2));
+
+#[embassy_executor::task]
+async fn fast() {
+    loop {
+        Timer::after_millis(100).await;
+        FAST.beat();
+    }
+}
+
+#[embassy_executor::task]
+async fn slow() {
+    // Gets stuck after 10 beats, so the supervisor lets the watchdog reset the chip.
+    for _ in 0..10 {
+        Timer::after_secs(1).await;
+        SLOW.beat();
+    }
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    if let Some(report) = take_crash_report() {
+        warn!("Reset after task {} was {} ms late", report.task, report.overdue_ms);
+    }
+
+    let wdg = IndependentWatchdog::new(p.IWDG1, 500_000);
+    let heartbeats = [&FAST, &SLOW];
+    let mut supervisor = Supervisor::new(wdg, &heartbeats);
+
+    unwrap!(spawner.spawn(fast()));
+    unwrap!(spawner.spawn(slow()));
+
+    let task = supervisor.run(Duration::from_millis(100)).await;
+    warn!("Task {} is late, resetting", task);
+}
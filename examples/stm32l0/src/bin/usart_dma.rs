@@ -5,6 +5,7 @@ use defmt::*;
 use embassy_executor::Spawner;
 use embassy_stm32::usart::{Config, Uart};
 use embassy_stm32::{bind_interrupts, peripherals, usart};
+use static_cell::StaticCell;
 use {defmt_rtt as _, panic_probe as _};
 
 bind_interrupts!(struct Irqs {
@@ -16,12 +17,16 @@ async fn main(_spawner: Spawner) {
     let p = embassy_stm32::init(Default::default());
     let mut usart = Uart::new(p.USART1, p.PB7, p.PB6, Irqs, p.DMA1_CH2, p.DMA1_CH3, Config::default()).unwrap();
 
-    usart.write(b"Hello Embassy World!\r\n").await.unwrap();
+    usart.write_buffer(b"Hello Embassy World!\r\n").await.0.unwrap();
     info!("wrote Hello, starting echo");
 
-    let mut buf = [0; 1];
+    static BUF: StaticCell<[u8; 1]> = StaticCell::new();
+    let mut buf = BUF.init([0; 1]);
     loop {
-        usart.read(&mut buf[..]).await.unwrap();
-        usart.write(&buf[..]).await.unwrap();
+        let (result, b) = usart.read_buffer(buf).await;
+        result.unwrap();
+        let (result, b) = usart.write_buffer(b).await;
+        buf = b;
+        result.unwrap();
     }
 }
@@ -0,0 +1,55 @@
+#![no_std]
+#![no_main]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::adc::{Adc, Oversampling, OversamplingRatio, Resolution, SampleTime};
+use embassy_stm32::pac;
+use embassy_time::Delay;
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    info!("Hello World!");
+
+    pac::RCC.ccipr().modify(|w| {
+        w.set_adcsel(pac::rcc::vals::Adcsel::SYS);
+    });
+    pac::RCC.ahb2enr().modify(|w| w.set_adcen(true));
+
+    let p = embassy_stm32::init(Default::default());
+
+    let mut adc = Adc::new(p.ADC1, &mut Delay);
+    adc.set_resolution(Resolution::TwelveBit);
+    // Average 16 conversions, the result stays 12 bits.
+    adc.set_oversampling(Some(Oversampling {
+        ratio: OversamplingRatio::X16,
+        shift: 4,
+    }));
+
+    let mut pc0 = p.PC0;
+    let mut pc1 = p.PC1;
+    let mut dma_buf = [0u16; 64];
+    let mut adc = adc.into_ring_buffered(
+        p.DMA1_CH1,
+        &mut dma_buf,
+        &mut [(&mut pc0, SampleTime::Cycles24_5), (&mut pc1, SampleTime::Cycles640_5)],
+    );
+    adc.start();
+
+    // 8 sequences of 2 channels.
+    let mut samples = [0u16; 16];
+    loop {
+        match adc.read(&mut samples).await {
+            Ok(_) => {
+                let (mut pc0, mut pc1) = (0u32, 0u32);
+                for pair in samples.chunks(2) {
+                    pc0 += pair[0] as u32;
+                    pc1 += pair[1] as u32;
+                }
+                info!("PC0 {}, PC1 {}", pc0 / 8, pc1 / 8);
+            }
+            Err(e) => warn!("{:?}", e),
+        }
+    }
+}
@@ -0,0 +1,40 @@
+#![no_std]
+#![no_main]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::adc::{Adc, SampleTime};
+use embassy_stm32::pac;
+use embassy_time::{Delay, Timer};
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    info!("Hello World!");
+
+    pac::RCC.ccipr().modify(|w| {
+        w.set_adcsel(pac::rcc::vals::Adcsel::SYS);
+    });
+    pac::RCC.ahb2enr().modify(|w| w.set_adcen(true));
+
+    let p = embassy_stm32::init(Default::default());
+
+    let mut adc = Adc::new(p.ADC1, &mut Delay);
+    // The internal channels need a sample time of a few microseconds.
+    adc.set_sample_time(SampleTime::Cycles640_5);
+
+    let mut vrefint = adc.enable_vrefint(&mut Delay);
+    let mut temperature = adc.enable_temperature();
+
+    loop {
+        let vdda = adc.read_vdda_mv(&mut vrefint);
+        let celsius = adc.read_temperature_celsius(&mut temperature, vdda);
+
+        let mut vbat = adc.enable_vbat();
+        let vbat_mv = adc.read_vbat_mv(&mut vbat, vdda);
+        adc.disable_vbat(vbat);
+
+        info!("VDDA: {} mV, VBAT: {} mV, temperature: {} C", vdda, vbat_mv, celsius);
+        Timer::after_secs(1).await;
+    }
+}
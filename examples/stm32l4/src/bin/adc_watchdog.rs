@@ -0,0 +1,50 @@
+//! Battery monitoring with the analog watchdog: PC0 is converted continuously, and the task only
+//! wakes up when it leaves the 1.0 V - 2.5 V window.
+
+#![no_std]
+#![no_main]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::adc::{self, Adc, SampleTime};
+use embassy_stm32::{bind_interrupts, pac, peripherals};
+use embassy_time::Delay;
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    ADC1_2 => adc::InterruptHandler<peripherals::ADC1>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    info!("Hello World!");
+
+    pac::RCC.ccipr().modify(|w| {
+        w.set_adcsel(pac::rcc::vals::Adcsel::SYS);
+    });
+    pac::RCC.ahb2enr().modify(|w| w.set_adcen(true));
+
+    let p = embassy_stm32::init(Default::default());
+
+    let mut adc = Adc::new_with_interrupt(p.ADC1, Irqs, &mut Delay);
+    let mut pc0 = p.PC0;
+
+    // Thresholds for VDDA = 3.3 V.
+    adc.enable_watchdog(Some(&mut pc0), 1241, 3103);
+
+    let mut dma_buf = [0u16; 8];
+    let mut adc = adc.into_ring_buffered(p.DMA1_CH1, &mut dma_buf, &mut [(&mut pc0, SampleTime::Cycles640_5)]);
+    adc.start();
+
+    let mut sample = [0u16; 1];
+    loop {
+        adc.wait_for_out_of_range().await;
+        // The ring buffer overran while waiting, restart it to read a fresh sample.
+        adc.stop();
+        adc.start();
+        match adc.read(&mut sample).await {
+            Ok(_) => warn!("battery out of range: {}", sample[0]),
+            Err(e) => warn!("{:?}", e),
+        }
+    }
+}
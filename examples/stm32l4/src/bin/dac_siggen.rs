@@ -0,0 +1,37 @@
+#![no_std]
+#![no_main]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_futures::join::join;
+use embassy_stm32::dac::{fill_table, DacCh1, Shape, SignalGenerator, TriggerSel};
+use embassy_stm32::time::Hertz;
+use embassy_time::{Duration, Ticker};
+use static_cell::StaticCell;
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    static TABLE: StaticCell<[u16; 128]> = StaticCell::new();
+    let table = TABLE.init([0; 128]);
+    fill_table(table, Shape::Sine, 2000, 2048);
+
+    let mut dac = DacCh1::new(p.DAC1, p.DMA1_CH3, p.PA4);
+    let generator = SignalGenerator::new(p.TIM6, TriggerSel::Tim6, table, Hertz(100));
+
+    // Sweep from 100 Hz to 1 kHz, while the signal is generated.
+    let sweep = async {
+        let mut ticker = Ticker::every(Duration::from_millis(100));
+        let mut frequency = 100;
+        loop {
+            ticker.next().await;
+            frequency = if frequency >= 1000 { 100 } else { frequency + 10 };
+            generator.set_frequency(Hertz(frequency));
+        }
+    };
+
+    join(sweep, dac.generate(&generator)).await;
+}
@@ -0,0 +1,40 @@
+#![no_std]
+#![no_main]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::dac::{DacCh1, TriggerSel, Value, Waveform};
+use embassy_stm32::dma::NoDma;
+use embassy_stm32::pac::timer::vals::Mms;
+use embassy_stm32::peripherals::TIM6;
+use embassy_stm32::rcc::low_level::RccPeripheral;
+use embassy_stm32::timer::low_level::Basic16bitInstance;
+use embassy_time::Timer;
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    // Triangle wave of 1023 steps on PA4, one step per TIM6 update.
+    let mut dac = DacCh1::new(p.DAC1, NoDma, p.PA4);
+    dac.set_trigger(TriggerSel::Tim6);
+    dac.set_triggering(true);
+    dac.set_waveform(Waveform::Triangle(10));
+    dac.set(Value::Bit12Right(1024));
+    dac.enable();
+
+    TIM6::enable_and_reset();
+    TIM6::regs().arr().modify(|w| w.set_arr(100 - 1));
+    TIM6::regs().cr2().modify(|w| w.set_mms(Mms::UPDATE));
+    TIM6::regs().cr1().modify(|w| {
+        w.set_opm(false);
+        w.set_cen(true);
+    });
+
+    loop {
+        info!("DAC output: {}", dac.read());
+        Timer::after_millis(500).await;
+    }
+}
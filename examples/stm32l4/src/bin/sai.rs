@@ -0,0 +1,70 @@
+#![no_std]
+#![no_main]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::rcc::{ClockSrc, Pll, PllMul, PllPDiv, PllPreDiv, PllQDiv, PllRDiv, PllSource};
+use embassy_stm32::sai::{self, split_subblocks, Config as SaiConfig, DataSize, Sai};
+use embassy_stm32::time::Hertz;
+use embassy_stm32::{peripherals, Config};
+use static_cell::StaticCell;
+use {defmt_rtt as _, panic_probe as _};
+
+const SAMPLE_RATE: Hertz = Hertz(48_000);
+// 1 kHz square wave, stereo.
+const PERIOD: usize = 48;
+
+static DMA_BUF: StaticCell<[u16; 1024]> = StaticCell::new();
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let mut config = Config::default();
+    config.rcc.mux = ClockSrc::PLL1_R;
+    config.rcc.hsi = true;
+    config.rcc.pll = Some(Pll {
+        source: PllSource::HSI,
+        prediv: PllPreDiv::DIV1,
+        mul: PllMul::MUL18,
+        divp: None,
+        divq: Some(PllQDiv::DIV6), // 48Mhz (16 / 1 * 18 / 6)
+        divr: Some(PllRDiv::DIV4), // sysclk 72Mhz clock (16 / 1 * 18 / 4)
+    });
+    // SAI1 kernel clock, 49.14Mhz (16 / 2 * 43 / 7), for 256 * 48khz with a divider of 4.
+    config.rcc.pllsai1 = Some(Pll {
+        source: PllSource::HSI,
+        prediv: PllPreDiv::DIV2,
+        mul: PllMul::MUL43,
+        divp: Some(PllPDiv::DIV7),
+        divq: None,
+        divr: None,
+    });
+    let p = embassy_stm32::init(config);
+    info!("Hello World!");
+
+    let mut sai_config = SaiConfig::i2s(DataSize::Data16);
+    sai_config.master_clock_divider = unwrap!(sai::master_clock_divider::<peripherals::SAI1>(SAMPLE_RATE));
+
+    let (sub_block_a, _sub_block_b) = split_subblocks(p.SAI1);
+    let mut sai = Sai::new_asynchronous_with_mclk(
+        sub_block_a,
+        p.PE5,
+        p.PE6,
+        p.PE4,
+        p.PE2,
+        p.DMA1_CH1,
+        DMA_BUF.init([0; 1024]),
+        sai_config,
+    );
+
+    let mut samples = [0u16; PERIOD * 2];
+    for (i, frame) in samples.chunks_mut(2).enumerate() {
+        let value = if i < PERIOD / 2 { 0x2000 } else { 0xE000 };
+        frame.fill(value);
+    }
+
+    sai.start();
+
+    loop {
+        unwrap!(sai.write(&samples).await);
+    }
+}
@@ -3,8 +3,10 @@
 
 use defmt::*;
 use embassy_executor::Spawner;
+use embassy_stm32::dma::buffer::Prefix;
 use embassy_stm32::usart::{Config, InterruptHandler, Uart};
 use embassy_stm32::{bind_interrupts, peripherals};
+use static_cell::StaticCell;
 use {defmt_rtt as _, panic_probe as _};
 
 bind_interrupts!(struct Irqs{
@@ -37,15 +39,18 @@ async fn main(_spawner: Spawner) {
     //RX1/TX1 (LPUART) on LoRa-E5 mini v1.0
     let mut usart2 = Uart::new(p.LPUART1, p.PC0, p.PC1, Irqs, p.DMA1_CH5, p.DMA1_CH6, config2).unwrap();
 
-    unwrap!(usart1.write(b"Hello Embassy World!\r\n").await);
-    unwrap!(usart2.write(b"Hello Embassy World!\r\n").await);
+    unwrap!(usart1.write_buffer(b"Hello Embassy World!\r\n").await.0);
+    unwrap!(usart2.write_buffer(b"Hello Embassy World!\r\n").await.0);
 
-    let mut buf = [0u8; 300];
+    static BUF: StaticCell<[u8; 300]> = StaticCell::new();
+    let mut buf = BUF.init([0u8; 300]);
     loop {
-        let result = usart2.read_until_idle(&mut buf).await;
+        let result = usart2.read_until_idle(&mut buf[..]).await;
         match result {
             Ok(size) => {
-                match usart1.write(&buf[0..size]).await {
+                let (result, b) = usart1.write_buffer(Prefix::new(buf, size)).await;
+                buf = b.into_inner();
+                match result {
                     Ok(()) => {
                         //Write suc.
                     }
@@ -8,7 +8,7 @@ use common::*;
 use embassy_executor::Spawner;
 use embassy_net::{Stack, StackResources};
 use embassy_stm32::eth::generic_smi::GenericSMI;
-use embassy_stm32::eth::{Ethernet, PacketQueue};
+use embassy_stm32::eth::{perf_counters, Ethernet, PacketQueue};
 use embassy_stm32::peripherals::ETH;
 use embassy_stm32::rng::Rng;
 use embassy_stm32::{bind_interrupts, eth, peripherals, rng};
@@ -111,6 +111,8 @@ async fn main(spawner: Spawner) {
     // Launch network task
     unwrap!(spawner.spawn(net_task(&stack)));
 
+    let before = perf_counters();
+
     perf_client::run(
         stack,
         perf_client::Expected {
@@ -121,6 +123,12 @@ async fn main(spawner: Spawner) {
     )
     .await;
 
+    let perf = perf_counters().delta(&before);
+    info!("perf counters: {:?}", perf);
+    assert!(perf.interrupts > 0);
+    assert!(perf.rx_descriptors > 0 && perf.tx_descriptors > 0);
+    assert!(perf.rx_bytes > 0 && perf.tx_bytes > 0);
+
     info!("Test OK");
     cortex_m::asm::bkpt();
 }
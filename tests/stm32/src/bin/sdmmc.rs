@@ -84,6 +84,18 @@ async fn main(_spawner: Spawner) {
     s.read_block(block_idx, &mut block).await.unwrap();
     assert_eq!(block, pattern2);
 
+    info!("writing multiple blocks...");
+    let patterns = [pattern1.clone(), pattern2.clone(), pattern1.clone()];
+    s.write_blocks(block_idx, &patterns).await.unwrap();
+
+    info!("reading multiple blocks...");
+    let mut blocks = [DataBlock([0u8; 512]), DataBlock([0u8; 512]), DataBlock([0u8; 512])];
+    s.read_blocks(block_idx, &mut blocks).await.unwrap();
+    assert_eq!(blocks, patterns);
+
+    info!("writing pattern2...");
+    s.write_block(block_idx, &pattern2).await.unwrap();
+
     drop(s);
 
     // ======== Try 1bit. ==============
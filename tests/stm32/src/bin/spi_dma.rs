@@ -8,6 +8,7 @@ use defmt::assert_eq;
 use embassy_executor::Spawner;
 use embassy_stm32::spi::{self, Spi};
 use embassy_stm32::time::Hertz;
+use static_cell::StaticCell;
 
 #[embassy_executor::main]
 async fn main(_spawner: Spawner) {
@@ -31,7 +32,13 @@ async fn main(_spawner: Spawner) {
         tx_dma, rx_dma, spi_config,
     );
 
-    let data: [u8; 9] = [0x00, 0xFF, 0xAA, 0x55, 0xC0, 0xFF, 0xEE, 0xC0, 0xDE];
+    static DATA: [u8; 9] = [0x00, 0xFF, 0xAA, 0x55, 0xC0, 0xFF, 0xEE, 0xC0, 0xDE];
+    let data = DATA;
+
+    // The DMA reads and writes take buffers owned by the transfer.
+    static DMA_BUF: StaticCell<[u8; 9]> = StaticCell::new();
+    static DMA_BUF16: StaticCell<[u16; 4]> = StaticCell::new();
+    let mut dma_buf = DMA_BUF.init([0; 9]);
 
     // Arduino pins D11 and D12 (MOSI-MISO) are connected together with a 1K resistor.
     // so we should get the data we sent back.
@@ -44,21 +51,43 @@ async fn main(_spawner: Spawner) {
 
     // Check read/write don't hang. We can't check they transfer the right data
     // without fancier test mechanisms.
-    spi.write(&buf).await.unwrap();
-    spi.read(&mut buf).await.unwrap();
-    spi.write(&buf).await.unwrap();
-    spi.read(&mut buf).await.unwrap();
-    spi.write(&buf).await.unwrap();
+    spi.write_buffer(&DATA).await.0.unwrap();
+    let (result, b) = spi.read_buffer(dma_buf).await;
+    dma_buf = b;
+    result.unwrap();
+    spi.write_buffer(&DATA).await.0.unwrap();
+    let (result, b) = spi.read_buffer(dma_buf).await;
+    dma_buf = b;
+    result.unwrap();
+    spi.write_buffer(&DATA).await.0.unwrap();
 
     // Check transfer doesn't break after having done a write, due to garbage in the FIFO
     spi.transfer(&mut buf, &data).await.unwrap();
     assert_eq!(buf, data);
 
+    // Check transfers with buffers of different lengths.
+    let mut short = [0; 4];
+    spi.transfer(&mut short, &data).await.unwrap();
+    assert_eq!(short, data[..4]);
+    spi.transfer(&mut buf, &data[..4]).await.unwrap();
+    assert_eq!(buf[..4], data[..4]);
+
+    // Check 16-bit frames.
+    let data16: [u16; 4] = [0x0000, 0xFFFF, 0xAA55, 0xC0DE];
+    let mut buf16 = [0; 4];
+    spi.transfer(&mut buf16, &data16).await.unwrap();
+    assert_eq!(buf16, data16);
+    let (result, dma_buf16) = spi.read_buffer(DMA_BUF16.init([0; 4])).await;
+    result.unwrap();
+    spi.write_buffer(dma_buf16).await.0.unwrap();
+
     // Check zero-length operations, these should be noops.
     spi.transfer::<u8>(&mut [], &[]).await.unwrap();
     spi.transfer_in_place::<u8>(&mut []).await.unwrap();
-    spi.read::<u8>(&mut []).await.unwrap();
-    spi.write::<u8>(&[]).await.unwrap();
+    let empty: &'static mut [u8] = &mut [];
+    spi.read_buffer(empty).await.0.unwrap();
+    let empty: &'static [u8] = &[];
+    spi.write_buffer(empty).await.0.unwrap();
 
     // === Check mixing blocking with async.
     spi.blocking_transfer(&mut buf, &data).unwrap();
@@ -70,11 +99,13 @@ async fn main(_spawner: Spawner) {
     assert_eq!(buf, data);
     spi.blocking_read(&mut buf).unwrap();
     spi.blocking_write(&buf).unwrap();
-    spi.write(&buf).await.unwrap();
-    spi.read(&mut buf).await.unwrap();
+    spi.write_buffer(&DATA).await.0.unwrap();
+    let (result, b) = spi.read_buffer(dma_buf).await;
+    dma_buf = b;
+    result.unwrap();
     spi.blocking_write(&buf).unwrap();
     spi.blocking_read(&mut buf).unwrap();
-    spi.write(&buf).await.unwrap();
+    spi.write_buffer(dma_buf).await.0.unwrap();
 
     info!("Test OK");
     cortex_m::asm::bkpt();
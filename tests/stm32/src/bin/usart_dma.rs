@@ -8,6 +8,7 @@ use defmt::assert_eq;
 use embassy_executor::Spawner;
 use embassy_futures::join::join;
 use embassy_stm32::usart::{Config, Uart};
+use static_cell::StaticCell;
 
 #[embassy_executor::main]
 async fn main(_spawner: Spawner) {
@@ -27,8 +28,10 @@ async fn main(_spawner: Spawner) {
     let usart = Uart::new(usart, rx, tx, irq, tx_dma, rx_dma, config).unwrap();
 
     const LEN: usize = 128;
-    let mut tx_buf = [0; LEN];
-    let mut rx_buf = [0; LEN];
+    static TX_BUF: StaticCell<[u8; LEN]> = StaticCell::new();
+    static RX_BUF: StaticCell<[u8; LEN]> = StaticCell::new();
+    let mut tx_buf = TX_BUF.init([0; LEN]);
+    let mut rx_buf = RX_BUF.init([0; LEN]);
 
     let (mut tx, mut rx) = usart.split();
 
@@ -37,18 +40,18 @@ async fn main(_spawner: Spawner) {
             tx_buf[i] = (i ^ n) as u8;
         }
 
-        let tx_fut = async {
-            tx.write(&tx_buf).await.unwrap();
-        };
-        let rx_fut = async {
-            rx.read(&mut rx_buf).await.unwrap();
-        };
+        let tx_fut = tx.write_buffer(tx_buf);
+        let rx_fut = rx.read_buffer(rx_buf);
 
         // note: rx needs to be polled first, to workaround this bug:
         // https://github.com/embassy-rs/embassy/issues/1426
-        join(rx_fut, tx_fut).await;
+        let ((rx_result, rx_b), (tx_result, tx_b)) = join(rx_fut, tx_fut).await;
+        rx_buf = rx_b;
+        tx_buf = tx_b;
+        rx_result.unwrap();
+        tx_result.unwrap();
 
-        assert_eq!(tx_buf, rx_buf);
+        assert_eq!(*tx_buf, *rx_buf);
     }
 
     info!("Test OK");
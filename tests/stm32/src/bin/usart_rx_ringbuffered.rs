@@ -8,10 +8,12 @@ mod common;
 use common::*;
 use defmt::{assert_eq, panic};
 use embassy_executor::Spawner;
+use embassy_stm32::dma::buffer::Prefix;
 use embassy_stm32::usart::{Config, DataBits, Parity, RingBufferedUartRx, StopBits, Uart, UartTx};
 use embassy_time::Timer;
 use rand_chacha::ChaCha8Rng;
 use rand_core::{RngCore, SeedableRng};
+use static_cell::StaticCell;
 
 const DMA_BUF_SIZE: usize = 256;
 
@@ -59,16 +61,19 @@ async fn transmit_task(mut tx: UartTx<'static, peris::UART, peris::UART_TX_DMA>)
 
     info!("Starting random transmissions into void...");
 
+    static BUF: StaticCell<[u8; 256]> = StaticCell::new();
+    let mut buf = BUF.init([0; 256]);
     let mut i: u8 = 0;
     loop {
-        let mut buf = [0; 256];
         let len = 1 + (rng.next_u32() as usize % buf.len());
         for b in &mut buf[..len] {
             *b = i;
             i = i.wrapping_add(1);
         }
 
-        tx.write(&buf[..len]).await.unwrap();
+        let (result, b) = tx.write_buffer(Prefix::new(buf, len)).await;
+        buf = b.into_inner();
+        result.unwrap();
         Timer::after_micros((rng.next_u32() % 1000) as _).await;
     }
 }